@@ -0,0 +1,329 @@
+//! Server-side apply with a strategy for conflicts with other field managers
+use kube_core::object::HasStatusSubresource;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Debug, future::Future, time::Duration};
+
+use crate::{
+    api::{Api, Patch, PatchParams, Resource, ValidationDirective},
+    error::{ConflictReport, ErrorResponse, FieldConflict},
+    Error, Result,
+};
+
+// Doubling backoffs stop growing at this delay, unless the first one is longer
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How [`Api::apply`] handles fields that are managed by other field managers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Fail with [`Error::ApplyConflict`], listing the conflicting fields
+    Fail,
+    /// Take over the conflicting fields, like `kubectl apply --server-side --force-conflicts`
+    Force,
+    /// Retry the apply after `backoff`, doubling it every attempt, then fail like [`ConflictStrategy::Fail`]
+    ///
+    /// The backoff stops doubling once it reaches a minute.
+    ///
+    /// For fields that other field managers are expected to release, such as while migrating between
+    /// controllers.
+    Retry {
+        /// The number of retries after the first attempt
+        retries: u32,
+        /// The delay before the first retry
+        backoff: Duration,
+    },
+}
+
+impl Default for ConflictStrategy {
+    fn default() -> Self {
+        ConflictStrategy::Fail
+    }
+}
+
+/// Parameters for [`Api::apply`]
+#[derive(Clone, Debug)]
+pub struct ApplyParams {
+    /// The field manager of the applied fields, such as the name of the controller
+    pub field_manager: String,
+    /// How fields that are managed by other field managers are handled
+    pub conflicts: ConflictStrategy,
+    /// Whether to run this as a dry run
+    pub dry_run: bool,
+    /// How the apiserver handles unknown or duplicate fields in the applied object
+    pub field_validation: Option<ValidationDirective>,
+}
+
+impl ApplyParams {
+    /// Parameters for applying as `field_manager`, failing on conflicts
+    pub fn new(field_manager: &str) -> Self {
+        Self {
+            field_manager: field_manager.into(),
+            conflicts: ConflictStrategy::default(),
+            dry_run: false,
+            field_validation: None,
+        }
+    }
+
+    /// Take over the fields managed by other field managers, see [`ConflictStrategy::Force`]
+    pub fn force(mut self) -> Self {
+        self.conflicts = ConflictStrategy::Force;
+        self
+    }
+
+    /// Retry on conflicts with a doubling backoff, see [`ConflictStrategy::Retry`]
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.conflicts = ConflictStrategy::Retry { retries, backoff };
+        self
+    }
+
+    /// Perform a dryRun only
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Set how the apiserver handles unknown or duplicate fields in the applied object
+    pub fn validation(mut self, directive: ValidationDirective) -> Self {
+        self.field_validation = Some(directive);
+        self
+    }
+
+    fn patch_params(&self) -> PatchParams {
+        PatchParams {
+            dry_run: self.dry_run,
+            force: self.conflicts == ConflictStrategy::Force,
+            field_manager: Some(self.field_manager.clone()),
+            field_validation: self.field_validation,
+        }
+    }
+}
+
+impl<K> Api<K>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    /// Server-side apply `obj` as the object `name`, handling conflicts with other field managers
+    ///
+    /// Sugar over [`Api::patch`] with [`Patch::Apply`], where conflicts fail with an
+    /// [`Error::ApplyConflict`] that lists the conflicting fields and their managers,
+    /// unless [`ApplyParams::conflicts`] forces or retries them.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ApplyParams}, Client, Error};
+    /// use k8s_openapi::api::apps::v1::Deployment;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let deploys: Api<Deployment> = Api::namespaced(client, "apps");
+    ///     let patch = serde_json::json!({
+    ///         "apiVersion": "apps/v1",
+    ///         "kind": "Deployment",
+    ///         "spec": { "replicas": 2 }
+    ///     });
+    ///     match deploys.apply("blog", &ApplyParams::new("myapp"), &patch).await {
+    ///         Err(Error::ApplyConflict(report)) => {
+    ///             for conflict in &report.conflicts {
+    ///                 println!("{} is managed by {}", conflict.field, conflict.manager);
+    ///             }
+    ///         }
+    ///         res => { res?; }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn apply<P: Serialize + Debug>(&self, name: &str, ap: &ApplyParams, obj: &P) -> Result<K> {
+        let pp = ap.patch_params();
+        let patch = Patch::Apply(obj);
+        apply_with(name, ap, || self.patch(name, &pp, &patch)).await
+    }
+}
+
+impl<K> Api<K>
+where
+    K: Resource<DynamicType = ()> + HasStatusSubresource + Clone + DeserializeOwned + Debug,
+    K::Status: Serialize,
+{
+    /// Server-side apply `status` as the status of the object `name`, handling conflicts like [`Api::apply`]
+    ///
+    /// Only resources with a status subresource implement [`HasStatusSubresource`], such as custom resources
+    /// derived with a `status`, so applying the status of other resources does not compile.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ApplyParams}, Client, CustomResource};
+    /// use schemars::JsonSchema;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+    /// #[kube(group = "clux.dev", version = "v1", kind = "Foo", namespaced, status = "FooStatus")]
+    /// struct FooSpec {}
+    ///
+    /// #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+    /// struct FooStatus {
+    ///     ready: bool,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let foos: Api<Foo> = Api::namespaced(client, "apps");
+    ///     let ap = ApplyParams::new("foo-controller").force();
+    ///     foos.apply_status("baz", &ap, &FooStatus { ready: true }).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn apply_status(&self, name: &str, ap: &ApplyParams, status: &K::Status) -> Result<K> {
+        let obj = serde_json::json!({
+            "apiVersion": K::api_version(&()),
+            "kind": K::kind(&()),
+            "status": serde_json::to_value(status).map_err(Error::SerdeError)?,
+        });
+        let pp = ap.patch_params();
+        let patch = Patch::Apply(&obj);
+        apply_with(name, ap, || self.patch_status(name, &pp, &patch)).await
+    }
+}
+
+// Sends an apply with `send` until it succeeds or fails with something else than a retried conflict
+async fn apply_with<K, F, Fut>(name: &str, ap: &ApplyParams, mut send: F) -> Result<K>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<K>>,
+{
+    let (mut retries, mut backoff) = match ap.conflicts {
+        ConflictStrategy::Retry { retries, backoff } => (retries, backoff),
+        ConflictStrategy::Fail | ConflictStrategy::Force => (0, Duration::ZERO),
+    };
+    loop {
+        let ae = match send().await {
+            Err(Error::Api(ae)) => ae,
+            res => return res,
+        };
+        let report = conflict_report(name, &ap.field_manager, ae).map_err(Error::Api)?;
+        if retries == 0 {
+            return Err(Error::ApplyConflict(report));
+        }
+        tracing::debug!("{}, retrying in {:?}", report, backoff);
+        tokio::time::sleep(backoff).await;
+        retries -= 1;
+        backoff = next_backoff(backoff);
+    }
+}
+
+// Doubles `backoff` without overflowing, up to `MAX_RETRY_BACKOFF`
+fn next_backoff(backoff: Duration) -> Duration {
+    backoff.saturating_mul(2).min(MAX_RETRY_BACKOFF.max(backoff))
+}
+
+// Reads the conflicting fields from the causes of a field manager conflict, or gives back other errors
+fn conflict_report(
+    name: &str,
+    field_manager: &str,
+    ae: ErrorResponse,
+) -> std::result::Result<ConflictReport, ErrorResponse> {
+    if ae.code != 409 {
+        return Err(ae);
+    }
+    let conflicts = ae
+        .details
+        .iter()
+        .flat_map(|details| &details.causes)
+        .filter(|cause| cause.reason == "FieldManagerConflict")
+        .map(|cause| {
+            // Like `conflict with "kubectl" using apps/v1`
+            let owner = cause
+                .message
+                .strip_prefix("conflict with \"")
+                .unwrap_or(&cause.message);
+            let (manager, api_version) = match owner.rsplit_once('"') {
+                Some((manager, rest)) => (manager, rest.strip_prefix(" using ")),
+                None => (owner, None),
+            };
+            FieldConflict {
+                field: cause.field.clone(),
+                manager: manager.into(),
+                api_version: api_version.map(Into::into),
+            }
+        })
+        .collect::<Vec<_>>();
+    if conflicts.is_empty() {
+        return Err(ae);
+    }
+    Ok(ConflictReport {
+        name: name.into(),
+        field_manager: field_manager.into(),
+        conflicts,
+        source: ae,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(code: u16, causes: serde_json::Value) -> ErrorResponse {
+        serde_json::from_value(serde_json::json!({
+            "status": "Failure",
+            "message": "Apply failed with 2 conflicts",
+            "reason": "Conflict",
+            "details": { "causes": causes },
+            "code": code,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn caps_retry_backoff() {
+        assert_eq!(next_backoff(Duration::from_secs(1)), Duration::from_secs(2));
+        assert_eq!(next_backoff(Duration::from_secs(50)), MAX_RETRY_BACKOFF);
+        assert_eq!(next_backoff(Duration::from_secs(120)), Duration::from_secs(120));
+        assert_eq!(next_backoff(Duration::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn reports_field_manager_conflicts() {
+        let ae = response(
+            409,
+            serde_json::json!([
+                {
+                    "reason": "FieldManagerConflict",
+                    "message": "conflict with \"kubectl\" using apps/v1",
+                    "field": ".spec.replicas",
+                },
+                {
+                    "reason": "FieldManagerConflict",
+                    "message": "conflict with \"hpa\"",
+                    "field": ".spec.template.spec.containers[name=\"blog\"].resources",
+                },
+            ]),
+        );
+        let report = conflict_report("blog", "myapp", ae).unwrap();
+        assert_eq!(
+            report.conflicts[0],
+            FieldConflict {
+                field: ".spec.replicas".into(),
+                manager: "kubectl".into(),
+                api_version: Some("apps/v1".into()),
+            }
+        );
+        assert_eq!(report.conflicts[1].manager, "hpa");
+        assert_eq!(report.conflicts[1].api_version, None);
+        assert!(report.to_string().starts_with(
+            "apply of blog by myapp conflicts with 2 field(s): .spec.replicas managed by kubectl"
+        ));
+
+        let other = response(
+            409,
+            serde_json::json!([{ "reason": "FieldValueInvalid", "field": ".spec" }]),
+        );
+        assert_eq!(conflict_report("blog", "myapp", other).unwrap_err().code, 409);
+        assert!(conflict_report("blog", "myapp", response(422, serde_json::json!([]))).is_err());
+    }
+
+    #[test]
+    fn forces_only_with_force_strategy() {
+        let ap = ApplyParams::new("myapp").retry(3, Duration::from_millis(100));
+        assert!(!ap.patch_params().force);
+        let pp = ap.force().dry_run().patch_params();
+        assert!(pp.force && pp.dry_run);
+        assert_eq!(pp.field_manager.as_deref(), Some("myapp"));
+    }
+}
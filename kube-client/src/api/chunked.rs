@@ -0,0 +1,249 @@
+//! Storing large binary data across several ConfigMaps or Secrets
+use bytes::{Bytes, BytesMut};
+use futures::{pin_mut, stream, Stream, StreamExt, TryStream, TryStreamExt};
+use k8s_openapi::{
+    api::core::v1::{ConfigMap, Secret},
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+    ByteString,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::BTreeMap, fmt::Debug};
+
+use crate::{
+    api::{Api, DeleteParams, Patch, PatchParams, Resource, ResourceExt},
+    error::ErrorResponse,
+    Error, Result,
+};
+
+const PARTS_ANNOTATION: &str = "kube.rs/chunked-parts";
+const SIZE_ANNOTATION: &str = "kube.rs/chunked-size";
+const UPLOAD_ANNOTATION: &str = "kube.rs/chunked-upload";
+const CHUNK_KEY: &str = "chunk";
+
+/// Objects that can hold the parts of [`Api::upload_chunked`]
+pub trait Chunked: Resource<DynamicType = ()> + Clone + Debug + Default + DeserializeOwned + Serialize {
+    /// The part of the data held by this object
+    fn chunk(&self) -> Option<&[u8]>;
+
+    /// An object holding `chunk`
+    fn from_chunk(chunk: Vec<u8>) -> Self;
+}
+
+impl Chunked for ConfigMap {
+    fn chunk(&self) -> Option<&[u8]> {
+        let chunk = self.binary_data.as_ref()?.get(CHUNK_KEY)?;
+        Some(&chunk.0)
+    }
+
+    fn from_chunk(chunk: Vec<u8>) -> Self {
+        ConfigMap {
+            binary_data: Some(BTreeMap::from([(CHUNK_KEY.to_string(), ByteString(chunk))])),
+            ..ConfigMap::default()
+        }
+    }
+}
+
+impl Chunked for Secret {
+    fn chunk(&self) -> Option<&[u8]> {
+        let chunk = self.data.as_ref()?.get(CHUNK_KEY)?;
+        Some(&chunk.0)
+    }
+
+    fn from_chunk(chunk: Vec<u8>) -> Self {
+        Secret {
+            data: Some(BTreeMap::from([(CHUNK_KEY.to_string(), ByteString(chunk))])),
+            ..Secret::default()
+        }
+    }
+}
+
+/// Parameters for [`Api::upload_chunked`]
+#[derive(Clone, Debug)]
+pub struct ChunkParams {
+    /// The field manager of the server-side applied objects
+    pub field_manager: String,
+    /// The number of bytes stored in each part, 512KiB by default
+    ///
+    /// Objects are limited to 1MiB by etcd, so this should stay well below that.
+    pub chunk_size: usize,
+}
+
+impl ChunkParams {
+    /// Parameters for uploading as `field_manager`, in parts of 512KiB
+    pub fn new(field_manager: &str) -> Self {
+        Self {
+            field_manager: field_manager.into(),
+            chunk_size: 512 * 1024,
+        }
+    }
+
+    /// Set the number of bytes stored in each part
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+}
+
+impl<K: Chunked> Api<K> {
+    /// Store `data` across the parts `{name}-{upload}-0`, `{name}-{upload}-1`, ... and an index object `name`
+    ///
+    /// The parts are written as `data` is read, so it is never held in memory at once. Every upload writes
+    /// new parts, named after its upload id. The index is switched to them last, so readers see either the
+    /// previous or the new upload, and the parts of the previous upload are deleted afterwards.
+    /// The parts are owned by the index, so deleting the index garbage collects them.
+    ///
+    /// The first upload of `name` creates an empty index before writing the parts, which readers
+    /// report as [`Error::InconsistentChunks`] until the upload completes.
+    ///
+    /// Fails with [`Error::ReadUpload`] if reading `data` fails, in which case the previous upload is left
+    /// in place and the parts written so far are deleted.
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::core::v1::ConfigMap;
+    /// use kube::{api::{Api, ChunkParams}, Client};
+    /// use tokio_util::io::ReaderStream;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::try_default().await?;
+    /// let cms: Api<ConfigMap> = Api::default_namespaced(client);
+    /// let bundle = tokio::fs::File::open("plugins.tar.gz").await?;
+    /// cms.upload_chunked("plugins", ReaderStream::new(bundle), &ChunkParams::new("bundler"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn upload_chunked<S>(&self, name: &str, data: S, cp: &ChunkParams) -> Result<K>
+    where
+        S: TryStream<Ok = Bytes, Error = std::io::Error>,
+    {
+        let pp = PatchParams::apply(&cp.field_manager).force();
+        // The parts need the uid of an existing index to be owned by it
+        let index = match self.get_opt(name).await? {
+            Some(index) => index,
+            None => {
+                let mut index = K::default();
+                index.meta_mut().name = Some(name.into());
+                self.patch(name, &pp, &Patch::Apply(&index)).await?
+            }
+        };
+        let previous = index_of(&index);
+        let owner = OwnerReference {
+            api_version: K::api_version(&()).into_owned(),
+            kind: K::kind(&()).into_owned(),
+            name: name.into(),
+            uid: index.uid().unwrap_or_default(),
+            ..OwnerReference::default()
+        };
+        let now = chrono::Utc::now();
+        let upload = format!("{}{:09}", now.timestamp(), now.timestamp_subsec_nanos());
+        let chunk_size = cp.chunk_size.max(1);
+
+        let data = data.into_stream();
+        pin_mut!(data);
+        let mut buf = BytesMut::new();
+        let mut parts = 0;
+        let mut size = 0;
+        let written: Result<()> = async {
+            loop {
+                let next = data.try_next().await.map_err(Error::ReadUpload)?;
+                let done = next.is_none();
+                if let Some(bytes) = next {
+                    buf.extend_from_slice(&bytes);
+                }
+                while buf.len() >= chunk_size || (done && !buf.is_empty()) {
+                    let chunk = buf.split_to(chunk_size.min(buf.len()));
+                    size += chunk.len();
+                    let part_name = part_name(name, &upload, parts);
+                    let mut part = K::from_chunk(chunk.to_vec());
+                    part.meta_mut().name = Some(part_name.clone());
+                    part.meta_mut().owner_references = Some(vec![owner.clone()]);
+                    part.annotations_mut()
+                        .insert(UPLOAD_ANNOTATION.into(), upload.clone());
+                    // Counted before writing, so that a part whose write failed halfway is cleaned up too
+                    parts += 1;
+                    self.patch(&part_name, &pp, &Patch::Apply(&part)).await?;
+                }
+                if done {
+                    return Ok(());
+                }
+            }
+        }
+        .await;
+        if let Err(err) = written {
+            // The previous upload is still the current one, so the new parts are of no use
+            let _ = self.delete_parts(name, &upload, parts).await;
+            return Err(err);
+        }
+
+        let mut index = K::default();
+        index.meta_mut().name = Some(name.into());
+        let annotations = index.annotations_mut();
+        annotations.insert(PARTS_ANNOTATION.into(), parts.to_string());
+        annotations.insert(SIZE_ANNOTATION.into(), size.to_string());
+        annotations.insert(UPLOAD_ANNOTATION.into(), upload);
+        let index = self.patch(name, &pp, &Patch::Apply(&index)).await?;
+
+        if let Some((previous_parts, previous_upload)) = previous {
+            self.delete_parts(name, &previous_upload, previous_parts).await?;
+        }
+        Ok(index)
+    }
+
+    // Delete the first `parts` parts of `upload`, ignoring those that are already gone
+    async fn delete_parts(&self, name: &str, upload: &str, parts: usize) -> Result<()> {
+        for part in 0..parts {
+            match self
+                .delete(&part_name(name, upload, part), &DeleteParams::default())
+                .await
+            {
+                Ok(_) | Err(Error::Api(ErrorResponse { code: 404, .. })) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Stream the data stored by [`Api::upload_chunked`] as `name`, one part at a time
+    ///
+    /// The stream fails with [`Error::InconsistentChunks`] if a part is missing or belongs to another upload,
+    /// which happens when the data is uploaded again while it is being read. Reading it again then
+    /// returns the new upload.
+    pub async fn download_chunked(&self, name: &str) -> Result<impl Stream<Item = Result<Bytes>>> {
+        let index = self.get(name).await?;
+        let (parts, upload) = index_of(&index).ok_or_else(|| Error::InconsistentChunks {
+            name: name.into(),
+            reason: "not an index of chunked data".into(),
+        })?;
+        let api = self.clone();
+        let name = name.to_string();
+        Ok(stream::iter(0..parts).then(move |i| {
+            let (api, name, upload) = (api.clone(), name.clone(), upload.clone());
+            async move {
+                let inconsistent = |reason: &str| Error::InconsistentChunks {
+                    name: name.clone(),
+                    reason: format!("part {} {}", i, reason),
+                };
+                let part = match api.get_opt(&part_name(&name, &upload, i)).await? {
+                    Some(part) => part,
+                    None => return Err(inconsistent("is missing")),
+                };
+                if part.annotations().get(UPLOAD_ANNOTATION) != Some(&upload) {
+                    return Err(inconsistent("belongs to another upload"));
+                }
+                let chunk = part.chunk().ok_or_else(|| inconsistent("has no data"))?;
+                Ok(Bytes::copy_from_slice(chunk))
+            }
+        }))
+    }
+}
+
+fn part_name(name: &str, upload: &str, part: usize) -> String {
+    format!("{}-{}-{}", name, upload, part)
+}
+
+// The number of parts and the upload id recorded on an index object
+fn index_of<K: Resource>(index: &K) -> Option<(usize, String)> {
+    let annotations = index.meta().annotations.as_ref()?;
+    let parts = annotations.get(PARTS_ANNOTATION)?.parse().ok()?;
+    let upload = annotations.get(UPLOAD_ANNOTATION)?.clone();
+    Some((parts, upload))
+}
@@ -0,0 +1,594 @@
+//! Copying files to and from containers, like `kubectl cp`
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use futures::future::join;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{AttachParams, Execute};
+use crate::{api::Api, Error, Result};
+
+// Tar archives are made of blocks of 512 bytes
+const BLOCK: usize = 512;
+const BUF_SIZE: usize = 64 * 1024;
+// Bound on the size of long names and pax headers that are read into memory
+const MAX_HEADER_DATA: u64 = 1024 * 1024;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+impl<K> Api<K>
+where
+    K: Clone + DeserializeOwned + Execute,
+{
+    /// Copy the local file or directory `local_path` to `remote_path` in a container of the pod `name`
+    ///
+    /// Like `kubectl cp`, this streams a tar archive into `tar` running in the container, which must
+    /// therefore have a `tar` binary. Directories are copied recursively, while symlinks and other special
+    /// files are skipped. `progress` is called with the number of bytes of file contents copied so far.
+    ///
+    /// Returns the number of bytes of file contents copied.
+    ///
+    /// Requires Kubernetes 1.29+, whose `v5.channel.k8s.io` protocol reports whether `tar` succeeded after
+    /// the archive is sent. Older servers close the connection without an exit status, which fails with
+    /// [`Error::CopyRemote`].
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use kube::{Api, Client};
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::try_default().await?;
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// pods.copy_to("web", Some("nginx"), "./site", "/usr/share/nginx/html", |copied| {
+    ///     println!("{} bytes copied", copied)
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn copy_to(
+        &self,
+        name: &str,
+        container: Option<&str>,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64> {
+        let local_path = local_path.as_ref();
+        let (dir, base) = split_remote(remote_path)?;
+        let ap = copy_params(container).stdin(true).stdout(false);
+        let mut attached = self
+            .exec(name, vec!["tar", "-xmf", "-", "-C", dir.as_str()], &ap)
+            .await?;
+        let mut stdin = attached.stdin().expect("stdin was requested");
+        let stderr = attached.stderr().expect("stderr was requested");
+        let abort = attached.abort_handle();
+        let upload = async move {
+            let res = async {
+                let copied = write_tar(&mut stdin, local_path, &base, &mut progress).await?;
+                // Ends the archive for `tar` in the container
+                stdin
+                    .shutdown()
+                    .await
+                    .map_err(|source| local_error(local_path, source))?;
+                Ok::<_, Error>(copied)
+            }
+            .await;
+            if res.is_err() {
+                abort.abort();
+            }
+            res
+        };
+        let (copied, stderr) = join(upload, read_all(stderr)).await;
+        check_status(attached.await, &stderr, remote_path)?;
+        copied
+    }
+
+    /// Copy the file or directory `remote_path` in a container of the pod `name` to `local_path`
+    ///
+    /// Like `kubectl cp`, this runs `tar` in the container, which must therefore have a `tar` binary.
+    /// Directories are copied recursively, while symlinks and other special files are skipped.
+    /// Entries of the archive that would be written outside of `local_path` are rejected.
+    /// `progress` is called with the number of bytes of file contents copied so far.
+    ///
+    /// Returns the number of bytes of file contents copied.
+    ///
+    /// Fails with [`Error::CopyRemote`] if the connection closes without the exit status of `tar`,
+    /// as the copy can then not be told apart from a truncated one.
+    pub async fn copy_from(
+        &self,
+        name: &str,
+        container: Option<&str>,
+        remote_path: &str,
+        local_path: impl AsRef<Path>,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64> {
+        let local_path = local_path.as_ref();
+        let (dir, base) = split_remote(remote_path)?;
+        let ap = copy_params(container);
+        let mut attached = self
+            .exec(
+                name,
+                vec!["tar", "cf", "-", "-C", dir.as_str(), base.as_str()],
+                &ap,
+            )
+            .await?;
+        let mut stdout = attached.stdout().expect("stdout was requested");
+        let stderr = attached.stderr().expect("stderr was requested");
+        let abort = attached.abort_handle();
+        let download = async move {
+            let res = read_tar(&mut stdout, local_path, &mut progress).await;
+            if res.is_err() {
+                abort.abort();
+            } else {
+                // `tar` pads the archive past its end, which is read so that `tar` can exit
+                let _ = tokio::io::copy(&mut stdout, &mut tokio::io::sink()).await;
+            }
+            res
+        };
+        let (copied, stderr) = join(download, read_all(stderr)).await;
+        // A failing `tar` usually also leaves a truncated archive, so its error comes first
+        check_status(attached.await, &stderr, remote_path)?;
+        copied
+    }
+}
+
+fn copy_params(container: Option<&str>) -> AttachParams {
+    let ap = AttachParams::default()
+        .max_stdin_buf_size(BUF_SIZE)
+        .max_stdout_buf_size(BUF_SIZE);
+    match container {
+        Some(container) => ap.container(container),
+        None => ap,
+    }
+}
+
+// Splits a remote path into the directory to run `tar` in and the name of the copied file
+fn split_remote(remote_path: &str) -> Result<(String, String)> {
+    let trimmed = remote_path.trim_end_matches('/');
+    let (dir, base) = match trimmed.rsplit_once('/') {
+        Some(("", base)) => ("/", base),
+        Some((dir, base)) => (dir, base),
+        None => (".", trimmed),
+    };
+    if base.is_empty() || base == "." || base == ".." {
+        return Err(Error::CopyRemote {
+            path: remote_path.into(),
+            reason: "not a path to a file or directory".into(),
+        });
+    }
+    Ok((dir.into(), base.into()))
+}
+
+async fn read_all(mut reader: impl AsyncRead + Unpin) -> String {
+    let mut buf = Vec::new();
+    let _ = reader.read_to_end(&mut buf).await;
+    String::from_utf8_lossy(&buf).trim().to_string()
+}
+
+// Without a status, the connection was closed before `tar` reported whether it succeeded,
+// which is all that servers without the v5 protocol allow once stdin is closed
+fn check_status(status: Option<Status>, stderr: &str, remote_path: &str) -> Result<()> {
+    match status {
+        Some(status) if status.status.as_deref() == Some("Success") => Ok(()),
+        Some(status) => Err(Error::CopyRemote {
+            path: remote_path.into(),
+            reason: if stderr.is_empty() {
+                status.message.unwrap_or_default()
+            } else {
+                stderr.into()
+            },
+        }),
+        None => Err(Error::CopyRemote {
+            path: remote_path.into(),
+            reason: if stderr.is_empty() {
+                "closed without the exit status of tar, which needs the v5.channel.k8s.io protocol".into()
+            } else {
+                stderr.into()
+            },
+        }),
+    }
+}
+
+fn local_error(path: &Path, source: io::Error) -> Error {
+    Error::CopyLocal {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Writing archives
+// ----------------------------------------------------------------------------
+
+// Writes `path` as the entry `name` and the entries below it, returning the bytes of file contents written
+async fn write_tar<W>(out: &mut W, path: &Path, name: &str, progress: &mut impl FnMut(u64)) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut copied = 0;
+    let mut pending = vec![(path.to_path_buf(), name.to_string())];
+    while let Some((path, name)) = pending.pop() {
+        let err = |source| local_error(&path, source);
+        let meta = tokio::fs::symlink_metadata(&path).await.map_err(err)?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_secs());
+        if meta.is_dir() {
+            let header = format!("{}/", name);
+            write_header(out, &header, 0, mode(&meta, 0o755), mtime, b'5')
+                .await
+                .map_err(err)?;
+            let mut entries = tokio::fs::read_dir(&path).await.map_err(err)?;
+            while let Some(entry) = entries.next_entry().await.map_err(err)? {
+                let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+                pending.push((entry.path(), child));
+            }
+        } else if meta.is_file() {
+            let size = meta.len();
+            write_header(out, &name, size, mode(&meta, 0o644), mtime, b'0')
+                .await
+                .map_err(err)?;
+            let mut file = tokio::fs::File::open(&path).await.map_err(err)?;
+            let mut buf = vec![0; BUF_SIZE];
+            let mut remaining = size;
+            while remaining > 0 {
+                let want = remaining.min(BUF_SIZE as u64) as usize;
+                let n = file.read(&mut buf[..want]).await.map_err(err)?;
+                if n == 0 {
+                    return Err(err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file shrank while being copied",
+                    )));
+                }
+                out.write_all(&buf[..n]).await.map_err(err)?;
+                remaining -= n as u64;
+                copied += n as u64;
+                progress(copied);
+            }
+            out.write_all(&[0; BLOCK][..padding(size)]).await.map_err(err)?;
+        } else {
+            tracing::warn!("skipping {}, which is not a file or directory", path.display());
+        }
+    }
+    // The archive ends with two empty blocks
+    out.write_all(&[0; 2 * BLOCK])
+        .await
+        .map_err(|source| local_error(path, source))?;
+    Ok(copied)
+}
+
+// Writes the header of an entry, preceded by a GNU long name entry if `name` does not fit
+async fn write_header<W>(
+    out: &mut W,
+    name: &str,
+    size: u64,
+    mode: u32,
+    mtime: u64,
+    kind: u8,
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    if name.len() > 100 {
+        let mut long_name = name.as_bytes().to_vec();
+        long_name.push(0);
+        let len = long_name.len() as u64;
+        out.write_all(&header("././@LongLink", len, 0, 0, b'L')).await?;
+        out.write_all(&long_name).await?;
+        out.write_all(&[0; BLOCK][..padding(len)]).await?;
+    }
+    out.write_all(&header(name, size, mode, mtime, kind)).await
+}
+
+fn header(name: &str, size: u64, mode: u32, mtime: u64, kind: u8) -> [u8; BLOCK] {
+    let mut h = [0; BLOCK];
+    let name = name.as_bytes();
+    let len = name.len().min(100);
+    h[..len].copy_from_slice(&name[..len]);
+    write_numeric(&mut h[100..108], mode.into());
+    write_numeric(&mut h[108..116], 0);
+    write_numeric(&mut h[116..124], 0);
+    write_numeric(&mut h[124..136], size);
+    write_numeric(&mut h[136..148], mtime);
+    h[156] = kind;
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+    // The checksum is computed with its own field set to spaces
+    h[148..156].copy_from_slice(b"        ");
+    let checksum = h.iter().map(|b| u64::from(*b)).sum();
+    write_numeric(&mut h[148..155], checksum);
+    h
+}
+
+// Octal with a trailing NUL, or base-256 for values that do not fit, like GNU tar
+fn write_numeric(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    if digits >= 22 || value < 1 << (3 * digits) {
+        let octal = format!("{:0width$o}", value, width = digits);
+        field[..digits].copy_from_slice(octal.as_bytes());
+        field[digits] = 0;
+    } else {
+        field.iter_mut().for_each(|b| *b = 0);
+        let len = field.len();
+        field[len - 8..].copy_from_slice(&value.to_be_bytes());
+        field[0] |= 0x80;
+    }
+}
+
+fn padding(size: u64) -> usize {
+    (BLOCK - (size % BLOCK as u64) as usize) % BLOCK
+}
+
+#[cfg(unix)]
+fn mode(meta: &std::fs::Metadata, _default: u32) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(_meta: &std::fs::Metadata, default: u32) -> u32 {
+    default
+}
+
+// ----------------------------------------------------------------------------
+// Reading archives
+// ----------------------------------------------------------------------------
+
+// Extracts the archive read from `input` to `local`, returning the bytes of file contents written
+async fn read_tar<R>(input: &mut R, local: &Path, progress: &mut impl FnMut(u64)) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+{
+    let invalid = |reason: &str| local_error(local, io::Error::new(io::ErrorKind::InvalidData, reason));
+    let mut copied = 0;
+    let mut long_name = None;
+    let mut header = [0; BLOCK];
+    loop {
+        let read = read_block(input, &mut header)
+            .await
+            .map_err(|source| local_error(local, source))?;
+        if !read || header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let size = read_numeric(&header[124..136]).ok_or_else(|| invalid("invalid entry size in archive"))?;
+        let name = long_name.take().unwrap_or_else(|| entry_name(&header));
+        match header[156] {
+            // GNU long name, and pax extended header
+            kind @ (b'L' | b'x') => {
+                if size > MAX_HEADER_DATA {
+                    return Err(invalid("header of archive entry is too large"));
+                }
+                let mut data = vec![0; size as usize];
+                input
+                    .read_exact(&mut data)
+                    .await
+                    .map_err(|source| local_error(local, source))?;
+                skip(input, padding(size) as u64)
+                    .await
+                    .map_err(|source| local_error(local, source))?;
+                long_name = if kind == b'L' {
+                    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                    Some(String::from_utf8_lossy(&data[..end]).into_owned())
+                } else {
+                    pax_path(&data)
+                };
+            }
+            b'0' | b'7' | 0 => {
+                let target =
+                    local_target(local, &name).ok_or_else(|| invalid("entry outside of destination"))?;
+                let err = |source| local_error(&target, source);
+                if let Some(parent) = target.parent() {
+                    tokio::fs::create_dir_all(parent).await.map_err(err)?;
+                }
+                let mut file = tokio::fs::File::create(&target).await.map_err(err)?;
+                let mut buf = vec![0; BUF_SIZE];
+                let mut remaining = size;
+                while remaining > 0 {
+                    let want = remaining.min(BUF_SIZE as u64) as usize;
+                    let n = input.read(&mut buf[..want]).await.map_err(err)?;
+                    if n == 0 {
+                        return Err(err(io::ErrorKind::UnexpectedEof.into()));
+                    }
+                    file.write_all(&buf[..n]).await.map_err(err)?;
+                    remaining -= n as u64;
+                    copied += n as u64;
+                    progress(copied);
+                }
+                file.flush().await.map_err(err)?;
+                set_mode(&target, &header).await.map_err(err)?;
+                skip(input, padding(size) as u64).await.map_err(err)?;
+            }
+            b'5' => {
+                let target =
+                    local_target(local, &name).ok_or_else(|| invalid("entry outside of destination"))?;
+                tokio::fs::create_dir_all(&target)
+                    .await
+                    .map_err(|source| local_error(&target, source))?;
+                skip(input, size + padding(size) as u64)
+                    .await
+                    .map_err(|source| local_error(local, source))?;
+            }
+            kind => {
+                tracing::warn!("skipping {} of unsupported type {:?}", name, char::from(kind));
+                skip(input, size + padding(size) as u64)
+                    .await
+                    .map_err(|source| local_error(local, source))?;
+            }
+        }
+    }
+    Ok(copied)
+}
+
+// Reads a whole block, returning `false` if the archive ended before it
+async fn read_block<R: AsyncRead + Unpin>(input: &mut R, block: &mut [u8; BLOCK]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < BLOCK {
+        let n = input.read(&mut block[filled..]).await?;
+        if n == 0 {
+            return if filled == 0 {
+                Ok(false)
+            } else {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            };
+        }
+        filled += n;
+    }
+    Ok(true)
+}
+
+async fn skip<R: AsyncRead + Unpin>(input: &mut R, len: u64) -> io::Result<()> {
+    let skipped = tokio::io::copy(&mut (&mut *input).take(len), &mut tokio::io::sink()).await?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_numeric(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        let value = field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7f), |value, b| value << 8 | u64::from(*b));
+        return Some(value);
+    }
+    let octal = std::str::from_utf8(field).ok()?;
+    let octal = octal.trim_matches(|c| c == '\0' || c == ' ');
+    if octal.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(octal, 8).ok()
+}
+
+fn entry_name(header: &[u8; BLOCK]) -> String {
+    let field = |bytes: &[u8]| {
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    };
+    let name = field(&header[..100]);
+    let prefix = if &header[257..262] == b"ustar" {
+        field(&header[345..500])
+    } else {
+        String::new()
+    };
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+// Records of pax extended headers are formatted as `<length> <key>=<value>\n`
+fn pax_path(data: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(data).lines().find_map(|record| {
+        let (_, pair) = record.split_once(' ')?;
+        pair.strip_prefix("path=").map(String::from)
+    })
+}
+
+// The copied file or directory itself is `local`, and entries below it must stay below it
+fn local_target(local: &Path, name: &str) -> Option<PathBuf> {
+    let mut components = Path::new(name).components();
+    if !matches!(components.next(), Some(Component::Normal(_))) {
+        return None;
+    }
+    let mut target = local.to_path_buf();
+    for component in components {
+        match component {
+            Component::Normal(part) => target.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(target)
+}
+
+#[cfg(unix)]
+async fn set_mode(target: &Path, header: &[u8; BLOCK]) -> io::Result<()> {
+    use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+    match read_numeric(&header[100..108]) {
+        Some(mode) => tokio::fs::set_permissions(target, Permissions::from_mode(mode as u32 & 0o777)).await,
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn set_mode(_target: &Path, _header: &[u8; BLOCK]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_status, local_target, read_tar, split_remote, write_tar};
+    use crate::Error;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn tar_roundtrip() {
+        let src = tempfile::tempdir().unwrap();
+        let long = "a".repeat(120);
+        std::fs::create_dir_all(src.path().join("site/assets")).unwrap();
+        std::fs::write(src.path().join("site/index.html"), "<h1>hi</h1>").unwrap();
+        std::fs::write(src.path().join("site/assets").join(&long), vec![7; 1500]).unwrap();
+
+        let mut archive = Vec::new();
+        let mut written = Vec::new();
+        let copied = write_tar(&mut archive, &src.path().join("site"), "html", &mut |n| {
+            written.push(n)
+        })
+        .await
+        .unwrap();
+        assert_eq!(copied, 1511);
+        assert_eq!(written.last(), Some(&1511));
+        assert_eq!(archive.len() % 512, 0);
+
+        let dst = tempfile::tempdir().unwrap();
+        let local = dst.path().join("copy");
+        let read = read_tar(&mut archive.as_slice(), &local, &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(read, 1511);
+        let index = std::fs::read_to_string(local.join("index.html")).unwrap();
+        assert_eq!(index, "<h1>hi</h1>");
+        assert_eq!(
+            std::fs::read(local.join("assets").join(&long)).unwrap(),
+            vec![7; 1500]
+        );
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_destination() {
+        let local = Path::new("/tmp/dst");
+        assert_eq!(local_target(local, "html"), Some(local.to_path_buf()));
+        assert_eq!(local_target(local, "html/./a/b"), Some(local.join("a/b")));
+        assert_eq!(local_target(local, "html/../../etc/passwd"), None);
+        assert_eq!(local_target(local, "/etc/passwd"), None);
+
+        assert_eq!(
+            split_remote("/usr/share/html/").unwrap(),
+            ("/usr/share".into(), "html".into())
+        );
+        assert_eq!(split_remote("/data").unwrap(), ("/".into(), "data".into()));
+        assert_eq!(split_remote("logs").unwrap(), (".".into(), "logs".into()));
+        assert!(split_remote("/").is_err());
+    }
+
+    #[test]
+    fn missing_status_is_an_error() {
+        let status = |status: &str| -> Status {
+            serde_json::from_value(serde_json::json!({ "metadata": {}, "status": status })).unwrap()
+        };
+        assert!(check_status(Some(status("Success")), "", "/data").is_ok());
+        assert!(check_status(Some(status("Failure")), "tar: /data: Cannot open", "/data").is_err());
+        match check_status(None, "", "/data") {
+            Err(Error::CopyRemote { reason, .. }) => assert!(reason.contains("v5.channel.k8s.io")),
+            other => panic!("expected a missing status to fail, got {:?}", other),
+        }
+    }
+}
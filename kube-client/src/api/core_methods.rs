@@ -1,10 +1,13 @@
 use either::Either;
-use futures::Stream;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 use serde::{de::DeserializeOwned, Serialize};
 use std::fmt::Debug;
 
 use crate::{api::Api, Error, Result};
-use kube_core::{object::ObjectList, params::*, response::Status, WatchEvent};
+use kube_core::{
+    metadata::PartialObjectMetadata, object::ObjectList, params::*, response::Status, table::Table, Resource,
+    WatchEvent,
+};
 
 /// PUSH/PUT/POST/GET abstractions
 impl<K> Api<K>
@@ -30,6 +33,53 @@ where
         self.client.request::<K>(req).await
     }
 
+    /// Get a named resource if it exists, returning `None` rather than a `404` error if it does not
+    ///
+    /// ```no_run
+    /// use kube::{Api, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::namespaced(client, "apps");
+    ///     if let Some(pod) = pods.get_opt("blog").await? {
+    ///         // Pod was found
+    ///     } else {
+    ///         // Pod was not found
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn get_opt(&self, name: &str) -> Result<Option<K>> {
+        match self.get(name).await {
+            Ok(obj) => Ok(Some(obj)),
+            Err(Error::Api(ae)) if ae.code == 404 => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Get only the metadata of a named resource
+    ///
+    /// Cheaper than [`Api::get`] when only names, labels, annotations or resource versions are needed.
+    ///
+    /// ```no_run
+    /// use kube::{Api, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::namespaced(client, "apps");
+    ///     let p = pods.get_metadata("blog").await?;
+    ///     println!("Pod labels: {:?}", p.metadata.labels);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn get_metadata(&self, name: &str) -> Result<PartialObjectMetadata> {
+        let mut req = self.request.get_metadata(name).map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("get_metadata");
+        self.client.request::<PartialObjectMetadata>(req).await
+    }
+
     /// Get a list of resources
     ///
     /// You get use this to get everything, or a subset matching fields/labels, say:
@@ -54,6 +104,141 @@ where
         self.client.request::<ObjectList<K>>(req).await
     }
 
+    /// Get a list of the metadata of resources
+    ///
+    /// Like [`Api::list`], but only the metadata of the resources is returned and kept in memory.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ListParams}, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::namespaced(client, "apps");
+    ///     for p in pods.list_metadata(&ListParams::default()).await? {
+    ///         println!("Found Pod: {:?}", p.metadata.name);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn list_metadata(&self, lp: &ListParams) -> Result<ObjectList<PartialObjectMetadata>> {
+        let mut req = self.request.list_metadata(lp).map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("list_metadata");
+        self.client.request::<ObjectList<PartialObjectMetadata>>(req).await
+    }
+
+    /// Get a list of resources as a [`Table`] of the columns `kubectl get` prints
+    ///
+    /// The columns are defined by the apiserver, including the `additionalPrinterColumns` of custom
+    /// resources. Each row carries the metadata of its object.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ListParams}, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::namespaced(client, "apps");
+    ///     let table = pods.list_table(&ListParams::default()).await?;
+    ///     let headers = table.column_definitions.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+    ///     println!("{}", headers.join("\t"));
+    ///     for row in &table.rows {
+    ///         let cells = row.cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+    ///         println!("{}", cells.join("\t"));
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn list_table(&self, lp: &ListParams) -> Result<Table> {
+        let mut req = self.request.list_table(lp).map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("list_table");
+        self.client.request::<Table>(req).await
+    }
+
+    /// Get a list of resources a page at a time
+    ///
+    /// Drives the `limit` and `continue` parameters of [`ListParams`], requesting the next page when
+    /// the previous one is consumed. Pages have at most [`ListParams::limit`] items, or 500 if unset.
+    ///
+    /// If the continue token expires before the listing is done (`410 Gone`), because listing took
+    /// longer than the apiserver keeps its snapshot, the rest is fetched as a single fresh (unpaginated)
+    /// list. The last page may then contain resources that were already seen on earlier pages.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ListParams}, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use futures::TryStreamExt;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::all(client);
+    ///     let mut pages = Box::pin(pods.list_pages(&ListParams::default().limit(100)));
+    ///     while let Some(page) = pages.try_next().await? {
+    ///         println!("Found {} pods", page.items.len());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn list_pages(&self, lp: &ListParams) -> impl Stream<Item = Result<ObjectList<K>>> {
+        let mut lp = lp.clone();
+        lp.limit.get_or_insert(500);
+        stream::try_unfold((self.clone(), Some(lp)), |(api, lp)| async move {
+            match lp {
+                Some(lp) => api.list_page(lp).await.map(|(list, next)| Some((list, (api, next)))),
+                None => Ok(None),
+            }
+        })
+    }
+
+    // Lists a page, along with the parameters of the next page if there is one
+    async fn list_page(&self, mut lp: ListParams) -> Result<(ObjectList<K>, Option<ListParams>)> {
+        let list = match self.list(&lp).await {
+            // The snapshot of the continue token is gone, so list the rest at once
+            Err(Error::Api(err)) if err.code == 410 && lp.continue_token.is_some() => {
+                lp.continue_token = None;
+                lp.limit = None;
+                self.list(&lp).await?
+            }
+            list => list?,
+        };
+        let next = match &list.metadata.continue_ {
+            // The continue token encodes the resource version of the first page
+            Some(token) if !token.is_empty() && lp.limit.is_some() => Some(ListParams {
+                continue_token: Some(token.clone()),
+                resource_version: None,
+                resource_version_match: None,
+                ..lp
+            }),
+            _ => None,
+        };
+        Ok((list, next))
+    }
+
+    /// Get a list of resources as a stream, fetched a page at a time
+    ///
+    /// Flattens the pages of [`Api::list_pages`], so only one page is kept in memory at a time.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ListParams, ResourceExt}, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use futures::TryStreamExt;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::all(client);
+    ///     let mut stream = Box::pin(pods.list_stream(&ListParams::default()));
+    ///     while let Some(p) = stream.try_next().await? {
+    ///         println!("Found Pod: {}", p.name());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn list_stream(&self, lp: &ListParams) -> impl Stream<Item = Result<K>> {
+        self.list_pages(lp)
+            .map_ok(|list| stream::iter(list.items.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
     /// Create a resource
     ///
     /// This function requires a type that Serializes to `K`, which can be:
@@ -108,6 +293,31 @@ where
         self.client.request_status::<K>(req).await
     }
 
+    /// Delete a named resource if it exists, returning `None` rather than a `404` error if it does not
+    ///
+    /// Otherwise like [`Api::delete`]. Handy for cleanups that must be idempotent.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, DeleteParams}, Client};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::namespaced(client, "apps");
+    ///     if pods.delete_opt("blog", &DeleteParams::default()).await?.is_none() {
+    ///         println!("Pod was already gone");
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn delete_opt(&self, name: &str, dp: &DeleteParams) -> Result<Option<Either<K, Status>>> {
+        match self.delete(name, dp).await {
+            Ok(res) => Ok(Some(res)),
+            Err(Error::Api(ae)) if ae.code == 404 => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Delete a collection of resources
     ///
     /// When you get an `ObjectList<K>` via `Left`, your delete has started.
@@ -148,6 +358,31 @@ where
         self.client.request_status::<ObjectList<K>>(req).await
     }
 
+    /// Delete the resources matching a label selector
+    ///
+    /// A shorthand for [`Api::delete_collection`] with a [`ListParams`] selecting on `labels`,
+    /// which replaces listing and deleting the resources one by one.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, DeleteParams}, Client};
+    /// use k8s_openapi::api::batch::v1::Job;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let jobs: Api<Job> = Api::namespaced(client, "apps");
+    ///     // Delete the pods of the jobs along with them
+    ///     jobs.delete_collection_matching("app=migration", &DeleteParams::background()).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn delete_collection_matching(
+        &self,
+        labels: &str,
+        dp: &DeleteParams,
+    ) -> Result<Either<ObjectList<K>, Status>> {
+        self.delete_collection(dp, &ListParams::default().labels(labels)).await
+    }
+
     /// Patch a subset of a resource's properties
     ///
     /// Takes a [`Patch`] along with [`PatchParams`] for the call.
@@ -256,6 +491,9 @@ where
     ///
     /// Consider using a managed [`watcher`] to deal with automatic re-watches and error cases.
     ///
+    /// When `version` is too old, the watch fails with [`Error::WatchExpired`] rather than
+    /// yielding a [`WatchEvent::Error`], either when starting or from the stream.
+    ///
     /// ```no_run
     /// use kube::{api::{Api, ListParams, ResourceExt, WatchEvent}, Client};
     /// use k8s_openapi::api::batch::v1::Job;
@@ -289,6 +527,145 @@ where
     ) -> Result<impl Stream<Item = Result<WatchEvent<K>>>> {
         let mut req = self.request.watch(lp, version).map_err(Error::BuildRequest)?;
         req.extensions_mut().insert("watch");
-        self.client.request_events::<K>(req).await
+        let events = self.client.request_events::<K>(req).await.map_err(watch_expired)?;
+        Ok(events.map(expired_event))
+    }
+
+    /// Watch only the metadata of a list of resources
+    ///
+    /// Like [`Api::watch`], but the events only carry the metadata of the resources.
+    ///
+    /// Consider using a managed [`metadata_watcher`] to deal with automatic re-watches and error cases.
+    ///
+    /// [`metadata_watcher`]: https://docs.rs/kube_runtime/*/kube_runtime/watcher/fn.metadata_watcher.html
+    pub async fn watch_metadata(
+        &self,
+        lp: &ListParams,
+        version: &str,
+    ) -> Result<impl Stream<Item = Result<WatchEvent<PartialObjectMetadata>>>> {
+        let mut req = self
+            .request
+            .watch_metadata(lp, version)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("watch_metadata");
+        let events = self
+            .client
+            .request_events::<PartialObjectMetadata>(req)
+            .await
+            .map_err(watch_expired)?;
+        Ok(events.map(expired_event))
+    }
+}
+
+// Turns `410 Gone` from the apiserver into `Error::WatchExpired`
+fn watch_expired(err: Error) -> Error {
+    match err {
+        Error::Api(source) if source.code == 410 => Error::WatchExpired {
+            latest_rv: latest_resource_version(&source.message),
+            source,
+        },
+        err => err,
+    }
+}
+
+fn expired_event<T>(event: Result<WatchEvent<T>>) -> Result<WatchEvent<T>> {
+    match event {
+        Ok(WatchEvent::Error(source)) if source.code == 410 => Err(watch_expired(Error::Api(source))),
+        Err(err) => Err(watch_expired(err)),
+        event => event,
+    }
+}
+
+// The apiserver reports `too old resource version: 1234 (5678)`, where 5678 is the oldest version
+// it can still watch from. Expired watches from etcd compaction do not carry a version.
+fn latest_resource_version(message: &str) -> Option<String> {
+    let (_, latest) = message.strip_suffix(')')?.rsplit_once('(')?;
+    if latest.is_empty() || !latest.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(latest.to_string())
+}
+
+/// The outcome of [`Api::create_or_get`]
+#[derive(Debug, Clone)]
+pub enum CreateOrGet<K> {
+    /// The object did not exist and was created
+    Created(K),
+    /// The object already existed and was fetched instead
+    Existing(K),
+}
+
+impl<K> CreateOrGet<K> {
+    /// Whether the object was created by this call
+    pub fn is_created(&self) -> bool {
+        matches!(self, CreateOrGet::Created(_))
+    }
+
+    /// Return the object, regardless of whether it was created or fetched
+    pub fn into_inner(self) -> K {
+        match self {
+            CreateOrGet::Created(obj) | CreateOrGet::Existing(obj) => obj,
+        }
+    }
+}
+
+/// Create-race handling
+impl<K> Api<K>
+where
+    K: Resource + Clone + DeserializeOwned + Serialize + Debug,
+{
+    /// Create a resource, or get the existing one if it has already been created
+    ///
+    /// A `409 AlreadyExists` response from the create is handled by fetching and returning the existing object.
+    /// This makes it safe to race with other creators of the same object.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, CreateOrGet, PostParams}, Client};
+    /// use k8s_openapi::api::core::v1::ConfigMap;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let cms: Api<ConfigMap> = Api::namespaced(client, "apps");
+    ///     let cm: ConfigMap = serde_json::from_value(serde_json::json!({
+    ///         "metadata": { "name": "settings" },
+    ///         "data": { "key": "value" },
+    ///     }))?;
+    ///     match cms.create_or_get(&PostParams::default(), &cm).await? {
+    ///         CreateOrGet::Created(_) => println!("created settings"),
+    ///         CreateOrGet::Existing(_) => println!("settings already existed"),
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn create_or_get(&self, pp: &PostParams, data: &K) -> Result<CreateOrGet<K>> {
+        self.create_or_get_verified(pp, data, |_| true).await
+    }
+
+    /// Create a resource, or get the existing one if it has already been created and passes `verify`
+    ///
+    /// This works like [`Api::create_or_get`], but the existing object is only returned if `verify` returns `true`,
+    /// e.g. to check that it carries the expected owner references or labels.
+    /// Otherwise, the original `409 AlreadyExists` error is returned.
+    pub async fn create_or_get_verified<F>(&self, pp: &PostParams, data: &K, verify: F) -> Result<CreateOrGet<K>>
+    where
+        F: FnOnce(&K) -> bool,
+    {
+        let err = match self.create(pp, data).await {
+            Ok(created) => return Ok(CreateOrGet::Created(created)),
+            Err(Error::Api(ae)) if ae.code == 409 && ae.reason == "AlreadyExists" => ae,
+            Err(err) => return Err(err),
+        };
+        // Objects with `generateName` cannot be looked up
+        let name = match &data.meta().name {
+            Some(name) => name,
+            None => return Err(Error::Api(err)),
+        };
+
+        let existing = self.get(name).await?;
+        if verify(&existing) {
+            Ok(CreateOrGet::Existing(existing))
+        } else {
+            Err(Error::Api(err))
+        }
     }
 }
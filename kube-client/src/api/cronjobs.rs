@@ -0,0 +1,142 @@
+//! Triggering CronJobs outside of their schedule, like `kubectl create job --from=cronjob/<name>`
+use k8s_openapi::{
+    api::batch::v1::{CronJob, Job},
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+};
+
+use crate::{
+    api::{Api, ObjectMeta, PostParams, Resource, ResourceExt},
+    Result,
+};
+
+/// The annotation that marks Jobs that were not created by the CronJob controller
+const INSTANTIATE_ANNOTATION: &str = "cronjob.kubernetes.io/instantiate";
+
+// Job names end up in the `job-name` label of their pods, which is limited to 63 characters,
+// and `generateName` appends 5 random characters
+const MAX_GENERATE_NAME_LEN: usize = 58;
+
+/// Instantiates a Job from the `jobTemplate` of `cronjob`
+///
+/// The Job is made like `kubectl create job --from=cronjob/<name>` does: with the labels, annotations and
+/// spec of the template, the `cronjob.kubernetes.io/instantiate: manual` annotation, and a controller
+/// owner reference to `cronjob`, so that the Job counts towards its history limits and is deleted with it.
+///
+/// The Job is named with a `generateName` of `<cronjob>-manual-`, set `metadata.name` to pick a name.
+/// `cronjob` must have been read from the apiserver, to reference its `uid`.
+pub fn job_from_cronjob(cronjob: &CronJob) -> Job {
+    let template = cronjob
+        .spec
+        .as_ref()
+        .map(|spec| spec.job_template.clone())
+        .unwrap_or_default();
+    let template_meta = template.metadata.unwrap_or_default();
+    let mut annotations = template_meta.annotations.unwrap_or_default();
+    annotations.insert(INSTANTIATE_ANNOTATION.into(), "manual".into());
+    let mut generate_name = format!("{}-manual-", cronjob.name());
+    if generate_name.len() > MAX_GENERATE_NAME_LEN {
+        // Names are ASCII, so this cuts at a char boundary
+        generate_name.truncate(MAX_GENERATE_NAME_LEN);
+    }
+    Job {
+        metadata: ObjectMeta {
+            generate_name: Some(generate_name),
+            namespace: cronjob.namespace(),
+            labels: template_meta.labels,
+            annotations: Some(annotations),
+            owner_references: Some(vec![OwnerReference {
+                api_version: CronJob::api_version(&()).into_owned(),
+                kind: CronJob::kind(&()).into_owned(),
+                name: cronjob.name(),
+                uid: cronjob.uid().unwrap_or_default(),
+                controller: Some(true),
+                // Setting it needs permission to update the finalizers of `cronjob`, which users creating jobs
+                // rarely have
+                block_owner_deletion: None,
+            }]),
+            ..ObjectMeta::default()
+        },
+        spec: template.spec,
+        status: None,
+    }
+}
+
+/// Runs the CronJob `name` now, by creating a Job from its `jobTemplate`
+///
+/// Returns the created Job, see [`job_from_cronjob`] for how it is made. The Job is created in the
+/// namespace of the CronJob, with the client of `api`.
+///
+/// ```no_run
+/// use kube::{api::{cronjobs, Api, ResourceExt}, Client};
+/// use k8s_openapi::api::batch::v1::CronJob;
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = Client::try_default().await?;
+///     let cronjobs: Api<CronJob> = Api::namespaced(client, "apps");
+///     let job = cronjobs::trigger_now(&cronjobs, "backup").await?;
+///     println!("started {}", job.name());
+///     Ok(())
+/// }
+/// ```
+pub async fn trigger_now(api: &Api<CronJob>, name: &str) -> Result<Job> {
+    let cronjob = api.get(name).await?;
+    let jobs: Api<Job> = match cronjob.namespace() {
+        Some(ns) => Api::namespaced(api.client.clone(), &ns),
+        None => Api::default_namespaced(api.client.clone()),
+    };
+    jobs.create(&PostParams::default(), &job_from_cronjob(&cronjob))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::batch::v1::{CronJobSpec, JobSpec, JobTemplateSpec};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn instantiates_job_template() {
+        let cronjob = CronJob {
+            metadata: ObjectMeta {
+                name: Some("backup".into()),
+                namespace: Some("apps".into()),
+                uid: Some("8e5a".into()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(CronJobSpec {
+                schedule: "0 * * * *".into(),
+                job_template: JobTemplateSpec {
+                    metadata: Some(ObjectMeta {
+                        labels: Some(BTreeMap::from([("app".to_string(), "backup".to_string())])),
+                        ..ObjectMeta::default()
+                    }),
+                    spec: Some(JobSpec {
+                        backoff_limit: Some(2),
+                        ..JobSpec::default()
+                    }),
+                },
+                ..CronJobSpec::default()
+            }),
+            status: None,
+        };
+        let job = job_from_cronjob(&cronjob);
+        assert_eq!(job.metadata.generate_name.as_deref(), Some("backup-manual-"));
+        assert_eq!(job.namespace().as_deref(), Some("apps"));
+        assert_eq!(job.labels().get("app").map(String::as_str), Some("backup"));
+        assert_eq!(job.annotations()[INSTANTIATE_ANNOTATION], "manual");
+        let owner = &job.owner_references()[0];
+        assert_eq!((owner.kind.as_str(), owner.uid.as_str()), ("CronJob", "8e5a"));
+        assert_eq!(owner.controller, Some(true));
+        assert_eq!(owner.block_owner_deletion, None);
+        assert_eq!(job.spec.unwrap().backoff_limit, Some(2));
+
+        let long = CronJob {
+            metadata: ObjectMeta {
+                name: Some("x".repeat(60)),
+                ..ObjectMeta::default()
+            },
+            ..CronJob::default()
+        };
+        assert_eq!(job_from_cronjob(&long).metadata.generate_name.unwrap().len(), 58);
+    }
+}
@@ -0,0 +1,169 @@
+//! Get-or-create-then-modify of a named object, with optimistic concurrency
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+
+use crate::{
+    api::{Api, PostParams, Resource},
+    Error, Result,
+};
+
+/// The number of times [`Entry::commit`] retries after losing a race with another writer, by default
+const DEFAULT_RETRIES: u32 = 5;
+
+/// A named object that may or may not exist yet, see [`Api::entry`]
+///
+/// Like the `Entry` of a `HashMap`, [`Entry::or_insert_with`] builds the object when it is missing and
+/// [`Entry::and_modify`] changes it when it exists. Nothing is sent until [`Entry::commit`].
+pub struct Entry<'a, K> {
+    api: &'a Api<K>,
+    name: String,
+    insert: Option<Box<dyn FnMut() -> K + Send + 'a>>,
+    modify: Vec<Box<dyn FnMut(&mut K) + Send + 'a>>,
+    pp: PostParams,
+    retries: u32,
+}
+
+/// The outcome of [`Entry::commit`]
+#[derive(Debug, Clone)]
+pub enum Committed<K> {
+    /// The object did not exist and was created
+    Created(K),
+    /// The object existed and was replaced with the modified object
+    Updated(K),
+    /// The object existed and the modifications did not change it, so it was not replaced
+    Unchanged(K),
+}
+
+impl<K> Committed<K> {
+    /// Return the object, regardless of whether it was created, updated or left unchanged
+    pub fn into_inner(self) -> K {
+        match self {
+            Committed::Created(obj) | Committed::Updated(obj) | Committed::Unchanged(obj) => obj,
+        }
+    }
+}
+
+impl<'a, K> Entry<'a, K>
+where
+    K: Resource + Clone + DeserializeOwned + Serialize + Debug,
+{
+    /// Builds the object with `insert` if it does not exist
+    ///
+    /// The name of the built object is set to the name of the entry. Without it, committing an entry that
+    /// does not exist fails with the `404 NotFound` of the apiserver.
+    pub fn or_insert_with(mut self, insert: impl FnMut() -> K + Send + 'a) -> Self {
+        self.insert = Some(Box::new(insert));
+        self
+    }
+
+    /// Changes the object with `modify` if it exists
+    ///
+    /// Modifications may be run more than once, on the latest version of the object, when a commit is
+    /// retried.
+    /// This can be called multiple times, in which case they are applied in order.
+    pub fn and_modify(mut self, modify: impl FnMut(&mut K) + Send + 'a) -> Self {
+        self.modify.push(Box::new(modify));
+        self
+    }
+
+    /// Sets the parameters of the create or replace call
+    pub fn params(mut self, pp: &PostParams) -> Self {
+        self.pp = pp.clone();
+        self
+    }
+
+    /// Sets how many times a commit is retried after losing a race with another writer
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Creates the object if it does not exist, or replaces it with its modifications
+    ///
+    /// The replace carries the `resourceVersion` the object was read at, so it fails rather than overwrite
+    /// concurrent changes. On such a `409 Conflict`, or when another writer created the object first,
+    /// the object is read again and the commit is retried, up to [`Entry::retries`] times.
+    pub async fn commit(mut self) -> Result<Committed<K>> {
+        let mut attempts = 0;
+        loop {
+            let existing = if self.insert.is_some() {
+                self.api.get_opt(&self.name).await?
+            } else {
+                Some(self.api.get(&self.name).await?)
+            };
+            let res = match (existing, &mut self.insert) {
+                (Some(mut obj), _) => {
+                    let before = serde_json::to_value(&obj).map_err(Error::SerdeError)?;
+                    for modify in &mut self.modify {
+                        modify(&mut obj);
+                    }
+                    if serde_json::to_value(&obj).map_err(Error::SerdeError)? == before {
+                        return Ok(Committed::Unchanged(obj));
+                    }
+                    self.api
+                        .replace(&self.name, &self.pp, &obj)
+                        .await
+                        .map(Committed::Updated)
+                }
+                (None, Some(insert)) => {
+                    let mut obj = insert();
+                    obj.meta_mut().name = Some(self.name.clone());
+                    self.api.create(&self.pp, &obj).await.map(Committed::Created)
+                }
+                (None, None) => unreachable!("missing objects fail the get without an insert"),
+            };
+            match res {
+                // Lost a race with a replace, or with a create of the missing object
+                Err(Error::Api(ae)) if ae.code == 409 && attempts < self.retries => {
+                    tracing::debug!("conflict committing {}, retrying: {}", self.name, ae);
+                    attempts += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl<K> Api<K>
+where
+    K: Resource + Clone + DeserializeOwned + Serialize + Debug,
+{
+    /// An [`Entry`] for getting, creating or modifying the object `name`
+    ///
+    /// Replaces the dance of getting an object, creating it on `404 NotFound`, and
+    /// retrying on `409 Conflict` or `409 AlreadyExists`.
+    ///
+    /// ```no_run
+    /// use kube::{Api, Client};
+    /// use k8s_openapi::api::core::v1::ConfigMap;
+    /// use std::collections::BTreeMap;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let cms: Api<ConfigMap> = Api::namespaced(client, "apps");
+    ///     let cm = cms
+    ///         .entry("settings")
+    ///         .or_insert_with(|| ConfigMap {
+    ///             data: Some(BTreeMap::from([("mode".to_string(), "fast".to_string())])),
+    ///             ..ConfigMap::default()
+    ///         })
+    ///         .and_modify(|cm| {
+    ///             cm.data.get_or_insert_with(BTreeMap::new).insert("mode".into(), "fast".into());
+    ///         })
+    ///         .commit()
+    ///         .await?
+    ///         .into_inner();
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn entry(&self, name: &str) -> Entry<'_, K> {
+        Entry {
+            api: self,
+            name: name.into(),
+            insert: None,
+            modify: vec![],
+            pp: PostParams::default(),
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
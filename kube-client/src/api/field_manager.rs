@@ -0,0 +1,335 @@
+//! Listing the field managers of objects, and moving field ownership between them
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{FieldsV1, ManagedFieldsEntry, Time};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt::Debug;
+
+use crate::{
+    api::{Api, Patch, PatchParams, Resource},
+    Error, Result,
+};
+
+/// A field manager of an object, from its `metadata.managedFields`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldManager {
+    /// Name of the manager, such as `kubectl` or the field manager of a controller
+    pub manager: String,
+    /// The operation that last changed the fields, `Apply` or `Update`
+    pub operation: String,
+    /// API version of the object when the fields were last changed
+    pub api_version: Option<String>,
+    /// The subresource the fields were changed through, such as `status`
+    ///
+    /// `None` for the main resource, and always `None` before Kubernetes 1.22.
+    pub subresource: Option<String>,
+    /// When the fields were last changed
+    pub time: Option<Time>,
+    /// The managed fields, in the `FieldsV1` format
+    pub fields: Value,
+}
+
+/// Lists the field managers of `obj`
+///
+/// A manager appears once for every operation and API version it changed the object with.
+pub fn field_managers<K: Resource>(obj: &K) -> Vec<FieldManager> {
+    obj.meta()
+        .managed_fields
+        .iter()
+        .flatten()
+        .map(|entry| FieldManager {
+            manager: entry.manager.clone().unwrap_or_default(),
+            operation: entry.operation.clone().unwrap_or_default(),
+            api_version: entry.api_version.clone(),
+            subresource: subresource(entry),
+            time: entry.time.clone(),
+            fields: entry.fields_v1.clone().map(|f| f.0).unwrap_or_else(|| json!({})),
+        })
+        .collect()
+}
+
+impl<K> Api<K>
+where
+    K: Resource + Clone + DeserializeOwned + Serialize + Debug,
+{
+    /// Moves ownership of `fields` of object `name` from the field manager `from_manager` to `to_manager`
+    ///
+    /// `fields` are in the `FieldsV1` format of [`FieldManager::fields`], and default to all fields of `from_manager`
+    /// on the main resource. Fields that `from_manager` owns through subresources, such as `status`, are left alone.
+    /// The current values of the fields are applied as `to_manager` with server-side apply, forcing conflicts,
+    /// and then the fields are removed from the `managedFields` of `from_manager`.
+    /// This migrates the fields between controllers without changing their values, so `from_manager` must
+    /// no longer update them.
+    pub async fn take_ownership(
+        &self,
+        name: &str,
+        fields: Option<&Value>,
+        from_manager: &str,
+        to_manager: &str,
+    ) -> Result<K> {
+        let obj = self.get(name).await?;
+        let from = field_managers(&obj)
+            .into_iter()
+            .filter(|m| m.manager == from_manager && m.subresource.is_none())
+            .collect::<Vec<_>>();
+        if from.is_empty() {
+            return Err(Error::MissingFieldManager(from_manager.into()));
+        }
+        let fields = match fields {
+            Some(fields) => fields.clone(),
+            None => from.iter().fold(json!({}), |mut all, m| {
+                union(&mut all, &m.fields);
+                all
+            }),
+        };
+
+        let mut value = serde_json::to_value(&obj).map_err(Error::SerdeError)?;
+        let mut config = extract(&value, &fields);
+        for key in ["apiVersion", "kind"] {
+            config[key] = value[key].take();
+        }
+        config["metadata"]["name"] = name.into();
+        let pp = PatchParams::apply(to_manager).force();
+        let obj = self.patch(name, &pp, &Patch::Apply(&config)).await?;
+
+        let entries = obj
+            .meta()
+            .managed_fields
+            .iter()
+            .flatten()
+            .filter_map(|entry| {
+                if entry.manager.as_deref() != Some(from_manager) || subresource(entry).is_some() {
+                    return Some(entry.clone());
+                }
+                let mut owned = entry.fields_v1.clone().map(|f| f.0).unwrap_or_else(|| json!({}));
+                subtract(&mut owned, &fields);
+                (!is_empty(&owned)).then(|| ManagedFieldsEntry {
+                    fields_v1: Some(FieldsV1(owned)),
+                    ..entry.clone()
+                })
+            })
+            .collect::<Vec<_>>();
+        let patch = json!({
+            "metadata": {
+                "managedFields": entries,
+                // Fail rather than overwrite managers that changed since the apply
+                "resourceVersion": obj.meta().resource_version,
+            }
+        });
+        self.patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+    }
+}
+
+k8s_openapi::k8s_if_ge_1_22! {
+    fn subresource(entry: &ManagedFieldsEntry) -> Option<String> {
+        entry.subresource.clone()
+    }
+}
+
+k8s_openapi::k8s_if_le_1_21! {
+    // Managed fields only record their subresource since Kubernetes 1.22
+    fn subresource(_entry: &ManagedFieldsEntry) -> Option<String> {
+        None
+    }
+}
+
+fn is_empty(fields: &Value) -> bool {
+    fields.as_object().map_or(true, Map::is_empty)
+}
+
+// Extracts the values of `fields` from `value`, like `extract` in client-go's `managedfields`
+fn extract(value: &Value, fields: &Value) -> Value {
+    let fields = match fields.as_object() {
+        Some(fields) => fields,
+        None => return Value::Null,
+    };
+    match value {
+        Value::Object(object) => {
+            let mut extracted = Map::new();
+            for (key, children) in fields {
+                if let Some(field) = key.strip_prefix("f:") {
+                    if let Some(child) = object.get(field) {
+                        extracted.insert(field.to_string(), extract_child(child, children));
+                    }
+                }
+            }
+            Value::Object(extracted)
+        }
+        Value::Array(items) => {
+            let mut extracted = vec![];
+            for (key, children) in fields {
+                if let Some(keys) = key.strip_prefix("k:") {
+                    let keys = match serde_json::from_str::<Map<String, Value>>(keys) {
+                        Ok(keys) => keys,
+                        Err(_) => continue,
+                    };
+                    let found = items
+                        .iter()
+                        .find(|item| keys.iter().all(|(k, v)| item.get(k) == Some(v)));
+                    if let Some(item) = found {
+                        let mut child = extract_child(item, children);
+                        // The keys identify the item when it is applied
+                        if let Value::Object(child) = &mut child {
+                            child.extend(keys);
+                        }
+                        extracted.push(child);
+                    }
+                } else if let Some(set_value) = key.strip_prefix("v:") {
+                    if let Ok(set_value) = serde_json::from_str::<Value>(set_value) {
+                        if items.contains(&set_value) {
+                            extracted.push(set_value);
+                        }
+                    }
+                } else if let Some(index) = key.strip_prefix("i:").and_then(|i| i.parse::<usize>().ok()) {
+                    if let Some(item) = items.get(index) {
+                        extracted.push(extract_child(item, children));
+                    }
+                }
+            }
+            Value::Array(extracted)
+        }
+        _ => value.clone(),
+    }
+}
+
+fn extract_child(value: &Value, children: &Value) -> Value {
+    // `.` marks the field itself as owned, alongside its children
+    let has_children = children.as_object().map_or(false, |c| c.keys().any(|k| k != "."));
+    if has_children {
+        extract(value, children)
+    } else {
+        // A leaf owns the whole value
+        value.clone()
+    }
+}
+
+// Adds `other` to the field set `fields`
+fn union(fields: &mut Value, other: &Value) {
+    if let (Some(fields), Some(other)) = (fields.as_object_mut(), other.as_object()) {
+        for (key, children) in other {
+            match fields.get_mut(key) {
+                Some(existing) => union(existing, children),
+                None => {
+                    fields.insert(key.clone(), children.clone());
+                }
+            }
+        }
+    }
+}
+
+// Removes `other` from the field set `fields`, including the descendants of removed fields
+fn subtract(fields: &mut Value, other: &Value) {
+    if let (Some(fields), Some(other)) = (fields.as_object_mut(), other.as_object()) {
+        for (key, children) in other {
+            let remove = match fields.get_mut(key) {
+                Some(_) if is_empty(children) => true,
+                Some(existing) => {
+                    subtract(existing, children);
+                    is_empty(existing)
+                }
+                None => false,
+            };
+            if remove {
+                fields.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment() -> Value {
+        json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "web", "labels": { "app": "web", "team": "a" } },
+            "spec": {
+                "replicas": 3,
+                "template": {
+                    "spec": {
+                        "containers": [
+                            { "name": "web", "image": "nginx:1.21", "ports": [{ "containerPort": 80 }] },
+                            { "name": "sidecar", "image": "envoy" }
+                        ]
+                    }
+                }
+            }
+        })
+    }
+
+    fn owned() -> Value {
+        json!({
+            "f:metadata": { "f:labels": { ".": {}, "f:app": {} } },
+            "f:spec": {
+                "f:replicas": {},
+                "f:template": { "f:spec": { "f:containers": {
+                    "k:{\"name\":\"web\"}": { ".": {}, "f:image": {}, "f:name": {} }
+                } } }
+            }
+        })
+    }
+
+    #[test]
+    fn extracts_owned_fields() {
+        assert_eq!(
+            extract(&deployment(), &owned()),
+            json!({
+                "metadata": { "labels": { "app": "web" } },
+                "spec": {
+                    "replicas": 3,
+                    "template": { "spec": { "containers": [{ "name": "web", "image": "nginx:1.21" }] } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn lists_managers_by_subresource() {
+        let mut cm = k8s_openapi::api::core::v1::ConfigMap::default();
+        cm.metadata.managed_fields = Some(vec![
+            ManagedFieldsEntry {
+                manager: Some("controller".into()),
+                operation: Some("Apply".into()),
+                fields_v1: Some(FieldsV1(owned())),
+                ..ManagedFieldsEntry::default()
+            },
+            ManagedFieldsEntry {
+                manager: Some("controller".into()),
+                operation: Some("Update".into()),
+                subresource: Some("status".into()),
+                ..ManagedFieldsEntry::default()
+            },
+        ]);
+        let managers = field_managers(&cm);
+        assert_eq!(managers.len(), 2);
+        assert_eq!(managers[0].subresource, None);
+        assert_eq!(managers[0].fields, owned());
+        assert_eq!(managers[1].subresource.as_deref(), Some("status"));
+        assert_eq!(managers[1].fields, json!({}));
+    }
+
+    #[test]
+    fn subtracts_fields() {
+        let mut fields = owned();
+        subtract(
+            &mut fields,
+            &json!({ "f:spec": { "f:replicas": {}, "f:template": {} } }),
+        );
+        assert_eq!(
+            fields,
+            json!({ "f:metadata": { "f:labels": { ".": {}, "f:app": {} } } })
+        );
+        subtract(
+            &mut fields,
+            &json!({ "f:metadata": { "f:labels": { ".": {}, "f:app": {} } } }),
+        );
+        assert!(is_empty(&fields));
+
+        let mut all = json!({});
+        union(&mut all, &json!({ "f:spec": { "f:replicas": {} } }));
+        union(&mut all, &json!({ "f:spec": { "f:paused": {} } }));
+        assert_eq!(all, json!({ "f:spec": { "f:replicas": {}, "f:paused": {} } }));
+    }
+}
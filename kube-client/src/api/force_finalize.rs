@@ -0,0 +1,188 @@
+//! Removing the finalizers of objects stuck in deletion
+//!
+//! # Dangers
+//!
+//! Finalizers hold an object back until the cleanup they stand for is done, usually by a controller.
+//! Removing them lets the object go away **without that cleanup**:
+//!
+//! - external resources (load balancers, volumes, DNS records, cloud accounts) may be leaked
+//! - the controller of the finalizer may never learn that the object is gone, and act on stale state
+//! - a force finalized namespace may leave objects behind in etcd, which reappear when it is recreated
+//!
+//! Objects are usually stuck because the controller of the finalizer is not running, or cannot reach
+//! the apiserver or the API it cleans up (like an unavailable `APIService` for namespaces).
+//! Fixing that is always preferable, and force finalizing is the last resort.
+//!
+//! The utilities here therefore require explicit confirmation, by default only act on objects that are
+//! already being deleted, and can be tried out as a dry run first.
+use k8s_openapi::api::core::v1::Namespace;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::fmt::Debug;
+
+use crate::{
+    api::{Api, Patch, PatchParams, PostParams, Resource, ResourceExt},
+    Error, Result,
+};
+
+/// Parameters for [`Api::force_finalize`] and [`Api::force_finalize_namespace`]
+///
+/// See the [dangers](crate::api::force_finalize#dangers) of force finalizing before using these.
+#[derive(Default, Clone, Debug)]
+pub struct ForceFinalizeParams {
+    /// Confirms that the cleanup the finalizers stand for is skipped, required to force finalize
+    pub confirm: bool,
+    /// Whether to also remove the finalizers of objects that are not being deleted
+    pub allow_live: bool,
+    /// Only remove these finalizers, rather than all of them
+    pub finalizers: Option<Vec<String>>,
+    /// Whether to run this as a dry run
+    pub dry_run: bool,
+}
+
+impl ForceFinalizeParams {
+    /// Confirms that the cleanup the finalizers stand for is skipped
+    pub fn confirm(mut self) -> Self {
+        self.confirm = true;
+        self
+    }
+
+    /// Only remove the `finalizers`, rather than all of them
+    pub fn only<S: Into<String>>(mut self, finalizers: impl IntoIterator<Item = S>) -> Self {
+        self.finalizers = Some(finalizers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Perform a dry run only
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    // Checks the guards for force finalizing `name`
+    fn check(&self, name: &str, deleting: bool) -> Result<()> {
+        if !self.confirm {
+            return Err(Error::UnconfirmedForceFinalize);
+        }
+        if !deleting && !self.allow_live {
+            return Err(Error::NotDeleting(name.into()));
+        }
+        Ok(())
+    }
+
+    // The finalizers left after removing the selected ones from `finalizers`
+    fn remaining(&self, finalizers: &[String]) -> Vec<String> {
+        match &self.finalizers {
+            Some(remove) => finalizers
+                .iter()
+                .filter(|finalizer| !remove.contains(finalizer))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl<K> Api<K>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    /// Removes the finalizers of the object `name`, letting a stuck deletion complete
+    ///
+    /// Fails unless [`ForceFinalizeParams::confirm`] is set, or if the object is not being deleted and
+    /// [`ForceFinalizeParams::allow_live`] is not set. Fails rather than overwrite concurrent changes
+    /// to the finalizers. See the [dangers](crate::api::force_finalize#dangers) first.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ForceFinalizeParams}, Client};
+    /// use k8s_openapi::api::core::v1::ConfigMap;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let cms: Api<ConfigMap> = Api::namespaced(client, "apps");
+    ///     let params = ForceFinalizeParams::default().only(["example.com/cleanup"]).confirm();
+    ///     cms.force_finalize("settings", &params.clone().dry_run()).await?;
+    ///     cms.force_finalize("settings", &params).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn force_finalize(&self, name: &str, params: &ForceFinalizeParams) -> Result<K> {
+        // Check the confirmation before any request
+        params.check(name, true)?;
+        let obj = self.get(name).await?;
+        params.check(name, obj.meta().deletion_timestamp.is_some())?;
+        let patch = json!({
+            "metadata": {
+                "finalizers": params.remaining(obj.finalizers()),
+                "resourceVersion": obj.resource_version(),
+            }
+        });
+        let pp = PatchParams {
+            dry_run: params.dry_run,
+            ..PatchParams::default()
+        };
+        self.patch(name, &pp, &Patch::Merge(&patch)).await
+    }
+}
+
+impl Api<Namespace> {
+    /// Removes the `spec.finalizers` of the namespace `name` through its `finalize` subresource
+    ///
+    /// This is what keeps namespaces in `Terminating` while their content is deleted, in addition to
+    /// the finalizers of the metadata that [`Api::force_finalize`] removes. Content that is left
+    /// is **not** deleted. Guarded like [`Api::force_finalize`].
+    pub async fn force_finalize_namespace(
+        &self,
+        name: &str,
+        params: &ForceFinalizeParams,
+    ) -> Result<Namespace> {
+        // Check the confirmation before any request
+        params.check(name, true)?;
+        let mut ns = self.get(name).await?;
+        params.check(name, ns.meta().deletion_timestamp.is_some())?;
+        if let Some(spec) = ns.spec.as_mut() {
+            spec.finalizers = Some(params.remaining(spec.finalizers.as_deref().unwrap_or_default()));
+        }
+        let pp = PostParams {
+            dry_run: params.dry_run,
+            ..PostParams::default()
+        };
+        let data = serde_json::to_vec(&ns).map_err(Error::SerdeError)?;
+        let mut req = self
+            .request
+            .replace_subresource("finalize", name, &pp, data)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("force_finalize_namespace");
+        self.client.request::<Namespace>(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guards_force_finalize() {
+        assert!(matches!(
+            ForceFinalizeParams::default().check("stuck", true),
+            Err(Error::UnconfirmedForceFinalize)
+        ));
+        let params = ForceFinalizeParams::default().confirm();
+        assert!(params.check("stuck", true).is_ok());
+        assert!(matches!(params.check("live", false), Err(Error::NotDeleting(name)) if name == "live"));
+        let params = ForceFinalizeParams {
+            allow_live: true,
+            ..params
+        };
+        assert!(params.check("live", false).is_ok());
+    }
+
+    #[test]
+    fn removes_selected_finalizers() {
+        let finalizers = vec!["a.io/cleanup".to_string(), "b.io/cleanup".to_string()];
+        let params = ForceFinalizeParams::default().confirm();
+        assert!(params.remaining(&finalizers).is_empty());
+        let params = params.only(["a.io/cleanup", "c.io/cleanup"]);
+        assert_eq!(params.remaining(&finalizers), vec!["b.io/cleanup"]);
+    }
+}
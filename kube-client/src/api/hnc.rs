@@ -0,0 +1,380 @@
+//! Helpers for the [Hierarchical Namespace Controller](https://github.com/kubernetes-sigs/hierarchical-namespaces) (HNC)
+//!
+//! HNC organizes namespaces into trees, and labels every namespace with its depth below each of its ancestors.
+//! These helpers rely on those conventions to resolve subtrees, and manage the HNC objects for subnamespaces
+//! and propagated labels.
+use std::collections::BTreeMap;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use k8s_openapi::api::core::v1::Namespace;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{
+    api::{Api, ApiResource, DynamicObject, GroupVersionKind, ListParams, Patch, PatchParams, PostParams},
+    Client, Resource, Result,
+};
+
+/// API group of the HNC resources
+pub const GROUP: &str = "hnc.x-k8s.io";
+/// API version of the HNC resources
+pub const VERSION: &str = "v1alpha2";
+/// Name of the singleton `HierarchyConfiguration` in every namespace
+pub const HIERARCHY_CONFIGURATION_NAME: &str = "hierarchy";
+/// Annotation set by HNC on subnamespaces, naming their parent
+pub const SUBNAMESPACE_OF_ANNOTATION: &str = "hnc.x-k8s.io/subnamespace-of";
+
+// How many namespaces `list_subtree` lists at a time
+const LIST_SUBTREE_CONCURRENCY: usize = 8;
+
+/// The label HNC sets on every namespace in the subtree of `root`, with the depth below `root` as the value
+///
+/// `root` itself is labelled with depth 0.
+pub fn tree_label(root: &str) -> String {
+    format!("{}.tree.{}/depth", root, GROUP)
+}
+
+/// `ApiResource` of `SubnamespaceAnchor`, for use with [`DynamicObject`]
+pub fn subnamespace_anchor_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(GROUP, VERSION, "SubnamespaceAnchor"))
+}
+
+/// `ApiResource` of `HierarchyConfiguration`, for use with [`DynamicObject`]
+pub fn hierarchy_configuration_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(GROUP, VERSION, "HierarchyConfiguration"))
+}
+
+/// A namespace in a subtree, see [`subtree`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtreeNamespace {
+    /// The name of the namespace
+    pub name: String,
+    /// The depth of the namespace below the root of the subtree
+    pub depth: u32,
+}
+
+/// Resolves the namespaces in the subtree of `root`, including `root` itself
+///
+/// Namespaces are ordered by depth, and then by name.
+/// Returns an empty list if `root` does not exist or is not managed by HNC.
+pub async fn subtree(client: Client, root: &str) -> Result<Vec<SubtreeNamespace>> {
+    let label = tree_label(root);
+    let namespaces = Api::<Namespace>::all(client)
+        .list(&ListParams::default().labels(&label))
+        .await?;
+    let mut subtree = namespaces
+        .items
+        .into_iter()
+        .filter_map(|ns| {
+            let depth = ns.metadata.labels.as_ref()?.get(&label)?.parse().ok()?;
+            Some(SubtreeNamespace {
+                name: ns.metadata.name?,
+                depth,
+            })
+        })
+        .collect::<Vec<_>>();
+    subtree.sort_by(|a, b| a.depth.cmp(&b.depth).then_with(|| a.name.cmp(&b.name)));
+    Ok(subtree)
+}
+
+/// Returns the parent of `namespace` in the hierarchy, if it has one
+pub async fn parent(client: Client, namespace: &str) -> Result<Option<String>> {
+    let api = Api::<DynamicObject>::namespaced_with(client, namespace, &hierarchy_configuration_resource());
+    // HNC only creates the configuration for namespaces that are part of a hierarchy
+    let hierarchy = api.get_opt(HIERARCHY_CONFIGURATION_NAME).await?;
+    Ok(hierarchy.and_then(|hierarchy| {
+        hierarchy
+            .data
+            .pointer("/spec/parent")
+            .and_then(serde_json::Value::as_str)
+            .map(String::from)
+    }))
+}
+
+/// Lists objects of kind `K` in every namespace in the subtree of `root`
+///
+/// This lists each namespace separately, so it only requires access to the namespaces in the subtree
+/// (and to list namespaces), rather than to all namespaces. At most 8 namespaces are listed at a time,
+/// and the objects are grouped by namespace in no particular order.
+pub async fn list_subtree<K>(client: Client, root: &str, lp: &ListParams) -> Result<Vec<K>>
+where
+    K: Resource + Clone + DeserializeOwned + std::fmt::Debug,
+    <K as Resource>::DynamicType: Default,
+{
+    let namespaces = subtree(client.clone(), root).await?;
+    let lists: Vec<_> = stream::iter(namespaces.iter().map(|ns| {
+        let api = Api::<K>::namespaced(client.clone(), &ns.name);
+        async move { api.list(lp).await }
+    }))
+    .buffer_unordered(LIST_SUBTREE_CONCURRENCY)
+    .try_collect()
+    .await?;
+    Ok(lists.into_iter().flat_map(|list| list.items).collect())
+}
+
+/// Creates the subnamespace `name` below `parent`, by creating its `SubnamespaceAnchor`
+///
+/// HNC creates the namespace itself asynchronously.
+pub async fn create_subnamespace(client: Client, parent: &str, name: &str) -> Result<DynamicObject> {
+    let resource = subnamespace_anchor_resource();
+    let anchor = DynamicObject::new(name, &resource).within(parent);
+    Api::<DynamicObject>::namespaced_with(client, parent, &resource)
+        .create(&PostParams::default(), &anchor)
+        .await
+}
+
+/// Sets the labels that HNC propagates from `namespace` to all of its descendants
+///
+/// These are the managed labels of the namespace's `HierarchyConfiguration`, and replace any previously set labels.
+/// Note that HNC only propagates labels whose keys are allowed by its `--managed-namespace-label` flags.
+pub async fn set_propagated_labels(
+    client: Client,
+    namespace: &str,
+    labels: &BTreeMap<String, String>,
+) -> Result<DynamicObject> {
+    let resource = hierarchy_configuration_resource();
+    let labels = labels
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": value }))
+        .collect::<Vec<_>>();
+    let patch = json!({
+        "apiVersion": resource.api_version,
+        "kind": resource.kind,
+        "metadata": { "name": HIERARCHY_CONFIGURATION_NAME },
+        "spec": { "labels": labels },
+    });
+    Api::<DynamicObject>::namespaced_with(client, namespace, &resource)
+        .patch(
+            HIERARCHY_CONFIGURATION_NAME,
+            &PatchParams::apply("kube-rs-hnc"),
+            &Patch::Apply(&patch),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::pin_mut;
+    use http::{Request, Response};
+    use hyper::Body;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tower_test::mock;
+
+    const NAMESPACES: &str = "/api/v1/namespaces?&labelSelector=team-a.tree.hnc.x-k8s.io%2Fdepth";
+
+    fn respond(send: mock::SendResponse<Response<Body>>, status: u16, body: Value) {
+        send.send_response(
+            Response::builder()
+                .status(status)
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        );
+    }
+
+    async fn body(request: Request<Body>) -> Value {
+        serde_json::from_slice(&hyper::body::to_bytes(request.into_body()).await.unwrap()).unwrap()
+    }
+
+    fn namespaces(depths: &[(&str, &str)]) -> Value {
+        let items = depths
+            .iter()
+            .map(|(name, depth)| {
+                let metadata = json!({ "name": name, "labels": { tree_label("team-a"): depth } });
+                json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": metadata })
+            })
+            .collect::<Vec<_>>();
+        json!({ "apiVersion": "v1", "kind": "NamespaceList", "metadata": {}, "items": items })
+    }
+
+    // A list of one ConfigMap in the namespace listed by `request`
+    fn configmaps(request: &Request<Body>) -> Value {
+        let path = request.uri().path();
+        let namespace = path.split('/').nth(4).unwrap();
+        assert_eq!(path, format!("/api/v1/namespaces/{}/configmaps", namespace));
+        let metadata = json!({ "name": "settings", "namespace": namespace });
+        let item = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": metadata });
+        json!({ "apiVersion": "v1", "kind": "ConfigMapList", "metadata": {}, "items": [item] })
+    }
+
+    #[test]
+    fn hnc_resources() {
+        assert_eq!(tree_label("team-a"), "team-a.tree.hnc.x-k8s.io/depth");
+        let anchor = subnamespace_anchor_resource();
+        assert_eq!(anchor.api_version, "hnc.x-k8s.io/v1alpha2");
+        assert_eq!(anchor.plural, "subnamespaceanchors");
+        assert_eq!(hierarchy_configuration_resource().plural, "hierarchyconfigurations");
+    }
+
+    #[tokio::test]
+    async fn subtree_is_ordered_by_depth_and_name() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), NAMESPACES);
+            let depths = [
+                ("child-b", "1"),
+                ("grandchild", "2"),
+                ("team-a", "0"),
+                ("child-a", "1"),
+                ("broken", "x"),
+            ];
+            respond(send, 200, namespaces(&depths));
+        });
+
+        let client = Client::new(mock_service, "default");
+        let names = subtree(client, "team-a")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|ns| format!("{}/{}", ns.depth, ns.name))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["0/team-a", "1/child-a", "1/child-b", "2/grandchild"]);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn parent_reads_the_hierarchy_configuration() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let url = "/apis/hnc.x-k8s.io/v1alpha2/namespaces";
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(
+                request.uri().to_string(),
+                format!("{}/child-a/hierarchyconfigurations/hierarchy", url)
+            );
+            let hierarchy = json!({
+                "apiVersion": "hnc.x-k8s.io/v1alpha2",
+                "kind": "HierarchyConfiguration",
+                "metadata": { "name": "hierarchy", "namespace": "child-a" },
+                "spec": { "parent": "team-a" },
+            });
+            respond(send, 200, hierarchy);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(
+                request.uri().to_string(),
+                format!("{}/team-a/hierarchyconfigurations/hierarchy", url)
+            );
+            let not_found = json!({ "status": "Failure", "message": "", "reason": "NotFound", "code": 404 });
+            respond(send, 404, not_found);
+        });
+
+        let client = Client::new(mock_service, "default");
+        let child_parent = parent(client.clone(), "child-a").await.unwrap();
+        assert_eq!(child_parent.as_deref(), Some("team-a"));
+        assert_eq!(parent(client, "team-a").await.unwrap(), None);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn list_subtree_lists_eight_namespaces_at_a_time() {
+        let names = (0..10).map(|i| format!("ns-{}", i)).collect::<Vec<_>>();
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn({
+            let names = names.clone();
+            async move {
+                pin_mut!(handle);
+                let (_, send) = handle.next_request().await.expect("service not called");
+                let depths = names.iter().map(|name| (name.as_str(), "1")).collect::<Vec<_>>();
+                respond(send, 200, namespaces(&depths));
+
+                let mut pending = Vec::new();
+                for _ in 0..8 {
+                    pending.push(handle.next_request().await.expect("service not called"));
+                }
+                let next = tokio::time::timeout(Duration::from_millis(100), handle.next_request()).await;
+                assert!(next.is_err(), "listed more than 8 namespaces at once");
+                // The remaining namespaces are listed as the first lists complete
+                for _ in 0..2 {
+                    let (request, send) = pending.remove(0);
+                    respond(send, 200, configmaps(&request));
+                    pending.push(handle.next_request().await.expect("service not called"));
+                }
+                for (request, send) in pending {
+                    respond(send, 200, configmaps(&request));
+                }
+            }
+        });
+
+        let client = Client::new(mock_service, "default");
+        let configmaps = list_subtree::<ConfigMap>(client, "team-a", &ListParams::default())
+            .await
+            .unwrap();
+        let mut namespaces = configmaps
+            .into_iter()
+            .map(|cm| cm.metadata.namespace.unwrap())
+            .collect::<Vec<_>>();
+        namespaces.sort();
+        assert_eq!(namespaces, names);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_subnamespace_creates_an_anchor() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::POST);
+            assert_eq!(
+                request.uri().to_string(),
+                "/apis/hnc.x-k8s.io/v1alpha2/namespaces/team-a/subnamespaceanchors?"
+            );
+            let anchor = body(request).await;
+            assert_eq!(anchor["kind"], "SubnamespaceAnchor");
+            assert_eq!(anchor["metadata"]["name"], "child-a");
+            assert_eq!(anchor["metadata"]["namespace"], "team-a");
+            respond(send, 201, anchor);
+        });
+
+        let client = Client::new(mock_service, "default");
+        let anchor = create_subnamespace(client, "team-a", "child-a").await.unwrap();
+        assert_eq!(anchor.metadata.name.as_deref(), Some("child-a"));
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn set_propagated_labels_applies_the_hierarchy_configuration() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::PATCH);
+            let uri = concat!(
+                "/apis/hnc.x-k8s.io/v1alpha2/namespaces/team-a/hierarchyconfigurations/hierarchy",
+                "?&fieldManager=kube-rs-hnc"
+            );
+            assert_eq!(request.uri().to_string(), uri);
+            assert_eq!(
+                request.headers()[http::header::CONTENT_TYPE],
+                "application/apply-patch+yaml"
+            );
+            let patch = body(request).await;
+            assert_eq!(
+                patch,
+                json!({
+                    "apiVersion": "hnc.x-k8s.io/v1alpha2",
+                    "kind": "HierarchyConfiguration",
+                    "metadata": { "name": "hierarchy" },
+                    "spec": { "labels": [
+                        { "key": "env", "value": "prod" },
+                        { "key": "team", "value": "a" },
+                    ] },
+                })
+            );
+            respond(send, 200, patch);
+        });
+
+        let labels = [("team", "a"), ("env", "prod")]
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let client = Client::new(mock_service, "default");
+        set_propagated_labels(client, "team-a", &labels).await.unwrap();
+        spawned.await.unwrap();
+    }
+}
@@ -2,33 +2,78 @@
 
 
 mod core_methods;
+pub use core_methods::CreateOrGet;
 #[cfg(feature = "ws")] mod remote_command;
-#[cfg(feature = "ws")] pub use remote_command::AttachedProcess;
+#[cfg(feature = "ws")] pub use remote_command::{exit_code, AttachedProcess};
+#[cfg(feature = "ws")] mod portforward;
+#[cfg(feature = "ws")] pub use portforward::Portforwarder;
+#[cfg(feature = "ws")] mod copy;
 
 mod subresource;
 #[cfg(feature = "ws")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
-pub use subresource::{Attach, AttachParams, Execute};
-pub use subresource::{Evict, EvictParams, Log, LogParams, ScaleSpec, ScaleStatus};
+pub use subresource::{Attach, AttachParams, Execute, Portforward, TerminalSize};
+pub use subresource::{Evict, EvictParams, Log, LogLine, LogParams, ScaleSpec, ScaleStatus};
+pub use subresource::{Proxy, RequestToken, TokenRequestParams};
 
 mod util;
 
+mod field_manager;
+pub use field_manager::{field_managers, FieldManager};
+
+mod apply;
+pub use apply::{ApplyParams, ConflictStrategy};
+
+mod entry;
+pub use entry::{Committed, Entry};
+
+mod chunked;
+pub use chunked::{ChunkParams, Chunked};
+
+mod rollout;
+pub use rollout::{revisions, Error as RolloutError, Revision};
+
+pub mod node_proxy;
+pub use node_proxy::StatsSummary;
+
+k8s_openapi::k8s_if_ge_1_21! {
+    pub mod cronjobs;
+}
+
+pub mod force_finalize;
+pub use force_finalize::ForceFinalizeParams;
+
+mod stats;
+pub use stats::ApiStats;
+pub(crate) use stats::StatsRecorder;
+
+#[cfg(feature = "hnc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hnc")))]
+pub mod hnc;
+
 // Re-exports from kube-core
 #[cfg(feature = "admission")]
 #[cfg_attr(docsrs, doc(cfg(feature = "admission")))]
 pub use kube_core::admission;
+#[cfg(feature = "jsonpatch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonpatch")))]
+pub use kube_core::jsonpatch::PatchOps;
 pub(crate) use kube_core::params;
 pub use kube_core::{
-    dynamic::{ApiResource, DynamicObject},
+    dynamic::{ApiResource, DynamicList, DynamicObject},
+    fields::FieldSelector,
     gvk::{GroupVersionKind, GroupVersionResource},
-    metadata::{ListMeta, ObjectMeta, TypeMeta},
+    labels::{Expression, Selector},
+    metadata::{ListMeta, ObjectMeta, PartialObjectMetadata, TypeMeta},
     object::{NotUsed, Object, ObjectList},
     request::Request,
+    table::{Table, TableColumnDefinition, TableRow, TableRowCondition},
     watch::WatchEvent,
     Resource, ResourceExt,
 };
 pub use params::{
-    DeleteParams, ListParams, Patch, PatchParams, PostParams, Preconditions, PropagationPolicy,
+    DeleteParams, ListParams, Patch, PatchParams, PostParams, Preconditions, PropagationPolicy, QueryParams,
+    ValidationDirective, VersionMatch,
 };
 
 use crate::Client;
@@ -61,7 +106,7 @@ impl<K: Resource> Api<K> {
     pub fn all_with(client: Client, dyntype: &K::DynamicType) -> Self {
         let url = K::url_path(dyntype, None);
         Self {
-            client,
+            client: client.with_stats(&url),
             request: Request::new(url),
             phantom: std::iter::empty(),
         }
@@ -73,7 +118,7 @@ impl<K: Resource> Api<K> {
     pub fn namespaced_with(client: Client, ns: &str, dyntype: &K::DynamicType) -> Self {
         let url = K::url_path(dyntype, Some(ns));
         Self {
-            client,
+            client: client.with_stats(&url),
             request: Request::new(url),
             phantom: std::iter::empty(),
         }
@@ -88,7 +133,7 @@ impl<K: Resource> Api<K> {
     pub fn default_namespaced_with(client: Client, dyntype: &K::DynamicType) -> Self {
         let url = K::url_path(dyntype, Some(client.default_ns()));
         Self {
-            client,
+            client: client.with_stats(&url),
             request: Request::new(url),
             phantom: std::iter::empty(),
         }
@@ -103,6 +148,20 @@ impl<K: Resource> Api<K> {
     pub fn resource_url(&self) -> &str {
         &self.request.url_path
     }
+
+    /// Conflicts and throttling of the requests of this [`Api`] and its clones so far
+    ///
+    /// See [`ApiStats`] for using them to adapt how resources are updated.
+    pub fn stats(&self) -> ApiStats {
+        self.client.stats().map(|stats| stats.snapshot()).unwrap_or_default()
+    }
+
+    /// Reset the [`Api::stats`] of this [`Api`] and its clones
+    pub fn reset_stats(&self) {
+        if let Some(stats) = self.client.stats() {
+            stats.reset();
+        }
+    }
 }
 
 
@@ -117,7 +176,7 @@ where
     pub fn all(client: Client) -> Self {
         let url = K::url_path(&Default::default(), None);
         Self {
-            client,
+            client: client.with_stats(&url),
             request: Request::new(url),
             phantom: std::iter::empty(),
         }
@@ -127,7 +186,7 @@ where
     pub fn namespaced(client: Client, ns: &str) -> Self {
         let url = K::url_path(&Default::default(), Some(ns));
         Self {
-            client,
+            client: client.with_stats(&url),
             request: Request::new(url),
             phantom: std::iter::empty(),
         }
@@ -140,7 +199,7 @@ where
     pub fn default_namespaced(client: Client) -> Self {
         let url = K::url_path(&Default::default(), Some(client.default_ns()));
         Self {
-            client,
+            client: client.with_stats(&url),
             request: Request::new(url),
             phantom: std::iter::empty(),
         }
@@ -149,6 +208,6 @@ where
 
 impl<K> From<Api<K>> for Client {
     fn from(api: Api<K>) -> Self {
-        api.client
+        api.client.without_stats()
     }
 }
@@ -0,0 +1,230 @@
+//! Reading logs, stats and metrics from kubelets, through the node proxy of the apiserver
+use bytes::Bytes;
+use futures::Stream;
+use k8s_openapi::{api::core::v1::Node, apimachinery::pkg::apis::meta::v1::Time};
+use serde::Deserialize;
+
+use crate::{api::Api, Error, Result};
+
+/// The stats summary of a kubelet, from [`Api::node_stats_summary`]
+///
+/// A subset of the `stats/summary` endpoint of the kubelet, which is also what metrics-server reads.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsSummary {
+    /// Stats of the node itself
+    pub node: NodeStats,
+    /// Stats of the pods running on the node
+    #[serde(default)]
+    pub pods: Vec<PodStats>,
+}
+
+/// Stats of a node
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStats {
+    /// Name of the node
+    pub node_name: String,
+    /// When the node started
+    pub start_time: Option<Time>,
+    /// CPU usage of the node
+    pub cpu: Option<CpuStats>,
+    /// Memory usage of the node
+    pub memory: Option<MemoryStats>,
+    /// Usage of the root filesystem of the node
+    pub fs: Option<FsStats>,
+    /// Network usage of the node
+    pub network: Option<NetworkStats>,
+}
+
+/// Stats of a pod
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PodStats {
+    /// The pod these stats belong to
+    pub pod_ref: PodReference,
+    /// When the pod started
+    pub start_time: Option<Time>,
+    /// Stats of the containers of the pod
+    #[serde(default)]
+    pub containers: Vec<ContainerStats>,
+    /// CPU usage of the pod
+    pub cpu: Option<CpuStats>,
+    /// Memory usage of the pod
+    pub memory: Option<MemoryStats>,
+    /// Network usage of the pod
+    pub network: Option<NetworkStats>,
+    /// Usage of ephemeral storage by the pod
+    #[serde(rename = "ephemeral-storage")]
+    pub ephemeral_storage: Option<FsStats>,
+}
+
+/// Identifies the pod of [`PodStats`]
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PodReference {
+    /// Name of the pod
+    pub name: String,
+    /// Namespace of the pod
+    pub namespace: String,
+    /// Uid of the pod
+    pub uid: String,
+}
+
+/// Stats of a container
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerStats {
+    /// Name of the container
+    pub name: String,
+    /// When the container started
+    pub start_time: Option<Time>,
+    /// CPU usage of the container
+    pub cpu: Option<CpuStats>,
+    /// Memory usage of the container
+    pub memory: Option<MemoryStats>,
+    /// Usage of the writable layer of the container
+    pub rootfs: Option<FsStats>,
+    /// Usage of the logs of the container
+    pub logs: Option<FsStats>,
+}
+
+/// CPU usage
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuStats {
+    /// When the usage was sampled
+    pub time: Option<Time>,
+    /// Average usage in nanocores since the previous sample
+    pub usage_nano_cores: Option<u64>,
+    /// Cumulative usage in core-nanoseconds
+    pub usage_core_nano_seconds: Option<u64>,
+}
+
+/// Memory usage
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    /// When the usage was sampled
+    pub time: Option<Time>,
+    /// Memory available for use
+    pub available_bytes: Option<u64>,
+    /// Memory in use, including caches
+    pub usage_bytes: Option<u64>,
+    /// Memory in use that cannot be reclaimed, which is what the kubelet evicts on
+    pub working_set_bytes: Option<u64>,
+    /// Anonymous and swap cache memory
+    pub rss_bytes: Option<u64>,
+}
+
+/// Filesystem usage
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FsStats {
+    /// When the usage was sampled
+    pub time: Option<Time>,
+    /// Bytes available for use
+    pub available_bytes: Option<u64>,
+    /// Total size of the filesystem
+    pub capacity_bytes: Option<u64>,
+    /// Bytes in use
+    pub used_bytes: Option<u64>,
+}
+
+/// Network usage of the default interface
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStats {
+    /// When the usage was sampled
+    pub time: Option<Time>,
+    /// Name of the interface
+    pub name: Option<String>,
+    /// Cumulative bytes received
+    pub rx_bytes: Option<u64>,
+    /// Cumulative bytes sent
+    pub tx_bytes: Option<u64>,
+}
+
+impl Api<Node> {
+    /// Stream the log file `path` of the node `name`, relative to `/var/log` on the node
+    ///
+    /// A `path` ending with `/` lists the directory instead, as an HTML page.
+    /// Reading node logs requires the `nodes/proxy` permission.
+    ///
+    /// ```no_run
+    /// use futures::TryStreamExt;
+    /// use k8s_openapi::api::core::v1::Node;
+    /// use kube::{Api, Client};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let nodes: Api<Node> = Api::all(client);
+    ///     let mut logs = nodes.node_logs("node-1", "kube-proxy.log").await?;
+    ///     while let Some(bytes) = logs.try_next().await? {
+    ///         print!("{}", String::from_utf8_lossy(&bytes));
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn node_logs(&self, name: &str, path: &str) -> Result<impl Stream<Item = Result<Bytes>>> {
+        let path = format!("logs/{}", path.trim_start_matches('/'));
+        let mut req = self
+            .request
+            .node_proxy(name, &path)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("node_logs");
+        self.client.request_text_stream(req).await
+    }
+
+    /// Get the stats summary of the kubelet of the node `name`
+    pub async fn node_stats_summary(&self, name: &str) -> Result<StatsSummary> {
+        let mut req = self
+            .request
+            .node_proxy(name, "stats/summary")
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("node_stats_summary");
+        self.client.request::<StatsSummary>(req).await
+    }
+
+    /// Get the metrics of the kubelet of the node `name`, in the Prometheus text format
+    pub async fn node_metrics(&self, name: &str) -> Result<String> {
+        let mut req = self
+            .request
+            .node_proxy(name, "metrics")
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("node_metrics");
+        self.client.request_text(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsSummary;
+
+    #[test]
+    fn parses_stats_summary() {
+        let summary: StatsSummary = serde_json::from_str(
+            r#"{
+                "node": {
+                    "nodeName": "node-1",
+                    "systemContainers": [{"name": "kubelet"}],
+                    "startTime": "2021-11-01T10:00:00Z",
+                    "cpu": {"time": "2021-11-02T10:00:00Z", "usageNanoCores": 151238020},
+                    "memory": {"workingSetBytes": 1073741824, "pageFaults": 12}
+                },
+                "pods": [{
+                    "podRef": {"name": "web-0", "namespace": "apps", "uid": "1234"},
+                    "containers": [{"name": "web", "rootfs": {"usedBytes": 4096}}],
+                    "ephemeral-storage": {"usedBytes": 8192}
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(summary.node.node_name, "node-1");
+        assert_eq!(summary.node.cpu.unwrap().usage_nano_cores, Some(151238020));
+        assert_eq!(summary.node.memory.unwrap().working_set_bytes, Some(1073741824));
+        let pod = &summary.pods[0];
+        assert_eq!(pod.pod_ref.namespace, "apps");
+        assert_eq!(pod.containers[0].rootfs.as_ref().unwrap().used_bytes, Some(4096));
+        assert_eq!(pod.ephemeral_storage.as_ref().unwrap().used_bytes, Some(8192));
+    }
+}
@@ -0,0 +1,254 @@
+use std::{collections::HashSet, future::Future, io};
+
+use bytes::Bytes;
+use futures::{
+    channel::oneshot,
+    future::{self, AbortHandle, Abortable},
+    stream, SinkExt, StreamExt,
+};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use tokio_tungstenite::{tungstenite as ws, WebSocketStream};
+use tokio_util::io::ReaderStream;
+
+use crate::{Error, Result};
+
+const MAX_BUF_SIZE: usize = 1024 * 1024;
+
+/// Forwarded ports of a pod, from [`Api::portforward`](crate::Api::portforward)
+///
+/// Each forwarded port is a duplex stream, like a TCP connection to the port in the pod.
+/// All of them share one WebSocket connection to the apiserver, where every port has a data channel
+/// and an error channel.
+///
+/// The connection is closed when the apiserver closes it, when all streams are dropped,
+/// or with [`Portforwarder::abort`].
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+pub struct Portforwarder {
+    ports: Vec<u16>,
+    streams: Vec<Option<DuplexStream>>,
+    errors: Vec<Option<oneshot::Receiver<String>>>,
+    result: oneshot::Receiver<Result<(), ws::Error>>,
+    abort: AbortHandle,
+}
+
+impl Portforwarder {
+    pub(crate) fn new<S>(stream: WebSocketStream<S>, ports: &[u16]) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Sized + Send + 'static,
+    {
+        let mut streams = Vec::with_capacity(ports.len());
+        let mut readers = Vec::with_capacity(ports.len());
+        let mut writers = Vec::with_capacity(ports.len());
+        let mut errors = Vec::with_capacity(ports.len());
+        let mut error_senders = Vec::with_capacity(ports.len());
+        for _ in ports {
+            let (a, b) = tokio::io::duplex(MAX_BUF_SIZE);
+            let (reader, writer) = tokio::io::split(a);
+            streams.push(Some(b));
+            readers.push(reader);
+            writers.push(Some(writer));
+            let (sender, receiver) = oneshot::channel();
+            errors.push(Some(receiver));
+            error_senders.push(Some(sender));
+        }
+
+        let (result_sender, result) = oneshot::channel();
+        let (abort, registration) = AbortHandle::new_pair();
+        let message_loop = start_message_loop(stream, readers, writers, error_senders);
+        tokio::spawn(async move {
+            // Aborting drops the connection, which is not an error
+            let res = Abortable::new(message_loop, registration).await.unwrap_or(Ok(()));
+            let _ = result_sender.send(res);
+        });
+
+        Portforwarder {
+            ports: ports.to_vec(),
+            streams,
+            errors,
+            result,
+            abort,
+        }
+    }
+
+    /// Take the stream of the forwarded `port`
+    ///
+    /// Returns `None` if `port` was not forwarded, or if its stream was already taken.
+    /// The connection stays open until the streams of all ports are dropped or shut down.
+    pub fn take_stream(&mut self, port: u16) -> Option<impl AsyncRead + AsyncWrite + Unpin> {
+        let index = self.index(port)?;
+        self.streams[index].take()
+    }
+
+    /// Take the error of the forwarded `port`
+    ///
+    /// The future resolves with the first error the apiserver reports for `port`, like a refused connection
+    /// inside the pod, and with `None` if the connection closes without one.
+    /// Returns `None` if `port` was not forwarded, or if its error was already taken.
+    pub fn take_error(&mut self, port: u16) -> Option<impl Future<Output = Option<String>>> {
+        let index = self.index(port)?;
+        let receiver = self.errors[index].take()?;
+        Some(async move { receiver.await.ok() })
+    }
+
+    /// Close the connection, which ends the streams of all ports
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// A handle to close the connection from other tasks, like [`Portforwarder::abort`]
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.clone()
+    }
+
+    /// Wait for the connection to close
+    ///
+    /// This does not time out. It waits until the apiserver closes the connection, the streams of all ports
+    /// are dropped, or [`Portforwarder::abort`] is called, so bound it with `tokio::time::timeout` if needed.
+    /// Fails with [`Error::WebSocket`] if the connection failed rather than being closed.
+    pub async fn join(self) -> Result<()> {
+        match self.result.await {
+            Ok(res) => res.map_err(Error::WebSocket),
+            Err(oneshot::Canceled) => Ok(()),
+        }
+    }
+
+    fn index(&self, port: u16) -> Option<usize> {
+        self.ports.iter().position(|p| *p == port)
+    }
+}
+
+// Events of the message loop, in the order they arrive
+enum Event {
+    Server(Result<ws::Message, ws::Error>),
+    ServerClosed,
+    Local(usize, io::Result<Bytes>),
+    LocalClosed,
+}
+
+// Port `i` has data channel `2 * i` and error channel `2 * i + 1`.
+async fn start_message_loop<S>(
+    stream: WebSocketStream<S>,
+    readers: Vec<ReadHalf<DuplexStream>>,
+    mut writers: Vec<Option<WriteHalf<DuplexStream>>>,
+    mut errors: Vec<Option<oneshot::Sender<String>>>,
+) -> Result<(), ws::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Sized + Send + 'static,
+{
+    let (mut server_send, server_recv) = stream.split();
+    let server = server_recv
+        .map(Event::Server)
+        .chain(stream::once(future::ready(Event::ServerClosed)));
+    let local = stream::select_all(
+        readers
+            .into_iter()
+            .enumerate()
+            .map(|(i, reader)| ReaderStream::new(reader).map(move |bytes| Event::Local(i, bytes))),
+    )
+    .chain(stream::once(future::ready(Event::LocalClosed)));
+    let mut events = stream::select(server, local);
+    // The first frame on every channel is the port it belongs to
+    let mut initialized = HashSet::new();
+
+    while let Some(event) = events.next().await {
+        match event {
+            Event::Server(Ok(ws::Message::Binary(bin))) if !bin.is_empty() => {
+                let channel = bin[0] as usize;
+                let index = channel / 2;
+                if index >= writers.len() {
+                    continue;
+                }
+                let mut data = &bin[1..];
+                if initialized.insert(channel) {
+                    data = data.get(2..).unwrap_or_default();
+                }
+                if data.is_empty() {
+                    continue;
+                }
+                if channel % 2 == 0 {
+                    if let Some(writer) = writers[index].as_mut() {
+                        if writer.write_all(data).await.is_err() {
+                            // The stream was dropped, so nobody reads from this port anymore
+                            writers[index] = None;
+                        }
+                    }
+                } else if let Some(sender) = errors[index].take() {
+                    let _ = sender.send(String::from_utf8_lossy(data).into_owned());
+                }
+            }
+            Event::Server(Ok(ws::Message::Close(_))) => {
+                // The apiserver closed the connection, acknowledging it is best effort
+                let _ = server_send.close().await;
+                break;
+            }
+            // Ignore any other message types
+            Event::Server(Ok(_)) => {}
+            Event::Server(Err(err)) => return Err(err),
+            Event::ServerClosed => break,
+
+            Event::Local(index, Ok(bytes)) => {
+                let mut frame = Vec::with_capacity(bytes.len() + 1);
+                frame.push((2 * index) as u8);
+                frame.extend_from_slice(&bytes[..]);
+                server_send.send(ws::Message::binary(frame)).await?;
+            }
+            // A failed read ends the stream of that port only
+            Event::Local(_, Err(_)) => {}
+            Event::LocalClosed => {
+                // All streams were dropped, let the server know and disconnect
+                server_send.close().await?;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Portforwarder;
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_tungstenite::{
+        tungstenite::{protocol::Role, Message},
+        WebSocketStream,
+    };
+
+    #[tokio::test]
+    async fn forwards_data_and_errors_per_port() {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let client = WebSocketStream::from_raw_socket(client_io, Role::Client, None).await;
+        let mut server = WebSocketStream::from_raw_socket(server_io, Role::Server, None).await;
+        let mut pf = Portforwarder::new(client, &[80, 8443]);
+
+        for channel in 0..4u8 {
+            let port: u16 = if channel < 2 { 80 } else { 8443 };
+            let mut frame = vec![channel];
+            frame.extend_from_slice(&port.to_le_bytes());
+            server.send(Message::binary(frame)).await.unwrap();
+        }
+        server.send(Message::binary(b"\x02hello".to_vec())).await.unwrap();
+        server.send(Message::binary(b"\x01refused".to_vec())).await.unwrap();
+
+        let mut https = pf.take_stream(8443).unwrap();
+        assert!(pf.take_stream(8443).is_none());
+        assert!(pf.take_stream(22).is_none());
+        let mut buf = [0; 5];
+        https.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        https.write_all(b"ping").await.unwrap();
+        let sent = server.next().await.unwrap().unwrap();
+        assert_eq!(sent.into_data(), b"\x02ping".to_vec());
+
+        let error = pf.take_error(80).unwrap().await;
+        assert_eq!(error.as_deref(), Some("refused"));
+
+        server.close(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), pf.join())
+            .await
+            .expect("connection did not close")
+            .unwrap();
+    }
+}
@@ -8,16 +8,18 @@ use std::{
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
 
 use futures::{
+    channel::mpsc,
     future::{
-        select,
+        self, select, AbortHandle, Abortable,
         Either::{Left, Right},
     },
-    SinkExt, StreamExt,
+    stream, SinkExt, StreamExt,
 };
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream};
 use tokio_tungstenite::{tungstenite as ws, WebSocketStream};
 
-use super::AttachParams;
+use super::{AttachParams, TerminalSize};
+use crate::client::StreamProtocol;
 
 // Internal state of an attached process
 struct AttachedProcessState {
@@ -27,6 +29,7 @@ struct AttachedProcessState {
     stdin_writer: Option<DuplexStream>,
     stdout_reader: Option<DuplexStream>,
     stderr_reader: Option<DuplexStream>,
+    terminal_size: Option<mpsc::Sender<TerminalSize>>,
 }
 
 const MAX_BUF_SIZE: usize = 1024;
@@ -36,6 +39,14 @@ const MAX_BUF_SIZE: usize = 1024;
 /// Resolves when the connection terminates with an optional [`Status`].
 /// Provides access to `stdin`, `stdout`, and `stderr` if attached.
 ///
+/// Dropping this closes the connection, unless `keep_alive` was set in the [`AttachParams`].
+/// The connection then stays open until the process exits, so that input written to `stdin` is still delivered.
+/// Use [`AttachedProcess::abort`] or [`AttachedProcess::abort_handle`] to close it early.
+///
+/// On Kubernetes 1.29+, dropping `stdin` only closes the input of the process, so its remaining output
+/// and exit [`Status`] are still received. Older servers close the whole connection instead.
+/// Use [`exit_code`] to get the exit code out of the [`Status`].
+///
 /// [`attach`]: crate::Api::attach
 /// [`exec`]: crate::Api::exec
 /// [`Status`]: k8s_openapi::apimachinery::pkg::apis::meta::v1::Status
@@ -44,11 +55,14 @@ pub struct AttachedProcess {
     has_stdin: bool,
     has_stdout: bool,
     has_stderr: bool,
+    has_tty: bool,
     state: Arc<Mutex<AttachedProcessState>>,
+    abort: AbortHandle,
+    keep_alive: bool,
 }
 
 impl AttachedProcess {
-    pub(crate) fn new<S>(stream: WebSocketStream<S>, ap: &AttachParams) -> Self
+    pub(crate) fn new<S>(stream: WebSocketStream<S>, ap: &AttachParams, protocol: StreamProtocol) -> Self
     where
         S: AsyncRead + AsyncWrite + Unpin + Sized + Send + 'static,
     {
@@ -68,6 +82,8 @@ impl AttachedProcess {
             (None, None)
         };
 
+        let (terminal_size, resize) = mpsc::channel(1);
+
         let state = Arc::new(Mutex::new(AttachedProcessState {
             waker: None,
             finished: false,
@@ -75,10 +91,21 @@ impl AttachedProcess {
             stdin_writer: Some(stdin_writer),
             stdout_reader,
             stderr_reader,
+            terminal_size: Some(terminal_size),
         }));
         let shared_state = state.clone();
+        let (abort, registration) = AbortHandle::new_pair();
+        let message_loop = start_message_loop(
+            stream,
+            protocol,
+            stdin_reader,
+            resize,
+            stdout_writer,
+            stderr_writer,
+        );
         tokio::spawn(async move {
-            let status = start_message_loop(stream, stdin_reader, stdout_writer, stderr_writer).await;
+            // Aborting drops the connection, and resolves without a status
+            let status = Abortable::new(message_loop, registration).await.unwrap_or(None);
 
             let mut shared = shared_state.lock().unwrap();
             shared.finished = true;
@@ -92,10 +119,26 @@ impl AttachedProcess {
             has_stdin: ap.stdin,
             has_stdout: ap.stdout,
             has_stderr: ap.stderr,
+            has_tty: ap.tty,
             state,
+            abort,
+            keep_alive: ap.keep_alive,
         }
     }
 
+    /// Close the connection, which stops streaming `stdout` and `stderr`
+    ///
+    /// The process then resolves without a [`Status`](k8s_openapi::apimachinery::pkg::apis::meta::v1::Status).
+    /// Note that closing the connection does not necessarily kill the process in the container.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// A handle to close the connection from other tasks, like [`AttachedProcess::abort`]
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.clone()
+    }
+
     /// Async writer to stdin.
     /// ```ignore
     /// let mut stdin_writer = attached.stdin().unwrap();
@@ -139,6 +182,29 @@ impl AttachedProcess {
         let mut state = self.state.lock().unwrap();
         state.stderr_reader.take()
     }
+
+    /// Sender of the terminal size, to resize the terminal of the process.
+    /// ```ignore
+    /// let mut terminal_size = attached.terminal_size().unwrap();
+    /// terminal_size.send(TerminalSize { width: 120, height: 40 }).await?;
+    /// ```
+    /// Only available if [`AttachParams`](super::AttachParams) had `tty`.
+    pub fn terminal_size(&mut self) -> Option<mpsc::Sender<TerminalSize>> {
+        if !self.has_tty {
+            return None;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.terminal_size.take()
+    }
+}
+
+impl Drop for AttachedProcess {
+    fn drop(&mut self) {
+        if !self.keep_alive {
+            self.abort.abort();
+        }
+    }
 }
 
 impl Future for AttachedProcess {
@@ -167,29 +233,72 @@ const STDOUT_CHANNEL: u8 = 1;
 const STDERR_CHANNEL: u8 = 2;
 // status channel receives `Status` object on exit.
 const STATUS_CHANNEL: u8 = 3;
-// const RESIZE_CHANNEL: u8 = 4;
+// resize channel receives `TerminalSize` objects as JSON.
+const RESIZE_CHANNEL: u8 = 4;
+// close channel receives the channel to close, only in the subprotocol v5.
+const CLOSE_CHANNEL: u8 = 255;
+
+/// The exit code of a process from the [`Status`] it resolved with
+///
+/// Returns `0` if the process succeeded, and the code of the `ExitCode` cause if it exited with
+/// a non-zero code.
+/// Returns `None` if the process failed without an exit code, e.g. if the command was not found.
+///
+/// ```ignore
+/// let status = attached.await.unwrap();
+/// assert_eq!(exit_code(&status), Some(0));
+/// ```
+pub fn exit_code(status: &Status) -> Option<i32> {
+    if status.status.as_deref() == Some("Success") {
+        return Some(0);
+    }
+    status
+        .details
+        .as_ref()?
+        .causes
+        .as_ref()?
+        .iter()
+        .find(|cause| cause.reason.as_deref() == Some("ExitCode"))?
+        .message
+        .as_deref()?
+        .parse()
+        .ok()
+}
+
+// Input to send to the server
+enum Input {
+    Stdin(std::io::Result<bytes::Bytes>),
+    StdinClosed,
+    Resize(TerminalSize),
+}
 
 async fn start_message_loop<S>(
     stream: WebSocketStream<S>,
+    protocol: StreamProtocol,
     stdin: impl AsyncRead + Unpin,
+    resize: mpsc::Receiver<TerminalSize>,
     mut stdout: Option<impl AsyncWrite + Unpin>,
     mut stderr: Option<impl AsyncWrite + Unpin>,
 ) -> Option<Status>
 where
     S: AsyncRead + AsyncWrite + Unpin + Sized + Send + 'static,
 {
-    let mut stdin_stream = tokio_util::io::ReaderStream::new(stdin);
+    let stdin_stream = tokio_util::io::ReaderStream::new(stdin)
+        .map(Input::Stdin)
+        .chain(stream::once(future::ready(Input::StdinClosed)));
+    // Input never ends, the loop ends when the connection closes
+    let mut input = stream::select(stdin_stream, resize.map(Input::Resize)).chain(stream::pending());
     let (mut server_send, raw_server_recv) = stream.split();
     // Work with filtered messages to reduce noise.
     let mut server_recv = raw_server_recv.filter_map(filter_message).boxed();
     let mut server_msg = server_recv.next();
-    let mut next_stdin = stdin_stream.next();
+    let mut next_input = input.next();
     let mut status: Option<Status> = None;
 
     loop {
-        match select(server_msg, next_stdin).await {
+        match select(server_msg, next_input).await {
             // from server
-            Left((Some(message), p_next_stdin)) => {
+            Left((Some(message), p_next_input)) => {
                 match message {
                     Ok(Message::Stdout(bin)) => {
                         if let Some(stdout) = stdout.as_mut() {
@@ -221,7 +330,7 @@ where
                     }
                 }
                 server_msg = server_recv.next();
-                next_stdin = p_next_stdin;
+                next_input = p_next_input;
             }
 
             Left((None, _)) => {
@@ -230,7 +339,7 @@ where
             }
 
             // from stdin
-            Right((Some(Ok(bytes)), p_server_msg)) => {
+            Right((Some(Input::Stdin(Ok(bytes))), p_server_msg)) => {
                 if !bytes.is_empty() {
                     let mut vec = Vec::with_capacity(bytes.len() + 1);
                     vec.push(STDIN_CHANNEL);
@@ -241,15 +350,44 @@ where
                         .expect("send stdin");
                 }
                 server_msg = p_server_msg;
-                next_stdin = stdin_stream.next();
+                next_input = input.next();
             }
 
-            Right((Some(Err(err)), _)) => {
+            Right((Some(Input::Stdin(Err(err))), _)) => {
                 server_send.close().await.expect("send close message");
                 panic!("AttachedProcess: failed to read from stdin pipe: {:?}", err);
             }
 
-            Right((None, _)) => {
+            // from the terminal size sender
+            Right((Some(Input::Resize(size)), p_server_msg)) => {
+                let mut vec = vec![RESIZE_CHANNEL];
+                if let Err(err) = serde_json::to_writer(&mut vec, &size) {
+                    tracing::warn!("AttachedProcess: failed to serialize terminal size: {}", err);
+                    break;
+                }
+                if let Err(err) = server_send.send(ws::Message::binary(vec)).await {
+                    // The connection is gone, so the process resolves without a status
+                    tracing::warn!("AttachedProcess: failed to send terminal size: {}", err);
+                    break;
+                }
+                server_msg = p_server_msg;
+                next_input = input.next();
+            }
+
+            Right((Some(Input::StdinClosed), p_server_msg)) if protocol == StreamProtocol::V5 => {
+                // Close only stdin, and keep receiving the output and the status
+                if let Err(err) = server_send
+                    .send(ws::Message::binary(vec![CLOSE_CHANNEL, STDIN_CHANNEL]))
+                    .await
+                {
+                    tracing::warn!("AttachedProcess: failed to close stdin: {}", err);
+                    break;
+                }
+                server_msg = p_server_msg;
+                next_input = input.next();
+            }
+
+            Right((Some(Input::StdinClosed), _)) | Right((None, _)) => {
                 // Stdin closed (writer half dropped).
                 // Let the server know and disconnect.
                 // REVIEW warn?
@@ -293,3 +431,38 @@ async fn filter_message(wsm: Result<ws::Message, ws::Error>) -> Option<Result<Me
         Err(err) => Some(Err(err)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::exit_code;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+
+    #[test]
+    fn exit_code_from_status() {
+        let success: Status = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "status": "Success",
+        }))
+        .unwrap();
+        assert_eq!(exit_code(&success), Some(0));
+
+        let failure: Status = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "status": "Failure",
+            "message": "command terminated with non-zero exit code: exit code 3",
+            "reason": "NonZeroExitCode",
+            "details": { "causes": [{ "reason": "ExitCode", "message": "3" }] },
+        }))
+        .unwrap();
+        assert_eq!(exit_code(&failure), Some(3));
+
+        let not_found: Status = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "status": "Failure",
+            "message": "executable file not found in $PATH",
+            "reason": "InternalError",
+        }))
+        .unwrap();
+        assert_eq!(exit_code(&not_found), None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Inspecting the revision history of Deployments and rolling them back, like `kubectl rollout history/undo`
+use k8s_openapi::api::{
+    apps::v1::{Deployment, ReplicaSet},
+    core::v1::PodTemplateSpec,
+};
+use kube_core::labels::{ParseOperatorError, Selector};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+use crate::{
+    api::{Api, ListParams, Patch, PatchParams, ResourceExt},
+    Result,
+};
+
+/// Possible errors when inspecting or rolling back the revisions of a Deployment
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The deployment has no revision to roll back to
+    #[error("deployment has no revision to roll back to")]
+    MissingRevision,
+
+    /// Paused deployments cannot be rolled back
+    #[error("cannot roll back paused deployment {0}")]
+    PausedDeployment(String),
+
+    /// The selector of the deployment has an unknown operator
+    #[error("invalid selector of deployment {0}: {1}")]
+    InvalidSelector(String, #[source] ParseOperatorError),
+}
+
+const REVISION_ANNOTATION: &str = "deployment.kubernetes.io/revision";
+const CHANGE_CAUSE_ANNOTATION: &str = "kubernetes.io/change-cause";
+const POD_TEMPLATE_HASH_LABEL: &str = "pod-template-hash";
+
+/// A revision of a Deployment, backed by one of its ReplicaSets
+#[derive(Clone, Debug)]
+pub struct Revision {
+    /// The revision number, from the `deployment.kubernetes.io/revision` annotation
+    pub revision: i64,
+    /// The reason for the change, from the `kubernetes.io/change-cause` annotation
+    pub change_cause: Option<String>,
+    /// The `pod-template-hash` label that identifies the pods of the revision
+    pub pod_template_hash: Option<String>,
+    /// The ReplicaSet of the revision
+    pub replica_set: ReplicaSet,
+}
+
+impl Revision {
+    fn from_replica_set(replica_set: ReplicaSet) -> Option<Self> {
+        let revision = replica_set.annotations().get(REVISION_ANNOTATION)?.parse().ok()?;
+        Some(Self {
+            revision,
+            change_cause: replica_set.annotations().get(CHANGE_CAUSE_ANNOTATION).cloned(),
+            pod_template_hash: replica_set.labels().get(POD_TEMPLATE_HASH_LABEL).cloned(),
+            replica_set,
+        })
+    }
+}
+
+/// Returns the revisions of `deployment` from `replica_sets`, ordered from the oldest to the current revision
+///
+/// ReplicaSets that are not controlled by `deployment`, or have no revision, are ignored.
+pub fn revisions(deployment: &Deployment, replica_sets: Vec<ReplicaSet>) -> Vec<Revision> {
+    let uid = deployment.uid();
+    let mut revisions = replica_sets
+        .into_iter()
+        .filter(|rs| {
+            rs.owner_references()
+                .iter()
+                .any(|owner| owner.controller == Some(true) && Some(&owner.uid) == uid.as_ref())
+        })
+        .filter_map(Revision::from_replica_set)
+        .collect::<Vec<_>>();
+    revisions.sort_by_key(|r| r.revision);
+    revisions
+}
+
+// The selector of the ReplicaSets of `deployment`, from both its `matchLabels` and `matchExpressions`
+fn replica_set_selector(deployment: &Deployment) -> Result<Selector, Error> {
+    let selector = deployment
+        .spec
+        .as_ref()
+        .map(|spec| Selector::try_from(spec.selector.clone()))
+        .transpose()
+        .map_err(|err| Error::InvalidSelector(deployment.name(), err))?;
+    Ok(selector.unwrap_or_default())
+}
+
+// The strategic merge patch that restores the pod template of `replica_set`, like `kubectl rollout undo`
+fn rollback_patch(replica_set: &ReplicaSet, resource_version: Option<String>) -> Value {
+    let mut template = replica_set
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.clone())
+        .unwrap_or_default();
+    if let Some(labels) = template.metadata.as_mut().and_then(|meta| meta.labels.as_mut()) {
+        labels.remove(POD_TEMPLATE_HASH_LABEL);
+    }
+    let mut template = serde_json::to_value::<PodTemplateSpec>(template).unwrap_or_default();
+    // Replace the whole template, rather than merging it with the current one
+    template["$patch"] = "replace".into();
+    json!({
+        "metadata": {
+            "annotations": {
+                CHANGE_CAUSE_ANNOTATION: replica_set.annotations().get(CHANGE_CAUSE_ANNOTATION),
+            },
+            // Fail rather than roll back over a concurrent change
+            "resourceVersion": resource_version,
+        },
+        "spec": { "template": template },
+    })
+}
+
+impl Api<Deployment> {
+    /// Lists the revisions of the Deployment `name`, ordered from the oldest to the current revision
+    ///
+    /// Revisions are backed by the ReplicaSets the Deployment controls, and are kept
+    /// up to its `revisionHistoryLimit`. Like `kubectl rollout history`.
+    pub async fn rollout_history(&self, name: &str) -> Result<Vec<Revision>> {
+        let deployment = self.get(name).await?;
+        self.revisions_of(&deployment).await
+    }
+
+    async fn revisions_of(&self, deployment: &Deployment) -> Result<Vec<Revision>> {
+        let replica_sets: Api<ReplicaSet> = match deployment.namespace() {
+            Some(ns) => Api::namespaced(self.client.clone(), &ns),
+            None => Api::all(self.client.clone()),
+        };
+        let selector = replica_set_selector(deployment).map_err(crate::Error::Rollout)?;
+        let list = replica_sets
+            .list(&ListParams::default().labels(&selector.to_string()))
+            .await?;
+        Ok(revisions(deployment, list.items))
+    }
+
+    /// Rolls the Deployment `name` back to the pod template of `revision`, like `kubectl rollout undo`
+    ///
+    /// Rolls back to the previous revision when `revision` is `None`. The rollback creates a new revision
+    /// with the old template, and fails if the Deployment changed concurrently or is paused.
+    pub async fn rollout_undo(&self, name: &str, revision: Option<i64>) -> Result<Deployment> {
+        let deployment = self.get(name).await?;
+        if deployment.spec.as_ref().and_then(|spec| spec.paused) == Some(true) {
+            return Err(crate::Error::Rollout(Error::PausedDeployment(name.into())));
+        }
+        let mut revisions = self.revisions_of(&deployment).await?;
+        let target = match revision {
+            Some(revision) => revisions.into_iter().find(|r| r.revision == revision),
+            None => {
+                // The current revision is the last one
+                revisions.pop();
+                revisions.pop()
+            }
+        }
+        .ok_or(crate::Error::Rollout(Error::MissingRevision))?;
+
+        let patch = rollback_patch(&target.replica_set, deployment.resource_version());
+        self.patch(name, &PatchParams::default(), &Patch::Strategic(&patch))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replica_set(revision: &str, hash: &str, owner: &str) -> ReplicaSet {
+        serde_json::from_value(json!({
+            "metadata": {
+                "name": format!("web-{}", hash),
+                "annotations": {
+                    REVISION_ANNOTATION: revision,
+                    CHANGE_CAUSE_ANNOTATION: format!("deploy {}", hash),
+                },
+                "labels": { "app": "web", POD_TEMPLATE_HASH_LABEL: hash },
+                "ownerReferences": [{
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "name": "web",
+                    "uid": owner,
+                    "controller": true,
+                }],
+            },
+            "spec": {
+                "selector": { "matchLabels": { "app": "web" } },
+                "template": {
+                    "metadata": { "labels": { "app": "web", POD_TEMPLATE_HASH_LABEL: hash } },
+                    "spec": { "containers": [{ "name": "web", "image": format!("web:{}", hash) }] },
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn orders_revisions_of_deployment() {
+        let deployment: Deployment = serde_json::from_value(json!({
+            "metadata": { "name": "web", "uid": "d1" }
+        }))
+        .unwrap();
+        let history = revisions(&deployment, vec![
+            replica_set("10", "c", "d1"),
+            replica_set("2", "b", "d1"),
+            replica_set("1", "other", "d2"),
+            replica_set("1", "a", "d1"),
+        ]);
+        let summary = history
+            .iter()
+            .map(|r| (r.revision, r.pod_template_hash.as_deref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![(1, "a"), (2, "b"), (10, "c")]);
+        assert_eq!(history[1].change_cause.as_deref(), Some("deploy b"));
+    }
+
+    #[test]
+    fn selects_replica_sets_with_expressions() {
+        let deployment = |operator: &str| -> Deployment {
+            serde_json::from_value(json!({
+                "metadata": { "name": "web" },
+                "spec": {
+                    "selector": {
+                        "matchLabels": { "app": "web" },
+                        "matchExpressions": [{ "key": "tier", "operator": operator, "values": ["frontend"] }],
+                    },
+                    "template": {},
+                }
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            replica_set_selector(&deployment("In")).unwrap().to_string(),
+            "app=web,tier in (frontend)"
+        );
+        assert!(matches!(
+            replica_set_selector(&deployment("Near")),
+            Err(Error::InvalidSelector(name, _)) if name == "web"
+        ));
+    }
+
+    #[test]
+    fn rollback_patch_replaces_template() {
+        let patch = rollback_patch(&replica_set("1", "a", "d1"), Some("42".into()));
+        assert_eq!(
+            patch,
+            json!({
+                "metadata": {
+                    "annotations": { CHANGE_CAUSE_ANNOTATION: "deploy a" },
+                    "resourceVersion": "42",
+                },
+                "spec": {
+                    "template": {
+                        "$patch": "replace",
+                        "metadata": { "labels": { "app": "web" } },
+                        "spec": { "containers": [{ "name": "web", "image": "web:a" }] },
+                    }
+                }
+            })
+        );
+    }
+}
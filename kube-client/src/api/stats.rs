@@ -0,0 +1,163 @@
+//! Counting conflicts and throttling of the requests of an [`Api`](crate::Api)
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+};
+
+use http::StatusCode;
+
+// Bound on the names counted in `conflicts_by_name`, so that conflicts on ever new names do not grow it forever
+const MAX_CONFLICTING_NAMES: usize = 100;
+
+/// Counts of the requests of an [`Api`](crate::Api) that the apiserver rejected with a conflict or throttled
+///
+/// Retrieved with [`Api::stats`](crate::Api::stats). Clones of an `Api` share their statistics.
+/// Frequent conflicts on an object suggest switching its updates to server-side apply, or backing off
+/// its updates, while throttling suggests slowing down requests altogether.
+///
+/// ```no_run
+/// use kube::{api::Api, Client};
+/// use k8s_openapi::api::core::v1::ConfigMap;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let cms: Api<ConfigMap> = Api::namespaced(client, "apps");
+/// // ...
+/// let stats = cms.stats();
+/// for (name, conflicts) in &stats.conflicts_by_name {
+///     if *conflicts > 3 {
+///         println!("{} keeps conflicting, consider server-side apply", name);
+///     }
+/// }
+/// if stats.throttled > 0 {
+///     println!("throttled {} out of {} requests", stats.throttled, stats.requests);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApiStats {
+    /// Requests that got a response
+    pub requests: u64,
+    /// Requests rejected with a conflict (`409`), usually because of an outdated `resourceVersion`
+    pub conflicts: u64,
+    /// Requests throttled by the apiserver (`429`)
+    pub throttled: u64,
+    /// Conflicts per object name, for requests on a named object
+    ///
+    /// Holds at most 100 names. Once full, a conflict on another name replaces the name with the fewest
+    /// conflicts, so the names that conflict the most are kept.
+    pub conflicts_by_name: BTreeMap<String, u64>,
+}
+
+// Records the statistics of the requests of an `Api`, shared by its clones
+pub(crate) struct StatsRecorder {
+    url_path: String,
+    stats: Mutex<ApiStats>,
+}
+
+impl StatsRecorder {
+    pub(crate) fn new(url_path: &str) -> Self {
+        Self {
+            url_path: url_path.to_string(),
+            stats: Mutex::new(ApiStats::default()),
+        }
+    }
+
+    pub(crate) fn record(&self, path: &str, status: StatusCode) {
+        let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.requests += 1;
+        match status {
+            StatusCode::CONFLICT => {
+                stats.conflicts += 1;
+                if let Some(name) = self.object_name(path) {
+                    let by_name = &mut stats.conflicts_by_name;
+                    if !by_name.contains_key(name) && by_name.len() >= MAX_CONFLICTING_NAMES {
+                        let fewest = by_name
+                            .iter()
+                            .min_by_key(|(_, conflicts)| **conflicts)
+                            .map(|(name, _)| name.clone());
+                        if let Some(fewest) = fewest {
+                            by_name.remove(&fewest);
+                        }
+                    }
+                    *by_name.entry(name.to_string()).or_default() += 1;
+                }
+            }
+            StatusCode::TOO_MANY_REQUESTS => stats.throttled += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ApiStats {
+        self.stats.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub(crate) fn reset(&self) {
+        *self.stats.lock().unwrap_or_else(PoisonError::into_inner) = ApiStats::default();
+    }
+
+    // The name of the object of a request to `{url_path}/{name}[/{subresource}]`
+    fn object_name<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let name = path
+            .strip_prefix(&self.url_path)?
+            .strip_prefix('/')?
+            .split('/')
+            .next()?;
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_conflicts_per_object() {
+        let recorder = StatsRecorder::new("/api/v1/namespaces/apps/configmaps");
+        recorder.record(
+            "/api/v1/namespaces/apps/configmaps/settings",
+            StatusCode::CONFLICT,
+        );
+        recorder.record("/api/v1/namespaces/apps/configmaps/settings", StatusCode::OK);
+        recorder.record("/api/v1/namespaces/apps/configmaps", StatusCode::CONFLICT);
+        recorder.record(
+            "/api/v1/namespaces/apps/configmaps",
+            StatusCode::TOO_MANY_REQUESTS,
+        );
+        let stats = recorder.snapshot();
+        assert_eq!(stats.requests, 4);
+        assert_eq!(stats.conflicts, 2);
+        assert_eq!(stats.throttled, 1);
+        assert_eq!(
+            stats.conflicts_by_name.into_iter().collect::<Vec<_>>(),
+            vec![("settings".to_string(), 1)]
+        );
+
+        recorder.reset();
+        assert_eq!(recorder.snapshot(), ApiStats::default());
+    }
+
+    #[test]
+    fn keeps_the_most_conflicting_names() {
+        let recorder = StatsRecorder::new("/api/v1/namespaces/apps/configmaps");
+        let conflict = |name: &str| {
+            recorder.record(
+                &format!("/api/v1/namespaces/apps/configmaps/{}", name),
+                StatusCode::CONFLICT,
+            )
+        };
+        conflict("settings");
+        conflict("settings");
+        for i in 0..2 * MAX_CONFLICTING_NAMES {
+            conflict(&format!("job-{}", i));
+        }
+        let stats = recorder.snapshot();
+        assert_eq!(stats.conflicts, 2 + 2 * MAX_CONFLICTING_NAMES as u64);
+        assert_eq!(stats.conflicts_by_name.len(), MAX_CONFLICTING_NAMES);
+        assert_eq!(stats.conflicts_by_name.get("settings"), Some(&2));
+    }
+}
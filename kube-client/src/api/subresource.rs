@@ -1,23 +1,34 @@
 use bytes::Bytes;
-use futures::Stream;
+use chrono::{DateTime, Duration, Utc};
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt, TryStreamExt,
+};
 use serde::de::DeserializeOwned;
 use std::fmt::Debug;
+use tokio_util::{
+    codec::{FramedRead, LinesCodec, LinesCodecError},
+    io::StreamReader,
+};
 
 use crate::{
-    api::{Api, Patch, PatchParams, PostParams},
+    api::{Api, DynamicObject, Patch, PatchParams, PostParams},
+    error::{ErrorResponse, EvictionBlocked},
     Error, Result,
 };
 
-use kube_core::response::Status;
-pub use kube_core::subresource::{EvictParams, LogParams};
+use kube_core::{discovery::Subresource, response::Status};
+pub use kube_core::subresource::{EvictParams, LogLine, LogParams, TokenRequestParams};
+use kube_core::subresource::TokenRequest;
 
 #[cfg(feature = "ws")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
-pub use kube_core::subresource::AttachParams;
+pub use kube_core::subresource::{AttachParams, TerminalSize};
 
 pub use k8s_openapi::api::autoscaling::v1::{Scale, ScaleSpec, ScaleStatus};
 
 #[cfg(feature = "ws")] use crate::api::remote_command::AttachedProcess;
+#[cfg(feature = "ws")] use crate::api::portforward::Portforwarder;
 
 /// Methods for [scale subresource](https://kubernetes.io/docs/tasks/access-kubernetes-api/custom-resources/custom-resource-definitions/#scale-subresource).
 impl<K> Api<K>
@@ -147,6 +158,125 @@ where
     }
 }
 
+// ----------------------------------------------------------------------------
+// Arbitrary subresources of dynamic types
+// ----------------------------------------------------------------------------
+
+/// Methods for any subresource of a dynamic type, such as those of custom resources discovered at runtime
+///
+/// The subresource is named by its last path segment, like `status` or `scale`, and its response is
+/// read as a [`DynamicObject`], which fits any object with `apiVersion`, `kind` and `metadata`.
+impl Api<DynamicObject> {
+    /// Get the subresource `subresource` of the object `name`
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, ApiResource, DynamicObject, GroupVersionKind}, Client};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let gvk = GroupVersionKind::gvk("clux.dev", "v1", "Foo");
+    ///     let ar = ApiResource::from_gvk(&gvk);
+    ///     let foos: Api<DynamicObject> = Api::namespaced_with(client, "apps", &ar);
+    ///     let scale = foos.get_subresource("scale", "baz").await?;
+    ///     println!("{:?}", scale.data["spec"]["replicas"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn get_subresource(&self, subresource: &str, name: &str) -> Result<DynamicObject> {
+        let mut req = self
+            .request
+            .get_subresource(subresource, name)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("get_subresource");
+        self.client.request::<DynamicObject>(req).await
+    }
+
+    /// Patch the subresource `subresource` of the object `name`
+    pub async fn patch_subresource<P: serde::Serialize + Debug>(
+        &self,
+        subresource: &str,
+        name: &str,
+        pp: &PatchParams,
+        patch: &Patch<P>,
+    ) -> Result<DynamicObject> {
+        let mut req = self
+            .request
+            .patch_subresource(subresource, name, pp, patch)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("patch_subresource");
+        self.client.request::<DynamicObject>(req).await
+    }
+
+    /// Replace the subresource `subresource` of the object `name` with the serialized `data`
+    pub async fn replace_subresource(
+        &self,
+        subresource: &str,
+        name: &str,
+        pp: &PostParams,
+        data: Vec<u8>,
+    ) -> Result<DynamicObject> {
+        let mut req = self
+            .request
+            .replace_subresource(subresource, name, pp, data)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("replace_subresource");
+        self.client.request::<DynamicObject>(req).await
+    }
+
+    /// Create the subresource `subresource` of the object `name` from the serialized `data`
+    pub async fn create_subresource(
+        &self,
+        subresource: &str,
+        name: &str,
+        pp: &PostParams,
+        data: Vec<u8>,
+    ) -> Result<DynamicObject> {
+        let mut req = self
+            .request
+            .create_subresource(subresource, name, pp, data)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("create_subresource");
+        self.client.request::<DynamicObject>(req).await
+    }
+
+    /// Use `verb` on a discovered `subresource` of the object `name`, sending the serialized `data`
+    ///
+    /// Fails with [`Error::BuildRequest`] before sending anything if the subresource does not support `verb`.
+    /// See [`Request::subresource_verb`](kube_core::Request::subresource_verb) for the supported verbs.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, DynamicObject, GroupVersionKind, PostParams}, discovery, Client};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let gvk = GroupVersionKind::gvk("certificates.k8s.io", "v1", "CertificateSigningRequest");
+    ///     let (ar, caps) = discovery::pinned_kind(&client, &gvk).await?;
+    ///     let approval = caps.subresource("approval").expect("csrs can be approved");
+    ///     let csrs: Api<DynamicObject> = Api::all_with(client, &ar);
+    ///     let mut csr = csrs.get("my-csr").await?;
+    ///     csr.data["status"] = serde_json::json!({"conditions": [{"type": "Approved", "status": "True"}]});
+    ///     let data = serde_json::to_vec(&csr)?;
+    ///     csrs.subresource_verb(&approval, "update", "my-csr", &PostParams::default(), data).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn subresource_verb(
+        &self,
+        subresource: &Subresource,
+        verb: &str,
+        name: &str,
+        pp: &PostParams,
+        data: Vec<u8>,
+    ) -> Result<DynamicObject> {
+        let mut req = self
+            .request
+            .subresource_verb(subresource, verb, name, pp, data)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("subresource_verb");
+        self.client.request::<DynamicObject>(req).await
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Log subresource
 // ----------------------------------------------------------------------------
@@ -186,6 +316,328 @@ where
         req.extensions_mut().insert("log_stream");
         self.client.request_text_stream(req).await
     }
+
+    /// Fetch logs as parsed lines
+    ///
+    /// With [`LogParams::timestamps`], the timestamps are split off into [`LogLine::timestamp`].
+    pub async fn log_lines(&self, name: &str, lp: &LogParams) -> Result<Vec<LogLine>> {
+        let logs = self.logs(name, lp).await?;
+        Ok(logs.lines().map(|line| LogLine::parse(line, lp.timestamps)).collect())
+    }
+
+    /// Fetch logs as a stream of parsed lines, such as to follow them
+    ///
+    /// With [`LogParams::timestamps`], the timestamps are split off into [`LogLine::timestamp`].
+    pub async fn log_line_stream(
+        &self,
+        name: &str,
+        lp: &LogParams,
+    ) -> Result<impl Stream<Item = Result<LogLine>>> {
+        let timestamps = lp.timestamps;
+        let bytes = self
+            .log_stream(name, lp)
+            .await?
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err));
+        let lines = FramedRead::new(StreamReader::new(bytes), LinesCodec::new());
+        Ok(lines.map(move |line| match line {
+            Ok(line) => Ok(LogLine::parse(&line, timestamps)),
+            Err(LinesCodecError::Io(err)) => Err(Error::ReadEvents(err)),
+            Err(LinesCodecError::MaxLineLengthExceeded) => Err(Error::LinesCodecMaxLineLengthExceeded),
+        }))
+    }
+}
+
+// How long to wait before reconnecting a followed log stream
+const LOG_RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+impl<K> Api<K>
+where
+    K: DeserializeOwned + Log + Clone + Send + Sync + 'static,
+{
+    /// Follow logs as a stream of parsed lines, reconnecting when the connection drops
+    ///
+    /// Every reconnect asks for the logs since the timestamp of the last line, and skips the lines
+    /// that were already returned, so the stream has no gaps or duplicates. The logs are always requested
+    /// with [`LogParams::follow`] and [`LogParams::timestamps`], but [`LogLine::timestamp`] is only set
+    /// if `lp` asks for timestamps. [`LogParams::limit_bytes`] limits the lines of all connections together.
+    ///
+    /// When a connection ends, the pod is fetched to see whether the container has terminated, and the
+    /// stream only ends once it has, once the pod is gone, or when a reconnect fails. Containers that are
+    /// waiting to start or to restart are reconnected to until they run.
+    /// Dropping the stream closes its connection.
+    ///
+    /// ```no_run
+    /// use futures::TryStreamExt;
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use kube::{api::{Api, LogParams}, Client};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::default_namespaced(client);
+    ///     let lp = LogParams { tail_lines: Some(10), ..LogParams::default() };
+    ///     let mut lines = pods.follow_log_lines("web-0", &lp);
+    ///     while let Some(line) = lines.try_next().await? {
+    ///         println!("{}", line.message);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn follow_log_lines(&self, name: &str, lp: &LogParams) -> impl Stream<Item = Result<LogLine>> {
+        let follow = LogFollow {
+            api: self.clone(),
+            name: name.to_string(),
+            timestamps: lp.timestamps,
+            lp: LogParams {
+                follow: true,
+                timestamps: true,
+                ..lp.clone()
+            },
+            lines: None,
+            last: LogResume::default(),
+            skip: 0,
+        };
+        stream::try_unfold(follow, |mut follow| async move {
+            Ok(follow.next_line().await?.map(|line| (line, follow)))
+        })
+    }
+
+    // Connects to the logs of `name`, with a stream of the raw length and the parsed line of every line
+    async fn log_connect(
+        &self,
+        name: &str,
+        lp: &LogParams,
+    ) -> Result<BoxStream<'static, Result<(usize, LogLine)>>> {
+        let mut req = self.request.logs(name, lp).map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("follow_log_lines");
+        let res = self.client.send(req.map(hyper::Body::from)).await?;
+        let status = res.status();
+        if !status.is_success() {
+            // The body is a `Status` rather than logs, such as for containers that are waiting to start
+            let body = hyper::body::to_bytes(res.into_body())
+                .await
+                .map_err(Error::HyperError)?;
+            crate::client::handle_api_errors(&String::from_utf8_lossy(&body), status)?;
+            return Ok(stream::empty().boxed());
+        }
+        let bytes = res
+            .into_body()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err));
+        let lines = FramedRead::new(StreamReader::new(bytes), LinesCodec::new());
+        Ok(lines
+            .map(|line| match line {
+                Ok(line) => Ok((line.len() + 1, LogLine::parse(&line, true))),
+                Err(LinesCodecError::Io(err)) => Err(Error::ReadEvents(err)),
+                Err(LinesCodecError::MaxLineLengthExceeded) => Err(Error::LinesCodecMaxLineLengthExceeded),
+            })
+            .boxed())
+    }
+
+    // Whether the logged `container` of the pod `name` has terminated, or all its containers if unset
+    async fn log_container_terminated(&self, name: &str, container: Option<&str>) -> Result<bool> {
+        // Only pods have logs, so the request of `self` addresses pods
+        let pods = Api::<k8s_openapi::api::core::v1::Pod> {
+            request: self.request.clone(),
+            client: self.client.clone(),
+            phantom: std::iter::empty(),
+        };
+        Ok(container_terminated(&pods.get(name).await?, container))
+    }
+}
+
+// The state of a followed log stream
+struct LogFollow<K> {
+    api: Api<K>,
+    name: String,
+    // Whether the caller asked for timestamps
+    timestamps: bool,
+    // The params of the next connection
+    lp: LogParams,
+    // The current connection
+    lines: Option<BoxStream<'static, Result<(usize, LogLine)>>>,
+    last: LogResume,
+    // How many lines of the current connection were already returned by an earlier one
+    skip: usize,
+}
+
+impl<K> LogFollow<K>
+where
+    K: DeserializeOwned + Log + Clone + Send + Sync + 'static,
+{
+    // Returns the next new line, reconnecting as needed, or `None` once the logs have ended
+    async fn next_line(&mut self) -> Result<Option<LogLine>> {
+        loop {
+            if self.lines.is_none() {
+                match self.api.log_connect(&self.name, &self.lp).await {
+                    Ok(lines) => {
+                        self.lines = Some(lines);
+                        self.skip = self.last.seen;
+                    }
+                    Err(Error::Api(ErrorResponse { code: 404, .. })) => return Ok(None),
+                    // The container is waiting to start or to restart, unless it has terminated
+                    Err(Error::Api(ErrorResponse { code: 400, .. })) => {
+                        if self.terminated().await? {
+                            return Ok(None);
+                        }
+                        tokio::time::sleep(LOG_RECONNECT_DELAY).await;
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            let next = match &mut self.lines {
+                Some(lines) => lines.next().await,
+                None => None,
+            };
+            let dropped = match next {
+                Some(Ok((len, mut line))) => {
+                    if !self.last.record(&line, &mut self.skip) {
+                        continue;
+                    }
+                    if let Some(limit) = &mut self.lp.limit_bytes {
+                        *limit = (*limit - len as i64).max(0);
+                    }
+                    if !self.timestamps {
+                        line.timestamp = None;
+                    }
+                    return Ok(Some(line));
+                }
+                Some(Err(_)) => true,
+                None => false,
+            };
+
+            self.lines = None;
+            if self.lp.limit_bytes == Some(0) {
+                return Ok(None);
+            }
+            // A clean end can also be a log rotation or a kubelet restart, so check the container
+            if !dropped && self.terminated().await? {
+                return Ok(None);
+            }
+            if let Some(time) = self.last.time {
+                self.lp.since_time = Some(time);
+                self.lp.since_seconds = None;
+                self.lp.tail_lines = None;
+            }
+            tokio::time::sleep(LOG_RECONNECT_DELAY).await;
+        }
+    }
+
+    // Whether the followed container has terminated, or the pod is gone
+    async fn terminated(&self) -> Result<bool> {
+        match self
+            .api
+            .log_container_terminated(&self.name, self.lp.container.as_deref())
+            .await
+        {
+            Ok(terminated) => Ok(terminated),
+            Err(Error::Api(ErrorResponse { code: 404, .. })) => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn container_terminated(pod: &k8s_openapi::api::core::v1::Pod, container: Option<&str>) -> bool {
+    let status = match &pod.status {
+        Some(status) => status,
+        None => return false,
+    };
+    if matches!(status.phase.as_deref(), Some("Succeeded" | "Failed")) {
+        return true;
+    }
+    let mut statuses = status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten())
+        .filter(|cs| container.map_or(true, |name| cs.name == name))
+        .peekable();
+    statuses.peek().is_some()
+        && statuses.all(|cs| cs.state.as_ref().map_or(false, |state| state.terminated.is_some()))
+}
+
+// The last timestamp of a followed log stream, and how many lines with that timestamp were returned
+#[derive(Default)]
+struct LogResume {
+    time: Option<DateTime<Utc>>,
+    seen: usize,
+}
+
+impl LogResume {
+    // Records `line`, returning whether it is new.
+    // `skip` is the number of lines with the last timestamp that a reconnect returns again.
+    fn record(&mut self, line: &LogLine, skip: &mut usize) -> bool {
+        let time = match line.timestamp {
+            Some(time) => time,
+            // Lines without a timestamp cannot be matched up, so they are always new
+            None => return true,
+        };
+        match self.time {
+            Some(last) if time < last => false,
+            Some(last) if time == last && *skip > 0 => {
+                *skip -= 1;
+                false
+            }
+            Some(last) if time == last => {
+                self.seen += 1;
+                true
+            }
+            _ => {
+                self.time = Some(time);
+                self.seen = 1;
+                *skip = 0;
+                true
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Proxy subresource
+// ----------------------------------------------------------------------------
+
+/// Marker trait for objects that can be reached through the proxy of the apiserver
+pub trait Proxy {}
+
+impl Proxy for k8s_openapi::api::core::v1::Pod {}
+impl Proxy for k8s_openapi::api::core::v1::Service {}
+
+impl<K> Api<K>
+where
+    K: Proxy,
+{
+    /// Send an HTTP `request` to the pod or service `name`, through the proxy of the apiserver
+    ///
+    /// This reaches in-cluster webhooks and dashboards without port-forwarding. See
+    /// [`Request::proxy`](kube_core::Request::proxy) for how `port` and `request` are passed on.
+    /// The response is returned whatever its status, which may also come from the apiserver,
+    /// like a `503` when a service has no ready endpoints.
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::core::v1::Service;
+    /// use kube::{Api, Client};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let services: Api<Service> = Api::namespaced(client, "monitoring");
+    ///     let request = http::Request::get("/-/healthy").body(vec![])?;
+    ///     let response = services.proxy_http("prometheus", Some("9090"), request).await?;
+    ///     println!("{}: {:?}", response.status(), response.body());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn proxy_http(
+        &self,
+        name: &str,
+        port: Option<&str>,
+        request: http::Request<Vec<u8>>,
+    ) -> Result<http::Response<Bytes>> {
+        let mut req = self
+            .request
+            .proxy(name, port, request)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("proxy_http");
+        self.client.request_response(req).await
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -211,14 +663,212 @@ impl<K> Api<K>
 where
     K: DeserializeOwned + Evict,
 {
-    /// Create an eviction
+    /// Create a `policy/v1` eviction, falling back to `policy/v1beta1` on clusters that do not serve it
+    ///
+    /// Fails with [`Error::EvictionBlocked`] when a PodDisruptionBudget does not allow the eviction yet,
+    /// which drain tooling can retry later, unlike other failures.
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, EvictParams}, Client, Error};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pods: Api<Pod> = Api::namespaced(client, "apps");
+    ///     loop {
+    ///         match pods.evict("blog", &EvictParams::default()).await {
+    ///             Err(Error::EvictionBlocked(blocked)) => {
+    ///                 let wait = blocked.retry_after.unwrap_or(std::time::Duration::from_secs(5));
+    ///                 tokio::time::sleep(wait).await;
+    ///             }
+    ///             res => {
+    ///                 res?;
+    ///                 break;
+    ///             }
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
     pub async fn evict(&self, name: &str, ep: &EvictParams) -> Result<Status> {
         let mut req = self.request.evict(name, ep).map_err(Error::BuildRequest)?;
         req.extensions_mut().insert("evict");
-        self.client.request::<Status>(req).await
+        let res = match self.client.request::<Status>(req).await {
+            Err(Error::Api(ae)) if serves_no_v1_eviction(&ae) => {
+                let mut req = self.request.evict_v1beta1(name, ep).map_err(Error::BuildRequest)?;
+                req.extensions_mut().insert("evict");
+                self.client.request::<Status>(req).await
+            }
+            res => res,
+        };
+        res.map_err(|err| eviction_blocked(name, err))
+    }
+}
+
+// Clusters before 1.22 reject the `policy/v1` version of the body
+fn serves_no_v1_eviction(ae: &ErrorResponse) -> bool {
+    (ae.code == 400 || ae.code == 404) && ae.message.contains("policy/v1")
+}
+
+// Types refusals by a PodDisruptionBudget, which are `429 TooManyRequests` with a `DisruptionBudget` cause
+fn eviction_blocked(name: &str, err: Error) -> Error {
+    let ae = match err {
+        Error::Api(ae) if ae.code == 429 => ae,
+        err => return err,
+    };
+    let cause = ae
+        .details
+        .iter()
+        .flat_map(|details| &details.causes)
+        .find(|cause| cause.reason == "DisruptionBudget");
+    let message = match cause {
+        Some(cause) => cause.message.clone(),
+        None => return Error::Api(ae),
+    };
+    let retry_after = ae
+        .details
+        .as_ref()
+        .map(|details| details.retry_after_seconds)
+        .filter(|&secs| secs > 0)
+        .map(|secs| std::time::Duration::from_secs(secs.into()));
+    Error::EvictionBlocked(EvictionBlocked {
+        name: name.into(),
+        message,
+        retry_after,
+        source: ae,
+    })
+}
+
+#[test]
+fn types_disruption_budget_refusals() {
+    let ae: ErrorResponse = serde_json::from_value(serde_json::json!({
+        "status": "Failure",
+        "message": "Cannot evict pod as it would violate the pod's disruption budget.",
+        "reason": "TooManyRequests",
+        "details": {
+            "causes": [{
+                "reason": "DisruptionBudget",
+                "message": "The disruption budget web needs 2 healthy pods and has 2 currently",
+            }],
+            "retryAfterSeconds": 10,
+        },
+        "code": 429,
+    }))
+    .unwrap();
+    match eviction_blocked("blog", Error::Api(ae.clone())) {
+        Error::EvictionBlocked(blocked) => {
+            assert!(blocked.message.starts_with("The disruption budget web"));
+            assert_eq!(blocked.retry_after, Some(std::time::Duration::from_secs(10)));
+        }
+        err => panic!("unexpected {:?}", err),
+    }
+    let throttled = ErrorResponse { details: None, ..ae };
+    assert!(matches!(eviction_blocked("blog", Error::Api(throttled)), Error::Api(_)));
+}
+
+// ----------------------------------------------------------------------------
+// TokenRequest subresource
+// ----------------------------------------------------------------------------
+
+#[test]
+fn token_request_path() {
+    use crate::api::{Request, Resource};
+    use k8s_openapi::api::core::v1 as corev1;
+    let tp = TokenRequestParams::default().audiences(["vault"]);
+    let url = corev1::ServiceAccount::url_path(&(), Some("ns"));
+    let req = Request::new(url).create_token_request("foo", &tp).unwrap();
+    assert_eq!(req.uri(), "/api/v1/namespaces/ns/serviceaccounts/foo/token?");
+}
+
+/// Marker trait for objects that can issue bound tokens
+pub trait RequestToken {}
+
+impl RequestToken for k8s_openapi::api::core::v1::ServiceAccount {}
+
+impl<K> Api<K>
+where
+    K: DeserializeOwned + RequestToken,
+{
+    /// Request a bound token for a ServiceAccount
+    ///
+    /// The issued token is found in the `status` of the returned `TokenRequest`,
+    /// along with its expiration timestamp. Fails with [`Error::TokenExpiry`] if no token was issued,
+    /// or if it expires sooner than [`TokenRequestParams::min_validity_seconds`].
+    ///
+    /// ```no_run
+    /// use kube::{api::{Api, TokenRequestParams}, Client};
+    /// use k8s_openapi::api::core::v1::{Pod, ServiceAccount};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let pod = Api::<Pod>::namespaced(client.clone(), "apps").get("worker").await?;
+    ///     let sas: Api<ServiceAccount> = Api::namespaced(client, "apps");
+    ///     let tp = TokenRequestParams::default()
+    ///         .audiences(["vault"])
+    ///         .expiration_seconds(3600)
+    ///         .min_validity_seconds(1800)
+    ///         .bound_to(&pod);
+    ///     let issued = sas.create_token_request("worker", &tp).await?;
+    ///     let token = issued.status.unwrap().token;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn create_token_request(&self, name: &str, tp: &TokenRequestParams) -> Result<TokenRequest> {
+        let mut req = self
+            .request
+            .create_token_request(name, tp)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("create_token_request");
+        let token_request = self.client.request::<TokenRequest>(req).await?;
+        check_token_expiry(tp, &token_request, Utc::now())?;
+        Ok(token_request)
     }
 }
 
+// Checks that the issued token is valid for at least the minimum validity after `now`
+fn check_token_expiry(
+    tp: &TokenRequestParams,
+    token_request: &TokenRequest,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let status = token_request
+        .status
+        .as_ref()
+        .ok_or_else(|| Error::TokenExpiry("no token was issued".into()))?;
+    let expiration = status.expiration_timestamp.0;
+    let min_validity = tp.min_validity_seconds.unwrap_or(0);
+    if expiration <= now + Duration::seconds(min_validity) {
+        return Err(Error::TokenExpiry(format!(
+            "expires at {}, but must be valid for at least {}s",
+            expiration.to_rfc3339(),
+            min_validity
+        )));
+    }
+    Ok(())
+}
+
+#[test]
+fn token_request_expiry() {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use kube_core::subresource::TokenRequestStatus;
+
+    let now = Utc::now();
+    let issued = |seconds| TokenRequest {
+        status: Some(TokenRequestStatus {
+            token: "token".into(),
+            expiration_timestamp: Time(now + Duration::seconds(seconds)),
+        }),
+        ..TokenRequest::default()
+    };
+    let tp = TokenRequestParams::default();
+    assert!(check_token_expiry(&tp, &issued(600), now).is_ok());
+    assert!(check_token_expiry(&tp, &issued(-1), now).is_err());
+    assert!(check_token_expiry(&tp, &TokenRequest::default(), now).is_err());
+    let tp = tp.min_validity_seconds(3600);
+    assert!(matches!(check_token_expiry(&tp, &issued(600), now), Err(Error::TokenExpiry(_))));
+    assert!(check_token_expiry(&tp, &issued(7200), now).is_ok());
+}
+
 // ----------------------------------------------------------------------------
 // Attach subresource
 // ----------------------------------------------------------------------------
@@ -259,8 +909,8 @@ where
     pub async fn attach(&self, name: &str, ap: &AttachParams) -> Result<AttachedProcess> {
         let mut req = self.request.attach(name, ap).map_err(Error::BuildRequest)?;
         req.extensions_mut().insert("attach");
-        let stream = self.client.connect(req).await?;
-        Ok(AttachedProcess::new(stream, ap))
+        let (stream, protocol) = self.client.connect_remote_command(req).await?;
+        Ok(AttachedProcess::new(stream, ap, protocol))
     }
 }
 
@@ -317,7 +967,107 @@ where
             .exec(name, command, ap)
             .map_err(Error::BuildRequest)?;
         req.extensions_mut().insert("exec");
+        let (stream, protocol) = self.client.connect_remote_command(req).await?;
+        Ok(AttachedProcess::new(stream, ap, protocol))
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Portforward subresource
+// ----------------------------------------------------------------------------
+
+/// Marker trait for objects that has portforward
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+pub trait Portforward {}
+
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+impl Portforward for k8s_openapi::api::core::v1::Pod {}
+
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+impl<K> Api<K>
+where
+    K: Clone + DeserializeOwned + Portforward,
+{
+    /// Forward `ports` of a pod
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use kube::{Api, Client};
+    /// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::try_default().await?;
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// let mut pf = pods.portforward("nginx", &[80]).await?;
+    /// let mut stream = pf.take_stream(80).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.0\r\nHost: 127.0.0.1\r\n\r\n").await?;
+    /// let mut response = Vec::new();
+    /// stream.read_to_end(&mut response).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn portforward(&self, name: &str, ports: &[u16]) -> Result<Portforwarder> {
+        let mut req = self
+            .request
+            .portforward(name, ports)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("portforward");
         let stream = self.client.connect(req).await?;
-        Ok(AttachedProcess::new(stream, ap))
+        Ok(Portforwarder::new(stream, ports))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_resume_skips_returned_lines() {
+        let line = |time: &str, message: &str| LogLine {
+            timestamp: Some(DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)),
+            message: message.into(),
+        };
+        let mut resume = LogResume::default();
+        let mut skip = 0;
+        assert!(resume.record(&line("2021-11-02T10:00:01Z", "a"), &mut skip));
+        assert!(resume.record(&line("2021-11-02T10:00:02Z", "b"), &mut skip));
+        assert!(resume.record(&line("2021-11-02T10:00:02Z", "c"), &mut skip));
+
+        // The reconnect returns everything since 10:00:02 again
+        let mut skip = resume.seen;
+        assert!(!resume.record(&line("2021-11-02T10:00:01Z", "a"), &mut skip));
+        assert!(!resume.record(&line("2021-11-02T10:00:02Z", "b"), &mut skip));
+        assert!(!resume.record(&line("2021-11-02T10:00:02Z", "c"), &mut skip));
+        assert!(resume.record(&line("2021-11-02T10:00:02Z", "d"), &mut skip));
+        assert!(resume.record(&line("2021-11-02T10:00:03Z", "e"), &mut skip));
+        assert_eq!((resume.time, resume.seen), (line("2021-11-02T10:00:03Z", "").timestamp, 1));
+    }
+
+    #[test]
+    fn container_termination() {
+        let pod = |phase: &str, web: serde_json::Value| -> k8s_openapi::api::core::v1::Pod {
+            serde_json::from_value(serde_json::json!({
+                "metadata": { "name": "web-0" },
+                "status": {
+                    "phase": phase,
+                    "containerStatuses": [
+                        { "name": "web", "image": "", "imageID": "", "ready": false, "restartCount": 1,
+                          "state": web },
+                        { "name": "sidecar", "image": "", "imageID": "", "ready": true, "restartCount": 0,
+                          "state": { "running": {} } },
+                    ],
+                },
+            }))
+            .unwrap()
+        };
+        let restarted = pod("Running", serde_json::json!({ "running": {} }));
+        assert!(!container_terminated(&restarted, Some("web")));
+        let crashed = pod("Running", serde_json::json!({ "terminated": { "exitCode": 1 } }));
+        assert!(container_terminated(&crashed, Some("web")));
+        assert!(!container_terminated(&crashed, None));
+        assert!(!container_terminated(&crashed, Some("missing")));
+        assert!(container_terminated(&pod("Succeeded", serde_json::json!({})), None));
     }
 }
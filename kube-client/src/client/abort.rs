@@ -0,0 +1,131 @@
+//! Cancelling long-running streams from other tasks
+//!
+//! Dropping the futures and streams returned by the [`Client`](crate::Client) and [`Api`](crate::Api)
+//! cancels the underlying HTTP request. [`abortable`] allows doing the same from another task,
+//! without having to own the stream.
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pub use futures::future::AbortHandle;
+use futures::{ready, stream::Stream};
+use pin_project::pin_project;
+
+/// A stream that ends, and drops the request under it, when aborted through its [`AbortHandle`]
+///
+/// Created with [`abortable`].
+#[pin_project]
+pub struct AbortableStream<S> {
+    #[pin]
+    inner: Option<futures::stream::Abortable<S>>,
+}
+
+impl<S: Stream> Stream for AbortableStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let item = match this.inner.as_mut().as_pin_mut() {
+            Some(inner) => ready!(inner.poll_next(cx)),
+            None => return Poll::Ready(None),
+        };
+        if item.is_none() {
+            // Drop the stream as soon as it ends or is aborted, rather than when the caller drops us
+            this.inner.set(None);
+        }
+        Poll::Ready(item)
+    }
+}
+
+/// Wraps `stream` so that it can be cancelled from other tasks through the returned [`AbortHandle`]
+///
+/// Once aborted, the stream ends and the underlying request is cancelled when the stream is next polled.
+/// The task polling the stream is woken up, so this happens promptly even if it is waiting for data.
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use k8s_openapi::api::core::v1::Pod;
+/// use kube::{api::{Api, LogParams}, client::abortable, Client};
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = Client::try_default().await?;
+///     let pods: Api<Pod> = Api::default_namespaced(client);
+///     let lp = LogParams { follow: true, ..LogParams::default() };
+///     let (mut logs, abort) = abortable(pods.log_stream("blog", &lp).await?);
+///     tokio::spawn(async move {
+///         tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+///         abort.abort();
+///     });
+///     while let Some(line) = logs.next().await {
+///         println!("{:?}", line?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn abortable<S: Stream>(stream: S) -> (AbortableStream<S>, AbortHandle) {
+    let (inner, handle) = futures::stream::abortable(stream);
+    (AbortableStream { inner: Some(inner) }, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::abortable;
+    use crate::Client;
+
+    use bytes::Bytes;
+    use futures::{pin_mut, StreamExt};
+    use http::{Request, Response};
+    use hyper::Body;
+    use tower_test::mock;
+
+    // Responds to a single request with a body that is streamed through the returned sender
+    fn streaming_client() -> (Client, tokio::sync::oneshot::Receiver<hyper::body::Sender>) {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            pin_mut!(handle);
+            let (_request, send) = handle.next_request().await.expect("service not called");
+            let (sender, body) = Body::channel();
+            send.send_response(Response::builder().body(body).unwrap());
+            tx.send(sender).ok();
+        });
+        (Client::new(mock_service, "default"), rx)
+    }
+
+    fn request() -> Request<Vec<u8>> {
+        Request::get("/api/v1/namespaces/default/pods/blog/log?follow=true")
+            .body(vec![])
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn dropping_stream_cancels_request() {
+        let (client, sender) = streaming_client();
+        let stream = client.request_text_stream(request()).await.unwrap();
+        let mut sender = sender.await.unwrap();
+        sender.send_data(Bytes::from("line\n")).await.unwrap();
+        drop(stream);
+        assert!(sender.send_data(Bytes::from("line\n")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn aborting_stream_cancels_request() {
+        let (client, sender) = streaming_client();
+        let stream = client.request_text_stream(request()).await.unwrap();
+        let (mut stream, abort) = abortable(stream);
+        let mut sender = sender.await.unwrap();
+        sender.send_data(Bytes::from("line\n")).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "line\n");
+
+        let reader = tokio::spawn(async move {
+            let rest = stream.next().await;
+            // The request is cancelled while the stream is still held
+            (rest.is_none(), stream)
+        });
+        abort.abort();
+        let (ended, _stream) = reader.await.unwrap();
+        assert!(ended);
+        assert!(sender.send_data(Bytes::from("line\n")).await.is_err());
+    }
+}
@@ -0,0 +1,473 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use http::{header::CONTENT_TYPE, Method, Request, StatusCode};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use super::issuer::{send, SendError};
+use crate::config::AuthProviderConfig;
+
+#[derive(Error, Debug)]
+/// Possible errors when requesting Azure AD tokens
+pub enum Error {
+    /// A key required to request tokens is missing from the auth-provider config
+    #[error("auth-provider config is missing {0}")]
+    MissingConfig(&'static str),
+
+    /// The `environment` of the auth-provider config is not a known Azure cloud
+    #[error("unknown Azure environment {0}")]
+    UnknownEnvironment(String),
+
+    /// Failed to request Azure AD
+    #[error("failed to request token: {0}")]
+    RequestToken(#[source] hyper::Error),
+
+    /// Azure AD responded with an error
+    #[error("token endpoint responded with {0}: {1}")]
+    TokenStatus(StatusCode, String),
+
+    /// Failed to parse the response of Azure AD
+    #[error("failed to parse token response: {0}")]
+    ParseResponse(#[source] serde_json::Error),
+
+    /// The user must sign in with a device code, as the message says, before tokens can be requested
+    ///
+    /// Sign-in is awaited in the background, and the token is used once the user has signed in.
+    #[error("azure sign-in required: {0}")]
+    SignInRequired(String),
+
+    /// The device code expired before the user signed in
+    #[error("device code expired before signing in")]
+    DeviceCodeExpired,
+
+    /// The managed identity endpoint did not respond in time
+    #[error("managed identity endpoint is unavailable")]
+    ManagedIdentityUnavailable,
+
+    /// Failed to build a request
+    #[error("failed to build request: {0}")]
+    BuildRequest(#[source] http::Error),
+
+    /// Failed to concatenate the buffers from response body
+    #[error("failed to concatenate the buffers from response body: {0}")]
+    ConcatBuffers(#[source] hyper::Error),
+
+    /// Failed to create a TLS connector
+    #[error("failed to create TLS connector: {0}")]
+    CreateTlsConnector(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Failed to create OpenSSL HTTPS connector
+    #[cfg(feature = "openssl-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "openssl-tls")))]
+    #[error("failed to create OpenSSL HTTPS connector: {0}")]
+    CreateOpensslHttpsConnector(#[source] openssl::error::ErrorStack),
+}
+
+// Refresh tokens a little before they expire, so they don't expire in flight
+const EXPIRY_SLACK_SECONDS: i64 = 60;
+const IMDS_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+const IMDS_TIMEOUT_SECONDS: u64 = 5;
+
+/// Azure AD access token for AKS, from the `azure` auth-provider
+///
+/// The cached access-token is used until it expires. It is then refreshed with the refresh-token, or
+/// requested with the first available flow if there is none or it was rejected:
+/// - client secret, from `AZURE_CLIENT_ID`, `AZURE_CLIENT_SECRET`, and `AZURE_TENANT_ID`
+/// - device code, if standard input is a terminal. Requests fail with [`Error::SignInRequired`], which says
+///   where to sign in, until the user has signed in.
+/// - managed identity, using `AZURE_CLIENT_ID` to select a user-assigned identity
+///
+/// See https://github.com/kubernetes/client-go/tree/release-1.22/plugin/pkg/client/auth/azure
+pub struct Azure {
+    // Tokens without an expiry are assumed to be valid, and left to the apiserver to reject
+    access_token: Option<(String, Option<DateTime<Utc>>)>,
+    refresh_token: Option<String>,
+    aad: Aad,
+    // Device code sign-in, awaited in the background so that the token is not locked meanwhile
+    sign_in: Option<SignIn>,
+}
+
+impl std::fmt::Debug for Azure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Azure")
+            .field("client_id", &self.aad.client_id)
+            .field("tenant_id", &self.aad.tenant_id)
+            .field("apiserver_id", &self.aad.apiserver_id)
+            .finish()
+    }
+}
+
+// The Azure AD application and tenant that tokens for the apiserver are requested from
+#[derive(Clone)]
+struct Aad {
+    client_id: String,
+    tenant_id: String,
+    apiserver_id: String,
+    authority: &'static str,
+    // `config-mode: "1"` selects the v2.0 endpoints, which use scopes instead of resources
+    v2: bool,
+}
+
+struct SignIn {
+    // Where to sign in, returned to the caller until the user has signed in
+    message: String,
+    response: oneshot::Receiver<Result<TokenResponse, Error>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    // v1 and managed identity responses use strings, v2 uses numbers
+    expires_on: Option<serde_json::Value>,
+    expires_in: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    message: String,
+    expires_in: serde_json::Value,
+    interval: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl Azure {
+    pub(crate) fn from_provider(provider: &AuthProviderConfig) -> Result<Self, Error> {
+        let get = |key: &str| provider.config.get(key).filter(|v| !v.is_empty()).cloned();
+        let authority = match get("environment").as_deref() {
+            None | Some("AzurePublicCloud") => "https://login.microsoftonline.com",
+            Some("AzureChinaCloud") => "https://login.chinacloudapi.cn",
+            Some("AzureUSGovernmentCloud") => "https://login.microsoftonline.us",
+            Some("AzureGermanCloud") => "https://login.microsoftonline.de",
+            Some(env) => return Err(Error::UnknownEnvironment(env.into())),
+        };
+        let access_token = get("access-token").map(|token| {
+            let expiry = get("expires-on").and_then(|e| timestamp(&e.into()));
+            (token, expiry)
+        });
+        Ok(Self {
+            access_token,
+            refresh_token: get("refresh-token"),
+            aad: Aad {
+                client_id: get("client-id").ok_or(Error::MissingConfig("client-id"))?,
+                tenant_id: get("tenant-id").ok_or(Error::MissingConfig("tenant-id"))?,
+                apiserver_id: get("apiserver-id").ok_or(Error::MissingConfig("apiserver-id"))?,
+                authority,
+                v2: get("config-mode").as_deref() == Some("1"),
+            },
+            sign_in: None,
+        })
+    }
+
+    /// Returns the access token, requesting a new one if it has expired
+    pub async fn token(&mut self) -> Result<String, Error> {
+        if let Some((token, expiry)) = &self.access_token {
+            let valid = expiry.map_or(true, |expiry| {
+                Utc::now() + Duration::seconds(EXPIRY_SLACK_SECONDS) < expiry
+            });
+            if valid {
+                return Ok(token.clone());
+            }
+        }
+
+        if let Some(sign_in) = &mut self.sign_in {
+            match sign_in.response.try_recv() {
+                Ok(response) => {
+                    self.sign_in = None;
+                    return self.update(response?);
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {
+                    return Err(Error::SignInRequired(sign_in.message.clone()));
+                }
+                Err(oneshot::error::TryRecvError::Closed) => self.sign_in = None,
+            }
+        }
+
+        let refreshed = match self.refresh_token.clone() {
+            Some(refresh_token) => match self
+                .aad
+                .request_token(&[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", refresh_token.as_str()),
+                    ("client_id", self.aad.client_id.as_str()),
+                ])
+                .await
+            {
+                Ok(response) => Some(response),
+                // Refresh-tokens expire or are revoked, request a new token instead
+                Err(Error::TokenStatus(status, body)) if status.is_client_error() => {
+                    tracing::debug!("refreshing azure token failed with {}: {}", status, body);
+                    self.refresh_token = None;
+                    None
+                }
+                Err(err) => return Err(err),
+            },
+            None => None,
+        };
+
+        let response = if let Some(response) = refreshed {
+            response
+        } else if let (Some(client_id), Some(secret), Some(tenant_id)) = (
+            env("AZURE_CLIENT_ID"),
+            env("AZURE_CLIENT_SECRET"),
+            env("AZURE_TENANT_ID"),
+        ) {
+            let endpoint = self.aad.endpoint(&tenant_id, "token");
+            self.aad
+                .post(&endpoint, &[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", client_id.as_str()),
+                    ("client_secret", secret.as_str()),
+                ])
+                .await?
+        } else if atty::is(atty::Stream::Stdin) {
+            let sign_in = self.aad.device_code().await?;
+            let message = sign_in.message.clone();
+            self.sign_in = Some(sign_in);
+            return Err(Error::SignInRequired(message));
+        } else {
+            self.aad.managed_identity().await?
+        };
+        self.update(response)
+    }
+
+    // Caches the token of the response
+    fn update(&mut self, response: TokenResponse) -> Result<String, Error> {
+        let expiry = response.expires_on.as_ref().and_then(timestamp).or_else(|| {
+            let expires_in = response.expires_in.as_ref().and_then(seconds)?;
+            Some(Utc::now() + Duration::seconds(expires_in))
+        });
+        if let Some(refresh_token) = response.refresh_token {
+            self.refresh_token = Some(refresh_token);
+        }
+        self.access_token = Some((response.access_token.clone(), expiry));
+        Ok(response.access_token)
+    }
+}
+
+impl Aad {
+    fn endpoint(&self, tenant_id: &str, path: &str) -> String {
+        if self.v2 {
+            format!("{}/{}/oauth2/v2.0/{}", self.authority, tenant_id, path)
+        } else {
+            format!("{}/{}/oauth2/{}", self.authority, tenant_id, path)
+        }
+    }
+
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<TokenResponse, Error> {
+        self.post(&self.endpoint(&self.tenant_id, "token"), params).await
+    }
+
+    // Adds the resource (v1) or scope (v2) of the apiserver
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, Error> {
+        // The serializer is not `Send`, so it must not be held across the `await`
+        let body = {
+            let mut form = form_urlencoded::Serializer::new(String::new());
+            form.extend_pairs(params);
+            if self.v2 {
+                form.append_pair("scope", &format!("{}/.default offline_access", self.apiserver_id));
+            } else {
+                form.append_pair("resource", &self.apiserver_id);
+            }
+            form.finish()
+        };
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(endpoint)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.into_bytes())
+            .map_err(Error::BuildRequest)?;
+        Ok(send(request, None).await?)
+    }
+
+    // Starts a device code sign-in, and awaits it in the background
+    async fn device_code(&self) -> Result<SignIn, Error> {
+        let endpoint = self.endpoint(&self.tenant_id, "devicecode");
+        let device: DeviceCodeResponse = self
+            .post(&endpoint, &[("client_id", self.client_id.as_str())])
+            .await?;
+        tracing::info!("{}", device.message);
+        let (tx, rx) = oneshot::channel();
+        let aad = self.clone();
+        let message = device.message.clone();
+        tokio::spawn(async move {
+            let response = aad.await_sign_in(device, &tx).await;
+            let _ = tx.send(response);
+        });
+        Ok(SignIn {
+            message,
+            response: rx,
+        })
+    }
+
+    // Polls for the token until the user has signed in, the code expires, or the sign-in is abandoned
+    async fn await_sign_in(
+        &self,
+        device: DeviceCodeResponse,
+        tx: &oneshot::Sender<Result<TokenResponse, Error>>,
+    ) -> Result<TokenResponse, Error> {
+        let deadline = Utc::now() + Duration::seconds(seconds(&device.expires_in).unwrap_or(900));
+        let interval = device.interval.as_ref().and_then(seconds).unwrap_or(5);
+        let (grant_type, code_param) = if self.v2 {
+            ("urn:ietf:params:oauth:grant-type:device_code", "device_code")
+        } else {
+            ("device_code", "code")
+        };
+        while Utc::now() < deadline && !tx.is_closed() {
+            tokio::time::sleep(std::time::Duration::from_secs(interval as u64)).await;
+            match self
+                .request_token(&[
+                    ("grant_type", grant_type),
+                    ("client_id", self.client_id.as_str()),
+                    (code_param, device.device_code.as_str()),
+                ])
+                .await
+            {
+                Err(Error::TokenStatus(status, body)) => match serde_json::from_str::<ErrorResponse>(&body) {
+                    Ok(err) if err.error == "authorization_pending" || err.error == "slow_down" => continue,
+                    _ => return Err(Error::TokenStatus(status, body)),
+                },
+                result => return result,
+            }
+        }
+        Err(Error::DeviceCodeExpired)
+    }
+
+    async fn managed_identity(&self) -> Result<TokenResponse, Error> {
+        let mut url = format!(
+            "{}?api-version=2018-02-01&resource={}",
+            IMDS_ENDPOINT,
+            form_urlencoded::byte_serialize(self.apiserver_id.as_bytes()).collect::<String>()
+        );
+        if let Some(client_id) = env("AZURE_CLIENT_ID") {
+            url.push_str("&client_id=");
+            url.extend(form_urlencoded::byte_serialize(client_id.as_bytes()));
+        }
+        let request = Request::get(url)
+            .header("Metadata", "true")
+            .body(Vec::new())
+            .map_err(Error::BuildRequest)?;
+        // The endpoint only exists on Azure, don't hang elsewhere
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(IMDS_TIMEOUT_SECONDS),
+            send(request, None),
+        )
+        .await
+        .map_err(|_| Error::ManagedIdentityUnavailable)?;
+        Ok(response?)
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+fn seconds(value: &serde_json::Value) -> Option<i64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn timestamp(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    seconds(value).and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+}
+
+impl From<SendError> for Error {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::Request(err) => Error::RequestToken(err),
+            SendError::Status(status, body) => Error::TokenStatus(status, body),
+            SendError::Parse(err) => Error::ParseResponse(err),
+            SendError::ConcatBuffers(err) => Error::ConcatBuffers(err),
+            SendError::CreateTlsConnector(err) => Error::CreateTlsConnector(err),
+            #[cfg(feature = "openssl-tls")]
+            SendError::CreateOpensslHttpsConnector(err) => Error::CreateOpensslHttpsConnector(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(config: &[(&str, &str)]) -> AuthProviderConfig {
+        AuthProviderConfig {
+            name: "azure".into(),
+            config: config.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn uses_cached_access_token() {
+        let expires_on = (Utc::now() + Duration::hours(1)).timestamp().to_string();
+        let mut azure = Azure::from_provider(&provider(&[
+            ("access-token", "cached"),
+            ("expires-on", &expires_on),
+            ("client-id", "client"),
+            ("tenant-id", "tenant"),
+            ("apiserver-id", "apiserver"),
+            ("config-mode", "1"),
+        ]))
+        .unwrap();
+        assert_eq!(azure.token().await.unwrap(), "cached");
+        assert_eq!(
+            azure.aad.endpoint("tenant", "token"),
+            "https://login.microsoftonline.com/tenant/oauth2/v2.0/token"
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_sign_in_prompt_until_signed_in() {
+        let mut azure = Azure::from_provider(&provider(&[
+            ("client-id", "client"),
+            ("tenant-id", "tenant"),
+            ("apiserver-id", "apiserver"),
+        ]))
+        .unwrap();
+        let (tx, rx) = oneshot::channel();
+        azure.sign_in = Some(SignIn {
+            message: "To sign in, enter the code ABC".into(),
+            response: rx,
+        });
+        match azure.token().await {
+            Err(Error::SignInRequired(message)) => assert_eq!(message, "To sign in, enter the code ABC"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Tokens without an expiry are used until the apiserver rejects them
+        let response = serde_json::from_str(r#"{"access_token": "signed-in"}"#).unwrap();
+        tx.send(Ok(response)).ok().unwrap();
+        assert_eq!(azure.token().await.unwrap(), "signed-in");
+        assert!(azure.sign_in.is_none());
+        assert_eq!(azure.token().await.unwrap(), "signed-in");
+    }
+
+    #[test]
+    fn requires_ids() {
+        assert!(matches!(
+            Azure::from_provider(&provider(&[("client-id", "client"), ("tenant-id", "tenant")])),
+            Err(Error::MissingConfig("apiserver-id"))
+        ));
+        assert!(matches!(
+            Azure::from_provider(&provider(&[
+                ("client-id", "client"),
+                ("tenant-id", "tenant"),
+                ("apiserver-id", "apiserver"),
+                ("environment", "Mars"),
+            ])),
+            Err(Error::UnknownEnvironment(_))
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+//! Requests to the token endpoints of identity providers, shared by the `oidc` and `azure` auth-providers
+use http::{Request, StatusCode};
+
+use crate::client::tls;
+
+/// Errors from [`send`], converted to the errors of the auth-providers
+pub(crate) enum SendError {
+    /// Failed to send the request
+    Request(hyper::Error),
+    /// The issuer responded with an error
+    Status(StatusCode, String),
+    /// Failed to parse the response of the issuer
+    Parse(serde_json::Error),
+    /// Failed to concatenate the buffers from response body
+    ConcatBuffers(hyper::Error),
+    /// Failed to create the TLS connector
+    CreateTlsConnector(Box<dyn std::error::Error + Send + Sync>),
+    /// Failed to create OpenSSL HTTPS connector
+    #[cfg(feature = "openssl-tls")]
+    CreateOpensslHttpsConnector(openssl::error::ErrorStack),
+}
+
+/// Sends `request` to an issuer and parses its JSON response
+///
+/// The issuer is verified with `root_certs` if set, and with the system roots otherwise.
+pub(crate) async fn send<T: serde::de::DeserializeOwned>(
+    request: Request<Vec<u8>>,
+    root_certs: Option<&Vec<Vec<u8>>>,
+) -> Result<T, SendError> {
+    #[cfg(not(any(feature = "native-tls", feature = "rustls-tls", feature = "openssl-tls")))]
+    compile_error!(
+        "At least one of native-tls or rustls-tls or openssl-tls feature must be enabled to use oidc or azure feature"
+    );
+    // Current TLS feature precedence when more than one are set:
+    // 1. openssl-tls
+    // 2. native-tls
+    // 3. rustls-tls
+    let mut http = hyper::client::HttpConnector::new();
+    http.enforce_http(false);
+    #[cfg(feature = "openssl-tls")]
+    let https = {
+        let ssl = tls::openssl_tls::ssl_connector_builder(None, None, root_certs, None, &[])
+            .map_err(|err| SendError::CreateTlsConnector(err.into()))?;
+        hyper_openssl::HttpsConnector::with_connector(http, ssl)
+            .map_err(SendError::CreateOpensslHttpsConnector)?
+    };
+    #[cfg(all(not(feature = "openssl-tls"), feature = "native-tls"))]
+    let https = {
+        let tls = tls::native_tls::native_tls_connector(None, None, root_certs, false)
+            .map_err(|err| SendError::CreateTlsConnector(err.into()))?;
+        hyper_tls::HttpsConnector::from((http, tokio_native_tls::TlsConnector::from(tls)))
+    };
+    #[cfg(all(
+        not(any(feature = "openssl-tls", feature = "native-tls")),
+        feature = "rustls-tls"
+    ))]
+    let https = {
+        let tls = tls::rustls_tls::rustls_client_config(
+            None,
+            None,
+            root_certs.map(Vec::as_slice),
+            false,
+            None,
+            false,
+            &[],
+        )
+        .map_err(|err| SendError::CreateTlsConnector(err.into()))?;
+        // The managed identity endpoint of Azure is plain HTTP
+        hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls)
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http)
+    };
+
+    let client = hyper::Client::builder().build::<_, hyper::Body>(https);
+    let res = client
+        .request(request.map(hyper::Body::from))
+        .await
+        .map_err(SendError::Request)?;
+    let status = res.status();
+    let bytes = hyper::body::to_bytes(res.into_body())
+        .await
+        .map_err(SendError::ConcatBuffers)?;
+    if !status.is_success() {
+        return Err(SendError::Status(
+            status,
+            String::from_utf8_lossy(&bytes).into_owned(),
+        ));
+    }
+    serde_json::from_slice(&bytes).map_err(SendError::Parse)
+}
@@ -1,4 +1,8 @@
-use std::{path::PathBuf, process::Command, sync::Arc};
+use std::{
+    path::PathBuf,
+    process::{Output, Stdio},
+    sync::Arc,
+};
 
 use chrono::{DateTime, Duration, Utc};
 use futures::future::BoxFuture;
@@ -9,13 +13,30 @@ use http::{
 use jsonpath_lib::select as jsonpath_select;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::Mutex,
+};
 use tower::{filter::AsyncPredicate, BoxError};
 
-use crate::config::{AuthInfo, AuthProviderConfig, ExecConfig};
+use crate::config::{AuthInfo, AuthProviderConfig, ExecAuthCluster, ExecConfig, ExecInteractiveMode};
 
+mod provider;
+pub use provider::{Token, TokenProvider};
+mod refresh;
+pub(crate) use refresh::RefreshPolicy;
+mod token_file;
 #[cfg(feature = "oauth")] mod oauth;
 #[cfg(feature = "oauth")] pub use oauth::Error as OAuthError;
+#[cfg(any(feature = "oidc", feature = "azure"))] mod issuer;
+#[cfg(feature = "oidc")] mod oidc;
+#[cfg(feature = "oidc")] pub use oidc::Error as OidcError;
+#[cfg(feature = "eks")] mod eks;
+#[cfg(feature = "azure")] mod azure;
+#[cfg(feature = "azure")] pub use azure::Error as AzureError;
+#[cfg(feature = "eks")] pub use eks::Error as EksError;
+#[cfg(feature = "spnego")] mod spnego;
+#[cfg(feature = "spnego")] pub use spnego::Error as SpnegoError;
 
 #[derive(Error, Debug)]
 /// Client auth errors
@@ -45,7 +66,10 @@ pub enum Error {
     AuthExecStart(#[source] std::io::Error),
 
     /// Failed to run auth exec command
-    #[error("auth exec command '{cmd}' failed with status {status}: {out:?}")]
+    #[error(
+        "auth exec command '{cmd}' failed with status {status}: {}",
+        String::from_utf8_lossy(&.out.stderr).trim()
+    )]
     AuthExecRun {
         /// The failed command
         cmd: String,
@@ -55,10 +79,37 @@ pub enum Error {
         out: std::process::Output,
     },
 
+    /// Auth exec command did not finish in time, and was killed
+    #[error("auth exec command '{cmd}' timed out after {timeout:?}: {stderr}")]
+    AuthExecTimeout {
+        /// The command that timed out
+        cmd: String,
+        /// The timeout of the command
+        timeout: std::time::Duration,
+        /// Stderr of the command until it was killed
+        stderr: String,
+    },
+
     /// Failed to parse auth exec output
     #[error("failed to parse auth exec output: {0}")]
     AuthExecParse(#[source] serde_json::Error),
 
+    /// Failed to serialize the `KUBERNETES_EXEC_INFO` passed to auth exec
+    #[error("failed to serialize auth exec info: {0}")]
+    AuthExecSerialize(#[source] serde_json::Error),
+
+    /// Exec plugin using `client.authentication.k8s.io/v1` did not specify `interactiveMode`
+    #[error("exec plugin using {0} must specify interactiveMode")]
+    AuthExecMissingInteractiveMode(String),
+
+    /// Exec plugin requires standard input, but it is not a terminal
+    #[error("exec plugin requires interactive mode, but standard input is not a terminal")]
+    AuthExecNotInteractive,
+
+    /// Exec plugin returned only one of `clientCertificateData` and `clientKeyData`
+    #[error("exec plugin must return both clientCertificateData and clientKeyData, or neither")]
+    AuthExecIncompleteClientCertificate,
+
     /// Failed to exec auth
     #[error("failed exec auth: {0}")]
     AuthExec(String),
@@ -71,11 +122,39 @@ pub enum Error {
     #[error("failed to parse token-key")]
     ParseTokenKey(#[source] serde_json::Error),
 
+    /// Custom token provider failed
+    #[error("failed to get token from provider: {0}")]
+    TokenProvider(#[source] BoxError),
+
     /// OAuth error
     #[cfg(feature = "oauth")]
     #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
     #[error("failed OAuth: {0}")]
     OAuth(#[source] OAuthError),
+
+    /// OIDC error
+    #[cfg(feature = "oidc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "oidc")))]
+    #[error("failed OIDC: {0}")]
+    Oidc(#[source] OidcError),
+
+    /// EKS token error
+    #[cfg(feature = "eks")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "eks")))]
+    #[error("failed to generate EKS token: {0}")]
+    Eks(#[source] EksError),
+
+    /// Azure error
+    #[cfg(feature = "azure")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "azure")))]
+    #[error("failed Azure auth: {0}")]
+    Azure(#[source] AzureError),
+
+    /// SPNEGO error
+    #[cfg(feature = "spnego")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "spnego")))]
+    #[error("failed SPNEGO auth: {0}")]
+    Spnego(#[source] SpnegoError),
 }
 
 #[derive(Debug, Clone)]
@@ -93,14 +172,138 @@ pub(crate) enum Auth {
 // - exec
 // - gcp: command based token source (exec)
 // - gcp: application credential based token source (requires `oauth` feature)
+// - oidc: id-token, refreshed with the refresh-token (refresh requires `oidc` feature)
+// - tokenFile: re-read periodically, since bound service account tokens are rotated by the kubelet
+// - azure: access-token, refreshed or requested from Azure AD (requires `azure` feature)
+// - exec running `aws eks get-token` or `aws-iam-authenticator`: tokens generated without the plugin (opt-in, requires `eks` feature)
+// - spnego: Kerberos `Negotiate` tokens computed for every request (requires `spnego` feature)
+// - custom `TokenProvider` set on the `Config`, taking precedence over the kubeconfig
 //
 // Note that the visibility must be `pub` for `impl Layer for AuthLayer`, but this is not exported from the crate.
 // It's not accessible from outside and not shown on docs.
 #[derive(Debug, Clone)]
 pub enum RefreshableToken {
-    Exec(Arc<Mutex<(String, DateTime<Utc>, AuthInfo)>>),
+    Exec(Arc<Mutex<ExecToken>>),
+    File(Arc<Mutex<token_file::TokenFile>>),
+    Provider(Arc<Mutex<provider::ProvidedToken>>),
     #[cfg(feature = "oauth")]
     GcpOauth(Arc<Mutex<oauth::Gcp>>),
+    #[cfg(feature = "oidc")]
+    Oidc(Arc<Mutex<oidc::Oidc>>),
+    #[cfg(feature = "eks")]
+    Eks(Arc<Mutex<eks::Eks>>),
+    #[cfg(feature = "azure")]
+    Azure(Arc<Mutex<azure::Azure>>),
+    #[cfg(feature = "spnego")]
+    Spnego(Arc<spnego::Spnego>),
+}
+
+// A token or client certificate from an exec plugin, or a token from a `gcp` auth-provider command
+#[derive(Debug)]
+pub struct ExecToken {
+    // Exec plugins that only issue client certificates do not return a token
+    token: Option<String>,
+    // Refreshed early, before the token or certificate expires. `None` if they do not expire
+    refresh_at: Option<DateTime<Utc>>,
+    info: AuthInfo,
+    policy: RefreshPolicy,
+    // Where client certificates issued by the exec plugin are passed to the connector
+    identity: Option<Arc<ExecIdentity>>,
+    // How long the exec plugin may run
+    timeout: Option<std::time::Duration>,
+}
+
+impl ExecToken {
+    pub(crate) fn new(
+        token: Option<String>,
+        expiry: DateTime<Utc>,
+        info: AuthInfo,
+        policy: RefreshPolicy,
+        identity: Option<Arc<ExecIdentity>>,
+    ) -> Self {
+        Self {
+            token,
+            refresh_at: Some(policy.refresh_at(expiry)),
+            info,
+            policy,
+            identity,
+            timeout: None,
+        }
+    }
+
+    // Credentials of an exec plugin or an auth-provider command, which runs when they are first needed
+    fn from_exec(
+        info: AuthInfo,
+        policy: RefreshPolicy,
+        identity: Option<Arc<ExecIdentity>>,
+        timeout: Option<std::time::Duration>,
+    ) -> Self {
+        Self {
+            token: None,
+            refresh_at: Some(Utc::now()),
+            info,
+            policy,
+            identity,
+            timeout,
+        }
+    }
+
+    // Runs the exec plugin or the auth-provider command again
+    async fn refresh(&mut self) -> Result<(), Error> {
+        if self.info.auth_provider.is_none() {
+            if let Some(exec) = &self.info.exec {
+                let (token, expiry, identity) = exec_credentials(exec, self.timeout).await?;
+                if let (Some(pem), Some(cell)) = (identity, &self.identity) {
+                    cell.set(pem);
+                }
+                self.token = token;
+                self.refresh_at = expiry.map(|expiry| self.policy.refresh_at(expiry));
+                return Ok(());
+            }
+        }
+
+        // The cached token of the auth-provider is the one being refreshed, so the command runs again
+        // instead of handing it back with a deadline that is never more than a few seconds away
+        match &self.info.auth_provider {
+            Some(provider) => {
+                let (token, expiry) = gcp_command_token(provider, self.timeout).await?;
+                self.token = Some(token);
+                self.refresh_at = expiry.map(|expiry| self.policy.refresh_at(expiry));
+                Ok(())
+            }
+            None => Err(Error::UnrefreshableTokenResponse),
+        }
+    }
+}
+
+/// The client certificate and private key in PEM issued by an exec plugin
+///
+/// Shared between the exec plugin token, which replaces them when the plugin issues a new certificate,
+/// and the connector of the `Client`, which uses the latest for new connections.
+#[derive(Debug, Default)]
+pub(crate) struct ExecIdentity {
+    // The identity, and how often it was replaced
+    current: std::sync::Mutex<(u64, Option<Vec<u8>>)>,
+}
+
+impl ExecIdentity {
+    /// The identity, and a generation that changes whenever it is replaced
+    pub(crate) fn get(&self) -> (u64, Option<Vec<u8>>) {
+        self.current
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    pub(crate) fn set(&self, pem: Vec<u8>) {
+        let mut current = self
+            .current
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if current.1.as_ref() != Some(&pem) {
+            *current = (current.0 + 1, Some(pem));
+        }
+    }
 }
 
 // For use with `AsyncFilterLayer` to add `Authorization` header with a refreshed token.
@@ -114,56 +317,109 @@ where
     fn check(&mut self, mut request: Self::Request) -> Self::Future {
         let refreshable = self.clone();
         Box::pin(async move {
-            refreshable.to_header().await.map_err(Into::into).map(|value| {
-                request.headers_mut().insert(AUTHORIZATION, value);
-                request
-            })
+            let uri = request.uri().clone();
+            refreshable
+                .to_header(&uri)
+                .await
+                .map_err(Into::into)
+                .map(|value| {
+                    if let Some(value) = value {
+                        request.headers_mut().insert(AUTHORIZATION, value);
+                    }
+                    request
+                })
         })
     }
 }
 
 impl RefreshableToken {
-    async fn to_header(&self) -> Result<HeaderValue, Error> {
+    // The `Authorization` header, if any, since exec plugins may only issue client certificates
+    #[cfg_attr(not(feature = "spnego"), allow(unused_variables))]
+    async fn to_header(&self, uri: &http::Uri) -> Result<Option<HeaderValue>, Error> {
         match self {
             RefreshableToken::Exec(data) => {
+                // Concurrent requests wait for the refresh while the lock is held, rather than all refreshing
                 let mut locked_data = data.lock().await;
                 // Add some wiggle room onto the current timestamp so we don't get any race
                 // conditions where the token expires while we are refreshing
-                if Utc::now() + Duration::seconds(60) >= locked_data.1 {
-                    match Auth::try_from(&locked_data.2)? {
-                        Auth::None | Auth::Basic(_, _) | Auth::Bearer(_) => {
-                            return Err(Error::UnrefreshableTokenResponse);
-                        }
-
-                        Auth::RefreshableToken(RefreshableToken::Exec(d)) => {
-                            let (new_token, new_expire, new_info) = Arc::try_unwrap(d)
-                                .expect("Unable to unwrap Arc, this is likely a programming error")
-                                .into_inner();
-                            locked_data.0 = new_token;
-                            locked_data.1 = new_expire;
-                            locked_data.2 = new_info;
-                        }
-
-                        // Unreachable because the token source does not change
-                        #[cfg(feature = "oauth")]
-                        Auth::RefreshableToken(RefreshableToken::GcpOauth(_)) => unreachable!(),
+                let expiring = locked_data.refresh_at.map_or(false, |refresh_at| {
+                    Utc::now() + Duration::seconds(60) >= refresh_at
+                });
+                if expiring {
+                    locked_data.refresh().await?;
+                }
+
+                match &locked_data.token {
+                    Some(token) => {
+                        let mut value = HeaderValue::try_from(format!("Bearer {}", token))
+                            .map_err(Error::InvalidBearerToken)?;
+                        value.set_sensitive(true);
+                        Ok(Some(value))
                     }
+                    None => Ok(None),
                 }
+            }
 
-                let mut value = HeaderValue::try_from(format!("Bearer {}", &locked_data.0))
+            RefreshableToken::File(data) => {
+                let mut token_file = data.lock().await;
+                let mut value = HeaderValue::try_from(format!("Bearer {}", token_file.token()))
                     .map_err(Error::InvalidBearerToken)?;
                 value.set_sensitive(true);
-                Ok(value)
+                Ok(Some(value))
+            }
+
+            RefreshableToken::Provider(data) => {
+                let token = data.lock().await.token().await.map_err(Error::TokenProvider)?;
+                let mut value =
+                    HeaderValue::try_from(format!("Bearer {}", token)).map_err(Error::InvalidBearerToken)?;
+                value.set_sensitive(true);
+                Ok(Some(value))
             }
 
             #[cfg(feature = "oauth")]
             RefreshableToken::GcpOauth(data) => {
-                let gcp_oauth = data.lock().await;
-                let token = (*gcp_oauth).token().await.map_err(Error::OAuth)?;
-                let mut value = HeaderValue::try_from(format!("Bearer {}", &token.access_token))
-                    .map_err(Error::InvalidBearerToken)?;
+                let token = data.lock().await.token().await.map_err(Error::OAuth)?;
+                let mut value =
+                    HeaderValue::try_from(format!("Bearer {}", token)).map_err(Error::InvalidBearerToken)?;
+                value.set_sensitive(true);
+                Ok(Some(value))
+            }
+
+            #[cfg(feature = "oidc")]
+            RefreshableToken::Oidc(data) => {
+                let mut oidc = data.lock().await;
+                let token = oidc.token().await.map_err(Error::Oidc)?;
+                let mut value =
+                    HeaderValue::try_from(format!("Bearer {}", token)).map_err(Error::InvalidBearerToken)?;
+                value.set_sensitive(true);
+                Ok(Some(value))
+            }
+
+            #[cfg(feature = "eks")]
+            RefreshableToken::Eks(data) => {
+                let token = data.lock().await.token().await.map_err(Error::Eks)?;
+                let mut value =
+                    HeaderValue::try_from(format!("Bearer {}", token)).map_err(Error::InvalidBearerToken)?;
+                value.set_sensitive(true);
+                Ok(Some(value))
+            }
+
+            #[cfg(feature = "azure")]
+            RefreshableToken::Azure(data) => {
+                let token = data.lock().await.token().await.map_err(Error::Azure)?;
+                let mut value =
+                    HeaderValue::try_from(format!("Bearer {}", token)).map_err(Error::InvalidBearerToken)?;
                 value.set_sensitive(true);
-                Ok(value)
+                Ok(Some(value))
+            }
+
+            #[cfg(feature = "spnego")]
+            RefreshableToken::Spnego(spnego) => {
+                let token = spnego.token(uri).await.map_err(Error::Spnego)?;
+                let mut value =
+                    HeaderValue::try_from(format!("Negotiate {}", token)).map_err(Error::InvalidBearerToken)?;
+                value.set_sensitive(true);
+                Ok(Some(value))
             }
         }
     }
@@ -176,87 +432,197 @@ impl TryFrom<&AuthInfo> for Auth {
     /// exec plugins as well as specified in
     /// https://kubernetes.io/docs/reference/access-authn-authz/authentication/#client-go-credential-plugins
     fn try_from(auth_info: &AuthInfo) -> Result<Self, Self::Error> {
+        Auth::with_identity(
+            auth_info,
+            RefreshPolicy::default(),
+            Some(crate::config::DEFAULT_EXEC_TIMEOUT),
+            false,
+            false,
+        )
+        .map(|(auth, _)| auth)
+    }
+}
+
+impl Auth {
+    /// Authenticate with the tokens of a custom `TokenProvider`
+    pub(crate) fn from_token_provider(provider: Arc<dyn TokenProvider>, policy: RefreshPolicy) -> Self {
+        Self::RefreshableToken(RefreshableToken::Provider(Arc::new(Mutex::new(
+            provider::ProvidedToken::new(provider, policy),
+        ))))
+    }
+
+    /// Like `Auth::try_from`, but also returns where the client certificate and private key
+    /// issued by an exec plugin are kept, for exec plugins.
+    ///
+    /// Exec plugins run when the credentials are first needed, and are killed after `exec_timeout`.
+    /// Expiring tokens and certificates from exec plugins are refreshed according to `policy`,
+    /// which replaces the certificate in the returned [`ExecIdentity`].
+    /// Tokens refreshed by auth providers are written back to their kubeconfig if `persist_tokens` is set.
+    /// EKS exec plugins are replaced by tokens generated from AWS credentials if `eks_tokens` is set.
+    pub(crate) fn with_identity(
+        auth_info: &AuthInfo,
+        policy: RefreshPolicy,
+        exec_timeout: Option<std::time::Duration>,
+        persist_tokens: bool,
+        eks_tokens: bool,
+    ) -> Result<(Self, Option<Arc<ExecIdentity>>), Error> {
         if let Some(provider) = &auth_info.auth_provider {
-            match token_from_provider(provider)? {
+            match token_from_provider(provider, persist_tokens)? {
                 ProviderToken::Oidc(token) => {
-                    return Ok(Self::Bearer(token));
+                    return Ok((Self::Bearer(token), None));
                 }
 
                 ProviderToken::GcpCommand(token, Some(expiry)) => {
-                    let mut info = auth_info.clone();
-                    let mut provider = provider.clone();
-                    provider.config.insert("access-token".into(), token.clone());
-                    provider.config.insert("expiry".into(), expiry.to_rfc3339());
-                    info.auth_provider = Some(provider);
-                    return Ok(Self::RefreshableToken(RefreshableToken::Exec(Arc::new(
-                        Mutex::new((token, expiry, info)),
-                    ))));
+                    let mut exec = ExecToken::new(Some(token), expiry, auth_info.clone(), policy, None);
+                    exec.timeout = exec_timeout;
+                    return Ok((
+                        Self::RefreshableToken(RefreshableToken::Exec(Arc::new(Mutex::new(exec)))),
+                        None,
+                    ));
+                }
+
+                ProviderToken::GcpCommandPending => {
+                    let exec = ExecToken::from_exec(auth_info.clone(), policy, None, exec_timeout);
+                    return Ok((
+                        Self::RefreshableToken(RefreshableToken::Exec(Arc::new(Mutex::new(exec)))),
+                        None,
+                    ));
                 }
 
                 ProviderToken::GcpCommand(token, None) => {
-                    return Ok(Self::Bearer(token));
+                    return Ok((Self::Bearer(token), None));
                 }
 
                 #[cfg(feature = "oauth")]
                 ProviderToken::GcpOauth(gcp) => {
-                    return Ok(Self::RefreshableToken(RefreshableToken::GcpOauth(Arc::new(
-                        Mutex::new(gcp),
-                    ))));
+                    return Ok((
+                        Self::RefreshableToken(RefreshableToken::GcpOauth(Arc::new(Mutex::new(gcp)))),
+                        None,
+                    ));
+                }
+
+                #[cfg(feature = "oidc")]
+                ProviderToken::OidcRefreshable(oidc) => {
+                    return Ok((
+                        Self::RefreshableToken(RefreshableToken::Oidc(Arc::new(Mutex::new(oidc)))),
+                        None,
+                    ));
+                }
+
+                #[cfg(feature = "azure")]
+                ProviderToken::Azure(azure) => {
+                    return Ok((
+                        Self::RefreshableToken(RefreshableToken::Azure(Arc::new(Mutex::new(azure)))),
+                        None,
+                    ));
+                }
+
+                #[cfg(feature = "spnego")]
+                ProviderToken::Spnego(spnego) => {
+                    return Ok((Self::RefreshableToken(RefreshableToken::Spnego(Arc::new(spnego))), None));
                 }
             }
         }
 
         if let (Some(u), Some(p)) = (&auth_info.username, &auth_info.password) {
-            return Ok(Self::Basic(u.to_owned(), p.to_owned()));
+            return Ok((Self::Basic(u.to_owned(), p.to_owned()), None));
         }
 
-        let (raw_token, expiration) = match &auth_info.token {
-            Some(token) => (Some(token.clone()), None),
-            None => {
-                if let Some(exec) = &auth_info.exec {
-                    let creds = auth_exec(exec)?;
-                    let status = creds.status.ok_or(Error::ExecPluginFailed)?;
-                    let expiration = status
-                        .expiration_timestamp
-                        .map(|ts| ts.parse())
-                        .transpose()
-                        .map_err(Error::MalformedTokenExpirationDate)?;
-                    (status.token, expiration)
-                } else if let Some(file) = &auth_info.token_file {
-                    let token = std::fs::read_to_string(&file)
-                        .map_err(|source| Error::ReadTokenFile(source, file.into()))?;
-                    (Some(token), None)
-                } else {
-                    (None, None)
-                }
+        // Generate EKS tokens directly rather than running the exec plugin on every refresh, when asked to
+        #[cfg(feature = "eks")]
+        {
+            let eks = auth_info.exec.as_ref().filter(|_| eks_tokens).and_then(eks::Eks::from_exec);
+            if let (None, Some(eks)) = (&auth_info.token, eks) {
+                return Ok((
+                    Self::RefreshableToken(RefreshableToken::Eks(Arc::new(Mutex::new(eks)))),
+                    None,
+                ));
             }
-        };
+        }
+        #[cfg(not(feature = "eks"))]
+        let _ = eks_tokens;
 
-        match (raw_token, expiration) {
-            (Some(token), None) => Ok(Self::Bearer(token)),
-            (Some(token), Some(expire)) => Ok(Self::RefreshableToken(RefreshableToken::Exec(Arc::new(
-                Mutex::new((token, expire, auth_info.clone())),
-            )))),
-            _ => Ok(Self::None),
+        match (&auth_info.token, &auth_info.exec, &auth_info.token_file) {
+            (Some(token), _, _) => Ok((Self::Bearer(token.clone()), None)),
+            (None, Some(_), _) => {
+                let identity = Arc::new(ExecIdentity::default());
+                let exec =
+                    ExecToken::from_exec(auth_info.clone(), policy, Some(identity.clone()), exec_timeout);
+                Ok((
+                    Self::RefreshableToken(RefreshableToken::Exec(Arc::new(Mutex::new(exec)))),
+                    Some(identity),
+                ))
+            }
+            (None, None, Some(file)) => {
+                let token_file = token_file::TokenFile::new(file)?;
+                Ok((
+                    Self::RefreshableToken(RefreshableToken::File(Arc::new(Mutex::new(token_file)))),
+                    None,
+                ))
+            }
+            (None, None, None) => Ok((Self::None, None)),
         }
     }
 }
 
+// Runs an exec plugin, returning the token, the expiry and the client certificate and key in PEM it issued
+async fn exec_credentials(
+    exec: &ExecConfig,
+    timeout: Option<std::time::Duration>,
+) -> Result<(Option<String>, Option<DateTime<Utc>>, Option<Vec<u8>>), Error> {
+    let creds = auth_exec(exec, timeout).await?;
+    let status = creds.status.ok_or(Error::ExecPluginFailed)?;
+    let expiration = status
+        .expiration_timestamp
+        .map(|ts| ts.parse())
+        .transpose()
+        .map_err(Error::MalformedTokenExpirationDate)?;
+    let identity = match (status.client_certificate_data, status.client_key_data) {
+        (Some(cert), Some(key)) => Some(format!("{}\n{}", cert, key).into_bytes()),
+        (None, None) => None,
+        _ => return Err(Error::AuthExecIncompleteClientCertificate),
+    };
+    Ok((status.token, expiration, identity))
+}
+
 // We need to differentiate providers because the keys/formats to store token expiration differs.
 enum ProviderToken {
     Oidc(String),
+    // "id-token", refreshed with "refresh-token" from "idp-issuer-url"
+    #[cfg(feature = "oidc")]
+    OidcRefreshable(oidc::Oidc),
     // "access-token", "expiry" (RFC3339)
     GcpCommand(String, Option<DateTime<Utc>>),
+    // "cmd-path", which runs when the token is first needed
+    GcpCommandPending,
     #[cfg(feature = "oauth")]
     GcpOauth(oauth::Gcp),
-    // "access-token", "expires-on" (timestamp)
-    // Azure(String, Option<DateTime<Utc>>),
+    // "access-token", "expires-on" (timestamp), refreshed with "refresh-token"
+    #[cfg(feature = "azure")]
+    Azure(azure::Azure),
+    // Computed for every request from the Kerberos credential cache
+    #[cfg(feature = "spnego")]
+    Spnego(spnego::Spnego),
 }
 
-fn token_from_provider(provider: &AuthProviderConfig) -> Result<ProviderToken, Error> {
+fn token_from_provider(provider: &AuthProviderConfig, persist_tokens: bool) -> Result<ProviderToken, Error> {
     match provider.name.as_ref() {
-        "oidc" => token_from_oidc_provider(provider),
+        "oidc" => token_from_oidc_provider(provider, persist_tokens),
         "gcp" => token_from_gcp_provider(provider),
+        #[cfg(feature = "azure")]
+        "azure" => Ok(ProviderToken::Azure(
+            azure::Azure::from_provider(provider).map_err(Error::Azure)?,
+        )),
+        #[cfg(not(feature = "azure"))]
+        "azure" => Err(Error::AuthExec(
+            "Enable azure feature to use the azure Authentication provider".into(),
+        )),
+        #[cfg(feature = "spnego")]
+        "spnego" => Ok(ProviderToken::Spnego(spnego::Spnego::from_provider(provider))),
+        #[cfg(not(feature = "spnego"))]
+        "spnego" => Err(Error::AuthExec(
+            "Enable spnego feature to use the spnego Authentication provider".into(),
+        )),
         _ => Err(Error::AuthExec(format!(
             "Authentication with provider {:} not supported",
             provider.name
@@ -264,7 +630,19 @@ fn token_from_provider(provider: &AuthProviderConfig) -> Result<ProviderToken, E
     }
 }
 
-fn token_from_oidc_provider(provider: &AuthProviderConfig) -> Result<ProviderToken, Error> {
+#[cfg_attr(not(feature = "oidc"), allow(unused_variables))]
+fn token_from_oidc_provider(
+    provider: &AuthProviderConfig,
+    persist_tokens: bool,
+) -> Result<ProviderToken, Error> {
+    #[cfg(feature = "oidc")]
+    {
+        if provider.config.contains_key("refresh-token") {
+            let oidc = oidc::Oidc::from_provider(provider, persist_tokens).map_err(Error::Oidc)?;
+            return Ok(ProviderToken::OidcRefreshable(oidc));
+        }
+    }
+
     match provider.config.get("id-token") {
         Some(id_token) => Ok(ProviderToken::Oidc(id_token.clone())),
         None => Err(Error::AuthExec(
@@ -278,6 +656,17 @@ fn token_from_gcp_provider(provider: &AuthProviderConfig) -> Result<ProviderToke
         return Ok(ProviderToken::GcpCommand(id_token.clone(), None));
     }
 
+    // Google Application Credentials-based token source, which also tracks the expiry of the cached access token.
+    // Without `cmd-path`, refreshing the cached token can only go through this source.
+    #[cfg(feature = "oauth")]
+    {
+        if !provider.config.contains_key("cmd-path") {
+            return Ok(ProviderToken::GcpOauth(
+                oauth::Gcp::from_provider(provider).map_err(Error::OAuth)?,
+            ));
+        }
+    }
+
     // Return cached access token if it's still valid
     if let Some(access_token) = provider.config.get("access-token") {
         if let Some(expiry) = provider.config.get("expiry") {
@@ -291,57 +680,66 @@ fn token_from_gcp_provider(provider: &AuthProviderConfig) -> Result<ProviderToke
     }
 
     // Command-based token source
-    if let Some(cmd) = provider.config.get("cmd-path") {
-        let params = provider.config.get("cmd-args").cloned().unwrap_or_default();
-
-        // TODO splitting args by space is not safe
-        let output = Command::new(cmd)
-            .args(params.trim().split(' '))
-            .output()
-            .map_err(|e| Error::AuthExec(format!("Executing {:} failed: {:?}", cmd, e)))?;
-
-        if !output.status.success() {
-            return Err(Error::AuthExecRun {
-                cmd: format!("{} {}", cmd, params),
-                status: output.status,
-                out: output,
-            });
-        }
+    if provider.config.contains_key("cmd-path") {
+        return Ok(ProviderToken::GcpCommandPending);
+    }
 
-        if let Some(field) = provider.config.get("token-key") {
-            let json_output: serde_json::Value =
-                serde_json::from_slice(&output.stdout).map_err(Error::ParseTokenKey)?;
-            let token = extract_value(&json_output, field)?;
-            if let Some(field) = provider.config.get("expiry-key") {
-                let expiry = extract_value(&json_output, field)?;
-                let expiry = expiry
-                    .parse::<DateTime<Utc>>()
-                    .map_err(Error::MalformedTokenExpirationDate)?;
-                return Ok(ProviderToken::GcpCommand(token, Some(expiry)));
-            } else {
-                return Ok(ProviderToken::GcpCommand(token, None));
-            }
-        } else {
-            let token = std::str::from_utf8(&output.stdout)
-                .map_err(|e| Error::AuthExec(format!("Result is not a string {:?} ", e)))?
-                .to_owned();
-            return Ok(ProviderToken::GcpCommand(token, None));
+    Err(Error::AuthExec(
+        "Enable oauth feature to use Google Application Credentials-based token source".into(),
+    ))
+}
+
+// Runs the `cmd-path` of a `gcp` auth-provider, killing it once `timeout` elapses,
+// and returns the token with its expiry
+async fn gcp_command_token(
+    provider: &AuthProviderConfig,
+    timeout: Option<std::time::Duration>,
+) -> Result<(String, Option<DateTime<Utc>>), Error> {
+    let cmd = match provider.config.get("cmd-path") {
+        Some(cmd) => cmd,
+        None => {
+            return Err(Error::AuthExec(
+                "Enable oauth feature to use Google Application Credentials-based token source".into(),
+            ))
         }
-    }
+    };
+    let params = provider.config.get("cmd-args").cloned().unwrap_or_default();
 
-    // Google Application Credentials-based token source
-    #[cfg(feature = "oauth")]
-    {
-        Ok(ProviderToken::GcpOauth(
-            oauth::Gcp::default_credentials_with_scopes(provider.config.get("scopes"))
-                .map_err(Error::OAuth)?,
-        ))
+    // TODO splitting args by space is not safe
+    let mut command = tokio::process::Command::new(cmd);
+    command
+        .args(params.trim().split(' '))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let output = run_exec(command, timeout).await?;
+
+    if !output.status.success() {
+        return Err(Error::AuthExecRun {
+            cmd: format!("{} {}", cmd, params),
+            status: output.status,
+            out: output,
+        });
     }
-    #[cfg(not(feature = "oauth"))]
-    {
-        Err(Error::AuthExec(
-            "Enable oauth feature to use Google Application Credentials-based token source".into(),
-        ))
+
+    if let Some(field) = provider.config.get("token-key") {
+        let json_output: serde_json::Value =
+            serde_json::from_slice(&output.stdout).map_err(Error::ParseTokenKey)?;
+        let token = extract_value(&json_output, field)?;
+        if let Some(field) = provider.config.get("expiry-key") {
+            let expiry = extract_value(&json_output, field)?;
+            let expiry = expiry
+                .parse::<DateTime<Utc>>()
+                .map_err(Error::MalformedTokenExpirationDate)?;
+            Ok((token, Some(expiry)))
+        } else {
+            Ok((token, None))
+        }
+    } else {
+        let token = std::str::from_utf8(&output.stdout)
+            .map_err(|e| Error::AuthExec(format!("Result is not a string {:?} ", e)))?
+            .to_owned();
+        Ok((token, None))
     }
 }
 
@@ -379,7 +777,12 @@ pub struct ExecCredential {
 /// ExecCredenitalSpec holds request and runtime specific information provided
 /// by transport.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ExecCredentialSpec {}
+pub struct ExecCredentialSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<ExecAuthCluster>,
+    #[serde(default)]
+    pub interactive: bool,
+}
 
 /// ExecCredentialStatus holds credentials for the transport to use.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -393,8 +796,14 @@ pub struct ExecCredentialStatus {
     pub client_key_data: Option<String>,
 }
 
-fn auth_exec(auth: &ExecConfig) -> Result<ExecCredential, Error> {
-    let mut cmd = Command::new(&auth.command);
+// Environment variable used to pass `ExecCredential` with the request information to exec plugins.
+const KUBERNETES_EXEC_INFO_ENV: &str = "KUBERNETES_EXEC_INFO";
+// `apiVersion` used for `KUBERNETES_EXEC_INFO` when the exec config does not specify one.
+const DEFAULT_EXEC_API_VERSION: &str = "client.authentication.k8s.io/v1beta1";
+// `apiVersion` of exec plugins that must specify `interactiveMode`.
+const EXEC_API_VERSION_V1: &str = "client.authentication.k8s.io/v1";
+async fn auth_exec(auth: &ExecConfig, timeout: Option<std::time::Duration>) -> Result<ExecCredential, Error> {
+    let mut cmd = tokio::process::Command::new(&auth.command);
     if let Some(args) = &auth.args {
         cmd.args(args);
     }
@@ -407,10 +816,49 @@ fn auth_exec(auth: &ExecConfig) -> Result<ExecCredential, Error> {
             });
         cmd.envs(envs);
     }
-    let out = cmd.output().map_err(Error::AuthExecStart)?;
+
+    let api_version = auth
+        .api_version
+        .clone()
+        .unwrap_or_else(|| DEFAULT_EXEC_API_VERSION.into());
+    let interactive_mode = match auth.interactive_mode {
+        Some(mode) => mode,
+        None if api_version == EXEC_API_VERSION_V1 => {
+            return Err(Error::AuthExecMissingInteractiveMode(api_version));
+        }
+        None => ExecInteractiveMode::IfAvailable,
+    };
+    let interactive = interactive_mode != ExecInteractiveMode::Never && atty::is(atty::Stream::Stdin);
+    if interactive_mode == ExecInteractiveMode::Always && !interactive {
+        return Err(Error::AuthExecNotInteractive);
+    }
+    if interactive {
+        // Let the plugin prompt the user, stdout is still captured for the credentials
+        cmd.stdin(Stdio::inherit()).stderr(Stdio::inherit());
+    } else {
+        cmd.stdin(Stdio::null()).stderr(Stdio::piped());
+    }
+    cmd.stdout(Stdio::piped());
+
+    // Pass the request information (and the cluster information if requested) to the plugin.
+    // See https://kubernetes.io/docs/reference/access-authn-authz/authentication/#input-and-output-formats
+    let exec_info = ExecCredential {
+        kind: Some("ExecCredential".into()),
+        api_version: Some(api_version),
+        spec: Some(ExecCredentialSpec {
+            cluster: auth.cluster.clone().filter(|_| auth.provide_cluster_info),
+            interactive,
+        }),
+        status: None,
+    };
+    let exec_info = serde_json::to_string(&exec_info).map_err(Error::AuthExecSerialize)?;
+    cmd.env(KUBERNETES_EXEC_INFO_ENV, exec_info);
+
+    let cmd_debug = format!("{:?}", cmd.as_std());
+    let out = run_exec(cmd, timeout).await?;
     if !out.status.success() {
         return Err(Error::AuthExecRun {
-            cmd: format!("{:?}", cmd),
+            cmd: cmd_debug,
             status: out.status,
             out,
         });
@@ -420,9 +868,56 @@ fn auth_exec(auth: &ExecConfig) -> Result<ExecCredential, Error> {
     Ok(creds)
 }
 
+// Runs an exec plugin, killing it once the timeout elapses or when the future is dropped
+async fn run_exec(
+    mut cmd: tokio::process::Command,
+    timeout: Option<std::time::Duration>,
+) -> Result<Output, Error> {
+    let mut child = cmd.kill_on_drop(true).spawn().map_err(Error::AuthExecStart)?;
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    // The pipes are read while waiting, so that the plugin never blocks on a full pipe
+    let run = async {
+        let (status, _, _) = futures::join!(
+            child.wait(),
+            read_pipe(&mut stdout, &mut out),
+            read_pipe(&mut stderr, &mut err)
+        );
+        status
+    };
+    let status = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, run).await {
+            Ok(status) => status,
+            Err(_) => {
+                let _ = child.kill().await;
+                return Err(Error::AuthExecTimeout {
+                    cmd: format!("{:?}", cmd.as_std()),
+                    timeout,
+                    stderr: String::from_utf8_lossy(&err).trim().to_owned(),
+                });
+            }
+        },
+        None => run.await,
+    }
+    .map_err(Error::AuthExecStart)?;
+    Ok(Output {
+        status,
+        stdout: out,
+        stderr: err,
+    })
+}
+
+async fn read_pipe<R: AsyncRead + Unpin>(pipe: &mut Option<R>, buf: &mut Vec<u8>) {
+    if let Some(pipe) = pipe {
+        let _ = pipe.read_to_end(buf).await;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::config::Kubeconfig;
+    use std::time::Instant;
 
     use super::*;
     #[tokio::test]
@@ -462,13 +957,224 @@ mod test {
         let auth_info = &config.auth_infos[0].auth_info;
         match Auth::try_from(auth_info).unwrap() {
             Auth::RefreshableToken(RefreshableToken::Exec(refreshable)) => {
-                let (token, _expire, info) = Arc::try_unwrap(refreshable).unwrap().into_inner();
-                assert_eq!(token, "my_token".to_owned());
-                let config = info.auth_provider.unwrap().config;
-                assert_eq!(config.get("access-token"), Some(&"my_token".to_owned()));
+                // The command runs when the token is first needed
+                let mut token = refreshable.lock().await;
+                assert_eq!(token.token, None);
+                token.refresh().await?;
+                assert_eq!(token.token.as_deref(), Some("my_token"));
+                assert!(token.refresh_at.is_some());
             }
             _ => unreachable!(),
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn gcp_command_refresh_replaces_the_cached_token() {
+        let expiry = (Utc::now() + Duration::hours(2)).to_rfc3339();
+        let auth_info: AuthInfo = serde_yaml::from_str(&format!(
+            r#"
+            auth-provider:
+              config:
+                access-token: cached
+                expiry: {cached_expiry}
+                cmd-args: '{{"access_token": "fresh", "token_expiry": "{expiry}"}}'
+                cmd-path: echo
+                expiry-key: '{{.token_expiry}}'
+                token-key: '{{.access_token}}'
+              name: gcp
+            "#,
+            cached_expiry = (Utc::now() + Duration::minutes(10)).to_rfc3339(),
+            expiry = expiry
+        ))
+        .unwrap();
+        let refreshable = match Auth::try_from(&auth_info).unwrap() {
+            Auth::RefreshableToken(RefreshableToken::Exec(refreshable)) => refreshable,
+            _ => unreachable!(),
+        };
+        let mut token = refreshable.lock().await;
+        assert_eq!(token.token.as_deref(), Some("cached"));
+
+        token.refresh().await.unwrap();
+        assert_eq!(token.token.as_deref(), Some("fresh"));
+        // The deadline is kept until the next refresh, rather than recomputed on every request
+        assert!(token.refresh_at.unwrap() > Utc::now() + Duration::hours(1));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn gcp_command_timeout_and_stderr() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The arguments of gcp commands are split by spaces, so the shell commands go in a script
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("gcloud");
+        let helper = "#!/bin/sh\necho 'reauthentication required' >&2\nsleep 10\n";
+        std::fs::write(&script, helper).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let auth_info: AuthInfo = serde_yaml::from_str(&format!(
+            r#"
+            auth-provider:
+              config:
+                cmd-path: {}
+              name: gcp
+            "#,
+            script.display()
+        ))
+        .unwrap();
+        let timeout = Some(std::time::Duration::from_millis(200));
+        let (auth, _) =
+            Auth::with_identity(&auth_info, RefreshPolicy::default(), timeout, false, false).unwrap();
+        let refreshable = match auth {
+            Auth::RefreshableToken(refreshable) => refreshable,
+            _ => panic!("gcp commands must run when the token is needed"),
+        };
+        let started = Instant::now();
+        match refreshable.to_header(&http::Uri::from_static("/")).await {
+            Err(Error::AuthExecTimeout { stderr, .. }) => assert_eq!(stderr, "reauthentication required"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn exec_v1_requires_interactive_mode() {
+        let exec: ExecConfig = serde_yaml::from_str(
+            r#"
+            apiVersion: client.authentication.k8s.io/v1
+            command: echo
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            auth_exec(&exec, None).await,
+            Err(Error::AuthExecMissingInteractiveMode(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn exec_auth_client_certificate() {
+        let creds = r#"{"apiVersion": "client.authentication.k8s.io/v1", "kind": "ExecCredential", "status": {"clientCertificateData": "CERT", "clientKeyData": "KEY"}}"#;
+        let auth_info = AuthInfo {
+            exec: Some(ExecConfig {
+                api_version: Some("client.authentication.k8s.io/v1".into()),
+                command: "echo".into(),
+                args: Some(vec![creds.into()]),
+                env: None,
+                provide_cluster_info: false,
+                interactive_mode: Some(ExecInteractiveMode::Never),
+                cluster: None,
+            }),
+            ..AuthInfo::default()
+        };
+        let (auth, identity) =
+            Auth::with_identity(&auth_info, RefreshPolicy::default(), None, false, false).unwrap();
+        let identity = identity.unwrap();
+        // The plugin only runs for the first request
+        assert_eq!(identity.get(), (0, None));
+        let refreshable = match auth {
+            Auth::RefreshableToken(refreshable) => refreshable,
+            _ => panic!("exec plugins must run when the credentials are needed"),
+        };
+        let header = refreshable.to_header(&http::Uri::from_static("/")).await.unwrap();
+        assert!(header.is_none());
+        assert_eq!(identity.get(), (1, Some(b"CERT\nKEY".to_vec())));
+    }
+
+    #[cfg(feature = "eks")]
+    #[test]
+    fn eks_tokens_replace_exec_plugins_when_asked() {
+        let args = ["eks", "get-token", "--cluster-name", "prod"];
+        let auth_info = AuthInfo {
+            exec: Some(ExecConfig {
+                api_version: Some("client.authentication.k8s.io/v1".into()),
+                command: "aws".into(),
+                args: Some(args.iter().map(|arg| arg.to_string()).collect()),
+                env: None,
+                provide_cluster_info: false,
+                interactive_mode: Some(ExecInteractiveMode::Never),
+                cluster: None,
+            }),
+            ..AuthInfo::default()
+        };
+        let auth = |eks_tokens| {
+            Auth::with_identity(&auth_info, RefreshPolicy::default(), None, false, eks_tokens)
+                .unwrap()
+                .0
+        };
+        assert!(matches!(
+            auth(false),
+            Auth::RefreshableToken(RefreshableToken::Exec(_))
+        ));
+        assert!(matches!(
+            auth(true),
+            Auth::RefreshableToken(RefreshableToken::Eks(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_auth_refreshes_client_certificate() {
+        // Expires within the wiggle room, so every request runs the plugin again, which issues a new certificate
+        let expiry = (Utc::now() + Duration::seconds(30)).to_rfc3339();
+        let creds = format!(
+            r#"{{"apiVersion": "client.authentication.k8s.io/v1", "kind": "ExecCredential", "status": {{"clientCertificateData": "CERT-'$$'", "clientKeyData": "KEY", "expirationTimestamp": "{}"}}}}"#,
+            expiry
+        );
+        let auth_info = AuthInfo {
+            exec: Some(ExecConfig {
+                api_version: Some("client.authentication.k8s.io/v1".into()),
+                command: "sh".into(),
+                args: Some(vec!["-c".into(), format!("echo '{}'", creds)]),
+                env: None,
+                provide_cluster_info: false,
+                interactive_mode: Some(ExecInteractiveMode::Never),
+                cluster: None,
+            }),
+            ..AuthInfo::default()
+        };
+        let (auth, identity) =
+            Auth::with_identity(&auth_info, RefreshPolicy::default(), None, false, false).unwrap();
+        let identity = identity.unwrap();
+        let refreshable = match auth {
+            Auth::RefreshableToken(refreshable) => refreshable,
+            _ => panic!("exec plugins must run when the credentials are needed"),
+        };
+        let uri = http::Uri::from_static("/");
+        assert!(refreshable.to_header(&uri).await.unwrap().is_none());
+        let (generation, first) = identity.get();
+        assert_eq!(generation, 1);
+        let first = first.unwrap();
+        assert!(first.starts_with(b"CERT-"));
+
+        assert!(refreshable.to_header(&uri).await.unwrap().is_none());
+        let (generation, second) = identity.get();
+        assert_eq!(generation, 2);
+        assert_ne!(second, Some(first));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_auth_timeout_and_stderr() {
+        let mut exec: ExecConfig = serde_yaml::from_str(
+            r#"
+            apiVersion: client.authentication.k8s.io/v1
+            command: sh
+            args: ["-c", "echo 'waiting for login' >&2; sleep 10"]
+            interactiveMode: Never
+            "#,
+        )
+        .unwrap();
+        let timeout = Some(std::time::Duration::from_millis(200));
+        let started = Instant::now();
+        match auth_exec(&exec, timeout).await {
+            Err(Error::AuthExecTimeout { stderr, .. }) => assert_eq!(stderr, "waiting for login"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        exec.args = Some(vec!["-c".into(), "echo 'token expired' >&2; exit 1".into()]);
+        let err = auth_exec(&exec, timeout).await.unwrap_err();
+        assert!(err.to_string().ends_with(": token expired"), "{}", err);
+    }
 }
@@ -1,9 +1,12 @@
+use chrono::{DateTime, Duration, Utc};
 use tame_oauth::{
     gcp::{TokenOrRequest, TokenProvider, TokenProviderWrapper},
     Token,
 };
 use thiserror::Error;
 
+use crate::config::AuthProviderConfig;
+
 #[derive(Error, Debug)]
 /// Possible errors when requesting token with OAuth
 pub enum Error {
@@ -43,6 +46,10 @@ pub enum Error {
     #[error("failed to parse token: {0}")]
     ParseToken(#[source] serde_json::Error),
 
+    /// Malformed expiry of the access-token cached in the auth-provider config
+    #[error("malformed access-token expiry: {0}")]
+    MalformedExpiry(#[source] chrono::ParseError),
+
     /// Failed to concatenate the buffers from response body
     #[error("failed to concatenate the buffers from response body: {0}")]
     ConcatBuffers(#[source] hyper::Error),
@@ -62,9 +69,17 @@ pub enum Error {
     CreateOpensslHttpsConnector(#[source] openssl::error::ErrorStack),
 }
 
+// Refresh tokens a little before they expire, so they don't expire in flight
+const EXPIRY_SLACK_SECONDS: i64 = 60;
+
 pub struct Gcp {
-    provider: TokenProviderWrapper,
+    // Created on the first refresh, so that a valid cached token does not require default credentials
+    provider: Option<TokenProviderWrapper>,
     scopes: Vec<String>,
+    // "access-token" and "expiry" cached in the auth-provider config by a previous client
+    cached: Option<(String, DateTime<Utc>)>,
+    // The last token issued by the provider
+    token: Option<Token>,
 }
 
 impl std::fmt::Debug for Gcp {
@@ -72,34 +87,77 @@ impl std::fmt::Debug for Gcp {
         f.debug_struct("Gcp")
             .field("provider", &"{}".to_owned())
             .field("scopes", &self.scopes)
+            .field("cached_expiry", &self.cached.as_ref().map(|(_, expiry)| expiry))
             .finish()
     }
 }
 
 impl Gcp {
-    // Initialize `TokenProvider` following the "Google Default Credentials" flow.
-    // `tame-oauth` supports the same default credentials flow as the Go oauth2:
-    // - `GOOGLE_APPLICATION_CREDENTIALS` environmment variable
-    // - gcloud's application default credentials
-    // - local metadata server if running on GCP
-    pub(crate) fn default_credentials_with_scopes(scopes: Option<&String>) -> Result<Self, Error> {
+    // Token source following the "Google Default Credentials" flow, with the scopes and the cached
+    // token from the `gcp` auth-provider config.
+    pub(crate) fn from_provider(provider: &AuthProviderConfig) -> Result<Self, Error> {
         const DEFAULT_SCOPES: &str =
             "https://www.googleapis.com/auth/cloud-platform,https://www.googleapis.com/auth/userinfo.email";
 
-        let provider = TokenProviderWrapper::get_default_provider()
-            .map_err(Error::InvalidDefaultProviderConfig)?
-            .ok_or(Error::NoDefaultProvider)?;
-        let scopes = scopes
-            .map(String::to_owned)
-            .unwrap_or_else(|| DEFAULT_SCOPES.to_owned())
+        let scopes = provider
+            .config
+            .get("scopes")
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_SCOPES)
             .split(',')
             .map(str::to_owned)
             .collect::<Vec<_>>();
-        Ok(Self { provider, scopes })
+        let cached = match (provider.config.get("access-token"), provider.config.get("expiry")) {
+            (Some(token), Some(expiry)) => Some((
+                token.clone(),
+                expiry.parse::<DateTime<Utc>>().map_err(Error::MalformedExpiry)?,
+            )),
+            _ => None,
+        };
+        Ok(Self {
+            provider: None,
+            scopes,
+            cached,
+            token: None,
+        })
+    }
+
+    /// Returns the access token, requesting a new one when the current one is about to expire
+    pub async fn token(&mut self) -> Result<String, Error> {
+        if let Some((token, expiry)) = &self.cached {
+            if Utc::now() + Duration::seconds(EXPIRY_SLACK_SECONDS) < *expiry {
+                return Ok(token.clone());
+            }
+            self.cached = None;
+        }
+        if let Some(token) = self.token.as_ref().filter(|token| !token.has_expired()) {
+            return Ok(token.access_token.clone());
+        }
+        let token = self.request_token().await?;
+        let access_token = token.access_token.clone();
+        self.token = Some(token);
+        Ok(access_token)
+    }
+
+    // Initialize `TokenProvider` following the "Google Default Credentials" flow.
+    // `tame-oauth` supports the same default credentials flow as the Go oauth2:
+    // - `GOOGLE_APPLICATION_CREDENTIALS` environmment variable
+    // - gcloud's application default credentials
+    // - local metadata server if running on GCP
+    fn provider(&mut self) -> Result<&TokenProviderWrapper, Error> {
+        if self.provider.is_none() {
+            let provider = TokenProviderWrapper::get_default_provider()
+                .map_err(Error::InvalidDefaultProviderConfig)?
+                .ok_or(Error::NoDefaultProvider)?;
+            self.provider = Some(provider);
+        }
+        Ok(self.provider.as_ref().expect("provider was just initialized"))
     }
 
-    pub async fn token(&self) -> Result<Token, Error> {
-        match self.provider.get_token(&self.scopes) {
+    async fn request_token(&mut self) -> Result<Token, Error> {
+        let scopes = self.scopes.clone();
+        let provider = self.provider()?;
+        match provider.get_token(&scopes) {
             Ok(TokenOrRequest::Request {
                 request, scope_hash, ..
             }) => {
@@ -136,7 +194,7 @@ impl Gcp {
                 let (parts, body) = res.into_parts();
                 let bytes = hyper::body::to_bytes(body).await.map_err(Error::ConcatBuffers)?;
                 let response = http::Response::from_parts(parts, bytes.to_vec());
-                match self.provider.parse_token_response(scope_hash, response) {
+                match provider.parse_token_response(scope_hash, response) {
                     Ok(token) => Ok(token),
 
                     Err(err) => Err(match err {
@@ -160,3 +218,33 @@ impl Gcp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_config(access_token: &str, expiry: DateTime<Utc>) -> AuthProviderConfig {
+        AuthProviderConfig {
+            name: "gcp".into(),
+            config: [
+                ("access-token".to_owned(), access_token.to_owned()),
+                ("expiry".to_owned(), expiry.to_rfc3339()),
+            ]
+            .into_iter()
+            .collect(),
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn uses_cached_token_until_expiry() {
+        let mut gcp = Gcp::from_provider(&provider_config("cached", Utc::now() + Duration::hours(1))).unwrap();
+        assert_eq!(gcp.token().await.unwrap(), "cached");
+        // The provider is only created once the cached token needs to be refreshed
+        assert!(gcp.provider.is_none());
+
+        let gcp = Gcp::from_provider(&provider_config("cached", Utc::now() + Duration::seconds(30))).unwrap();
+        let (_, expiry) = gcp.cached.as_ref().unwrap();
+        assert!(Utc::now() + Duration::seconds(EXPIRY_SLACK_SECONDS) >= *expiry);
+    }
+}
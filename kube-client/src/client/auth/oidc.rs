@@ -0,0 +1,327 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use http::{header::CONTENT_TYPE, Method, Request};
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::issuer::{send, SendError};
+use crate::config::{self, AuthProviderConfig};
+
+#[derive(Error, Debug)]
+/// Possible errors when refreshing OIDC tokens
+pub enum Error {
+    /// The id-token is expired and there is no refresh-token to refresh it
+    #[error("id-token is expired and refresh-token is not set")]
+    MissingRefreshToken,
+
+    /// A key required to refresh the id-token is missing from the auth-provider config
+    #[error("auth-provider config is missing {0}")]
+    MissingConfig(&'static str),
+
+    /// Failed to request the issuer
+    #[error("failed to request issuer: {0}")]
+    RequestIssuer(#[source] hyper::Error),
+
+    /// The issuer responded with an error
+    #[error("issuer responded with {0}: {1}")]
+    IssuerStatus(http::StatusCode, String),
+
+    /// Failed to parse the response of the issuer
+    #[error("failed to parse issuer response: {0}")]
+    ParseResponse(#[source] serde_json::Error),
+
+    /// The token response of the issuer did not contain an id-token
+    #[error("token response did not contain an id_token")]
+    MissingIdToken,
+
+    /// Failed to build a request
+    #[error("failed to build request: {0}")]
+    BuildRequest(#[source] http::Error),
+
+    /// Failed to concatenate the buffers from response body
+    #[error("failed to concatenate the buffers from response body: {0}")]
+    ConcatBuffers(#[source] hyper::Error),
+
+    /// Failed to decode the base64 `idp-certificate-authority-data`
+    #[error("failed to decode idp-certificate-authority-data: {0}")]
+    DecodeCertificateAuthority(#[source] base64::DecodeError),
+
+    /// Failed to read the `idp-certificate-authority` file
+    #[error("failed to read idp-certificate-authority {1:?}: {0}")]
+    ReadCertificateAuthority(#[source] std::io::Error, PathBuf),
+
+    /// Failed to parse the PEM-encoded certificates of the issuer
+    #[error("failed to parse idp-certificate-authority: {0}")]
+    ParseCertificateAuthority(#[source] pem::PemError),
+
+    /// Failed to create a TLS connector trusting the certificates of the issuer
+    #[error("failed to create TLS connector: {0}")]
+    CreateTlsConnector(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Failed to create OpenSSL HTTPS connector
+    #[cfg(feature = "openssl-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "openssl-tls")))]
+    #[error("failed to create OpenSSL HTTPS connector: {0}")]
+    CreateOpensslHttpsConnector(#[source] openssl::error::ErrorStack),
+}
+
+// Refresh tokens a little before they expire, so they don't expire in flight
+const EXPIRY_SLACK_SECONDS: i64 = 10;
+
+/// OIDC id-token from the `oidc` auth-provider, refreshed with the refresh-token when it expires
+///
+/// See https://kubernetes.io/docs/reference/access-authn-authz/authentication/#using-kubectl
+pub struct Oidc {
+    id_token: Option<String>,
+    refresh_token: Option<String>,
+    issuer_url: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    // DER-encoded certificates to verify the issuer with, instead of the system roots
+    certificate_authority: Option<Vec<Vec<u8>>>,
+    // Discovered from the issuer on the first refresh
+    token_endpoint: Option<String>,
+    // The kubeconfig file that refreshed tokens are written back to, if enabled
+    kubeconfig: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for Oidc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Oidc")
+            .field("issuer_url", &self.issuer_url)
+            .field("client_id", &self.client_id)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    exp: Option<i64>,
+}
+
+impl Oidc {
+    pub(crate) fn from_provider(provider: &AuthProviderConfig, persist_tokens: bool) -> Result<Self, Error> {
+        let get = |key: &str| provider.config.get(key).filter(|v| !v.is_empty()).cloned();
+        let certificate_authority = if let Some(data) = get("idp-certificate-authority-data") {
+            Some(base64::decode(&data).map_err(Error::DecodeCertificateAuthority)?)
+        } else if let Some(path) = get("idp-certificate-authority") {
+            Some(std::fs::read(&path).map_err(|err| Error::ReadCertificateAuthority(err, path.into()))?)
+        } else {
+            None
+        };
+        let certificate_authority = match certificate_authority {
+            Some(pem) => {
+                let certs = pem::parse_many(pem).map_err(Error::ParseCertificateAuthority)?;
+                let ders = certs.into_iter().filter(|cert| cert.tag == "CERTIFICATE");
+                Some(ders.map(|cert| cert.contents).collect())
+            }
+            None => None,
+        };
+        Ok(Self {
+            id_token: get("id-token"),
+            refresh_token: get("refresh-token"),
+            issuer_url: get("idp-issuer-url"),
+            client_id: get("client-id"),
+            client_secret: get("client-secret"),
+            certificate_authority,
+            token_endpoint: None,
+            kubeconfig: provider.source.clone().filter(|_| persist_tokens),
+        })
+    }
+
+    /// Returns the id-token, refreshing it first if it has expired
+    pub async fn token(&mut self) -> Result<String, Error> {
+        if let Some(id_token) = self.id_token.as_ref().filter(|token| !is_expired(token)) {
+            return Ok(id_token.clone());
+        }
+
+        let refresh_token = self.refresh_token.clone().ok_or(Error::MissingRefreshToken)?;
+        let client_id = self.client_id.clone().ok_or(Error::MissingConfig("client-id"))?;
+        let token_endpoint = match &self.token_endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => {
+                let issuer_url = self
+                    .issuer_url
+                    .as_ref()
+                    .ok_or(Error::MissingConfig("idp-issuer-url"))?;
+                let discovery = format!(
+                    "{}/.well-known/openid-configuration",
+                    issuer_url.trim_end_matches('/')
+                );
+                let request = Request::get(discovery)
+                    .body(Vec::new())
+                    .map_err(Error::BuildRequest)?;
+                let metadata: ProviderMetadata = send(request, self.certificate_authority.as_ref()).await?;
+                self.token_endpoint = Some(metadata.token_endpoint.clone());
+                metadata.token_endpoint
+            }
+        };
+
+        // The serializer is not `Send`, so it must not be held across the `await`
+        let body = {
+            let mut form = form_urlencoded::Serializer::new(String::new());
+            form.append_pair("grant_type", "refresh_token")
+                .append_pair("refresh_token", &refresh_token)
+                .append_pair("client_id", &client_id);
+            if let Some(secret) = &self.client_secret {
+                form.append_pair("client_secret", secret);
+            }
+            form.finish()
+        };
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(token_endpoint)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.into_bytes())
+            .map_err(Error::BuildRequest)?;
+        let response: TokenResponse = send(request, self.certificate_authority.as_ref()).await?;
+
+        let id_token = response.id_token.ok_or(Error::MissingIdToken)?;
+        // Issuers may rotate the refresh-token
+        if let Some(refresh_token) = response.refresh_token {
+            self.refresh_token = Some(refresh_token);
+        }
+        self.id_token = Some(id_token.clone());
+        self.persist(&refresh_token);
+        Ok(id_token)
+    }
+
+    // Writes the refreshed tokens back to the user that had `refresh_token`, like kubectl does.
+    // Issuers that rotate refresh-tokens invalidate the old one, which otherwise stays in the kubeconfig.
+    fn persist(&self, refresh_token: &str) {
+        let path = match &self.kubeconfig {
+            Some(path) => path,
+            None => return,
+        };
+        let updates = [
+            ("id-token", self.id_token.as_deref().unwrap_or_default()),
+            ("refresh-token", self.refresh_token.as_deref().unwrap_or_default()),
+        ];
+        let res = config::update_auth_provider_config(path, "refresh-token", refresh_token, &updates);
+        if let Err(err) = res {
+            tracing::warn!("failed to write refreshed oidc tokens to kubeconfig: {}", err);
+        }
+    }
+}
+
+// Tokens that can't be decoded are assumed to be valid, and left to the apiserver to reject
+fn is_expired(id_token: &str) -> bool {
+    match expiry(id_token) {
+        Some(expiry) => Utc::now() + Duration::seconds(EXPIRY_SLACK_SECONDS) >= expiry,
+        None => false,
+    }
+}
+
+fn expiry(id_token: &str) -> Option<DateTime<Utc>> {
+    let payload = id_token.split('.').nth(1)?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    let claims: Claims = serde_json::from_slice(&payload).ok()?;
+    Utc.timestamp_opt(claims.exp?, 0).single()
+}
+
+impl From<SendError> for Error {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::Request(err) => Error::RequestIssuer(err),
+            SendError::Status(status, body) => Error::IssuerStatus(status, body),
+            SendError::Parse(err) => Error::ParseResponse(err),
+            SendError::ConcatBuffers(err) => Error::ConcatBuffers(err),
+            SendError::CreateTlsConnector(err) => Error::CreateTlsConnector(err),
+            #[cfg(feature = "openssl-tls")]
+            SendError::CreateOpensslHttpsConnector(err) => Error::CreateOpensslHttpsConnector(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_expiring_at(exp: i64) -> String {
+        let claims = base64::encode_config(format!(r#"{{"exp":{}}}"#, exp), base64::URL_SAFE_NO_PAD);
+        format!("header.{}.signature", claims)
+    }
+
+    #[test]
+    fn id_token_expiry() {
+        let valid = token_expiring_at((Utc::now() + Duration::hours(1)).timestamp());
+        assert!(!is_expired(&valid));
+        let expired = token_expiring_at((Utc::now() - Duration::hours(1)).timestamp());
+        assert!(is_expired(&expired));
+        assert!(!is_expired("not-a-jwt"));
+    }
+
+    #[tokio::test]
+    async fn expired_token_without_refresh_token() {
+        let mut oidc = Oidc::from_provider(
+            &AuthProviderConfig {
+                name: "oidc".into(),
+                config: [(
+                    "id-token".to_owned(),
+                    token_expiring_at((Utc::now() - Duration::hours(1)).timestamp()),
+                )]
+                .into_iter()
+                .collect(),
+                source: None,
+            },
+            false,
+        )
+        .unwrap();
+        assert!(matches!(oidc.token().await, Err(Error::MissingRefreshToken)));
+    }
+
+    #[test]
+    fn loads_certificate_authority() {
+        let pem = "-----BEGIN CERTIFICATE-----\nAQID\n-----END CERTIFICATE-----\n";
+        let provider = |key: &str, value: String| AuthProviderConfig {
+            name: "oidc".into(),
+            config: [(key.to_owned(), value)].into_iter().collect(),
+            source: None,
+        };
+
+        let data = provider("idp-certificate-authority-data", base64::encode(pem));
+        let oidc = Oidc::from_provider(&data, false).unwrap();
+        assert_eq!(oidc.certificate_authority, Some(vec![vec![1, 2, 3]]));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ca.crt");
+        std::fs::write(&path, pem).unwrap();
+        let file = provider("idp-certificate-authority", path.to_string_lossy().into_owned());
+        let oidc = Oidc::from_provider(&file, false).unwrap();
+        assert_eq!(oidc.certificate_authority, Some(vec![vec![1, 2, 3]]));
+
+        let missing = provider("idp-certificate-authority", "/missing/ca.crt".into());
+        assert!(matches!(
+            Oidc::from_provider(&missing, false),
+            Err(Error::ReadCertificateAuthority(..))
+        ));
+    }
+
+    #[test]
+    fn writes_back_to_source_when_enabled() {
+        let provider = AuthProviderConfig {
+            name: "oidc".into(),
+            config: [("refresh-token".to_owned(), "refresh".to_owned())]
+                .into_iter()
+                .collect(),
+            source: Some("/home/user/.kube/config".into()),
+        };
+        assert_eq!(Oidc::from_provider(&provider, false).unwrap().kubeconfig, None);
+        assert_eq!(
+            Oidc::from_provider(&provider, true).unwrap().kubeconfig,
+            provider.source
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fmt::Debug, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+use tower::BoxError;
+
+use super::RefreshPolicy;
+
+/// A bearer token issued by a [`TokenProvider`]
+#[derive(Clone, Debug)]
+pub struct Token {
+    /// The bearer token
+    pub token: String,
+    /// When the token expires
+    ///
+    /// Tokens are reused until they are due for refresh,
+    /// see [`Config::token_refresh_ratio`](crate::Config::token_refresh_ratio).
+    /// Tokens without an expiry are not reused, and a new token is requested for every request.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Source of bearer tokens for custom authentication, such as tokens issued by Vault or an internal broker
+///
+/// Set it with [`Config::token_provider`](crate::Config::token_provider) to authenticate with its tokens
+/// instead of the credentials in the kubeconfig.
+///
+/// ```no_run
+/// use futures::future::BoxFuture;
+/// use kube::{client::{Token, TokenProvider}, Client, Config};
+/// use tower::BoxError;
+///
+/// #[derive(Debug)]
+/// struct Broker;
+///
+/// impl TokenProvider for Broker {
+///     fn token(&self) -> BoxFuture<'_, Result<Token, BoxError>> {
+///         Box::pin(async {
+///             Ok(Token {
+///                 token: std::fs::read_to_string("/var/run/broker/token")?,
+///                 expires_at: Some(chrono::Utc::now() + chrono::Duration::minutes(10)),
+///             })
+///         })
+///     }
+/// }
+///
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = Config::infer().await?.token_provider(Broker);
+/// let client = Client::try_from(config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait TokenProvider: Debug + Send + Sync + 'static {
+    /// Request a new token
+    fn token(&self) -> BoxFuture<'_, Result<Token, BoxError>>;
+}
+
+#[derive(Debug)]
+pub struct ProvidedToken {
+    provider: Arc<dyn TokenProvider>,
+    policy: RefreshPolicy,
+    // The token and when to refresh it
+    cached: Option<(String, DateTime<Utc>)>,
+}
+
+impl ProvidedToken {
+    pub(crate) fn new(provider: Arc<dyn TokenProvider>, policy: RefreshPolicy) -> Self {
+        Self {
+            provider,
+            policy,
+            cached: None,
+        }
+    }
+
+    pub(crate) async fn token(&mut self) -> Result<String, BoxError> {
+        if let Some((token, refresh_at)) = &self.cached {
+            // Leave some room so that the token doesn't expire while the request is in flight
+            if Utc::now() + Duration::seconds(60) < *refresh_at {
+                return Ok(token.clone());
+            }
+        }
+        let Token { token, expires_at } = self.provider.token().await?;
+        self.cached = expires_at.map(|expiry| (token.clone(), self.policy.refresh_at(expiry)));
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct Counter {
+        calls: AtomicUsize,
+        lifetime: Option<Duration>,
+    }
+
+    impl TokenProvider for Counter {
+        fn token(&self) -> BoxFuture<'_, Result<Token, BoxError>> {
+            Box::pin(async move {
+                let call = self.calls.fetch_add(1, Ordering::SeqCst);
+                Ok(Token {
+                    token: format!("token-{}", call),
+                    expires_at: self.lifetime.map(|lifetime| Utc::now() + lifetime),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_until_refresh() {
+        let mut provided = ProvidedToken::new(
+            Arc::new(Counter {
+                lifetime: Some(Duration::minutes(10)),
+                ..Counter::default()
+            }),
+            RefreshPolicy::default(),
+        );
+        assert_eq!(provided.token().await.unwrap(), "token-0");
+        assert_eq!(provided.token().await.unwrap(), "token-0");
+
+        // Expiring within the leeway
+        let mut provided = ProvidedToken::new(
+            Arc::new(Counter {
+                lifetime: Some(Duration::seconds(30)),
+                ..Counter::default()
+            }),
+            RefreshPolicy::default(),
+        );
+        assert_eq!(provided.token().await.unwrap(), "token-0");
+        assert_eq!(provided.token().await.unwrap(), "token-1");
+
+        let mut provided = ProvidedToken::new(Arc::new(Counter::default()), RefreshPolicy::default());
+        assert_eq!(provided.token().await.unwrap(), "token-0");
+        assert_eq!(provided.token().await.unwrap(), "token-1");
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// When to refresh expiring tokens, see `Config::token_refresh_ratio`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RefreshPolicy {
+    ratio: f64,
+    jitter: f64,
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        Self::new(
+            crate::config::DEFAULT_TOKEN_REFRESH_RATIO,
+            crate::config::DEFAULT_TOKEN_REFRESH_JITTER,
+        )
+    }
+}
+
+impl RefreshPolicy {
+    pub(crate) fn new(ratio: f64, jitter: f64) -> Self {
+        Self {
+            ratio: ratio.clamp(0.0, 1.0),
+            jitter: jitter.clamp(0.0, 1.0),
+        }
+    }
+
+    /// When to refresh a token obtained now, which expires at `expiry`
+    pub(crate) fn refresh_at(&self, expiry: DateTime<Utc>) -> DateTime<Utc> {
+        self.refresh_at_with(Utc::now(), expiry, rand::random())
+    }
+
+    fn refresh_at_with(&self, now: DateTime<Utc>, expiry: DateTime<Utc>, random: f64) -> DateTime<Utc> {
+        let lifetime = (expiry - now).num_milliseconds();
+        if lifetime <= 0 {
+            return expiry;
+        }
+        let fraction = (self.ratio - self.jitter * random).max(0.0);
+        now + Duration::milliseconds((lifetime as f64 * fraction) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refreshes_early_with_jitter() {
+        let now = Utc::now();
+        let expiry = now + Duration::minutes(100);
+        let policy = RefreshPolicy::new(0.8, 0.1);
+        assert_eq!(policy.refresh_at_with(now, expiry, 0.0), now + Duration::minutes(80));
+        assert_eq!(policy.refresh_at_with(now, expiry, 0.5), now + Duration::minutes(75));
+
+        // Tokens that already expired are refreshed right away
+        let expired = now - Duration::minutes(1);
+        assert_eq!(policy.refresh_at_with(now, expired, 0.5), expired);
+
+        let refresh_at = policy.refresh_at(expiry);
+        assert!(refresh_at > now + Duration::minutes(69) && refresh_at <= now + Duration::minutes(81));
+    }
+}
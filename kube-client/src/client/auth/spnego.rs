@@ -0,0 +1,115 @@
+use http::Uri;
+use libgssapi::{
+    context::{ClientCtx, CtxFlags},
+    credential::{Cred, CredUsage},
+    name::Name,
+    oid::{OidSet, GSS_MECH_KRB5, GSS_MECH_SPNEGO, GSS_NT_HOSTBASED_SERVICE},
+};
+use thiserror::Error;
+
+use crate::config::AuthProviderConfig;
+
+#[derive(Error, Debug)]
+/// Possible errors when computing SPNEGO tokens
+pub enum Error {
+    /// The request has no host to derive the service principal from
+    #[error("request has no host to derive the service principal from")]
+    MissingHost,
+
+    /// GSSAPI failed, for example because there is no Kerberos ticket
+    #[error("GSSAPI failed: {0}")]
+    Gssapi(#[source] libgssapi::error::Error),
+
+    /// GSSAPI did not produce an initial token
+    #[error("GSSAPI did not produce an initial token")]
+    NoToken,
+
+    /// The blocking GSSAPI task failed
+    #[error("GSSAPI task failed: {0}")]
+    Task(#[source] tokio::task::JoinError),
+}
+
+/// Kerberos authentication with SPNEGO, from the `spnego` auth-provider
+///
+/// A `Negotiate` token for the service principal is computed for every request, using the credentials in the
+/// Kerberos credential cache (see `kinit`). This is meant for clusters behind authenticating proxies.
+///
+/// The service principal defaults to `HTTP@<host>` for the host of the request, and can be set with the
+/// `service-principal` key of the auth-provider config.
+#[derive(Debug)]
+pub struct Spnego {
+    service_principal: Option<String>,
+    delegate: bool,
+}
+
+impl Spnego {
+    pub(crate) fn from_provider(provider: &AuthProviderConfig) -> Self {
+        let get = |key: &str| provider.config.get(key).filter(|v| !v.is_empty()).cloned();
+        Self {
+            service_principal: get("service-principal"),
+            delegate: get("delegate").as_deref() == Some("true"),
+        }
+    }
+
+    fn service_principal(&self, uri: &Uri) -> Result<String, Error> {
+        match &self.service_principal {
+            Some(principal) => Ok(principal.clone()),
+            None => uri
+                .host()
+                .map(|host| format!("HTTP@{}", host))
+                .ok_or(Error::MissingHost),
+        }
+    }
+
+    /// Returns the `Negotiate` token for a request to `uri`
+    pub async fn token(&self, uri: &Uri) -> Result<String, Error> {
+        let principal = self.service_principal(uri)?;
+        let mut flags = CtxFlags::GSS_C_MUTUAL_FLAG;
+        if self.delegate {
+            flags |= CtxFlags::GSS_C_DELEG_FLAG;
+        }
+        // GSSAPI blocks, and may contact the KDC for a service ticket
+        tokio::task::spawn_blocking(move || initial_token(&principal, flags))
+            .await
+            .map_err(Error::Task)?
+    }
+}
+
+fn initial_token(principal: &str, flags: CtxFlags) -> Result<String, Error> {
+    let name = Name::new(principal.as_bytes(), Some(&GSS_NT_HOSTBASED_SERVICE)).map_err(Error::Gssapi)?;
+    let name = name.canonicalize(Some(&GSS_MECH_KRB5)).map_err(Error::Gssapi)?;
+    let mut mechs = OidSet::new().map_err(Error::Gssapi)?;
+    mechs.add(&GSS_MECH_SPNEGO).map_err(Error::Gssapi)?;
+    let cred = Cred::acquire(None, None, CredUsage::Initiate, Some(&mechs)).map_err(Error::Gssapi)?;
+    let mut ctx = ClientCtx::new(cred, name, flags, Some(&GSS_MECH_SPNEGO));
+    let token = ctx.step(None).map_err(Error::Gssapi)?.ok_or(Error::NoToken)?;
+    Ok(base64::encode(&*token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_principal_from_host() {
+        let uri: Uri = "https://proxy.example.com:8443/api/v1/pods".parse().unwrap();
+        let spnego = Spnego::from_provider(&AuthProviderConfig {
+            name: "spnego".into(),
+            config: Default::default(),
+            source: None,
+        });
+        assert_eq!(spnego.service_principal(&uri).unwrap(), "HTTP@proxy.example.com");
+
+        let spnego = Spnego::from_provider(&AuthProviderConfig {
+            name: "spnego".into(),
+            config: [(
+                "service-principal".to_string(),
+                "HTTP@k8s.example.com".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+            source: None,
+        });
+        assert_eq!(spnego.service_principal(&uri).unwrap(), "HTTP@k8s.example.com");
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::Error;
+
+// Re-read the file this often, like client-go, since there's no reliable way to tell when it's rotated
+const RELOAD_INTERVAL_SECONDS: i64 = 60;
+
+/// Bearer token read from a file that is rotated externally, such as bound service account tokens
+#[derive(Debug)]
+pub struct TokenFile {
+    path: PathBuf,
+    token: String,
+    reload_at: DateTime<Utc>,
+}
+
+impl TokenFile {
+    pub(crate) fn new(path: &str) -> Result<Self, Error> {
+        let path = PathBuf::from(path);
+        let token = read(&path)?;
+        Ok(Self {
+            path,
+            token,
+            reload_at: Utc::now() + Duration::seconds(RELOAD_INTERVAL_SECONDS),
+        })
+    }
+
+    /// Returns the token, re-reading the file if it was read more than a minute ago
+    ///
+    /// If the file can't be read, the previous token is returned, in case the file is being replaced.
+    pub fn token(&mut self) -> &str {
+        let now = Utc::now();
+        if now >= self.reload_at {
+            match read(&self.path) {
+                Ok(token) => self.token = token,
+                Err(err) => tracing::warn!("failed to reload token, using the previous token: {}", err),
+            }
+            self.reload_at = now + Duration::seconds(RELOAD_INTERVAL_SECONDS);
+        }
+        &self.token
+    }
+}
+
+fn read(path: &PathBuf) -> Result<String, Error> {
+    std::fs::read_to_string(path)
+        .map(|token| token.trim().to_owned())
+        .map_err(|source| Error::ReadTokenFile(source, path.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn reloads_rotated_token() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "first").unwrap();
+        let mut token_file = TokenFile::new(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(token_file.token(), "first");
+
+        std::fs::write(file.path(), "second").unwrap();
+        assert_eq!(token_file.token(), "first");
+        token_file.reload_at = Utc::now();
+        assert_eq!(token_file.token(), "second");
+
+        // Keep the previous token while the file is missing
+        let path = file.path().to_owned();
+        drop(file);
+        assert!(!path.exists());
+        token_file.reload_at = Utc::now();
+        assert_eq!(token_file.token(), "second");
+    }
+}
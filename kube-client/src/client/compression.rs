@@ -0,0 +1,148 @@
+use std::{
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use http::{
+    header::{HeaderValue, CONTENT_ENCODING},
+    Method, Request, StatusCode,
+};
+
+use crate::error::ErrorResponse;
+
+/// Compresses large request bodies with gzip, until the apiserver turns out not to support it
+///
+/// The apiserver does not advertise whether it accepts compressed request bodies,
+/// so support is assumed until a compressed request is rejected while its uncompressed retry is accepted.
+pub(crate) struct RequestCompression {
+    min_size: usize,
+    unsupported: AtomicBool,
+}
+
+impl RequestCompression {
+    pub(crate) fn new(min_size: usize) -> Self {
+        Self {
+            min_size,
+            unsupported: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns a gzipped copy of `request` if it should be compressed
+    pub(crate) fn compress(&self, request: &Request<Vec<u8>>) -> Option<Request<Vec<u8>>> {
+        if self.unsupported.load(Ordering::Relaxed)
+            || request.body().len() < self.min_size
+            || request.headers().contains_key(CONTENT_ENCODING)
+            || ![Method::POST, Method::PUT, Method::PATCH].contains(request.method())
+        {
+            return None;
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(request.body()).ok()?;
+        let body = encoder.finish().ok()?;
+
+        let mut compressed = Request::new(body);
+        *compressed.method_mut() = request.method().clone();
+        *compressed.uri_mut() = request.uri().clone();
+        *compressed.version_mut() = request.version();
+        *compressed.headers_mut() = request.headers().clone();
+        compressed
+            .headers_mut()
+            .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        if let Some(name) = request.extensions().get::<&'static str>() {
+            compressed.extensions_mut().insert(*name);
+        }
+        Some(compressed)
+    }
+
+    /// Whether a response with `status` may reject the encoding of a compressed request, see [`Self::is_rejected`]
+    pub(crate) fn may_be_rejected(status: StatusCode) -> bool {
+        status == StatusCode::UNSUPPORTED_MEDIA_TYPE || status == StatusCode::BAD_REQUEST
+    }
+
+    /// Whether the apiserver rejected the encoding of a compressed request, given the response `body`
+    ///
+    /// The apiserver does not decode compressed request bodies, and fails to parse the gzipped bytes
+    /// with a `400 Bad Request`. Other errors, such as invalid objects, are not retried uncompressed.
+    pub(crate) fn is_rejected(status: StatusCode, body: &[u8]) -> bool {
+        if status == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            return true;
+        }
+        if status != StatusCode::BAD_REQUEST {
+            return false;
+        }
+        let message = match serde_json::from_slice::<ErrorResponse>(body) {
+            Ok(status) => status.message,
+            Err(_) => String::from_utf8_lossy(body).into_owned(),
+        };
+        // Gzip data starts with the bytes 0x1f 0x8b, which JSON and YAML decoders stop at
+        ["\\x1f", "\\u001f", "invalid character", "json parse error", "yaml:", "error decoding"]
+            .iter()
+            .any(|decode_error| message.contains(decode_error))
+    }
+
+    /// Stop compressing after the uncompressed retry of a rejected request was accepted
+    pub(crate) fn set_unsupported(&self) {
+        if !self.unsupported.swap(true, Ordering::Relaxed) {
+            tracing::debug!("apiserver does not accept compressed request bodies");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn compresses_large_writes() {
+        let compression = RequestCompression::new(16);
+        let body = br#"{"metadata":{"name":"large"},"data":{"key":"value"}}"#.to_vec();
+        let mut request = Request::post("/api/v1/namespaces/ns/configmaps")
+            .body(body.clone())
+            .unwrap();
+        request.extensions_mut().insert("create");
+
+        let compressed = compression.compress(&request).unwrap();
+        assert_eq!(compressed.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(compressed.extensions().get::<&'static str>(), Some(&"create"));
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.body().as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body);
+
+        let small = Request::post("/api/v1/namespaces/ns/configmaps").body(b"{}".to_vec()).unwrap();
+        assert!(compression.compress(&small).is_none());
+        let get = Request::get("/api/v1/namespaces/ns/configmaps").body(body).unwrap();
+        assert!(compression.compress(&get).is_none());
+
+        compression.set_unsupported();
+        assert!(compression.compress(&request).is_none());
+    }
+
+    #[test]
+    fn rejected_encodings() {
+        let status = |code: u16, message: &str| {
+            serde_json::json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "status": "Failure",
+                "message": message,
+                "reason": "BadRequest",
+                "code": code,
+            })
+            .to_string()
+        };
+        let decode_error = status(
+            400,
+            "couldn't get version/kind; json parse error: invalid character '\\x1f' looking for beginning of value",
+        );
+        let invalid = status(400, "the server rejected our request for an unknown reason");
+        let rejected = |code, body: &str| RequestCompression::is_rejected(code, body.as_bytes());
+        assert!(rejected(StatusCode::UNSUPPORTED_MEDIA_TYPE, ""));
+        assert!(rejected(StatusCode::BAD_REQUEST, &decode_error));
+        assert!(!rejected(StatusCode::BAD_REQUEST, &invalid));
+        assert!(!rejected(StatusCode::UNPROCESSABLE_ENTITY, &decode_error));
+    }
+}
@@ -3,7 +3,7 @@ use tower::{filter::AsyncFilterLayer, util::Either};
 #[cfg(any(feature = "native-tls", feature = "rustls-tls", feature = "openssl-tls"))]
 use super::tls;
 use super::{
-    auth::Auth,
+    auth::{Auth, RefreshPolicy},
     middleware::{AddAuthorizationLayer, AuthLayer, BaseUriLayer},
 };
 use crate::{Config, Error, Result};
@@ -18,10 +18,19 @@ pub trait ConfigExt: private::Sealed {
     fn base_uri_layer(&self) -> BaseUriLayer;
 
     /// Optional layer to set up `Authorization` header depending on the config.
+    ///
+    /// Uses the [`TokenProvider`](crate::client::TokenProvider) of the config if one is set.
     fn auth_layer(&self) -> Result<Option<AuthLayer>>;
 
     /// Create [`hyper_tls::HttpsConnector`] based on config.
     ///
+    /// Note that `native-tls` takes the server name from the requested URI, so [`Config::tls_server_name`]
+    /// is not applied to this connector. The default [`Client`](crate::Client) handles it when built
+    /// with [`Client::try_from`](crate::Client::try_from).
+    ///
+    /// `native-tls` can't check [`Config::tls_pinned_spki_sha256`] during the handshake either, so this fails
+    /// if any public keys are pinned, rather than connecting to servers with other keys.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -54,6 +63,28 @@ pub trait ConfigExt: private::Sealed {
     #[cfg(feature = "rustls-tls")]
     fn rustls_https_connector(&self) -> Result<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
 
+    /// Create [`hyper_rustls::HttpsConnector`] based on config and `connector`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # async fn doc() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use hyper::client::HttpConnector;
+    /// # use kube::{client::ConfigExt, Config};
+    /// let mut http = HttpConnector::new();
+    /// http.enforce_http(false);
+    /// let config = Config::infer().await?;
+    /// let https = config.rustls_https_connector_with_connector(http)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "rustls-tls")))]
+    #[cfg(feature = "rustls-tls")]
+    fn rustls_https_connector_with_connector(
+        &self,
+        connector: hyper::client::HttpConnector,
+    ) -> Result<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
+
     /// Create [`native_tls::TlsConnector`](tokio_native_tls::native_tls::TlsConnector) based on config.
     /// # Example
     ///
@@ -167,24 +198,26 @@ impl ConfigExt for Config {
     }
 
     fn auth_layer(&self) -> Result<Option<AuthLayer>> {
-        Ok(match Auth::try_from(&self.auth_info).map_err(Error::Auth)? {
-            Auth::None => None,
-            Auth::Basic(user, pass) => Some(AuthLayer(Either::A(
-                AddAuthorizationLayer::basic(&user, &pass).as_sensitive(true),
-            ))),
-            Auth::Bearer(token) => Some(AuthLayer(Either::A(
-                AddAuthorizationLayer::bearer(&token).as_sensitive(true),
-            ))),
-            Auth::RefreshableToken(refreshable) => {
-                Some(AuthLayer(Either::B(AsyncFilterLayer::new(refreshable))))
-            }
-        })
+        let policy = RefreshPolicy::new(self.token_refresh_ratio, self.token_refresh_jitter);
+        if let Some(provider) = &self.token_provider {
+            return Ok(auth_layer(Auth::from_token_provider(provider.clone(), policy)));
+        }
+        let (auth, _) = Auth::with_identity(
+            &self.auth_info,
+            policy,
+            self.exec_timeout,
+            self.persist_auth_provider_tokens,
+            self.native_eks_tokens,
+        )
+        .map_err(Error::Auth)?;
+        Ok(auth_layer(auth))
     }
 
     #[cfg(feature = "native-tls")]
     fn native_tls_connector(&self) -> Result<tokio_native_tls::native_tls::TlsConnector> {
         tls::native_tls::native_tls_connector(
             self.identity_pem.as_ref(),
+            self.identity_pkcs12.as_ref(),
             self.root_cert.as_ref(),
             self.accept_invalid_certs,
         )
@@ -193,6 +226,9 @@ impl ConfigExt for Config {
 
     #[cfg(feature = "native-tls")]
     fn native_tls_https_connector(&self) -> Result<hyper_tls::HttpsConnector<hyper::client::HttpConnector>> {
+        if !self.tls_pinned_spki_sha256.is_empty() {
+            return Err(Error::NativeTls(tls::native_tls::Error::UnsupportedPinnedPublicKeys));
+        }
         let tls = tokio_native_tls::TlsConnector::from(self.native_tls_connector()?);
         let mut http = hyper::client::HttpConnector::new();
         http.enforce_http(false);
@@ -203,24 +239,56 @@ impl ConfigExt for Config {
     fn rustls_client_config(&self) -> Result<rustls::ClientConfig> {
         tls::rustls_tls::rustls_client_config(
             self.identity_pem.as_deref(),
+            self.identity_pkcs12.as_ref(),
             self.root_cert.as_deref(),
             self.accept_invalid_certs,
+            self.legacy_host(),
+            self.tls_key_log,
+            &self.tls_pinned_spki_sha256,
         )
         .map_err(Error::RustlsTls)
     }
 
     #[cfg(feature = "rustls-tls")]
     fn rustls_https_connector(&self) -> Result<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>> {
-        let rustls_config = std::sync::Arc::new(self.rustls_client_config()?);
         let mut http = hyper::client::HttpConnector::new();
         http.enforce_http(false);
-        Ok(hyper_rustls::HttpsConnector::from((http, rustls_config)))
+        self.rustls_https_connector_with_connector(http)
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    fn rustls_https_connector_with_connector(
+        &self,
+        connector: hyper::client::HttpConnector,
+    ) -> Result<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>> {
+        let builder = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(self.rustls_client_config()?)
+            .https_or_http();
+        let builder = if let Some(server_name) = &self.tls_server_name {
+            builder.with_server_name(server_name.clone())
+        } else if self
+            .legacy_host()
+            .map_or(false, |host| host.parse::<std::net::IpAddr>().is_ok())
+        {
+            // rustls can't present IP addresses, the legacy verifier checks the certificate against the IP instead
+            builder.with_server_name(tls::rustls_tls::LEGACY_IP_SERVER_NAME.to_owned())
+        } else {
+            builder
+        };
+        Ok(builder.enable_http1().wrap_connector(connector))
     }
 
     #[cfg(feature = "openssl-tls")]
     fn openssl_ssl_connector_builder(&self) -> Result<openssl::ssl::SslConnectorBuilder> {
-        tls::openssl_tls::ssl_connector_builder(self.identity_pem.as_ref(), self.root_cert.as_ref())
-            .map_err(|e| Error::OpensslTls(tls::openssl_tls::Error::CreateSslConnector(e)))
+        let key_log = std::env::var_os("SSLKEYLOGFILE").filter(|_| self.tls_key_log);
+        tls::openssl_tls::ssl_connector_builder(
+            self.identity_pem.as_ref(),
+            self.identity_pkcs12.as_ref(),
+            self.root_cert.as_ref(),
+            key_log.as_ref().map(std::path::Path::new),
+            &self.tls_pinned_spki_sha256,
+        )
+        .map_err(|e| Error::OpensslTls(tls::openssl_tls::Error::CreateSslConnector(e)))
     }
 
     #[cfg(feature = "openssl-tls")]
@@ -238,12 +306,61 @@ impl ConfigExt for Config {
         let mut https =
             hyper_openssl::HttpsConnector::with_connector(connector, self.openssl_ssl_connector_builder()?)
                 .map_err(|e| Error::OpensslTls(tls::openssl_tls::Error::CreateHttpsConnector(e)))?;
-        if self.accept_invalid_certs {
-            https.set_callback(|ssl, _uri| {
-                ssl.set_verify(openssl::ssl::SslVerifyMode::NONE);
+        if self.accept_invalid_certs || self.tls_server_name.is_some() {
+            let accept_invalid_certs = self.accept_invalid_certs;
+            let tls_server_name = self.tls_server_name.clone();
+            let pins = self.tls_pinned_spki_sha256.clone();
+            https.set_callback(move |ssl, _uri| {
+                if accept_invalid_certs && pins.is_empty() {
+                    ssl.set_verify(openssl::ssl::SslVerifyMode::NONE);
+                } else if accept_invalid_certs {
+                    // Only verify the pinned public key instead of the certificate chain
+                    let pins = pins.clone();
+                    ssl.set_verify_callback(openssl::ssl::SslVerifyMode::PEER, move |_, ctx| {
+                        ctx.error_depth() != 0 || tls::openssl_tls::is_pinned(ctx, &pins)
+                    });
+                }
+                if let Some(server_name) = &tls_server_name {
+                    // Prevent `hyper_openssl` from using the host of the URI instead
+                    ssl.set_use_server_name_indication(false);
+                    ssl.set_verify_hostname(false);
+                    ssl.set_hostname(server_name)?;
+                    ssl.param_mut().set_host(server_name)?;
+                }
                 Ok(())
             });
         }
         Ok(https)
     }
 }
+
+/// Layer to set up `Authorization` header for `auth`
+pub(crate) fn auth_layer(auth: Auth) -> Option<AuthLayer> {
+    match auth {
+        Auth::None => None,
+        Auth::Basic(user, pass) => Some(AuthLayer(Either::A(
+            AddAuthorizationLayer::basic(&user, &pass).as_sensitive(true),
+        ))),
+        Auth::Bearer(token) => Some(AuthLayer(Either::A(
+            AddAuthorizationLayer::bearer(&token).as_sensitive(true),
+        ))),
+        Auth::RefreshableToken(refreshable) => {
+            Some(AuthLayer(Either::B(AsyncFilterLayer::new(refreshable))))
+        }
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
+impl Config {
+    /// Host to verify server certificates against when `accept_legacy_server_certs` is set.
+    fn legacy_host(&self) -> Option<&str> {
+        if !self.accept_legacy_server_certs {
+            return None;
+        }
+        self.tls_server_name
+            .as_deref()
+            .or_else(|| self.cluster_url.host())
+            // IPv6 hosts are bracketed in URIs
+            .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+};
+
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower::{BoxError, Service, ServiceExt};
+
+use super::auth::ExecIdentity;
+
+/// Statistics of the connection pool of a [`Client`](crate::Client)
+///
+/// Retrieved with [`Client::connection_stats`](crate::Client::connection_stats), for clients created from
+/// a [`Config`](crate::Config). Idle connections are closed after
+/// [`Config::pool_idle_timeout`](crate::Config::pool_idle_timeout), and at most
+/// [`Config::pool_max_idle_per_host`](crate::Config::pool_max_idle_per_host) of them are kept per host,
+/// which bounds the sockets held by processes that talk to many clusters.
+///
+/// ```no_run
+/// use kube::Client;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// // ...
+/// if let Some(stats) = client.connection_stats() {
+///     let reused = stats.reuse_rate() * 100.0;
+///     println!("{} open connections, {:.0}% of requests reused one", stats.open, reused);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Connections that are currently open, idle or in use
+    pub open: usize,
+    /// Connections opened so far
+    pub opened: u64,
+    /// Requests sent so far
+    pub requests: u64,
+    /// Connections that are currently open per `host:port`
+    pub open_by_host: BTreeMap<String, usize>,
+}
+
+impl ConnectionStats {
+    /// The fraction of requests that reused an open connection rather than opening one
+    pub fn reuse_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.requests.saturating_sub(self.opened) as f64 / self.requests as f64
+    }
+}
+
+// Records the connections and requests of the hyper client of a `Client`
+#[derive(Default)]
+pub(crate) struct ConnectionRecorder {
+    stats: Mutex<ConnectionStats>,
+}
+
+impl ConnectionRecorder {
+    pub(crate) fn record_request(&self) {
+        self.lock().requests += 1;
+    }
+
+    pub(crate) fn snapshot(&self) -> ConnectionStats {
+        self.lock().clone()
+    }
+
+    fn opened(&self, host: &str) {
+        let mut stats = self.lock();
+        stats.open += 1;
+        stats.opened += 1;
+        *stats.open_by_host.entry(host.to_string()).or_default() += 1;
+    }
+
+    fn closed(&self, host: &str) {
+        let mut stats = self.lock();
+        stats.open = stats.open.saturating_sub(1);
+        if let Some(open) = stats.open_by_host.get_mut(host) {
+            *open -= 1;
+            if *open == 0 {
+                stats.open_by_host.remove(host);
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ConnectionStats> {
+        self.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Connector that records the connections of `C` until they are dropped
+#[derive(Clone)]
+pub(crate) struct CountConnections<C> {
+    inner: C,
+    recorder: Arc<ConnectionRecorder>,
+}
+
+impl<C> CountConnections<C> {
+    pub(crate) fn new(inner: C, recorder: Arc<ConnectionRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl<C> Service<Uri> for CountConnections<C>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = Counted<C::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let host = match (uri.host(), uri.port_u16()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => String::new(),
+        };
+        let recorder = self.recorder.clone();
+        let connecting = self.inner.call(uri);
+        Box::pin(async move {
+            let io = connecting.await?;
+            recorder.opened(&host);
+            Ok(Counted { io, host, recorder })
+        })
+    }
+}
+
+// A connection that is recorded as closed when dropped
+pub(crate) struct Counted<IO> {
+    io: IO,
+    host: String,
+    recorder: Arc<ConnectionRecorder>,
+}
+
+impl<IO> Drop for Counted<IO> {
+    fn drop(&mut self) {
+        self.recorder.closed(&self.host);
+    }
+}
+
+impl<IO: Connection> Connection for Counted<IO> {
+    fn connected(&self) -> Connected {
+        self.io.connected()
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for Counted<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for Counted<IO> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+// Builds a connector that presents the given client certificate and key in PEM, if any
+type BuildConnector<C> = dyn Fn(Option<Vec<u8>>) -> crate::Result<C> + Send + Sync;
+
+// Connector that is rebuilt whenever an exec plugin issues a new client certificate,
+// so that new connections present the latest one
+#[derive(Clone)]
+pub(crate) struct RotateIdentity<C> {
+    // The connector, and the generation of the identity it was built with
+    current: Arc<Mutex<(u64, C)>>,
+    build: Arc<BuildConnector<C>>,
+    identity: Option<Arc<ExecIdentity>>,
+}
+
+impl<C> RotateIdentity<C> {
+    pub(crate) fn new(
+        build: impl Fn(Option<Vec<u8>>) -> crate::Result<C> + Send + Sync + 'static,
+        identity: Option<Arc<ExecIdentity>>,
+    ) -> crate::Result<Self> {
+        let (generation, pem) = identity.as_ref().map(|id| id.get()).unwrap_or_default();
+        Ok(Self {
+            current: Arc::new(Mutex::new((generation, build(pem)?))),
+            build: Arc::new(build),
+            identity,
+        })
+    }
+}
+
+impl<C: Clone> RotateIdentity<C> {
+    fn connector(&self) -> crate::Result<C> {
+        let mut current = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(identity) = &self.identity {
+            let (generation, pem) = identity.get();
+            if generation != current.0 {
+                *current = (generation, (self.build)(pem)?);
+            }
+        }
+        Ok(current.1.clone())
+    }
+}
+
+impl<C> Service<Uri> for RotateIdentity<C>
+where
+    C: Service<Uri> + Clone + Send + 'static,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxError>,
+{
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = C::Response;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Readiness is checked on the connector that is picked for each connection
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.connector();
+        Box::pin(async move { connector?.oneshot(uri).await.map_err(Into::into) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_connections_per_host() {
+        let recorder = ConnectionRecorder::default();
+        recorder.opened("10.0.0.1:443");
+        recorder.opened("10.0.0.1:443");
+        recorder.opened("10.0.0.2:443");
+        for _ in 0..8 {
+            recorder.record_request();
+        }
+        recorder.closed("10.0.0.2:443");
+        let stats = recorder.snapshot();
+        assert_eq!((stats.open, stats.opened, stats.requests), (2, 3, 8));
+        assert_eq!(
+            stats.open_by_host,
+            BTreeMap::from([("10.0.0.1:443".to_string(), 2)])
+        );
+        assert!((stats.reuse_rate() - 0.625).abs() < f64::EPSILON);
+        assert!(ConnectionStats::default().reuse_rate().abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn rebuilds_connector_for_new_identities() {
+        let identity = Arc::new(ExecIdentity::default());
+        // Connects by returning the identity the connector was built with
+        let build = |pem: Option<Vec<u8>>| {
+            Ok(tower::service_fn(move |_: Uri| {
+                let pem = pem.clone();
+                async move { Ok::<_, BoxError>(pem) }
+            }))
+        };
+        let mut connector = RotateIdentity::new(build, Some(identity.clone())).unwrap();
+        let uri = Uri::from_static("https://10.0.0.1");
+        assert_eq!(connector.call(uri.clone()).await.unwrap(), None);
+
+        identity.set(b"CERT\nKEY".to_vec());
+        assert_eq!(
+            connector.call(uri.clone()).await.unwrap(),
+            Some(b"CERT\nKEY".to_vec())
+        );
+        identity.set(b"CERT\nKEY".to_vec());
+        assert_eq!(identity.get().0, 1);
+    }
+}
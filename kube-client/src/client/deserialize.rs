@@ -0,0 +1,155 @@
+//! Deserializing responses with the context of failures
+use std::fmt::Write;
+
+use kube_core::TypeMeta;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use serde_path_to_error::{Path, Segment};
+
+use crate::error::DeserializeError;
+
+// Deserializes `text`, describing where and in which object it failed
+//
+// Paths are only tracked when deserializing again after a failure, so successes take no detour.
+pub(crate) fn from_str<T: DeserializeOwned>(text: &str) -> Result<T, DeserializeError> {
+    let err = match serde_json::from_str(text) {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+    let de = &mut serde_json::Deserializer::from_str(text);
+    match serde_path_to_error::deserialize::<_, T>(de) {
+        Err(err) => {
+            let path = err.path().clone();
+            Err(DeserializeError::new(text, Some(&path), err.into_inner()))
+        }
+        // Cannot succeed the second time, but report the first failure if it does
+        Ok(_) => Err(DeserializeError::new(text, None, err)),
+    }
+}
+
+impl DeserializeError {
+    fn new(text: &str, path: Option<&Path>, source: serde_json::Error) -> Self {
+        // Only parsed on failures, to find the object that failed
+        let root = serde_json::from_str::<Value>(text).unwrap_or_default();
+        // Watch events wrap the object
+        let mut object = match root.get("object") {
+            Some(object) if object.get("metadata").is_some() => object,
+            _ => &root,
+        };
+
+        let mut node = Some(&root);
+        let mut json_path = String::new();
+        for segment in path.into_iter().flat_map(Path::iter) {
+            match segment {
+                Segment::Seq { index } => {
+                    let _ = write!(json_path, "[{}]", index);
+                    node = node.and_then(|node| node.get(index));
+                }
+                Segment::Map { key } => {
+                    let _ = write!(json_path, ".{}", key);
+                    node = node.and_then(|node| node.get(key));
+                }
+                // Variants are not keys, like the type of watch events
+                Segment::Enum { .. } => {}
+                Segment::Unknown => {
+                    json_path.push_str(".?");
+                    node = None;
+                }
+            }
+            // The innermost named object, which skips embedded metadata like the ones of pod templates
+            if let Some(named) = node.filter(|node| node.pointer("/metadata/name").is_some()) {
+                object = named;
+            }
+        }
+
+        let str_at =
+            |value: &Value, pointer: &str| value.pointer(pointer).and_then(Value::as_str).map(String::from);
+        // Items of lists often leave out their type, which is then the one of the list
+        let types = match (str_at(object, "/apiVersion"), str_at(object, "/kind")) {
+            (Some(api_version), Some(kind)) => Some(TypeMeta { api_version, kind }),
+            _ => match (str_at(&root, "/apiVersion"), str_at(&root, "/kind")) {
+                (Some(api_version), Some(kind)) => Some(TypeMeta {
+                    api_version,
+                    kind: kind.strip_suffix("List").map(String::from).unwrap_or(kind),
+                }),
+                _ => None,
+            },
+        };
+
+        let offset = text
+            .split('\n')
+            .take(source.line().saturating_sub(1))
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            + source.column().saturating_sub(1);
+        DeserializeError {
+            types,
+            name: str_at(object, "/metadata/name"),
+            namespace: str_at(object, "/metadata/namespace"),
+            path: if json_path.is_empty() {
+                ".".into()
+            } else {
+                json_path
+            },
+            offset,
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::from_str;
+    use k8s_openapi::api::apps::v1::Deployment;
+    use kube_core::{ObjectList, WatchEvent};
+    use serde_json::json;
+
+    #[test]
+    fn describes_failing_list_item() {
+        let text = json!({
+            "apiVersion": "apps/v1",
+            "kind": "DeploymentList",
+            "metadata": {},
+            "items": [
+                { "metadata": { "name": "ok", "namespace": "web" } },
+                { "metadata": { "name": "broken", "namespace": "web" }, "spec": {
+                    "selector": {},
+                    "template": { "metadata": {} },
+                    "replicas": "three",
+                } },
+            ],
+        })
+        .to_string();
+        let err = from_str::<ObjectList<Deployment>>(&text).unwrap_err();
+        assert_eq!(err.path, ".items[1].spec.replicas");
+        assert_eq!(err.name.as_deref(), Some("broken"));
+        assert_eq!(err.namespace.as_deref(), Some("web"));
+        let types = err.types.as_ref().unwrap();
+        assert_eq!(
+            (types.api_version.as_str(), types.kind.as_str()),
+            ("apps/v1", "Deployment")
+        );
+        let three = text.find("\"three\"").unwrap();
+        assert!((three..=three + "\"three\"".len()).contains(&err.offset));
+        assert!(err.to_string().starts_with(
+            "failed to deserialize apps/v1 Deployment web/broken at .items[1].spec.replicas (byte "
+        ));
+    }
+
+    #[test]
+    fn describes_failing_watch_event() {
+        let text = json!({
+            "type": "ADDED",
+            "object": {
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": { "name": "broken" },
+                "spec": { "selector": {}, "template": {}, "paused": "yes" },
+            },
+        })
+        .to_string();
+        let err = from_str::<WatchEvent<Deployment>>(&text).unwrap_err();
+        assert_eq!(err.name.as_deref(), Some("broken"));
+        assert_eq!(err.types.unwrap().kind, "Deployment");
+    }
+}
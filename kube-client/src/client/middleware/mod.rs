@@ -36,7 +36,13 @@ mod tests {
     use tower::filter::AsyncFilterLayer;
     use tower_test::{mock, mock::Handle};
 
-    use crate::{client::AuthError, config::AuthInfo};
+    use crate::{
+        client::{
+            auth::{ExecToken, RefreshPolicy},
+            AuthError,
+        },
+        config::AuthInfo,
+    };
 
     #[tokio::test(flavor = "current_thread")]
     async fn valid_token() {
@@ -88,6 +94,12 @@ mod tests {
             token: Some(token.clone()),
             ..Default::default()
         };
-        RefreshableToken::Exec(Arc::new(Mutex::new((token, expiry, info))))
+        RefreshableToken::Exec(Arc::new(Mutex::new(ExecToken::new(
+            Some(token),
+            expiry,
+            info,
+            RefreshPolicy::default(),
+            None,
+        ))))
     }
 }
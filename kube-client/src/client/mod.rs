@@ -7,6 +7,8 @@
 //!
 //! The [`Client`] can also be used with [`Discovery`](crate::Discovery) to dynamically
 //! retrieve the resources served by the kubernetes API.
+use std::sync::{Arc, Mutex, PoisonError};
+
 use bytes::Bytes;
 use either::{Either, Left, Right};
 use futures::{self, Stream, StreamExt, TryStream, TryStreamExt};
@@ -23,21 +25,37 @@ use tokio_util::{
     codec::{FramedRead, LinesCodec, LinesCodecError},
     io::StreamReader,
 };
+use tokio::sync::watch;
 use tower::{buffer::Buffer, util::BoxService, BoxError, Layer, Service, ServiceBuilder, ServiceExt};
 use tower_http::{
     classify::ServerErrorsFailureClass, map_response_body::MapResponseBodyLayer, trace::TraceLayer,
 };
 
-use crate::{api::WatchEvent, error::ErrorResponse, Config, Error, Result};
+use crate::{
+    api::{StatsRecorder, WatchEvent},
+    error::ErrorResponse,
+    Config, Error, Result,
+};
 
+mod abort;
 mod auth;
 mod body;
+mod deserialize;
+#[cfg(feature = "gzip")] mod compression;
+#[cfg(feature = "gzip")] use compression::RequestCompression;
 // Add `into_stream()` to `http::Body`
 use body::BodyStreamExt;
 mod config_ext;
+mod connections;
+pub use connections::ConnectionStats;
+use connections::{ConnectionRecorder, CountConnections, RotateIdentity};
 pub use auth::Error as AuthError;
+pub use abort::{abortable, AbortHandle, AbortableStream};
+pub use auth::{Token, TokenProvider};
 pub use config_ext::ConfigExt;
 pub mod middleware;
+mod priority;
+pub use priority::PriorityHint;
 #[cfg(any(feature = "native-tls", feature = "rustls-tls", feature = "openssl-tls"))]
 mod tls;
 #[cfg(feature = "native-tls")] pub use tls::native_tls::Error as NativeTlsError;
@@ -50,6 +68,7 @@ pub use tls::openssl_tls::Error as OpensslTlsError;
 #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
 pub use auth::OAuthError;
 
+#[cfg(feature = "ws")] pub(crate) use upgrade::StreamProtocol;
 #[cfg(feature = "ws")] pub use upgrade::UpgradeConnectionError;
 
 /// Client for connecting with a Kubernetes cluster.
@@ -61,10 +80,28 @@ pub use auth::OAuthError;
 #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
 #[derive(Clone)]
 pub struct Client {
-    // - `Buffer` for cheap clone
-    // - `BoxService` for dynamic response future type
-    inner: Buffer<BoxService<Request<Body>, Response<Body>, BoxError>, Request<Body>>,
+    // Shared by all clones, so that `Client::rebuild` applies to them
+    inner: Arc<Inner>,
     default_ns: String,
+    priority: Option<Arc<PriorityHint>>,
+    #[cfg(feature = "gzip")]
+    compression: Option<Arc<RequestCompression>>,
+    // Statistics of the `Api` this client belongs to
+    stats: Option<Arc<StatsRecorder>>,
+}
+
+// - `Buffer` for cheap clone
+// - `BoxService` for dynamic response future type
+type ClientService = Buffer<BoxService<Request<Body>, Response<Body>, BoxError>, Request<Body>>;
+
+struct Inner {
+    // A `Mutex` rather than a `RwLock`, since `Buffer` is not `Sync`
+    service: Mutex<ClientService>,
+    // Generation of `service`, bumped by `Client::rebuild` to end watches on the previous connections
+    generation: watch::Sender<u64>,
+    generation_rx: watch::Receiver<u64>,
+    // Connections of `service`, when it was created from a `Config`
+    connections: Mutex<Option<Arc<ConnectionRecorder>>>,
 }
 
 impl Client {
@@ -108,12 +145,128 @@ impl Client {
         let service = MapResponseBodyLayer::new(|b: B| Body::wrap_stream(b.into_stream()))
             .layer(service)
             .map_err(|e| e.into());
+        let (generation, generation_rx) = watch::channel(0);
         Self {
-            inner: Buffer::new(BoxService::new(service), 1024),
+            inner: Arc::new(Inner {
+                service: Mutex::new(Buffer::new(BoxService::new(service), 1024)),
+                generation,
+                generation_rx,
+                connections: Mutex::new(None),
+            }),
             default_ns: default_namespace.into(),
+            priority: None,
+            #[cfg(feature = "gzip")]
+            compression: None,
+            stats: None,
+        }
+    }
+
+    /// Rebuild the connections of this [`Client`] and all of its clones with an updated [`Config`]
+    ///
+    /// This allows applying rotated certificates or a new proxy without recreating every [`Api`](crate::Api).
+    /// New requests use connections created from `config`, while requests in flight complete on the previous
+    /// connections, which are closed once they are done.
+    /// Watches on the previous connections are ended, so watchers restart them from their last `resourceVersion`.
+    ///
+    /// The default namespace of the client is not changed.
+    pub fn rebuild(&self, config: Config) -> Result<()> {
+        self.replace_service(&Client::try_from(config)?);
+        Ok(())
+    }
+
+    fn replace_service(&self, other: &Client) {
+        let service = other.service();
+        *self.inner.connections.lock().unwrap_or_else(PoisonError::into_inner) = other.connection_recorder();
+        let mut current = self.inner.service.lock().unwrap_or_else(PoisonError::into_inner);
+        *current = service;
+        // Bumped while holding the lock, so that requests never see a new generation with the previous service
+        let next = *self.inner.generation_rx.borrow() + 1;
+        // Never fails, since `Inner` holds a receiver
+        let _ = self.inner.generation.send(next);
+    }
+
+    fn service(&self) -> ClientService {
+        self.inner
+            .service
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn connection_recorder(&self) -> Option<Arc<ConnectionRecorder>> {
+        self.inner
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Statistics of the connection pool of this [`Client`] and its clones
+    ///
+    /// Returns `None` for clients created with [`Client::new`], whose connections are not known.
+    /// After [`Client::rebuild`], only the connections created from the new [`Config`] are counted.
+    pub fn connection_stats(&self) -> Option<ConnectionStats> {
+        self.connection_recorder().map(|connections| connections.snapshot())
+    }
+
+    // Resolves once the client is rebuilt after `generation`
+    fn rebuilt(&self, generation: u64) -> impl std::future::Future<Output = ()> {
+        let mut rx = self.inner.generation_rx.clone();
+        async move {
+            loop {
+                let current = *rx.borrow();
+                if current != generation {
+                    break;
+                }
+                if rx.changed().await.is_err() {
+                    futures::future::pending::<()>().await;
+                }
+            }
+        }
+    }
+
+    /// Returns a clone of this [`Client`] that tags its requests with a [`PriorityHint`]
+    ///
+    /// The clone shares the connection pool and middleware stack with this client.
+    #[must_use]
+    pub fn with_priority(&self, priority: PriorityHint) -> Self {
+        Self {
+            priority: Some(Arc::new(priority)),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a clone of this [`Client`] that gzips request bodies of at least `min_size` bytes
+    ///
+    /// Only creates, replaces and patches are compressed, which reduces upload time for large manifests over slow links.
+    /// The apiserver does not advertise support for compressed request bodies, so a rejected compressed request
+    /// is retried uncompressed. Compression is turned off for this client if the retry is accepted.
+    #[cfg(feature = "gzip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "gzip")))]
+    #[must_use]
+    pub fn with_request_compression(&self, min_size: usize) -> Self {
+        Self {
+            compression: Some(Arc::new(RequestCompression::new(min_size))),
+            ..self.clone()
+        }
+    }
+
+    // A clone of this client that records the statistics of requests to `url_path` for an `Api`
+    pub(crate) fn with_stats(&self, url_path: &str) -> Self {
+        Self {
+            stats: Some(Arc::new(StatsRecorder::new(url_path))),
+            ..self.clone()
         }
     }
 
+    pub(crate) fn without_stats(self) -> Self {
+        Self { stats: None, ..self }
+    }
+
+    pub(crate) fn stats(&self) -> Option<&StatsRecorder> {
+        self.stats.as_deref()
+    }
+
     /// Create and initialize a [`Client`] using the inferred
     /// configuration.
     ///
@@ -132,8 +285,12 @@ impl Client {
         &self.default_ns
     }
 
-    async fn send(&self, request: Request<Body>) -> Result<Response<Body>> {
-        let mut svc = self.inner.clone();
+    pub(crate) async fn send(&self, mut request: Request<Body>) -> Result<Response<Body>> {
+        if let Some(priority) = &self.priority {
+            priority.apply(request.headers_mut()).map_err(Error::HttpError)?;
+        }
+        let path = self.stats.as_ref().map(|_| request.uri().path().to_string());
+        let mut svc = self.service();
         let res = svc
             .ready()
             .await
@@ -152,9 +309,42 @@ impl Client {
                     Error::Service(err)
                 }
             })?;
+        if let (Some(stats), Some(path)) = (&self.stats, path) {
+            stats.record(&path, res.status());
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "gzip")]
+    async fn send_compressed(&self, request: Request<Vec<u8>>) -> Result<Response<Body>> {
+        let (compression, compressed) = match &self.compression {
+            Some(compression) => match compression.compress(&request) {
+                Some(compressed) => (compression, compressed),
+                None => return self.send(request.map(request_body)).await,
+            },
+            None => return self.send(request.map(request_body)).await,
+        };
+        let res = self.send(compressed.map(request_body)).await?;
+        if !RequestCompression::may_be_rejected(res.status()) {
+            return Ok(res);
+        }
+        let (parts, body) = res.into_parts();
+        let body = hyper::body::to_bytes(body).await.map_err(Error::HyperError)?;
+        if !RequestCompression::is_rejected(parts.status, &body) {
+            return Ok(Response::from_parts(parts, Body::from(body)));
+        }
+        let res = self.send(request.map(request_body)).await?;
+        if res.status().is_success() {
+            compression.set_unsupported();
+        }
         Ok(res)
     }
 
+    #[cfg(not(feature = "gzip"))]
+    async fn send_compressed(&self, request: Request<Vec<u8>>) -> Result<Response<Body>> {
+        self.send(request.map(request_body)).await
+    }
+
     /// Make WebSocket connection.
     #[cfg(feature = "ws")]
     #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
@@ -162,6 +352,33 @@ impl Client {
         &self,
         request: Request<Vec<u8>>,
     ) -> Result<WebSocketStream<hyper::upgrade::Upgraded>> {
+        let (stream, _) = self.upgrade(request, &[upgrade::WS_PROTOCOL]).await?;
+        Ok(stream)
+    }
+
+    // Make WebSocket connection for exec and attach, preferring the subprotocol v5.
+    // Servers that do not know v5 choose v4 instead.
+    #[cfg(feature = "ws")]
+    pub(crate) async fn connect_remote_command(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<(WebSocketStream<hyper::upgrade::Upgraded>, upgrade::StreamProtocol)> {
+        let protocols = [upgrade::WS_PROTOCOL_V5, upgrade::WS_PROTOCOL];
+        let (stream, protocol) = self.upgrade(request, &protocols).await?;
+        let protocol = if protocol == upgrade::WS_PROTOCOL_V5 {
+            upgrade::StreamProtocol::V5
+        } else {
+            upgrade::StreamProtocol::V4
+        };
+        Ok((stream, protocol))
+    }
+
+    #[cfg(feature = "ws")]
+    async fn upgrade(
+        &self,
+        request: Request<Vec<u8>>,
+        protocols: &[&'static str],
+    ) -> Result<(WebSocketStream<hyper::upgrade::Upgraded>, &'static str)> {
         use http::header::HeaderValue;
         let (mut parts, body) = request.into_parts();
         parts
@@ -179,21 +396,24 @@ impl Client {
             http::header::SEC_WEBSOCKET_KEY,
             key.parse().expect("valid header value"),
         );
-        // Use the binary subprotocol v4, to get JSON `Status` object in `error` channel (3).
+        // Use the binary subprotocols v4 and later, to get JSON `Status` object in `error` channel (3).
         // There's no official documentation about this protocol, but it's described in
         // [`k8s.io/apiserver/pkg/util/wsstream/conn.go`](https://git.io/JLQED).
         // There's a comment about v4 and `Status` object in
         // [`kublet/cri/streaming/remotecommand/httpstream.go`](https://git.io/JLQEh).
+        // The server chooses the first subprotocol it supports, in order of preference.
         parts.headers.insert(
             http::header::SEC_WEBSOCKET_PROTOCOL,
-            HeaderValue::from_static(upgrade::WS_PROTOCOL),
+            protocols.join(", ").parse().expect("valid header value"),
         );
 
         let res = self.send(Request::from_parts(parts, Body::from(body))).await?;
-        upgrade::verify_response(&res, &key).map_err(Error::UpgradeConnection)?;
+        let protocol = upgrade::verify_response(&res, &key, protocols).map_err(Error::UpgradeConnection)?;
         match hyper::upgrade::on(res).await {
             Ok(upgraded) => {
-                Ok(WebSocketStream::from_raw_socket(upgraded, ws::protocol::Role::Client, None).await)
+                let role = ws::protocol::Role::Client;
+                let stream = WebSocketStream::from_raw_socket(upgraded, role, None).await;
+                Ok((stream, protocol))
             }
 
             Err(e) => Err(Error::UpgradeConnection(
@@ -210,23 +430,24 @@ impl Client {
     {
         let text = self.request_text(request).await?;
 
-        serde_json::from_str(&text).map_err(|e| {
+        deserialize::from_str(&text).map_err(|e| {
             tracing::warn!("{}, {:?}", text, e);
-            Error::SerdeError(e)
+            Error::Deserialize(e)
         })
     }
 
     /// Perform a raw HTTP request against the API and get back the response
     /// as a string
     pub async fn request_text(&self, request: Request<Vec<u8>>) -> Result<String> {
-        let res = self.send(request.map(Body::from)).await?;
+        let size = request.body().len();
+        let res = self.send_compressed(request).await?;
         let status = res.status();
         // trace!("Status = {:?} for {}", status, res.url());
         let body_bytes = hyper::body::to_bytes(res.into_body())
             .await
             .map_err(Error::HyperError)?;
         let text = String::from_utf8(body_bytes.to_vec()).map_err(Error::FromUtf8)?;
-        handle_api_errors(&text, status)?;
+        handle_api_errors(&text, status).map_err(|err| request_too_large(err, size))?;
 
         Ok(text)
     }
@@ -242,6 +463,16 @@ impl Client {
         Ok(res.into_body().map_err(Error::HyperError))
     }
 
+    /// Perform a raw HTTP request against the API and get back the whole response
+    ///
+    /// Unlike the other methods, responses with error statuses are returned rather than turned into errors.
+    pub async fn request_response(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>> {
+        let res = self.send(request.map(Body::from)).await?;
+        let (parts, body) = res.into_parts();
+        let body = hyper::body::to_bytes(body).await.map_err(Error::HyperError)?;
+        Ok(Response::from_parts(parts, body))
+    }
+
     /// Perform a raw HTTP request against the API and get back either an object
     /// deserialized as JSON or a [`Status`] Object.
     pub async fn request_status<T>(&self, request: Request<Vec<u8>>) -> Result<Either<T, Status>>
@@ -258,9 +489,9 @@ impl Client {
                 Error::SerdeError(e)
             })?))
         } else {
-            Ok(Left(serde_json::from_str::<T>(&text).map_err(|e| {
+            Ok(Left(deserialize::from_str::<T>(&text).map_err(|e| {
                 tracing::warn!("{}, {:?}", text, e);
-                Error::SerdeError(e)
+                Error::Deserialize(e)
             })?))
         }
     }
@@ -273,6 +504,7 @@ impl Client {
     where
         T: Clone + DeserializeOwned,
     {
+        let rebuilt = self.rebuilt(*self.inner.generation_rx.borrow());
         let res = self.send(request.map(Body::from)).await?;
         // trace!("Streaming from {} -> {}", res.url(), res.status().as_str());
         tracing::trace!("headers: {:?}", res.headers());
@@ -293,13 +525,15 @@ impl Client {
             LinesCodec::new(),
         );
 
+        // End the watch when the client is rebuilt, so that it is restarted on the new connections
+        let frames = frames.take_until(rebuilt);
         Ok(frames.filter_map(|res| async {
             match res {
-                Ok(line) => match serde_json::from_str::<WatchEvent<T>>(&line) {
+                Ok(line) => match deserialize::from_str::<WatchEvent<T>>(&line) {
                     Ok(event) => Some(Ok(event)),
                     Err(e) => {
                         // Ignore EOF error that can happen for incomplete line from `decode_eof`.
-                        if e.is_eof() {
+                        if e.source.is_eof() {
                             return None;
                         }
 
@@ -308,7 +542,7 @@ impl Client {
                             return Some(Err(Error::Api(e_resp)));
                         }
                         // Parsing error
-                        Some(Err(Error::SerdeError(e)))
+                        Some(Err(Error::Deserialize(e)))
                     }
                 },
 
@@ -425,7 +659,7 @@ impl Client {
 ///
 /// In either case, present an ApiError upstream.
 /// The latter is probably a bug if encountered.
-fn handle_api_errors(text: &str, s: StatusCode) -> Result<()> {
+pub(crate) fn handle_api_errors(text: &str, s: StatusCode) -> Result<()> {
     if s.is_client_error() || s.is_server_error() {
         // Print better debug when things do fail
         // trace!("Parsing error: {}", text);
@@ -439,6 +673,7 @@ fn handle_api_errors(text: &str, s: StatusCode) -> Result<()> {
                 code: s.as_u16(),
                 message: format!("{:?}", text),
                 reason: "Failed to parse error data".into(),
+                details: None,
             };
             tracing::debug!("Unsuccessful: {:?} (reconstruct)", ae);
             Err(Error::Api(ae))
@@ -448,6 +683,37 @@ fn handle_api_errors(text: &str, s: StatusCode) -> Result<()> {
     }
 }
 
+/// Size of the chunks that request bodies larger than it are streamed in
+const BODY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Stream large request bodies with chunked transfer encoding, so they are written to the connection piece by piece
+fn request_body(body: Vec<u8>) -> Body {
+    if body.len() <= BODY_CHUNK_SIZE {
+        return Body::from(body);
+    }
+    let body = Bytes::from(body);
+    let chunks = (0..body.len()).step_by(BODY_CHUNK_SIZE).map(move |start| {
+        let end = (start + BODY_CHUNK_SIZE).min(body.len());
+        Ok::<_, std::convert::Infallible>(body.slice(start..end))
+    });
+    Body::wrap_stream(futures::stream::iter(chunks))
+}
+
+/// Explain errors caused by the size of the request body, which otherwise only mention the limit
+fn request_too_large(err: Error, size: usize) -> Error {
+    match err {
+        // Rejected by the apiserver, or by etcd (which the apiserver passes on as an internal error)
+        Error::Api(ae)
+            if ae.code == StatusCode::PAYLOAD_TOO_LARGE.as_u16()
+                || (ae.code == StatusCode::INTERNAL_SERVER_ERROR.as_u16()
+                    && ae.message.contains("request is too large")) =>
+        {
+            Error::RequestTooLarge { size, source: ae }
+        }
+        err => err,
+    }
+}
+
 impl TryFrom<Config> for Client {
     type Error = Error;
 
@@ -458,41 +724,80 @@ impl TryFrom<Config> for Client {
         use http::header::HeaderMap;
         use tracing::Span;
 
+        // Exec plugins may issue client certificates, which the connector presents once they are issued
+        let policy = auth::RefreshPolicy::new(config.token_refresh_ratio, config.token_refresh_jitter);
+        let (auth, exec_identity) = match &config.token_provider {
+            Some(provider) => (auth::Auth::from_token_provider(provider.clone(), policy), None),
+            None => auth::Auth::with_identity(
+                &config.auth_info,
+                policy,
+                config.exec_timeout,
+                config.persist_auth_provider_tokens,
+                config.native_eks_tokens,
+            )
+            .map_err(Error::Auth)?,
+        };
+        let exec_identity =
+            exec_identity.filter(|_| config.identity_pem.is_none() && config.identity_pkcs12.is_none());
+
         let timeout = config.timeout;
         let default_ns = config.default_namespace.clone();
+        let connections = Arc::new(ConnectionRecorder::default());
+
+        let build_connector = {
+            let config = config.clone();
+            move |identity_pem: Option<Vec<u8>>| -> Result<_> {
+                let mut config = config.clone();
+                if identity_pem.is_some() {
+                    config.identity_pem = identity_pem;
+                }
+                let mut connector = HttpConnector::new();
+                connector.enforce_http(false);
+
+                // Current TLS feature precedence when more than one are set:
+                // 1. openssl-tls
+                // 2. native-tls
+                // 3. rustls-tls
+                // Create a custom client to use something else.
+                // If TLS features are not enabled, http connector will be used.
+                #[cfg(feature = "openssl-tls")]
+                let connector = config.openssl_https_connector_with_connector(connector)?;
+                #[cfg(all(not(feature = "openssl-tls"), feature = "native-tls"))]
+                let connector = tls::native_tls::https_connector_with_server_name(
+                    connector,
+                    tokio_native_tls::TlsConnector::from(config.native_tls_connector()?),
+                    &config.cluster_url,
+                    config.tls_server_name.as_deref(),
+                    &config.tls_pinned_spki_sha256,
+                );
+                #[cfg(all(
+                    not(any(feature = "openssl-tls", feature = "native-tls")),
+                    feature = "rustls-tls"
+                ))]
+                let connector = config.rustls_https_connector_with_connector(connector)?;
+                Ok(connector)
+            }
+        };
 
         let client: hyper::Client<_, Body> = {
-            let mut connector = HttpConnector::new();
-            connector.enforce_http(false);
-
-            // Current TLS feature precedence when more than one are set:
-            // 1. openssl-tls
-            // 2. native-tls
-            // 3. rustls-tls
-            // Create a custom client to use something else.
-            // If TLS features are not enabled, http connector will be used.
-            #[cfg(feature = "openssl-tls")]
-            let connector = config.openssl_https_connector_with_connector(connector)?;
-            #[cfg(all(not(feature = "openssl-tls"), feature = "native-tls"))]
-            let connector = hyper_tls::HttpsConnector::from((
-                connector,
-                tokio_native_tls::TlsConnector::from(config.native_tls_connector()?),
-            ));
-            #[cfg(all(
-                not(any(feature = "openssl-tls", feature = "native-tls")),
-                feature = "rustls-tls"
-            ))]
-            let connector = hyper_rustls::HttpsConnector::from((
-                connector,
-                std::sync::Arc::new(config.rustls_client_config()?),
-            ));
-
-            let mut connector = TimeoutConnector::new(connector);
+            let connector = RotateIdentity::new(build_connector, exec_identity)?;
+            let mut connector = TimeoutConnector::new(CountConnections::new(connector, connections.clone()));
             connector.set_connect_timeout(timeout);
             connector.set_read_timeout(timeout);
 
-            hyper::Client::builder().build(connector)
+            // The pool closes connections that stay idle for longer than `pool_idle_timeout`
+            hyper::Client::builder()
+                .pool_idle_timeout(config.pool_idle_timeout)
+                .pool_max_idle_per_host(config.pool_max_idle_per_host)
+                .build(connector)
         };
+        let recorder = connections.clone();
+        let client = ServiceBuilder::new()
+            .map_request(move |req: Request<Body>| {
+                recorder.record_request();
+                req
+            })
+            .service(client);
 
         let stack = ServiceBuilder::new().layer(config.base_uri_layer()).into_inner();
         #[cfg(feature = "gzip")]
@@ -503,7 +808,7 @@ impl TryFrom<Config> for Client {
 
         let service = ServiceBuilder::new()
             .layer(stack)
-            .option_layer(config.auth_layer()?)
+            .option_layer(config_ext::auth_layer(auth))
             .layer(
                 // Attribute names follow [Semantic Conventions].
                 // [Semantic Conventions]: https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/trace/semantic_conventions/http.md
@@ -553,7 +858,9 @@ impl TryFrom<Config> for Client {
                     }),
             )
             .service(client);
-        Ok(Self::new(service, default_ns))
+        let client = Self::new(service, default_ns);
+        *client.inner.connections.lock().unwrap_or_else(PoisonError::into_inner) = Some(connections);
+        Ok(client)
     }
 }
 
@@ -600,4 +907,893 @@ mod tests {
         assert_eq!(pod.metadata.annotations.unwrap().get("kube-rs").unwrap(), "test");
         spawned.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_list_metadata() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/api/v1/namespaces/default/pods?");
+            assert_eq!(
+                request.headers()[http::header::ACCEPT],
+                "application/json;as=PartialObjectMetadataList;g=meta.k8s.io;v=v1"
+            );
+            let list = serde_json::json!({
+                "apiVersion": "meta.k8s.io/v1",
+                "kind": "PartialObjectMetadataList",
+                "metadata": { "resourceVersion": "42" },
+                "items": [{
+                    "apiVersion": "meta.k8s.io/v1",
+                    "kind": "PartialObjectMetadata",
+                    "metadata": { "name": "test", "labels": { "app": "blog" } },
+                }],
+            });
+            send.send_response(
+                Response::builder()
+                    .body(Body::from(serde_json::to_vec(&list).unwrap()))
+                    .unwrap(),
+            );
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let list = pods.list_metadata(&Default::default()).await.unwrap();
+        assert_eq!(list.metadata.resource_version.as_deref(), Some("42"));
+        assert_eq!(list.items[0].metadata.name.as_deref(), Some("test"));
+        assert_eq!(list.items[0].types.as_ref().unwrap().kind, "PartialObjectMetadata");
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_stream_pages() {
+        use crate::api::ListParams;
+        use futures::TryStreamExt;
+
+        fn pods(names: &[&str], continue_: &str) -> Body {
+            let items = names
+                .iter()
+                .map(|name| serde_json::json!({ "metadata": { "name": name } }))
+                .collect::<Vec<_>>();
+            let list = serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "PodList",
+                "metadata": { "resourceVersion": "42", "continue": continue_ },
+                "items": items,
+            });
+            Body::from(serde_json::to_vec(&list).unwrap())
+        }
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/api/v1/namespaces/default/pods?&limit=2");
+            send.send_response(Response::builder().body(pods(&["a", "b"], "c")).unwrap());
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(
+                request.uri().to_string(),
+                "/api/v1/namespaces/default/pods?&limit=2&continue=c"
+            );
+            send.send_response(Response::builder().body(pods(&["c", "d"], "e")).unwrap());
+
+            // The continue token expired, so the rest is listed at once
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(
+                request.uri().to_string(),
+                "/api/v1/namespaces/default/pods?&limit=2&continue=e"
+            );
+            let expired = serde_json::json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "status": "Failure",
+                "message": "The provided continue parameter is too old",
+                "reason": "Expired",
+                "code": 410,
+            });
+            send.send_response(
+                Response::builder()
+                    .status(410)
+                    .body(Body::from(serde_json::to_vec(&expired).unwrap()))
+                    .unwrap(),
+            );
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/api/v1/namespaces/default/pods?");
+            send.send_response(Response::builder().body(pods(&["a", "e"], "")).unwrap());
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let names = pods
+            .list_stream(&ListParams::default().limit(2))
+            .map_ok(|pod| pod.metadata.name.unwrap())
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(names, ["a", "b", "c", "d", "a", "e"]);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discovery_partial() {
+        use crate::discovery::Discovery;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/apis");
+            let groups = serde_json::json!({
+                "kind": "APIGroupList",
+                "apiVersion": "v1",
+                "groups": [
+                    {
+                        "name": "apps",
+                        "versions": [{ "groupVersion": "apps/v1", "version": "v1" }],
+                        "preferredVersion": { "groupVersion": "apps/v1", "version": "v1" },
+                    },
+                    {
+                        "name": "metrics.k8s.io",
+                        "versions": [{ "groupVersion": "metrics.k8s.io/v1beta1", "version": "v1beta1" }],
+                    },
+                ],
+            });
+            send.send_response(Response::builder().body(Body::from(groups.to_string())).unwrap());
+
+            for _ in 0..2 {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                let response = match request.uri().path() {
+                    "/apis/apps/v1" => {
+                        let resources = serde_json::json!({
+                            "kind": "APIResourceList",
+                            "groupVersion": "apps/v1",
+                            "resources": [{
+                                "name": "deployments",
+                                "singularName": "",
+                                "namespaced": true,
+                                "kind": "Deployment",
+                                "verbs": ["get", "list", "watch"],
+                            }],
+                        });
+                        Response::builder().body(Body::from(resources.to_string()))
+                    }
+                    "/apis/metrics.k8s.io/v1beta1" => {
+                        let unavailable = serde_json::json!({
+                            "kind": "Status",
+                            "apiVersion": "v1",
+                            "status": "Failure",
+                            "message": "the server is currently unable to handle the request",
+                            "reason": "ServiceUnavailable",
+                            "code": 503,
+                        });
+                        Response::builder()
+                            .status(503)
+                            .body(Body::from(unavailable.to_string()))
+                    }
+                    path => panic!("unexpected request to {}", path),
+                };
+                send.send_response(response.unwrap());
+            }
+        });
+
+        let discovery = Discovery::new(Client::new(mock_service, "default"))
+            .exclude(&[""])
+            .partial()
+            .run()
+            .await
+            .unwrap();
+        assert!(discovery.has_group("apps"));
+        assert!(!discovery.has_group("metrics.k8s.io"));
+        let failed = discovery.failed_groups().map(|(group, _)| group).collect::<Vec<_>>();
+        assert_eq!(failed, ["metrics.k8s.io"]);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discovery_retries_transient_errors() {
+        use crate::{discovery::Discovery, Error};
+        use std::time::Duration;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/apis");
+            let groups = serde_json::json!({
+                "kind": "APIGroupList",
+                "apiVersion": "v1",
+                "groups": [{
+                    "name": "apps",
+                    "versions": [{ "groupVersion": "apps/v1", "version": "v1" }],
+                    "preferredVersion": { "groupVersion": "apps/v1", "version": "v1" },
+                }],
+            });
+            send.send_response(Response::builder().body(Body::from(groups.to_string())).unwrap());
+
+            // Unavailable is retried, forbidden is not
+            for (code, reason) in [(503, "ServiceUnavailable"), (403, "Forbidden")] {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.uri().path(), "/apis/apps/v1");
+                let status = serde_json::json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "status": "Failure",
+                    "message": reason,
+                    "reason": reason,
+                    "code": code,
+                });
+                send.send_response(
+                    Response::builder()
+                        .status(code)
+                        .body(Body::from(status.to_string()))
+                        .unwrap(),
+                );
+            }
+        });
+
+        let discovery = Discovery::new(Client::new(mock_service, "default"))
+            .exclude(&[""])
+            .retry(3, Duration::from_millis(1))
+            .run()
+            .await;
+        match discovery {
+            Err(Error::Api(ae)) => assert_eq!(ae.code, 403),
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_follow_log_lines_waits_for_container() {
+        use crate::api::LogParams;
+        use futures::TryStreamExt;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let pod = |state: serde_json::Value| {
+                serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "metadata": { "name": "web-0" },
+                    "status": {
+                        "phase": "Running",
+                        "containerStatuses": [{
+                            "name": "web", "image": "", "imageID": "", "ready": false, "restartCount": 0,
+                            "state": state,
+                        }],
+                    },
+                })
+            };
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().path(), "/api/v1/namespaces/default/pods/web-0/log");
+            let waiting = serde_json::json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "status": "Failure",
+                "message": "container \"web\" in pod \"web-0\" is waiting to start: ContainerCreating",
+                "reason": "BadRequest",
+                "code": 400,
+            });
+            send.send_response(
+                Response::builder()
+                    .status(400)
+                    .body(Body::from(waiting.to_string()))
+                    .unwrap(),
+            );
+
+            // The container has not terminated, so the logs are requested again
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().path(), "/api/v1/namespaces/default/pods/web-0");
+            let created = pod(serde_json::json!({ "waiting": { "reason": "ContainerCreating" } }));
+            send.send_response(Response::builder().body(Body::from(created.to_string())).unwrap());
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().path(), "/api/v1/namespaces/default/pods/web-0/log");
+            let logs = "2021-11-02T10:00:01Z started\n2021-11-02T10:00:02Z done\n";
+            send.send_response(Response::builder().body(Body::from(logs)).unwrap());
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().path(), "/api/v1/namespaces/default/pods/web-0");
+            let done = pod(serde_json::json!({ "terminated": { "exitCode": 0 } }));
+            send.send_response(Response::builder().body(Body::from(done.to_string())).unwrap());
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let lines: Vec<_> = pods
+            .follow_log_lines("web-0", &LogParams::default())
+            .map_ok(|line| line.message)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(lines, ["started", "done"]);
+        spawned.await.unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_compressed_request_falls_back_after_decode_error() {
+        use crate::api::PostParams;
+        use http::header::CONTENT_ENCODING;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.headers()[CONTENT_ENCODING], "gzip");
+            let decode_error = serde_json::json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "status": "Failure",
+                "message": "couldn't get version/kind; json parse error: invalid character '\\x1f' looking for beginning of value",
+                "reason": "BadRequest",
+                "code": 400,
+            });
+            send.send_response(
+                Response::builder()
+                    .status(400)
+                    .body(Body::from(decode_error.to_string()))
+                    .unwrap(),
+            );
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert!(request.headers().get(CONTENT_ENCODING).is_none());
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            send.send_response(Response::builder().body(Body::from(body)).unwrap());
+        });
+
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "test", "annotations": { "data": "x".repeat(1024) } },
+        }))
+        .unwrap();
+        let client = Client::new(mock_service, "default").with_request_compression(512);
+        let pods: Api<Pod> = Api::default_namespaced(client);
+        let created = pods.create(&PostParams::default(), &pod).await.unwrap();
+        assert_eq!(created, pod);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_or_get_existing() {
+        use crate::api::{CreateOrGet, PostParams};
+
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "test" },
+        }))
+        .unwrap();
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let existing = pod.clone();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::POST);
+            send.send_response(
+                Response::builder()
+                    .status(409)
+                    .body(Body::from(
+                        serde_json::to_vec(&serde_json::json!({
+                            "kind": "Status",
+                            "apiVersion": "v1",
+                            "status": "Failure",
+                            "message": "pods \"test\" already exists",
+                            "reason": "AlreadyExists",
+                            "code": 409,
+                        }))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            );
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::GET);
+            assert_eq!(request.uri().to_string(), "/api/v1/namespaces/default/pods/test");
+            send.send_response(
+                Response::builder()
+                    .body(Body::from(serde_json::to_vec(&existing).unwrap()))
+                    .unwrap(),
+            );
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let res = pods.create_or_get(&PostParams::default(), &pod).await.unwrap();
+        assert!(matches!(res, CreateOrGet::Existing(_)));
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_ends_watches() {
+        use crate::api::{ListParams, WatchEvent};
+        use futures::StreamExt;
+
+        let (old_service, old_handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let (new_service, new_handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let (mut watch_body, body) = Body::channel();
+        let old_server = tokio::spawn(async move {
+            pin_mut!(old_handle);
+            let (_request, send) = old_handle.next_request().await.expect("service not called");
+            send.send_response(Response::builder().body(body).unwrap());
+        });
+        let new_server = tokio::spawn(async move {
+            pin_mut!(new_handle);
+            let (request, send) = new_handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/api/v1/namespaces/default/pods/test");
+            let pod = serde_json::json!({ "apiVersion": "v1", "kind": "Pod", "metadata": { "name": "test" } });
+            send.send_response(Response::builder().body(Body::from(pod.to_string())).unwrap());
+        });
+
+        let client = Client::new(old_service, "default");
+        let pods: Api<Pod> = Api::default_namespaced(client.clone());
+        let stream = pods.watch(&ListParams::default(), "0").await.unwrap();
+        pin_mut!(stream);
+        let event = serde_json::json!({
+            "type": "ADDED",
+            "object": { "apiVersion": "v1", "kind": "Pod", "metadata": { "name": "test" } },
+        });
+        watch_body
+            .send_data(format!("{}\n", event).into())
+            .await
+            .unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(WatchEvent::Added(_)))));
+
+        client.replace_service(&Client::new(new_service, "default"));
+        // The watch ends, even though the previous connection is still open
+        assert!(stream.next().await.is_none());
+        // Clones of the client use the new service
+        pods.get("test").await.unwrap();
+        old_server.await.unwrap();
+        new_server.await.unwrap();
+        drop(watch_body);
+    }
+
+    #[tokio::test]
+    async fn test_large_request_bodies_are_streamed() {
+        use crate::api::{Patch, PatchParams};
+        use hyper::body::HttpBody;
+
+        let data = "x".repeat(3 * super::BODY_CHUNK_SIZE);
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert!(request.headers().get(http::header::CONTENT_LENGTH).is_none());
+            assert_eq!(request.body().size_hint().exact(), None);
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            let pod: Pod = serde_json::from_slice(&body).unwrap();
+            send.send_response(
+                Response::builder()
+                    .body(Body::from(serde_json::to_vec(&pod).unwrap()))
+                    .unwrap(),
+            );
+        });
+
+        let patch = serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "test", "annotations": { "data": data } },
+        });
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let pp = PatchParams::apply("test");
+        let pod = pods.patch("test", &pp, &Patch::Apply(&patch)).await.unwrap();
+        assert_eq!(pod.metadata.annotations.unwrap()["data"], data);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_too_large() {
+        use crate::{api::PostParams, Error};
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (_request, send) = handle.next_request().await.expect("service not called");
+            send.send_response(
+                Response::builder()
+                    .status(500)
+                    .body(Body::from(
+                        serde_json::json!({
+                            "kind": "Status",
+                            "apiVersion": "v1",
+                            "status": "Failure",
+                            "message": "etcdserver: request is too large",
+                            "code": 500,
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            );
+        });
+
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "test", "annotations": { "data": "x".repeat(1024) } },
+        }))
+        .unwrap();
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        match pods.create(&PostParams::default(), &pod).await {
+            Err(Error::RequestTooLarge { size, source }) => {
+                assert!(size > 1024);
+                assert_eq!(source.code, 500);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_retries_conflicts() {
+        use crate::{api::ApplyParams, Error};
+        use std::time::Duration;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let conflict = serde_json::json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "status": "Failure",
+                "message": "Apply failed with 1 conflict: conflict with \"kubectl\": .metadata.labels.app",
+                "reason": "Conflict",
+                "details": { "causes": [{
+                    "reason": "FieldManagerConflict",
+                    "message": "conflict with \"kubectl\"",
+                    "field": ".metadata.labels.app",
+                }]},
+                "code": 409,
+            });
+            for _ in 0..3 {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.method(), http::Method::PATCH);
+                assert_eq!(
+                    request.uri().to_string(),
+                    "/api/v1/namespaces/default/pods/blog?&fieldManager=myapp"
+                );
+                send.send_response(
+                    Response::builder()
+                        .status(409)
+                        .body(Body::from(conflict.to_string()))
+                        .unwrap(),
+                );
+            }
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let ap = ApplyParams::new("myapp").retry(2, Duration::from_millis(1));
+        let patch = serde_json::json!({ "metadata": { "labels": { "app": "blog" } } });
+        match pods.apply("blog", &ap, &patch).await {
+            Err(Error::ApplyConflict(report)) => {
+                assert_eq!(report.conflicts.len(), 1);
+                assert_eq!(report.conflicts[0].manager, "kubectl");
+                assert_eq!(report.source.code, 409);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_entry_retries_lost_create() {
+        use crate::api::Committed;
+        use http::Method;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let status = |code: u16, reason: &str| {
+                let status = serde_json::json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "status": "Failure",
+                    "message": reason,
+                    "reason": reason,
+                    "code": code,
+                });
+                Response::builder()
+                    .status(code)
+                    .body(Body::from(status.to_string()))
+                    .unwrap()
+            };
+            let pod = |labels: serde_json::Value| {
+                let pod = serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "metadata": { "name": "blog", "resourceVersion": "1", "labels": labels },
+                });
+                Response::builder().body(Body::from(pod.to_string())).unwrap()
+            };
+            let expected = [
+                (Method::GET, status(404, "NotFound")),
+                (Method::POST, status(409, "AlreadyExists")),
+                (Method::GET, pod(serde_json::json!({ "app": "other" }))),
+                (Method::PUT, pod(serde_json::json!({ "app": "blog" }))),
+            ];
+            for (method, response) in expected {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.method(), &method);
+                if method == Method::PUT {
+                    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                    let pod: Pod = serde_json::from_slice(&body).unwrap();
+                    assert_eq!(pod.metadata.resource_version.as_deref(), Some("1"));
+                }
+                send.send_response(response);
+            }
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let committed = pods
+            .entry("blog")
+            .or_insert_with(Pod::default)
+            .and_modify(|pod| {
+                pod.metadata
+                    .labels
+                    .get_or_insert_with(Default::default)
+                    .insert("app".into(), "blog".into());
+            })
+            .commit()
+            .await
+            .unwrap();
+        assert!(matches!(committed, Committed::Updated(_)));
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_status() {
+        use crate::api::ApplyParams;
+        use kube::CustomResource;
+        use schemars::JsonSchema;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+        #[kube(group = "clux.dev", version = "v1", kind = "Foo", namespaced, status = "FooStatus")]
+        struct FooSpec {}
+
+        #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+        struct FooStatus {
+            ready: bool,
+        }
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::PATCH);
+            assert_eq!(
+                request.uri().to_string(),
+                "/apis/clux.dev/v1/namespaces/default/foos/baz/status?&force=true&fieldManager=foo-controller"
+            );
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            let applied: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                applied,
+                serde_json::json!({ "apiVersion": "clux.dev/v1", "kind": "Foo", "status": { "ready": true } })
+            );
+            let foo = serde_json::json!({
+                "apiVersion": "clux.dev/v1",
+                "kind": "Foo",
+                "metadata": { "name": "baz" },
+                "spec": {},
+                "status": { "ready": true },
+            });
+            send.send_response(Response::builder().body(Body::from(foo.to_string())).unwrap());
+        });
+
+        let foos: Api<Foo> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let ap = ApplyParams::new("foo-controller").force();
+        let foo = foos.apply_status("baz", &ap, &FooStatus { ready: true }).await.unwrap();
+        assert!(foo.status.unwrap().ready);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_subresources() {
+        use crate::api::{ApiResource, DynamicObject, GroupVersionKind, Patch, PatchParams};
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let scale = serde_json::json!({
+                "apiVersion": "autoscaling/v1",
+                "kind": "Scale",
+                "metadata": { "name": "baz" },
+                "spec": { "replicas": 3 },
+            });
+            let expected = [
+                (http::Method::GET, "/apis/clux.dev/v1/namespaces/default/foos/baz/scale"),
+                (http::Method::PATCH, "/apis/clux.dev/v1/namespaces/default/foos/baz/approval?"),
+            ];
+            for (method, uri) in expected {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.method(), &method);
+                assert_eq!(request.uri().to_string(), uri);
+                send.send_response(Response::builder().body(Body::from(scale.to_string())).unwrap());
+            }
+        });
+
+        let ar = ApiResource::from_gvk(&GroupVersionKind::gvk("clux.dev", "v1", "Foo"));
+        let client = Client::new(mock_service, "default");
+        let foos: Api<DynamicObject> = Api::default_namespaced_with(client, &ar);
+        let scale = foos.get_subresource("scale", "baz").await.unwrap();
+        assert_eq!(scale.data["spec"]["replicas"], 3);
+        let patch = Patch::Merge(serde_json::json!({ "approved": true }));
+        foos.patch_subresource("approval", "baz", &PatchParams::default(), &patch)
+            .await
+            .unwrap();
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_expired() {
+        use crate::{api::ListParams, Error};
+        use futures::StreamExt;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(
+                request.uri().to_string(),
+                "/api/v1/namespaces/default/pods?&watch=true&resourceVersion=12&timeoutSeconds=290&allowWatchBookmarks=true"
+            );
+            let event = serde_json::json!({
+                "type": "ERROR",
+                "object": {
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "status": "Failure",
+                    "message": "too old resource version: 12 (34)",
+                    "reason": "Expired",
+                    "code": 410,
+                },
+            });
+            send.send_response(Response::builder().body(Body::from(format!("{}\n", event))).unwrap());
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let stream = pods.watch(&ListParams::default(), "12").await.unwrap();
+        pin_mut!(stream);
+        match stream.next().await {
+            Some(Err(Error::WatchExpired { latest_rv, source })) => {
+                assert_eq!(latest_rv.as_deref(), Some("34"));
+                assert_eq!(source.reason, "Expired");
+            }
+            other => panic!("expected an expired watch, got {:?}", other),
+        }
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chunked_roundtrip() {
+        use crate::api::{ChunkParams, ResourceExt};
+        use bytes::Bytes;
+        use futures::{stream, TryStreamExt};
+        use k8s_openapi::api::core::v1::ConfigMap;
+        use std::collections::HashMap;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let mut stored = HashMap::new();
+            // One read and the creation of the index, three parts and the index, then the download
+            for _ in 0..(2 + 4 + 4) {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                let path = request.uri().path().to_string();
+                let name = path.rsplit('/').next().unwrap().to_string();
+                if request.method() == http::Method::PATCH {
+                    assert_eq!(request.uri().query(), Some("&force=true&fieldManager=bundler"));
+                    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                    stored.insert(name, body.clone());
+                    send.send_response(Response::builder().body(Body::from(body)).unwrap());
+                } else if let Some(body) = stored.get(&name) {
+                    send.send_response(Response::builder().body(Body::from(body.clone())).unwrap());
+                } else {
+                    let not_found = serde_json::json!({
+                        "kind": "Status",
+                        "apiVersion": "v1",
+                        "status": "Failure",
+                        "message": "not found",
+                        "reason": "NotFound",
+                        "code": 404,
+                    });
+                    send.send_response(
+                        Response::builder()
+                            .status(404)
+                            .body(Body::from(not_found.to_string()))
+                            .unwrap(),
+                    );
+                }
+            }
+            let index: ConfigMap = serde_json::from_slice(&stored["plugins"]).unwrap();
+            let upload = &index.annotations()["kube.rs/chunked-upload"];
+            let mut names = stored.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            let parts = (0..3).map(|i| format!("plugins-{}-{}", upload, i));
+            assert_eq!(names, std::iter::once("plugins".to_string()).chain(parts).collect::<Vec<_>>());
+            let part: ConfigMap = serde_json::from_slice(&stored[&names[1]]).unwrap();
+            let owners = part.metadata.owner_references.unwrap();
+            assert_eq!((owners[0].kind.as_str(), owners[0].name.as_str()), ("ConfigMap", "plugins"));
+        });
+
+        let cms: Api<ConfigMap> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let data = stream::iter(vec![
+            Ok::<_, std::io::Error>(Bytes::from_static(b"hello")),
+            Ok(Bytes::from_static(b" world")),
+        ]);
+        let cp = ChunkParams::new("bundler").chunk_size(4);
+        cms.upload_chunked("plugins", data, &cp).await.unwrap();
+        let parts = cms
+            .download_chunked("plugins")
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(parts.concat(), b"hello world");
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_proxy_http() {
+        use k8s_openapi::api::core::v1::Service;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::PUT);
+            assert_eq!(
+                request.uri().to_string(),
+                "/api/v1/namespaces/default/services/dashboard:8443/proxy/settings?theme=dark%20mode"
+            );
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            assert_eq!(&body[..], b"{}");
+            send.send_response(Response::builder().status(403).body(Body::from("forbidden")).unwrap());
+        });
+
+        let services: Api<Service> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let request = Request::put("/settings?theme=dark%20mode").body(b"{}".to_vec()).unwrap();
+        let response = services
+            .proxy_http("dashboard", Some("8443"), request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        assert_eq!(&response.body()[..], b"forbidden");
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_priority_hint_headers() {
+        use super::PriorityHint;
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            let headers = request.headers();
+            assert_eq!(
+                headers.get(http::header::USER_AGENT).unwrap(),
+                concat!("kube-rs/", env!("CARGO_PKG_VERSION"), " relist")
+            );
+            assert_eq!(headers.get("impersonate-user").unwrap(), "controller");
+            let groups = headers.get_all("impersonate-group").iter().collect::<Vec<_>>();
+            assert_eq!(groups, vec!["background", "system:authenticated"]);
+            send.send_response(Response::builder().body(Body::from("ok")).unwrap());
+        });
+
+        let client = Client::new(mock_service, "default").with_priority(
+            PriorityHint::default()
+                .user_agent_suffix("relist")
+                .impersonate("controller", &["background", "system:authenticated"]),
+        );
+        let text = client
+            .request_text(Request::get("/version").body(vec![]).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(text, "ok");
+        spawned.await.unwrap();
+    }
 }
@@ -5,6 +5,16 @@ use tokio_tungstenite::tungstenite as ws;
 
 // Binary subprotocol v4. See `Client::connect`.
 pub const WS_PROTOCOL: &str = "v4.channel.k8s.io";
+// Binary subprotocol v5, which adds closing a single channel.
+// Supported by Kubernetes 1.29+ for exec and attach.
+pub const WS_PROTOCOL_V5: &str = "v5.channel.k8s.io";
+
+// The subprotocol the server chose for exec and attach
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamProtocol {
+    V4,
+    V5,
+}
 
 /// Possible errors from upgrading to a WebSocket connection
 #[cfg(feature = "ws")]
@@ -42,7 +52,12 @@ pub enum UpgradeConnectionError {
 
 // Verify upgrade response according to RFC6455.
 // Based on `tungstenite` and added subprotocol verification.
-pub fn verify_response(res: &Response<Body>, key: &str) -> Result<(), UpgradeConnectionError> {
+// Returns the subprotocol the server chose out of the requested `protocols`.
+pub fn verify_response(
+    res: &Response<Body>,
+    key: &str,
+    protocols: &[&'static str],
+) -> Result<&'static str, UpgradeConnectionError> {
     if res.status() != StatusCode::SWITCHING_PROTOCOLS {
         return Err(UpgradeConnectionError::ProtocolSwitch(res.status()));
     }
@@ -75,16 +90,12 @@ pub fn verify_response(res: &Response<Body>, key: &str) -> Result<(), UpgradeCon
         return Err(UpgradeConnectionError::SecWebSocketAcceptKeyMismatch);
     }
 
-    // Make sure that the server returned the correct subprotocol.
-    if !headers
+    // Make sure that the server returned one of the requested subprotocols.
+    headers
         .get(http::header::SEC_WEBSOCKET_PROTOCOL)
-        .map(|h| h == WS_PROTOCOL)
-        .unwrap_or(false)
-    {
-        return Err(UpgradeConnectionError::SecWebSocketProtocolMismatch);
-    }
-
-    Ok(())
+        .and_then(|h| protocols.iter().find(|p| h == **p))
+        .copied()
+        .ok_or(UpgradeConnectionError::SecWebSocketProtocolMismatch)
 }
 
 /// Generate a random key for the `Sec-WebSocket-Key` header.
@@ -0,0 +1,398 @@
+//! Bootstrapping a Config from the `cluster-info` ConfigMap with a bootstrap token, like `kubeadm join`
+use hmac::{Hmac, Mac, NewMac};
+use k8s_openapi::api::core::v1::ConfigMap;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::{AuthInfo, Config, Kubeconfig, KubeconfigError};
+use crate::{Api, Client};
+
+const CLUSTER_INFO_NAMESPACE: &str = "kube-public";
+const CLUSTER_INFO_NAME: &str = "cluster-info";
+const KUBECONFIG_KEY: &str = "kubeconfig";
+const SIGNATURE_KEY_PREFIX: &str = "jws-kubeconfig-";
+const CA_CERT_HASH_PREFIX: &str = "sha256:";
+
+/// Errors from bootstrapping a Config from the `cluster-info` ConfigMap
+#[derive(Error, Debug)]
+pub enum BootstrapError {
+    /// The bootstrap token is not of the form `[a-z0-9]{6}.[a-z0-9]{16}`
+    #[error("invalid bootstrap token")]
+    InvalidToken,
+
+    /// Failed to create a client for the discovery
+    #[error("failed to create discovery client: {0}")]
+    CreateClient(#[source] crate::Error),
+
+    /// Failed to fetch the `cluster-info` ConfigMap
+    #[error("failed to fetch cluster-info: {0}")]
+    FetchClusterInfo(#[source] crate::Error),
+
+    /// The `cluster-info` ConfigMap has no kubeconfig
+    #[error("cluster-info has no kubeconfig")]
+    MissingKubeconfig,
+
+    /// The `cluster-info` ConfigMap has no signature for the token, which may have expired
+    #[error("cluster-info has no signature for token {0}")]
+    MissingSignature(String),
+
+    /// The signature of the `cluster-info` ConfigMap does not match the token
+    #[error("cluster-info signature does not match the token")]
+    InvalidSignature,
+
+    /// The kubeconfig of the `cluster-info` ConfigMap is invalid
+    #[error("invalid cluster-info kubeconfig: {0}")]
+    Kubeconfig(#[source] KubeconfigError),
+
+    /// The kubeconfig of the `cluster-info` ConfigMap has no cluster
+    #[error("cluster-info kubeconfig has no cluster")]
+    MissingCluster,
+
+    /// No CA certificate hashes were given to verify the CA of the cluster
+    #[error("no CA certificate hashes to verify the cluster CA with")]
+    MissingCaCertHashes,
+
+    /// A CA certificate hash is not of the form `sha256:<hex>`
+    #[error("invalid CA certificate hash {0}, expected sha256:<hex>")]
+    InvalidCaCertHash(String),
+
+    /// The kubeconfig of the `cluster-info` ConfigMap has no certificate authority
+    #[error("cluster-info kubeconfig has no certificate authority")]
+    MissingCertificateAuthority,
+
+    /// None of the CA certificates of the `cluster-info` ConfigMap match the CA certificate hashes
+    #[error("cluster-info CA does not match any of the CA certificate hashes")]
+    UntrustedCertificateAuthority,
+
+    /// The `cluster-info` ConfigMap fetched while verifying the apiserver with the CA differs from the first one
+    #[error("cluster-info changed when fetched with the verified CA")]
+    ClusterInfoChanged,
+}
+
+// Splits a bootstrap token into its id and secret
+fn parse_token(token: &str) -> Result<(&str, &str), BootstrapError> {
+    let valid = |part: &str, len: usize| {
+        part.len() == len && part.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+    };
+    match token.split_once('.') {
+        Some((id, secret)) if valid(id, 6) && valid(secret, 16) => Ok((id, secret)),
+        _ => Err(BootstrapError::InvalidToken),
+    }
+}
+
+// Verifies the detached JWS `signature` of `content`, signed with HS256 by the token like kubeadm does
+fn verify_signature(
+    content: &str,
+    signature: &str,
+    token_id: &str,
+    secret: &str,
+) -> Result<(), BootstrapError> {
+    let (header, sig) = match signature.split_once("..") {
+        Some(parts) => parts,
+        None => return Err(BootstrapError::InvalidSignature),
+    };
+    let decoded_header = base64::decode_config(header, base64::URL_SAFE_NO_PAD)
+        .ok()
+        .and_then(|h| serde_json::from_slice::<serde_json::Value>(&h).ok())
+        .ok_or(BootstrapError::InvalidSignature)?;
+    if decoded_header["alg"] != "HS256" || decoded_header["kid"] != token_id {
+        return Err(BootstrapError::InvalidSignature);
+    }
+    let sig =
+        base64::decode_config(sig, base64::URL_SAFE_NO_PAD).map_err(|_| BootstrapError::InvalidSignature)?;
+
+    let payload = base64::encode_config(content, base64::URL_SAFE_NO_PAD);
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}.{}", header, payload).as_bytes());
+    mac.verify(&sig).map_err(|_| BootstrapError::InvalidSignature)
+}
+
+// Parses a CA certificate hash of the form `sha256:<hex>`, like the `--discovery-token-ca-cert-hash` of kubeadm
+fn parse_ca_cert_hash(hash: &str) -> Result<[u8; 32], BootstrapError> {
+    let invalid = || BootstrapError::InvalidCaCertHash(hash.into());
+    let hex = hash.strip_prefix(CA_CERT_HASH_PREFIX).ok_or_else(invalid)?;
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut digest = [0; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(digest)
+}
+
+// Splits the DER element at the start of `der` into its tag, the whole element, its contents and the rest
+fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&len, rest) = rest.split_first()?;
+    let (len, rest) = if len < 0x80 {
+        (len as usize, rest)
+    } else {
+        let size = (len & 0x7f) as usize;
+        if size == 0 || size > 4 || rest.len() < size {
+            return None;
+        }
+        let len = rest[..size].iter().fold(0, |len, &b| (len << 8) | b as usize);
+        (len, &rest[size..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let header = der.len() - rest.len();
+    Some((tag, &der[..header + len], &rest[..len], &rest[len..]))
+}
+
+// The SHA-256 digest of the DER-encoded `SubjectPublicKeyInfo` of a certificate, which kubeadm pins CAs by
+fn spki_sha256(cert: &[u8]) -> Option<[u8; 32]> {
+    let (_, _, cert, _) = der_element(cert)?;
+    let (_, _, mut tbs, _) = der_element(cert)?;
+    // Skip the optional version, then the serial number, signature algorithm, issuer, validity and subject
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_element(tbs)?.3;
+    }
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.3;
+    }
+    match der_element(tbs)? {
+        (0x30, spki, _, _) => Some(Sha256::digest(spki).into()),
+        _ => None,
+    }
+}
+
+// Verifies that one of the CA certificates has one of the pinned public keys
+fn verify_ca(root_cert: &[Vec<u8>], pins: &[[u8; 32]]) -> Result<(), BootstrapError> {
+    if root_cert
+        .iter()
+        .filter_map(|cert| spki_sha256(cert))
+        .any(|spki| pins.contains(&spki))
+    {
+        Ok(())
+    } else {
+        Err(BootstrapError::UntrustedCertificateAuthority)
+    }
+}
+
+async fn fetch_cluster_info(config: Config) -> Result<ConfigMap, BootstrapError> {
+    let client = Client::try_from(config).map_err(BootstrapError::CreateClient)?;
+    Api::<ConfigMap>::namespaced(client, CLUSTER_INFO_NAMESPACE)
+        .get(CLUSTER_INFO_NAME)
+        .await
+        .map_err(BootstrapError::FetchClusterInfo)
+}
+
+// Verifies the `cluster-info` ConfigMap, and returns its kubeconfig
+fn verified_kubeconfig(cluster_info: &ConfigMap, token: &str) -> Result<Kubeconfig, BootstrapError> {
+    let (token_id, secret) = parse_token(token)?;
+    let data = cluster_info.data.clone().unwrap_or_default();
+    let kubeconfig = data
+        .get(KUBECONFIG_KEY)
+        .ok_or(BootstrapError::MissingKubeconfig)?;
+    let signature = data
+        .get(&format!("{}{}", SIGNATURE_KEY_PREFIX, token_id))
+        .ok_or_else(|| BootstrapError::MissingSignature(token_id.into()))?;
+    verify_signature(kubeconfig, signature, token_id, secret)?;
+    Kubeconfig::from_yaml(kubeconfig).map_err(BootstrapError::Kubeconfig)
+}
+
+impl Config {
+    /// Create configuration for joining a cluster with a bootstrap token, like `kubeadm join`
+    ///
+    /// Fetches the public `cluster-info` ConfigMap from `kube-public` at `cluster_url`, and verifies that it was
+    /// signed with the bootstrap `token` (`<id>.<secret>`) and that its CA has the public key of one of
+    /// `ca_cert_hashes`. The hashes are of the form `sha256:<hex>`, like the `--discovery-token-ca-cert-hash`
+    /// of `kubeadm join`. `cluster-info` is then fetched again while verifying the apiserver with the CA,
+    /// to make sure that it is served by the cluster.
+    ///
+    /// The returned configuration trusts the CA and uses the apiserver URL from `cluster-info`,
+    /// and authenticates with the bootstrap token, for example to request a client certificate.
+    pub async fn from_bootstrap_token(
+        cluster_url: http::Uri,
+        token: &str,
+        ca_cert_hashes: &[&str],
+    ) -> Result<Self, BootstrapError> {
+        if ca_cert_hashes.is_empty() {
+            return Err(BootstrapError::MissingCaCertHashes);
+        }
+        let pins = ca_cert_hashes
+            .iter()
+            .map(|hash| parse_ca_cert_hash(hash))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::bootstrap(cluster_url, token, Some(&pins)).await
+    }
+
+    /// Create configuration for joining a cluster with a bootstrap token, without verifying the cluster CA
+    ///
+    /// Like [`Config::from_bootstrap_token`], but trusts whichever CA `cluster-info` names, like
+    /// `kubeadm join --discovery-token-unsafe-skip-ca-verification`. The signature only authenticates
+    /// `cluster-info` as long as the token secret is kept secret, so anyone who knows the token can
+    /// impersonate the cluster.
+    pub async fn from_bootstrap_token_unsafe_skip_ca_verification(
+        cluster_url: http::Uri,
+        token: &str,
+    ) -> Result<Self, BootstrapError> {
+        Self::bootstrap(cluster_url, token, None).await
+    }
+
+    async fn bootstrap(
+        cluster_url: http::Uri,
+        token: &str,
+        pins: Option<&[[u8; 32]]>,
+    ) -> Result<Self, BootstrapError> {
+        parse_token(token)?;
+        let mut discovery = Config::new(cluster_url.clone());
+        // The CA is what we are discovering, and `cluster-info` is verified by its signature and the pins instead
+        discovery.accept_invalid_certs = true;
+        let cluster_info = fetch_cluster_info(discovery).await?;
+
+        let kubeconfig = verified_kubeconfig(&cluster_info, token)?;
+        let cluster = kubeconfig
+            .clusters
+            .into_iter()
+            .next()
+            .ok_or(BootstrapError::MissingCluster)?
+            .cluster;
+        let server = cluster
+            .server
+            .parse::<http::Uri>()
+            .map_err(|e| BootstrapError::Kubeconfig(KubeconfigError::ParseClusterUrl(e)))?;
+        let root_cert = match cluster
+            .load_certificate_authority()
+            .map_err(BootstrapError::Kubeconfig)?
+        {
+            Some(ca) => Some(
+                super::certs(&ca)
+                    .map_err(|e| BootstrapError::Kubeconfig(KubeconfigError::ParseCertificates(e)))?,
+            ),
+            None => None,
+        };
+
+        if let Some(pins) = pins {
+            let ca = root_cert
+                .as_deref()
+                .ok_or(BootstrapError::MissingCertificateAuthority)?;
+            verify_ca(ca, pins)?;
+            // Like kubeadm, make sure that the apiserver has a certificate issued by the verified CA
+            let mut secure = Config::new(cluster_url);
+            secure.root_cert = Some(ca.to_vec());
+            let secure_cluster_info = fetch_cluster_info(secure).await?;
+            let data = |cm: &ConfigMap| {
+                cm.data
+                    .as_ref()
+                    .and_then(|data| data.get(KUBECONFIG_KEY).cloned())
+            };
+            if data(&secure_cluster_info) != data(&cluster_info) {
+                return Err(BootstrapError::ClusterInfoChanged);
+            }
+        }
+
+        let mut config = Config::new(server);
+        config.root_cert = root_cert;
+        config.auth_info = AuthInfo {
+            token: Some(token.into()),
+            ..AuthInfo::default()
+        };
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // The kubeconfig of cluster-info only has the cluster
+    const KUBECONFIG: &str = r#"apiVersion: v1
+clusters:
+- cluster:
+    server: https://10.0.0.1:6443
+  name: ""
+contexts: null
+current-context: ""
+kind: Config
+preferences: {}
+users: null
+"#;
+
+    // A CA certificate, and the hash of its public key like `kubeadm token create --print-join-command` prints
+    const CA_CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBgDCCASegAwIBAgIUTm7f2cnH5Gxz+XQq6agfKPWvs34wCgYIKoZIzj0EAwIw
+FTETMBEGA1UEAwwKa3ViZXJuZXRlczAgFw0yNjEwMTYwNzQ2MjJaGA8yMTI2MDky
+MjA3NDYyMlowFTETMBEGA1UEAwwKa3ViZXJuZXRlczBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABDxXt2EPA/ZzBBSsczRu1wzi+wpMe3uDbcyrLky1HU6q7L9RtI0l
+3hnAtRS7sK4GnoGbTyoI+bRV7Dp1Khoeb82jUzBRMB0GA1UdDgQWBBSEFlXFfDHJ
+CAGB1VvtGIijDYuUEzAfBgNVHSMEGDAWgBSEFlXFfDHJCAGB1VvtGIijDYuUEzAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIGN3cyg+4bz0tm8Fb6Dz
+lyRfKQXmrrQtaSnMU98uz+gtAiARmzh6tiYu1UwmDYixsPh191ldHmrXjrata3XP
+hMO+oA==
+-----END CERTIFICATE-----
+";
+    const CA_CERT_HASH: &str = "sha256:5b44c1b2ce2b797dc85c2d9ca0f0bc24086f28d1b729b3401bf16b2ea7a67809";
+
+    fn sign(content: &str, token_id: &str, secret: &str) -> String {
+        let header = base64::encode_config(
+            format!(r#"{{"alg":"HS256","kid":"{}"}}"#, token_id),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let payload = base64::encode_config(content, base64::URL_SAFE_NO_PAD);
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", header, payload).as_bytes());
+        let sig = base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+        format!("{}..{}", header, sig)
+    }
+
+    fn cluster_info(signature: &str) -> ConfigMap {
+        serde_json::from_value(json!({
+            "metadata": { "name": "cluster-info", "namespace": "kube-public" },
+            "data": { "kubeconfig": KUBECONFIG, "jws-kubeconfig-abcdef": signature },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn verifies_cluster_info() {
+        let token = "abcdef.0123456789abcdef";
+        let signed = cluster_info(&sign(KUBECONFIG, "abcdef", "0123456789abcdef"));
+        let kubeconfig = verified_kubeconfig(&signed, token).unwrap();
+        assert_eq!(kubeconfig.clusters[0].cluster.server, "https://10.0.0.1:6443");
+
+        let forged = cluster_info(&sign(KUBECONFIG, "abcdef", "fedcba9876543210"));
+        assert!(matches!(
+            verified_kubeconfig(&forged, token),
+            Err(BootstrapError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verified_kubeconfig(&signed, "ghijkl.0123456789abcdef"),
+            Err(BootstrapError::MissingSignature(_))
+        ));
+        assert!(matches!(
+            verified_kubeconfig(&signed, "abcdef"),
+            Err(BootstrapError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn verifies_ca_against_hashes() {
+        let ca = super::super::certs(CA_CERT.as_bytes()).unwrap();
+        let pin = parse_ca_cert_hash(CA_CERT_HASH).unwrap();
+        verify_ca(&ca, &[pin]).unwrap();
+        assert!(matches!(
+            verify_ca(&ca, &[[0; 32]]),
+            Err(BootstrapError::UntrustedCertificateAuthority)
+        ));
+        assert!(matches!(
+            verify_ca(&[b"not a certificate".to_vec()], &[pin]),
+            Err(BootstrapError::UntrustedCertificateAuthority)
+        ));
+
+        for hash in [
+            &CA_CERT_HASH[7..],
+            "sha256:5b44",
+            "md5:5b44c1b2ce2b797dc85c2d9ca0f0bc24",
+        ] {
+            assert!(matches!(
+                parse_ca_cert_hash(hash),
+                Err(BootstrapError::InvalidCaCertHash(_))
+            ));
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use super::{KubeconfigError, LoadDataError};
 
@@ -24,11 +24,14 @@ pub struct Kubeconfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub preferences: Option<Preferences>,
     /// Referencable names to cluster configs
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub clusters: Vec<NamedCluster>,
     /// Referencable names to user configs
     #[serde(rename = "users")]
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub auth_infos: Vec<NamedAuthInfo>,
     /// Referencable names to context configs
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
     pub contexts: Vec<NamedContext>,
     /// The name of the context that you would like to use by default
     #[serde(rename = "current-context")]
@@ -70,6 +73,50 @@ pub struct NamedExtension {
     pub extension: serde_json::Value,
 }
 
+impl NamedExtension {
+    /// Deserialize the extension into `T`
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, KubeconfigError> {
+        serde_json::from_value(self.extension.clone()).map_err(KubeconfigError::ParseExtension)
+    }
+}
+
+// Finds the extension `name`, and deserializes it into `T`
+fn find_extension<T: DeserializeOwned>(
+    extensions: &[NamedExtension],
+    name: &str,
+) -> Result<Option<T>, KubeconfigError> {
+    extensions
+        .iter()
+        .find(|extension| extension.name == name)
+        .map(NamedExtension::parse)
+        .transpose()
+}
+
+/// The extensions of the context, user and cluster that a [`Config`](crate::Config) was loaded from
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct KubeconfigExtensions {
+    /// Extensions of the context
+    pub context: Vec<NamedExtension>,
+    /// Extensions of the user
+    pub user: Vec<NamedExtension>,
+    /// Extensions of the cluster
+    pub cluster: Vec<NamedExtension>,
+}
+
+impl KubeconfigExtensions {
+    /// Deserialize the extension `name` of the context, the user or the cluster, in that order of precedence
+    ///
+    /// Returns `None` if none of them has the extension.
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, KubeconfigError> {
+        [&self.context, &self.user, &self.cluster]
+            .iter()
+            .find_map(|extensions| extensions.iter().find(|extension| extension.name == name))
+            .map(NamedExtension::parse)
+            .transpose()
+    }
+}
+
 /// NamedCluster associates name with cluster.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -102,6 +149,12 @@ pub struct Cluster {
     #[serde(rename = "proxy-url")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxy_url: Option<String>,
+    /// Name used to check server certificate.
+    ///
+    /// If `tls_server_name` is `None`, the hostname used to contact the server is used.
+    #[serde(rename = "tls-server-name")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_server_name: Option<String>,
     /// Additional information for extenders so that reads and writes don't clobber unknown fields
     #[serde(skip_serializing_if = "Option::is_none")]
     pub extensions: Option<Vec<NamedExtension>>,
@@ -172,6 +225,10 @@ pub struct AuthInfo {
     /// Specifies a custom exec-based authentication plugin for the kubernetes cluster.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exec: Option<ExecConfig>,
+
+    /// Additional information for extenders so that reads and writes don't clobber unknown fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<NamedExtension>>,
 }
 
 /// AuthProviderConfig stores auth for specified cloud provider.
@@ -182,6 +239,12 @@ pub struct AuthProviderConfig {
     pub name: String,
     /// Auth provider configuration
     pub config: HashMap<String, String>,
+    /// The kubeconfig file the auth provider was read from
+    ///
+    /// This is not part of the kubeconfig and is set by [`Kubeconfig::read_from`]. Refreshed tokens are written
+    /// back to this file when enabled with [`Config::persist_auth_provider_tokens`](crate::Config::persist_auth_provider_tokens).
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
 /// ExecConfig stores credential-plugin configuration.
@@ -204,6 +267,105 @@ pub struct ExecConfig {
     /// TODO: These are unioned with the host's environment, as well as variables client-go uses to pass argument to the plugin.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub env: Option<Vec<HashMap<String, String>>>,
+    /// Whether to pass the cluster information to the plugin via the `KUBERNETES_EXEC_INFO` environment variable.
+    ///
+    /// Some credential plugins need the server address or the certificate authority of the cluster
+    /// to issue credentials.
+    #[serde(rename = "provideClusterInfo")]
+    #[serde(default)]
+    pub provide_cluster_info: bool,
+    /// Whether the plugin may interact with the user through standard input.
+    ///
+    /// Required by `client.authentication.k8s.io/v1`, and defaults to `IfAvailable` for older versions.
+    #[serde(rename = "interactiveMode")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactive_mode: Option<ExecInteractiveMode>,
+    /// Cluster information passed to the plugin when `provide_cluster_info` is set.
+    ///
+    /// This is not part of the kubeconfig and is populated from the selected cluster when the config is loaded.
+    #[serde(skip)]
+    pub cluster: Option<ExecAuthCluster>,
+}
+
+/// ExecInteractiveMode defines whether an exec-based credential plugin may use standard input.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ExecInteractiveMode {
+    /// The plugin never uses standard input.
+    Never,
+    /// The plugin uses standard input if it is available, for example to prompt the user for a password.
+    IfAvailable,
+    /// The plugin requires standard input to function.
+    Always,
+}
+
+/// Cluster information passed to exec-based credential plugins that have `provideClusterInfo` enabled.
+///
+/// This is a copy of [`Cluster`] with the certificate authority always passed as data.
+/// An analogue of [`Cluster` from client-go](https://github.com/kubernetes/client-go/blob/477cb782cf024bc70b7239f0dca91e5774811950/pkg/apis/clientauthentication/types.go#L73-L129).
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecAuthCluster {
+    /// The address of the kubernetes cluster (https://hostname:port).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// Skips the validity check for the server's certificate. This will make your HTTPS connections insecure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insecure_skip_tls_verify: Option<bool>,
+    /// PEM-encoded certificate authority certificates.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "base64serde")]
+    pub certificate_authority_data: Option<Vec<u8>>,
+    /// URL to the proxy to be used for all requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    /// Name used to check server certificate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_server_name: Option<String>,
+    /// Additional configuration for the plugin from the `client.authentication.k8s.io/exec` cluster extension.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<serde_json::Value>,
+}
+
+impl TryFrom<&Cluster> for ExecAuthCluster {
+    type Error = KubeconfigError;
+
+    fn try_from(cluster: &Cluster) -> Result<Self, Self::Error> {
+        Ok(Self {
+            server: Some(cluster.server.clone()),
+            insecure_skip_tls_verify: cluster.insecure_skip_tls_verify,
+            certificate_authority_data: cluster.load_certificate_authority()?,
+            proxy_url: cluster.proxy_url.clone(),
+            tls_server_name: cluster.tls_server_name.clone(),
+            config: cluster.extensions.as_ref().and_then(|extensions| {
+                extensions
+                    .iter()
+                    .find(|extension| extension.name == CLUSTER_EXTENSION_KEY)
+                    .map(|extension| extension.extension.clone())
+            }),
+        })
+    }
+}
+
+// Name of the cluster extension holding additional configuration for exec plugins.
+const CLUSTER_EXTENSION_KEY: &str = "client.authentication.k8s.io/exec";
+
+// Serializes the certificate authority as base64 like `[]byte` fields in Go.
+mod base64serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => base64::encode(v).serialize(s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(v) => base64::decode(v).map(Some).map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
 }
 
 /// NamedContext associates name with context.
@@ -241,33 +403,13 @@ impl Kubeconfig {
         let data = fs::read_to_string(&path)
             .map_err(|source| KubeconfigError::ReadConfig(source, path.as_ref().into()))?;
 
-        // Remap all files we read to absolute paths.
         let mut merged_docs = None;
         for mut config in kubeconfig_from_yaml(&data)? {
-            if let Some(dir) = path.as_ref().parent() {
-                for named in config.clusters.iter_mut() {
-                    if let Some(path) = &named.cluster.certificate_authority {
-                        if let Some(abs_path) = to_absolute(dir, path) {
-                            named.cluster.certificate_authority = Some(abs_path);
-                        }
-                    }
-                }
-                for named in config.auth_infos.iter_mut() {
-                    if let Some(path) = &named.auth_info.client_certificate {
-                        if let Some(abs_path) = to_absolute(dir, path) {
-                            named.auth_info.client_certificate = Some(abs_path);
-                        }
-                    }
-                    if let Some(path) = &named.auth_info.client_key {
-                        if let Some(abs_path) = to_absolute(dir, path) {
-                            named.auth_info.client_key = Some(abs_path);
-                        }
-                    }
-                    if let Some(path) = &named.auth_info.token_file {
-                        if let Some(abs_path) = to_absolute(dir, path) {
-                            named.auth_info.token_file = Some(abs_path);
-                        }
-                    }
+            // Remap all files we read to absolute paths.
+            config.resolve_paths(path.as_ref().parent());
+            for named in config.auth_infos.iter_mut() {
+                if let Some(provider) = &mut named.auth_info.auth_provider {
+                    provider.source = Some(path.as_ref().to_path_buf());
                 }
             }
             if let Some(c) = merged_docs {
@@ -280,6 +422,33 @@ impl Kubeconfig {
         Ok(merged_docs.unwrap_or_default())
     }
 
+    /// Read Configs from several locations, and merge them like the `KUBECONFIG` list
+    ///
+    /// Values from earlier paths take precedence, see [`Kubeconfig::from_env`].
+    /// Paths that do not exist are skipped, like kubectl does, unless none of them exist.
+    pub fn read_from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Kubeconfig, KubeconfigError> {
+        let mut merged = Kubeconfig::default();
+        let mut missing = None;
+        let mut found = false;
+        for path in paths {
+            match Kubeconfig::read_from(path) {
+                Ok(config) => {
+                    merged = merged.merge(config)?;
+                    found = true;
+                }
+                Err(KubeconfigError::ReadConfig(err, path)) if err.kind() == std::io::ErrorKind::NotFound => {
+                    tracing::debug!("Skipping missing kubeconfig {:?}", path);
+                    missing.get_or_insert(KubeconfigError::ReadConfig(err, path));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        match missing {
+            Some(err) if !found => Err(err),
+            _ => Ok(merged),
+        }
+    }
+
     /// Read a Config from an arbitrary YAML string
     ///
     /// This is preferable to using serde_yaml::from_str() because it will correctly
@@ -287,9 +456,38 @@ impl Kubeconfig {
     pub fn from_yaml(text: &str) -> Result<Kubeconfig, KubeconfigError> {
         kubeconfig_from_yaml(text)?
             .into_iter()
+            .map(|mut config| {
+                config.resolve_paths(None);
+                config
+            })
             .try_fold(Kubeconfig::default(), Kubeconfig::merge)
     }
 
+    /// Read a Config from an arbitrary JSON string
+    pub fn from_json(text: &str) -> Result<Kubeconfig, KubeconfigError> {
+        let mut config: Kubeconfig = serde_json::from_str(text).map_err(KubeconfigError::ParseJson)?;
+        config.resolve_paths(None);
+        Ok(config)
+    }
+
+    // Expands the paths of the files this Config refers to, and makes relative paths relative to `dir`.
+    // This happens once when the Config is loaded, so that expanded paths are never expanded again.
+    fn resolve_paths(&mut self, dir: Option<&Path>) {
+        let resolve = |file: &mut Option<String>| {
+            if let Some(resolved) = file.as_deref().and_then(|file| to_absolute(dir, file)) {
+                *file = Some(resolved);
+            }
+        };
+        for named in self.clusters.iter_mut() {
+            resolve(&mut named.cluster.certificate_authority);
+        }
+        for named in self.auth_infos.iter_mut() {
+            resolve(&mut named.auth_info.client_certificate);
+            resolve(&mut named.auth_info.client_key);
+            resolve(&mut named.auth_info.token_file);
+        }
+    }
+
     /// Read a Config from `KUBECONFIG` or the the default location.
     pub fn read() -> Result<Kubeconfig, KubeconfigError> {
         match Self::from_env()? {
@@ -299,29 +497,129 @@ impl Kubeconfig {
     }
 
     /// Create `Kubeconfig` from `KUBECONFIG` environment variable.
-    /// Supports list of files to be merged.
+    /// Supports list of files to be merged, separated by `:` (`;` on Windows).
+    /// Files that do not exist are skipped, unless none of them exist.
     ///
     /// # Panics
     ///
     /// Panics if `KUBECONFIG` value contains the NUL character.
     pub fn from_env() -> Result<Option<Self>, KubeconfigError> {
-        match std::env::var_os(KUBECONFIG) {
-            Some(value) => {
-                let paths = std::env::split_paths(&value)
-                    .filter(|p| !p.as_os_str().is_empty())
-                    .collect::<Vec<_>>();
-                if paths.is_empty() {
-                    return Ok(None);
-                }
+        match env_paths() {
+            Some(paths) => Self::read_from_paths(&paths).map(Some),
+            None => Ok(None),
+        }
+    }
 
-                let merged = paths.iter().try_fold(Kubeconfig::default(), |m, p| {
-                    Kubeconfig::read_from(p).and_then(|c| m.merge(c))
-                })?;
-                Ok(Some(merged))
-            }
+    /// The paths of the files this Config refers to, such as certificates, keys and token files
+    #[cfg(feature = "client")]
+    pub(crate) fn referenced_files(&self) -> Vec<PathBuf> {
+        let clusters = self
+            .clusters
+            .iter()
+            .flat_map(|named| &named.cluster.certificate_authority);
+        let users = self.auth_infos.iter().flat_map(|named| {
+            let user = &named.auth_info;
+            user.client_certificate
+                .iter()
+                .chain(&user.client_key)
+                .chain(&user.token_file)
+        });
+        clusters.chain(users).map(PathBuf::from).collect()
+    }
 
-            None => Ok(None),
+    /// Serialize the Config to YAML
+    pub fn to_yaml(&self) -> Result<String, KubeconfigError> {
+        serde_yaml::to_string(self).map_err(KubeconfigError::Serialize)
+    }
+
+    /// Write the Config to an arbitrary location, replacing the file if it exists
+    ///
+    /// The file is written to a temporary file next to it first, so that readers never see a partial kubeconfig.
+    /// On Unix, new files are only readable by their owner, since they typically contain credentials.
+    ///
+    /// Note that the paths of certificates and keys of a Config read from a file are absolute.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), KubeconfigError> {
+        let path = path.as_ref();
+        let write_err = |source| KubeconfigError::WriteConfig(source, path.into());
+        let yaml = self.to_yaml()?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(write_err)?;
         }
+        let (tmp, mut file) = create_temp_file(path).map_err(write_err)?;
+        let written = std::io::Write::write_all(&mut file, yaml.as_bytes()).and_then(|_| file.sync_all());
+        // Closed before renaming, which fails for open files on Windows
+        drop(file);
+        let result = written.and_then(|_| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result.map_err(write_err)
+    }
+
+    /// Add a cluster, replacing any existing cluster with the same name
+    ///
+    /// Returns the replaced cluster, like `kubectl config set-cluster`.
+    pub fn set_cluster(&mut self, name: &str, cluster: Cluster) -> Option<Cluster> {
+        set_named(
+            &mut self.clusters,
+            name,
+            cluster,
+            |x| &x.name,
+            |x| &mut x.cluster,
+            |name, cluster| NamedCluster { name, cluster },
+        )
+    }
+
+    /// Add a user, replacing any existing user with the same name
+    ///
+    /// Returns the replaced user, like `kubectl config set-credentials`.
+    pub fn set_auth_info(&mut self, name: &str, auth_info: AuthInfo) -> Option<AuthInfo> {
+        set_named(
+            &mut self.auth_infos,
+            name,
+            auth_info,
+            |x| &x.name,
+            |x| &mut x.auth_info,
+            |name, auth_info| NamedAuthInfo { name, auth_info },
+        )
+    }
+
+    /// Add a context, replacing any existing context with the same name
+    ///
+    /// Returns the replaced context, like `kubectl config set-context`.
+    pub fn set_context(&mut self, name: &str, context: Context) -> Option<Context> {
+        set_named(
+            &mut self.contexts,
+            name,
+            context,
+            |x| &x.name,
+            |x| &mut x.context,
+            |name, context| NamedContext { name, context },
+        )
+    }
+
+    /// Deserialize the top level extension `name`, if present
+    pub fn extension<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, KubeconfigError> {
+        find_extension(self.extensions.as_deref().unwrap_or_default(), name)
+    }
+
+    /// The names of all contexts, like `kubectl config get-contexts -o name`
+    pub fn contexts(&self) -> impl Iterator<Item = &str> {
+        self.contexts.iter().map(|x| x.name.as_str())
+    }
+
+    /// Set the current context, like `kubectl config use-context`
+    ///
+    /// Returns an error if there is no context with that name.
+    pub fn use_context(&mut self, name: &str) -> Result<(), KubeconfigError> {
+        if !self.contexts.iter().any(|x| x.name == name) {
+            return Err(KubeconfigError::MissingContext {
+                context: name.to_owned(),
+                available: self.contexts().map(str::to_owned).collect(),
+            });
+        }
+        self.current_context = Some(name.to_owned());
+        Ok(())
     }
 
     /// Merge kubeconfig file according to the rules described in
@@ -356,6 +654,15 @@ impl Kubeconfig {
     }
 }
 
+// Missing and null lists are empty, as in the `cluster-info` kubeconfig of kubeadm
+fn deserialize_null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 fn kubeconfig_from_yaml(text: &str) -> Result<Vec<Kubeconfig>, KubeconfigError> {
     let mut documents = vec![];
     for doc in serde_yaml::Deserializer::from_str(text) {
@@ -366,6 +673,47 @@ fn kubeconfig_from_yaml(text: &str) -> Result<Vec<Kubeconfig>, KubeconfigError>
     Ok(documents)
 }
 
+/// Creates a new file next to `path` for writing it, with a name that no other writer uses
+///
+/// On Unix, the file is only readable by its owner.
+fn create_temp_file(path: &Path) -> std::io::Result<(PathBuf, fs::File)> {
+    static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    loop {
+        let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".{}.{}.tmp", std::process::id(), count));
+        let tmp = PathBuf::from(tmp);
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&tmp) {
+            // Left behind by a process that had the same id
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            result => return result.map(|file| (tmp, file)),
+        }
+    }
+}
+
+// Replaces the value of the entry named `name`, or appends a new entry
+fn set_named<T, V>(
+    entries: &mut Vec<T>,
+    name: &str,
+    value: V,
+    name_of: impl Fn(&T) -> &String,
+    value_of: impl Fn(&mut T) -> &mut V,
+    new: impl FnOnce(String, V) -> T,
+) -> Option<V> {
+    match entries.iter_mut().find(|x| name_of(x) == name) {
+        Some(existing) => Some(std::mem::replace(value_of(existing), value)),
+        None => {
+            entries.push(new(name.to_owned(), value));
+            None
+        }
+    }
+}
+
 #[allow(clippy::redundant_closure)]
 fn append_new_named<T, F>(base: &mut Vec<T>, next: Vec<T>, f: F)
 where
@@ -380,16 +728,62 @@ where
     });
 }
 
-fn to_absolute(dir: &Path, file: &str) -> Option<String> {
-    let path = Path::new(&file);
-    if path.is_relative() {
+fn to_absolute(dir: Option<&Path>, file: &str) -> Option<String> {
+    let expanded = expand_path(file);
+    let path = Path::new(&expanded);
+    if let (true, Some(dir)) = (path.is_relative(), dir) {
         dir.join(path).to_str().map(str::to_owned)
+    } else if expanded != file {
+        Some(expanded)
     } else {
         None
     }
 }
 
+// Expands a leading `~` to the home directory, and `$VAR` or `${VAR}` to the value of environment variables.
+// Unset variables expand to the empty string, like `os.ExpandEnv` in Go.
+fn expand_path(path: &str) -> String {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        if let Some(home) = dirs::home_dir().and_then(|home| home.to_str().map(str::to_owned)) {
+            expanded.push_str(&home);
+            rest = &rest[1..];
+        }
+    }
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            // Not a variable reference
+            expanded.push('$');
+            rest = after;
+        } else {
+            expanded.push_str(&std::env::var(name).unwrap_or_default());
+            rest = remainder;
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
 impl Cluster {
+    /// Deserialize the extension `name` of the cluster, if present
+    pub fn extension<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, KubeconfigError> {
+        find_extension(self.extensions.as_deref().unwrap_or_default(), name)
+    }
+
     pub(crate) fn load_certificate_authority(&self) -> Result<Option<Vec<u8>>, KubeconfigError> {
         if self.certificate_authority.is_none() && self.certificate_authority_data.is_none() {
             return Ok(None);
@@ -401,7 +795,19 @@ impl Cluster {
     }
 }
 
+impl Context {
+    /// Deserialize the extension `name` of the context, if present
+    pub fn extension<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, KubeconfigError> {
+        find_extension(self.extensions.as_deref().unwrap_or_default(), name)
+    }
+}
+
 impl AuthInfo {
+    /// Deserialize the extension `name` of the user, if present
+    pub fn extension<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, KubeconfigError> {
+        find_extension(self.extensions.as_deref().unwrap_or_default(), name)
+    }
+
     pub(crate) fn load_client_certificate(&self) -> Result<Vec<u8>, KubeconfigError> {
         load_from_base64_or_file(&self.client_certificate_data, &self.client_certificate)
             .map_err(KubeconfigError::LoadClientCertificate)
@@ -430,7 +836,8 @@ fn load_from_base64(value: &str) -> Result<Vec<u8>, LoadDataError> {
 }
 
 fn load_from_file<P: AsRef<Path>>(file: &P) -> Result<Vec<u8>, LoadDataError> {
-    fs::read(&file).map_err(|source| LoadDataError::ReadFile(source, file.as_ref().into()))
+    let path = file.as_ref().to_owned();
+    fs::read(&path).map_err(|source| LoadDataError::ReadFile(source, path))
 }
 
 // Ensure there is a trailing newline in the blob
@@ -442,6 +849,71 @@ fn ensure_trailing_newline(mut data: Vec<u8>) -> Vec<u8> {
     data
 }
 
+// The non-empty paths of the `KUBECONFIG` list, if set
+fn env_paths() -> Option<Vec<PathBuf>> {
+    let value = std::env::var_os(KUBECONFIG)?;
+    let paths = std::env::split_paths(&value)
+        .filter(|p| !p.as_os_str().is_empty())
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        None
+    } else {
+        Some(paths)
+    }
+}
+
+/// Returns the kubeconfig paths that [`Kubeconfig::read`] reads
+#[cfg(feature = "client")]
+pub(crate) fn kubeconfig_paths() -> Result<Vec<PathBuf>, KubeconfigError> {
+    match env_paths() {
+        Some(paths) => Ok(paths),
+        None => Ok(vec![default_kube_path().ok_or(KubeconfigError::FindPath)?]),
+    }
+}
+
+/// Updates the `auth-provider` config of the users in the kubeconfig file at `path` whose `key` is `value`
+///
+/// This is how refreshed tokens are written back, like kubectl does. The file is edited as YAML,
+/// so that fields unknown to [`Kubeconfig`] are kept. Returns whether a user was updated.
+#[cfg(feature = "oidc")]
+pub(crate) fn update_auth_provider_config(
+    path: &Path,
+    key: &str,
+    value: &str,
+    updates: &[(&str, &str)],
+) -> Result<bool, KubeconfigError> {
+    use serde_yaml::Value;
+    let data = fs::read_to_string(path).map_err(|source| KubeconfigError::ReadConfig(source, path.into()))?;
+    // Files with several documents are left alone
+    let mut doc = match serde_yaml::from_str::<Value>(&data) {
+        Ok(doc) => doc,
+        Err(_) => return Ok(false),
+    };
+    let users = doc.get_mut("users").and_then(Value::as_sequence_mut);
+    let mut updated = false;
+    for user in users.into_iter().flatten() {
+        let config = user
+            .get_mut("user")
+            .and_then(|user| user.get_mut("auth-provider"))
+            .and_then(|provider| provider.get_mut("config"))
+            .and_then(Value::as_mapping_mut);
+        let config = match config {
+            Some(config) if config.get(&Value::from(key)) == Some(&Value::from(value)) => config,
+            _ => continue,
+        };
+        for (key, value) in updates {
+            config.insert(Value::from(*key), Value::from(*value));
+        }
+        updated = true;
+    }
+    if updated {
+        let yaml = serde_yaml::to_string(&doc).map_err(KubeconfigError::Serialize)?;
+        // Written in place, which keeps the permissions of the file
+        fs::write(path, yaml).map_err(|source| KubeconfigError::WriteConfig(source, path.into()))?;
+    }
+    Ok(updated)
+}
+
 /// Returns kubeconfig path from `$HOME/.kube/config`.
 fn default_kube_path() -> Option<PathBuf> {
     use dirs::home_dir;
@@ -507,6 +979,7 @@ clusters:
 - cluster:
     certificate-authority-data: LS0t<SNIP>LS0tLQo=
     server: https://ABCDEF0123456789.gr7.us-west-2.eks.amazonaws.com
+    tls-server-name: kubernetes.default
   name: eks
 - cluster:
     certificate-authority: /home/kevin/.minikube/ca.crt
@@ -560,6 +1033,10 @@ users:
         let config = Kubeconfig::from_yaml(config_yaml).unwrap();
 
         assert_eq!(config.clusters[0].name, "eks");
+        assert_eq!(
+            config.clusters[0].cluster.tls_server_name.as_deref(),
+            Some("kubernetes.default")
+        );
         assert_eq!(config.clusters[1].name, "minikube");
         assert_eq!(
             config.clusters[1].cluster.extensions.as_ref().unwrap()[0]
@@ -569,6 +1046,65 @@ users:
         );
     }
 
+    #[test]
+    fn kubeconfig_extensions() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Fleet {
+            id: String,
+        }
+
+        let config = Kubeconfig::from_yaml(
+            r#"
+clusters:
+- cluster:
+    server: https://example.com
+    extensions:
+    - name: fleet
+      extension:
+        id: cluster
+  name: prod
+contexts:
+- context:
+    cluster: prod
+    user: admin
+    extensions:
+    - name: fleet
+      extension:
+        id: context
+  name: prod
+users:
+- name: admin
+  user:
+    extensions:
+    - name: fleet
+      extension:
+        id: 42
+"#,
+        )
+        .unwrap();
+        let fleet = |id: &str| Some(Fleet { id: id.into() });
+        assert_eq!(config.clusters[0].cluster.extension("fleet").unwrap(), fleet("cluster"));
+        assert_eq!(config.contexts[0].context.extension("fleet").unwrap(), fleet("context"));
+        assert_eq!(config.clusters[0].cluster.extension::<Fleet>("other").unwrap(), None);
+        assert_eq!(config.extension::<Fleet>("fleet").unwrap(), None);
+        assert!(matches!(
+            config.auth_infos[0].auth_info.extension::<Fleet>("fleet"),
+            Err(KubeconfigError::ParseExtension(_))
+        ));
+
+        let extensions = KubeconfigExtensions {
+            user: config.auth_infos[0].auth_info.extensions.clone().unwrap(),
+            cluster: config.clusters[0].cluster.extensions.clone().unwrap(),
+            ..KubeconfigExtensions::default()
+        };
+        assert!(extensions.get::<Fleet>("fleet").is_err());
+        let extensions = KubeconfigExtensions {
+            context: config.contexts[0].context.extensions.clone().unwrap(),
+            ..extensions
+        };
+        assert_eq!(extensions.get("fleet").unwrap(), fleet("context"));
+    }
+
     #[test]
     fn kubeconfig_multi_document_merge() -> Result<(), KubeconfigError> {
         let config_yaml = r#"---
@@ -621,6 +1157,219 @@ users:
         Ok(())
     }
 
+    #[test]
+    fn exec_auth_cluster_from_cluster() {
+        let cluster: Cluster = serde_yaml::from_str(
+            "
+server: https://0.0.0.0:6443
+certificate-authority-data: aGVsbG8K
+extensions:
+- name: client.authentication.k8s.io/exec
+  extension:
+    audience: foo",
+        )
+        .unwrap();
+
+        let exec_cluster = ExecAuthCluster::try_from(&cluster).unwrap();
+        assert_eq!(exec_cluster.certificate_authority_data, Some(b"hello\n".to_vec()));
+        assert_eq!(
+            serde_json::to_value(&exec_cluster).unwrap(),
+            serde_json::json!({
+                "server": "https://0.0.0.0:6443",
+                "certificate-authority-data": "aGVsbG8K",
+                "config": { "audience": "foo" },
+            })
+        );
+    }
+
+    #[test]
+    fn kubeconfig_read_from_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        let missing = dir.path().join("missing");
+        fs::write(
+            &first,
+            "current-context: first\nclusters:\n- name: a\n  cluster:\n    server: https://a\n",
+        )
+        .unwrap();
+        fs::write(
+            &second,
+            "current-context: second\nclusters:\n- name: a\n  cluster:\n    server: https://b\n- name: c\n  cluster:\n    server: https://c\n",
+        )
+        .unwrap();
+
+        let merged = Kubeconfig::read_from_paths(&[&first, &missing, &second]).unwrap();
+        assert_eq!(merged.current_context.as_deref(), Some("first"));
+        assert_eq!(
+            merged
+                .clusters
+                .iter()
+                .map(|c| (c.name.as_str(), c.cluster.server.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("a", "https://a"), ("c", "https://c")]
+        );
+        assert!(matches!(
+            Kubeconfig::read_from_paths(&[&missing]),
+            Err(KubeconfigError::ReadConfig(_, _))
+        ));
+    }
+
+    #[test]
+    fn kubeconfig_set_and_write_back() {
+        let mut config = Kubeconfig::from_yaml(
+            "clusters:\n- name: kind\n  cluster:\n    server: https://127.0.0.1:6443\n",
+        )
+        .unwrap();
+        let replaced = config.set_cluster("kind", Cluster {
+            server: "https://127.0.0.1:7443".into(),
+            insecure_skip_tls_verify: None,
+            certificate_authority: None,
+            certificate_authority_data: Some("Q0EK".into()),
+            proxy_url: None,
+            tls_server_name: None,
+            extensions: None,
+        });
+        assert_eq!(replaced.unwrap().server, "https://127.0.0.1:6443");
+        assert!(config
+            .set_auth_info("kind-admin", AuthInfo {
+                token: Some("secret".into()),
+                ..AuthInfo::default()
+            })
+            .is_none());
+        assert!(config.use_context("kind").is_err());
+        config.set_context("kind", Context {
+            cluster: "kind".into(),
+            user: "kind-admin".into(),
+            namespace: None,
+            extensions: None,
+        });
+        config.use_context("kind").unwrap();
+        assert_eq!(config.clusters.len(), 1);
+
+        assert_eq!(Kubeconfig::from_yaml(&config.to_yaml().unwrap()).unwrap(), config);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".kube").join("config");
+        config.write_to(&path).unwrap();
+        assert_eq!(Kubeconfig::read_from(&path).unwrap(), config);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // Concurrent writers do not clobber each other's temporary files
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let (config, path) = (config.clone(), path.clone());
+                std::thread::spawn(move || config.write_to(&path))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        assert_eq!(Kubeconfig::read_from(&path).unwrap(), config);
+        let files: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["config"]);
+    }
+
+    #[cfg(feature = "oidc")]
+    #[test]
+    fn updates_auth_provider_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        fs::write(
+            &path,
+            r#"
+users:
+- name: dev
+  user:
+    auth-provider:
+      name: oidc
+      config: { id-token: old-id, refresh-token: old-refresh, client-id: kubectl }
+    unknown-field: kept
+"#,
+        )
+        .unwrap();
+        let updates = [("id-token", "new-id"), ("refresh-token", "new-refresh")];
+        assert!(update_auth_provider_config(&path, "refresh-token", "old-refresh", &updates).unwrap());
+        assert!(!update_auth_provider_config(&path, "refresh-token", "old-refresh", &updates).unwrap());
+
+        let config = Kubeconfig::read_from(&path).unwrap();
+        let provider = config.auth_infos[0].auth_info.auth_provider.as_ref().unwrap();
+        assert_eq!(provider.source.as_deref(), Some(path.as_path()));
+        assert_eq!(provider.config["id-token"], "new-id");
+        assert_eq!(provider.config["refresh-token"], "new-refresh");
+        assert_eq!(provider.config["client-id"], "kubectl");
+        assert!(fs::read_to_string(&path).unwrap().contains("unknown-field: kept"));
+    }
+
+    #[test]
+    fn kubeconfig_from_json() {
+        let json = r#"{
+            "apiVersion": "v1",
+            "kind": "Config",
+            "clusters": [{ "name": "kind", "cluster": { "server": "https://127.0.0.1:6443" } }],
+            "contexts": [{ "name": "kind", "context": { "cluster": "kind", "user": "admin" } }],
+            "users": [{ "name": "admin", "user": { "token": "secret" } }],
+            "current-context": "kind"
+        }"#;
+        let config = Kubeconfig::from_json(json).unwrap();
+        assert_eq!(config, Kubeconfig::from_yaml(json).unwrap());
+        assert_eq!(config.current_context.as_deref(), Some("kind"));
+        assert!(Kubeconfig::from_json("clusters: []").is_err());
+    }
+
+    #[test]
+    fn expands_paths() {
+        std::env::set_var("KUBE_RS_TEST_CERTS", "/etc/certs");
+        assert_eq!(expand_path("$KUBE_RS_TEST_CERTS/ca.crt"), "/etc/certs/ca.crt");
+        assert_eq!(expand_path("${KUBE_RS_TEST_CERTS}ca.crt"), "/etc/certsca.crt");
+        assert_eq!(expand_path("/certs/$KUBE_RS_TEST_UNSET/ca.crt"), "/certs//ca.crt");
+        assert_eq!(expand_path("/certs/$/ca.crt"), "/certs/$/ca.crt");
+        assert_eq!(expand_path("/certs/${unclosed"), "/certs/${unclosed");
+        assert_eq!(expand_path("certs/~/ca.crt"), "certs/~/ca.crt");
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(
+                expand_path("~/.minikube/ca.crt"),
+                format!("{}/.minikube/ca.crt", home.display())
+            );
+            assert_eq!(
+                to_absolute(Some(Path::new("/kube")), "~/.minikube/ca.crt"),
+                Some(format!("{}/.minikube/ca.crt", home.display()))
+            );
+        }
+        assert_eq!(
+            to_absolute(Some(Path::new("/kube")), "$KUBE_RS_TEST_CERTS/ca.crt").as_deref(),
+            Some("/etc/certs/ca.crt")
+        );
+        assert_eq!(to_absolute(Some(Path::new("/kube")), "/etc/ca.crt"), None);
+        assert_eq!(to_absolute(None, "certs/ca.crt"), None);
+    }
+
+    #[test]
+    fn expands_paths_once_when_loaded() {
+        std::env::set_var("KUBE_RS_TEST_DOLLAR_CERTS", "/etc/$certs");
+        let config = Kubeconfig::from_yaml(
+            r#"
+clusters:
+- name: kind
+  cluster:
+    server: https://127.0.0.1:6443
+    certificate-authority: $KUBE_RS_TEST_DOLLAR_CERTS/ca.crt
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.clusters[0].cluster.certificate_authority.as_deref(),
+            Some("/etc/$certs/ca.crt")
+        );
+    }
+
     #[test]
     fn kubeconfig_from_empty_string() {
         let cfg = Kubeconfig::from_yaml("").unwrap();
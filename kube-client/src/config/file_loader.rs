@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use super::{
     file_config::{AuthInfo, Cluster, Context, Kubeconfig},
     KubeconfigError,
@@ -71,7 +73,10 @@ impl ConfigLoader {
             .iter()
             .find(|named_context| &named_context.name == context_name)
             .map(|named_context| &named_context.context)
-            .ok_or_else(|| KubeconfigError::LoadContext(context_name.clone()))?;
+            .ok_or_else(|| KubeconfigError::MissingContext {
+                context: context_name.clone(),
+                available: config.contexts().map(str::to_owned).collect(),
+            })?;
 
         let cluster_name = cluster.unwrap_or(&current_context.cluster);
         let cluster = config
@@ -79,7 +84,10 @@ impl ConfigLoader {
             .iter()
             .find(|named_cluster| &named_cluster.name == cluster_name)
             .map(|named_cluster| &named_cluster.cluster)
-            .ok_or_else(|| KubeconfigError::LoadClusterOfContext(cluster_name.clone()))?;
+            .ok_or_else(|| KubeconfigError::MissingCluster {
+                context: context_name.clone(),
+                cluster: cluster_name.clone(),
+            })?;
 
         let user_name = user.unwrap_or(&current_context.user);
         let user = config
@@ -87,12 +95,23 @@ impl ConfigLoader {
             .iter()
             .find(|named_user| &named_user.name == user_name)
             .map(|named_user| &named_user.auth_info)
-            .ok_or_else(|| KubeconfigError::FindUser(user_name.clone()))?;
+            .ok_or_else(|| KubeconfigError::MissingUser {
+                context: context_name.clone(),
+                user: user_name.clone(),
+            })?;
+        check_files(context_name, cluster, user)?;
+
+        let mut user = user.clone();
+        if let Some(exec_config) = &mut user.exec {
+            if exec_config.provide_cluster_info {
+                exec_config.cluster = Some(cluster.try_into()?);
+            }
+        }
 
         Ok(ConfigLoader {
             current_context: current_context.clone(),
             cluster: cluster.clone(),
-            user: user.clone(),
+            user,
         })
     }
 
@@ -131,3 +150,92 @@ impl ConfigLoader {
         }
     }
 }
+
+// Checks that the files referenced by the cluster and user exist, unless their data is inlined
+fn check_files(context: &str, cluster: &Cluster, user: &AuthInfo) -> Result<(), KubeconfigError> {
+    let files = [
+        (
+            "certificate-authority",
+            &cluster.certificate_authority,
+            &cluster.certificate_authority_data,
+        ),
+        ("client-certificate", &user.client_certificate, &user.client_certificate_data),
+        ("client-key", &user.client_key, &user.client_key_data),
+        ("tokenFile", &user.token_file, &user.token),
+    ];
+    for (field, file, data) in files {
+        if let (Some(file), None) = (file, data) {
+            let path = PathBuf::from(file);
+            if !path.exists() {
+                return Err(KubeconfigError::MissingFile {
+                    context: context.to_owned(),
+                    field,
+                    path,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigLoader;
+    use crate::config::{Kubeconfig, KubeconfigError};
+
+    #[tokio::test]
+    async fn names_missing_context_and_files() {
+        let config = Kubeconfig::from_yaml(
+            r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: kind
+  cluster:
+    server: https://127.0.0.1:6443
+users:
+- name: admin
+  user:
+    client-certificate: /nonexistent/admin.crt
+    client-key-data: a2V5
+contexts:
+- name: kind-admin
+  context:
+    cluster: kind
+    user: admin
+- name: kind-dev
+  context:
+    cluster: kind
+    user: dev
+"#,
+        )
+        .unwrap();
+
+        let err = ConfigLoader::load(config.clone(), Some(&"kind".into()), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            KubeconfigError::MissingContext { context, available }
+                if context == "kind" && available == &["kind-admin", "kind-dev"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            r#"context "kind" not found in kubeconfig, available contexts: kind-admin, kind-dev"#
+        );
+
+        let err = ConfigLoader::load(config.clone(), Some(&"kind-dev".into()), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, KubeconfigError::MissingUser { user, .. } if user == "dev"));
+
+        let err = ConfigLoader::load(config, Some(&"kind-admin".into()), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            KubeconfigError::MissingFile { field: "client-certificate", path, .. }
+                if path.to_str() == Some("/nonexistent/admin.crt")
+        ));
+    }
+}
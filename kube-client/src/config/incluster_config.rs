@@ -1,4 +1,7 @@
-use std::env;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 use thiserror::Error;
 
@@ -8,9 +11,12 @@ const SERVICE_PORTENV: &str = "KUBERNETES_SERVICE_PORT";
 // New method to connect to kubernetes
 const SERVICE_DNS: &str = "kubernetes.default.svc";
 // Mounted credential files
-const SERVICE_TOKENFILE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
-const SERVICE_CERTFILE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/ca.crt";
-const SERVICE_DEFAULT_NS: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+/// Environment variable overriding the directory of the mounted service account credentials
+pub const SERVICE_ACCOUNT_DIR_ENV: &str = "KUBE_SERVICE_ACCOUNT_DIR";
+const SERVICE_TOKENFILE: &str = "token";
+const SERVICE_CERTFILE: &str = "ca.crt";
+const SERVICE_DEFAULT_NS: &str = "namespace";
 
 /// Errors from loading in-cluster config
 #[derive(Error, Debug)]
@@ -44,6 +50,27 @@ pub enum Error {
     ParseCertificates(#[source] pem::PemError),
 }
 
+/// How in-cluster configuration addresses the apiserver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InClusterAddress {
+    /// The `kubernetes.default.svc` service DNS name
+    Dns,
+    /// The `KUBERNETES_SERVICE_HOST` and `KUBERNETES_SERVICE_PORT` environment variables
+    Env,
+}
+
+impl Default for InClusterAddress {
+    /// `Dns` with `rustls-tls`, which does not support IP based urls, and `Env` otherwise
+    fn default() -> Self {
+        if cfg!(feature = "rustls-tls") {
+            // see https://github.com/kube-rs/kube-rs/issues/587
+            Self::Dns
+        } else {
+            Self::Env
+        }
+    }
+}
+
 /// Returns Kubernetes address from specified environment variables.
 pub fn kube_server() -> Result<http::Uri, Error> {
     kube_host_port()
@@ -75,20 +102,31 @@ fn kube_port() -> Option<String> {
     env::var(SERVICE_PORTENV).ok()
 }
 
-/// Returns token from specified path in cluster.
-pub fn load_token() -> Result<String, Error> {
-    std::fs::read_to_string(&SERVICE_TOKENFILE).map_err(Error::ReadToken)
+/// Returns the directory of the service account credentials, from `KUBE_SERVICE_ACCOUNT_DIR` or the default mount.
+pub fn service_account_dir() -> PathBuf {
+    env::var_os(SERVICE_ACCOUNT_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| PathBuf::from(SERVICE_ACCOUNT_DIR), PathBuf::from)
 }
 
-/// Returns certification from specified path in cluster.
-pub fn load_cert() -> Result<Vec<Vec<u8>>, Error> {
-    let certs = std::fs::read(&SERVICE_CERTFILE).map_err(Error::ReadCertificateBundle)?;
+/// Returns the path of the token in `dir`, after checking that it can be read.
+///
+/// The token is rotated by the kubelet, so the client re-reads it rather than using a copy.
+pub fn token_file(dir: &Path) -> Result<String, Error> {
+    let path = dir.join(SERVICE_TOKENFILE);
+    std::fs::read_to_string(&path).map_err(Error::ReadToken)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Returns certification from `dir`.
+pub fn load_cert(dir: &Path) -> Result<Vec<Vec<u8>>, Error> {
+    let certs = std::fs::read(dir.join(SERVICE_CERTFILE)).map_err(Error::ReadCertificateBundle)?;
     super::certs(&certs).map_err(Error::ParseCertificates)
 }
 
-/// Returns the default namespace from specified path in cluster.
-pub fn load_default_ns() -> Result<String, Error> {
-    std::fs::read_to_string(&SERVICE_DEFAULT_NS).map_err(Error::ReadDefaultNamespace)
+/// Returns the default namespace from `dir`.
+pub fn load_default_ns(dir: &Path) -> Result<String, Error> {
+    std::fs::read_to_string(dir.join(SERVICE_DEFAULT_NS)).map_err(Error::ReadDefaultNamespace)
 }
 
 #[test]
@@ -114,3 +152,11 @@ fn test_kube_server() {
     env::set_var(SERVICE_PORTENV, port);
     assert_eq!(kube_server().unwrap(), "https://fake.io:8080");
 }
+
+#[test]
+fn test_service_account_dir() {
+    env::set_var(SERVICE_ACCOUNT_DIR_ENV, "/run/projected");
+    assert_eq!(service_account_dir(), Path::new("/run/projected"));
+    env::set_var(SERVICE_ACCOUNT_DIR_ENV, "");
+    assert_eq!(service_account_dir(), Path::new(SERVICE_ACCOUNT_DIR));
+}
@@ -4,17 +4,27 @@
 //! The [`Config`] has several constructors plus logic to infer environment.
 //!
 //! Unless you have issues, prefer using [`Config::infer`], and pass it to a [`Client`][crate::Client].
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use thiserror::Error;
 
+#[cfg(feature = "bootstrap")]
+mod bootstrap;
 mod file_config;
 mod file_loader;
 mod incluster_config;
+#[cfg(feature = "client")]
+mod reload;
 
 use file_loader::ConfigLoader;
+#[cfg(feature = "bootstrap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bootstrap")))]
+pub use bootstrap::BootstrapError;
 pub use file_loader::KubeConfigOptions;
-pub use incluster_config::Error as InClusterError;
+pub use incluster_config::{Error as InClusterError, InClusterAddress};
 
 /// Failed to infer config
 #[derive(Error, Debug)]
@@ -26,6 +36,32 @@ pub struct InferConfigError {
     kubeconfig: KubeconfigError,
 }
 
+/// Which configuration source [`Config::infer_with`] tries first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InferOrder {
+    /// Try the in-cluster environment, then the local kubeconfig
+    InClusterFirst,
+    /// Try the local kubeconfig, then the in-cluster environment
+    KubeconfigFirst,
+}
+
+impl Default for InferOrder {
+    fn default() -> Self {
+        Self::InClusterFirst
+    }
+}
+
+/// Options for [`Config::infer_with`]
+#[derive(Clone, Default)]
+pub struct InferOptions {
+    /// The source to try first
+    pub order: InferOrder,
+    /// How the in-cluster configuration addresses the apiserver
+    pub in_cluster_address: InClusterAddress,
+    /// Options for loading the local kubeconfig
+    pub kubeconfig: KubeConfigOptions,
+}
+
 /// Possible errors when loading kubeconfig
 #[derive(Error, Debug)]
 pub enum KubeconfigError {
@@ -41,17 +77,43 @@ pub enum KubeconfigError {
     #[error("kubeconfigs with mismatching api version cannot be merged")]
     ApiVersionMismatch,
 
-    /// Failed to load current context
-    #[error("failed to load current context: {0}")]
-    LoadContext(String),
-
-    /// Failed to load the cluster of context
-    #[error("failed to load the cluster of context: {0}")]
-    LoadClusterOfContext(String),
-
-    /// Failed to find named user
-    #[error("failed to find named user: {0}")]
-    FindUser(String),
+    /// The context was not found in the kubeconfig
+    #[error("context {context:?} not found in kubeconfig, available contexts: {}", .available.join(", "))]
+    MissingContext {
+        /// The name of the context
+        context: String,
+        /// The names of the contexts of the kubeconfig
+        available: Vec<String>,
+    },
+
+    /// The cluster of the context was not found in the kubeconfig
+    #[error("cluster {cluster:?} of context {context:?} not found in kubeconfig")]
+    MissingCluster {
+        /// The name of the context
+        context: String,
+        /// The name of the cluster
+        cluster: String,
+    },
+
+    /// The user of the context was not found in the kubeconfig
+    #[error("user {user:?} of context {context:?} not found in kubeconfig")]
+    MissingUser {
+        /// The name of the context
+        context: String,
+        /// The name of the user
+        user: String,
+    },
+
+    /// A file referenced by the cluster or user of the context does not exist
+    #[error("{field} file {path:?} of context {context:?} does not exist")]
+    MissingFile {
+        /// The name of the context
+        context: String,
+        /// The kubeconfig field referencing the file, such as `client-certificate`
+        field: &'static str,
+        /// The path of the file
+        path: PathBuf,
+    },
 
     /// Failed to find the path of kubeconfig
     #[error("failed to find the path of kubeconfig")]
@@ -65,14 +127,30 @@ pub enum KubeconfigError {
     #[error("failed to parse kubeconfig YAML: {0}")]
     Parse(#[source] serde_yaml::Error),
 
+    /// Failed to parse kubeconfig JSON
+    #[error("failed to parse kubeconfig JSON: {0}")]
+    ParseJson(#[source] serde_json::Error),
+
     /// The structure of the parsed kubeconfig is invalid
     #[error("the structure of the parsed kubeconfig is invalid: {0}")]
     InvalidStructure(#[source] serde_yaml::Error),
 
+    /// Failed to serialize kubeconfig to YAML
+    #[error("failed to serialize kubeconfig YAML: {0}")]
+    Serialize(#[source] serde_yaml::Error),
+
+    /// Failed to write kubeconfig
+    #[error("failed to write kubeconfig to '{1:?}': {0}")]
+    WriteConfig(#[source] std::io::Error, PathBuf),
+
     /// Failed to parse cluster url
     #[error("failed to parse cluster url: {0}")]
     ParseClusterUrl(#[source] http::uri::InvalidUri),
 
+    /// Failed to parse a kubeconfig extension
+    #[error("failed to parse kubeconfig extension: {0}")]
+    ParseExtension(#[source] serde_json::Error),
+
     /// Failed to parse proxy url
     #[error("failed to parse proxy url: {0}")]
     ParseProxyUrl(#[source] http::uri::InvalidUri),
@@ -134,11 +212,77 @@ pub struct Config {
     pub timeout: Option<std::time::Duration>,
     /// Whether to accept invalid ceritifacts
     pub accept_invalid_certs: bool,
+    /// Server name to use for SNI and to verify the server certificate against.
+    ///
+    /// Defaults to the host of `cluster_url` when `None`.
+    pub tls_server_name: Option<String>,
+    /// Whether to verify server certificates with a relaxed verifier when using `rustls-tls`
+    ///
+    /// The relaxed verifier still validates the certificate chain, but also accepts certificates
+    /// issued for IP addresses and legacy certificates that only set the subject common name,
+    /// such as the ones generated by k3d/k3s. This allows using `rustls-tls` with IP based cluster urls.
+    /// Ignored by the other TLS backends, which already accept these certificates.
+    pub accept_legacy_server_certs: bool,
+    /// Whether to append TLS secrets to the file named by the `SSLKEYLOGFILE` environment variable
+    ///
+    /// This allows decrypting the traffic to the apiserver with tools like Wireshark, and must only be used for debugging.
+    /// Supported by `rustls-tls` and `openssl-tls` only.
+    pub tls_key_log: bool,
+    /// SHA-256 hashes of the DER-encoded `SubjectPublicKeyInfo` of the apiserver certificates to accept
+    ///
+    /// If not empty, the apiserver certificate must have one of these public keys, in addition to being verified
+    /// against the root certificates. Combine with `accept_invalid_certs` to verify the public key only.
+    pub tls_pinned_spki_sha256: Vec<[u8; 32]>,
+    /// Fraction of the lifetime of expiring tokens after which they are refreshed
+    ///
+    /// Refreshing well before tokens expire avoids requests racing the expiry. Defaults to `0.8`.
+    /// Applies to tokens from exec plugins and custom [`TokenProvider`](crate::client::TokenProvider)s only.
+    /// Tokens of auth-providers (`gcp`, `oidc`, `azure`) and of EKS are refreshed shortly before they expire.
+    pub token_refresh_ratio: f64,
+    /// Fraction of the lifetime of expiring tokens by which refreshes are randomly brought forward
+    ///
+    /// This spreads out the refreshes of clients that obtained their tokens at the same time. Defaults to `0.1`.
+    pub token_refresh_jitter: f64,
+    /// How long exec credential plugins and the commands of `gcp` auth-providers may run before they are killed
+    ///
+    /// Applies to plugins that prompt the user as well. Defaults to 2 minutes,
+    /// a value of `None` lets plugins run until they exit.
+    pub exec_timeout: Option<std::time::Duration>,
+    /// Whether to write tokens refreshed by the `oidc` auth provider back to the kubeconfig, like kubectl does
+    ///
+    /// The tokens are written to the kubeconfig file that the auth provider was read from, so this has no effect
+    /// unless the config was loaded from a kubeconfig file. Issuers that rotate refresh tokens invalidate the one
+    /// in the kubeconfig, so enable this when other clients share the kubeconfig. Defaults to `false`.
+    pub persist_auth_provider_tokens: bool,
+    /// Whether to generate EKS tokens from AWS credentials instead of running `aws eks get-token` or `aws-iam-authenticator`
+    ///
+    /// This avoids spawning the exec plugin for every token, but only supports static credentials from the environment
+    /// or the shared credentials file. Exec plugins assuming a role are still run. Requires the `eks` feature,
+    /// and defaults to `false`.
+    pub native_eks_tokens: bool,
+    /// How long connections to the apiserver are kept open while idle
+    ///
+    /// Idle connections are closed by a background task of the connection pool. Defaults to 90 seconds,
+    /// a value of `None` keeps idle connections open until the apiserver closes them.
+    pub pool_idle_timeout: Option<std::time::Duration>,
+    /// The maximum number of idle connections kept open per host
+    ///
+    /// Lower it to bound the sockets held by processes that talk to many clusters. Unlimited by default.
+    pub pool_max_idle_per_host: usize,
     // TODO should keep client key and certificate separate. It's split later anyway.
     /// Client certificate and private key in PEM.
     pub(crate) identity_pem: Option<Vec<u8>>,
+    /// Client certificate and private key in a DER-encoded PKCS#12 archive. Takes precedence over `identity_pem`.
+    pub(crate) identity_pkcs12: Option<Pkcs12Identity>,
     /// Stores information to tell the cluster who you are.
     pub(crate) auth_info: AuthInfo,
+    /// Custom source of bearer tokens, used instead of `auth_info`
+    #[cfg(feature = "client")]
+    pub(crate) token_provider: Option<std::sync::Arc<dyn crate::client::TokenProvider>>,
+    /// Extensions of the kubeconfig context, user and cluster, for tool specific data
+    ///
+    /// Empty unless loaded from a kubeconfig.
+    pub extensions: KubeconfigExtensions,
     // TODO Actually support proxy or create an example with custom client
     /// Optional proxy URL.
     pub proxy_url: Option<http::Uri>,
@@ -157,8 +301,23 @@ impl Config {
             root_cert: None,
             timeout: Some(DEFAULT_TIMEOUT),
             accept_invalid_certs: false,
+            tls_server_name: None,
+            accept_legacy_server_certs: false,
+            tls_key_log: false,
+            tls_pinned_spki_sha256: Vec::new(),
             identity_pem: None,
+            identity_pkcs12: None,
             auth_info: AuthInfo::default(),
+            token_refresh_ratio: DEFAULT_TOKEN_REFRESH_RATIO,
+            token_refresh_jitter: DEFAULT_TOKEN_REFRESH_JITTER,
+            exec_timeout: Some(DEFAULT_EXEC_TIMEOUT),
+            persist_auth_provider_tokens: false,
+            native_eks_tokens: false,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            pool_max_idle_per_host: usize::MAX,
+            #[cfg(feature = "client")]
+            token_provider: None,
+            extensions: KubeconfigExtensions::default(),
             proxy_url: None,
         }
     }
@@ -170,20 +329,46 @@ impl Config {
     ///
     /// Fails if inference from both sources fails
     pub async fn infer() -> Result<Self, InferConfigError> {
-        match Self::from_cluster_env() {
-            Err(in_cluster_err) => {
-                tracing::trace!("No in-cluster config found: {}", in_cluster_err);
-                tracing::trace!("Falling back to local kubeconfig");
-                let config = Self::from_kubeconfig(&KubeConfigOptions::default())
-                    .await
-                    .map_err(|kubeconfig_err| InferConfigError {
-                        in_cluster: in_cluster_err,
-                        kubeconfig: kubeconfig_err,
-                    })?;
-
-                Ok(config)
-            }
-            Ok(success) => Ok(success),
+        Self::infer_with(InferOptions::default()).await
+    }
+
+    /// Infer the configuration from the environment, with custom precedence and in-cluster addressing
+    ///
+    /// Like [`Config::infer`], but tries the sources in the order of `options.order`, and addresses the
+    /// apiserver as chosen by `options.in_cluster_address` when using the in-cluster environment.
+    ///
+    /// Fails if inference from both sources fails
+    pub async fn infer_with(options: InferOptions) -> Result<Self, InferConfigError> {
+        let dir = incluster_config::service_account_dir();
+        match options.order {
+            InferOrder::InClusterFirst => match Self::incluster(&dir, options.in_cluster_address) {
+                Err(in_cluster_err) => {
+                    tracing::trace!("No in-cluster config found: {}", in_cluster_err);
+                    tracing::trace!("Falling back to local kubeconfig");
+                    let config = Self::from_kubeconfig(&options.kubeconfig)
+                        .await
+                        .map_err(|kubeconfig_err| InferConfigError {
+                            in_cluster: in_cluster_err,
+                            kubeconfig: kubeconfig_err,
+                        })?;
+
+                    Ok(config)
+                }
+                Ok(success) => Ok(success),
+            },
+            InferOrder::KubeconfigFirst => match Self::from_kubeconfig(&options.kubeconfig).await {
+                Err(kubeconfig_err) => {
+                    tracing::trace!("No local kubeconfig found: {}", kubeconfig_err);
+                    tracing::trace!("Falling back to in-cluster config");
+                    Self::incluster(&dir, options.in_cluster_address).map_err(|in_cluster_err| {
+                        InferConfigError {
+                            in_cluster: in_cluster_err,
+                            kubeconfig: kubeconfig_err,
+                        }
+                    })
+                }
+                Ok(success) => Ok(success),
+            },
         }
     }
 
@@ -192,18 +377,30 @@ impl Config {
     /// This follows the standard [API Access from a Pod](https://kubernetes.io/docs/tasks/access-application-cluster/access-cluster/#accessing-the-api-from-a-pod)
     /// and relies on you having the service account's token mounted,
     /// as well as having given the service account rbac access to do what you need.
+    ///
+    /// The credentials are read from `/var/run/secrets/kubernetes.io/serviceaccount`, unless the
+    /// `KUBE_SERVICE_ACCOUNT_DIR` evar points elsewhere.
     pub fn from_cluster_env() -> Result<Self, InClusterError> {
-        let cluster_url = if cfg!(feature = "rustls-tls") {
-            // try rolling out new method for rustls which does not support ip based urls anyway
-            // see https://github.com/kube-rs/kube-rs/issues/587
-            incluster_config::kube_dns()
-        } else {
-            incluster_config::kube_server()?
+        Self::from_cluster_env_with_service_account(incluster_config::service_account_dir())
+    }
+
+    /// Create configuration from the cluster's environment variables and the service account credentials in `dir`
+    ///
+    /// Like [`Config::from_cluster_env`], for sidecars and projected tokens that mount the `token`, `ca.crt`
+    /// and `namespace` files somewhere else than the default location.
+    pub fn from_cluster_env_with_service_account(dir: impl AsRef<Path>) -> Result<Self, InClusterError> {
+        Self::incluster(dir.as_ref(), InClusterAddress::default())
+    }
+
+    fn incluster(dir: &Path, address: InClusterAddress) -> Result<Self, InClusterError> {
+        let cluster_url = match address {
+            InClusterAddress::Dns => incluster_config::kube_dns(),
+            InClusterAddress::Env => incluster_config::kube_server()?,
         };
 
-        let default_namespace = incluster_config::load_default_ns()?;
-        let root_cert = incluster_config::load_cert()?;
-        let token = incluster_config::load_token()?;
+        let default_namespace = incluster_config::load_default_ns(dir)?;
+        let root_cert = incluster_config::load_cert(dir)?;
+        let token_file = incluster_config::token_file(dir)?;
 
         Ok(Self {
             cluster_url,
@@ -211,11 +408,26 @@ impl Config {
             root_cert: Some(root_cert),
             timeout: Some(DEFAULT_TIMEOUT),
             accept_invalid_certs: false,
+            tls_server_name: None,
+            accept_legacy_server_certs: false,
+            tls_key_log: false,
+            tls_pinned_spki_sha256: Vec::new(),
             identity_pem: None,
+            identity_pkcs12: None,
             auth_info: AuthInfo {
-                token: Some(token),
+                token_file: Some(token_file),
                 ..Default::default()
             },
+            token_refresh_ratio: DEFAULT_TOKEN_REFRESH_RATIO,
+            token_refresh_jitter: DEFAULT_TOKEN_REFRESH_JITTER,
+            exec_timeout: Some(DEFAULT_EXEC_TIMEOUT),
+            persist_auth_provider_tokens: false,
+            native_eks_tokens: false,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            pool_max_idle_per_host: usize::MAX,
+            #[cfg(feature = "client")]
+            token_provider: None,
+            extensions: KubeconfigExtensions::default(),
             proxy_url: None,
         })
     }
@@ -241,6 +453,49 @@ impl Config {
         Self::new_from_loader(loader).await
     }
 
+    /// Create configuration from the YAML or JSON text of a kubeconfig
+    ///
+    /// Useful for kubeconfigs received from secrets or APIs, which would otherwise have to be written to a file.
+    /// Certificates and keys referenced by relative paths are resolved against the working directory,
+    /// so such kubeconfigs should embed them as `*-data` instead.
+    pub async fn from_custom_kubeconfig_data(
+        data: &str,
+        options: &KubeConfigOptions,
+    ) -> Result<Self, KubeconfigError> {
+        Self::from_custom_kubeconfig(Kubeconfig::from_yaml(data)?, options).await
+    }
+
+    /// Create configuration from the context `name` of the default local config file
+    ///
+    /// Like [`Config::from_kubeconfig`], but selects another context than the current-context.
+    /// See [`Kubeconfig::contexts`] for the names of the available contexts.
+    pub async fn from_kubeconfig_context(name: &str) -> Result<Self, KubeconfigError> {
+        Self::from_kubeconfig(&KubeConfigOptions {
+            context: Some(name.to_owned()),
+            ..KubeConfigOptions::default()
+        })
+        .await
+    }
+
+    /// Create one configuration per context of a [`Kubeconfig`] struct
+    ///
+    /// Returns the configuration of every context along with its name, in the order of the kubeconfig.
+    /// Contexts that fail to load do not prevent loading the others, so their errors are returned in place.
+    pub async fn from_kubeconfig_contexts(
+        kubeconfig: &Kubeconfig,
+    ) -> Vec<(String, Result<Self, KubeconfigError>)> {
+        let mut configs = Vec::new();
+        for name in kubeconfig.contexts() {
+            let options = KubeConfigOptions {
+                context: Some(name.to_owned()),
+                ..KubeConfigOptions::default()
+            };
+            let config = Self::from_custom_kubeconfig(kubeconfig.clone(), &options).await;
+            configs.push((name.to_owned(), config));
+        }
+        configs
+    }
+
     async fn new_from_loader(loader: ConfigLoader) -> Result<Self, KubeconfigError> {
         let cluster_url = loader
             .cluster
@@ -283,11 +538,73 @@ impl Config {
             root_cert,
             timeout: Some(DEFAULT_TIMEOUT),
             accept_invalid_certs,
+            tls_server_name: loader.cluster.tls_server_name.clone(),
+            accept_legacy_server_certs: false,
+            tls_key_log: false,
+            tls_pinned_spki_sha256: Vec::new(),
             identity_pem,
+            identity_pkcs12: None,
             proxy_url: loader.proxy_url()?,
+            extensions: KubeconfigExtensions {
+                context: loader.current_context.extensions.clone().unwrap_or_default(),
+                user: loader.user.extensions.clone().unwrap_or_default(),
+                cluster: loader.cluster.extensions.clone().unwrap_or_default(),
+            },
             auth_info: loader.user,
+            token_refresh_ratio: DEFAULT_TOKEN_REFRESH_RATIO,
+            token_refresh_jitter: DEFAULT_TOKEN_REFRESH_JITTER,
+            exec_timeout: Some(DEFAULT_EXEC_TIMEOUT),
+            persist_auth_provider_tokens: false,
+            native_eks_tokens: false,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            pool_max_idle_per_host: usize::MAX,
+            #[cfg(feature = "client")]
+            token_provider: None,
         })
     }
+
+    /// Authenticate with the tokens of a custom [`TokenProvider`](crate::client::TokenProvider)
+    ///
+    /// The provider takes precedence over the credentials loaded from the kubeconfig or the cluster environment.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    #[must_use]
+    pub fn token_provider(mut self, provider: impl crate::client::TokenProvider) -> Self {
+        self.token_provider = Some(std::sync::Arc::new(provider));
+        self
+    }
+
+    /// Authenticate with the client certificate and private key of a DER-encoded PKCS#12 archive,
+    /// protected by `password`
+    ///
+    /// Kubeconfigs have no field for PKCS#12 archives, so they can only be set here. The archive
+    /// takes precedence over the client certificate loaded from the kubeconfig.
+    #[must_use]
+    pub fn identity_pkcs12(mut self, der: Vec<u8>, password: &str) -> Self {
+        self.identity_pkcs12 = Some(Pkcs12Identity {
+            der,
+            password: password.to_owned(),
+        });
+        self
+    }
+}
+
+/// Client identity stored in a DER-encoded PKCS#12 archive.
+#[derive(Clone)]
+pub(crate) struct Pkcs12Identity {
+    /// The DER-encoded archive
+    pub(crate) der: Vec<u8>,
+    /// The password protecting the archive
+    pub(crate) password: String,
+}
+
+impl std::fmt::Debug for Pkcs12Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs12Identity")
+            .field("der", &format!("{} bytes", self.der.len()))
+            .field("password", &"<redacted>")
+            .finish()
+    }
 }
 
 fn certs(data: &[u8]) -> Result<Vec<Vec<u8>>, pem::PemError> {
@@ -306,6 +623,14 @@ fn certs(data: &[u8]) -> Result<Vec<Vec<u8>>, pem::PemError> {
 // https://github.com/kube-rs/kube-rs/issues/146#issuecomment-590924397
 /// Default Timeout
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(295);
+/// Default `pool_idle_timeout`, the same as for hyper's connection pool
+const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Default `token_refresh_ratio`
+pub(crate) const DEFAULT_TOKEN_REFRESH_RATIO: f64 = 0.8;
+/// Default `token_refresh_jitter`
+pub(crate) const DEFAULT_TOKEN_REFRESH_JITTER: f64 = 0.1;
+/// Default `exec_timeout`
+pub(crate) const DEFAULT_EXEC_TIMEOUT: Duration = Duration::from_secs(120);
 
 // temporary catalina hack for openssl only
 #[cfg(all(target_os = "macos", feature = "native-tls"))]
@@ -325,9 +650,11 @@ fn hacky_cert_lifetime_for_macos(_: &[u8]) -> bool {
 
 // Expose raw config structs
 pub use file_config::{
-    AuthInfo, AuthProviderConfig, Cluster, Context, ExecConfig, Kubeconfig, NamedAuthInfo, NamedCluster,
-    NamedContext, NamedExtension, Preferences,
+    AuthInfo, AuthProviderConfig, Cluster, Context, ExecAuthCluster, ExecConfig, ExecInteractiveMode, Kubeconfig,
+    KubeconfigExtensions, NamedAuthInfo, NamedCluster, NamedContext, NamedExtension, Preferences,
 };
+#[cfg(feature = "oidc")]
+pub(crate) use file_config::update_auth_provider_config;
 
 
 #[cfg(test)]
@@ -363,4 +690,89 @@ mod tests {
         let kubeconfig = Config::infer().await.unwrap();
         assert_eq!(kubeconfig.cluster_url, "https://0.0.0.0:6443/");
     }
+
+    #[tokio::test]
+    async fn config_per_context() {
+        use super::{Config, Kubeconfig};
+        let kubeconfig = Kubeconfig::from_yaml(
+            r#"
+        clusters:
+        - cluster:
+            server: https://prod.example.com
+          name: prod
+        - cluster:
+            server: https://staging.example.com
+          name: staging
+        contexts:
+        - context:
+            cluster: prod
+            user: admin
+            namespace: web
+          name: prod
+        - context:
+            cluster: staging
+            user: admin
+          name: staging
+        - context:
+            cluster: missing
+            user: admin
+          name: broken
+        current-context: staging
+        users:
+        - name: admin
+          user:
+            token: secret
+        "#,
+        )
+        .unwrap();
+        assert_eq!(kubeconfig.contexts().collect::<Vec<_>>(), vec![
+            "prod", "staging", "broken"
+        ]);
+
+        let staging = Config::from_custom_kubeconfig_data(&kubeconfig.to_yaml().unwrap(), &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(staging.cluster_url, "https://staging.example.com/");
+
+        let configs = Config::from_kubeconfig_contexts(&kubeconfig).await;
+        assert_eq!(configs.len(), 3);
+        let (name, prod) = &configs[0];
+        assert_eq!(name, "prod");
+        let prod = prod.as_ref().unwrap();
+        assert_eq!(prod.cluster_url, "https://prod.example.com/");
+        assert_eq!(prod.default_namespace, "web");
+        assert_eq!(
+            configs[1].1.as_ref().unwrap().cluster_url,
+            "https://staging.example.com/"
+        );
+        assert!(configs[2].1.is_err());
+    }
+
+    #[test]
+    fn incluster_config_from_custom_dir() {
+        use super::{Config, InClusterAddress};
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("token"), "token").unwrap();
+        std::fs::write(dir.path().join("namespace"), "sidecars").unwrap();
+        std::fs::write(
+            dir.path().join("ca.crt"),
+            "-----BEGIN CERTIFICATE-----\naGVsbG8K\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        std::env::set_var("KUBERNETES_SERVICE_HOST", "fake.io");
+        std::env::set_var("KUBERNETES_SERVICE_PORT", "8080");
+
+        let config = Config::from_cluster_env_with_service_account(dir.path()).unwrap();
+        assert_eq!(config.default_namespace, "sidecars");
+        assert_eq!(config.root_cert, Some(vec![b"hello\n".to_vec()]));
+        assert_eq!(
+            config.auth_info.token_file.unwrap(),
+            dir.path().join("token").to_string_lossy()
+        );
+
+        let config = Config::incluster(dir.path(), InClusterAddress::Env).unwrap();
+        assert_eq!(config.cluster_url, "https://fake.io:8080");
+        let config = Config::incluster(dir.path(), InClusterAddress::Dns).unwrap();
+        assert_eq!(config.cluster_url, "https://kubernetes.default.svc/");
+    }
 }
@@ -0,0 +1,170 @@
+//! Reloading the kubeconfig when it changes on disk
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use futures::{stream, Stream};
+
+use super::{file_config::kubeconfig_paths, Config, KubeConfigOptions, Kubeconfig, KubeconfigError};
+
+// The modification time and length of every file a kubeconfig depends on, `None` for missing files
+type Fingerprint = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+fn fingerprint(paths: impl IntoIterator<Item = PathBuf>) -> Fingerprint {
+    paths
+        .into_iter()
+        .map(|path| {
+            let stat = std::fs::metadata(&path)
+                .and_then(|meta| Ok((meta.modified()?, meta.len())))
+                .ok();
+            (path, stat)
+        })
+        .collect()
+}
+
+// Reads the kubeconfig and fingerprints the files it depends on
+fn load(paths: &[PathBuf]) -> (Fingerprint, Result<Kubeconfig, KubeconfigError>) {
+    // Fingerprint before reading, so that changes while loading are picked up by the next poll
+    let mut current = fingerprint(paths.iter().cloned());
+    let kubeconfig = Kubeconfig::read_from_paths(paths);
+    if let Ok(kubeconfig) = &kubeconfig {
+        current.extend(fingerprint(kubeconfig.referenced_files()));
+    }
+    (current, kubeconfig)
+}
+
+struct Watch {
+    paths: Vec<PathBuf>,
+    options: KubeConfigOptions,
+    interval: Duration,
+    last: Option<Fingerprint>,
+}
+
+impl Watch {
+    // Polls until the kubeconfig (or a file it references) changes, and loads it again
+    async fn next(mut self) -> (Result<Config, KubeconfigError>, Self) {
+        loop {
+            if self.last.is_some() {
+                tokio::time::sleep(self.interval).await;
+            }
+            // Reading files blocks, so it does not run on the runtime
+            let paths = self.paths.clone();
+            let (current, kubeconfig) = tokio::task::spawn_blocking(move || load(&paths))
+                .await
+                .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+            if self.last.as_ref() == Some(&current) {
+                continue;
+            }
+            self.last = Some(current);
+            let config = match kubeconfig {
+                Ok(kubeconfig) => Config::from_custom_kubeconfig(kubeconfig, &self.options).await,
+                Err(err) => Err(err),
+            };
+            return (config, self);
+        }
+    }
+}
+
+pub(crate) fn watch_paths(
+    paths: Vec<PathBuf>,
+    options: KubeConfigOptions,
+    interval: Duration,
+) -> impl Stream<Item = Result<Config, KubeconfigError>> {
+    let watch = Watch {
+        paths,
+        options,
+        interval,
+        last: None,
+    };
+    stream::unfold(watch, |watch| async move { Some(watch.next().await) })
+}
+
+impl Config {
+    /// Watch the default local config file for changes
+    ///
+    /// Returns a stream that yields the current configuration, and then a new configuration whenever the
+    /// kubeconfig files (see [`Config::from_kubeconfig`]) or the certificates, keys and token files they reference
+    /// change on disk. Changes are detected by polling the modification times every `interval`.
+    ///
+    /// This lets long-running tools follow credential rotation, by building a new [`Client`][crate::Client]
+    /// from every configuration. Failures to load a changed kubeconfig, such as a half-written file,
+    /// are yielded without ending the stream.
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use kube::{Client, Config};
+    /// use std::time::Duration;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut configs = Config::watch_kubeconfig(Default::default(), Duration::from_secs(10))?.boxed();
+    /// while let Some(config) = configs.next().await {
+    ///     match config {
+    ///         Ok(config) => {
+    ///             let client = Client::try_from(config)?;
+    ///             // replace the client used by the application
+    ///         }
+    ///         Err(err) => eprintln!("ignoring invalid kubeconfig: {}", err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_kubeconfig(
+        options: KubeConfigOptions,
+        interval: Duration,
+    ) -> Result<impl Stream<Item = Result<Config, KubeconfigError>>, KubeconfigError> {
+        Ok(watch_paths(kubeconfig_paths()?, options, interval))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::watch_paths;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    fn kubeconfig(server: &str) -> String {
+        format!(
+            r#"
+clusters:
+- cluster:
+    server: {}
+  name: test
+contexts:
+- context:
+    cluster: test
+    user: test
+  name: test
+current-context: test
+users:
+- name: test
+  user:
+    token: abc
+"#,
+            server
+        )
+    }
+
+    #[tokio::test]
+    async fn reloads_changed_kubeconfig() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(&path, kubeconfig("https://a.example.com")).unwrap();
+
+        let configs = watch_paths(vec![path.clone()], Default::default(), Duration::from_millis(10));
+        futures::pin_mut!(configs);
+        let config = configs.next().await.unwrap().unwrap();
+        assert_eq!(config.cluster_url, "https://a.example.com/");
+
+        std::fs::write(&path, kubeconfig("https://rotated.example.com")).unwrap();
+        let config = configs.next().await.unwrap().unwrap();
+        assert_eq!(config.cluster_url, "https://rotated.example.com/");
+
+        std::fs::write(&path, "clusters: [").unwrap();
+        assert!(configs.next().await.unwrap().is_err());
+        // Nothing changed since the error
+        assert!(tokio::time::timeout(Duration::from_millis(100), configs.next())
+            .await
+            .is_err());
+    }
+}
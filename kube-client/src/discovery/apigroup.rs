@@ -3,6 +3,8 @@ use super::{
     version::Version,
 };
 use crate::{error::DiscoveryError, Client, Error, Result};
+#[cfg(test)]
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResourceList;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIGroup, APIVersions};
 pub use kube_core::discovery::{verbs, ApiCapabilities, ApiResource, Scope};
 use kube_core::gvk::{GroupVersion, GroupVersionKind, ParseGroupVersionError};
@@ -115,6 +117,22 @@ impl ApiGroup {
         Ok(group)
     }
 
+    #[cfg(test)]
+    pub(crate) fn from_resource_lists(name: &str, lists: Vec<APIResourceList>) -> Result<Self> {
+        let mut data = vec![];
+        for list in lists {
+            let version = list.group_version.parse::<GroupVersion>().unwrap().version;
+            data.push(GroupVersionData::new(version, list)?);
+        }
+        let mut group = ApiGroup {
+            name: name.to_string(),
+            data,
+            preferred: None,
+        };
+        group.sort_versions();
+        Ok(group)
+    }
+
     fn sort_versions(&mut self) {
         self.data
             .sort_by_cached_key(|gvd| Version::parse(gvd.version.as_str()))
@@ -0,0 +1,592 @@
+//! Applying a set of manifests, optionally validating all of them before changing any
+use kube_core::{
+    discovery::{ApiCapabilities, ApiResource},
+    gvk::GroupVersion,
+    params::Preconditions,
+    request,
+};
+use std::{collections::HashSet, str::FromStr, time::Duration};
+
+use super::{ApiGroup, Discovery, Scope};
+use crate::{
+    api::{Api, ApplyParams, DeleteParams, DynamicObject, GroupVersionKind, PostParams, ResourceExt},
+    error::{BulkApplyFailure, DiscoveryError, ErrorResponse},
+    Error, Result,
+};
+
+// How often, and how long apart, to look for the kinds of custom resource definitions applied before
+const KIND_ATTEMPTS: u32 = 10;
+const KIND_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Parameters for [`Discovery::apply_all`]
+#[derive(Clone, Debug)]
+pub struct BulkApplyParams {
+    /// How each object is applied
+    pub apply: ApplyParams,
+    /// Whether to dry-run apply every object first, and apply none of them if any fails
+    pub validate_first: bool,
+    /// Whether to undo the applies of earlier objects when a later object fails to apply
+    pub rollback: bool,
+}
+
+impl BulkApplyParams {
+    /// Parameters for applying as `field_manager`, one object after the other
+    pub fn new(field_manager: &str) -> Self {
+        Self {
+            apply: ApplyParams::new(field_manager),
+            validate_first: false,
+            rollback: false,
+        }
+    }
+
+    /// Validate all objects with a dry run before applying any of them
+    pub fn validate_first(mut self) -> Self {
+        self.validate_first = true;
+        self
+    }
+
+    /// Undo the applies of earlier objects when an object fails to apply
+    ///
+    /// Objects created by the failed call are deleted, and objects that existed are replaced with
+    /// the version read before applying them. Objects that others changed since they were applied
+    /// are left alone, and are not reported as rolled back.
+    pub fn rollback(mut self) -> Self {
+        self.rollback = true;
+        self
+    }
+}
+
+/// The phase of [`Discovery::apply_all`] that failed, in a [`BulkApplyFailure`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BulkApplyPhase {
+    /// The dry run of [`BulkApplyParams::validate_first`], so no object was changed
+    Validation,
+    /// The dry run of an object that needs earlier objects of the call to exist, so it could only
+    /// be validated once they were applied
+    DeferredValidation,
+    /// The real apply
+    Apply,
+}
+
+// An object that was applied, with how to undo it
+struct Applied {
+    api: Api<DynamicObject>,
+    name: String,
+    previous: Option<DynamicObject>,
+    // The version the apply left, so that rollbacks do not clobber later changes of others
+    result: DynamicObject,
+}
+
+// What the objects of a call create that later objects of the call depend on
+#[derive(Default)]
+struct Bundle {
+    namespaces: HashSet<String>,
+    kinds: HashSet<(String, String)>,
+}
+
+impl Bundle {
+    fn new(objs: &[DynamicObject]) -> Self {
+        let mut bundle = Bundle::default();
+        for obj in objs {
+            let types = match &obj.types {
+                Some(types) => types,
+                None => continue,
+            };
+            match (types.api_version.as_str(), types.kind.as_str()) {
+                ("v1", "Namespace") => bundle.namespaces.extend(obj.metadata.name.clone()),
+                (api_version, "CustomResourceDefinition")
+                    if api_version.starts_with("apiextensions.k8s.io/") =>
+                {
+                    let spec = &obj.data["spec"];
+                    if let (Some(group), Some(kind)) =
+                        (spec["group"].as_str(), spec["names"]["kind"].as_str())
+                    {
+                        bundle.kinds.insert((group.to_string(), kind.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        bundle
+    }
+
+    // Whether a custom resource definition of the bundle defines the kind
+    fn defines(&self, gvk: &GroupVersionKind) -> bool {
+        self.kinds.contains(&(gvk.group.clone(), gvk.kind.clone()))
+    }
+
+    // Whether the apiserver failed because a namespace of the bundle does not exist yet
+    fn creates_missing_namespace(&self, err: &ErrorResponse) -> bool {
+        err.code == 404
+            && err.details.as_ref().map_or(false, |d| {
+                d.kind == "namespaces" && self.namespaces.contains(&d.name)
+            })
+    }
+}
+
+impl Discovery {
+    /// Server-side apply `objs` in order, resolving their kinds with this discovery
+    ///
+    /// With [`BulkApplyParams::validate_first`], every object is dry-run applied before any is applied,
+    /// so that invalid manifests fail the call before it changes anything. Objects that need earlier objects
+    /// of `objs` to exist, like objects in a namespace or of a custom resource definition that `objs` create,
+    /// cannot be validated before those are applied. They are dry-run applied right before they are applied
+    /// instead, so they fail in [`BulkApplyPhase::DeferredValidation`] after earlier objects were changed.
+    /// With [`BulkApplyParams::rollback`], a failure to apply an object undoes the applies of the objects
+    /// before it. Other writers may still change the objects in between, so this makes deploys
+    /// all-or-nothing in the common case only.
+    ///
+    /// Kinds that this discovery does not know are looked up when their objects are applied, so objects
+    /// may be of custom resource definitions that `objs` apply before them.
+    /// Objects of namespaced kinds without a namespace are applied to the default namespace of the client.
+    /// Fails with [`Error::BulkApply`] when an object fails to apply.
+    ///
+    /// ```no_run
+    /// use kube::{api::DynamicObject, discovery::{BulkApplyParams, Discovery}, Client};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let discovery = Discovery::new(client).run().await?;
+    ///     let objs: Vec<DynamicObject> = serde_json::from_str(&std::fs::read_to_string("app.json")?)?;
+    ///     let bp = BulkApplyParams::new("deployer").validate_first().rollback();
+    ///     discovery.apply_all(&objs, &bp).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn apply_all(
+        &self,
+        objs: &[DynamicObject],
+        bp: &BulkApplyParams,
+    ) -> Result<Vec<DynamicObject>> {
+        let bundle = Bundle::new(objs);
+        let dry_run = ApplyParams {
+            dry_run: true,
+            ..bp.apply.clone()
+        };
+
+        // Objects that can only be validated once the objects they depend on are applied
+        let mut deferred = HashSet::new();
+        if bp.validate_first {
+            for (i, obj) in objs.iter().enumerate() {
+                let fail = |err| failure(obj, BulkApplyPhase::Validation, vec![], err);
+                let name = name_of(obj).map_err(fail)?;
+                let gvk = gvk_of(obj).map_err(fail)?;
+                let api = match self.resolve_gvk(&gvk) {
+                    Some((ar, caps)) => self.api_with(obj, &ar, &caps),
+                    // The kind is only served once the bundle applied its definition
+                    None if bundle.defines(&gvk) => {
+                        deferred.insert(i);
+                        continue;
+                    }
+                    None => return Err(fail(missing_kind(&gvk))),
+                };
+                match api.apply(&name, &dry_run, obj).await {
+                    Ok(_) => {}
+                    Err(Error::Api(err)) if bundle.creates_missing_namespace(&err) => {
+                        deferred.insert(i);
+                    }
+                    Err(err) => return Err(fail(err)),
+                }
+            }
+        }
+
+        let mut applied = Vec::new();
+        let mut results = Vec::new();
+        for (i, obj) in objs.iter().enumerate() {
+            let validate = deferred.contains(&i).then(|| &dry_run);
+            match self.apply_one(obj, &bundle, bp, validate).await {
+                Ok(done) => {
+                    results.push(done.result.clone());
+                    applied.push(done);
+                }
+                Err((phase, err)) => {
+                    let rolled_back = if bp.rollback {
+                        roll_back(applied).await
+                    } else {
+                        vec![]
+                    };
+                    return Err(failure(obj, phase, rolled_back, err));
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    // Applies `obj`, after dry-running it with `validate` if set, and reading its previous version first
+    // if it may have to be rolled back
+    async fn apply_one(
+        &self,
+        obj: &DynamicObject,
+        bundle: &Bundle,
+        bp: &BulkApplyParams,
+        validate: Option<&ApplyParams>,
+    ) -> std::result::Result<Applied, (BulkApplyPhase, Error)> {
+        let apply = |err| (BulkApplyPhase::Apply, err);
+        let name = name_of(obj).map_err(apply)?;
+        let gvk = gvk_of(obj).map_err(apply)?;
+        let (ar, caps) = match self.resolve_gvk(&gvk) {
+            Some(found) => found,
+            None if bundle.defines(&gvk) => query_defined_kind(&self.client, &gvk).await.map_err(apply)?,
+            None => return Err(apply(missing_kind(&gvk))),
+        };
+        let api = self.api_with(obj, &ar, &caps);
+        if let Some(dry_run) = validate {
+            api.apply(&name, dry_run, obj)
+                .await
+                .map_err(|err| (BulkApplyPhase::DeferredValidation, err))?;
+        }
+        let previous = if bp.rollback {
+            api.get_opt(&name).await.map_err(apply)?
+        } else {
+            None
+        };
+        let result = api.apply(&name, &bp.apply, obj).await.map_err(apply)?;
+        Ok(Applied {
+            api,
+            name,
+            previous,
+            result,
+        })
+    }
+
+    fn api_with(&self, obj: &DynamicObject, ar: &ApiResource, caps: &ApiCapabilities) -> Api<DynamicObject> {
+        let client = self.client.clone();
+        match (&caps.scope, obj.namespace()) {
+            (Scope::Cluster, _) => Api::all_with(client, ar),
+            (Scope::Namespaced, Some(ns)) => Api::namespaced_with(client, &ns, ar),
+            (Scope::Namespaced, None) => Api::default_namespaced_with(client, ar),
+        }
+    }
+}
+
+fn name_of(obj: &DynamicObject) -> Result<String> {
+    obj.metadata.name.clone().ok_or_else(|| {
+        Error::BuildRequest(request::Error::Validation(
+            "objects to apply must have a name".into(),
+        ))
+    })
+}
+
+fn gvk_of(obj: &DynamicObject) -> Result<GroupVersionKind> {
+    let name = obj.metadata.name.clone().unwrap_or_default();
+    let types = obj
+        .types
+        .as_ref()
+        .ok_or(Error::Discovery(DiscoveryError::MissingKind(name)))?;
+    let gv = GroupVersion::from_str(&types.api_version)
+        .map_err(|_| Error::Discovery(DiscoveryError::InvalidGroupVersion(types.api_version.clone())))?;
+    Ok(GroupVersionKind::gvk(&gv.group, &gv.version, &types.kind))
+}
+
+fn missing_kind(gvk: &GroupVersionKind) -> Error {
+    Error::Discovery(DiscoveryError::MissingKind(format!("{:?}", gvk)))
+}
+
+// Looks up a kind of a custom resource definition that was just applied, until the apiserver serves it
+async fn query_defined_kind(
+    client: &crate::Client,
+    gvk: &GroupVersionKind,
+) -> Result<(ApiResource, ApiCapabilities)> {
+    let mut attempt = 1;
+    loop {
+        match ApiGroup::query_gvk(client, gvk).await {
+            Err(_) if attempt < KIND_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(KIND_BACKOFF).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+// Undoes the applies in reverse order, returning the names of the objects that were rolled back
+async fn roll_back(applied: Vec<Applied>) -> Vec<String> {
+    let mut rolled_back = Vec::new();
+    for Applied {
+        api,
+        name,
+        previous,
+        result,
+    } in applied.into_iter().rev()
+    {
+        let res = match previous {
+            None => {
+                // Only deletes the object the apply created, not one that was changed since
+                let dp = DeleteParams {
+                    preconditions: Some(Preconditions {
+                        resource_version: result.metadata.resource_version,
+                        uid: result.metadata.uid,
+                    }),
+                    ..DeleteParams::default()
+                };
+                api.delete(&name, &dp).await.map(|_| ())
+            }
+            Some(mut previous) => {
+                // Conflicts instead of replacing what others changed after the apply
+                previous.metadata.resource_version = result.metadata.resource_version;
+                previous.metadata.managed_fields = None;
+                api.replace(&name, &PostParams::default(), &previous)
+                    .await
+                    .map(|_| ())
+            }
+        };
+        match res {
+            Ok(()) => rolled_back.push(name),
+            Err(err) => tracing::warn!("failed to roll back apply of {}: {}", name, err),
+        }
+    }
+    rolled_back
+}
+
+fn failure(obj: &DynamicObject, phase: BulkApplyPhase, rolled_back: Vec<String>, err: Error) -> Error {
+    let kind = obj
+        .types
+        .as_ref()
+        .map(|types| types.kind.as_str())
+        .unwrap_or_default();
+    Error::BulkApply(BulkApplyFailure {
+        object: format!("{}/{}", kind, obj.metadata.name.as_deref().unwrap_or_default()),
+        phase,
+        rolled_back,
+        source: Box::new(err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use futures::pin_mut;
+    use http::{Request, Response};
+    use hyper::Body;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIResource, APIResourceList};
+    use serde_json::{json, Value};
+    use tower_test::mock;
+
+    fn list(group_version: &str, resources: &[(&str, &str, bool)]) -> APIResourceList {
+        APIResourceList {
+            group_version: group_version.into(),
+            resources: resources
+                .iter()
+                .map(|(name, kind, namespaced)| APIResource {
+                    name: name.to_string(),
+                    kind: kind.to_string(),
+                    namespaced: *namespaced,
+                    verbs: vec!["get".into(), "patch".into(), "update".into(), "delete".into()],
+                    ..APIResource::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn discovery(client: Client) -> Discovery {
+        let mut discovery = Discovery::new(client);
+        let core = list(
+            "v1",
+            &[
+                ("namespaces", "Namespace", false),
+                ("configmaps", "ConfigMap", true),
+            ],
+        );
+        let apiexts = list(
+            "apiextensions.k8s.io/v1",
+            &[("customresourcedefinitions", "CustomResourceDefinition", false)],
+        );
+        for (name, list) in [("", core), ("apiextensions.k8s.io", apiexts)] {
+            let group = ApiGroup::from_resource_lists(name, vec![list]).unwrap();
+            discovery.groups.insert(name.to_string(), group);
+        }
+        discovery
+    }
+
+    fn obj(value: Value) -> DynamicObject {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn respond(send: mock::SendResponse<Response<Body>>, status: u16, body: Value) {
+        send.send_response(
+            Response::builder()
+                .status(status)
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        );
+    }
+
+    async fn body(request: Request<Body>) -> Value {
+        serde_json::from_slice(&hyper::body::to_bytes(request.into_body()).await.unwrap()).unwrap()
+    }
+
+    fn configmap(name: &str, resource_version: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": name,
+                "namespace": "web",
+                "uid": name,
+                "resourceVersion": resource_version,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn validates_bundles_of_namespaces_and_custom_resources() {
+        let objs = vec![
+            obj(json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "web" } })),
+            obj(json!({
+                "apiVersion": "apiextensions.k8s.io/v1",
+                "kind": "CustomResourceDefinition",
+                "metadata": { "name": "crontabs.stable.example.com" },
+                "spec": {
+                    "group": "stable.example.com",
+                    "names": { "kind": "CronTab", "plural": "crontabs" },
+                },
+            })),
+            obj(json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": { "name": "a", "namespace": "web" },
+            })),
+            obj(json!({
+                "apiVersion": "stable.example.com/v1",
+                "kind": "CronTab",
+                "metadata": { "name": "b", "namespace": "web" },
+            })),
+        ];
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let namespace = "/api/v1/namespaces/web?&fieldManager=deployer";
+            let crd = concat!(
+                "/apis/apiextensions.k8s.io/v1/customresourcedefinitions/crontabs.stable.example.com",
+                "?&fieldManager=deployer"
+            );
+            let configmap = "/api/v1/namespaces/web/configmaps/a?&fieldManager=deployer";
+            // The custom resource is not validated, its kind is not served yet
+            for uri in [namespace, crd, configmap] {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.uri().to_string(), uri.replacen("?&", "?&dryRun=All&", 1));
+                if uri == configmap {
+                    respond(
+                        send,
+                        404,
+                        json!({
+                            "status": "Failure",
+                            "message": "namespaces \"web\" not found",
+                            "reason": "NotFound",
+                            "code": 404, "details": { "name": "web", "kind": "namespaces" },
+                        }),
+                    );
+                } else {
+                    respond(send, 200, body(request).await);
+                }
+            }
+            // Both are validated once the namespace and the definition are applied
+            let validate_configmap = configmap.replacen("?&", "?&dryRun=All&", 1);
+            for uri in [namespace, crd, validate_configmap.as_str(), configmap] {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.uri().to_string(), uri);
+                respond(send, 200, body(request).await);
+            }
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), "/apis/stable.example.com/v1");
+            respond(
+                send,
+                200,
+                serde_json::to_value(list("stable.example.com/v1", &[("crontabs", "CronTab", true)]))
+                    .unwrap(),
+            );
+            let crontab = "/apis/stable.example.com/v1/namespaces/web/crontabs/b?&fieldManager=deployer";
+            for uri in [crontab.replacen("?&", "?&dryRun=All&", 1).as_str(), crontab] {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.uri().to_string(), uri);
+                respond(send, 200, body(request).await);
+            }
+        });
+
+        let discovery = discovery(Client::new(mock_service, "default"));
+        let bp = BulkApplyParams::new("deployer").validate_first();
+        let applied = discovery.apply_all(&objs, &bp).await.unwrap();
+        assert_eq!(applied.len(), 4);
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rolls_back_unless_changed_since() {
+        let objs = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let metadata = json!({ "name": name, "namespace": "web" });
+                obj(json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": metadata }))
+            })
+            .collect::<Vec<_>>();
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let url = "/api/v1/namespaces/web/configmaps";
+            let not_found = json!({ "status": "Failure", "message": "", "reason": "NotFound", "code": 404 });
+            // a is created, b existed, c fails to apply
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), format!("{}/a", url));
+            respond(send, 404, not_found.clone());
+            let (_, send) = handle.next_request().await.expect("service not called");
+            respond(send, 200, configmap("a", "2"));
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.uri().to_string(), format!("{}/b", url));
+            respond(send, 200, configmap("b", "3"));
+            let (_, send) = handle.next_request().await.expect("service not called");
+            respond(send, 200, configmap("b", "4"));
+            let (_, send) = handle.next_request().await.expect("service not called");
+            respond(send, 404, not_found);
+            let (_, send) = handle.next_request().await.expect("service not called");
+            let invalid = json!({ "status": "Failure", "message": "", "reason": "Invalid", "code": 422 });
+            respond(send, 422, invalid);
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::PUT);
+            assert_eq!(request.uri().to_string(), format!("{}/b?", url));
+            let replaced = body(request).await;
+            assert_eq!(replaced["metadata"]["resourceVersion"], "4");
+            let conflict = json!({ "status": "Failure", "message": "", "reason": "Conflict", "code": 409 });
+            respond(send, 409, conflict);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::DELETE);
+            assert_eq!(request.uri().to_string(), format!("{}/a?", url));
+            let deleted = body(request).await;
+            assert_eq!(
+                deleted["preconditions"],
+                json!({ "resourceVersion": "2", "uid": "a" })
+            );
+            respond(send, 200, configmap("a", "2"));
+        });
+
+        let discovery = discovery(Client::new(mock_service, "default"));
+        let bp = BulkApplyParams::new("deployer").rollback();
+        match discovery.apply_all(&objs, &bp).await {
+            Err(Error::BulkApply(failure)) => {
+                assert_eq!(failure.object, "ConfigMap/c");
+                assert_eq!(failure.phase, BulkApplyPhase::Apply);
+                assert_eq!(failure.rolled_back, vec!["a".to_string()]);
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn objects_without_names_fail() {
+        let (mock_service, _handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let discovery = discovery(Client::new(mock_service, "default"));
+        let objs = vec![obj(
+            json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": {} }),
+        )];
+        let bp = BulkApplyParams::new("deployer").validate_first();
+        match discovery.apply_all(&objs, &bp).await {
+            Err(Error::BulkApply(failure)) => {
+                assert_eq!(failure.object, "ConfigMap/");
+                assert_eq!(failure.phase, BulkApplyPhase::Validation);
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
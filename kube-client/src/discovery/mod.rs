@@ -1,13 +1,18 @@
 //! High-level utilities for runtime API discovery.
 
-use crate::{Client, Result};
-pub use kube_core::discovery::{verbs, ApiCapabilities, ApiResource, Scope};
+use crate::{error::DiscoveryError, Client, Error, Result};
+use futures::{future::BoxFuture, stream, Future, FutureExt, StreamExt};
+pub use kube_core::discovery::{verbs, ApiCapabilities, ApiResource, Scope, Subresource};
 use kube_core::gvk::GroupVersionKind;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 mod apigroup;
 pub mod oneshot;
 pub use apigroup::ApiGroup;
+mod bulk;
+pub use bulk::{BulkApplyParams, BulkApplyPhase};
 mod parse;
+mod resource_arg;
+pub use resource_arg::{ParseResourceArgError, ResourceArg};
 // an implementation of mentioned kubernetes version priority
 mod version;
 
@@ -50,12 +55,22 @@ impl DiscoveryMode {
 ///
 /// If caching of results is __not required__, then a simpler [`oneshot`](crate::discovery::oneshot) discovery system can be used.
 ///
+/// Clusters with broken aggregated apis (such as an unavailable `metrics.k8s.io`) can make discovery slow or fail.
+/// Use [`Discovery::timeout`] and [`Discovery::retry`] to bound the time spent on each group, and
+/// [`Discovery::partial`] to keep the groups that could be discovered rather than failing.
+///
 /// [`ApiGroup`]: crate::discovery::ApiGroup
 #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
 pub struct Discovery {
     client: Client,
     groups: HashMap<String, ApiGroup>,
     mode: DiscoveryMode,
+    timeout: Option<Duration>,
+    concurrency: usize,
+    retries: u32,
+    backoff: Duration,
+    partial: bool,
+    failed: Vec<(String, Error)>,
 }
 
 /// Caching discovery interface
@@ -66,7 +81,17 @@ impl Discovery {
     pub fn new(client: Client) -> Self {
         let groups = HashMap::new();
         let mode = DiscoveryMode::Block(vec![]);
-        Self { client, groups, mode }
+        Self {
+            client,
+            groups,
+            mode,
+            timeout: None,
+            concurrency: 8,
+            retries: 0,
+            backoff: Duration::from_millis(500),
+            partial: false,
+            failed: vec![],
+        }
     }
 
     /// Configure the discovery client to only look for the listed apigroups
@@ -81,6 +106,38 @@ impl Discovery {
         self
     }
 
+    /// Configure the time allowed for discovering each api group, including its retries
+    ///
+    /// Groups that take longer fail with [`DiscoveryError::Timeout`]. Unlimited by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Configure how many api groups are discovered at once, 8 by default
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Configure retrying the discovery of api groups that fail, up to `retries` times
+    ///
+    /// The first retry waits for `backoff`, which doubles for each following retry. Groups are not retried by default.
+    /// Only transient errors are retried: connection errors, and responses with status 429 or 5xx.
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Configure the discovery client to skip the api groups that fail, rather than failing altogether
+    ///
+    /// The skipped groups and their errors are available from [`Discovery::failed_groups`] after [`Discovery::run`].
+    pub fn partial(mut self) -> Self {
+        self.partial = true;
+        self
+    }
+
     /// Runs or re-runs the configured discovery algorithm and updates/populates the cache
     ///
     /// The cache is empty cleared when this is started. By default, every api group found is checked,
@@ -110,24 +167,97 @@ impl Discovery {
     /// See a bigger example in [examples/dynamic.api](https://github.com/kube-rs/kube-rs/blob/master/examples/dynamic_api.rs)
     pub async fn run(mut self) -> Result<Self> {
         self.groups.clear();
+        self.failed.clear();
         let api_groups = self.client.list_api_groups().await?;
-        // query regular groups + crds under /apis
-        for g in api_groups.groups {
-            let key = g.name.clone();
-            if self.mode.is_queryable(&key) {
-                let apigroup = ApiGroup::query_apis(&self.client, g).await?;
-                self.groups.insert(key, apigroup);
+        let results = {
+            let this = &self;
+            let mut queries: Vec<BoxFuture<'_, (String, Result<ApiGroup>)>> = vec![];
+            // query regular groups + crds under /apis
+            for g in api_groups.groups {
+                let key = g.name.clone();
+                if this.mode.is_queryable(&key) {
+                    queries.push(
+                        async move {
+                            let res = this.query(&key, || ApiGroup::query_apis(&this.client, g.clone())).await;
+                            (key, res)
+                        }
+                        .boxed(),
+                    );
+                }
+            }
+            // query core versions under /api
+            let corekey = ApiGroup::CORE_GROUP.to_string();
+            if this.mode.is_queryable(&corekey) {
+                queries.push(
+                    async move {
+                        let res = this
+                            .query(&corekey, || async move {
+                                let coreapis = this.client.list_core_api_versions().await?;
+                                ApiGroup::query_core(&this.client, coreapis).await
+                            })
+                            .await;
+                        (corekey, res)
+                    }
+                    .boxed(),
+                );
+            }
+            stream::iter(queries)
+                .buffer_unordered(this.concurrency)
+                .collect::<Vec<_>>()
+                .await
+        };
+        for (key, res) in results {
+            match res {
+                Ok(apigroup) => {
+                    self.groups.insert(key, apigroup);
+                }
+                Err(err) if self.partial => {
+                    tracing::warn!(group = key.as_str(), "Skipping api group that failed discovery: {}", err);
+                    self.failed.push((key, err));
+                }
+                Err(err) => return Err(err),
             }
-        }
-        // query core versions under /api
-        let corekey = ApiGroup::CORE_GROUP.to_string();
-        if self.mode.is_queryable(&corekey) {
-            let coreapis = self.client.list_core_api_versions().await?;
-            let apigroup = ApiGroup::query_core(&self.client, coreapis).await?;
-            self.groups.insert(corekey, apigroup);
         }
         Ok(self)
     }
+
+    // Runs a discovery query for the group `key` with the configured timeout and retries
+    async fn query<F, Fut>(&self, key: &str, query: F) -> Result<ApiGroup>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<ApiGroup>>,
+    {
+        let attempts = async {
+            let mut backoff = self.backoff;
+            let mut retries = 0;
+            loop {
+                match query().await {
+                    Err(err) if retries < self.retries && is_transient(&err) => {
+                        tracing::debug!(group = key, "Retrying api group discovery after error: {}", err);
+                        tokio::time::sleep(backoff).await;
+                        backoff = backoff.saturating_mul(2);
+                        retries += 1;
+                    }
+                    res => return res,
+                }
+            }
+        };
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, attempts)
+                .await
+                .unwrap_or_else(|_| Err(Error::Discovery(DiscoveryError::Timeout(key.to_string())))),
+            None => attempts.await,
+        }
+    }
+}
+
+// Whether a failed discovery query may succeed when retried
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Api(ae) => ae.code == 429 || ae.code >= 500,
+        Error::HyperError(_) | Error::Service(_) => true,
+        _ => false,
+    }
 }
 
 /// Interface to the Discovery cache
@@ -147,6 +277,11 @@ impl Discovery {
         self.groups.contains_key(group)
     }
 
+    /// Returns the groups that were skipped by a [`partial`](Discovery::partial) discovery, along with their errors
+    pub fn failed_groups(&self) -> impl Iterator<Item = (&str, &Error)> {
+        self.failed.iter().map(|(group, err)| (group.as_str(), err))
+    }
+
     /// Finds an [`ApiResource`] and its [`ApiCapabilities`] after discovery by matching a GVK
     ///
     /// This is for quick extraction after having done a complete discovery.
@@ -157,4 +292,28 @@ impl Discovery {
             .into_iter()
             .find(|res| res.0.kind == gvk.kind)
     }
+
+    /// Resolves a kubectl-style resource argument to the matching [`ApiResource`]s and their [`ApiCapabilities`]
+    ///
+    /// Resource types are matched by plural name, kind, or short name, optionally qualified by a group or a version and group
+    /// (e.g. `deploy`, `pods.v1.`, `crontabs.stable.example.com`). A type matching no resource is expanded as a category
+    /// (e.g. `all`), which can match several resources. Returns an empty list if nothing matches.
+    ///
+    /// ```no_run
+    /// use kube::{Client, api::{Api, DynamicObject}, discovery::{Discovery, ResourceArg}};
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = Client::try_default().await?;
+    ///     let discovery = Discovery::new(client.clone()).run().await?;
+    ///     let arg: ResourceArg = "deploy/web".parse()?;
+    ///     for (ar, _caps) in discovery.resolve_resource_arg(&arg) {
+    ///         let api: Api<DynamicObject> = Api::default_namespaced_with(client.clone(), &ar);
+    ///         let deploy = api.get(arg.name.as_deref().unwrap()).await?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn resolve_resource_arg(&self, arg: &ResourceArg) -> Vec<(ApiResource, ApiCapabilities)> {
+        resource_arg::resolve(self.groups(), arg)
+    }
 }
@@ -2,7 +2,7 @@
 use crate::{error::DiscoveryError, Error, Result};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIResource, APIResourceList};
 use kube_core::{
-    discovery::{ApiCapabilities, ApiResource, Scope},
+    discovery::{builtin_selectable_fields, ApiCapabilities, ApiResource, Scope},
     gvk::{GroupVersion, ParseGroupVersionError},
 };
 
@@ -52,10 +52,20 @@ pub(crate) fn parse_apicapabilities(list: &APIResourceList, name: &str) -> Resul
             subresources.push((api_resource, caps));
         }
     }
+    let group = ar
+        .group
+        .clone()
+        .or_else(|| list.group_version.parse::<GroupVersion>().ok().map(|gv| gv.group))
+        .unwrap_or_default();
+    let selectable_fields = builtin_selectable_fields(&group, name)
+        .map(|fields| fields.iter().map(|field| field.to_string()).collect());
     Ok(ApiCapabilities {
         scope,
         subresources,
         operations: ar.verbs.clone(),
+        short_names: ar.short_names.clone().unwrap_or_default(),
+        categories: ar.categories.clone().unwrap_or_default(),
+        selectable_fields,
     })
 }
 
@@ -0,0 +1,227 @@
+//! Parsing and resolution of kubectl-style resource arguments
+use super::{ApiCapabilities, ApiGroup, ApiResource};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("failed to parse resource argument: {0}")]
+/// Failed to parse resource argument.
+pub struct ParseResourceArgError(pub String);
+
+/// A kubectl-style resource argument, such as `deploy/web`, `pods.v1.` or `crontabs.stable.example.com`
+///
+/// The resource type can be a plural name, kind, or short name, and is matched case-insensitively.
+/// It can be qualified by a group (`deployments.apps`), or by a version and group (`deployments.v1.apps`),
+/// where a trailing dot denotes the core group (`pods.v1.`).
+///
+/// Use [`Discovery::resolve_resource_arg`](crate::discovery::Discovery::resolve_resource_arg) to resolve it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceArg {
+    /// The resource type, lowercased
+    pub resource: String,
+    /// The group, or version and group, qualifying the resource type
+    pub qualifier: Option<String>,
+    /// The name of the object, if given as `type/name`
+    pub name: Option<String>,
+}
+
+impl FromStr for ResourceArg {
+    type Err = ParseResourceArgError;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let (ty, name) = match arg.split_once('/') {
+            Some((ty, name)) if !name.is_empty() && !name.contains('/') => (ty, Some(name.to_string())),
+            Some(_) => return Err(ParseResourceArgError(arg.into())),
+            None => (arg, None),
+        };
+        let (resource, qualifier) = match ty.split_once('.') {
+            Some((resource, qualifier)) => (resource, Some(qualifier.to_string())),
+            None => (ty, None),
+        };
+        if resource.is_empty() {
+            return Err(ParseResourceArgError(arg.into()));
+        }
+        Ok(Self {
+            resource: resource.to_lowercase(),
+            qualifier,
+            name,
+        })
+    }
+}
+
+impl ResourceArg {
+    /// The (version, group) pairs the qualifier could denote, most specific first
+    fn candidates(&self) -> Vec<(Option<&str>, Option<&str>)> {
+        match self.qualifier.as_deref() {
+            None => vec![(None, None)],
+            Some(qualifier) => {
+                let mut candidates = vec![];
+                if let Some((version, group)) = qualifier.split_once('.') {
+                    candidates.push((Some(version), Some(group)));
+                }
+                candidates.push((None, Some(qualifier)));
+                candidates
+            }
+        }
+    }
+}
+
+pub(crate) fn resolve<'a>(
+    groups: impl Iterator<Item = &'a ApiGroup>,
+    arg: &ResourceArg,
+) -> Vec<(ApiResource, ApiCapabilities)> {
+    let mut groups = groups.collect::<Vec<_>>();
+    // Prefer the core group when a type is served by several groups (e.g. `events`), then go alphabetically
+    groups.sort_by(|a, b| {
+        (a.name() != ApiGroup::CORE_GROUP, a.name()).cmp(&(b.name() != ApiGroup::CORE_GROUP, b.name()))
+    });
+
+    let by_name = |ar: &ApiResource, _: &ApiCapabilities| {
+        ar.plural == arg.resource || ar.kind.to_lowercase() == arg.resource
+    };
+    let by_short_name =
+        |_: &ApiResource, caps: &ApiCapabilities| caps.short_names.iter().any(|s| *s == arg.resource);
+    let matchers: [&dyn Fn(&ApiResource, &ApiCapabilities) -> bool; 2] = [&by_name, &by_short_name];
+
+    for (version, group) in arg.candidates() {
+        for is_match in matchers {
+            for g in groups
+                .iter()
+                .filter(|g| group.map_or(true, |group| g.name() == group))
+            {
+                let resources = match version {
+                    Some(version) => g.versioned_resources(version),
+                    None => g.recommended_resources(),
+                };
+                if let Some(found) = resources.into_iter().find(|(ar, caps)| is_match(ar, caps)) {
+                    return vec![found];
+                }
+            }
+        }
+    }
+
+    // Nothing matched by name, so try the type as a category
+    if arg.qualifier.is_some() {
+        return vec![];
+    }
+    groups
+        .iter()
+        .flat_map(|g| g.recommended_resources())
+        .filter(|(_, caps)| caps.categories.iter().any(|c| *c == arg.resource))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIResource, APIResourceList};
+
+    fn list(group_version: &str, resources: &[(&str, &str, &[&str], &[&str])]) -> APIResourceList {
+        APIResourceList {
+            group_version: group_version.into(),
+            resources: resources
+                .iter()
+                .map(|(name, kind, short_names, categories)| APIResource {
+                    name: name.to_string(),
+                    kind: kind.to_string(),
+                    namespaced: true,
+                    short_names: Some(short_names.iter().map(|s| s.to_string()).collect()),
+                    categories: Some(categories.iter().map(|s| s.to_string()).collect()),
+                    verbs: vec!["get".into(), "list".into()],
+                    ..APIResource::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn groups() -> Vec<ApiGroup> {
+        vec![
+            ApiGroup::from_resource_lists(
+                "",
+                vec![list(
+                    "v1",
+                    &[
+                        ("pods", "Pod", &["po"], &["all"]),
+                        ("events", "Event", &["ev"], &[]),
+                    ],
+                )],
+            )
+            .unwrap(),
+            ApiGroup::from_resource_lists(
+                "apps",
+                vec![list(
+                    "apps/v1",
+                    &[("deployments", "Deployment", &["deploy"], &["all"])],
+                )],
+            )
+            .unwrap(),
+            ApiGroup::from_resource_lists(
+                "events.k8s.io",
+                vec![list("events.k8s.io/v1", &[("events", "Event", &["ev"], &[])])],
+            )
+            .unwrap(),
+            ApiGroup::from_resource_lists(
+                "stable.example.com",
+                vec![
+                    list("stable.example.com/v1", &[("crontabs", "CronTab", &["ct"], &[])]),
+                    list("stable.example.com/v2", &[("crontabs", "CronTab", &["ct"], &[])]),
+                ],
+            )
+            .unwrap(),
+        ]
+    }
+
+    fn resolve_arg(arg: &str) -> Vec<String> {
+        let groups = groups();
+        resolve(groups.iter(), &arg.parse().unwrap())
+            .into_iter()
+            .map(|(ar, _)| format!("{}.{}", ar.plural, ar.api_version))
+            .collect()
+    }
+
+    #[test]
+    fn parses_resource_args() {
+        let arg: ResourceArg = "Deploy/web".parse().unwrap();
+        assert_eq!(
+            arg,
+            ResourceArg {
+                resource: "deploy".into(),
+                qualifier: None,
+                name: Some("web".into()),
+            }
+        );
+        let arg: ResourceArg = "pods.v1.".parse().unwrap();
+        assert_eq!(arg.qualifier.as_deref(), Some("v1."));
+        assert_eq!(
+            arg.candidates(),
+            vec![(Some("v1"), Some("")), (None, Some("v1."))]
+        );
+        assert!("".parse::<ResourceArg>().is_err());
+        assert!("pods/".parse::<ResourceArg>().is_err());
+        assert!("pods/a/b".parse::<ResourceArg>().is_err());
+        assert!(".apps/web".parse::<ResourceArg>().is_err());
+    }
+
+    #[test]
+    fn resolves_resource_args() {
+        assert_eq!(resolve_arg("deploy/web"), vec!["deployments.apps/v1"]);
+        assert_eq!(resolve_arg("Deployment"), vec!["deployments.apps/v1"]);
+        assert_eq!(resolve_arg("pods.v1."), vec!["pods.v1"]);
+        assert_eq!(resolve_arg("events"), vec!["events.v1"]);
+        assert_eq!(
+            resolve_arg("events.events.k8s.io"),
+            vec!["events.events.k8s.io/v1"]
+        );
+        assert_eq!(
+            resolve_arg("crontabs.stable.example.com"),
+            vec!["crontabs.stable.example.com/v2"]
+        );
+        assert_eq!(
+            resolve_arg("ct.v1.stable.example.com"),
+            vec!["crontabs.stable.example.com/v1"]
+        );
+        assert_eq!(resolve_arg("all"), vec!["pods.v1", "deployments.apps/v1"]);
+        assert!(resolve_arg("deployments.v1.").is_empty());
+        assert!(resolve_arg("nope").is_empty());
+    }
+}
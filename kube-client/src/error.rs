@@ -39,6 +39,22 @@ pub enum Error {
     #[error("Error reading events stream: {0}")]
     ReadEvents(#[source] std::io::Error),
 
+    /// Failed to read the data given to [`Api::upload_chunked`](crate::Api::upload_chunked)
+    #[cfg(feature = "client")]
+    #[error("failed to read data to upload: {0}")]
+    ReadUpload(#[source] std::io::Error),
+
+    /// The parts of the data stored by [`Api::upload_chunked`](crate::Api::upload_chunked)
+    /// do not match their index
+    #[cfg(feature = "client")]
+    #[error("chunked data {name} is inconsistent: {reason}")]
+    InconsistentChunks {
+        /// The name of the index object
+        name: String,
+        /// What does not match
+        reason: String,
+    },
+
     /// Http based error
     #[error("HttpError: {0}")]
     HttpError(#[source] http::Error),
@@ -47,6 +63,10 @@ pub enum Error {
     #[error("Error deserializing response")]
     SerdeError(#[source] serde_json::Error),
 
+    /// Failed to deserialize an object from a response, with the context of the failure
+    #[error("Error deserializing response: {0}")]
+    Deserialize(#[source] DeserializeError),
+
     /// Failed to build request
     #[error("Failed to build request: {0}")]
     BuildRequest(#[source] kube_core::request::Error),
@@ -83,11 +103,271 @@ pub enum Error {
     #[error("failed to upgrade to a WebSocket connection: {0}")]
     UpgradeConnection(#[source] crate::client::UpgradeConnectionError),
 
+    /// The WebSocket connection of a port-forward failed
+    #[cfg(feature = "ws")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+    #[error("websocket connection failed: {0}")]
+    WebSocket(#[source] tokio_tungstenite::tungstenite::Error),
+
+    /// Copying files to or from a container failed in the container,
+    /// see [`Api::copy_to`](crate::Api::copy_to)
+    #[cfg(feature = "ws")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+    #[error("failed to copy {path} in the container: {reason}")]
+    CopyRemote {
+        /// The path in the container
+        path: String,
+        /// The error reported by `tar` in the container
+        reason: String,
+    },
+
+    /// Copying files to or from a container failed locally, see [`Api::copy_to`](crate::Api::copy_to)
+    #[cfg(feature = "ws")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+    #[error("failed to copy {path}: {source}")]
+    CopyLocal {
+        /// The local path
+        path: String,
+        /// The error reading or writing it
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The request body exceeded the size limit of the apiserver or etcd
+    ///
+    /// The apiserver rejects request bodies over 3MiB, and etcd rejects objects over 1.5MiB by default.
+    /// Large data should be split across several objects, or kept outside of the cluster.
+    #[error("request body of {size} bytes is too large, split large data across several objects: {source}")]
+    RequestTooLarge {
+        /// Size of the rejected request body in bytes
+        size: usize,
+        /// The error returned by the apiserver
+        #[source]
+        source: ErrorResponse,
+    },
+
+    /// The `resourceVersion` of a watch is too old (`410 Gone`), from [`Api::watch`](crate::Api::watch)
+    ///
+    /// The events since that version have been compacted away, so the watch cannot be resumed without
+    /// missing events. Relist to resync, or watch from `latest_rv` if missing events is acceptable.
+    #[error("watch expired, latest resourceVersion is {latest_rv:?}: {source}")]
+    WatchExpired {
+        /// The oldest `resourceVersion` the apiserver can still watch from, if it suggested one
+        latest_rv: Option<String>,
+        /// The error returned by the apiserver
+        #[source]
+        source: ErrorResponse,
+    },
+
     /// Errors related to client auth
     #[cfg(feature = "client")]
     #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
     #[error("auth error: {0}")]
     Auth(#[source] crate::client::AuthError),
+
+    /// The object has no fields managed by the field manager
+    #[error("object has no fields managed by {0}")]
+    MissingFieldManager(String),
+
+    /// Inspecting or rolling back the revisions of a Deployment failed,
+    /// see [`Api::rollout_undo`](crate::Api::rollout_undo)
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    #[error("rollout error: {0}")]
+    Rollout(#[source] crate::api::RolloutError),
+
+    /// Force finalizing was not confirmed with [`ForceFinalizeParams::confirm`](crate::api::ForceFinalizeParams::confirm)
+    #[error("force finalizing skips the cleanup of finalizers and must be confirmed")]
+    UnconfirmedForceFinalize,
+
+    /// The object to force finalize is not being deleted
+    #[error("refusing to force finalize {0}, which is not being deleted")]
+    NotDeleting(String),
+
+    /// The issued token is missing, or expires sooner than
+    /// [`TokenRequestParams::min_validity_seconds`](crate::api::TokenRequestParams::min_validity_seconds)
+    #[error("rejected issued token: {0}")]
+    TokenExpiry(String),
+
+    /// Server-side apply conflicted with the fields of other field managers, see [`Api::apply`]
+    ///
+    /// [`Api::apply`]: crate::Api::apply
+    #[error("{0}")]
+    ApplyConflict(#[source] ConflictReport),
+
+    /// A PodDisruptionBudget does not allow the eviction yet, see [`Api::evict`]
+    ///
+    /// The eviction can be retried later, once other disrupted pods are healthy again.
+    ///
+    /// [`Api::evict`]: crate::Api::evict
+    #[error("{0}")]
+    EvictionBlocked(#[source] EvictionBlocked),
+
+    /// An object of a bulk apply failed, see [`Discovery::apply_all`]
+    ///
+    /// [`Discovery::apply_all`]: crate::Discovery::apply_all
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    #[error("{0}")]
+    BulkApply(#[source] BulkApplyFailure),
+}
+
+/// The failure of an object of [`Discovery::apply_all`](crate::Discovery::apply_all)
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+#[derive(Debug)]
+pub struct BulkApplyFailure {
+    /// The failed object, as `kind/name`
+    pub object: String,
+    /// Whether the object failed validation, in which case no object was changed unless its validation
+    /// was deferred, or its apply
+    pub phase: crate::discovery::BulkApplyPhase,
+    /// The names of the objects whose applies were undone, with [`BulkApplyParams::rollback`]
+    ///
+    /// [`BulkApplyParams::rollback`]: crate::discovery::BulkApplyParams::rollback
+    pub rolled_back: Vec<String>,
+    /// The error of the object
+    pub source: Box<Error>,
+}
+
+#[cfg(feature = "client")]
+impl std::fmt::Display for BulkApplyFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self.phase {
+            crate::discovery::BulkApplyPhase::Validation
+            | crate::discovery::BulkApplyPhase::DeferredValidation => "validating",
+            crate::discovery::BulkApplyPhase::Apply => "applying",
+        };
+        write!(f, "failed {} {}: {}", phase, self.object, self.source)?;
+        if !self.rolled_back.is_empty() {
+            write!(f, " (rolled back {})", self.rolled_back.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "client")]
+impl std::error::Error for BulkApplyFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// An eviction that was refused because it would violate a PodDisruptionBudget
+///
+/// Made from the `429 TooManyRequests` response of the apiserver with a `DisruptionBudget` cause.
+#[derive(Clone, Debug)]
+pub struct EvictionBlocked {
+    /// The name of the pod
+    pub name: String,
+    /// Why the disruption budget blocks the eviction, like `The disruption budget web needs 2 healthy pods`
+    pub message: String,
+    /// How long the apiserver asks to wait before retrying, if it does
+    pub retry_after: Option<std::time::Duration>,
+    /// The error returned by the apiserver
+    pub source: ErrorResponse,
+}
+
+impl std::fmt::Display for EvictionBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "eviction of {} is blocked by a disruption budget: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for EvictionBlocked {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// The conflicts of a server-side apply with the fields of other field managers
+///
+/// Made from the `409 Conflict` response of the apiserver, which lists a cause per conflicting field.
+#[derive(Clone, Debug)]
+pub struct ConflictReport {
+    /// The name of the applied object
+    pub name: String,
+    /// The field manager of the apply
+    pub field_manager: String,
+    /// The conflicting fields
+    pub conflicts: Vec<FieldConflict>,
+    /// The error returned by the apiserver
+    pub source: ErrorResponse,
+}
+
+/// A field of an applied object that is managed by another field manager, in a [`ConflictReport`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldConflict {
+    /// The path of the field, like `.spec.replicas`
+    pub field: String,
+    /// The field manager that manages the field, like `kubectl`
+    pub manager: String,
+    /// The API version the field manager last applied the field with
+    pub api_version: Option<String>,
+}
+
+impl std::fmt::Display for ConflictReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "apply of {} by {} conflicts with {} field(s)",
+            self.name,
+            self.field_manager,
+            self.conflicts.len()
+        )?;
+        for (i, conflict) in self.conflicts.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { ", " };
+            write!(f, "{}{} managed by {}", sep, conflict.field, conflict.manager)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConflictReport {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Context of a failure to deserialize an object from a response
+///
+/// Identifies the object that failed to deserialize, and where in the response it failed,
+/// such as a field that does not match the schema of the expected type.
+#[derive(Debug)]
+pub struct DeserializeError {
+    /// The `apiVersion` and `kind` of the object, if present in the response
+    pub types: Option<kube_core::TypeMeta>,
+    /// The name of the object, if present in the response
+    pub name: Option<String>,
+    /// The namespace of the object, if present in the response
+    pub namespace: Option<String>,
+    /// The path to the failing value in the response, like `.items[1].spec.replicas`
+    pub path: String,
+    /// The byte offset of the failure in the response
+    pub offset: usize,
+    /// The error from `serde_json`
+    pub source: serde_json::Error,
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("failed to deserialize")?;
+        if let Some(types) = &self.types {
+            write!(f, " {} {}", types.api_version, types.kind)?;
+        }
+        match (&self.namespace, &self.name) {
+            (Some(namespace), Some(name)) => write!(f, " {}/{}", namespace, name)?,
+            (None, Some(name)) => write!(f, " {}", name)?,
+            _ => {}
+        }
+        write!(f, " at {} (byte {}): {}", self.path, self.offset, self.source)
+    }
+}
+
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -112,4 +392,8 @@ pub enum DiscoveryError {
     /// Empty ApiGroup
     #[error("Empty Api Group: {0}")]
     EmptyApiGroup(String),
+
+    /// Discovering an ApiGroup took longer than the configured timeout
+    #[error("Timed out discovering Api Group: {0}")]
+    Timeout(String),
 }
@@ -97,6 +97,7 @@ cfg_client! {
     pub mod api;
     pub mod discovery;
     pub mod client;
+    pub mod search;
 
     #[doc(inline)]
     pub use api::Api;
@@ -112,6 +113,10 @@ cfg_config! {
     pub use config::Config;
 }
 
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+#[cfg(feature = "testing")]
+pub mod testing;
+
 cfg_error! {
     pub mod error;
     #[doc(inline)] pub use error::Error;
@@ -0,0 +1,206 @@
+//! Searching for objects by label across all kinds, like `kubectl get <every listable kind> -l <selector>`
+use futures::{stream, StreamExt, TryStreamExt};
+use kube_core::{
+    discovery::{verbs, ApiResource, Scope},
+    dynamic::DynamicObject,
+    params::ListParams,
+    request::{Request, METADATA_LIST_MIME},
+    ObjectList, Resource, TypeMeta,
+};
+
+use crate::{discovery::Discovery, error::ErrorResponse, Client, Error, Result};
+
+/// Options for [`by_label`]
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    /// Only search this namespace, skipping cluster scoped kinds
+    pub namespace: Option<String>,
+    /// Only fetch the metadata of matching objects (default)
+    ///
+    /// The `data` of the returned objects is then empty.
+    pub metadata_only: bool,
+    /// The maximum number of kinds listed at once
+    pub concurrency: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            namespace: None,
+            metadata_only: true,
+            concurrency: 8,
+        }
+    }
+}
+
+/// The result of a [`by_label`] search
+#[derive(Clone, Debug, Default)]
+pub struct SearchResult {
+    /// The matching objects, along with their kind
+    pub matches: Vec<(ApiResource, DynamicObject)>,
+    /// The kinds that could not be searched, because listing them is forbidden or they went away
+    pub skipped: Vec<ApiResource>,
+    /// The api groups whose kinds could not be discovered, with the error of their discovery
+    pub failed_groups: Vec<(String, String)>,
+}
+
+/// Finds the objects of every listable kind that match the label `selector`
+///
+/// Discovers the kinds served by the apiserver, and lists the recommended version of each with the
+/// label selector, at most [`SearchOptions::concurrency`] at a time. Kinds that the client may not list
+/// are reported in [`SearchResult::skipped`] rather than failing the search, and so are api groups that fail
+/// discovery in [`SearchResult::failed_groups`]. Failing to authenticate still fails the search.
+///
+/// Kinds served by several groups (like `events` in the core and `events.k8s.io` groups) are searched in each group.
+///
+/// ```no_run
+/// use kube::{Client, search::{self, SearchOptions}, ResourceExt};
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = Client::try_default().await?;
+///     let found = search::by_label(&client, "app.kubernetes.io/instance=blog", &SearchOptions::default()).await?;
+///     for (ar, obj) in found.matches {
+///         println!("{} {}", ar.kind, obj.name());
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn by_label(client: &Client, selector: &str, options: &SearchOptions) -> Result<SearchResult> {
+    let discovery = Discovery::new(client.clone()).partial().run().await?;
+    let resources = discovery
+        .groups()
+        .flat_map(|group| group.recommended_resources())
+        .filter(|(_, caps)| caps.supports_operation(verbs::LIST))
+        .filter(|(_, caps)| options.namespace.is_none() || caps.scope == Scope::Namespaced)
+        .map(|(ar, _)| ar)
+        .collect::<Vec<_>>();
+
+    let lp = ListParams::default().labels(selector);
+    let searches = resources.into_iter().map(|ar| {
+        let lp = &lp;
+        async move {
+            match list_all(client, &ar, lp, options).await {
+                Ok(objects) => Ok((ar, Some(objects))),
+                Err(Error::Api(err)) if is_forbidden(&err) => Ok((ar, None)),
+                Err(err) => Err(err),
+            }
+        }
+    });
+    let mut listed = stream::iter(searches)
+        .buffer_unordered(options.concurrency.max(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    listed.sort_by(|(a, _), (b, _)| (&a.group, &a.kind).cmp(&(&b.group, &b.kind)));
+
+    let mut result = SearchResult {
+        failed_groups: discovery
+            .failed_groups()
+            .map(|(group, err)| (group.to_string(), err.to_string()))
+            .collect(),
+        ..SearchResult::default()
+    };
+    for (ar, objects) in listed {
+        match objects {
+            Some(objects) => result
+                .matches
+                .extend(objects.into_iter().map(|obj| (ar.clone(), obj))),
+            None => result.skipped.push(ar),
+        }
+    }
+    Ok(result)
+}
+
+// Lists every page of the matching objects of a kind
+async fn list_all(
+    client: &Client,
+    ar: &ApiResource,
+    lp: &ListParams,
+    options: &SearchOptions,
+) -> Result<Vec<DynamicObject>> {
+    let mut lp = lp.clone();
+    let mut objects = Vec::new();
+    loop {
+        let req = list_request(ar, options, &lp)?;
+        let list = client.request::<ObjectList<DynamicObject>>(req).await?;
+        objects.extend(list.items.into_iter().map(|mut obj| {
+            // Items of lists, and partial metadata, do not have the type of the kind
+            obj.types = Some(TypeMeta {
+                api_version: ar.api_version.clone(),
+                kind: ar.kind.clone(),
+            });
+            obj
+        }));
+        match list.metadata.continue_ {
+            Some(token) if !token.is_empty() => lp.continue_token = Some(token),
+            _ => return Ok(objects),
+        }
+    }
+}
+
+fn list_request(
+    ar: &ApiResource,
+    options: &SearchOptions,
+    lp: &ListParams,
+) -> Result<http::Request<Vec<u8>>> {
+    let url = DynamicObject::url_path(ar, options.namespace.as_deref());
+    let mut req = Request::new(url).list(lp).map_err(Error::BuildRequest)?;
+    if options.metadata_only {
+        req.headers_mut().insert(
+            http::header::ACCEPT,
+            http::HeaderValue::from_static(METADATA_LIST_MIME),
+        );
+    }
+    Ok(req)
+}
+
+// Whether the client may not list a kind, or the kind went away since discovery
+//
+// Unauthorized requests are not skipped, as they fail for every kind.
+fn is_forbidden(err: &ErrorResponse) -> bool {
+    matches!(err.code, 403 | 404)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kube_core::gvk::GroupVersionKind;
+
+    #[test]
+    fn lists_metadata_by_default() {
+        let ar = ApiResource::from_gvk(&GroupVersionKind::gvk("apps", "v1", "Deployment"));
+        let lp = ListParams::default().labels("app=blog");
+        let options = SearchOptions {
+            namespace: Some("web".into()),
+            ..SearchOptions::default()
+        };
+        let req = list_request(&ar, &options, &lp).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/apis/apps/v1/namespaces/web/deployments?&labelSelector=app%3Dblog"
+        );
+        assert_eq!(req.headers()[http::header::ACCEPT], METADATA_LIST_MIME);
+
+        let options = SearchOptions {
+            metadata_only: false,
+            ..SearchOptions::default()
+        };
+        let req = list_request(&ar, &options, &lp).unwrap();
+        assert_eq!(req.uri(), "/apis/apps/v1/deployments?&labelSelector=app%3Dblog");
+        assert!(req.headers().get(http::header::ACCEPT).is_none());
+    }
+
+    #[test]
+    fn skips_only_forbidden_and_missing_kinds() {
+        let response = |code| ErrorResponse {
+            status: "Failure".into(),
+            message: String::new(),
+            reason: String::new(),
+            code,
+            details: None,
+        };
+        assert!(is_forbidden(&response(403)));
+        assert!(is_forbidden(&response(404)));
+        assert!(!is_forbidden(&response(401)));
+        assert!(!is_forbidden(&response(500)));
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt::Debug;
+
+use kube_core::params::{Patch, PatchParams};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{Error, Result};
+use crate::{Api, Resource};
+
+/// A sequence of server-side apply payloads that are validated with dry-runs
+///
+/// Every step is applied with `dryRun=All`, so the apiserver validates and defaults the payload
+/// (including admission webhooks and field ownership conflicts) without persisting anything.
+/// Since nothing is persisted, each step is validated against the current state of the cluster.
+/// Steps added with [`ApplyFixture::then`] build their payload from the result of the previous step.
+///
+/// ```no_run
+/// use k8s_openapi::api::core::v1::ConfigMap;
+/// use kube_client::{testing::ApplyFixture, Api, Client};
+/// use serde_json::json;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let outcomes = ApplyFixture::new(Api::<ConfigMap>::default_namespaced(client), "my-controller")
+///     .step("initial", json!({
+///         "apiVersion": "v1",
+///         "kind": "ConfigMap",
+///         "metadata": { "name": "settings" },
+///         "data": { "replicas": "1" }
+///     }))
+///     .then("scaled", |previous: &ConfigMap| {
+///         let mut scaled = previous.clone();
+///         scaled.data.get_or_insert_with(Default::default).insert("replicas".into(), "3".into());
+///         scaled
+///     })
+///     .run()
+///     .await?;
+/// assert_eq!(outcomes[0].object.data.as_ref().unwrap()["replicas"], "1");
+/// assert_eq!(outcomes[1].object.data.as_ref().unwrap()["replicas"], "3");
+/// # Ok(())
+/// # }
+/// ```
+pub struct ApplyFixture<K> {
+    api: Api<K>,
+    params: PatchParams,
+    steps: Vec<(String, Payload<K>)>,
+}
+
+// Builds the payload of a step from the object of the previous step, if any
+type Payload<K> = Box<dyn FnOnce(Option<&K>) -> Result<serde_json::Value> + Send>;
+
+/// The result of a successful [`ApplyFixture`] step
+#[derive(Debug, Clone)]
+pub struct ApplyOutcome<K> {
+    /// The name of the step
+    pub step: String,
+    /// The object as it would have been persisted by the apiserver
+    pub object: K,
+}
+
+impl<K> ApplyFixture<K>
+where
+    K: Resource + Clone + DeserializeOwned + Debug + 'static,
+{
+    /// Create an empty fixture applying as `field_manager`
+    pub fn new(api: Api<K>, field_manager: &str) -> Self {
+        Self {
+            api,
+            params: PatchParams::apply(field_manager).dry_run(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Take ownership of fields that are owned by other field managers, rather than failing with a conflict
+    pub fn force(mut self) -> Self {
+        self.params = self.params.force();
+        self
+    }
+
+    /// Add a named payload to apply
+    ///
+    /// The payload must be a complete apply configuration, including `apiVersion`, `kind`, and `metadata.name`.
+    /// Payloads that fail to serialize fail the step when the fixture runs.
+    pub fn step(mut self, name: &str, payload: impl Serialize) -> Self {
+        let step = name.to_owned();
+        let payload = serde_json::to_value(payload).map_err(|source| Error::Serialize { step, source });
+        self.steps
+            .push((name.to_owned(), Box::new(move |_: Option<&K>| payload)));
+        self
+    }
+
+    /// Add a named payload to apply, built from the object returned by the previous step
+    ///
+    /// The object is passed as the apiserver returned it, including its `metadata.managedFields`,
+    /// which the apiserver ignores in apply configurations. The first step cannot be added with `then`.
+    pub fn then<P, F>(mut self, name: &str, payload: F) -> Self
+    where
+        P: Serialize,
+        F: FnOnce(&K) -> P + Send + 'static,
+    {
+        let step = name.to_owned();
+        self.steps.push((
+            name.to_owned(),
+            Box::new(move |previous: Option<&K>| match previous {
+                Some(previous) => serde_json::to_value(payload(previous))
+                    .map_err(|source| Error::Serialize { step, source }),
+                None => Err(Error::MissingPrevious(step)),
+            }),
+        ));
+        self
+    }
+
+    /// Dry-run all steps in order
+    ///
+    /// Fails on the first step that is rejected by the apiserver.
+    pub async fn run(self) -> Result<Vec<ApplyOutcome<K>>> {
+        let mut outcomes: Vec<ApplyOutcome<K>> = Vec::with_capacity(self.steps.len());
+        for (step, payload) in self.steps {
+            let payload = payload(outcomes.last().map(|outcome| &outcome.object))?;
+            let name = match payload.pointer("/metadata/name").and_then(serde_json::Value::as_str) {
+                Some(name) => name.to_owned(),
+                None => return Err(Error::MissingName(step)),
+            };
+            match self.api.patch(&name, &self.params, &Patch::Apply(&payload)).await {
+                Ok(object) => outcomes.push(ApplyOutcome { step, object }),
+                Err(source) => return Err(Error::ApplyFailed { step, source }),
+            }
+        }
+        Ok(outcomes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use futures::pin_mut;
+    use http::{Request, Response};
+    use hyper::Body;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use serde_json::{json, Value};
+    use tower_test::mock;
+
+    fn configmap(replicas: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "settings", "namespace": "default" },
+            "data": { "replicas": replicas }
+        })
+    }
+
+    #[tokio::test]
+    async fn steps_build_on_the_previous_step() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            for replicas in ["1", "2"] {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(
+                    request.uri().to_string(),
+                    "/api/v1/namespaces/default/configmaps/settings?&dryRun=All&fieldManager=fixture"
+                );
+                let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                let payload: Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(payload, configmap(replicas));
+                send.send_response(Response::builder().body(Body::from(body)).unwrap());
+            }
+        });
+
+        let api = Api::<ConfigMap>::default_namespaced(Client::new(mock_service, "default"));
+        let outcomes = ApplyFixture::new(api, "fixture")
+            .step("initial", configmap("1"))
+            .then("scaled", |previous: &ConfigMap| {
+                let replicas = previous.data.as_ref().unwrap()["replicas"]
+                    .parse::<u32>()
+                    .unwrap();
+                configmap(&(replicas + 1).to_string())
+            })
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(outcomes[1].step, "scaled");
+        assert_eq!(outcomes[1].object.data.as_ref().unwrap()["replicas"], "2");
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_steps_that_do_not_serialize() {
+        let (mock_service, _handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let api = Api::<ConfigMap>::default_namespaced(Client::new(mock_service, "default"));
+        let invalid = std::collections::HashMap::from([((1, 2), "tuple keys are not valid in JSON")]);
+        let result = ApplyFixture::new(api.clone(), "fixture")
+            .step("invalid", invalid)
+            .run()
+            .await;
+        assert!(matches!(result, Err(Error::Serialize { step, .. }) if step == "invalid"));
+
+        let result = ApplyFixture::new(api, "fixture")
+            .then("first", |_: &ConfigMap| configmap("1"))
+            .run()
+            .await;
+        assert!(matches!(result, Err(Error::MissingPrevious(step)) if step == "first"));
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use super::{Error, Result};
+use crate::{config::AuthInfo, Client, Config};
+
+// Environment variable pointing to the directory containing the binaries, compatible with controller-runtime's envtest
+const ASSETS_ENV: &str = "KUBEBUILDER_ASSETS";
+const ADMIN_TOKEN: &str = "envtest-admin";
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+static INSTANCES: AtomicUsize = AtomicUsize::new(0);
+
+/// A local `kube-apiserver` and `etcd` for integration tests
+///
+/// The binaries are looked up in the directory named by `KUBEBUILDER_ASSETS`, and in `PATH` otherwise.
+/// They can be installed with [setup-envtest](https://github.com/kubernetes-sigs/controller-runtime/tree/master/tools/setup-envtest).
+///
+/// There are no controllers or nodes, so only the API itself is available.
+/// For example, deployments are never rolled out and namespaces are never finalized.
+/// Both processes are stopped, and their data removed, when the `Envtest` is dropped.
+///
+/// ```no_run
+/// use kube_client::{testing::Envtest, Api};
+/// use k8s_openapi::api::core::v1::Namespace;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let envtest = Envtest::start().await?;
+/// let namespaces: Api<Namespace> = Api::all(envtest.client());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Envtest {
+    etcd: Child,
+    apiserver: Child,
+    dir: PathBuf,
+    config: Config,
+    client: Client,
+}
+
+impl Envtest {
+    /// Start `etcd` and `kube-apiserver`, and wait for the apiserver to become ready
+    pub async fn start() -> Result<Self> {
+        let assets = std::env::var_os(ASSETS_ENV).map(PathBuf::from);
+        let etcd_bin = find_binary(assets.as_deref(), "etcd")?;
+        let apiserver_bin = find_binary(assets.as_deref(), "kube-apiserver")?;
+
+        let dir = std::env::temp_dir().join(format!(
+            "kube-envtest-{}-{}",
+            std::process::id(),
+            INSTANCES.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::create_dir_all(dir.join("certs")).map_err(Error::Prepare)?;
+        let token_file = dir.join("token.csv");
+        std::fs::write(&token_file, format!("{},admin,admin,system:masters\n", ADMIN_TOKEN))
+            .map_err(Error::Prepare)?;
+        let sa_key_file = dir.join("sa.pem");
+        let sa_key = openssl::rsa::Rsa::generate(2048)
+            .and_then(|key| key.private_key_to_pem())
+            .map_err(Error::GenerateKey)?;
+        std::fs::write(&sa_key_file, sa_key).map_err(Error::Prepare)?;
+
+        let etcd_port = free_port()?;
+        let etcd_peer_port = free_port()?;
+        let apiserver_port = free_port()?;
+
+        // The apiserver generates a self-signed certificate for localhost in `cert-dir`
+        let mut config = Config::new(
+            format!("https://localhost:{}", apiserver_port)
+                .parse()
+                .expect("valid envtest url"),
+        );
+        config.accept_invalid_certs = true;
+        config.auth_info = AuthInfo {
+            token: Some(ADMIN_TOKEN.into()),
+            ..AuthInfo::default()
+        };
+        let client = Client::try_from(config.clone()).map_err(Error::CreateClient)?;
+
+        let etcd_url = format!("http://127.0.0.1:{}", etcd_port);
+        let etcd = Command::new(&etcd_bin)
+            .arg(format!("--data-dir={}", dir.join("etcd").display()))
+            .arg(format!("--listen-client-urls={}", etcd_url))
+            .arg(format!("--advertise-client-urls={}", etcd_url))
+            .arg(format!("--listen-peer-urls=http://127.0.0.1:{}", etcd_peer_port))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::StartProcess("etcd".into(), e))?;
+
+        let apiserver = Command::new(&apiserver_bin)
+            .arg(format!("--etcd-servers={}", etcd_url))
+            .arg(format!("--cert-dir={}", dir.join("certs").display()))
+            .arg(format!("--secure-port={}", apiserver_port))
+            .arg("--bind-address=127.0.0.1")
+            .arg("--advertise-address=127.0.0.1")
+            .arg("--service-cluster-ip-range=10.0.0.0/24")
+            .arg("--authorization-mode=RBAC")
+            .arg(format!("--token-auth-file={}", token_file.display()))
+            .arg("--service-account-issuer=https://kubernetes.default.svc")
+            .arg(format!("--service-account-key-file={}", sa_key_file.display()))
+            .arg(format!(
+                "--service-account-signing-key-file={}",
+                sa_key_file.display()
+            ))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let apiserver = match apiserver {
+            Ok(apiserver) => apiserver,
+            Err(e) => {
+                let mut etcd = etcd;
+                let _ = etcd.kill();
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(Error::StartProcess("kube-apiserver".into(), e));
+            }
+        };
+
+        let mut envtest = Self {
+            etcd,
+            apiserver,
+            dir,
+            config,
+            client,
+        };
+        envtest.wait_ready().await?;
+        Ok(envtest)
+    }
+
+    async fn wait_ready(&mut self) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            match self.client.apiserver_version().await {
+                Ok(_) => return Ok(()),
+                Err(e) if started.elapsed() > READY_TIMEOUT => return Err(Error::NotReady(e)),
+                Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        }
+    }
+
+    /// A `Client` authenticated as a cluster admin
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// The `Config` used by [`Envtest::client`], for creating customized clients
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+impl Drop for Envtest {
+    fn drop(&mut self) {
+        let _ = self.apiserver.kill();
+        let _ = self.apiserver.wait();
+        let _ = self.etcd.kill();
+        let _ = self.etcd.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn find_binary(assets: Option<&Path>, name: &str) -> Result<PathBuf> {
+    if let Some(path) = assets.map(|dir| dir.join(name)).filter(|path| path.is_file()) {
+        return Ok(path);
+    }
+    std::env::var_os("PATH")
+        .and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+        })
+        .ok_or_else(|| Error::MissingBinary(name.into()))
+}
+
+// Let the OS pick a free port. It could be taken again before it's used, but that's unlikely for tests.
+fn free_port() -> Result<u16> {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(Error::Prepare)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Envtest;
+    use crate::{testing::ApplyFixture, Api};
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use serde_json::json;
+
+    #[tokio::test]
+    #[ignore] // needs etcd and kube-apiserver, see KUBEBUILDER_ASSETS
+    async fn envtest_validates_apply_fixture() {
+        let envtest = Envtest::start().await.unwrap();
+        let api = Api::<ConfigMap>::namespaced(envtest.client(), "default");
+        let outcomes = ApplyFixture::new(api.clone(), "envtest")
+            .step(
+                "initial",
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "ConfigMap",
+                    "metadata": { "name": "fixture" },
+                    "data": { "key": "value" }
+                }),
+            )
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(outcomes[0].object.data.as_ref().unwrap()["key"], "value");
+        // Dry-runs are not persisted
+        assert!(api.get("fixture").await.is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Utilities for testing controllers against a real apiserver
+//!
+//! - [`ApplyFixture`] validates server-side apply payloads with dry-runs
+//! - [`Envtest`] boots a local `kube-apiserver` and `etcd` for integration tests
+use thiserror::Error;
+
+mod apply;
+mod envtest;
+
+pub use apply::{ApplyFixture, ApplyOutcome};
+pub use envtest::Envtest;
+
+/// Errors from the testing utilities
+#[derive(Error, Debug)]
+pub enum Error {
+    /// A step of an [`ApplyFixture`] was rejected by the apiserver
+    #[error("apply step '{step}' failed: {source}")]
+    ApplyFailed {
+        /// The name of the failed step
+        step: String,
+        /// The error returned by the apiserver
+        #[source]
+        source: crate::Error,
+    },
+
+    /// An object in an [`ApplyFixture`] has no name
+    #[error("apply step '{0}' has no metadata.name")]
+    MissingName(String),
+
+    /// The payload of a step of an [`ApplyFixture`] failed to serialize
+    #[error("apply step '{step}' failed to serialize: {source}")]
+    Serialize {
+        /// The name of the failed step
+        step: String,
+        /// The serialization error
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The first step of an [`ApplyFixture`] was added with [`ApplyFixture::then`]
+    #[error("apply step '{0}' has no previous step to build on")]
+    MissingPrevious(String),
+
+    /// Failed to find a binary required by [`Envtest`]
+    #[error("failed to find {0}, set KUBEBUILDER_ASSETS to the directory containing it")]
+    MissingBinary(String),
+
+    /// Failed to prepare the files or ports used by [`Envtest`]
+    #[error("failed to prepare envtest: {0}")]
+    Prepare(#[source] std::io::Error),
+
+    /// Failed to generate the service account signing key used by [`Envtest`]
+    #[error("failed to generate service account signing key: {0}")]
+    GenerateKey(#[source] openssl::error::ErrorStack),
+
+    /// Failed to start a process of [`Envtest`]
+    #[error("failed to start {0}: {1}")]
+    StartProcess(String, #[source] std::io::Error),
+
+    /// Failed to create a client for [`Envtest`]
+    #[error("failed to create envtest client: {0}")]
+    CreateClient(#[source] crate::Error),
+
+    /// The apiserver of [`Envtest`] did not become ready in time
+    #[error("envtest apiserver did not become ready: {0}")]
+    NotReady(#[source] crate::Error),
+}
+
+/// Convenient alias for `Result<T, Error>`
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,391 @@
+//! Fluent builders for common specs, such as pods for tests and operators
+//!
+//! ```
+//! use kube::core::builder::{ContainerBuilder, PodBuilder, VolumeBuilder};
+//! let pod = PodBuilder::new("web")
+//!     .namespace("apps")
+//!     .label("app", "web")
+//!     .image("nginx:1.21")
+//!     .port(80)
+//!     .volume_mount("config", "/etc/nginx/conf.d")
+//!     .volume(VolumeBuilder::new("config").config_map("nginx").build())
+//!     .container(
+//!         ContainerBuilder::new("sidecar")
+//!             .image("busybox")
+//!             .command(["sh", "-c", "sleep infinity"])
+//!             .build(),
+//!     )
+//!     .build();
+//! assert_eq!(pod.spec.unwrap().containers.len(), 2);
+//! ```
+use k8s_openapi::{
+    api::core::v1::{
+        ConfigMapVolumeSource, Container, ContainerPort, EmptyDirVolumeSource, EnvVar, HostPathVolumeSource,
+        PersistentVolumeClaimVolumeSource, Pod, PodSpec, SecretVolumeSource, Volume, VolumeMount,
+    },
+    apimachinery::pkg::apis::meta::v1::ObjectMeta,
+};
+use std::collections::BTreeMap;
+
+/// Builder for a [`Pod`]
+///
+/// The container methods (`image`, `command`, `env`, ...) configure the main container of the pod,
+/// which is named after the pod, like `kubectl run`. More containers can be added with [`PodBuilder::container`].
+#[derive(Clone, Debug)]
+pub struct PodBuilder {
+    metadata: ObjectMeta,
+    spec: PodSpec,
+    main: ContainerBuilder,
+    main_configured: bool,
+}
+
+impl PodBuilder {
+    /// Start building a pod called `name`
+    pub fn new(name: &str) -> Self {
+        Self {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: PodSpec::default(),
+            main: ContainerBuilder::new(name),
+            main_configured: false,
+        }
+    }
+
+    /// Set the namespace of the pod
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.metadata.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Add a label to the pod
+    pub fn label(mut self, key: &str, value: &str) -> Self {
+        insert(&mut self.metadata.labels, key, value);
+        self
+    }
+
+    /// Add an annotation to the pod
+    pub fn annotation(mut self, key: &str, value: &str) -> Self {
+        insert(&mut self.metadata.annotations, key, value);
+        self
+    }
+
+    fn main(mut self, f: impl FnOnce(ContainerBuilder) -> ContainerBuilder) -> Self {
+        self.main = f(self.main);
+        self.main_configured = true;
+        self
+    }
+
+    /// Set the image of the main container
+    pub fn image(self, image: &str) -> Self {
+        self.main(|c| c.image(image))
+    }
+
+    /// Set the command of the main container
+    pub fn command<I, S>(self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.main(|c| c.command(command))
+    }
+
+    /// Set the arguments of the main container
+    pub fn args<I, S>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.main(|c| c.args(args))
+    }
+
+    /// Add an environment variable to the main container
+    pub fn env(self, name: &str, value: &str) -> Self {
+        self.main(|c| c.env(name, value))
+    }
+
+    /// Expose a port of the main container
+    pub fn port(self, port: i32) -> Self {
+        self.main(|c| c.port(port))
+    }
+
+    /// Mount the volume `name` at `path` in the main container
+    pub fn volume_mount(self, name: &str, path: &str) -> Self {
+        self.main(|c| c.volume_mount(name, path))
+    }
+
+    /// Add a container to the pod
+    pub fn container(mut self, container: Container) -> Self {
+        self.spec.containers.push(container);
+        self
+    }
+
+    /// Add an init container to the pod
+    pub fn init_container(mut self, container: Container) -> Self {
+        self.spec
+            .init_containers
+            .get_or_insert_with(Vec::new)
+            .push(container);
+        self
+    }
+
+    /// Add a volume to the pod
+    pub fn volume(mut self, volume: Volume) -> Self {
+        self.spec.volumes.get_or_insert_with(Vec::new).push(volume);
+        self
+    }
+
+    /// Set the restart policy of the pod: `Always`, `OnFailure` or `Never`
+    pub fn restart_policy(mut self, policy: &str) -> Self {
+        self.spec.restart_policy = Some(policy.to_string());
+        self
+    }
+
+    /// Run the pod as the service account `name`
+    pub fn service_account(mut self, name: &str) -> Self {
+        self.spec.service_account_name = Some(name.to_string());
+        self
+    }
+
+    /// Only schedule the pod on nodes with the label `key=value`
+    pub fn node_selector(mut self, key: &str, value: &str) -> Self {
+        insert(&mut self.spec.node_selector, key, value);
+        self
+    }
+
+    /// Build the pod
+    ///
+    /// The main container comes first, if any of its methods were used.
+    pub fn build(self) -> Pod {
+        let mut spec = self.spec;
+        if self.main_configured {
+            spec.containers.insert(0, self.main.build());
+        }
+        Pod {
+            metadata: self.metadata,
+            spec: Some(spec),
+            status: None,
+        }
+    }
+}
+
+/// Builder for a [`Container`]
+#[derive(Clone, Debug)]
+pub struct ContainerBuilder {
+    container: Container,
+}
+
+impl ContainerBuilder {
+    /// Start building a container called `name`
+    pub fn new(name: &str) -> Self {
+        Self {
+            container: Container {
+                name: name.to_string(),
+                ..Container::default()
+            },
+        }
+    }
+
+    /// Set the image
+    pub fn image(mut self, image: &str) -> Self {
+        self.container.image = Some(image.to_string());
+        self
+    }
+
+    /// Set the command, overriding the entrypoint of the image
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.container.command = Some(command.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the arguments to the command
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.container.args = Some(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Add an environment variable
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.container.env.get_or_insert_with(Vec::new).push(EnvVar {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+            value_from: None,
+        });
+        self
+    }
+
+    /// Expose a TCP port
+    pub fn port(mut self, port: i32) -> Self {
+        self.container
+            .ports
+            .get_or_insert_with(Vec::new)
+            .push(ContainerPort {
+                container_port: port,
+                ..ContainerPort::default()
+            });
+        self
+    }
+
+    /// Mount the volume `name` at `path`
+    pub fn volume_mount(mut self, name: &str, path: &str) -> Self {
+        self.container
+            .volume_mounts
+            .get_or_insert_with(Vec::new)
+            .push(VolumeMount {
+                name: name.to_string(),
+                mount_path: path.to_string(),
+                ..VolumeMount::default()
+            });
+        self
+    }
+
+    /// Set the working directory
+    pub fn working_dir(mut self, dir: &str) -> Self {
+        self.container.working_dir = Some(dir.to_string());
+        self
+    }
+
+    /// Build the container
+    pub fn build(self) -> Container {
+        self.container
+    }
+}
+
+/// Builder for a [`Volume`]
+///
+/// Set exactly one source of the volume, such as [`VolumeBuilder::config_map`].
+#[derive(Clone, Debug)]
+pub struct VolumeBuilder {
+    volume: Volume,
+}
+
+impl VolumeBuilder {
+    /// Start building a volume called `name`
+    pub fn new(name: &str) -> Self {
+        Self {
+            volume: Volume {
+                name: name.to_string(),
+                ..Volume::default()
+            },
+        }
+    }
+
+    /// Use the config map `name` as the source
+    pub fn config_map(mut self, name: &str) -> Self {
+        self.volume.config_map = Some(ConfigMapVolumeSource {
+            name: Some(name.to_string()),
+            ..ConfigMapVolumeSource::default()
+        });
+        self
+    }
+
+    /// Use the secret `name` as the source
+    pub fn secret(mut self, name: &str) -> Self {
+        self.volume.secret = Some(SecretVolumeSource {
+            secret_name: Some(name.to_string()),
+            ..SecretVolumeSource::default()
+        });
+        self
+    }
+
+    /// Use an empty directory that lives as long as the pod
+    pub fn empty_dir(mut self) -> Self {
+        self.volume.empty_dir = Some(EmptyDirVolumeSource::default());
+        self
+    }
+
+    /// Use the directory `path` of the node
+    pub fn host_path(mut self, path: &str) -> Self {
+        self.volume.host_path = Some(HostPathVolumeSource {
+            path: path.to_string(),
+            type_: None,
+        });
+        self
+    }
+
+    /// Use the persistent volume claim `claim`
+    pub fn persistent_volume_claim(mut self, claim: &str) -> Self {
+        self.volume.persistent_volume_claim = Some(PersistentVolumeClaimVolumeSource {
+            claim_name: claim.to_string(),
+            read_only: None,
+        });
+        self
+    }
+
+    /// Build the volume
+    pub fn build(self) -> Volume {
+        self.volume
+    }
+}
+
+fn insert(map: &mut Option<BTreeMap<String, String>>, key: &str, value: &str) {
+    map.get_or_insert_with(BTreeMap::new)
+        .insert(key.to_string(), value.to_string());
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ContainerBuilder, PodBuilder, VolumeBuilder};
+    use serde_json::json;
+
+    #[test]
+    fn builds_pod() {
+        let pod = PodBuilder::new("web")
+            .namespace("apps")
+            .label("app", "web")
+            .image("nginx:1.21")
+            .env("MODE", "prod")
+            .port(80)
+            .volume_mount("data", "/data")
+            .volume(VolumeBuilder::new("data").empty_dir().build())
+            .init_container(
+                ContainerBuilder::new("init")
+                    .image("busybox")
+                    .command(["sh", "-c"])
+                    .args(vec!["echo hi".to_string()])
+                    .build(),
+            )
+            .restart_policy("Never")
+            .build();
+        assert_eq!(
+            serde_json::to_value(&pod).unwrap(),
+            json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": { "name": "web", "namespace": "apps", "labels": { "app": "web" } },
+                "spec": {
+                    "containers": [{
+                        "name": "web",
+                        "image": "nginx:1.21",
+                        "env": [{ "name": "MODE", "value": "prod" }],
+                        "ports": [{ "containerPort": 80 }],
+                        "volumeMounts": [{ "name": "data", "mountPath": "/data" }]
+                    }],
+                    "initContainers": [{
+                        "name": "init",
+                        "image": "busybox",
+                        "command": ["sh", "-c"],
+                        "args": ["echo hi"]
+                    }],
+                    "restartPolicy": "Never",
+                    "volumes": [{ "name": "data", "emptyDir": {} }]
+                }
+            })
+        );
+
+        // Without the main container
+        let pod = PodBuilder::new("sidecars")
+            .container(ContainerBuilder::new("a").image("a").build())
+            .build();
+        assert_eq!(pod.spec.unwrap().containers[0].name, "a");
+    }
+}
@@ -0,0 +1,208 @@
+//! Status conditions of custom resources, following the Kubernetes conventions
+//!
+//! See the [API conventions](https://github.com/kubernetes/community/blob/master/contributors/devel/sig-architecture/api-conventions.md#typical-status-properties)
+//! for how conditions are meant to be used.
+use chrono::{SubsecRound, Utc};
+pub use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use serde::{Deserialize, Serialize};
+
+/// The type of the condition that summarizes the other conditions, see [`StatusConditions::summarize_ready`]
+pub const READY: &str = "Ready";
+
+/// The `conditions` of the status of a custom resource, with at most one condition of each type
+///
+/// Serializes as the list of conditions, so it can be used as the `conditions` field of a status struct.
+/// The `lastTransitionTime` of a condition only changes when its status does, and every condition records
+/// the `metadata.generation` of the object it was observed at.
+///
+/// ```
+/// use kube_core::conditions::StatusConditions;
+/// let mut conditions = StatusConditions::default();
+/// conditions.set("Available", true, "MinimumReplicasAvailable", "", Some(2));
+/// conditions.set("Progressing", false, "Pending", "waiting for rollout", Some(2));
+/// assert!(!conditions.summarize_ready(&["Available", "Progressing"], Some(2)));
+/// assert_eq!(conditions.get("Ready").unwrap().reason, "Pending");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StatusConditions(Vec<Condition>);
+
+impl StatusConditions {
+    /// The condition of type `type_`, if any
+    pub fn get(&self, type_: &str) -> Option<&Condition> {
+        self.0.iter().find(|c| c.type_ == type_)
+    }
+
+    /// Whether the condition of type `type_` is `True`
+    pub fn is_true(&self, type_: &str) -> bool {
+        self.get(type_).map_or(false, |c| c.status == "True")
+    }
+
+    /// Whether the condition of type `type_` is `True` and was observed at `generation`
+    ///
+    /// Conditions observed at an earlier generation describe a spec that has since changed.
+    pub fn is_true_at(&self, type_: &str, generation: Option<i64>) -> bool {
+        self.get(type_).map_or(false, |c| {
+            c.status == "True" && c.observed_generation == generation
+        })
+    }
+
+    /// Sets the condition of type `type_`, returning whether it changed
+    ///
+    /// The `lastTransitionTime` is set to now when the condition is added or its status changes.
+    /// `observed_generation` should be the `metadata.generation` of the reconciled object.
+    pub fn set(
+        &mut self,
+        type_: &str,
+        status: bool,
+        reason: &str,
+        message: &str,
+        observed_generation: Option<i64>,
+    ) -> bool {
+        let status = if status { "True" } else { "False" };
+        self.set_condition(Condition {
+            type_: type_.into(),
+            status: status.into(),
+            reason: reason.into(),
+            message: message.into(),
+            observed_generation,
+            // `Time` is serialized with second precision, truncate it to compare equal after a round-trip
+            last_transition_time: Time(Utc::now().trunc_subsecs(0)),
+        })
+    }
+
+    /// Sets `condition`, replacing the condition of the same type, and returns whether it changed
+    ///
+    /// Keeps the `lastTransitionTime` of the replaced condition unless the status changes,
+    /// like `meta.SetStatusCondition` of apimachinery.
+    pub fn set_condition(&mut self, mut condition: Condition) -> bool {
+        match self.0.iter_mut().find(|c| c.type_ == condition.type_) {
+            Some(existing) => {
+                if existing.status == condition.status {
+                    condition.last_transition_time = existing.last_transition_time.clone();
+                }
+                let changed = *existing != condition;
+                *existing = condition;
+                changed
+            }
+            None => {
+                self.0.push(condition);
+                true
+            }
+        }
+    }
+
+    /// Removes the condition of type `type_`, returning it
+    pub fn remove(&mut self, type_: &str) -> Option<Condition> {
+        let index = self.0.iter().position(|c| c.type_ == type_)?;
+        Some(self.0.remove(index))
+    }
+
+    /// Sets the [`READY`] condition from the conditions of types `dependencies`, returning its status
+    ///
+    /// The object is ready when all of them are `True` and were observed at `generation`.
+    /// Otherwise the reason and message of the `Ready` condition are taken from the first one that is not.
+    pub fn summarize_ready(&mut self, dependencies: &[&str], generation: Option<i64>) -> bool {
+        let blocking = dependencies
+            .iter()
+            .find(|type_| !self.is_true_at(type_, generation))
+            .map(|type_| match self.get(type_) {
+                Some(c) if c.observed_generation != generation => (
+                    "Reconciling".to_string(),
+                    format!("{} has not been observed at the current generation", type_),
+                ),
+                Some(c) => (c.reason.clone(), c.message.clone()),
+                None => ("Unknown".to_string(), format!("{} has not been reported", type_)),
+            });
+        let ready = blocking.is_none();
+        let (reason, message) = blocking.unwrap_or_else(|| ("Ready".to_string(), String::new()));
+        self.set(READY, ready, &reason, &message, generation);
+        ready
+    }
+
+    /// Iterates over the conditions
+    pub fn iter(&self) -> impl Iterator<Item = &Condition> {
+        self.0.iter()
+    }
+
+    /// Whether there are no conditions
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The conditions, as a list
+    pub fn into_inner(self) -> Vec<Condition> {
+        self.0
+    }
+}
+
+/// The schema of the `conditions` list, for using [`StatusConditions`] in derived custom resources
+///
+/// Conditions are a list map keyed by `type`, so server-side apply merges them per condition.
+#[cfg(feature = "schema")]
+#[cfg_attr(docsrs, doc(cfg(feature = "schema")))]
+impl schemars::JsonSchema for StatusConditions {
+    fn schema_name() -> String {
+        "StatusConditions".into()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        serde_json::from_value(serde_json::json!({
+            "type": "array",
+            "x-kubernetes-list-type": "map",
+            "x-kubernetes-list-map-keys": ["type"],
+            "items": {
+                "type": "object",
+                "required": ["lastTransitionTime", "message", "reason", "status", "type"],
+                "properties": {
+                    "lastTransitionTime": { "type": "string", "format": "date-time" },
+                    "message": { "type": "string" },
+                    "observedGeneration": { "type": "integer", "format": "int64" },
+                    "reason": { "type": "string" },
+                    "status": { "type": "string", "enum": ["True", "False", "Unknown"] },
+                    "type": { "type": "string" },
+                },
+            },
+        }))
+        .expect("valid schema")
+    }
+}
+
+impl From<Vec<Condition>> for StatusConditions {
+    fn from(conditions: Vec<Condition>) -> Self {
+        Self(conditions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StatusConditions, READY};
+
+    #[test]
+    fn keeps_transition_times_and_summarizes_ready() {
+        let mut conditions = StatusConditions::default();
+        assert!(conditions.set("Available", false, "Scaling", "", Some(1)));
+        let since = conditions.get("Available").unwrap().last_transition_time.clone();
+        assert!(conditions.set("Available", false, "Scaling", "1 of 3 replicas", Some(1)));
+        assert!(!conditions.set("Available", false, "Scaling", "1 of 3 replicas", Some(1)));
+        assert_eq!(conditions.get("Available").unwrap().last_transition_time, since);
+
+        assert!(!conditions.summarize_ready(&["Available", "Synced"], Some(1)));
+        assert_eq!(conditions.get(READY).unwrap().reason, "Scaling");
+
+        conditions.set("Available", true, "Scaled", "", Some(1));
+        conditions.set("Synced", true, "Synced", "", Some(1));
+        assert!(conditions.summarize_ready(&["Available", "Synced"], Some(1)));
+        assert!(conditions.is_true(READY));
+
+        // A new generation is not ready until it has been observed
+        assert!(!conditions.summarize_ready(&["Available", "Synced"], Some(2)));
+        assert_eq!(conditions.get(READY).unwrap().reason, "Reconciling");
+
+        let json = serde_json::to_value(&conditions).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+        let parsed: StatusConditions = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, conditions);
+    }
+}
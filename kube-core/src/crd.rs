@@ -27,6 +27,33 @@ pub mod v1 {
         ///
         /// [`Pod`]: `k8s_openapi::api::core::v1::Pod`
         fn shortnames() -> &'static [&'static str];
+
+        /// Fields that objects can be selected by with field selectors, besides `metadata.name` and `metadata.namespace`
+        ///
+        /// Declared with `#[kube(selectable = "spec.field")]`, and requires kubernetes >= 1.30.
+        fn selectable_fields() -> &'static [&'static str] {
+            &[]
+        }
+
+        /// Helper to generate the CRD as JSON, including fields that are missing from [`CustomResourceExt::crd`]
+        ///
+        /// The `CustomResourceDefinition` of `k8s_openapi` does not have the `selectableFields` of versions yet,
+        /// so install this instead of `crd()` when the resource declares [`CustomResourceExt::selectable_fields`].
+        fn crd_json() -> serde_json::Value {
+            let mut crd = serde_json::to_value(Self::crd()).expect("valid custom resource definition");
+            let selectable = Self::selectable_fields()
+                .iter()
+                .map(|field| serde_json::json!({ "jsonPath": format!(".{}", field) }))
+                .collect::<Vec<_>>();
+            if !selectable.is_empty() {
+                if let Some(versions) = crd["spec"]["versions"].as_array_mut() {
+                    for version in versions {
+                        version["selectableFields"] = selectable.clone().into();
+                    }
+                }
+            }
+            crd
+        }
     }
 }
 
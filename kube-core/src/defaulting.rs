@@ -0,0 +1,139 @@
+//! Client-side defaulting of custom resources from the schemas of their CustomResourceDefinitions
+//!
+//! The apiserver fills in the `default`s of the structural schema of a CustomResourceDefinition when it
+//! stores an object. Applying the same defaults to a desired object before comparing it with the stored
+//! one keeps reconcilers from seeing differences in fields they never set, and from patching forever.
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+    CustomResourceDefinition, JSONSchemaProps, JSONSchemaPropsOrArray, JSONSchemaPropsOrBool,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// The `openAPIV3Schema` of the version `version` of `crd`
+pub fn crd_schema<'a>(crd: &'a CustomResourceDefinition, version: &str) -> Option<&'a JSONSchemaProps> {
+    crd.spec
+        .versions
+        .iter()
+        .find(|v| v.name == version)?
+        .schema
+        .as_ref()?
+        .open_api_v3_schema
+        .as_ref()
+}
+
+/// Fills in the defaults of `schema` that are missing from `value`, like the apiserver does
+///
+/// A default is applied to properties that are missing, or `null` without being `nullable`, and then
+/// defaulted itself. Properties, `additionalProperties` and array `items` are defaulted recursively.
+pub fn apply_defaults(schema: &JSONSchemaProps, value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, property) in schema.properties.iter().flatten() {
+                let default = match &property.default {
+                    Some(default) => &default.0,
+                    None => continue,
+                };
+                let missing = match map.get(key) {
+                    None => true,
+                    Some(Value::Null) => !property.nullable.unwrap_or(false),
+                    Some(_) => false,
+                };
+                if missing {
+                    map.insert(key.clone(), default.clone());
+                }
+            }
+            let additional = match &schema.additional_properties {
+                Some(JSONSchemaPropsOrBool::Schema(schema)) => Some(&**schema),
+                _ => None,
+            };
+            for (key, value) in map.iter_mut() {
+                let property = schema.properties.as_ref().and_then(|p| p.get(key));
+                if let Some(property) = property.or(additional) {
+                    apply_defaults(property, value);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(JSONSchemaPropsOrArray::Schema(schema)) = &schema.items {
+                for item in items {
+                    apply_defaults(schema, item);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns `obj` with the defaults of `schema` filled in, see [`apply_defaults`]
+///
+/// Fails if `obj` does not serialize to JSON, or the defaulted object does not deserialize into `K`.
+pub fn default_object<K: Serialize + DeserializeOwned>(
+    schema: &JSONSchemaProps,
+    obj: &K,
+) -> Result<K, serde_json::Error> {
+    let mut value = serde_json::to_value(obj)?;
+    apply_defaults(schema, &mut value);
+    serde_json::from_value(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply_defaults;
+    use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::JSONSchemaProps;
+    use serde_json::json;
+
+    #[test]
+    fn defaults_like_the_apiserver() {
+        let schema: JSONSchemaProps = serde_json::from_value(json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "default": {},
+                    "properties": {
+                        "replicas": { "type": "integer", "default": 1 },
+                        "image": { "type": "string", "nullable": true, "default": "nginx" },
+                        "ports": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": { "protocol": { "type": "string", "default": "TCP" } },
+                            },
+                        },
+                        "limits": {
+                            "type": "object",
+                            "additionalProperties": {
+                                "type": "object",
+                                "properties": { "unit": { "type": "string", "default": "Mi" } },
+                            },
+                        },
+                    },
+                },
+            },
+        }))
+        .unwrap();
+
+        let mut empty = json!({ "metadata": { "name": "blog" } });
+        apply_defaults(&schema, &mut empty);
+        assert_eq!(empty["spec"], json!({ "replicas": 1, "image": "nginx" }));
+
+        let mut obj = json!({
+            "spec": {
+                "replicas": null,
+                "image": null,
+                "ports": [{ "port": 80 }, { "port": 53, "protocol": "UDP" }],
+                "limits": { "memory": { "value": 64 } },
+            },
+        });
+        apply_defaults(&schema, &mut obj);
+        assert_eq!(
+            obj["spec"],
+            json!({
+                "replicas": 1,
+                "image": null,
+                "ports": [{ "port": 80, "protocol": "TCP" }, { "port": 53, "protocol": "UDP" }],
+                "limits": { "memory": { "value": 64, "unit": "Mi" } },
+            })
+        );
+    }
+}
@@ -1,5 +1,5 @@
 //! Type information structs for API discovery
-use crate::{gvk::GroupVersionKind, resource::Resource};
+use crate::{gvk::GroupVersionKind, request::Error, resource::Resource};
 use serde::{Deserialize, Serialize};
 
 /// Information about a Kubernetes API resource
@@ -95,10 +95,20 @@ pub struct ApiCapabilities {
     ///
     /// Please note that returned ApiResources are not standalone resources.
     /// Their name will be of form `subresource_name`, not `resource_name/subresource_name`.
-    /// To work with subresources, use `Request` methods for now.
+    /// To work with subresources, look them up with [`ApiCapabilities::subresource`].
     pub subresources: Vec<(ApiResource, ApiCapabilities)>,
     /// Supported operations on this resource
     pub operations: Vec<String>,
+    /// Short names of the resource, e.g. `deploy` for deployments
+    pub short_names: Vec<String>,
+    /// Categories the resource belongs to, e.g. `all`
+    pub categories: Vec<String>,
+    /// Fields that objects can be selected by with field selectors, besides [`METADATA_SELECTABLE_FIELDS`]
+    ///
+    /// `None` when they are not known, in which case field selectors are not validated.
+    /// Discovery fills them in for built-in resources, see [`builtin_selectable_fields`].
+    /// For custom resources, these are the `selectableFields` of the CRD version.
+    pub selectable_fields: Option<Vec<String>>,
 }
 
 impl ApiCapabilities {
@@ -106,6 +116,154 @@ impl ApiCapabilities {
     pub fn supports_operation(&self, operation: &str) -> bool {
         self.operations.iter().any(|op| op == operation)
     }
+
+    /// The subresource `name`, like `status`, `scale` or `approval`, if the resource has it
+    pub fn subresource(&self, name: &str) -> Option<Subresource> {
+        self.subresources
+            .iter()
+            .find(|(ar, _)| ar.plural == name)
+            .map(|(ar, caps)| Subresource {
+                name: ar.plural.clone(),
+                kind: ar.kind.clone(),
+                verbs: caps.operations.clone(),
+            })
+    }
+
+    /// Checks that a field selector only uses fields this resource can be selected by
+    ///
+    /// The API server rejects unsupported fields for most resources, but not all.
+    /// Always succeeds if the selectable fields are not known.
+    pub fn validate_field_selector(&self, selector: &str) -> Result<(), Error> {
+        match &self.selectable_fields {
+            Some(selectable) => {
+                validate_field_selector(selector, &selectable.iter().map(String::as_str).collect::<Vec<_>>())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// A subresource of an API resource, with the verbs it supports
+///
+/// Found with [`ApiCapabilities::subresource`], which includes the custom subresources of aggregated APIs.
+/// Subresources that are not discovered can be described with [`Subresource::new`].
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Subresource {
+    /// Name of the subresource, the last segment of its url path
+    pub name: String,
+    /// Kind of the objects it accepts and returns, e.g. `Scale` for `scale`
+    pub kind: String,
+    /// Supported verbs, see [`verbs`]
+    pub verbs: Vec<String>,
+}
+
+impl Subresource {
+    /// Describe the subresource `name` of objects of `kind`, supporting `verbs`
+    pub fn new(name: &str, kind: &str, verbs: &[&str]) -> Self {
+        Self {
+            name: name.into(),
+            kind: kind.into(),
+            verbs: verbs.iter().map(|verb| verb.to_string()).collect(),
+        }
+    }
+
+    /// Checks that given verb is supported on this subresource.
+    pub fn supports_verb(&self, verb: &str) -> bool {
+        self.verbs.iter().any(|v| v == verb)
+    }
+}
+
+// Checks that `selector` only uses `selectable` fields, or `METADATA_SELECTABLE_FIELDS`
+pub(crate) fn validate_field_selector(selector: &str, selectable: &[&str]) -> Result<(), Error> {
+    for field in field_selector_keys(selector)? {
+        if !METADATA_SELECTABLE_FIELDS.contains(&field) && !selectable.contains(&field) {
+            return Err(Error::Validation(format!(
+                "field selector uses unsupported field {}, supported fields are: {}",
+                field,
+                METADATA_SELECTABLE_FIELDS
+                    .iter()
+                    .chain(selectable)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Fields that objects of all resources can be selected by
+pub const METADATA_SELECTABLE_FIELDS: [&str; 2] = ["metadata.name", "metadata.namespace"];
+
+/// Fields that objects of a built-in resource can be selected by, besides [`METADATA_SELECTABLE_FIELDS`]
+///
+/// Returns `None` for resources that are not known to this table, such as custom resources.
+pub fn builtin_selectable_fields(group: &str, plural: &str) -> Option<&'static [&'static str]> {
+    let fields: &'static [&'static str] = match (group, plural) {
+        ("", "pods") => &[
+            "spec.nodeName",
+            "spec.restartPolicy",
+            "spec.schedulerName",
+            "spec.serviceAccountName",
+            "spec.hostNetwork",
+            "status.phase",
+            "status.podIP",
+            "status.podIPs",
+            "status.nominatedNodeName",
+        ],
+        ("", "events") => &[
+            "involvedObject.kind",
+            "involvedObject.namespace",
+            "involvedObject.name",
+            "involvedObject.uid",
+            "involvedObject.apiVersion",
+            "involvedObject.resourceVersion",
+            "involvedObject.fieldPath",
+            "reason",
+            "reportingComponent",
+            "source",
+            "type",
+        ],
+        ("", "secrets") => &["type"],
+        ("", "namespaces") => &["status.phase"],
+        ("", "nodes") => &["spec.unschedulable"],
+        ("", "replicationcontrollers") => &["status.replicas"],
+        ("", "configmaps") | ("", "serviceaccounts") => &[],
+        ("batch", "jobs") => &["status.successful"],
+        ("certificates.k8s.io", "certificatesigningrequests") => &["spec.signerName"],
+        _ => return None,
+    };
+    Some(fields)
+}
+
+// The fields used by a field selector such as `status.phase=Running,spec.nodeName!=node-1`
+fn field_selector_keys(selector: &str) -> Result<Vec<&str>, Error> {
+    let mut keys = vec![];
+    let mut rest = selector;
+    while !rest.is_empty() {
+        // Commas in values are escaped with a backslash
+        let mut end = rest.len();
+        let mut escaped = false;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                ',' if !escaped => {
+                    end = i;
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+        let term = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or("");
+        let key = term
+            .find(|c: char| c == '=' || c == '!')
+            .map(|op| term[..op].trim())
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| Error::Validation(format!("invalid field selector requirement: {}", term)))?;
+        keys.push(key);
+    }
+    Ok(keys)
 }
 
 // Simple pluralizer. Handles the special cases.
@@ -145,6 +303,65 @@ fn to_plural(word: &str) -> String {
     format!("{}s", word)
 }
 
+#[test]
+fn test_validate_field_selector() {
+    let caps = ApiCapabilities {
+        scope: Scope::Namespaced,
+        subresources: vec![],
+        operations: vec![],
+        short_names: vec![],
+        categories: vec![],
+        selectable_fields: builtin_selectable_fields("", "pods")
+            .map(|fields| fields.iter().map(|f| f.to_string()).collect()),
+    };
+    assert!(caps.validate_field_selector("").is_ok());
+    assert!(caps
+        .validate_field_selector("status.phase=Running,spec.nodeName!=node-1,metadata.name==web")
+        .is_ok());
+    assert!(caps.validate_field_selector("spec.containers=web").is_err());
+    assert!(caps.validate_field_selector("status.phase").is_err());
+    // Escaped commas are part of the value
+    assert!(caps.validate_field_selector("metadata.name=a\\,spec.x=b").is_ok());
+
+    let unknown = ApiCapabilities {
+        selectable_fields: None,
+        ..caps
+    };
+    assert!(unknown.validate_field_selector("spec.anything=1").is_ok());
+}
+
+#[test]
+fn test_subresource_lookup() {
+    let approval = ApiResource {
+        group: "certificates.k8s.io".into(),
+        version: "v1".into(),
+        api_version: "certificates.k8s.io/v1".into(),
+        kind: "CertificateSigningRequest".into(),
+        plural: "approval".into(),
+    };
+    let approval_caps = ApiCapabilities {
+        scope: Scope::Cluster,
+        subresources: vec![],
+        operations: vec![verbs::GET.into(), verbs::UPDATE.into(), verbs::PATCH.into()],
+        short_names: vec![],
+        categories: vec![],
+        selectable_fields: None,
+    };
+    let caps = ApiCapabilities {
+        subresources: vec![(approval, approval_caps.clone())],
+        operations: vec![verbs::GET.into(), verbs::LIST.into()],
+        ..approval_caps
+    };
+    let sub = caps.subresource("approval").unwrap();
+    assert_eq!(
+        sub,
+        Subresource::new("approval", "CertificateSigningRequest", &["get", "update", "patch"])
+    );
+    assert!(sub.supports_verb(verbs::UPDATE));
+    assert!(!sub.supports_verb(verbs::CREATE));
+    assert!(caps.subresource("status").is_none());
+}
+
 #[test]
 fn test_to_plural_native() {
     // Extracted from `swagger.json`
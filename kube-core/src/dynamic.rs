@@ -3,7 +3,10 @@
 //! For concrete usage see [examples prefixed with dynamic_](https://github.com/kube-rs/kube-rs/tree/master/examples).
 
 pub use crate::discovery::ApiResource;
-use crate::{metadata::TypeMeta, resource::Resource};
+use crate::{
+    metadata::{ListMeta, TypeMeta},
+    resource::Resource,
+};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use std::borrow::Cow;
 
@@ -84,6 +87,86 @@ impl Resource for DynamicObject {
     }
 }
 
+/// A list of objects of any kind, like the `v1` `List` documents emitted by kubectl
+///
+/// Objects keep their own `apiVersion` and `kind`, so a list can mix kinds and round-trips unchanged.
+/// Items of typed lists (such as `PodList`), which don't carry type information, get their types from the list.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(from = "RawDynamicList")]
+pub struct DynamicList {
+    /// The type fields of the list, `v1` `List` unless it is a typed list
+    #[serde(flatten)]
+    pub types: TypeMeta,
+    /// List metadata
+    pub metadata: ListMeta,
+    /// The objects in the list
+    pub items: Vec<DynamicObject>,
+}
+
+impl DynamicList {
+    /// Create a `v1` `List` of objects
+    pub fn new(items: Vec<DynamicObject>) -> Self {
+        Self {
+            types: TypeMeta {
+                api_version: "v1".into(),
+                kind: "List".into(),
+            },
+            metadata: ListMeta::default(),
+            items,
+        }
+    }
+}
+
+impl From<Vec<DynamicObject>> for DynamicList {
+    fn from(items: Vec<DynamicObject>) -> Self {
+        Self::new(items)
+    }
+}
+
+impl IntoIterator for DynamicList {
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type Item = DynamicObject;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RawDynamicList {
+    #[serde(flatten, default)]
+    types: Option<TypeMeta>,
+    #[serde(default)]
+    metadata: ListMeta,
+    #[serde(default)]
+    items: Vec<DynamicObject>,
+}
+
+impl From<RawDynamicList> for DynamicList {
+    fn from(raw: RawDynamicList) -> Self {
+        let types = raw.types.unwrap_or_else(|| DynamicList::new(vec![]).types);
+        let item_kind = types.kind.strip_suffix("List").filter(|kind| !kind.is_empty());
+        let items = raw
+            .items
+            .into_iter()
+            .map(|mut item| {
+                if let (None, Some(kind)) = (&item.types, item_kind) {
+                    item.types = Some(TypeMeta {
+                        api_version: types.api_version.clone(),
+                        kind: kind.to_string(),
+                    });
+                }
+                item
+            })
+            .collect();
+        Self {
+            types,
+            metadata: raw.metadata,
+            items,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -110,6 +193,61 @@ mod test {
         assert_eq!(req.method(), "PATCH");
     }
 
+    #[test]
+    fn mixed_list_round_trip() {
+        use super::DynamicList;
+        let list: DynamicList = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "List",
+            "metadata": { "resourceVersion": "" },
+            "items": [
+                {
+                    "apiVersion": "v1",
+                    "kind": "Service",
+                    "metadata": { "name": "web" },
+                    "spec": { "ports": [{ "port": 80 }] }
+                },
+                {
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "metadata": { "name": "web", "namespace": "prod" },
+                    "spec": { "replicas": 2 }
+                }
+            ]
+        }))
+        .unwrap();
+        let types = list
+            .items
+            .iter()
+            .map(|o| o.types.clone().unwrap())
+            .map(|t| (t.api_version, t.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec![
+            ("v1".to_string(), "Service".to_string()),
+            ("apps/v1".to_string(), "Deployment".to_string())
+        ]);
+        assert_eq!(list.items[1].data["spec"]["replicas"], 2);
+
+        let value = serde_json::to_value(&list).unwrap();
+        assert_eq!(value["kind"], "List");
+        assert_eq!(value["items"][1]["kind"], "Deployment");
+        assert_eq!(value["items"][1]["metadata"]["namespace"], "prod");
+        assert_eq!(value["items"][0]["spec"]["ports"][0]["port"], 80);
+        let again: DynamicList = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&again).unwrap(), value);
+
+        // Items of typed lists don't have types
+        let pods: DynamicList = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "PodList",
+            "metadata": {},
+            "items": [{ "metadata": { "name": "a" } }]
+        }))
+        .unwrap();
+        let pod_types = pods.items[0].types.as_ref().unwrap();
+        assert_eq!((pod_types.api_version.as_str(), pod_types.kind.as_str()), ("v1", "Pod"));
+    }
+
     #[test]
     fn raw_resource_in_default_group() {
         let gvk = GroupVersionKind::gvk("", "v1", "Service");
@@ -0,0 +1,172 @@
+//! Type-safe field selectors
+use std::fmt;
+
+use crate::Resource;
+
+/// A field selector, built from requirements on fields that must all be fulfilled
+///
+/// Values are escaped, so that commas, `=` and backslashes in values select what they say.
+/// Renders to the selector string of list and watch calls, and plugs into
+/// [`ListParams::fields_from`](crate::params::ListParams::fields_from).
+///
+/// Only some fields of each resource can be selected by. The fields of built-in resources are listed by
+/// [`builtin_selectable_fields`](crate::discovery::builtin_selectable_fields).
+///
+/// ```
+/// use kube_core::{fields::FieldSelector, params::ListParams};
+/// let selector = FieldSelector::default()
+///     .node_name("node-1")
+///     .not_equal("status.phase", "Succeeded");
+/// let lp = ListParams::default().fields_from(&selector);
+/// assert_eq!(lp.field_selector.unwrap(), "spec.nodeName=node-1,status.phase!=Succeeded");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FieldSelector(Vec<Requirement>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Requirement {
+    field: String,
+    equal: bool,
+    value: String,
+}
+
+impl FieldSelector {
+    /// Requires the field to have the value
+    pub fn equal(mut self, field: &str, value: &str) -> Self {
+        self.0.push(Requirement {
+            field: field.into(),
+            equal: true,
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Requires the field to not have the value
+    pub fn not_equal(mut self, field: &str, value: &str) -> Self {
+        self.0.push(Requirement {
+            field: field.into(),
+            equal: false,
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Requires the object to have the name, `metadata.name`
+    pub fn name(self, name: &str) -> Self {
+        self.equal("metadata.name", name)
+    }
+
+    /// Requires the object to be in the namespace, `metadata.namespace`
+    pub fn namespace(self, namespace: &str) -> Self {
+        self.equal("metadata.namespace", namespace)
+    }
+
+    /// Requires pods to be scheduled to the node, `spec.nodeName`
+    pub fn node_name(self, node_name: &str) -> Self {
+        self.equal("spec.nodeName", node_name)
+    }
+
+    /// Requires pods or namespaces to be in the phase, `status.phase`
+    pub fn phase(self, phase: &str) -> Self {
+        self.equal("status.phase", phase)
+    }
+
+    /// Requires events to be about objects with the field value, `involvedObject.{field}` such as `kind`
+    pub fn involved_object_field(self, field: &str, value: &str) -> Self {
+        self.equal(&format!("involvedObject.{}", field), value)
+    }
+
+    /// Requires events to be about the object, by its kind, name, namespace and uid
+    pub fn involved_object<K: Resource<DynamicType = ()>>(self, obj: &K) -> Self {
+        let meta = obj.meta();
+        let mut selector = self.involved_object_field("kind", &K::kind(&()));
+        if let Some(name) = &meta.name {
+            selector = selector.involved_object_field("name", name);
+        }
+        if let Some(namespace) = &meta.namespace {
+            selector = selector.involved_object_field("namespace", namespace);
+        }
+        if let Some(uid) = &meta.uid {
+            selector = selector.involved_object_field("uid", uid);
+        }
+        selector
+    }
+
+    /// The fields the selector selects by
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|requirement| requirement.field.as_str())
+    }
+
+    /// Whether the selector selects everything
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for FieldSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, requirement) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            let op = if requirement.equal { "=" } else { "!=" };
+            write!(
+                f,
+                "{}{}{}",
+                requirement.field,
+                op,
+                escape_value(&requirement.value)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Escapes the characters with a meaning in field selectors, like `fields.EscapeValue` in apimachinery
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ',' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldSelector;
+    use crate::{discovery::validate_field_selector, ObjectMeta};
+    use k8s_openapi::api::core::v1::Pod;
+
+    #[test]
+    fn escapes_values() {
+        let selector = FieldSelector::default()
+            .name("a,b=c\\d")
+            .not_equal("status.phase", "Running");
+        assert_eq!(
+            selector.to_string(),
+            r"metadata.name=a\,b\=c\\d,status.phase!=Running"
+        );
+        validate_field_selector(&selector.to_string(), &["status.phase"]).unwrap();
+    }
+
+    #[test]
+    fn selects_events_of_objects() {
+        let pod = Pod {
+            metadata: ObjectMeta {
+                name: Some("blog".into()),
+                namespace: Some("apps".into()),
+                ..ObjectMeta::default()
+            },
+            ..Pod::default()
+        };
+        let selector = FieldSelector::default().involved_object(&pod);
+        assert_eq!(
+            selector.to_string(),
+            "involvedObject.kind=Pod,involvedObject.name=blog,involvedObject.namespace=apps"
+        );
+        assert_eq!(selector.fields().count(), 3);
+    }
+}
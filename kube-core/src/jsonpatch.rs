@@ -0,0 +1,215 @@
+//! A builder for JSON patches, with escaped paths
+use std::fmt;
+
+use json_patch::{
+    AddOperation, CopyOperation, MoveOperation, PatchOperation, RemoveOperation, ReplaceOperation,
+    TestOperation,
+};
+use serde_json::Value;
+
+use crate::params::Patch;
+
+/// A JSON pointer to a value in an object, like `/metadata/labels/app`
+///
+/// Made from a string that is already a pointer, or from the segments of the path, which are escaped
+/// like [RFC 6901](https://datatracker.ietf.org/doc/html/rfc6901) requires: `~` as `~0` and `/` as `~1`.
+///
+/// ```
+/// use kube_core::jsonpatch::Pointer;
+/// let pointer = Pointer::from(["metadata", "labels", "app.kubernetes.io/name"]);
+/// assert_eq!(pointer.to_string(), "/metadata/labels/app.kubernetes.io~1name");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pointer(String);
+
+impl Pointer {
+    /// A pointer to the value at the path of `segments`, escaping each of them
+    pub fn from_segments<S: AsRef<str>>(segments: impl IntoIterator<Item = S>) -> Self {
+        let mut pointer = String::new();
+        for segment in segments {
+            pointer.push('/');
+            pointer.push_str(&escape(segment.as_ref()));
+        }
+        Self(pointer)
+    }
+}
+
+/// Escapes a segment of a JSON pointer, `~` as `~0` and `/` as `~1`
+pub fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Uses the string as a pointer as is, so its segments must already be escaped
+impl From<&str> for Pointer {
+    fn from(pointer: &str) -> Self {
+        Self(pointer.into())
+    }
+}
+
+/// Uses the string as a pointer as is, so its segments must already be escaped
+impl From<String> for Pointer {
+    fn from(pointer: String) -> Self {
+        Self(pointer)
+    }
+}
+
+impl<const N: usize> From<[&str; N]> for Pointer {
+    fn from(segments: [&str; N]) -> Self {
+        Self::from_segments(segments)
+    }
+}
+
+impl From<&[&str]> for Pointer {
+    fn from(segments: &[&str]) -> Self {
+        Self::from_segments(segments)
+    }
+}
+
+/// A builder for the operations of a JSON patch, for [`Patch::Json`]
+///
+/// Paths are [`Pointer`]s, given as pointer strings or as segments that are escaped.
+///
+/// ```
+/// use kube_core::jsonpatch::PatchOps;
+/// let patch = PatchOps::new()
+///     .test("/metadata/resourceVersion", "42")
+///     .add(["metadata", "labels", "app.kubernetes.io/name"], "blog")
+///     .remove("/metadata/annotations/obsolete")
+///     .into_patch();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatchOps(Vec<PatchOperation>);
+
+impl PatchOps {
+    /// An empty patch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `value` at `path`, inserting it into arrays and replacing existing values of objects
+    pub fn add(mut self, path: impl Into<Pointer>, value: impl Into<Value>) -> Self {
+        self.0.push(PatchOperation::Add(AddOperation {
+            path: path.into().0,
+            value: value.into(),
+        }));
+        self
+    }
+
+    /// Removes the value at `path`, which must exist
+    pub fn remove(mut self, path: impl Into<Pointer>) -> Self {
+        self.0
+            .push(PatchOperation::Remove(RemoveOperation { path: path.into().0 }));
+        self
+    }
+
+    /// Replaces the value at `path`, which must exist, with `value`
+    pub fn replace(mut self, path: impl Into<Pointer>, value: impl Into<Value>) -> Self {
+        self.0.push(PatchOperation::Replace(ReplaceOperation {
+            path: path.into().0,
+            value: value.into(),
+        }));
+        self
+    }
+
+    /// Moves the value at `from` to `path`
+    pub fn move_value(mut self, from: impl Into<Pointer>, path: impl Into<Pointer>) -> Self {
+        self.0.push(PatchOperation::Move(MoveOperation {
+            from: from.into().0,
+            path: path.into().0,
+        }));
+        self
+    }
+
+    /// Copies the value at `from` to `path`
+    pub fn copy_value(mut self, from: impl Into<Pointer>, path: impl Into<Pointer>) -> Self {
+        self.0.push(PatchOperation::Copy(CopyOperation {
+            from: from.into().0,
+            path: path.into().0,
+        }));
+        self
+    }
+
+    /// Fails the whole patch unless the value at `path` equals `value`
+    ///
+    /// Guards the other operations against concurrent changes, such as with the `resourceVersion`.
+    pub fn test(mut self, path: impl Into<Pointer>, value: impl Into<Value>) -> Self {
+        self.0.push(PatchOperation::Test(TestOperation {
+            path: path.into().0,
+            value: value.into(),
+        }));
+        self
+    }
+
+    /// The operations of the patch
+    pub fn operations(&self) -> &[PatchOperation] {
+        &self.0
+    }
+
+    /// Whether the patch has no operations
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The [`Patch::Json`] with the operations, ready for the patch calls of an `Api`
+    pub fn into_patch(self) -> Patch<()> {
+        Patch::Json(self.into())
+    }
+}
+
+impl From<PatchOps> for json_patch::Patch {
+    fn from(ops: PatchOps) -> Self {
+        json_patch::Patch(ops.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, PatchOps, Pointer};
+    use serde_json::json;
+
+    #[test]
+    fn escapes_segments() {
+        assert_eq!(escape("a~b/c"), "a~0b~1c");
+        assert_eq!(Pointer::from_segments(Vec::<String>::new()).to_string(), "");
+        assert_eq!(
+            Pointer::from(&["spec", "containers", "0"][..]).to_string(),
+            "/spec/containers/0"
+        );
+        assert_eq!(Pointer::from("/a~1b").to_string(), "/a~1b");
+    }
+
+    #[test]
+    fn builds_json_patches() {
+        let patch: json_patch::Patch = PatchOps::new()
+            .add(["metadata", "annotations", "example.com/owner"], "blog")
+            .replace("/spec/replicas", 2)
+            .move_value("/metadata/labels/old", "/metadata/labels/new")
+            .remove(["metadata", "finalizers", "0"])
+            .into();
+        assert_eq!(
+            serde_json::to_value(&patch).unwrap(),
+            json!([
+                { "op": "add", "path": "/metadata/annotations/example.com~1owner", "value": "blog" },
+                { "op": "replace", "path": "/spec/replicas", "value": 2 },
+                { "op": "move", "from": "/metadata/labels/old", "path": "/metadata/labels/new" },
+                { "op": "remove", "path": "/metadata/finalizers/0" },
+            ])
+        );
+
+        // Applies like the apiserver would
+        let mut obj = json!({
+            "metadata": { "labels": { "old": "x" }, "annotations": {}, "finalizers": ["a"] },
+            "spec": { "replicas": 1 },
+        });
+        json_patch::patch(&mut obj, &patch).unwrap();
+        assert_eq!(obj["metadata"]["annotations"]["example.com/owner"], "blog");
+        assert_eq!(obj["metadata"]["labels"], json!({ "new": "x" }));
+        assert_eq!(obj["spec"]["replicas"], 2);
+    }
+}
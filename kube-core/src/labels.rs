@@ -0,0 +1,294 @@
+//! Type-safe label selectors
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt,
+};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use thiserror::Error;
+
+use crate::validation::{validate_label_value, validate_qualified_name, ValidationError};
+
+#[derive(Debug, Error)]
+#[error("failed to parse label selector operator: {0}")]
+/// Failed to parse the operator of a label selector requirement.
+pub struct ParseOperatorError(pub String);
+
+/// A requirement on the labels of an object, one of the terms of a [`Selector`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expression {
+    /// The label has the value, `key=value`
+    Equal(String, String),
+    /// The label is missing or does not have the value, `key!=value`
+    NotEqual(String, String),
+    /// The label has one of the values, `key in (a,b)`
+    In(String, BTreeSet<String>),
+    /// The label is missing or has none of the values, `key notin (a,b)`
+    NotIn(String, BTreeSet<String>),
+    /// The label is set, `key`
+    Exists(String),
+    /// The label is missing, `!key`
+    DoesNotExist(String),
+}
+
+impl Expression {
+    /// The label key of the requirement
+    pub fn key(&self) -> &str {
+        match self {
+            Expression::Equal(key, _)
+            | Expression::NotEqual(key, _)
+            | Expression::In(key, _)
+            | Expression::NotIn(key, _)
+            | Expression::Exists(key)
+            | Expression::DoesNotExist(key) => key,
+        }
+    }
+
+    /// Whether the labels fulfill the requirement
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        let label = labels.get(self.key());
+        match self {
+            Expression::Equal(_, value) => label == Some(value),
+            Expression::NotEqual(_, value) => label != Some(value),
+            Expression::In(_, values) => label.map_or(false, |label| values.contains(label)),
+            Expression::NotIn(_, values) => label.map_or(true, |label| !values.contains(label)),
+            Expression::Exists(_) => label.is_some(),
+            Expression::DoesNotExist(_) => label.is_none(),
+        }
+    }
+
+    fn values(&self) -> Vec<&String> {
+        match self {
+            Expression::Equal(_, value) | Expression::NotEqual(_, value) => vec![value],
+            Expression::In(_, values) | Expression::NotIn(_, values) => values.iter().collect(),
+            Expression::Exists(_) | Expression::DoesNotExist(_) => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join =
+            |values: &BTreeSet<String>| values.iter().map(String::as_str).collect::<Vec<_>>().join(",");
+        match self {
+            Expression::Equal(key, value) => write!(f, "{}={}", key, value),
+            Expression::NotEqual(key, value) => write!(f, "{}!={}", key, value),
+            Expression::In(key, values) => write!(f, "{} in ({})", key, join(values)),
+            Expression::NotIn(key, values) => write!(f, "{} notin ({})", key, join(values)),
+            Expression::Exists(key) => write!(f, "{}", key),
+            Expression::DoesNotExist(key) => write!(f, "!{}", key),
+        }
+    }
+}
+
+/// A label selector, built from [`Expression`]s that must all be fulfilled
+///
+/// Renders to the selector string of list and watch calls, and plugs into
+/// [`ListParams::labels_from`](crate::params::ListParams::labels_from).
+///
+/// ```
+/// use kube_core::{labels::Selector, params::ListParams};
+/// let selector = Selector::default()
+///     .equal("app", "blog")
+///     .is_in("tier", &["frontend", "cache"])
+///     .does_not_exist("canary");
+/// assert!(selector.validate().is_empty());
+/// let lp = ListParams::default().labels_from(&selector);
+/// assert_eq!(lp.label_selector.unwrap(), "app=blog,tier in (cache,frontend),!canary");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Selector(Vec<Expression>);
+
+impl Selector {
+    /// Adds a requirement
+    pub fn expression(mut self, expression: Expression) -> Self {
+        self.0.push(expression);
+        self
+    }
+
+    /// Requires the label `key` to have the value
+    pub fn equal(self, key: &str, value: &str) -> Self {
+        self.expression(Expression::Equal(key.into(), value.into()))
+    }
+
+    /// Requires the label `key` to be missing or to not have the value
+    pub fn not_equal(self, key: &str, value: &str) -> Self {
+        self.expression(Expression::NotEqual(key.into(), value.into()))
+    }
+
+    /// Requires the label `key` to have one of the values
+    pub fn is_in(self, key: &str, values: &[&str]) -> Self {
+        let values = values.iter().map(ToString::to_string).collect();
+        self.expression(Expression::In(key.into(), values))
+    }
+
+    /// Requires the label `key` to be missing or to have none of the values
+    pub fn not_in(self, key: &str, values: &[&str]) -> Self {
+        let values = values.iter().map(ToString::to_string).collect();
+        self.expression(Expression::NotIn(key.into(), values))
+    }
+
+    /// Requires the label `key` to be set
+    pub fn exists(self, key: &str) -> Self {
+        self.expression(Expression::Exists(key.into()))
+    }
+
+    /// Requires the label `key` to be missing
+    pub fn does_not_exist(self, key: &str) -> Self {
+        self.expression(Expression::DoesNotExist(key.into()))
+    }
+
+    /// The requirements of the selector
+    pub fn expressions(&self) -> &[Expression] {
+        &self.0
+    }
+
+    /// Whether the selector selects everything
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether the labels fulfill all the requirements
+    ///
+    /// Selects objects like the apiserver does, for filtering objects that are already fetched, such as
+    /// the objects of a [`reflector`](https://docs.rs/kube-runtime/*/kube_runtime/fn.reflector.html) store.
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.0.iter().all(|expression| expression.matches(labels))
+    }
+
+    /// Validates the label keys and values of the requirements
+    ///
+    /// The apiserver rejects selectors with invalid keys or values, which cannot be escaped,
+    /// and `in` or `notin` requirements without values.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        for expression in &self.0 {
+            let key = expression.key();
+            for message in validate_qualified_name(key) {
+                errors.push(ValidationError::new("labelSelector", key, message));
+            }
+            if let Expression::In(_, values) | Expression::NotIn(_, values) = expression {
+                if values.is_empty() {
+                    let message = "for 'in', 'notin' operators, values set can't be empty".to_string();
+                    let value = expression.to_string();
+                    errors.push(ValidationError::new("labelSelector", &value, message));
+                }
+            }
+            for value in expression.values() {
+                for message in validate_label_value(value) {
+                    errors.push(ValidationError::new("labelSelector", value, message));
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expressions = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", expressions.join(","))
+    }
+}
+
+/// Selects labels with all the values, like the `matchLabels` of a [`LabelSelector`]
+impl From<BTreeMap<String, String>> for Selector {
+    fn from(labels: BTreeMap<String, String>) -> Self {
+        Self(
+            labels
+                .into_iter()
+                .map(|(key, value)| Expression::Equal(key, value))
+                .collect(),
+        )
+    }
+}
+
+/// Converts the selector of a workload, such as the `spec.selector` of a `Deployment`, to select its pods
+impl TryFrom<LabelSelector> for Selector {
+    type Error = ParseOperatorError;
+
+    fn try_from(selector: LabelSelector) -> Result<Self, Self::Error> {
+        let mut expressions = Self::from(selector.match_labels.unwrap_or_default()).0;
+        for requirement in selector.match_expressions.unwrap_or_default() {
+            let key = requirement.key;
+            let values = requirement.values.unwrap_or_default().into_iter().collect();
+            expressions.push(match requirement.operator.as_str() {
+                "In" => Expression::In(key, values),
+                "NotIn" => Expression::NotIn(key, values),
+                "Exists" => Expression::Exists(key),
+                "DoesNotExist" => Expression::DoesNotExist(key),
+                other => return Err(ParseOperatorError(other.into())),
+            });
+        }
+        Ok(Self(expressions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expression, Selector};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{LabelSelector, LabelSelectorRequirement};
+    use std::{collections::BTreeMap, convert::TryFrom};
+
+    #[test]
+    fn renders_and_matches_selectors() {
+        let selector = Selector::default()
+            .equal("app", "blog")
+            .not_equal("env", "dev")
+            .not_in("tier", &["db"])
+            .exists("team");
+        assert_eq!(selector.to_string(), "app=blog,env!=dev,tier notin (db),team");
+
+        let labels = BTreeMap::from([
+            ("app".to_string(), "blog".to_string()),
+            ("team".to_string(), "web".to_string()),
+        ]);
+        assert!(selector.matches(&labels));
+        assert!(!selector.clone().does_not_exist("team").matches(&labels));
+        assert!(Selector::default().matches(&labels));
+    }
+
+    #[test]
+    fn validates_keys_and_values() {
+        let selector = Selector::default()
+            .equal("app", "blog post")
+            .exists("example.com/");
+        let errors = selector.validate();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].value, "blog post");
+        assert_eq!(errors[1].value, "example.com/");
+
+        let errors = Selector::default().is_in("tier", &[]).validate();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].value, "tier in ()");
+    }
+
+    #[test]
+    fn converts_label_selectors() {
+        let selector = Selector::try_from(LabelSelector {
+            match_labels: Some(BTreeMap::from([("app".to_string(), "blog".to_string())])),
+            match_expressions: Some(vec![LabelSelectorRequirement {
+                key: "tier".into(),
+                operator: "In".into(),
+                values: Some(vec!["web".into(), "cache".into()]),
+            }]),
+        })
+        .unwrap();
+        assert_eq!(
+            selector.expressions()[0],
+            Expression::Equal("app".into(), "blog".into())
+        );
+        assert_eq!(selector.to_string(), "app=blog,tier in (cache,web)");
+
+        let invalid = LabelSelector {
+            match_expressions: Some(vec![LabelSelectorRequirement {
+                key: "tier".into(),
+                operator: "Equals".into(),
+                values: None,
+            }]),
+            ..LabelSelector::default()
+        };
+        assert_eq!(Selector::try_from(invalid).unwrap_err().0, "Equals");
+    }
+}
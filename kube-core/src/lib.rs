@@ -14,25 +14,43 @@
 #[cfg(feature = "admission")]
 pub mod admission;
 
+pub mod builder;
+
 pub mod discovery;
 
 pub mod dynamic;
-pub use dynamic::{ApiResource, DynamicObject};
+pub use dynamic::{ApiResource, DynamicList, DynamicObject};
 
 pub mod crd;
 pub use crd::CustomResourceExt;
 
+k8s_openapi::k8s_if_ge_1_19! {
+    pub mod conditions;
+}
+
+pub mod defaulting;
+
+pub mod fields;
+
 pub mod gvk;
 pub use gvk::{GroupVersion, GroupVersionKind, GroupVersionResource};
 
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonpatch")))]
+#[cfg(feature = "jsonpatch")]
+pub mod jsonpatch;
+
+pub mod labels;
+
 pub mod metadata;
-pub use metadata::{ListMeta, ObjectMeta, TypeMeta};
+pub use metadata::{ListMeta, ObjectMeta, PartialObjectMetadata, TypeMeta};
 
 pub mod object;
 pub use object::{NotUsed, Object, ObjectList};
 
 pub mod params;
 
+pub mod printer;
+
 pub mod request;
 pub use request::Request;
 
@@ -44,8 +62,13 @@ pub use response::Status;
 
 pub mod subresource;
 
+pub mod table;
+pub use table::Table;
+
 pub mod util;
 
+pub mod validation;
+
 pub mod watch;
 pub use watch::WatchEvent;
 
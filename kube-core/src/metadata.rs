@@ -1,6 +1,9 @@
 //! Metadata structs used in traits, lists, and dynamic objects.
 pub use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ListMeta, ObjectMeta};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::{discovery::ApiResource, resource::Resource};
 
 /// Type information that is flattened into every kubernetes object
 #[derive(Deserialize, Serialize, Clone, Default, Debug, Eq, PartialEq, Hash)]
@@ -12,3 +15,51 @@ pub struct TypeMeta {
     /// The name of the API
     pub kind: String,
 }
+
+/// An object of any kind with only its metadata, as returned by metadata-only requests
+///
+/// Requesting metadata only saves deserializing, and keeping, the specs and statuses of objects
+/// when only names, labels or resource versions are needed.
+///
+/// Like [`DynamicObject`](crate::DynamicObject), the kind of the object is described by an [`ApiResource`].
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq)]
+pub struct PartialObjectMetadata {
+    /// The type fields, `meta.k8s.io/v1` `PartialObjectMetadata` rather than the kind of the object
+    #[serde(flatten, default)]
+    pub types: Option<TypeMeta>,
+    /// Object metadata
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+}
+
+impl Resource for PartialObjectMetadata {
+    type DynamicType = ApiResource;
+
+    fn group(dt: &ApiResource) -> Cow<'_, str> {
+        dt.group.as_str().into()
+    }
+
+    fn version(dt: &ApiResource) -> Cow<'_, str> {
+        dt.version.as_str().into()
+    }
+
+    fn kind(dt: &ApiResource) -> Cow<'_, str> {
+        dt.kind.as_str().into()
+    }
+
+    fn api_version(dt: &ApiResource) -> Cow<'_, str> {
+        dt.api_version.as_str().into()
+    }
+
+    fn plural(dt: &ApiResource) -> Cow<'_, str> {
+        dt.plural.as_str().into()
+    }
+
+    fn meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn meta_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
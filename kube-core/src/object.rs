@@ -142,6 +142,15 @@ pub trait HasStatus {
     fn status_mut(&mut self) -> &mut Option<Self::Status>;
 }
 
+/// A marker trait for resources whose `status` is served through the status subresource.
+///
+/// Writes to the `status` of such resources are ignored by the main endpoint, and must go through
+/// the status subresource instead, such as with `Api::apply_status`, which requires this trait.
+///
+/// This trait is automatically implemented by the kube-derive macro for custom resources with a `status`,
+/// since their `CustomResourceDefinition` enables the status subresource.
+pub trait HasStatusSubresource: HasStatus {}
+
 // -------------------------------------------------------
 
 /// A standard Kubernetes object with `.spec` and `.status`.
@@ -1,5 +1,5 @@
 //! A port of request parameter *Optionals from apimachinery/types.go
-use crate::request::Error;
+use crate::{discovery::ApiCapabilities, fields::FieldSelector, labels::Selector, request::Error};
 use serde::Serialize;
 
 /// Common query parameters used in watch/list/delete calls on collections
@@ -19,7 +19,7 @@ pub struct ListParams {
     ///
     /// This limits the duration of the call, regardless of any activity or inactivity.
     /// If unset for a watch call, we will use 290s.
-    /// We limit this to 295s due to [inherent watch limitations](https://github.com/kubernetes/kubernetes/issues/6513).
+    /// Watch calls limit this to 295s due to [inherent watch limitations](https://github.com/kubernetes/kubernetes/issues/6513).
     pub timeout: Option<u32>,
 
     /// Enables watch events with type "BOOKMARK".
@@ -44,6 +44,19 @@ pub struct ListParams {
     ///
     /// After listing results with a limit, a continue token can be used to fetch another page of results.
     pub continue_token: Option<String>,
+
+    /// The resource version to list at, interpreted according to `resource_version_match`.
+    ///
+    /// Unset lists the most recent data, with a quorum read from etcd. `"0"` lists any data the
+    /// apiserver has cached, which is much cheaper but may be stale. Ignored by watch calls,
+    /// which start at the version they are given.
+    pub resource_version: Option<String>,
+
+    /// How `resource_version` is matched by list calls.
+    ///
+    /// Defaults to `NotOlderThan` semantics for a `resource_version`, except for pages of a list with a `limit`.
+    /// `Exact` lists a consistent snapshot at a version from a previous list. Cannot be used with watch calls.
+    pub resource_version_match: Option<VersionMatch>,
 }
 
 impl Default for ListParams {
@@ -58,17 +71,32 @@ impl Default for ListParams {
             timeout: None,
             limit: None,
             continue_token: None,
+            resource_version: None,
+            resource_version_match: None,
         }
     }
 }
 
 impl ListParams {
     pub(crate) fn validate(&self) -> Result<(), Error> {
-        if let Some(to) = &self.timeout {
-            // https://github.com/kubernetes/kubernetes/issues/6513
-            if *to >= 295 {
-                return Err(Error::Validation("ListParams::timeout must be < 295s".into()));
-            }
+        if self.resource_version_match.is_some() && self.resource_version.is_none() {
+            return Err(Error::Validation(
+                "ListParams::resource_version_match requires a resource_version".into(),
+            ));
+        }
+        if self.resource_version_match == Some(VersionMatch::Exact)
+            && self.resource_version.as_deref() == Some("0")
+        {
+            return Err(Error::Validation(
+                "ListParams::resource_version_match cannot be Exact for resource_version 0".into(),
+            ));
+        }
+        if self.continue_token.is_some()
+            && (self.resource_version.is_some() || self.resource_version_match.is_some())
+        {
+            return Err(Error::Validation(
+                "ListParams::resource_version cannot be used with a continue token".into(),
+            ));
         }
         Ok(())
     }
@@ -103,6 +131,14 @@ impl ListParams {
         self
     }
 
+    /// Configure the selector to restrict the list of returned objects by their fields, from a [`FieldSelector`].
+    ///
+    /// Unlike [`ListParams::fields`], commas and `=` in the values are escaped.
+    pub fn fields_from(mut self, selector: &FieldSelector) -> Self {
+        self.field_selector = Some(selector.to_string());
+        self
+    }
+
     /// Configure the selector to restrict the list of returned objects by their labels.
     ///
     /// Defaults to everything.
@@ -112,6 +148,15 @@ impl ListParams {
         self
     }
 
+    /// Configure the selector to restrict the list of returned objects by their labels, from a [`Selector`].
+    ///
+    /// Unlike [`ListParams::labels`], the set-based `in`, `notin` and existence requirements
+    /// do not have to be formatted by hand.
+    pub fn labels_from(mut self, selector: &Selector) -> Self {
+        self.label_selector = Some(selector.to_string());
+        self
+    }
+
     /// Disables watch bookmarks to simplify watch handling
     ///
     /// This is not recommended to use with production watchers as it can cause desyncs.
@@ -121,6 +166,28 @@ impl ListParams {
         self
     }
 
+    /// Checks that the field selector only uses fields the resource can be selected by
+    ///
+    /// Catches field selectors that the API server would reject, or silently ignore.
+    /// The capabilities of a resource come from discovery, see [`ApiCapabilities::selectable_fields`].
+    pub fn validate_fields(&self, capabilities: &ApiCapabilities) -> Result<(), Error> {
+        match &self.field_selector {
+            Some(selector) => capabilities.validate_field_selector(selector),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the field selector only uses fields the custom resource `K` can be selected by
+    ///
+    /// These are the fields declared with `#[kube(selectable = "spec.field")]`, see
+    /// [`CustomResourceExt::selectable_fields`](crate::CustomResourceExt::selectable_fields).
+    pub fn validate_fields_for<K: crate::CustomResourceExt>(&self) -> Result<(), Error> {
+        match &self.field_selector {
+            Some(selector) => crate::discovery::validate_field_selector(selector, K::selectable_fields()),
+            None => Ok(()),
+        }
+    }
+
     /// Sets a result limit.
     pub fn limit(mut self, limit: u32) -> Self {
         self.limit = Some(limit);
@@ -132,6 +199,20 @@ impl ListParams {
         self.continue_token = Some(token.to_string());
         self
     }
+
+    /// Sets the resource version to list at.
+    ///
+    /// Use `"0"` for a cheap list of the data cached by the apiserver.
+    pub fn resource_version(mut self, resource_version: &str) -> Self {
+        self.resource_version = Some(resource_version.to_string());
+        self
+    }
+
+    /// Sets how the resource version is matched.
+    pub fn resource_version_match(mut self, version_match: VersionMatch) -> Self {
+        self.resource_version_match = Some(version_match);
+        self
+    }
 }
 
 /// Common query parameters for put/post calls
@@ -141,6 +222,10 @@ pub struct PostParams {
     pub dry_run: bool,
     /// fieldManager is a name of the actor that is making changes
     pub field_manager: Option<String>,
+    /// How the apiserver handles unknown or duplicate fields in the object
+    ///
+    /// Requires kubernetes >= 1.25 for `Strict` and `Warn`, older apiservers ignore it.
+    pub field_validation: Option<ValidationDirective>,
 }
 
 impl PostParams {
@@ -264,6 +349,10 @@ pub struct PatchParams {
     /// fieldManager is a name of the actor that is making changes. Required for [`Patch::Apply`]
     /// optional for everything else.
     pub field_manager: Option<String>,
+    /// How the apiserver handles unknown or duplicate fields in the patch
+    ///
+    /// Requires kubernetes >= 1.25 for `Strict` and `Warn`, older apiservers ignore it.
+    pub field_validation: Option<ValidationDirective>,
 }
 
 impl PatchParams {
@@ -285,18 +374,6 @@ impl PatchParams {
         Ok(())
     }
 
-    pub(crate) fn populate_qp(&self, qp: &mut form_urlencoded::Serializer<String>) {
-        if self.dry_run {
-            qp.append_pair("dryRun", "All");
-        }
-        if self.force {
-            qp.append_pair("force", "true");
-        }
-        if let Some(ref fm) = self.field_manager {
-            qp.append_pair("fieldManager", fm);
-        }
-    }
-
     /// Construct `PatchParams` for server-side apply
     pub fn apply(manager: &str) -> Self {
         Self {
@@ -318,6 +395,313 @@ impl PatchParams {
         self.dry_run = true;
         self
     }
+
+    /// Set how the apiserver handles unknown or duplicate fields in the patch
+    pub fn validation(mut self, directive: ValidationDirective) -> Self {
+        self.field_validation = Some(directive);
+        self
+    }
+}
+
+/// How the apiserver handles unknown or duplicate fields in the objects of create, replace and patch calls
+///
+/// Without it, the apiserver silently drops unknown fields and keeps the last of duplicate fields.
+/// See the [Kubernetes API docs](https://kubernetes.io/docs/reference/using-api/api-concepts/#field-validation).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationDirective {
+    /// Reject the request if the object has unknown or duplicate fields
+    Strict,
+    /// Drop unknown fields and keep the last of duplicate fields, returning a warning for each of them
+    Warn,
+    /// Silently drop unknown fields and keep the last of duplicate fields
+    Ignore,
+}
+
+impl ValidationDirective {
+    fn as_str(self) -> &'static str {
+        match self {
+            ValidationDirective::Strict => "Strict",
+            ValidationDirective::Warn => "Warn",
+            ValidationDirective::Ignore => "Ignore",
+        }
+    }
+}
+
+/// How the `resourceVersion` of list calls is matched
+///
+/// See the [Kubernetes API docs](https://kubernetes.io/docs/reference/using-api/api-concepts/#resource-versions)
+/// for the semantics of each option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionMatch {
+    /// Return data at least as new as the provided `resourceVersion`
+    NotOlderThan,
+    /// Return data at the exact `resourceVersion` provided
+    Exact,
+}
+
+impl VersionMatch {
+    fn as_str(self) -> &'static str {
+        match self {
+            VersionMatch::NotOlderThan => "NotOlderThan",
+            VersionMatch::Exact => "Exact",
+        }
+    }
+}
+
+/// Typed query parameters of Kubernetes API calls
+///
+/// Covers the query parameters of all verbs, and is used by the methods of [`Request`](crate::Request).
+/// Custom requests can use it with [`Request::url`](crate::Request::url) instead of formatting query strings.
+///
+/// Usage:
+/// ```
+/// use kube_core::{params::QueryParams, Request};
+/// let qp = QueryParams::default()
+///     .labels("app=web")
+///     .limit(50)
+///     .resource_version("0");
+/// let url = Request::new("/api/v1/pods").url(None, None, &qp).unwrap();
+/// assert_eq!(url, "/api/v1/pods?&resourceVersion=0&labelSelector=app%3Dweb&limit=50");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryParams {
+    /// Watch for changes instead of returning the current state
+    pub watch: bool,
+    /// The resource version to list, or to start watching from
+    pub resource_version: Option<String>,
+    /// How `resource_version` is matched by list calls
+    pub resource_version_match: Option<VersionMatch>,
+    /// Timeout for list/watch calls in seconds
+    pub timeout_seconds: Option<u32>,
+    /// A selector to restrict the returned objects by their fields
+    pub field_selector: Option<String>,
+    /// A selector to restrict the returned objects by their labels
+    pub label_selector: Option<String>,
+    /// Limit the number of results of list calls
+    pub limit: Option<u32>,
+    /// Continue token to fetch the next page of results of list calls
+    pub continue_token: Option<String>,
+    /// Enables watch events with type "BOOKMARK"
+    pub allow_watch_bookmarks: bool,
+    /// Whether to run this as a dry run
+    pub dry_run: bool,
+    /// Force conflicts of server-side apply
+    pub force: bool,
+    /// Name of the actor that is making changes
+    pub field_manager: Option<String>,
+    /// How unknown or duplicate fields are handled by create, replace and patch calls
+    pub field_validation: Option<ValidationDirective>,
+}
+
+/// Builder interface to QueryParams
+impl QueryParams {
+    /// Watch for changes instead of returning the current state
+    pub fn watch(mut self) -> Self {
+        self.watch = true;
+        self
+    }
+
+    /// Sets the resource version
+    pub fn resource_version(mut self, version: &str) -> Self {
+        self.resource_version = Some(version.to_string());
+        self
+    }
+
+    /// Sets how the resource version is matched
+    pub fn resource_version_match(mut self, version_match: VersionMatch) -> Self {
+        self.resource_version_match = Some(version_match);
+        self
+    }
+
+    /// Sets the timeout in seconds
+    pub fn timeout(mut self, timeout_secs: u32) -> Self {
+        self.timeout_seconds = Some(timeout_secs);
+        self
+    }
+
+    /// Sets a field selector
+    pub fn fields(mut self, field_selector: &str) -> Self {
+        self.field_selector = Some(field_selector.to_string());
+        self
+    }
+
+    /// Sets a label selector
+    pub fn labels(mut self, label_selector: &str) -> Self {
+        self.label_selector = Some(label_selector.to_string());
+        self
+    }
+
+    /// Sets a result limit
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets a continue token
+    pub fn continue_token(mut self, token: &str) -> Self {
+        self.continue_token = Some(token.to_string());
+        self
+    }
+
+    /// Enables watch bookmarks
+    pub fn bookmarks(mut self) -> Self {
+        self.allow_watch_bookmarks = true;
+        self
+    }
+
+    /// Run this as a dry run
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Force conflicts of server-side apply
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    /// Sets the field manager
+    pub fn field_manager(mut self, manager: &str) -> Self {
+        self.field_manager = Some(manager.to_string());
+        self
+    }
+
+    /// Sets how unknown or duplicate fields are handled
+    pub fn field_validation(mut self, directive: ValidationDirective) -> Self {
+        self.field_validation = Some(directive);
+        self
+    }
+}
+
+impl QueryParams {
+    /// Checks for combinations of parameters that the API server rejects
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.watch && self.limit.is_some() {
+            return Err(Error::Validation("QueryParams::limit cannot be used with a watch".into()));
+        }
+        if self.watch && self.continue_token.is_some() {
+            return Err(Error::Validation(
+                "QueryParams::continue_token cannot be used with a watch".into(),
+            ));
+        }
+        if self.watch && self.resource_version_match.is_some() {
+            return Err(Error::Validation(
+                "QueryParams::resource_version_match cannot be used with a watch".into(),
+            ));
+        }
+        if self.continue_token.is_some() && self.resource_version.is_some() {
+            return Err(Error::Validation(
+                "QueryParams::resource_version cannot be used with a continue token".into(),
+            ));
+        }
+        if self.resource_version_match.is_some() && self.resource_version.is_none() {
+            return Err(Error::Validation(
+                "QueryParams::resource_version_match requires a resource_version".into(),
+            ));
+        }
+        if self.resource_version_match == Some(VersionMatch::Exact)
+            && self.resource_version.as_deref() == Some("0")
+        {
+            return Err(Error::Validation(
+                "QueryParams::resource_version_match cannot be Exact for resource_version 0".into(),
+            ));
+        }
+        if let Some(to) = &self.timeout_seconds {
+            // https://github.com/kubernetes/kubernetes/issues/6513
+            if self.watch && *to >= 295 {
+                return Err(Error::Validation("QueryParams::timeout must be < 295s".into()));
+            }
+        }
+        if let Some(field_manager) = &self.field_manager {
+            if field_manager.len() > 128 {
+                return Err(Error::Validation(
+                    "QueryParams::field_manager must be at most 128 characters".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn populate_qp(&self, qp: &mut form_urlencoded::Serializer<String>) {
+        if self.watch {
+            qp.append_pair("watch", "true");
+        }
+        if let Some(version) = &self.resource_version {
+            qp.append_pair("resourceVersion", version);
+        }
+        if let Some(version_match) = self.resource_version_match {
+            qp.append_pair("resourceVersionMatch", version_match.as_str());
+        }
+        if let Some(timeout) = &self.timeout_seconds {
+            qp.append_pair("timeoutSeconds", &timeout.to_string());
+        }
+        if let Some(fields) = &self.field_selector {
+            qp.append_pair("fieldSelector", fields);
+        }
+        if let Some(labels) = &self.label_selector {
+            qp.append_pair("labelSelector", labels);
+        }
+        if let Some(limit) = &self.limit {
+            qp.append_pair("limit", &limit.to_string());
+        }
+        if let Some(continue_token) = &self.continue_token {
+            qp.append_pair("continue", continue_token);
+        }
+        if self.allow_watch_bookmarks {
+            qp.append_pair("allowWatchBookmarks", "true");
+        }
+        if self.dry_run {
+            qp.append_pair("dryRun", "All");
+        }
+        if self.force {
+            qp.append_pair("force", "true");
+        }
+        if let Some(fm) = &self.field_manager {
+            qp.append_pair("fieldManager", fm);
+        }
+        if let Some(directive) = self.field_validation {
+            qp.append_pair("fieldValidation", directive.as_str());
+        }
+    }
+}
+
+/// The query parameters of list calls
+impl From<&ListParams> for QueryParams {
+    fn from(lp: &ListParams) -> Self {
+        Self {
+            field_selector: lp.field_selector.clone(),
+            label_selector: lp.label_selector.clone(),
+            limit: lp.limit,
+            continue_token: lp.continue_token.clone(),
+            resource_version: lp.resource_version.clone(),
+            resource_version_match: lp.resource_version_match,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&PostParams> for QueryParams {
+    fn from(pp: &PostParams) -> Self {
+        Self {
+            dry_run: pp.dry_run,
+            field_manager: pp.field_manager.clone(),
+            field_validation: pp.field_validation,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&PatchParams> for QueryParams {
+    fn from(pp: &PatchParams) -> Self {
+        Self {
+            dry_run: pp.dry_run,
+            force: pp.force,
+            field_manager: pp.field_manager.clone(),
+            field_validation: pp.field_validation,
+            ..Self::default()
+        }
+    }
 }
 
 /// Common query parameters for delete calls
@@ -353,6 +737,57 @@ pub struct DeleteParams {
     pub preconditions: Option<Preconditions>,
 }
 
+/// Builder interface to DeleteParams
+impl DeleteParams {
+    /// Construct `DeleteParams` with [`PropagationPolicy::Background`]
+    ///
+    /// The objects are deleted at once, and the garbage collector deletes their dependents in the background.
+    pub fn background() -> Self {
+        Self {
+            propagation_policy: Some(PropagationPolicy::Background),
+            ..Self::default()
+        }
+    }
+
+    /// Construct `DeleteParams` with [`PropagationPolicy::Foreground`]
+    ///
+    /// The objects are only deleted once all their dependents with `blockOwnerDeletion` are deleted.
+    pub fn foreground() -> Self {
+        Self {
+            propagation_policy: Some(PropagationPolicy::Foreground),
+            ..Self::default()
+        }
+    }
+
+    /// Construct `DeleteParams` with [`PropagationPolicy::Orphan`]
+    ///
+    /// The dependents of the objects are kept, with their `ownerReferences` to the objects removed.
+    pub fn orphan() -> Self {
+        Self {
+            propagation_policy: Some(PropagationPolicy::Orphan),
+            ..Self::default()
+        }
+    }
+
+    /// Perform a dryRun only
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Sets the grace period in seconds, `0` deletes immediately
+    pub fn grace_period(mut self, secs: u32) -> Self {
+        self.grace_period_seconds = Some(secs);
+        self
+    }
+
+    /// Sets the preconditions that the objects must fulfill to be deleted
+    pub fn preconditions(mut self, preconditions: Preconditions) -> Self {
+        self.preconditions = Some(preconditions);
+        self
+    }
+}
+
 // dryRun serialization differ when used as body parameters and query strings:
 // query strings are either true/false
 // body params allow only: missing field, or ["All"]
@@ -388,6 +823,13 @@ mod test {
         let ser = serde_json::to_string(&dp).unwrap();
         //println!("ser is: {}", ser);
         assert_eq!(ser, "{\"dryRun\":[\"All\"]}");
+
+        let dp = DeleteParams::foreground().grace_period(0).dry_run();
+        let ser = serde_json::to_string(&dp).unwrap();
+        assert_eq!(
+            ser,
+            r#"{"dryRun":["All"],"gracePeriodSeconds":0,"propagationPolicy":"Foreground"}"#
+        );
     }
 }
 
@@ -0,0 +1,348 @@
+//! Summarizes custom resources by the printer columns of their CRD, like `kubectl get`
+//!
+//! ```
+//! use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+//! use kube::core::{printer::summarize, DynamicObject};
+//! fn print(crd: &CustomResourceDefinition, objects: &[DynamicObject]) {
+//!     for obj in objects {
+//!         if let Some(summary) = summarize(crd, obj) {
+//!             let cells = summary.columns.iter().map(|c| c.value.to_string()).collect::<Vec<_>>();
+//!             println!("{}\t{}", summary.name, cells.join("\t"));
+//!         }
+//!     }
+//! }
+//! ```
+use chrono::{DateTime, Duration, Utc};
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+    CustomResourceColumnDefinition, CustomResourceDefinition,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// The printer columns of a custom resource, see [`summarize`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    /// The name of the resource, which `kubectl get` always prints first
+    pub name: String,
+    /// The printer columns of the resource version, in the order of the CRD
+    pub columns: Vec<Column>,
+}
+
+/// A printer column of a custom resource
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    /// The header of the column, such as `AGE`
+    pub name: String,
+    /// Columns with a priority above `0` are only printed by `kubectl get -o wide`
+    pub priority: i32,
+    /// The value of the column
+    pub value: ColumnValue,
+}
+
+/// The typed value of a printer column
+///
+/// Displays like the cells of `kubectl get`, with dates as the age relative to now.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnValue {
+    /// The JSON path of the column did not match
+    None,
+    /// An `integer` column
+    Integer(i64),
+    /// A `number` column
+    Number(f64),
+    /// A `boolean` column
+    Boolean(bool),
+    /// A `string` column, or a value that does not match the type of its column
+    String(String),
+    /// A `date` column
+    Date(DateTime<Utc>),
+}
+
+impl fmt::Display for ColumnValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnValue::None => f.write_str("<none>"),
+            ColumnValue::Integer(i) => write!(f, "{}", i),
+            ColumnValue::Number(n) => write!(f, "{}", n),
+            ColumnValue::Boolean(b) => write!(f, "{}", b),
+            ColumnValue::String(s) => f.write_str(s),
+            ColumnValue::Date(date) => f.write_str(&human_duration(Utc::now() - *date)),
+        }
+    }
+}
+
+/// Summarizes `obj` by the `additionalPrinterColumns` of its version in `crd`
+///
+/// Versions without printer columns get an `AGE` column, like `kubectl get` prints for them.
+/// The version is taken from the `apiVersion` of `obj`, and defaults to the storage version.
+/// Returns `None` if `obj` does not serialize to an object, or `crd` has no matching version.
+pub fn summarize<K: Serialize>(crd: &CustomResourceDefinition, obj: &K) -> Option<Summary> {
+    let obj = serde_json::to_value(obj).ok()?;
+    let version = obj["apiVersion"]
+        .as_str()
+        .and_then(|api_version| api_version.rsplit('/').next());
+    let versions = &crd.spec.versions;
+    let version = versions
+        .iter()
+        .find(|v| Some(v.name.as_str()) == version)
+        .or_else(|| versions.iter().find(|v| v.storage))?;
+
+    let age = CustomResourceColumnDefinition {
+        name: "Age".into(),
+        type_: "date".into(),
+        json_path: ".metadata.creationTimestamp".into(),
+        ..CustomResourceColumnDefinition::default()
+    };
+    let definitions = match &version.additional_printer_columns {
+        Some(columns) if !columns.is_empty() => columns.clone(),
+        _ => vec![age],
+    };
+    let columns = definitions
+        .into_iter()
+        .map(|column| Column {
+            value: column_value(&column.type_, select(&obj, &column.json_path)),
+            name: column.name.to_uppercase(),
+            priority: column.priority.unwrap_or(0),
+        })
+        .collect();
+    Some(Summary {
+        name: obj["metadata"]["name"].as_str()?.to_string(),
+        columns,
+    })
+}
+
+fn column_value(type_: &str, values: Vec<&Value>) -> ColumnValue {
+    let value = match values.as_slice() {
+        [] => return ColumnValue::None,
+        [value] => *value,
+        // Multiple matches are printed separated by spaces
+        values => return ColumnValue::String(values.iter().map(|v| plain(v)).collect::<Vec<_>>().join(" ")),
+    };
+    let typed = match type_ {
+        "integer" => value.as_i64().map(ColumnValue::Integer),
+        "number" => value.as_f64().map(ColumnValue::Number),
+        "boolean" => value.as_bool().map(ColumnValue::Boolean),
+        "date" => value
+            .as_str()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map(|date| ColumnValue::Date(date.with_timezone(&Utc))),
+        _ => None,
+    };
+    match (typed, value) {
+        (Some(typed), _) => typed,
+        (None, Value::Null) => ColumnValue::None,
+        (None, value) => ColumnValue::String(plain(value)),
+    }
+}
+
+// Strings are printed without quotes
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Selects the values at the JSON path `path`, such as `.status.conditions[?(@.type=="Ready")].status`
+///
+/// Supports fields (`.a`, `['a']`), indices (`[0]`), wildcards (`[*]`) and equality filters (`[?(@.a=="b")]`),
+/// which covers the simple paths allowed in printer columns.
+/// Returns no values if the path does not match, or cannot be parsed.
+pub fn select<'a>(value: &'a Value, path: &str) -> Vec<&'a Value> {
+    let path = path.trim();
+    let path = path
+        .strip_prefix('{')
+        .and_then(|p| p.strip_suffix('}'))
+        .unwrap_or(path);
+    let mut current = vec![value];
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    while !rest.is_empty() {
+        let (step, remaining) = match next_step(rest) {
+            Some(next) => next,
+            None => return vec![],
+        };
+        current = current.into_iter().flat_map(|v| step.apply(v)).collect();
+        rest = remaining;
+    }
+    current.retain(|v| !v.is_null());
+    current
+}
+
+enum Step<'p> {
+    Field(&'p str),
+    Index(usize),
+    All,
+    Filter(&'p str, Value),
+}
+
+impl Step<'_> {
+    fn apply<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        match self {
+            Step::Field(field) => value.get(*field).into_iter().collect(),
+            Step::Index(i) => value.get(*i).into_iter().collect(),
+            Step::All => match value {
+                Value::Array(items) => items.iter().collect(),
+                Value::Object(map) => map.values().collect(),
+                _ => vec![],
+            },
+            Step::Filter(path, expected) => value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|item| select(item, path).first() == Some(&expected))
+                .collect(),
+        }
+    }
+}
+
+// Parses the next step of a JSON path, and returns it with the rest of the path
+fn next_step(path: &str) -> Option<(Step<'_>, &str)> {
+    if let Some(rest) = path.strip_prefix('.') {
+        let end = rest.find(|c: char| c == '.' || c == '[').unwrap_or(rest.len());
+        return Some((Step::Field(&rest[..end]), &rest[end..]));
+    }
+    let rest = path.strip_prefix('[')?;
+    if let Some(filter) = rest.strip_prefix("?(@") {
+        let end = filter.find(")]")?;
+        let (path, expected) = filter[..end].split_once("==")?;
+        let expected = expected.trim().replace('\'', "\"");
+        let expected = serde_json::from_str(&expected).ok()?;
+        return Some((Step::Filter(path.trim(), expected), &filter[end + 2..]));
+    }
+    let end = rest.find(']')?;
+    let (inner, rest) = (rest[..end].trim(), &rest[end + 1..]);
+    let step = if inner == "*" {
+        Step::All
+    } else if let Ok(index) = inner.parse() {
+        Step::Index(index)
+    } else {
+        Step::Field(inner.trim_matches(|c| c == '\'' || c == '"'))
+    };
+    Some((step, rest))
+}
+
+/// Formats a duration like `kubectl get` formats ages, such as `5m30s` or `3d2h`
+pub fn human_duration(d: Duration) -> String {
+    let seconds = d.num_seconds();
+    if seconds < -1 {
+        return "<invalid>".into();
+    }
+    if seconds < 0 {
+        return "0s".into();
+    }
+    let minutes = d.num_minutes();
+    let hours = d.num_hours();
+    let days = d.num_days();
+    let years = days / 365;
+    let with = |major: String, minor: i64, unit: &str| {
+        if minor == 0 {
+            major
+        } else {
+            format!("{}{}{}", major, minor, unit)
+        }
+    };
+    if seconds < 60 * 2 {
+        format!("{}s", seconds)
+    } else if minutes < 10 {
+        with(format!("{}m", minutes), seconds % 60, "s")
+    } else if minutes < 60 * 3 {
+        format!("{}m", minutes)
+    } else if hours < 8 {
+        with(format!("{}h", hours), minutes % 60, "m")
+    } else if hours < 48 {
+        format!("{}h", hours)
+    } else if hours < 24 * 8 {
+        with(format!("{}d", days), hours % 24, "h")
+    } else if hours < 24 * 365 * 2 {
+        format!("{}d", days)
+    } else if hours < 24 * 365 * 8 {
+        with(format!("{}y", years), days % 365, "d")
+    } else {
+        format!("{}y", years)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{human_duration, summarize, ColumnValue};
+    use chrono::Duration;
+    use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+    use serde_json::json;
+
+    fn crd(columns: serde_json::Value) -> CustomResourceDefinition {
+        serde_json::from_value(json!({
+            "metadata": { "name": "foos.clux.dev" },
+            "spec": {
+                "group": "clux.dev",
+                "names": { "kind": "Foo", "plural": "foos" },
+                "scope": "Namespaced",
+                "versions": [{
+                    "name": "v1",
+                    "served": true,
+                    "storage": true,
+                    "additionalPrinterColumns": columns,
+                }],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn summarizes_printer_columns() {
+        let foo_crd = crd(json!([
+            { "name": "Replicas", "type": "integer", "jsonPath": ".spec.replicas" },
+            { "name": "Ready", "type": "string", "jsonPath": ".status.conditions[?(@.type==\"Ready\")].status" },
+            { "name": "Images", "type": "string", "jsonPath": ".spec.images[*]", "priority": 1 },
+            { "name": "Missing", "type": "string", "jsonPath": ".status.missing" },
+            { "name": "Created", "type": "date", "jsonPath": ".metadata.creationTimestamp" },
+        ]));
+        let obj = json!({
+            "apiVersion": "clux.dev/v1",
+            "kind": "Foo",
+            "metadata": { "name": "foo", "creationTimestamp": "2021-11-01T00:00:00Z" },
+            "spec": { "replicas": 3, "images": ["a", "b"] },
+            "status": { "conditions": [
+                { "type": "Progressing", "status": "True" },
+                { "type": "Ready", "status": "False" },
+            ] },
+        });
+        let summary = summarize(&foo_crd, &obj).unwrap();
+        assert_eq!(summary.name, "foo");
+        let cells = summary
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.priority, c.value.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(cells, vec![
+            ("REPLICAS", 0, ColumnValue::Integer(3)),
+            ("READY", 0, ColumnValue::String("False".into())),
+            ("IMAGES", 1, ColumnValue::String("a b".into())),
+            ("MISSING", 0, ColumnValue::None),
+            ("CREATED", 0, ColumnValue::Date("2021-11-01T00:00:00Z".parse().unwrap())),
+        ]);
+
+        // Versions without columns print the age
+        let summary = summarize(&crd(json!(null)), &obj).unwrap();
+        assert_eq!(summary.columns[0].name, "AGE");
+    }
+
+    #[test]
+    fn formats_ages_like_kubectl() {
+        let ages = [
+            (Duration::seconds(-5), "<invalid>"),
+            (Duration::seconds(90), "90s"),
+            (Duration::seconds(5 * 60 + 30), "5m30s"),
+            (Duration::minutes(45), "45m"),
+            (Duration::minutes(5 * 60 + 3), "5h3m"),
+            (Duration::hours(30), "30h"),
+            (Duration::hours(3 * 24 + 2), "3d2h"),
+            (Duration::days(40), "40d"),
+            (Duration::days(3 * 365 + 10), "3y10d"),
+            (Duration::days(10 * 365), "10y"),
+        ];
+        for (duration, age) in ages {
+            assert_eq!(human_duration(duration), age);
+        }
+    }
+}
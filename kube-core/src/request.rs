@@ -1,9 +1,22 @@
 //! Request builder type for arbitrary api types
 use thiserror::Error;
 
-use super::params::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use super::{
+    discovery::{verbs, Subresource},
+    params::{DeleteParams, ListParams, Patch, PatchParams, PostParams, QueryParams},
+};
 
 pub(crate) const JSON_MIME: &str = "application/json";
+/// Accept header for the metadata of an object, as a [`PartialObjectMetadata`](crate::PartialObjectMetadata)
+///
+/// Falls back to the full object on apiservers that cannot serve partial metadata, like aggregated apiservers.
+pub const METADATA_MIME: &str =
+    "application/json;as=PartialObjectMetadata;g=meta.k8s.io;v=v1,application/json";
+/// Accept header for the metadata of a list of objects, falling back to the full objects like [`METADATA_MIME`]
+pub const METADATA_LIST_MIME: &str =
+    "application/json;as=PartialObjectMetadataList;g=meta.k8s.io;v=v1,application/json";
+/// Accept header for a list of objects rendered as a [`Table`](crate::Table)
+pub const TABLE_MIME: &str = "application/json;as=Table;g=meta.k8s.io;v=v1";
 
 /// Possible errors when building a request.
 #[derive(Debug, Error)]
@@ -36,6 +49,26 @@ impl Request {
             url_path: url_path.into(),
         }
     }
+
+    /// The url of a custom request for the object `name` and its `subresource`, with typed query parameters
+    ///
+    /// The query parameters are validated with [`QueryParams::validate`].
+    pub fn url(&self, name: Option<&str>, subresource: Option<&str>, qp: &QueryParams) -> Result<String, Error> {
+        qp.validate()?;
+        let mut target = self.url_path.clone();
+        for segment in name.into_iter().chain(subresource) {
+            target.push('/');
+            target.push_str(segment);
+        }
+        target.push('?');
+        Ok(with_query(target, qp))
+    }
+}
+
+fn with_query(target: String, qp: &QueryParams) -> String {
+    let mut ser = form_urlencoded::Serializer::new(target);
+    qp.populate_qp(&mut ser);
+    ser.finish()
 }
 
 // -------------------------------------------------------
@@ -45,22 +78,8 @@ impl Request {
     /// List a collection of a resource
     pub fn list(&self, lp: &ListParams) -> Result<http::Request<Vec<u8>>, Error> {
         let target = format!("{}?", self.url_path);
-        let mut qp = form_urlencoded::Serializer::new(target);
-
-        if let Some(fields) = &lp.field_selector {
-            qp.append_pair("fieldSelector", fields);
-        }
-        if let Some(labels) = &lp.label_selector {
-            qp.append_pair("labelSelector", labels);
-        }
-        if let Some(limit) = &lp.limit {
-            qp.append_pair("limit", &limit.to_string());
-        }
-        if let Some(continue_token) = &lp.continue_token {
-            qp.append_pair("continue", continue_token);
-        }
-
-        let urlstr = qp.finish();
+        lp.validate()?;
+        let urlstr = with_query(target, &QueryParams::from(lp));
         let req = http::Request::get(urlstr);
         req.body(vec![]).map_err(Error::BuildRequest)
     }
@@ -68,8 +87,13 @@ impl Request {
     /// Watch a resource at a given version
     pub fn watch(&self, lp: &ListParams, ver: &str) -> Result<http::Request<Vec<u8>>, Error> {
         let target = format!("{}?", self.url_path);
-        let mut qp = form_urlencoded::Serializer::new(target);
         lp.validate()?;
+        if let Some(to) = &lp.timeout {
+            // https://github.com/kubernetes/kubernetes/issues/6513
+            if *to >= 295 {
+                return Err(Error::Validation("ListParams::timeout must be < 295s".into()));
+            }
+        }
         if lp.limit.is_some() {
             return Err(Error::Validation(
                 "ListParams::limit cannot be used with a watch.".into(),
@@ -80,27 +104,33 @@ impl Request {
                 "ListParams::continue_token cannot be used with a watch.".into(),
             ));
         }
-
-        qp.append_pair("watch", "true");
-        qp.append_pair("resourceVersion", ver);
-
-        // https://github.com/kubernetes/kubernetes/issues/6513
-        qp.append_pair("timeoutSeconds", &lp.timeout.unwrap_or(290).to_string());
-        if let Some(fields) = &lp.field_selector {
-            qp.append_pair("fieldSelector", fields);
-        }
-        if let Some(labels) = &lp.label_selector {
-            qp.append_pair("labelSelector", labels);
-        }
-        if lp.bookmarks {
-            qp.append_pair("allowWatchBookmarks", "true");
+        if lp.resource_version_match.is_some() {
+            return Err(Error::Validation(
+                "ListParams::resource_version_match cannot be used with a watch.".into(),
+            ));
         }
 
-        let urlstr = qp.finish();
+        let qp = QueryParams {
+            watch: true,
+            resource_version: Some(ver.into()),
+            // https://github.com/kubernetes/kubernetes/issues/6513
+            timeout_seconds: Some(lp.timeout.unwrap_or(290)),
+            allow_watch_bookmarks: lp.bookmarks,
+            ..QueryParams::from(lp)
+        };
+        let urlstr = with_query(target, &qp);
         let req = http::Request::get(urlstr);
         req.body(vec![]).map_err(Error::BuildRequest)
     }
 
+    /// Watch the metadata of a resource at a given version
+    pub fn watch_metadata(&self, lp: &ListParams, ver: &str) -> Result<http::Request<Vec<u8>>, Error> {
+        let mut req = self.watch(lp, ver)?;
+        req.headers_mut()
+            .insert(http::header::ACCEPT, http::HeaderValue::from_static(METADATA_MIME));
+        Ok(req)
+    }
+
     /// Get a single instance
     pub fn get(&self, name: &str) -> Result<http::Request<Vec<u8>>, Error> {
         let target = format!("{}/{}", self.url_path, name);
@@ -110,15 +140,37 @@ impl Request {
         req.body(vec![]).map_err(Error::BuildRequest)
     }
 
+    /// Get the metadata of a single instance
+    pub fn get_metadata(&self, name: &str) -> Result<http::Request<Vec<u8>>, Error> {
+        let mut req = self.get(name)?;
+        req.headers_mut()
+            .insert(http::header::ACCEPT, http::HeaderValue::from_static(METADATA_MIME));
+        Ok(req)
+    }
+
+    /// List the metadata of a collection of a resource
+    pub fn list_metadata(&self, lp: &ListParams) -> Result<http::Request<Vec<u8>>, Error> {
+        let mut req = self.list(lp)?;
+        req.headers_mut().insert(
+            http::header::ACCEPT,
+            http::HeaderValue::from_static(METADATA_LIST_MIME),
+        );
+        Ok(req)
+    }
+
+    /// List a collection of a resource as a [`Table`](crate::Table) of the columns `kubectl get` prints
+    pub fn list_table(&self, lp: &ListParams) -> Result<http::Request<Vec<u8>>, Error> {
+        let mut req = self.list(lp)?;
+        req.headers_mut()
+            .insert(http::header::ACCEPT, http::HeaderValue::from_static(TABLE_MIME));
+        Ok(req)
+    }
+
     /// Create an instance of a resource
     pub fn create(&self, pp: &PostParams, data: Vec<u8>) -> Result<http::Request<Vec<u8>>, Error> {
         pp.validate()?;
         let target = format!("{}?", self.url_path);
-        let mut qp = form_urlencoded::Serializer::new(target);
-        if pp.dry_run {
-            qp.append_pair("dryRun", "All");
-        }
-        let urlstr = qp.finish();
+        let urlstr = with_query(target, &QueryParams::from(pp));
         let req = http::Request::post(urlstr).header(http::header::CONTENT_TYPE, JSON_MIME);
         req.body(data).map_err(Error::BuildRequest)
     }
@@ -134,20 +186,22 @@ impl Request {
     }
 
     /// Delete a collection of a resource
+    ///
+    /// The objects are selected by the selectors and resource version of the [`ListParams`],
+    /// its limit and continue token are ignored. The [`DeleteParams`] apply to every object.
     pub fn delete_collection(
         &self,
         dp: &DeleteParams,
         lp: &ListParams,
     ) -> Result<http::Request<Vec<u8>>, Error> {
+        lp.validate()?;
         let target = format!("{}?", self.url_path);
-        let mut qp = form_urlencoded::Serializer::new(target);
-        if let Some(fields) = &lp.field_selector {
-            qp.append_pair("fieldSelector", fields);
-        }
-        if let Some(labels) = &lp.label_selector {
-            qp.append_pair("labelSelector", labels);
-        }
-        let urlstr = qp.finish();
+        let qp = QueryParams {
+            limit: None,
+            continue_token: None,
+            ..QueryParams::from(lp)
+        };
+        let urlstr = with_query(target, &qp);
         let body = serde_json::to_vec(&dp).map_err(Error::SerializeBody)?;
         let req = http::Request::delete(urlstr).header(http::header::CONTENT_TYPE, JSON_MIME);
         req.body(body).map_err(Error::BuildRequest)
@@ -164,9 +218,7 @@ impl Request {
     ) -> Result<http::Request<Vec<u8>>, Error> {
         pp.validate(patch)?;
         let target = format!("{}/{}?", self.url_path, name);
-        let mut qp = form_urlencoded::Serializer::new(target);
-        pp.populate_qp(&mut qp);
-        let urlstr = qp.finish();
+        let urlstr = with_query(target, &QueryParams::from(pp));
 
         http::Request::patch(urlstr)
             .header(http::header::ACCEPT, JSON_MIME)
@@ -185,11 +237,7 @@ impl Request {
         data: Vec<u8>,
     ) -> Result<http::Request<Vec<u8>>, Error> {
         let target = format!("{}/{}?", self.url_path, name);
-        let mut qp = form_urlencoded::Serializer::new(target);
-        if pp.dry_run {
-            qp.append_pair("dryRun", "All");
-        }
-        let urlstr = qp.finish();
+        let urlstr = with_query(target, &QueryParams::from(pp));
         let req = http::Request::put(urlstr).header(http::header::CONTENT_TYPE, JSON_MIME);
         req.body(data).map_err(Error::BuildRequest)
     }
@@ -220,9 +268,7 @@ impl Request {
     ) -> Result<http::Request<Vec<u8>>, Error> {
         pp.validate(patch)?;
         let target = format!("{}/{}/{}?", self.url_path, name, subresource_name);
-        let mut qp = form_urlencoded::Serializer::new(target);
-        pp.populate_qp(&mut qp);
-        let urlstr = qp.finish();
+        let urlstr = with_query(target, &QueryParams::from(pp));
 
         http::Request::patch(urlstr)
             .header(http::header::ACCEPT, JSON_MIME)
@@ -240,14 +286,63 @@ impl Request {
         data: Vec<u8>,
     ) -> Result<http::Request<Vec<u8>>, Error> {
         let target = format!("{}/{}/{}?", self.url_path, name, subresource_name);
-        let mut qp = form_urlencoded::Serializer::new(target);
-        if pp.dry_run {
-            qp.append_pair("dryRun", "All");
-        }
-        let urlstr = qp.finish();
+        let urlstr = with_query(target, &QueryParams::from(pp));
         let req = http::Request::put(urlstr).header(http::header::CONTENT_TYPE, JSON_MIME);
         req.body(data).map_err(Error::BuildRequest)
     }
+
+    /// Create an instance of the subresource, like an `eviction` of a pod
+    pub fn create_subresource(
+        &self,
+        subresource_name: &str,
+        name: &str,
+        pp: &PostParams,
+        data: Vec<u8>,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
+        let target = format!("{}/{}/{}?", self.url_path, name, subresource_name);
+        let urlstr = with_query(target, &QueryParams::from(pp));
+        let req = http::Request::post(urlstr).header(http::header::CONTENT_TYPE, JSON_MIME);
+        req.body(data).map_err(Error::BuildRequest)
+    }
+
+    /// Use `verb` on the `subresource` of the object `name`
+    ///
+    /// Meant for subresources found by discovery, like the custom subresources of aggregated APIs.
+    /// `create` posts `data` and `update` puts it, while `get` and `delete` send no body.
+    /// Use [`Request::patch_subresource`] for `patch`.
+    ///
+    /// Fails with [`Error::Validation`] if the subresource does not support `verb`.
+    pub fn subresource_verb(
+        &self,
+        subresource: &Subresource,
+        verb: &str,
+        name: &str,
+        pp: &PostParams,
+        data: Vec<u8>,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
+        if !subresource.supports_verb(verb) {
+            return Err(Error::Validation(format!(
+                "subresource {} does not support {}, supported verbs are: {}",
+                subresource.name,
+                verb,
+                subresource.verbs.join(", ")
+            )));
+        }
+        match verb {
+            verbs::GET => self.get_subresource(&subresource.name, name),
+            verbs::CREATE => self.create_subresource(&subresource.name, name, pp, data),
+            verbs::UPDATE => self.replace_subresource(&subresource.name, name, pp, data),
+            verbs::DELETE => {
+                let target = format!("{}/{}/{}", self.url_path, name, subresource.name);
+                let req = http::Request::delete(target);
+                req.body(vec![]).map_err(Error::BuildRequest)
+            }
+            _ => Err(Error::Validation(format!(
+                "verb {} is not supported on subresources, use a dedicated method instead",
+                verb
+            ))),
+        }
+    }
 }
 
 /// Extensive tests for Request of k8s_openapi::Resource structs
@@ -267,6 +362,28 @@ mod test {
     // NB: stable requires >= 1.17
     use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1 as apiextsv1;
 
+    #[test]
+    fn get_and_list_metadata() {
+        let url = appsv1::Deployment::url_path(&(), Some("ns"));
+        let req = Request::new(&url).get_metadata("web").unwrap();
+        assert_eq!(req.uri(), "/apis/apps/v1/namespaces/ns/deployments/web");
+        assert_eq!(req.headers()[http::header::ACCEPT], super::METADATA_MIME);
+        let req = Request::new(&url).list_metadata(&Default::default()).unwrap();
+        assert_eq!(req.uri(), "/apis/apps/v1/namespaces/ns/deployments");
+        assert_eq!(req.headers()[http::header::ACCEPT], super::METADATA_LIST_MIME);
+        let req = Request::new(&url).watch_metadata(&Default::default(), "0").unwrap();
+        assert_eq!(req.headers()[http::header::ACCEPT], super::METADATA_MIME);
+    }
+
+    #[test]
+    fn list_table() {
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let lp = ListParams::default().limit(10);
+        let req = Request::new(&url).list_table(&lp).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods?&limit=10");
+        assert_eq!(req.headers()[http::header::ACCEPT], super::TABLE_MIME);
+    }
+
     // TODO: fixturize these tests
     #[test]
     fn api_url_secret() {
@@ -368,7 +485,7 @@ mod test {
 
     /// -----------------------------------------------------------------
     /// Tests that the misc mappings are also sensible
-    use crate::params::{DeleteParams, ListParams, Patch, PatchParams};
+    use crate::params::{DeleteParams, ListParams, Patch, PatchParams, QueryParams, VersionMatch};
 
     #[test]
     fn list_path() {
@@ -432,6 +549,149 @@ mod test {
         );
     }
 
+    #[test]
+    fn delete_collection_params() {
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let lp = ListParams::default()
+            .labels("app=myapp")
+            .resource_version("100")
+            .limit(10);
+        let dp = DeleteParams::background().grace_period(5).dry_run();
+        let req = Request::new(url).delete_collection(&dp, &lp).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/pods?&resourceVersion=100&labelSelector=app%3Dmyapp"
+        );
+        let body: serde_json::Value = serde_json::from_slice(req.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "dryRun": ["All"],
+                "gracePeriodSeconds": 5,
+                "propagationPolicy": "Background",
+            })
+        );
+    }
+
+    #[test]
+    fn create_field_manager() {
+        let url = corev1::ConfigMap::url_path(&(), Some("ns"));
+        let pp = PostParams {
+            field_manager: Some("kube".into()),
+            ..Default::default()
+        };
+        let req = Request::new(url).create(&pp, vec![]).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/configmaps?&fieldManager=kube");
+    }
+
+    #[test]
+    fn field_validation() {
+        use crate::params::ValidationDirective;
+        let url = corev1::ConfigMap::url_path(&(), Some("ns"));
+        let pp = PostParams {
+            field_validation: Some(ValidationDirective::Strict),
+            ..Default::default()
+        };
+        let req = Request::new(&url).replace("settings", &pp, vec![]).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/configmaps/settings?&fieldValidation=Strict"
+        );
+        let pp = PatchParams::apply("kube").validation(ValidationDirective::Warn);
+        let req = Request::new(url)
+            .patch("settings", &pp, &Patch::Apply(()))
+            .unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/configmaps/settings?&fieldManager=kube&fieldValidation=Warn"
+        );
+    }
+
+    #[test]
+    fn custom_url_with_query() {
+        let req = Request::new(corev1::Pod::url_path(&(), Some("ns")));
+        let qp = QueryParams::default()
+            .resource_version("100")
+            .resource_version_match(VersionMatch::NotOlderThan)
+            .fields("spec.nodeName=node-1")
+            .limit(10);
+        assert_eq!(
+            req.url(None, None, &qp).unwrap(),
+            "/api/v1/namespaces/ns/pods?&resourceVersion=100&resourceVersionMatch=NotOlderThan&fieldSelector=spec.nodeName%3Dnode-1&limit=10"
+        );
+        let qp = QueryParams::default().dry_run().field_manager("kube");
+        assert_eq!(
+            req.url(Some("web"), Some("status"), &qp).unwrap(),
+            "/api/v1/namespaces/ns/pods/web/status?&dryRun=All&fieldManager=kube"
+        );
+    }
+
+    #[test]
+    fn query_validation() {
+        let invalid = [
+            QueryParams::default().watch().limit(10),
+            QueryParams::default().watch().continue_token("abc"),
+            QueryParams::default().watch().timeout(300),
+            QueryParams::default().continue_token("abc").resource_version("10"),
+            QueryParams::default().resource_version_match(VersionMatch::Exact),
+            QueryParams::default()
+                .resource_version("0")
+                .resource_version_match(VersionMatch::Exact),
+            QueryParams::default().field_manager(&"a".repeat(129)),
+        ];
+        for qp in &invalid {
+            assert!(qp.validate().is_err(), "{:?} should be invalid", qp);
+        }
+        assert!(QueryParams::default()
+            .watch()
+            .resource_version("10")
+            .timeout(290)
+            .bookmarks()
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn list_resource_version() {
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let lp = ListParams::default().resource_version("0");
+        let req = Request::new(&url).list(&lp).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods?&resourceVersion=0");
+        let lp = ListParams::default()
+            .resource_version("100")
+            .resource_version_match(VersionMatch::Exact)
+            .limit(10);
+        let req = Request::new(&url).list(&lp).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/pods?&resourceVersion=100&resourceVersionMatch=Exact&limit=10"
+        );
+
+        let invalid = [
+            ListParams::default().resource_version_match(VersionMatch::NotOlderThan),
+            ListParams::default()
+                .resource_version("0")
+                .resource_version_match(VersionMatch::Exact),
+            ListParams::default().resource_version("100").continue_token("abc"),
+        ];
+        for lp in &invalid {
+            assert!(Request::new(&url).list(lp).is_err(), "{:?} should be invalid", lp);
+        }
+        let lp = ListParams::default()
+            .resource_version("100")
+            .resource_version_match(VersionMatch::NotOlderThan);
+        assert!(Request::new(&url).watch(&lp, "100").is_err());
+    }
+
+    #[test]
+    fn long_timeouts_only_fail_watches() {
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let lp = ListParams::default().timeout(600);
+        assert!(Request::new(&url).list(&lp).is_ok());
+        assert!(Request::new(&url).watch(&lp, "0").is_err());
+        assert!(Request::new(&url).watch(&lp.timeout(290), "0").is_ok());
+    }
+
     #[test]
     fn namespace_path() {
         let url = corev1::Namespace::url_path(&(), None);
@@ -528,6 +788,38 @@ mod test {
         assert_eq!(req.uri(), "/api/v1/nodes/mynode/scale?");
         assert_eq!(req.method(), "PUT");
     }
+    #[test]
+    fn create_eviction_path() {
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let pp = PostParams::default();
+        let req = Request::new(url)
+            .create_subresource("eviction", "mypod", &pp, vec![])
+            .unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods/mypod/eviction?");
+        assert_eq!(req.method(), "POST");
+    }
+    #[test]
+    fn discovered_subresource_verbs() {
+        use crate::discovery::Subresource;
+        let url = "/apis/certificates.k8s.io/v1/certificatesigningrequests";
+        let approval = Subresource::new("approval", "CertificateSigningRequest", &["get", "update"]);
+        let pp = PostParams::default();
+        let req = Request::new(url)
+            .subresource_verb(&approval, "update", "mycsr", &pp, vec![])
+            .unwrap();
+        assert_eq!(
+            req.uri(),
+            "/apis/certificates.k8s.io/v1/certificatesigningrequests/mycsr/approval?"
+        );
+        assert_eq!(req.method(), "PUT");
+        let req = Request::new(url)
+            .subresource_verb(&approval, "get", "mycsr", &pp, vec![])
+            .unwrap();
+        assert_eq!(req.method(), "GET");
+        assert!(Request::new(url)
+            .subresource_verb(&approval, "create", "mycsr", &pp, vec![])
+            .is_err());
+    }
 
     // TODO: reinstate if we get scoping in trait
     //#[test]
@@ -1,12 +1,20 @@
 //! Request builder types and parameters for subresources
+use chrono::{DateTime, SecondsFormat, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use std::fmt::Debug;
 
 use crate::{
-    params::{DeleteParams, PostParams},
+    params::{DeleteParams, PostParams, QueryParams},
     request::{Error, Request, JSON_MIME},
+    Resource,
 };
 
-pub use k8s_openapi::api::autoscaling::v1::{Scale, ScaleSpec, ScaleStatus};
+pub use k8s_openapi::api::{
+    authentication::v1::{BoundObjectReference, TokenRequest, TokenRequestSpec, TokenRequestStatus},
+    autoscaling::v1::{Scale, ScaleSpec, ScaleStatus},
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+#[cfg(feature = "ws")] use serde::{Deserialize, Serialize};
 
 // ----------------------------------------------------------------------------
 // Log subresource
@@ -30,6 +38,9 @@ pub struct LogParams {
     /// If this value precedes the time a pod was started, only logs since the pod start will be returned.
     /// If this value is in the future, no logs will be returned. Only one of sinceSeconds or sinceTime may be specified.
     pub since_seconds: Option<i64>,
+    /// An absolute time from which to show logs, sent with a precision of seconds.
+    /// Only one of sinceSeconds or sinceTime may be specified.
+    pub since_time: Option<DateTime<Utc>>,
     /// If set, the number of lines from the end of the logs to show.
     /// If not specified, logs are shown from the creation of the container or sinceSeconds or sinceTime
     pub tail_lines: Option<i64>,
@@ -67,6 +78,10 @@ impl Request {
             qp.append_pair("sinceSeconds", &ss.to_string());
         }
 
+        if let Some(st) = &lp.since_time {
+            qp.append_pair("sinceTime", &st.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+
         if let Some(tl) = &lp.tail_lines {
             qp.append_pair("tailLines", &tl.to_string());
         }
@@ -81,6 +96,49 @@ impl Request {
     }
 }
 
+/// A line of container logs
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    /// When the line was logged, if the logs were requested with [`LogParams::timestamps`]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The logged line, without the timestamp and the line ending
+    pub message: String,
+}
+
+impl LogLine {
+    /// Parses a line of logs, splitting off its timestamp if the logs were requested with `timestamps`
+    ///
+    /// The kubelet prefixes lines with an RFC3339 timestamp and a space. Lines without a valid prefix,
+    /// like the end of a line cut off by [`LogParams::limit_bytes`], are kept whole.
+    pub fn parse(line: &str, timestamps: bool) -> Self {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if timestamps {
+            if let Some((prefix, message)) = line.split_once(' ') {
+                if let Ok(timestamp) = DateTime::parse_from_rfc3339(prefix) {
+                    return Self {
+                        timestamp: Some(timestamp.with_timezone(&Utc)),
+                        message: message.into(),
+                    };
+                }
+            }
+        }
+        Self {
+            timestamp: None,
+            message: line.into(),
+        }
+    }
+
+    /// Parses the message as JSON, for containers that log structured JSON objects
+    ///
+    /// Returns `None` for messages that are not JSON objects.
+    pub fn json(&self) -> Option<serde_json::Value> {
+        if !self.message.trim_start().starts_with('{') {
+            return None;
+        }
+        serde_json::from_str(&self.message).ok()
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Eviction subresource
 // ----------------------------------------------------------------------------
@@ -95,8 +153,22 @@ pub struct EvictParams {
 }
 
 impl Request {
-    /// Create an eviction
+    /// Create a `policy/v1` eviction
     pub fn evict(&self, name: &str, ep: &EvictParams) -> Result<http::Request<Vec<u8>>, Error> {
+        self.evict_with_version(name, ep, "policy/v1")
+    }
+
+    /// Create a `policy/v1beta1` eviction, for clusters older than Kubernetes 1.22
+    pub fn evict_v1beta1(&self, name: &str, ep: &EvictParams) -> Result<http::Request<Vec<u8>>, Error> {
+        self.evict_with_version(name, ep, "policy/v1beta1")
+    }
+
+    fn evict_with_version(
+        &self,
+        name: &str,
+        ep: &EvictParams,
+        api_version: &str,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
         let target = format!("{}/{}/eviction?", self.url_path, name);
         // This is technically identical to Request::create, but different url
         let pp = &ep.post_options;
@@ -108,7 +180,9 @@ impl Request {
         let urlstr = qp.finish();
         // eviction body parameters are awkward, need metadata with name
         let data = serde_json::to_vec(&serde_json::json!({
-            "delete_options": ep.delete_options,
+            "apiVersion": api_version,
+            "kind": "Eviction",
+            "deleteOptions": ep.delete_options,
             "metadata": { "name": name }
         }))
         .map_err(Error::SerializeBody)?;
@@ -117,6 +191,126 @@ impl Request {
     }
 }
 
+// ----------------------------------------------------------------------------
+// TokenRequest subresource
+// ----------------------------------------------------------------------------
+
+/// Params for requesting a bound token for a ServiceAccount
+#[derive(Default, Clone, Debug)]
+pub struct TokenRequestParams {
+    /// Intended audiences of the token. Defaults to the audiences of the apiserver.
+    pub audiences: Vec<String>,
+    /// Requested duration of validity of the token in seconds.
+    ///
+    /// The apiserver may issue a token with a different validity, and requires at least 10 minutes.
+    pub expiration_seconds: Option<i64>,
+    /// Object the token is bound to. The token is invalidated when the object is deleted.
+    pub bound_object_ref: Option<BoundObjectReference>,
+    /// Minimum validity of the issued token in seconds, from the time it is received.
+    ///
+    /// The apiserver may shorten the requested expiration, such as with `--service-account-max-token-expiration`,
+    /// so a token that expires sooner than this is rejected rather than used. Issued tokens that already expired
+    /// are always rejected.
+    pub min_validity_seconds: Option<i64>,
+    /// How the http post should occur
+    pub post_options: PostParams,
+}
+
+impl TokenRequestParams {
+    /// Request a token for the given audiences
+    #[must_use]
+    pub fn audiences<S: Into<String>>(mut self, audiences: impl IntoIterator<Item = S>) -> Self {
+        self.audiences = audiences.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Request a token valid for the given number of seconds
+    #[must_use]
+    pub fn expiration_seconds(mut self, seconds: i64) -> Self {
+        self.expiration_seconds = Some(seconds);
+        self
+    }
+
+    /// Bind the token to an object, such as the Pod using it
+    #[must_use]
+    pub fn bound_object_ref(mut self, object: BoundObjectReference) -> Self {
+        self.bound_object_ref = Some(object);
+        self
+    }
+
+    /// Bind the token to `object`, which the apiserver supports for Pods and Secrets
+    ///
+    /// The reference includes the `uid` of the object, so the token is not valid for a recreated object of the same name.
+    #[must_use]
+    pub fn bound_to<K: Resource<DynamicType = ()>>(self, object: &K) -> Self {
+        self.bound_object_ref(BoundObjectReference {
+            api_version: Some(K::api_version(&()).into_owned()),
+            kind: Some(K::kind(&()).into_owned()),
+            name: object.meta().name.clone(),
+            uid: object.meta().uid.clone(),
+        })
+    }
+
+    /// Reject issued tokens that are valid for less than the given number of seconds
+    #[must_use]
+    pub fn min_validity_seconds(mut self, seconds: i64) -> Self {
+        self.min_validity_seconds = Some(seconds);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if self.audiences.iter().any(String::is_empty) {
+            return Err(Error::Validation(
+                "TokenRequestParams::audiences must not be empty strings".into(),
+            ));
+        }
+        if let Some(seconds) = self.expiration_seconds {
+            if seconds < 600 {
+                return Err(Error::Validation(
+                    "TokenRequestParams::expiration_seconds must be at least 600".into(),
+                ));
+            }
+        }
+        if let Some(bound) = &self.bound_object_ref {
+            if bound.name.is_none() || bound.kind.is_none() {
+                return Err(Error::Validation(
+                    "TokenRequestParams::bound_object_ref requires a kind and a name".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Request {
+    /// Request a bound token for a ServiceAccount
+    pub fn create_token_request(
+        &self,
+        name: &str,
+        tp: &TokenRequestParams,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
+        let pp = &tp.post_options;
+        pp.validate()?;
+        tp.validate()?;
+        let urlstr = self.url(Some(name), Some("token"), &QueryParams::from(pp))?;
+        let token_request = TokenRequest {
+            metadata: ObjectMeta {
+                name: Some(name.into()),
+                ..ObjectMeta::default()
+            },
+            spec: TokenRequestSpec {
+                audiences: tp.audiences.clone(),
+                expiration_seconds: tp.expiration_seconds,
+                bound_object_ref: tp.bound_object_ref.clone(),
+            },
+            status: None,
+        };
+        let data = serde_json::to_vec(&token_request).map_err(Error::SerializeBody)?;
+        let req = http::Request::post(urlstr).header(http::header::CONTENT_TYPE, JSON_MIME);
+        req.body(data).map_err(Error::BuildRequest)
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Attach subresource
 // ----------------------------------------------------------------------------
@@ -145,7 +339,7 @@ pub struct AttachParams {
     pub stderr: bool,
     /// Allocate TTY. Defaults to `false`.
     ///
-    /// NOTE: Terminal resizing is not implemented yet.
+    /// Call [`AttachedProcess::terminal_size`](https://docs.rs/kube/*/kube/api/struct.AttachedProcess.html#method.terminal_size) to resize the terminal.
     pub tty: bool,
 
     /// The maximum amount of bytes that can be written to the internal `stdin`
@@ -166,6 +360,26 @@ pub struct AttachParams {
     ///
     /// This is not sent to the server.
     pub max_stderr_buf_size: Option<usize>,
+    /// Keep the connection open when the attached process is dropped, until the process exits.
+    /// Defaults to `false`, closing the connection on drop.
+    ///
+    /// This lets input written to `stdin` be delivered after the attached process is dropped.
+    /// This is not sent to the server.
+    pub keep_alive: bool,
+}
+
+/// The size of the terminal of a process attached with [`AttachParams::tty`]
+///
+/// Sent on the resize channel, e.g. when the local terminal receives `SIGWINCH`.
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TerminalSize {
+    /// Number of columns
+    pub width: u16,
+    /// Number of rows
+    pub height: u16,
 }
 
 #[cfg(feature = "ws")]
@@ -182,6 +396,7 @@ impl Default for AttachParams {
             max_stdin_buf_size: None,
             max_stdout_buf_size: None,
             max_stderr_buf_size: None,
+            keep_alive: false,
         }
     }
 }
@@ -248,6 +463,12 @@ impl AttachParams {
         self
     }
 
+    /// Set `keep_alive` field.
+    pub fn keep_alive(mut self, enable: bool) -> Self {
+        self.keep_alive = enable;
+        self
+    }
+
     fn validate(&self) -> Result<(), Error> {
         if !self.stdin && !self.stdout && !self.stderr {
             return Err(Error::Validation(
@@ -332,6 +553,123 @@ impl Request {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Portforward subresource
+// ----------------------------------------------------------------------------
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+impl Request {
+    /// Forward ports of a pod
+    ///
+    /// Every port is given two channels, so at most 128 ports can be forwarded at once.
+    pub fn portforward(&self, name: &str, ports: &[u16]) -> Result<http::Request<Vec<u8>>, Error> {
+        if ports.is_empty() {
+            return Err(Error::Validation("at least one port must be forwarded".into()));
+        }
+        if ports.len() > 128 {
+            return Err(Error::Validation(
+                "at most 128 ports can be forwarded at once".into(),
+            ));
+        }
+
+        let target = format!("{}/{}/portforward?", self.url_path, name);
+        let mut qp = form_urlencoded::Serializer::new(target);
+        let ports = ports.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        qp.append_pair("ports", &ports.join(","));
+
+        let req = http::Request::get(qp.finish());
+        req.body(vec![]).map_err(Error::BuildRequest)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Node proxy subresource
+// ----------------------------------------------------------------------------
+// Characters that may not appear in a segment of a uri path, see RFC 3986
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+fn encode_segment(segment: &str) -> String {
+    utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
+
+impl Request {
+    /// Get `path` from the kubelet of a node, through the proxy of the apiserver
+    ///
+    /// Paths like `stats/summary`, `metrics` or `logs/` are relative to the kubelet, leading slashes are
+    /// ignored. The segments of `path` are percent-encoded, and paths with `..` segments are rejected.
+    pub fn node_proxy(&self, name: &str, path: &str) -> Result<http::Request<Vec<u8>>, Error> {
+        let path = path.trim_start_matches('/');
+        if path.split('/').any(|segment| segment == "..") {
+            return Err(Error::Validation(format!("node proxy path {} leaves the kubelet", path)));
+        }
+        let path = path.split('/').map(encode_segment).collect::<Vec<_>>().join("/");
+        let target = format!("{}/{}/proxy/{}", self.url_path, encode_segment(name), path);
+        let req = http::Request::get(target);
+        req.body(vec![]).map_err(Error::BuildRequest)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Proxy subresource
+// ----------------------------------------------------------------------------
+impl Request {
+    /// Send `request` to a pod or service through the proxy of the apiserver
+    ///
+    /// The method, headers, body, and the path and query of the uri of `request` are passed on,
+    /// so the uri only needs a path like `/healthz?verbose`, encoded as usual for uris.
+    /// `port` is the number or name of the port, optionally prefixed with `https:` to proxy over TLS.
+    /// Paths with `..` segments are rejected.
+    ///
+    /// The name, scheme and port are percent-encoded into the path of the apiserver,
+    /// as `{scheme}:{name}:{port}` like `https:prometheus:web`.
+    pub fn proxy(
+        &self,
+        name: &str,
+        port: Option<&str>,
+        request: http::Request<Vec<u8>>,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
+        let (parts, body) = request.into_parts();
+        let path = parts.uri.path();
+        if path.split('/').any(|segment| segment == "..") {
+            return Err(Error::Validation(format!("proxy path {} leaves the target", path)));
+        }
+        let name = encode_segment(name);
+        let target_name = match port.map(|port| port.split_once(':').unwrap_or(("", port))) {
+            Some((scheme, port)) if port.is_empty() || port.contains(':') => {
+                return Err(Error::Validation(format!("invalid proxy port {}:{}", scheme, port)));
+            }
+            Some(("", port)) => format!("{}:{}", name, encode_segment(port)),
+            Some((scheme, port)) => format!("{}:{}:{}", encode_segment(scheme), name, encode_segment(port)),
+            None => name,
+        };
+        let mut target = format!("{}/{}/proxy{}", self.url_path, target_name, path);
+        if let Some(query) = parts.uri.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let mut req = http::Request::builder().method(parts.method).uri(target);
+        for (key, value) in parts.headers.iter() {
+            // The host is the apiserver's, not the one of the proxied request
+            if key != http::header::HOST {
+                req = req.header(key, value);
+            }
+        }
+        req.body(body).map_err(Error::BuildRequest)
+    }
+}
+
 // ----------------------------------------------------------------------------
 // tests
 // ----------------------------------------------------------------------------
@@ -343,7 +681,7 @@ mod test {
     use k8s::{apps::v1 as appsv1, core::v1 as corev1};
     use k8s_openapi::api as k8s;
 
-    use crate::subresource::LogParams;
+    use crate::subresource::{LogLine, LogParams, TokenRequest, TokenRequestParams};
 
     #[test]
     fn logs_all_params() {
@@ -355,10 +693,138 @@ mod test {
             pretty: true,
             previous: true,
             since_seconds: Some(3600),
+            since_time: None,
             tail_lines: Some(4096),
             timestamps: true,
         };
         let req = Request::new(url).logs("mypod", &lp).unwrap();
         assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods/mypod/log?&container=nginx&follow=true&limitBytes=10485760&pretty=true&previous=true&sinceSeconds=3600&tailLines=4096&timestamps=true");
     }
+
+    #[test]
+    fn logs_since_time() {
+        use chrono::{DateTime, Utc};
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let since: DateTime<Utc> = "2021-11-02T10:00:05.250Z".parse().unwrap();
+        let lp = LogParams {
+            since_time: Some(since),
+            ..LogParams::default()
+        };
+        let req = Request::new(url).logs("mypod", &lp).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/pods/mypod/log?&sinceTime=2021-11-02T10%3A00%3A05Z"
+        );
+    }
+
+    #[test]
+    fn node_proxy_paths() {
+        let url = corev1::Node::url_path(&(), None);
+        let req = Request::new(&url).node_proxy("node-1", "/stats/summary").unwrap();
+        assert_eq!(req.uri(), "/api/v1/nodes/node-1/proxy/stats/summary");
+        let req = Request::new(&url).node_proxy("node-1", "logs/").unwrap();
+        assert_eq!(req.uri(), "/api/v1/nodes/node-1/proxy/logs/");
+        let req = Request::new(&url).node_proxy("node-1", "logs/pods/a b?.log").unwrap();
+        assert_eq!(req.uri(), "/api/v1/nodes/node-1/proxy/logs/pods/a%20b%3F.log");
+        assert!(Request::new(&url).node_proxy("node-1", "logs/../configz").is_err());
+    }
+
+    #[test]
+    fn proxy_requests() {
+        let url = corev1::Service::url_path(&(), Some("ns"));
+        let inner = http::Request::post("/api/v1/query?query=up%7Bjob%3D%22x%22%7D")
+            .header(http::header::HOST, "prometheus")
+            .header(http::header::CONTENT_TYPE, "text/plain")
+            .body(b"data".to_vec())
+            .unwrap();
+        let req = Request::new(&url).proxy("prometheus", Some("https:web"), inner).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/services/https:prometheus:web/proxy/api/v1/query?query=up%7Bjob%3D%22x%22%7D"
+        );
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.headers()[http::header::CONTENT_TYPE], "text/plain");
+        assert!(req.headers().get(http::header::HOST).is_none());
+        assert_eq!(req.body(), b"data");
+
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let inner = http::Request::get("http://ignored/").body(vec![]).unwrap();
+        let req = Request::new(&url).proxy("web-0", None, inner).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods/web-0/proxy/");
+        let inner = http::Request::get("/metrics").body(vec![]).unwrap();
+        let req = Request::new(&url).proxy("web-0", Some("8080"), inner).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods/web-0:8080/proxy/metrics");
+        let inner = http::Request::get("/").body(vec![]).unwrap();
+        assert!(Request::new(&url).proxy("web-0", Some("https:"), inner).is_err());
+        let inner = http::Request::get("/static/../../secrets").body(vec![]).unwrap();
+        assert!(Request::new(&url).proxy("web-0", Some("80"), inner).is_err());
+    }
+
+    #[test]
+    fn log_line_timestamps() {
+        let line = LogLine::parse("2021-11-29T09:12:45.123456789Z {\"level\":\"info\"}\r", true);
+        let timestamp = line.timestamp.unwrap();
+        assert_eq!(timestamp.to_rfc3339(), "2021-11-29T09:12:45.123456789+00:00");
+        assert_eq!(line.message, r#"{"level":"info"}"#);
+        assert_eq!(line.json().unwrap()["level"], "info");
+
+        let cut = LogLine::parse("45.123Z partial line", true);
+        assert_eq!(cut.timestamp, None);
+        assert_eq!(cut.message, "45.123Z partial line");
+        assert_eq!(cut.json(), None);
+        assert_eq!(LogLine::parse("2021-11-29T09:12:45Z plain", false).timestamp, None);
+    }
+
+    #[test]
+    fn token_request_body() {
+        let url = corev1::ServiceAccount::url_path(&(), Some("ns"));
+        let tp = TokenRequestParams::default()
+            .audiences(["vault"])
+            .expiration_seconds(3600);
+        let req = Request::new(url).create_token_request("builder", &tp).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/serviceaccounts/builder/token?");
+        let body: TokenRequest = serde_json::from_slice(req.body()).unwrap();
+        assert_eq!(body.metadata.name.as_deref(), Some("builder"));
+        assert_eq!(body.spec.audiences, vec!["vault".to_string()]);
+        assert_eq!(body.spec.expiration_seconds, Some(3600));
+    }
+
+    #[test]
+    fn token_request_bound_to_pod() {
+        let url = corev1::ServiceAccount::url_path(&(), Some("ns"));
+        let mut pod = corev1::Pod::default();
+        pod.metadata.name = Some("web".into());
+        pod.metadata.uid = Some("1234".into());
+        let tp = TokenRequestParams::default().audiences(["vault"]).bound_to(&pod);
+        let req = Request::new(&url).create_token_request("builder", &tp).unwrap();
+        let body: TokenRequest = serde_json::from_slice(req.body()).unwrap();
+        let bound = body.spec.bound_object_ref.unwrap();
+        assert_eq!(bound.api_version.as_deref(), Some("v1"));
+        assert_eq!(bound.kind.as_deref(), Some("Pod"));
+        assert_eq!(bound.name.as_deref(), Some("web"));
+        assert_eq!(bound.uid.as_deref(), Some("1234"));
+
+        let mut dry = TokenRequestParams::default();
+        dry.post_options.dry_run = true;
+        dry.post_options.field_manager = Some("vault-injector".into());
+        let req = Request::new(&url).create_token_request("builder", &dry).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/serviceaccounts/builder/token?&dryRun=All&fieldManager=vault-injector"
+        );
+
+        let short = TokenRequestParams::default().expiration_seconds(60);
+        assert!(Request::new(&url).create_token_request("builder", &short).is_err());
+        let unnamed = TokenRequestParams::default().bound_to(&corev1::Pod::default());
+        assert!(Request::new(&url).create_token_request("builder", &unnamed).is_err());
+    }
+
+    #[cfg(feature = "ws")]
+    #[test]
+    fn portforward_ports() {
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let req = Request::new(&url).portforward("web", &[80, 8443]).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods/web/portforward?&ports=80%2C8443");
+        assert!(Request::new(&url).portforward("web", &[]).is_err());
+    }
 }
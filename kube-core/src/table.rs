@@ -0,0 +1,120 @@
+//! Tables of objects with the columns defined by the apiserver, as printed by `kubectl get`
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::metadata::{ListMeta, PartialObjectMetadata, TypeMeta};
+
+/// A list of objects rendered as a table by the apiserver, as a `meta.k8s.io/v1` `Table`
+///
+/// The columns are the ones `kubectl get` prints: the printer columns of built-in kinds, and the
+/// `additionalPrinterColumns` of custom resources.
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Table {
+    /// The type fields, `meta.k8s.io/v1` `Table`
+    #[serde(flatten, default)]
+    pub types: Option<TypeMeta>,
+    /// The list metadata, with the `resourceVersion` and `continue` token of the list
+    #[serde(default)]
+    pub metadata: ListMeta,
+    /// The columns of the table
+    #[serde(default)]
+    pub column_definitions: Vec<TableColumnDefinition>,
+    /// The rows of the table, one per object
+    #[serde(default)]
+    pub rows: Vec<TableRow>,
+}
+
+impl Table {
+    /// The index of the column `name`, such as `Ready` for pods
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.column_definitions
+            .iter()
+            .position(|column| column.name == name)
+    }
+}
+
+/// A column of a [`Table`]
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct TableColumnDefinition {
+    /// The header of the column
+    pub name: String,
+    /// The OpenAPI type of the cells, such as `string`, `integer` or `date`
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// The OpenAPI format of the cells, such as `name` for the column of the object names
+    #[serde(default)]
+    pub format: String,
+    /// A description of the column
+    #[serde(default)]
+    pub description: String,
+    /// Columns with a priority above `0` are only printed by `kubectl get -o wide`
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// A row of a [`Table`]
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq)]
+pub struct TableRow {
+    /// The cells of the row, in the order of the [`Table::column_definitions`]
+    #[serde(default)]
+    pub cells: Vec<Value>,
+    /// Conditions of the row, such as `Completed` for finished pods
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<TableRowCondition>,
+    /// The metadata of the object of the row, unless it was left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<PartialObjectMetadata>,
+}
+
+/// A condition of a [`TableRow`]
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct TableRowCondition {
+    /// The type of the condition, such as `Completed`
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// The status of the condition, `True`, `False` or `Unknown`
+    pub status: String,
+    /// A machine readable reason for the status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// A human readable message about the status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Table;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_pod_table() {
+        let table: Table = serde_json::from_value(json!({
+            "kind": "Table",
+            "apiVersion": "meta.k8s.io/v1",
+            "metadata": { "resourceVersion": "42" },
+            "columnDefinitions": [
+                { "name": "Name", "type": "string", "format": "name", "description": "Name", "priority": 0 },
+                { "name": "Ready", "type": "string", "format": "", "description": "", "priority": 0 },
+                { "name": "IP", "type": "string", "format": "", "description": "", "priority": 1 },
+            ],
+            "rows": [{
+                "cells": ["blog", "1/1", "10.0.0.1"],
+                "object": {
+                    "kind": "PartialObjectMetadata",
+                    "apiVersion": "meta.k8s.io/v1",
+                    "metadata": { "name": "blog", "namespace": "apps" },
+                },
+            }],
+        }))
+        .unwrap();
+        assert_eq!(table.types.as_ref().unwrap().kind, "Table");
+        let ready = table.column("Ready").unwrap();
+        assert_eq!(table.rows[0].cells[ready], "1/1");
+        assert_eq!(table.column_definitions[2].priority, 1);
+        let object = table.rows[0].object.as_ref().unwrap();
+        assert_eq!(object.metadata.namespace.as_deref(), Some("apps"));
+        assert!(table.rows[0].conditions.is_empty());
+    }
+}
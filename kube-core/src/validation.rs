@@ -0,0 +1,323 @@
+//! Client-side validation of object metadata
+//!
+//! Mirrors the rules the apiserver applies to names, labels and annotations (see `k8s.io/apimachinery/pkg/util/validation`),
+//! so that invalid metadata can be reported before it is submitted.
+//! The validators return a list of problems, which is empty when the value is valid.
+use std::collections::BTreeMap;
+
+use crate::metadata::ObjectMeta;
+use thiserror::Error;
+
+/// Maximum length of a DNS-1123 label, such as a namespace name
+pub const DNS1123_LABEL_MAX_LENGTH: usize = 63;
+/// Maximum length of a DNS-1123 subdomain, such as most object names
+pub const DNS1123_SUBDOMAIN_MAX_LENGTH: usize = 253;
+/// Maximum length of the name part of a qualified name, such as a label key
+pub const QUALIFIED_NAME_MAX_LENGTH: usize = 63;
+/// Maximum length of a label value
+pub const LABEL_VALUE_MAX_LENGTH: usize = 63;
+/// Maximum total size of the keys and values of the annotations of an object
+pub const TOTAL_ANNOTATION_SIZE_LIMIT: usize = 256 * 1024;
+
+const DNS1123_LABEL_ERROR: &str =
+    "a lowercase RFC 1123 label must consist of lower case alphanumeric characters or '-', \
+     and must start and end with an alphanumeric character (e.g. 'my-name', or '123-abc')";
+const DNS1123_SUBDOMAIN_ERROR: &str =
+    "a lowercase RFC 1123 subdomain must consist of lower case alphanumeric characters, \
+     '-' or '.', and must start and end with an alphanumeric character (e.g. 'example.com')";
+const QUALIFIED_NAME_ERROR: &str = "name part must consist of alphanumeric characters, '-', '_' or '.', \
+     and must start and end with an alphanumeric character (e.g. 'MyName', or 'my.name', or '123-abc')";
+const LABEL_VALUE_ERROR: &str = "a valid label must be an empty string or consist of alphanumeric characters, \
+     '-', '_' or '.', and must start and end with an alphanumeric character (e.g. 'MyValue', or 'my_value', or '12345')";
+
+/// An invalid metadata field
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("{field}: Invalid value: {value:?}: {message}")]
+pub struct ValidationError {
+    /// Path of the invalid field, such as `metadata.labels`
+    pub field: String,
+    /// The invalid value
+    pub value: String,
+    /// Why the value is invalid
+    pub message: String,
+}
+
+impl ValidationError {
+    pub(crate) fn new(field: &str, value: &str, message: String) -> Self {
+        Self {
+            field: field.to_string(),
+            value: value.to_string(),
+            message,
+        }
+    }
+}
+
+fn max_length(length: usize) -> String {
+    format!("must be no more than {} characters", length)
+}
+
+/// Validates a DNS-1123 label (RFC 1123), as used for the names of namespaces and services
+pub fn validate_dns1123_label(value: &str) -> Vec<String> {
+    let mut errors = vec![];
+    if value.len() > DNS1123_LABEL_MAX_LENGTH {
+        errors.push(max_length(DNS1123_LABEL_MAX_LENGTH));
+    }
+    if !is_dns1123_label(value) {
+        errors.push(DNS1123_LABEL_ERROR.to_string());
+    }
+    errors
+}
+
+/// Validates a DNS-1123 subdomain (RFC 1123), as used for the names of most objects
+pub fn validate_dns1123_subdomain(value: &str) -> Vec<String> {
+    let mut errors = vec![];
+    if value.len() > DNS1123_SUBDOMAIN_MAX_LENGTH {
+        errors.push(max_length(DNS1123_SUBDOMAIN_MAX_LENGTH));
+    }
+    if !value.split('.').all(is_dns1123_label) {
+        errors.push(DNS1123_SUBDOMAIN_ERROR.to_string());
+    }
+    errors
+}
+
+/// Validates a qualified name, as used for label and annotation keys
+///
+/// A qualified name is a name part with an optional DNS-1123 subdomain prefix, such as `example.com/MyName`.
+pub fn validate_qualified_name(value: &str) -> Vec<String> {
+    let mut errors = vec![];
+    let name = match value.split('/').collect::<Vec<_>>()[..] {
+        [name] => name,
+        [prefix, name] => {
+            if prefix.is_empty() {
+                errors.push("prefix part must be non-empty".to_string());
+            } else {
+                errors.extend(
+                    validate_dns1123_subdomain(prefix)
+                        .into_iter()
+                        .map(|e| format!("prefix part {}", e)),
+                );
+            }
+            name
+        }
+        _ => {
+            return vec![format!(
+                "a qualified name {} with an optional DNS subdomain prefix and '/' (e.g. 'example.com/MyName')",
+                QUALIFIED_NAME_ERROR.trim_start_matches("name part ")
+            )];
+        }
+    };
+    if name.is_empty() {
+        errors.push("name part must be non-empty".to_string());
+    } else if name.len() > QUALIFIED_NAME_MAX_LENGTH {
+        errors.push(format!("name part {}", max_length(QUALIFIED_NAME_MAX_LENGTH)));
+    }
+    if !name.is_empty() && !is_qualified_name_part(name) {
+        errors.push(QUALIFIED_NAME_ERROR.to_string());
+    }
+    errors
+}
+
+/// Validates a label value
+pub fn validate_label_value(value: &str) -> Vec<String> {
+    let mut errors = vec![];
+    if value.len() > LABEL_VALUE_MAX_LENGTH {
+        errors.push(max_length(LABEL_VALUE_MAX_LENGTH));
+    }
+    if !value.is_empty() && !is_qualified_name_part(value) {
+        errors.push(LABEL_VALUE_ERROR.to_string());
+    }
+    errors
+}
+
+/// Validates the keys and values of labels
+pub fn validate_labels(labels: &BTreeMap<String, String>, field: &str) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    for (key, value) in labels {
+        for message in validate_qualified_name(key) {
+            errors.push(ValidationError::new(field, key, message));
+        }
+        for message in validate_label_value(value) {
+            errors.push(ValidationError::new(field, value, message));
+        }
+    }
+    errors
+}
+
+/// Validates the keys and total size of annotations
+pub fn validate_annotations(annotations: &BTreeMap<String, String>, field: &str) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let mut total_size = 0;
+    for (key, value) in annotations {
+        // Annotation keys are validated case-insensitively
+        for message in validate_qualified_name(&key.to_lowercase()) {
+            errors.push(ValidationError::new(field, key, message));
+        }
+        total_size += key.len() + value.len();
+    }
+    if total_size > TOTAL_ANNOTATION_SIZE_LIMIT {
+        errors.push(ValidationError {
+            field: field.to_string(),
+            value: String::new(),
+            message: format!(
+                "annotations are {} bytes, but must have at most {} bytes",
+                total_size, TOTAL_ANNOTATION_SIZE_LIMIT
+            ),
+        });
+    }
+    errors
+}
+
+/// Validates the name, generated name prefix, namespace, labels and annotations of an object
+///
+/// The name is validated as a DNS-1123 subdomain, which is the rule for most kinds.
+/// Kinds with stricter rules, such as `Namespace` and `Service`, can additionally check their name with
+/// [`validate_dns1123_label`].
+pub fn validate_object_meta(meta: &ObjectMeta) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    match (&meta.name, &meta.generate_name) {
+        (Some(name), _) => {
+            for message in validate_dns1123_subdomain(name) {
+                errors.push(ValidationError::new("metadata.name", name, message));
+            }
+        }
+        (None, Some(prefix)) => {
+            // The apiserver appends a random suffix, so the prefix may end with a dash
+            let masked = match prefix.strip_suffix('-') {
+                Some(rest) if !rest.is_empty() => format!("{}a", rest),
+                _ => prefix.clone(),
+            };
+            for message in validate_dns1123_subdomain(&masked) {
+                errors.push(ValidationError::new("metadata.generateName", prefix, message));
+            }
+        }
+        (None, None) => errors.push(ValidationError::new(
+            "metadata.name",
+            "",
+            "name or generateName is required".to_string(),
+        )),
+    }
+    if let Some(namespace) = &meta.namespace {
+        for message in validate_dns1123_label(namespace) {
+            errors.push(ValidationError::new("metadata.namespace", namespace, message));
+        }
+    }
+    if let Some(labels) = &meta.labels {
+        errors.extend(validate_labels(labels, "metadata.labels"));
+    }
+    if let Some(annotations) = &meta.annotations {
+        errors.extend(validate_annotations(annotations, "metadata.annotations"));
+    }
+    errors
+}
+
+fn is_dns1123_label(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    match (bytes.first(), bytes.last()) {
+        (Some(first), Some(last)) => {
+            is_lower_alphanumeric(*first)
+                && is_lower_alphanumeric(*last)
+                && bytes.iter().all(|b| is_lower_alphanumeric(*b) || *b == b'-')
+        }
+        _ => false,
+    }
+}
+
+fn is_qualified_name_part(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    match (bytes.first(), bytes.last()) {
+        (Some(first), Some(last)) => {
+            first.is_ascii_alphanumeric()
+                && last.is_ascii_alphanumeric()
+                && bytes
+                    .iter()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+        }
+        _ => false,
+    }
+}
+
+fn is_lower_alphanumeric(b: u8) -> bool {
+    b.is_ascii_lowercase() || b.is_ascii_digit()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(validate_dns1123_label("my-name").is_empty());
+        assert!(validate_dns1123_label("123-abc").is_empty());
+        assert_eq!(validate_dns1123_label("My-Name"), vec![DNS1123_LABEL_ERROR]);
+        assert_eq!(validate_dns1123_label("a.b"), vec![DNS1123_LABEL_ERROR]);
+        assert_eq!(validate_dns1123_label(&"a".repeat(64)), vec![max_length(63)]);
+
+        assert!(validate_dns1123_subdomain("example.com").is_empty());
+        assert_eq!(
+            validate_dns1123_subdomain("example..com"),
+            vec![DNS1123_SUBDOMAIN_ERROR]
+        );
+        assert_eq!(
+            validate_dns1123_subdomain("-example"),
+            vec![DNS1123_SUBDOMAIN_ERROR]
+        );
+        assert_eq!(validate_dns1123_subdomain(""), vec![DNS1123_SUBDOMAIN_ERROR]);
+    }
+
+    #[test]
+    fn labels() {
+        assert!(validate_qualified_name("app.kubernetes.io/name").is_empty());
+        assert!(validate_qualified_name("MyName").is_empty());
+        assert_eq!(
+            validate_qualified_name("Example.com/name"),
+            vec![format!("prefix part {}", DNS1123_SUBDOMAIN_ERROR)]
+        );
+        assert_eq!(
+            validate_qualified_name("example.com/"),
+            vec!["name part must be non-empty"]
+        );
+        assert_eq!(validate_qualified_name("a/b/c").len(), 1);
+        assert_eq!(validate_qualified_name("-name"), vec![QUALIFIED_NAME_ERROR]);
+
+        assert!(validate_label_value("").is_empty());
+        assert!(validate_label_value("my_value").is_empty());
+        assert_eq!(validate_label_value("my value"), vec![LABEL_VALUE_ERROR]);
+    }
+
+    #[test]
+    fn object_meta() {
+        let meta = ObjectMeta {
+            generate_name: Some("web-".into()),
+            namespace: Some("default".into()),
+            labels: Some(BTreeMap::from([("app".to_string(), "web".to_string())])),
+            annotations: Some(BTreeMap::from([(
+                "Example.com/Note".to_string(),
+                "x".to_string(),
+            )])),
+            ..ObjectMeta::default()
+        };
+        assert_eq!(validate_object_meta(&meta), vec![]);
+
+        let meta = ObjectMeta {
+            name: Some("Web".into()),
+            labels: Some(BTreeMap::from([("app".to_string(), "web server".to_string())])),
+            annotations: Some(BTreeMap::from([(
+                "note".to_string(),
+                "x".repeat(TOTAL_ANNOTATION_SIZE_LIMIT),
+            )])),
+            ..ObjectMeta::default()
+        };
+        let errors = validate_object_meta(&meta);
+        assert_eq!(
+            errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+            vec!["metadata.name", "metadata.labels", "metadata.annotations"]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            format!(
+                "metadata.labels: Invalid value: \"web server\": {}",
+                LABEL_VALUE_ERROR
+            )
+        );
+    }
+}
@@ -35,6 +35,8 @@ struct KubeAttrs {
     printcolums: Vec<String>,
     #[darling(default)]
     scale: Option<String>,
+    #[darling(multiple, rename = "selectable")]
+    selectable_fields: Vec<String>,
     #[darling(default)]
     crates: Crates,
 }
@@ -160,6 +162,7 @@ pub(crate) fn derive(input: proc_macro2::TokenStream) -> proc_macro2::TokenStrea
         printcolums,
         apiextensions,
         scale,
+        selectable_fields,
         crates:
             Crates {
                 kube_core,
@@ -178,6 +181,9 @@ pub(crate) fn derive(input: proc_macro2::TokenStream) -> proc_macro2::TokenStrea
         )
         .to_compile_error();
     }
+    if let Err(err) = check_selectable_fields(&selectable_fields, &apiextensions) {
+        return syn::Error::new_spanned(derive_input.ident, err).to_compile_error();
+    }
     let visibility = derive_input.vis;
     let ident = derive_input.ident;
 
@@ -368,6 +374,21 @@ pub(crate) fn derive(input: proc_macro2::TokenStream) -> proc_macro2::TokenStrea
         quote! { &[#names] }
     };
 
+    // Only the v1 trait has selectable fields, they are rejected for v1beta1 above
+    let selectable_fields_fn = if selectable_fields.is_empty() {
+        quote! {}
+    } else {
+        let fields = selectable_fields
+            .iter()
+            .map(|field| quote! { #field, })
+            .collect::<TokenStream>();
+        quote! {
+            fn selectable_fields() -> &'static [&'static str] {
+                &[#fields]
+            }
+        }
+    };
+
     let categories_json = serde_json::to_string(&categories).unwrap();
     let short_json = serde_json::to_string(&shortnames).unwrap();
     let crd_meta_name = format!("{}.{}", plural, group);
@@ -490,6 +511,8 @@ pub(crate) fn derive(input: proc_macro2::TokenStream) -> proc_macro2::TokenStrea
             fn shortnames() -> &'static [&'static str] {
                 #shortnames_slice
             }
+
+            #selectable_fields_fn
         }
     };
 
@@ -506,6 +529,35 @@ pub(crate) fn derive(input: proc_macro2::TokenStream) -> proc_macro2::TokenStrea
     }
 }
 
+// Checks the `#[kube(selectable = "...")]` fields against the rules of the API server
+fn check_selectable_fields(fields: &[String], apiextensions: &str) -> Result<(), String> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+    if apiextensions != "v1" {
+        return Err(r#"#[kube(selectable = "...")] requires `apiextensions = "v1"`"#.into());
+    }
+    // https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definitions/#crd-selectable-fields
+    if fields.len() > 8 {
+        return Err("at most 8 selectable fields are allowed".into());
+    }
+    for field in fields {
+        if field.is_empty() || field.starts_with('.') || field.split('.').any(str::is_empty) {
+            return Err(format!(
+                r#"invalid selectable field "{}", expected a path like "spec.field""#,
+                field
+            ));
+        }
+        if field == "metadata" || field.starts_with("metadata.") {
+            return Err(format!(
+                r#"invalid selectable field "{}", metadata fields are always selectable"#,
+                field
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// This generates the code for the `#kube_core::object::HasSpec` trait implementation.
 ///
 /// All CRDs have a spec so it is implemented for all of them.
@@ -536,7 +588,7 @@ struct StatusInformation {
     field: TokenStream,
     /// The initialization code to use in a `Default` and `::new()` implementation
     default: TokenStream,
-    /// The implementation code for the `HasStatus` trait
+    /// The implementation code for the `HasStatus` and `HasStatusSubresource` traits
     impl_hasstatus: TokenStream,
 }
 
@@ -579,6 +631,8 @@ fn process_status(
                         &mut self.status
                     }
                 }
+
+                impl #kube_core::object::HasStatusSubresource for #root_ident {}
             },
         }
     } else {
@@ -107,6 +107,9 @@ mod custom_resource;
 /// Adds a status struct to the top level generated type and enables the status
 /// subresource in your crd.
 ///
+/// The generated type implements `HasStatusSubresource`, which lets you update its status
+/// with `Api::apply_status`.
+///
 /// ### `#[kube(derive = "Trait")]`
 /// Adding `#[kube(derive = "PartialEq")]` is required if you want your generated
 /// top level type to be able to `#[derive(PartialEq)]`
@@ -136,6 +139,13 @@ mod custom_resource;
 /// ### `#[kube(shortname = "sn")]`
 /// Add a single shortname to the generated crd.
 ///
+/// ### `#[kube(selectable = "spec.field")]`
+/// Allows selecting custom resources by the field with [field selectors](https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definitions/#crd-selectable-fields),
+/// which requires kubernetes >= 1.30. Can be repeated, up to 8 fields.
+///
+/// `k8s_openapi` does not have selectable fields yet, so they are only in `CustomResourceExt::crd_json()`, not in `crd()`.
+/// Use `ListParams::validate_fields_for` to check field selectors against them.
+///
 /// ## Example with all properties
 ///
 /// ```rust
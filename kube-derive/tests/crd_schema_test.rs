@@ -13,7 +13,8 @@ use serde::{Deserialize, Serialize};
     namespaced,
     derive = "PartialEq",
     shortname = "fo",
-    shortname = "f"
+    shortname = "f",
+    selectable = "spec.nonNullable"
 )]
 #[kube(apiextensions = "v1")]
 #[serde(rename_all = "camelCase")]
@@ -58,6 +59,24 @@ fn test_shortnames() {
     assert_eq!(&["fo", "f"], Foo::shortnames());
 }
 
+#[test]
+fn test_selectable_fields() {
+    use kube::core::{params::ListParams, CustomResourceExt};
+    assert_eq!(&["spec.nonNullable"], Foo::selectable_fields());
+    assert_eq!(
+        Foo::crd_json()["spec"]["versions"][0]["selectableFields"],
+        serde_json::json!([{ "jsonPath": ".spec.nonNullable" }])
+    );
+    assert!(ListParams::default()
+        .fields("spec.nonNullable=a,metadata.name=b")
+        .validate_fields_for::<Foo>()
+        .is_ok());
+    assert!(ListParams::default()
+        .fields("spec.nullable=a")
+        .validate_fields_for::<Foo>()
+        .is_err());
+}
+
 #[test]
 fn test_serialized_matches_expected() {
     assert_eq!(
@@ -0,0 +1,332 @@
+//! Dumps of what a [`Controller`](super::Controller) is doing, for debugging controllers that are stuck
+use super::ReconcileRequest;
+use crate::watcher;
+use futures::{Stream, StreamExt};
+use kube_client::Resource;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// A handle to the state of a [`Controller`](super::Controller)
+///
+/// Retrieved with [`Controller::diagnostics`](super::Controller::diagnostics).
+///
+/// Clones share the same state, so a handle can be kept (for example, by a debug HTTP endpoint)
+/// while the controller runs.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    watchers: Vec<WatcherState>,
+    // Keyed by the object, holding the reason and when the reconcile started
+    running: HashMap<String, (String, Instant)>,
+    // Keyed by the object, holding the reason and when the reconcile is due
+    queued: HashMap<String, (String, Instant)>,
+}
+
+#[derive(Debug)]
+struct WatcherState {
+    name: String,
+    events: u64,
+    restarts: u64,
+    errors: u64,
+    last_event: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl Diagnostics {
+    /// Takes a snapshot of the watchers, the running reconciles and the queued reconciles
+    #[must_use]
+    pub fn dump(&self) -> Dump {
+        let now = Instant::now();
+        let state = self.state();
+        let watchers = state
+            .watchers
+            .iter()
+            .map(|watcher| WatcherStatus {
+                name: watcher.name.clone(),
+                events: watcher.events,
+                restarts: watcher.restarts,
+                errors: watcher.errors,
+                since_last_event: watcher.last_event.map(|last_event| now - last_event),
+                last_error: watcher.last_error.clone(),
+            })
+            .collect();
+        let mut running = state
+            .running
+            .iter()
+            .map(|(object, (reason, started))| RunningReconcile {
+                object: object.clone(),
+                reason: reason.clone(),
+                running_for: now - *started,
+            })
+            .collect::<Vec<_>>();
+        // Longest running first, those are the likeliest to be stuck
+        running.sort_by(|a, b| {
+            b.running_for
+                .cmp(&a.running_for)
+                .then_with(|| a.object.cmp(&b.object))
+        });
+        let mut queued = state
+            .queued
+            .iter()
+            .map(|(object, (reason, run_at))| QueuedReconcile {
+                object: object.clone(),
+                reason: reason.clone(),
+                due_in: run_at.saturating_duration_since(now),
+            })
+            .collect::<Vec<_>>();
+        queued.sort_by(|a, b| a.due_in.cmp(&b.due_in).then_with(|| a.object.cmp(&b.object)));
+        Dump {
+            watchers,
+            running,
+            queued,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // The state is only updated by simple assignments, so it is consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Counts the events and errors of a watcher stream as the watcher `name`
+    pub(crate) fn watch<K>(
+        &self,
+        name: String,
+        stream: impl Stream<Item = watcher::Result<watcher::Event<K>>>,
+    ) -> impl Stream<Item = watcher::Result<watcher::Event<K>>> {
+        let index = {
+            let mut state = self.state();
+            state.watchers.push(WatcherState {
+                name,
+                events: 0,
+                restarts: 0,
+                errors: 0,
+                last_event: None,
+                last_error: None,
+            });
+            state.watchers.len() - 1
+        };
+        let diagnostics = self.clone();
+        stream.inspect(move |event| {
+            let mut state = diagnostics.state();
+            let status = &mut state.watchers[index];
+            match event {
+                Ok(watcher::Event::Restarted(_)) => {
+                    status.restarts += 1;
+                    status.last_event = Some(Instant::now());
+                }
+                Ok(_) => {
+                    status.events += 1;
+                    status.last_event = Some(Instant::now());
+                }
+                Err(err) => {
+                    status.errors += 1;
+                    status.last_error = Some(err.to_string());
+                }
+            }
+        })
+    }
+
+    /// Records that a reconcile was scheduled, keeping the earliest time like the scheduler does
+    pub(crate) fn queued<K: Resource>(&self, request: &ReconcileRequest<K>, run_at: Instant) {
+        let mut state = self.state();
+        let entry = state
+            .queued
+            .entry(request.obj_ref.to_string())
+            .or_insert_with(|| (request.reason.to_string(), run_at));
+        if run_at < entry.1 {
+            *entry = (request.reason.to_string(), run_at);
+        }
+    }
+
+    /// Records that a reconcile was taken off the queue, it is running until the returned guard is dropped
+    pub(crate) fn started<K: Resource>(&self, request: &ReconcileRequest<K>) -> RunningGuard {
+        let object = request.obj_ref.to_string();
+        let mut state = self.state();
+        state.queued.remove(&object);
+        state
+            .running
+            .insert(object.clone(), (request.reason.to_string(), Instant::now()));
+        RunningGuard {
+            diagnostics: self.clone(),
+            object,
+        }
+    }
+}
+
+/// Marks a reconcile as finished when dropped, which also covers aborted reconciles
+pub(crate) struct RunningGuard {
+    diagnostics: Diagnostics,
+    object: String,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.diagnostics.state().running.remove(&self.object);
+    }
+}
+
+/// A snapshot of a [`Controller`](super::Controller), taken by [`Diagnostics::dump`]
+///
+/// Printed as a multi-line report with [`Display`](fmt::Display).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dump {
+    /// The watchers feeding the controller, in the order they were added
+    pub watchers: Vec<WatcherStatus>,
+    /// The reconciles in flight, longest running first
+    pub running: Vec<RunningReconcile>,
+    /// The reconciles waiting to run, soonest first
+    pub queued: Vec<QueuedReconcile>,
+}
+
+/// The state of a watcher in a [`Dump`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatcherStatus {
+    /// The watched kind, prefixed by `owned` or `watched` for the watchers of related objects
+    pub name: String,
+    /// The number of applied and deleted events
+    pub events: u64,
+    /// The number of times the watch was restarted with a full list
+    pub restarts: u64,
+    /// The number of errors returned by the watcher
+    pub errors: u64,
+    /// The time since the last event or restart, if any
+    pub since_last_event: Option<Duration>,
+    /// The last error returned by the watcher
+    pub last_error: Option<String>,
+}
+
+/// A reconcile in flight in a [`Dump`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunningReconcile {
+    /// The reconciled object
+    pub object: String,
+    /// Why the object is reconciled
+    pub reason: String,
+    /// The time since the reconcile started
+    pub running_for: Duration,
+}
+
+/// A reconcile waiting to run in a [`Dump`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedReconcile {
+    /// The object to reconcile
+    pub object: String,
+    /// Why the object is to be reconciled
+    pub reason: String,
+    /// The time until the reconcile is due
+    ///
+    /// Zero for reconciles that are overdue, waiting for a running reconcile of the same object.
+    pub due_in: Duration,
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "watchers ({}):", self.watchers.len())?;
+        for watcher in &self.watchers {
+            write!(
+                f,
+                "  {}: {} events, {} restarts, {} errors",
+                watcher.name, watcher.events, watcher.restarts, watcher.errors
+            )?;
+            if let Some(since) = watcher.since_last_event {
+                write!(f, ", last event {:?} ago", since)?;
+            }
+            if let Some(err) = &watcher.last_error {
+                write!(f, ", last error: {}", err)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "running ({}):", self.running.len())?;
+        for reconcile in &self.running {
+            writeln!(
+                f,
+                "  {} for {:?} ({})",
+                reconcile.object, reconcile.running_for, reconcile.reason
+            )?;
+        }
+        writeln!(f, "queued ({}):", self.queued.len())?;
+        for reconcile in &self.queued {
+            writeln!(
+                f,
+                "  {} in {:?} ({})",
+                reconcile.object, reconcile.due_in, reconcile.reason
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostics;
+    use crate::{
+        controller::{ReconcileReason, ReconcileRequest},
+        reflector::ObjectRef,
+        watcher,
+    };
+    use futures::{stream, StreamExt};
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use std::time::Duration;
+    use tokio::time::{advance, Instant};
+
+    fn request(name: &str, reason: ReconcileReason) -> ReconcileRequest<ConfigMap> {
+        ReconcileRequest {
+            obj_ref: ObjectRef::new(name).within("default"),
+            reason,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dumps_running_and_queued_reconciles() {
+        let diagnostics = Diagnostics::default();
+        let events = stream::iter(vec![
+            Ok(watcher::Event::Restarted(vec![])),
+            Ok(watcher::Event::Applied(ConfigMap::default())),
+        ]);
+        diagnostics
+            .watch("ConfigMap".to_string(), events)
+            .collect::<Vec<_>>()
+            .await;
+        diagnostics.queued(
+            &request("a", ReconcileReason::ObjectUpdated),
+            Instant::now() + Duration::from_secs(5),
+        );
+        diagnostics.queued(
+            &request("a", ReconcileReason::BulkReconcile),
+            Instant::now() + Duration::from_secs(1),
+        );
+        diagnostics.queued(
+            &request("b", ReconcileReason::ReconcilerRequestedRetry),
+            Instant::now() + Duration::from_secs(60),
+        );
+        advance(Duration::from_secs(1)).await;
+        let running = diagnostics.started(&request("a", ReconcileReason::BulkReconcile));
+        advance(Duration::from_secs(30)).await;
+
+        let dump = diagnostics.dump();
+        assert_eq!(dump.watchers[0].events, 1);
+        assert_eq!(dump.watchers[0].restarts, 1);
+        assert_eq!(dump.watchers[0].since_last_event, Some(Duration::from_secs(31)));
+        assert_eq!(dump.running.len(), 1);
+        assert_eq!(dump.running[0].object, "ConfigMap.v1./a.default");
+        assert_eq!(dump.running[0].reason, "bulk reconcile requested");
+        assert_eq!(dump.running[0].running_for, Duration::from_secs(30));
+        assert_eq!(dump.queued.len(), 1);
+        assert_eq!(dump.queued[0].due_in, Duration::from_secs(29));
+        assert!(dump
+            .to_string()
+            .contains("running (1):\n  ConfigMap.v1./a.default for 30s"));
+
+        drop(running);
+        assert!(diagnostics.dump().running.is_empty());
+    }
+}
@@ -33,9 +33,12 @@ use thiserror::Error;
 use tokio::{runtime::Handle, time::Instant};
 use tracing::{info_span, Instrument};
 
+mod diagnostics;
 mod future_hash_map;
 mod runner;
 
+pub use self::diagnostics::{Diagnostics, Dump, QueuedReconcile, RunningReconcile, WatcherStatus};
+
 #[derive(Debug, Error)]
 pub enum Error<ReconcilerErr: std::error::Error + 'static, QueueErr: std::error::Error + 'static> {
     #[error("tried to reconcile object {0} that was not found in local store")]
@@ -219,11 +222,32 @@ impl Display for ReconcileReason {
 /// This is the "hard-mode" version of [`Controller`], which allows you some more customization
 /// (such as triggering from arbitrary [`Stream`]s), at the cost of being a bit more verbose.
 pub fn applier<K, QueueStream, ReconcilerFut, T>(
+    reconciler: impl FnMut(K, Context<T>) -> ReconcilerFut,
+    error_policy: impl FnMut(&ReconcilerFut::Error, Context<T>) -> ReconcilerAction,
+    context: Context<T>,
+    store: Store<K>,
+    queue: QueueStream,
+) -> impl Stream<Item = Result<(ObjectRef<K>, ReconcilerAction), Error<ReconcilerFut::Error, QueueStream::Error>>>
+where
+    K: Clone + Resource + 'static,
+    K::DynamicType: Debug + Eq + Hash + Clone + Unpin,
+    ReconcilerFut: TryFuture<Ok = ReconcilerAction> + Unpin,
+    ReconcilerFut::Error: std::error::Error + 'static,
+    QueueStream: TryStream,
+    QueueStream::Ok: Into<ReconcileRequest<K>>,
+    QueueStream::Error: std::error::Error + 'static,
+{
+    applier_with_diagnostics(reconciler, error_policy, context, store, queue, None)
+}
+
+/// [`applier`], recording the queued and running reconciles in `diagnostics`
+fn applier_with_diagnostics<K, QueueStream, ReconcilerFut, T>(
     mut reconciler: impl FnMut(K, Context<T>) -> ReconcilerFut,
     mut error_policy: impl FnMut(&ReconcilerFut::Error, Context<T>) -> ReconcilerAction,
     context: Context<T>,
     store: Store<K>,
     queue: QueueStream,
+    diagnostics: Option<Diagnostics>,
 ) -> impl Stream<Item = Result<(ObjectRef<K>, ReconcilerAction), Error<ReconcilerFut::Error, QueueStream::Error>>>
 where
     K: Clone + Resource + 'static,
@@ -259,8 +283,15 @@ where
         )),
         // all the Oks from the select gets passed through the scheduler stream, and are then executed
         move |s| {
+            let queued_diagnostics = diagnostics.clone();
+            let s = s.inspect(move |request| {
+                if let Some(diagnostics) = &queued_diagnostics {
+                    diagnostics.queued(&request.message, request.run_at);
+                }
+            });
             Runner::new(scheduler(s), move |request| {
                 let request = request.clone();
+                let running = diagnostics.as_ref().map(|diagnostics| diagnostics.started(&request));
                 match store.get(&request.obj_ref) {
                     Some(obj) => {
                         let reconciler_span = info_span!("reconciling object", "object.ref" = %request.obj_ref, object.reason = %request.reason);
@@ -269,7 +300,10 @@ where
                         .instrument(reconciler_span.clone())
                         // Reconciler errors are OK from the applier's PoV, we need to apply the error policy
                         // to them separately
-                        .map(|res| Ok((request.obj_ref, res, reconciler_span)))
+                        .map(|res| {
+                            drop(running);
+                            Ok((request.obj_ref, res, reconciler_span))
+                        })
                         .left_future()
                     },
                     None => future::err(
@@ -401,6 +435,7 @@ where
     forceful_shutdown_selector: Vec<BoxFuture<'static, ()>>,
     dyntype: K::DynamicType,
     reader: Store<K>,
+    diagnostics: Diagnostics,
 }
 
 impl<K> Controller<K>
@@ -443,9 +478,11 @@ where
     pub fn new_with(owned_api: Api<K>, lp: ListParams, dyntype: K::DynamicType) -> Self {
         let writer = Writer::<K>::new(dyntype.clone());
         let reader = writer.as_reader();
+        let diagnostics = Diagnostics::default();
         let mut trigger_selector = stream::SelectAll::new();
+        let owned_watcher = diagnostics.watch(K::kind(&dyntype).into_owned(), watcher(owned_api, lp));
         let self_watcher = trigger_self(
-            try_flatten_applied(reflector(writer, watcher(owned_api, lp))),
+            try_flatten_applied(reflector(writer, owned_watcher)),
             dyntype.clone(),
         )
         .boxed();
@@ -462,6 +499,7 @@ where
             ],
             dyntype,
             reader,
+            diagnostics,
         }
     }
 
@@ -470,6 +508,16 @@ where
         self.reader.clone()
     }
 
+    /// Retrieve a handle to the diagnostics of the controller, for dumping its state while it runs
+    ///
+    /// The [`Dump`]s of the handle list the watchers of the controller, the reconciles in flight and
+    /// for how long they have been running, and the queued reconciles.
+    /// To log dumps on demand, see [`Controller::dump_diagnostics_on`].
+    #[must_use]
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.clone()
+    }
+
     /// Specify `Child` objects which `K` owns and should be watched
     ///
     /// Takes an [`Api`] object that determines how the `Controller` listens for changes to the `Child`.
@@ -500,8 +548,9 @@ where
     where
         Child::DynamicType: Debug + Eq + Hash + Clone,
     {
+        let name = format!("owned {}", Child::kind(&dyntype));
         let child_watcher = trigger_owners(
-            try_flatten_touched(watcher(api, lp)),
+            try_flatten_touched(self.diagnostics.watch(name, watcher(api, lp))),
             self.dyntype.clone(),
             dyntype,
         );
@@ -553,7 +602,9 @@ where
         I::IntoIter: Send,
         Other::DynamicType: Clone,
     {
-        let other_watcher = trigger_with(try_flatten_touched(watcher(api, lp)), move |obj| {
+        let name = format!("watched {}", Other::kind(&dyntype));
+        let other_watcher = self.diagnostics.watch(name, watcher(api, lp));
+        let other_watcher = trigger_with(try_flatten_touched(other_watcher), move |obj| {
             let watched_obj_ref = ObjectRef::from_obj_with(&obj, dyntype.clone()).erase();
             mapper(obj)
                 .into_iter()
@@ -724,6 +775,52 @@ where
         self
     }
 
+    /// Log a [`Dump`] of the controller's [`Diagnostics`] whenever `trigger` emits a value
+    ///
+    /// The dumps are logged at the `INFO` level, and keep being logged during a graceful shutdown so that
+    /// reconciles that never finish can be found.
+    /// To dump on `SIGUSR1`, see [`Controller::dump_diagnostics_on_signal`].
+    ///
+    /// This can be called multiple times, in which case they are additive.
+    pub fn dump_diagnostics_on(mut self, trigger: impl Stream<Item = ()> + Send + 'static) -> Self {
+        let diagnostics = self.diagnostics.clone();
+        self.forceful_shutdown_selector.push(
+            async move {
+                trigger
+                    .for_each(|()| {
+                        tracing::info!("controller diagnostics:\n{}", diagnostics.dump());
+                        future::ready(())
+                    })
+                    .await;
+                // Ending the trigger must not stop the controller
+                future::pending::<()>().await;
+            }
+            .boxed(),
+        );
+        self
+    }
+
+    /// Log a [`Dump`] of the controller's [`Diagnostics`] whenever the process receives `SIGUSR1`
+    ///
+    /// For example, `kill -USR1 <pid>` dumps a controller that stopped making progress,
+    /// see [`Controller::dump_diagnostics_on`].
+    #[cfg(unix)]
+    pub fn dump_diagnostics_on_signal(self) -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+        let signals =
+            stream::once(async { signal(SignalKind::user_defined1()) }).flat_map(|signal| match signal {
+                Ok(signal) => stream::unfold(signal, |mut signal| async move {
+                    signal.recv().await.map(|()| ((), signal))
+                })
+                .left_stream(),
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to listen for SIGUSR1, diagnostics will not be dumped");
+                    stream::empty().right_stream()
+                }
+            });
+        self.dump_diagnostics_on(signals)
+    }
+
     /// Consume all the parameters of the Controller and start the applier stream
     ///
     /// This creates a stream from all builder calls and starts an applier with
@@ -740,7 +837,7 @@ where
         ReconcilerFut: TryFuture<Ok = ReconcilerAction> + Send + 'static,
         ReconcilerFut::Error: std::error::Error + Send + 'static,
     {
-        applier(
+        applier_with_diagnostics(
             move |obj, ctx| {
                 CancelableJoinHandle::spawn(
                     reconciler(obj, ctx).into_future().in_current_span(),
@@ -752,6 +849,7 @@ where
             self.reader,
             self.trigger_selector
                 .take_until(future::select_all(self.graceful_shutdown_selector)),
+            Some(self.diagnostics),
         )
         .take_until(futures::future::select_all(self.forceful_shutdown_selector))
     }
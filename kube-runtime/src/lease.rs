@@ -0,0 +1,434 @@
+//! Coordination leases held by one holder at a time, for kubernetes >= 1.14
+//!
+//! A [`LeaseLock`] is the primitive underneath leader election, usable on its own for heartbeats,
+//! running scheduled jobs on one replica, or claiming work per node.
+use k8s_openapi::{
+    api::coordination::v1::{Lease, LeaseSpec},
+    apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta},
+    chrono::{DateTime, Utc},
+};
+use kube_client::{
+    api::{Api, PostParams},
+    error::ErrorResponse,
+};
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::time::Instant;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to read lease: {0}")]
+    ReadLease(#[source] kube_client::Error),
+    #[error("failed to write lease: {0}")]
+    WriteLease(#[source] kube_client::Error),
+    /// The lease was changed by another holder between reading and writing it
+    #[error("lease was modified concurrently")]
+    Conflict,
+    #[error("lease is held by {holder}")]
+    HeldByOther { holder: String },
+    #[error("lease is not held by {identity}")]
+    NotHeld { identity: String },
+}
+
+/// A [`Lease`] held by at most one identity at a time
+///
+/// The holder has to [`renew`](LeaseLock::renew) the lease within its duration, or it expires and
+/// [`adopt_if_expired`](LeaseLock::adopt_if_expired) lets another identity take it over.
+/// Like client-go, expiry is judged by the local clock rather than the `renewTime` written by the holder,
+/// so that clock skew between replicas does not matter: a lease expires once this lock has seen it unchanged
+/// for its duration. A lock therefore never adopts a lease the first time it reads it.
+/// Every write is conditional on the `resourceVersion` that was read, so two identities racing for the
+/// same lease cannot both win, and the loser gets [`Error::Conflict`].
+///
+/// ```no_run
+/// use k8s_openapi::api::coordination::v1::Lease;
+/// use kube::{runtime::lease::LeaseLock, Api, Client};
+/// use std::time::Duration;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let identity = std::env::var("POD_NAME")?;
+/// let lock = LeaseLock::new(Api::<Lease>::default_namespaced(client), "nightly-backup", &identity)
+///     .duration(Duration::from_secs(60));
+/// if lock.adopt_if_expired().await.is_ok() {
+///     // run the backup, calling `lock.renew()` at least every minute
+///     lock.release().await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct LeaseLock {
+    api: Api<Lease>,
+    name: String,
+    identity: String,
+    duration: Duration,
+    // The lease as last read, and when it was first read like that, shared by clones
+    observed: Arc<Mutex<Option<(LeaseSpec, Instant)>>>,
+}
+
+impl LeaseLock {
+    /// A lock on the lease `name`, taken as `identity`
+    ///
+    /// The lease is created when it is first acquired. It expires 15 seconds after it was last renewed,
+    /// unless configured with [`LeaseLock::duration`].
+    #[must_use]
+    pub fn new(api: Api<Lease>, name: &str, identity: &str) -> Self {
+        Self {
+            api,
+            name: name.into(),
+            identity: identity.into(),
+            duration: Duration::from_secs(15),
+            observed: Arc::default(),
+        }
+    }
+
+    /// Set how long the lease is held after it was last acquired or renewed
+    #[must_use]
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// The identity this lock takes the lease as
+    #[must_use]
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// Take the lease if it is missing, free, or already held by this identity
+    ///
+    /// Unlike [`LeaseLock::adopt_if_expired`], this never takes over from another holder,
+    /// even when they have stopped renewing the lease.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::HeldByOther`] if another identity holds the lease,
+    /// and with [`Error::Conflict`] if another identity wrote the lease concurrently.
+    pub async fn acquire(&self) -> Result<Lease, Error> {
+        self.take(false).await
+    }
+
+    /// Take the lease like [`LeaseLock::acquire`], or from a holder that has not renewed it in time
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::HeldByOther`] if another identity holds the lease and it has not expired,
+    /// and with [`Error::Conflict`] if another identity wrote the lease concurrently.
+    pub async fn adopt_if_expired(&self) -> Result<Lease, Error> {
+        self.take(true).await
+    }
+
+    /// Extend the lease held by this identity
+    ///
+    /// A lease that expired can still be renewed as long as no other identity took it over.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::NotHeld`] if the lease is held by another identity,
+    /// and with [`Error::Conflict`] if another identity wrote the lease concurrently.
+    pub async fn renew(&self) -> Result<Lease, Error> {
+        let mut lease = self.api.get(&self.name).await.map_err(Error::ReadLease)?;
+        let spec = lease.spec.get_or_insert_with(Default::default);
+        if holder(spec) != Some(&self.identity) {
+            return Err(Error::NotHeld {
+                identity: self.identity.clone(),
+            });
+        }
+        spec.renew_time = Some(MicroTime(Utc::now()));
+        spec.lease_duration_seconds = Some(self.duration_seconds());
+        self.replace(&lease).await
+    }
+
+    /// Give up the lease if it is held by this identity, so that it can be acquired right away
+    ///
+    /// Does nothing if the lease is missing or held by another identity.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Conflict`] if another identity wrote the lease concurrently.
+    pub async fn release(&self) -> Result<(), Error> {
+        let mut lease = match self.api.get_opt(&self.name).await.map_err(Error::ReadLease)? {
+            Some(lease) => lease,
+            None => return Ok(()),
+        };
+        let spec = lease.spec.get_or_insert_with(Default::default);
+        if holder(spec) != Some(&self.identity) {
+            return Ok(());
+        }
+        spec.holder_identity = None;
+        self.replace(&lease).await.map(|_| ())
+    }
+
+    async fn take(&self, adopt_expired: bool) -> Result<Lease, Error> {
+        let now = Utc::now();
+        let mut lease = match self.api.get_opt(&self.name).await.map_err(Error::ReadLease)? {
+            Some(lease) => lease,
+            None => return self.create(now).await,
+        };
+        let spec = lease.spec.get_or_insert_with(Default::default);
+        let observed_at = self.observe(spec);
+        match holder(spec) {
+            Some(holder) if *holder == self.identity => {}
+            Some(holder) if !(adopt_expired && is_expired(spec, observed_at, Instant::now())) => {
+                return Err(Error::HeldByOther {
+                    holder: holder.clone(),
+                });
+            }
+            _ => {
+                spec.holder_identity = Some(self.identity.clone());
+                spec.acquire_time = Some(MicroTime(now));
+                spec.lease_transitions = Some(spec.lease_transitions.unwrap_or_default() + 1);
+            }
+        }
+        spec.renew_time = Some(MicroTime(now));
+        spec.lease_duration_seconds = Some(self.duration_seconds());
+        self.replace(&lease).await
+    }
+
+    async fn create(&self, now: DateTime<Utc>) -> Result<Lease, Error> {
+        let lease = Lease {
+            metadata: ObjectMeta {
+                name: Some(self.name.clone()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: Some(self.identity.clone()),
+                acquire_time: Some(MicroTime(now)),
+                renew_time: Some(MicroTime(now)),
+                lease_duration_seconds: Some(self.duration_seconds()),
+                lease_transitions: Some(0),
+            }),
+        };
+        let lease = self
+            .api
+            .create(&PostParams::default(), &lease)
+            .await
+            .map_err(write_error)?;
+        self.observe(lease.spec.as_ref().unwrap_or(&LeaseSpec::default()));
+        Ok(lease)
+    }
+
+    // Writes the lease as read, so that the write fails if it changed in between
+    async fn replace(&self, lease: &Lease) -> Result<Lease, Error> {
+        let lease = self
+            .api
+            .replace(&self.name, &PostParams::default(), lease)
+            .await
+            .map_err(write_error)?;
+        self.observe(lease.spec.as_ref().unwrap_or(&LeaseSpec::default()));
+        Ok(lease)
+    }
+
+    // Records `spec` as read now, returning when it was first read unchanged
+    fn observe(&self, spec: &LeaseSpec) -> Instant {
+        let mut observed = self.observed.lock().unwrap_or_else(PoisonError::into_inner);
+        match &*observed {
+            Some((seen, at)) if seen == spec => *at,
+            _ => {
+                let now = Instant::now();
+                *observed = Some((spec.clone(), now));
+                now
+            }
+        }
+    }
+
+    fn duration_seconds(&self) -> i32 {
+        i32::try_from(self.duration.as_secs()).unwrap_or(i32::MAX)
+    }
+}
+
+fn holder(spec: &LeaseSpec) -> Option<&String> {
+    spec.holder_identity.as_ref().filter(|holder| !holder.is_empty())
+}
+
+// Whether the lease, unchanged since `observed_at`, has not been renewed within its duration
+fn is_expired(spec: &LeaseSpec, observed_at: Instant, now: Instant) -> bool {
+    match spec.lease_duration_seconds {
+        Some(seconds) => observed_at + Duration::from_secs(u64::try_from(seconds).unwrap_or(0)) < now,
+        None => true,
+    }
+}
+
+fn write_error(err: kube_client::Error) -> Error {
+    match err {
+        // 409 is returned both for a stale resourceVersion and for creating a lease that already exists
+        kube_client::Error::Api(ErrorResponse { code: 409, .. }) => Error::Conflict,
+        err => Error::WriteLease(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{holder, is_expired, Error, LeaseLock};
+    use futures::pin_mut;
+    use http::{Method, Request, Response, StatusCode};
+    use hyper::Body;
+    use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+    use kube_client::{Api, Client};
+    use serde_json::{json, Value};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::time::Instant;
+    use tower_test::mock;
+
+    #[test]
+    fn lease_expires_after_its_duration_since_observed() {
+        let observed_at = Instant::now();
+        let spec = LeaseSpec {
+            holder_identity: Some("a".into()),
+            lease_duration_seconds: Some(15),
+            ..LeaseSpec::default()
+        };
+        assert!(!is_expired(
+            &spec,
+            observed_at,
+            observed_at + Duration::from_secs(10)
+        ));
+        assert!(is_expired(
+            &spec,
+            observed_at,
+            observed_at + Duration::from_secs(16)
+        ));
+        assert!(is_expired(&LeaseSpec::default(), observed_at, observed_at));
+
+        let released = LeaseSpec {
+            holder_identity: Some(String::new()),
+            ..spec
+        };
+        assert_eq!(holder(&released), None);
+    }
+
+    // The lease stored by a fake apiserver, which can let another writer win the next replace
+    #[derive(Default)]
+    struct Stored {
+        lease: Option<Value>,
+        version: u64,
+        race_next_replace: bool,
+    }
+
+    // Serves one lease like the apiserver, checking the resourceVersion of replaces
+    fn lease_api(stored: Arc<Mutex<Stored>>) -> Api<Lease> {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        tokio::spawn(async move {
+            pin_mut!(handle);
+            while let Some((request, send)) = handle.next_request().await {
+                let method = request.method().clone();
+                let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                let mut stored = stored.lock().unwrap();
+                let written = (!body.is_empty()).then(|| serde_json::from_slice::<Value>(&body).unwrap());
+                if stored.race_next_replace && method == Method::PUT {
+                    // Another writer replaced the lease since it was read
+                    stored.race_next_replace = false;
+                    stored.version += 1;
+                    let version = stored.version.to_string();
+                    if let Some(lease) = stored.lease.as_mut() {
+                        lease["metadata"]["resourceVersion"] = version.into();
+                    }
+                }
+                let (status, response) = match (method, stored.lease.clone(), written) {
+                    (Method::GET, Some(lease), _) => (StatusCode::OK, lease),
+                    (Method::POST, None, Some(lease)) | (Method::PUT, Some(_), Some(lease))
+                        if stored.lease.is_none()
+                            || lease["metadata"]["resourceVersion"] == stored.version.to_string() =>
+                    {
+                        stored.version += 1;
+                        let mut lease = lease;
+                        lease["metadata"]["resourceVersion"] = stored.version.to_string().into();
+                        stored.lease = Some(lease.clone());
+                        (StatusCode::OK, lease)
+                    }
+                    (Method::GET, None, _) => (StatusCode::NOT_FOUND, failure(404, "NotFound")),
+                    _ => (StatusCode::CONFLICT, failure(409, "Conflict")),
+                };
+                send.send_response(
+                    Response::builder()
+                        .status(status)
+                        .body(Body::from(response.to_string()))
+                        .unwrap(),
+                );
+            }
+        });
+        Api::namespaced(Client::new(mock_service, "default"), "default")
+    }
+
+    fn failure(code: u16, reason: &str) -> Value {
+        json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "message": reason,
+            "reason": reason,
+            "code": code,
+        })
+    }
+
+    fn holder_of(lease: &Lease) -> Option<&str> {
+        lease.spec.as_ref()?.holder_identity.as_deref()
+    }
+
+    #[tokio::test]
+    async fn acquires_and_renews_leases() {
+        let api = lease_api(Arc::default());
+        let a = LeaseLock::new(api.clone(), "backup", "a");
+        let b = LeaseLock::new(api, "backup", "b");
+
+        let lease = a.acquire().await.unwrap();
+        assert_eq!(holder_of(&lease), Some("a"));
+        assert!(matches!(b.acquire().await, Err(Error::HeldByOther { holder }) if holder == "a"));
+        assert!(matches!(b.renew().await, Err(Error::NotHeld { .. })));
+        let renewed = a.renew().await.unwrap();
+        assert_eq!(holder_of(&renewed), Some("a"));
+        assert_ne!(renewed.metadata.resource_version, lease.metadata.resource_version);
+
+        a.release().await.unwrap();
+        let lease = b.acquire().await.unwrap();
+        assert_eq!(holder_of(&lease), Some("b"));
+        assert_eq!(lease.spec.unwrap().lease_transitions, Some(1));
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes_conflict() {
+        let stored = Arc::<Mutex<Stored>>::default();
+        let api = lease_api(stored.clone());
+        let a = LeaseLock::new(api, "backup", "a");
+        a.acquire().await.unwrap();
+        stored.lock().unwrap().race_next_replace = true;
+        assert!(matches!(a.renew().await, Err(Error::Conflict)));
+        a.renew().await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn adopts_leases_unchanged_for_their_duration() {
+        let api = lease_api(Arc::default());
+        let a = LeaseLock::new(api.clone(), "backup", "a").duration(Duration::from_secs(15));
+        let b = LeaseLock::new(api, "backup", "b");
+        a.acquire().await.unwrap();
+
+        // The renew time written by `a` is not trusted, only how long `b` has seen the lease unchanged
+        assert!(matches!(
+            b.adopt_if_expired().await,
+            Err(Error::HeldByOther { .. })
+        ));
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(matches!(
+            b.adopt_if_expired().await,
+            Err(Error::HeldByOther { .. })
+        ));
+        a.renew().await.unwrap();
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(matches!(
+            b.adopt_if_expired().await,
+            Err(Error::HeldByOther { .. })
+        ));
+        tokio::time::advance(Duration::from_secs(16)).await;
+        let lease = b.adopt_if_expired().await.unwrap();
+        assert_eq!(holder_of(&lease), Some("b"));
+    }
+}
@@ -22,8 +22,14 @@ k8s_openapi::k8s_if_ge_1_19! {
     pub mod events;
 }
 pub mod finalizer;
+k8s_openapi::k8s_if_ge_1_14! {
+    pub mod lease;
+}
+pub mod printer;
 pub mod reflector;
+pub mod rotation;
 pub mod scheduler;
+pub mod tail;
 pub mod utils;
 pub mod wait;
 pub mod watcher;
@@ -32,4 +38,4 @@ pub use controller::{applier, Controller};
 pub use finalizer::finalizer;
 pub use reflector::reflector;
 pub use scheduler::scheduler;
-pub use watcher::watcher;
+pub use watcher::{metadata_watcher, watcher};
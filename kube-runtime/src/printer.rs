@@ -0,0 +1,342 @@
+//! Printing watch events like `kubectl get -w`, for command line tools built on a [`watcher`]
+//!
+//! ```no_run
+//! use futures::{StreamExt, TryStreamExt};
+//! use k8s_openapi::api::core::v1::Pod;
+//! use kube::{api::{Api, ListParams}, Client};
+//! use kube::runtime::{printer::{Column, Format, Printer}, watcher};
+//! # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+//! let pods: Api<Pod> = Api::default_namespaced(Client::try_default().await?);
+//! let mut printer = Printer::new(Format::Table)
+//!     .column(Column::new("STATUS", ".status.phase"))
+//!     .column(Column::new("NODE", ".spec.nodeName"))
+//!     .column(Column::age("AGE", ".metadata.creationTimestamp"));
+//! let mut events = watcher(pods, ListParams::default()).boxed();
+//! let mut stdout = std::io::stdout();
+//! while let Some(event) = events.try_next().await? {
+//!     printer.print(&event, &mut stdout)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::io::Write;
+
+use k8s_openapi::{
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceColumnDefinition,
+    chrono::{DateTime, Utc},
+};
+use kube_client::core::printer::{human_duration, select};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::watcher;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to serialize object: {0}")]
+    SerializeObject(#[source] serde_json::Error),
+    #[error("failed to write output: {0}")]
+    Write(#[source] std::io::Error),
+}
+
+/// How a [`Printer`] prints events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Aligned columns under a header, like `kubectl get -w`
+    Table,
+    /// One JSON object per line, with a key per column
+    JsonLines,
+}
+
+/// A column printed by a [`Printer`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Column {
+    header: String,
+    json_path: String,
+    age: bool,
+}
+
+impl Column {
+    /// A column with the value at `json_path`, such as `.status.conditions[?(@.type=="Ready")].status`
+    ///
+    /// Supports the paths of [`select`]. Missing values are printed as `<none>`, and multiple values are
+    /// separated by spaces.
+    #[must_use]
+    pub fn new(header: &str, json_path: &str) -> Self {
+        Self {
+            header: header.to_string(),
+            json_path: json_path.to_string(),
+            age: false,
+        }
+    }
+
+    /// A column with the age of the timestamp at `json_path`, like the `AGE` column of `kubectl get`
+    #[must_use]
+    pub fn age(header: &str, json_path: &str) -> Self {
+        Self {
+            age: true,
+            ..Self::new(header, json_path)
+        }
+    }
+
+    fn render(&self, object: &Value, now: DateTime<Utc>) -> Value {
+        match select(object, &self.json_path).as_slice() {
+            [] => Value::Null,
+            [Value::String(timestamp)] if self.age => DateTime::parse_from_rfc3339(timestamp)
+                .map_or(Value::Null, |time| {
+                    Value::String(human_duration(now - time.with_timezone(&Utc)))
+                }),
+            [value] => (*value).clone(),
+            values => Value::String(
+                values
+                    .iter()
+                    .map(|value| format_cell(value))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        }
+    }
+}
+
+/// Prints the objects of watch events as tables or JSON lines
+///
+/// Starts with a `NAME` column, more columns are added with [`Printer::column`] and [`Printer::printer_columns`].
+#[derive(Clone, Debug)]
+pub struct Printer {
+    format: Format,
+    events: bool,
+    columns: Vec<Column>,
+    widths: Vec<usize>,
+    printed_header: bool,
+}
+
+impl Printer {
+    /// A printer with a `NAME` column
+    #[must_use]
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            events: false,
+            columns: vec![Column::new("NAME", ".metadata.name")],
+            widths: vec![],
+            printed_header: false,
+        }
+    }
+
+    /// Adds a column
+    #[must_use]
+    pub fn column(mut self, column: Column) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    /// Adds the `additionalPrinterColumns` of a custom resource version, like `kubectl get`
+    ///
+    /// Columns with a priority above `0` are only added when `wide` is set, like `kubectl get -o wide`.
+    #[must_use]
+    pub fn printer_columns(mut self, columns: &[CustomResourceColumnDefinition], wide: bool) -> Self {
+        for column in columns {
+            if column.priority.unwrap_or_default() > 0 && !wide {
+                continue;
+            }
+            let header = column.name.to_uppercase();
+            self.columns.push(if column.type_ == "date" {
+                Column::age(&header, &column.json_path)
+            } else {
+                Column::new(&header, &column.json_path)
+            });
+        }
+        self
+    }
+
+    /// Adds an `EVENT` column with the type of the event, like `kubectl get -w --output-watch-events`
+    ///
+    /// The objects of [`watcher::Event::Restarted`] events are printed as `RESTARTED`.
+    #[must_use]
+    pub fn events(mut self) -> Self {
+        self.events = true;
+        self
+    }
+
+    /// Prints the objects of an event to `out`, one line per object
+    ///
+    /// In [`Format::Table`], the header is printed before the first object and the columns widen to fit
+    /// the longest value seen so far.
+    ///
+    /// # Errors
+    ///
+    /// Fails if an object cannot be serialized, or if writing to `out` fails.
+    pub fn print<K: Serialize>(
+        &mut self,
+        event: &watcher::Event<K>,
+        out: &mut impl Write,
+    ) -> Result<(), Error> {
+        for line in self.lines(event, Utc::now())? {
+            writeln!(out, "{}", line).map_err(Error::Write)?;
+        }
+        out.flush().map_err(Error::Write)
+    }
+
+    fn lines<K: Serialize>(
+        &mut self,
+        event: &watcher::Event<K>,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<String>, Error> {
+        let (kind, objects) = match event {
+            watcher::Event::Applied(obj) => ("APPLIED", vec![obj]),
+            watcher::Event::Deleted(obj) => ("DELETED", vec![obj]),
+            watcher::Event::Restarted(objs) => ("RESTARTED", objs.iter().collect()),
+        };
+        let mut rows = Vec::with_capacity(objects.len());
+        for obj in objects {
+            let obj = serde_json::to_value(obj).map_err(Error::SerializeObject)?;
+            let mut row = Vec::with_capacity(self.columns.len() + 1);
+            if self.events {
+                row.push(Value::String(kind.to_string()));
+            }
+            row.extend(self.columns.iter().map(|column| column.render(&obj, now)));
+            rows.push(row);
+        }
+        Ok(match self.format {
+            Format::Table => self.table_lines(&rows),
+            Format::JsonLines => rows.into_iter().map(|row| self.json_line(row)).collect(),
+        })
+    }
+
+    fn headers(&self) -> impl Iterator<Item = &str> {
+        let event = if self.events { Some("EVENT") } else { None };
+        event
+            .into_iter()
+            .chain(self.columns.iter().map(|column| column.header.as_str()))
+    }
+
+    fn table_lines(&mut self, rows: &[Vec<Value>]) -> Vec<String> {
+        let headers = self.headers().map(ToString::to_string).collect::<Vec<_>>();
+        let cells = rows
+            .iter()
+            .map(|row| row.iter().map(format_cell).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        self.widths.resize(headers.len(), 0);
+        for line in std::iter::once(&headers).chain(&cells) {
+            for (width, cell) in self.widths.iter_mut().zip(line) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut lines = Vec::with_capacity(cells.len() + 1);
+        if !self.printed_header {
+            self.printed_header = true;
+            lines.push(align(&headers, &self.widths));
+        }
+        lines.extend(cells.iter().map(|line| align(line, &self.widths)));
+        lines
+    }
+
+    fn json_line(&self, row: Vec<Value>) -> String {
+        let line = self
+            .headers()
+            .map(ToString::to_string)
+            .zip(row)
+            .collect::<Map<String, Value>>();
+        Value::Object(line).to_string()
+    }
+}
+
+fn format_cell(value: &Value) -> String {
+    match value {
+        Value::Null => "<none>".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Pads the cells to the column widths, separated by three spaces like kubectl
+fn align(cells: &[String], widths: &[usize]) -> String {
+    let line = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:width$}", cell, width = width))
+        .collect::<Vec<_>>()
+        .join("   ");
+    line.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Column, Format, Printer};
+    use crate::watcher::Event;
+    use k8s_openapi::{
+        api::core::v1::Pod,
+        chrono::{DateTime, Utc},
+    };
+    use serde_json::json;
+
+    fn pod(name: &str, phase: Option<&str>) -> Pod {
+        serde_json::from_value(json!({
+            "metadata": { "name": name, "creationTimestamp": "2021-11-01T12:00:00Z" },
+            "status": { "phase": phase },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn prints_aligned_table() {
+        let now = "2021-11-01T12:05:30Z".parse::<DateTime<Utc>>().unwrap();
+        let mut printer = Printer::new(Format::Table)
+            .events()
+            .column(Column::new("STATUS", "{.status.phase}"))
+            .column(Column::age("AGE", ".metadata.creationTimestamp"));
+        let restarted = Event::Restarted(vec![pod("blog", Some("Running")), pod("blog-db", None)]);
+        assert_eq!(
+            printer.lines(&restarted, now).unwrap(),
+            [
+                "EVENT       NAME      STATUS    AGE",
+                "RESTARTED   blog      Running   5m30s",
+                "RESTARTED   blog-db   <none>    5m30s",
+            ]
+        );
+        let deleted = Event::Deleted(pod("blog", Some("Succeeded")));
+        assert_eq!(
+            printer.lines(&deleted, now).unwrap(),
+            ["DELETED     blog      Succeeded   5m30s"]
+        );
+    }
+
+    #[test]
+    fn prints_json_lines() {
+        let now = "2021-11-04T15:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut printer = Printer::new(Format::JsonLines)
+            .column(Column::new("STATUS", ".status.phase"))
+            .column(Column::age("AGE", ".metadata.creationTimestamp"));
+        let applied = Event::Applied(pod("blog", Some("Running")));
+        let lines = printer.lines(&applied, now).unwrap();
+        let line: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(
+            line,
+            json!({ "NAME": "blog", "STATUS": "Running", "AGE": "3d3h" })
+        );
+    }
+
+    #[test]
+    fn selects_json_path_filters() {
+        let now = "2021-11-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut printer = Printer::new(Format::Table)
+            .column(Column::new(
+                "READY",
+                r#".status.conditions[?(@.type=="Ready")].status"#,
+            ))
+            .column(Column::new("CONDITIONS", ".status.conditions[*].type"));
+        let pod: Pod = serde_json::from_value(json!({
+            "metadata": { "name": "blog" },
+            "status": { "conditions": [
+                { "type": "Initialized", "status": "True" },
+                { "type": "Ready", "status": "False" },
+            ] },
+        }))
+        .unwrap();
+        assert_eq!(printer.lines(&Event::Applied(pod), now).unwrap(), [
+            "NAME   READY   CONDITIONS",
+            "blog   False   Initialized Ready",
+        ]);
+    }
+}
@@ -0,0 +1,474 @@
+//! A size-bounded variant of the reflector [`Store`](super::Store)
+//!
+//! Caching every object of a kind in memory can be infeasible for huge resources (like `Secret`s in big clusters).
+//! The bounded [`Store`] only keeps a subset of the objects in memory, evicting the least recently used objects
+//! once the configured [`EvictionPolicy`] is exceeded, and refetches objects from the apiserver on a miss.
+use super::ObjectRef;
+use crate::watcher;
+use derivative::Derivative;
+use futures::{Stream, TryStreamExt};
+use kube_client::{Api, Client, Resource};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// When the bounded store starts evicting the least recently used objects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Keep at most this many objects
+    MaxObjects(usize),
+    /// Keep objects while their total JSON-serialized size does not exceed this many bytes
+    MaxBytes(usize),
+}
+
+/// Which objects are admitted into the bounded store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Cache every object seen by the watcher, as well as objects refetched on a miss
+    All,
+    /// Only cache objects that have been requested through [`Store::get`]
+    ///
+    /// Cached objects are still kept up to date by the watcher.
+    OnAccess,
+}
+
+struct Entry<K> {
+    obj: K,
+    last_used: u64,
+    size: usize,
+}
+
+// Refetches of an object that are in flight
+struct Refetch {
+    pending: usize,
+    // Whether the watcher changed or deleted the object since the refetches started,
+    // in which case the refetched object may be outdated and is not cached
+    watched: bool,
+}
+
+struct Cache<K: Resource>
+where
+    K::DynamicType: Eq + Hash,
+{
+    entries: HashMap<ObjectRef<K>, Entry<K>>,
+    refetching: HashMap<ObjectRef<K>, Refetch>,
+    // Keys by the tick they were last used at, oldest first
+    recency: BTreeMap<u64, ObjectRef<K>>,
+    tick: u64,
+    bytes: usize,
+    policy: EvictionPolicy,
+    admission: Admission,
+}
+
+impl<K: Resource + Clone + Serialize> Cache<K>
+where
+    K::DynamicType: Eq + Hash + Clone,
+{
+    fn new(policy: EvictionPolicy, admission: Admission) -> Self {
+        Self {
+            entries: HashMap::new(),
+            refetching: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            policy,
+            admission,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, key: &ObjectRef<K>) -> Option<K> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(tick, key.clone());
+        entry.last_used = tick;
+        Some(entry.obj.clone())
+    }
+
+    fn insert(&mut self, key: ObjectRef<K>, obj: K) {
+        self.remove(&key);
+        let size = match self.policy {
+            EvictionPolicy::MaxObjects(_) => 0,
+            EvictionPolicy::MaxBytes(_) => serde_json::to_vec(&obj).map_or(0, |data| data.len()),
+        };
+        let tick = self.next_tick();
+        self.bytes += size;
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(key, Entry {
+            obj,
+            last_used: tick,
+            size,
+        });
+        self.evict();
+    }
+
+    /// Apply an object from the watcher, respecting the admission policy
+    fn apply(&mut self, key: ObjectRef<K>, obj: K) {
+        let refetching = self.mark_watched(&key);
+        if self.admission == Admission::All || refetching || self.entries.contains_key(&key) {
+            self.insert(key, obj);
+        }
+    }
+
+    /// Delete an object seen deleted by the watcher
+    fn delete(&mut self, key: &ObjectRef<K>) {
+        self.mark_watched(key);
+        self.remove(key);
+    }
+
+    // Returns whether the object is being refetched
+    fn mark_watched(&mut self, key: &ObjectRef<K>) -> bool {
+        match self.refetching.get_mut(key) {
+            Some(refetch) => {
+                refetch.watched = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn begin_refetch(&mut self, key: &ObjectRef<K>) {
+        self.refetching
+            .entry(key.clone())
+            .or_insert(Refetch {
+                pending: 0,
+                watched: false,
+            })
+            .pending += 1;
+    }
+
+    /// Cache a refetched object, unless the watcher has seen it change or get deleted since the refetch started
+    fn insert_refetched(&mut self, key: ObjectRef<K>, obj: K) {
+        if matches!(self.refetching.get(&key), Some(refetch) if !refetch.watched) {
+            self.insert(key, obj);
+        }
+    }
+
+    fn end_refetch(&mut self, key: &ObjectRef<K>) {
+        if let Some(refetch) = self.refetching.get_mut(key) {
+            refetch.pending -= 1;
+            if refetch.pending == 0 {
+                self.refetching.remove(key);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &ObjectRef<K>) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.bytes -= entry.size;
+        }
+    }
+
+    fn is_over_limit(&self) -> bool {
+        match self.policy {
+            EvictionPolicy::MaxObjects(max) => self.entries.len() > max,
+            EvictionPolicy::MaxBytes(max) => self.bytes > max,
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.is_over_limit() {
+            let oldest = match self.recency.keys().next() {
+                Some(tick) => *tick,
+                None => return,
+            };
+            if let Some(key) = self.recency.remove(&oldest) {
+                if let Some(entry) = self.entries.remove(&key) {
+                    self.bytes -= entry.size;
+                }
+            }
+        }
+    }
+}
+
+fn lock<K: Resource>(cache: &Mutex<Cache<K>>) -> MutexGuard<'_, Cache<K>>
+where
+    K::DynamicType: Eq + Hash,
+{
+    // The cache is always left in a consistent state, so a poisoned lock is safe to reuse
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Ends a refetch when dropped, including when the refetch fails or is cancelled
+struct RefetchGuard<'a, K: Resource + Clone + Serialize>
+where
+    K::DynamicType: Eq + Hash + Clone,
+{
+    cache: &'a Mutex<Cache<K>>,
+    key: &'a ObjectRef<K>,
+}
+
+impl<'a, K: Resource + Clone + Serialize> RefetchGuard<'a, K>
+where
+    K::DynamicType: Eq + Hash + Clone,
+{
+    fn new(cache: &'a Mutex<Cache<K>>, key: &'a ObjectRef<K>) -> Self {
+        lock(cache).begin_refetch(key);
+        Self { cache, key }
+    }
+}
+
+impl<'a, K: Resource + Clone + Serialize> Drop for RefetchGuard<'a, K>
+where
+    K::DynamicType: Eq + Hash + Clone,
+{
+    fn drop(&mut self) {
+        lock(self.cache).end_refetch(self.key);
+    }
+}
+
+/// A writable handle to a bounded [`Store`]
+///
+/// Like [`store::Writer`](super::store::Writer), this is exclusive since it's not safe to share a single
+/// bounded store between multiple reflectors.
+pub struct Writer<K: 'static + Resource>
+where
+    K::DynamicType: Eq + Hash,
+{
+    cache: Arc<Mutex<Cache<K>>>,
+    client: Client,
+    dyntype: K::DynamicType,
+}
+
+impl<K: 'static + Resource + Clone + Serialize> Writer<K>
+where
+    K::DynamicType: Eq + Hash + Clone,
+{
+    /// Creates a new bounded Writer
+    ///
+    /// The `client` is used to refetch objects that are not cached.
+    #[must_use]
+    pub fn new(client: Client, dyntype: K::DynamicType, policy: EvictionPolicy, admission: Admission) -> Self {
+        Writer {
+            cache: Arc::new(Mutex::new(Cache::new(policy, admission))),
+            client,
+            dyntype,
+        }
+    }
+
+    /// Return a read handle to the bounded store
+    #[must_use]
+    pub fn as_reader(&self) -> Store<K> {
+        Store {
+            cache: self.cache.clone(),
+            client: self.client.clone(),
+            dyntype: self.dyntype.clone(),
+        }
+    }
+
+    /// Applies a single watcher event to the bounded store
+    pub fn apply_watcher_event(&mut self, event: &watcher::Event<K>) {
+        let mut cache = lock(&self.cache);
+        match event {
+            watcher::Event::Applied(obj) => {
+                cache.apply(ObjectRef::from_obj_with(obj, self.dyntype.clone()), obj.clone());
+            }
+            watcher::Event::Deleted(obj) => {
+                cache.delete(&ObjectRef::from_obj_with(obj, self.dyntype.clone()));
+            }
+            watcher::Event::Restarted(new_objs) => {
+                let new_objs = new_objs
+                    .iter()
+                    .map(|obj| (ObjectRef::from_obj_with(obj, self.dyntype.clone()), obj))
+                    .collect::<HashMap<_, _>>();
+                let stale = cache
+                    .entries
+                    .keys()
+                    .filter(|key| !new_objs.contains_key(key))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                // The relist supersedes all refetches in flight, including of objects that were deleted
+                for refetch in cache.refetching.values_mut() {
+                    refetch.watched = true;
+                }
+                for key in stale {
+                    cache.remove(&key);
+                }
+                for (key, obj) in new_objs {
+                    cache.apply(key, obj.clone());
+                }
+            }
+        }
+    }
+}
+
+/// A size-bounded, readable cache of Kubernetes objects of kind `K`
+///
+/// Cloning will produce a new reference to the same backing store.
+///
+/// Cannot be constructed directly since one writer handle is required,
+/// use `Writer::as_reader()` instead.
+#[derive(Derivative)]
+#[derivative(Clone(bound = "K::DynamicType: Clone"))]
+pub struct Store<K: 'static + Resource>
+where
+    K::DynamicType: Eq + Hash,
+{
+    cache: Arc<Mutex<Cache<K>>>,
+    client: Client,
+    dyntype: K::DynamicType,
+}
+
+impl<K> Store<K>
+where
+    K: 'static + Resource + Clone + Serialize + DeserializeOwned + Debug,
+    K::DynamicType: Eq + Hash + Clone,
+{
+    /// Retrieve a `clone()` of the entry referred to by `key`, refetching it from the apiserver if it is not cached.
+    ///
+    /// Refetched objects are cached (unless they exceed the [`EvictionPolicy`] on their own), unless the watcher
+    /// has seen the object change or get deleted during the refetch, so deleted objects are never cached again.
+    /// Returns `Ok(None)` if the object does not exist in the cluster.
+    ///
+    /// Like [`Store::get`](super::Store::get), cached objects may be stale.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the object is not cached and refetching it from the apiserver fails.
+    pub async fn get(&self, key: &ObjectRef<K>) -> kube_client::Result<Option<K>> {
+        if let Some(obj) = self.get_cached(key) {
+            return Ok(Some(obj));
+        }
+
+        let api = match &key.namespace {
+            Some(ns) => Api::<K>::namespaced_with(self.client.clone(), ns, &self.dyntype),
+            None => Api::<K>::all_with(self.client.clone(), &self.dyntype),
+        };
+        let refetch = RefetchGuard::new(&self.cache, key);
+        let obj = api.get_opt(&key.name).await?;
+        if let Some(obj) = &obj {
+            lock(&self.cache).insert_refetched(key.clone(), obj.clone());
+        }
+        drop(refetch);
+        Ok(obj)
+    }
+
+    /// Retrieve a `clone()` of the entry referred to by `key`, if it is in the cache.
+    ///
+    /// `key.namespace` is ignored for cluster-scoped resources.
+    #[must_use]
+    pub fn get_cached(&self, key: &ObjectRef<K>) -> Option<K> {
+        let mut cache = lock(&self.cache);
+        cache.get(key).or_else(|| {
+            // Try to erase the namespace and try again, in case the object is cluster-scoped
+            let mut cluster_key = key.clone();
+            cluster_key.namespace = None;
+            cache.get(&cluster_key)
+        })
+    }
+
+    /// Return a snapshot of the currently cached values
+    ///
+    /// Unlike [`Store::state`](super::Store::state), this is not a full snapshot of the cluster state.
+    #[must_use]
+    pub fn cached_state(&self) -> Vec<K> {
+        lock(&self.cache)
+            .entries
+            .values()
+            .map(|entry| entry.obj.clone())
+            .collect()
+    }
+}
+
+/// Caches objects from `watcher::Event`s to a bounded local [`Store`]
+///
+/// This is the bounded equivalent of [`reflector`](super::reflector).
+pub fn reflector<K, W>(mut writer: Writer<K>, stream: W) -> impl Stream<Item = W::Item>
+where
+    K: Resource + Clone + Serialize,
+    K::DynamicType: Eq + Hash + Clone,
+    W: Stream<Item = watcher::Result<watcher::Event<K>>>,
+{
+    stream.inspect_ok(move |event| writer.apply_watcher_event(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Admission, Cache, EvictionPolicy};
+    use crate::reflector::ObjectRef;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use kube_client::api::ObjectMeta;
+
+    fn cm(name: &str) -> ConfigMap {
+        ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        }
+    }
+
+    #[test]
+    fn should_evict_least_recently_used() {
+        let mut cache = Cache::new(EvictionPolicy::MaxObjects(2), Admission::All);
+        cache.apply(ObjectRef::from_obj(&cm("a")), cm("a"));
+        cache.apply(ObjectRef::from_obj(&cm("b")), cm("b"));
+        // Touch a, so that b is the least recently used
+        assert_eq!(cache.get(&ObjectRef::new("a")), Some(cm("a")));
+        cache.apply(ObjectRef::from_obj(&cm("c")), cm("c"));
+        assert_eq!(cache.get(&ObjectRef::new("b")), None);
+        assert_eq!(cache.get(&ObjectRef::new("a")), Some(cm("a")));
+        assert_eq!(cache.get(&ObjectRef::new("c")), Some(cm("c")));
+    }
+
+    #[test]
+    fn should_only_update_accessed_objects_on_access_admission() {
+        let mut cache = Cache::new(EvictionPolicy::MaxObjects(10), Admission::OnAccess);
+        cache.apply(ObjectRef::from_obj(&cm("a")), cm("a"));
+        assert_eq!(cache.get(&ObjectRef::new("a")), None);
+        cache.insert(ObjectRef::from_obj(&cm("a")), cm("a"));
+        cache.apply(ObjectRef::from_obj(&cm("a")), cm("a"));
+        assert_eq!(cache.get(&ObjectRef::new("a")), Some(cm("a")));
+    }
+
+    #[test]
+    fn should_not_cache_refetched_objects_changed_by_the_watcher() {
+        let mut cache = Cache::new(EvictionPolicy::MaxObjects(10), Admission::OnAccess);
+        let key = ObjectRef::from_obj(&cm("a"));
+        cache.begin_refetch(&key);
+        cache.insert_refetched(key.clone(), cm("a"));
+        cache.end_refetch(&key);
+        assert_eq!(cache.get(&key), Some(cm("a")));
+
+        // The object is deleted while it is refetched
+        cache.delete(&key);
+        cache.begin_refetch(&key);
+        cache.delete(&key);
+        cache.insert_refetched(key.clone(), cm("a"));
+        cache.end_refetch(&key);
+        assert_eq!(cache.get(&key), None);
+        assert!(cache.refetching.is_empty());
+
+        // The watcher's version is newer than the refetched one, and is cached even though it was not accessed yet
+        let mut updated = cm("a");
+        updated.data = Some([("key".to_string(), "value".to_string())].into());
+        cache.begin_refetch(&key);
+        cache.apply(key.clone(), updated.clone());
+        cache.insert_refetched(key.clone(), cm("a"));
+        cache.end_refetch(&key);
+        assert_eq!(cache.get(&key), Some(updated));
+    }
+
+    #[test]
+    fn should_evict_by_size() {
+        let size = serde_json::to_vec(&cm("a")).unwrap().len();
+        let mut cache = Cache::new(EvictionPolicy::MaxBytes(size * 2), Admission::All);
+        cache.apply(ObjectRef::from_obj(&cm("a")), cm("a"));
+        cache.apply(ObjectRef::from_obj(&cm("b")), cm("b"));
+        cache.apply(ObjectRef::from_obj(&cm("c")), cm("c"));
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.bytes, size * 2);
+        assert_eq!(cache.get(&ObjectRef::new("a")), None);
+    }
+}
@@ -0,0 +1,241 @@
+//! Tracks the rollout of a `DaemonSet` node by node, from the [`Store`]s of its pods and the nodes
+//!
+//! This lets infrastructure operators coordinate node-by-node changes, such as only draining nodes
+//! that already run the updated daemon.
+use super::Store;
+use k8s_openapi::{
+    api::{
+        apps::v1::{ControllerRevision, DaemonSet},
+        core::v1::{Node, Pod},
+    },
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+};
+use kube_client::ResourceExt;
+use std::collections::BTreeMap;
+
+const REVISION_HASH_LABEL: &str = "controller-revision-hash";
+const TEMPLATE_GENERATION_LABEL: &str = "pod-template-generation";
+
+/// The rollout state of a `DaemonSet` on a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeRolloutState {
+    /// The node runs a ready pod of the current revision
+    Updated,
+    /// The node runs a pod of the current revision that is not ready yet
+    Updating,
+    /// The node still runs a pod of an older revision
+    Outdated,
+    /// The node runs no pod of the `DaemonSet`
+    Missing,
+}
+
+/// The rollout of a `DaemonSet` on a node, see [`node_rollout`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeRollout {
+    /// The name of the node
+    pub node: String,
+    /// The rollout state on the node
+    pub state: NodeRolloutState,
+    /// The name of the pod of the `DaemonSet` on the node, if any
+    pub pod: Option<String>,
+}
+
+/// Maps the rollout of `ds` on every node it selects, ordered by node name
+///
+/// Pods of `ds` are joined to `nodes` by their `spec.nodeName`. A pod is of the current revision if its
+/// `controller-revision-hash` label matches the latest `ControllerRevision` of `ds` in `revisions`, like the
+/// `DaemonSet` controller decides. Without revisions of `ds`, such as before they are listed, a pod is
+/// considered current if its `pod-template-generation` label matches the generation of `ds` instead.
+///
+/// Nodes are selected by the `nodeSelector` of the pod template. Taints and node affinity are not evaluated,
+/// so nodes that `ds` cannot run on may be reported as [`NodeRolloutState::Missing`].
+#[must_use]
+pub fn node_rollout(
+    ds: &DaemonSet,
+    pods: &Store<Pod>,
+    nodes: &Store<Node>,
+    revisions: &Store<ControllerRevision>,
+) -> Vec<NodeRollout> {
+    rollout_of(ds, &pods.state(), &nodes.state(), &revisions.state())
+}
+
+fn rollout_of(
+    ds: &DaemonSet,
+    pods: &[Pod],
+    nodes: &[Node],
+    revisions: &[ControllerRevision],
+) -> Vec<NodeRollout> {
+    let uid = ds.uid();
+    let is_controlled = |owners: &[OwnerReference]| {
+        owners
+            .iter()
+            .any(|owner| owner.controller == Some(true) && Some(&owner.uid) == uid.as_ref())
+    };
+    let current_label = revisions
+        .iter()
+        .filter(|revision| is_controlled(revision.owner_references()))
+        .max_by_key(|revision| revision.revision)
+        .and_then(|revision| revision.labels().get(REVISION_HASH_LABEL).cloned())
+        .map(|hash| (REVISION_HASH_LABEL, hash))
+        .or_else(|| {
+            let generation = ds.metadata.generation?;
+            Some((TEMPLATE_GENERATION_LABEL, generation.to_string()))
+        });
+    let node_selector = ds
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.spec.as_ref())
+        .and_then(|spec| spec.node_selector.clone())
+        .unwrap_or_default();
+
+    let mut pods_by_node = BTreeMap::new();
+    for pod in pods {
+        let owned = is_controlled(pod.owner_references());
+        let node = pod.spec.as_ref().and_then(|spec| spec.node_name.as_ref());
+        if let (true, Some(node)) = (owned, node) {
+            // Prefer the current revision while an old pod is terminating
+            let current = current_label
+                .as_ref()
+                .map_or(false, |(key, value)| pod.labels().get(*key) == Some(value));
+            let replace = pods_by_node
+                .get(node)
+                .map_or(true, |(was_current, _)| current && !was_current);
+            if replace {
+                pods_by_node.insert(node.clone(), (current, pod));
+            }
+        }
+    }
+
+    let mut rollout = nodes
+        .iter()
+        .filter(|node| {
+            node_selector
+                .iter()
+                .all(|(key, value)| node.labels().get(key) == Some(value))
+        })
+        .map(|node| {
+            let name = node.name();
+            let (state, pod) = match pods_by_node.get(&name) {
+                Some((true, pod)) if is_ready(pod) => (NodeRolloutState::Updated, Some(pod.name())),
+                Some((true, pod)) => (NodeRolloutState::Updating, Some(pod.name())),
+                Some((false, pod)) => (NodeRolloutState::Outdated, Some(pod.name())),
+                None => (NodeRolloutState::Missing, None),
+            };
+            NodeRollout {
+                node: name,
+                state,
+                pod,
+            }
+        })
+        .collect::<Vec<_>>();
+    rollout.sort_by(|a, b| a.node.cmp(&b.node));
+    rollout
+}
+
+fn is_ready(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .map_or(false, |conditions| {
+            conditions
+                .iter()
+                .any(|c| c.type_ == "Ready" && c.status == "True")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rollout_of, NodeRollout, NodeRolloutState};
+    use k8s_openapi::api::{
+        apps::v1::{ControllerRevision, DaemonSet},
+        core::v1::{Node, Pod},
+    };
+    use serde_json::json;
+
+    fn node(name: &str, role: &str) -> Node {
+        serde_json::from_value(json!({ "metadata": { "name": name, "labels": { "role": role } } })).unwrap()
+    }
+
+    fn owner() -> serde_json::Value {
+        json!([{
+            "apiVersion": "apps/v1",
+            "kind": "DaemonSet",
+            "name": "agent",
+            "uid": "ds",
+            "controller": true,
+        }])
+    }
+
+    fn revision(hash: &str, revision: i64) -> ControllerRevision {
+        serde_json::from_value(json!({
+            "metadata": {
+                "name": format!("agent-{}", hash),
+                "labels": { "controller-revision-hash": hash },
+                "ownerReferences": owner(),
+            },
+            "revision": revision,
+        }))
+        .unwrap()
+    }
+
+    fn pod(name: &str, node: &str, hash: &str, ready: bool) -> Pod {
+        serde_json::from_value(json!({
+            "metadata": {
+                "name": name,
+                // The generation also changes when only the metadata of the daemonset does
+                "labels": { "controller-revision-hash": hash, "pod-template-generation": "1" },
+                "ownerReferences": owner(),
+            },
+            "spec": { "nodeName": node, "containers": [] },
+            "status": { "conditions": [{ "type": "Ready", "status": if ready { "True" } else { "False" } }] },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn maps_rollout_per_node() {
+        let ds: DaemonSet = serde_json::from_value(json!({
+            "metadata": { "name": "agent", "uid": "ds", "generation": 2 },
+            "spec": {
+                "selector": {},
+                "template": { "spec": { "nodeSelector": { "role": "worker" }, "containers": [] } },
+            },
+        }))
+        .unwrap();
+        let nodes = ["a", "b", "c", "d"].map(|name| node(name, "worker"));
+        let nodes = [&nodes[..], &[node("control-plane", "master")]].concat();
+        let pods = [
+            pod("agent-a", "a", "new", true),
+            pod("agent-b", "b", "new", false),
+            pod("agent-c-old", "c", "old", true),
+            pod("agent-a-old", "a", "old", true),
+        ];
+        let revisions = [revision("new", 3), revision("old", 2)];
+        let rollout = rollout_of(&ds, &pods, &nodes, &revisions);
+        let entry = |node: &str, state, pod: Option<&str>| NodeRollout {
+            node: node.into(),
+            state,
+            pod: pod.map(Into::into),
+        };
+        assert_eq!(
+            rollout,
+            vec![
+                entry("a", NodeRolloutState::Updated, Some("agent-a")),
+                entry("b", NodeRolloutState::Updating, Some("agent-b")),
+                entry("c", NodeRolloutState::Outdated, Some("agent-c-old")),
+                entry("d", NodeRolloutState::Missing, None),
+            ]
+        );
+
+        // Without revisions, all pods match the generation of the daemonset they were created with
+        let ds = DaemonSet {
+            metadata: kube_client::core::ObjectMeta {
+                generation: Some(1),
+                ..ds.metadata
+            },
+            ..ds
+        };
+        let rollout = rollout_of(&ds, &pods, &nodes, &[]);
+        assert_eq!(rollout[2], entry("c", NodeRolloutState::Updated, Some("agent-c-old")));
+    }
+}
@@ -1,9 +1,18 @@
 //! Caches objects in memory
 
 mod object_ref;
+mod projection;
+mod secret_summary;
+pub mod bounded;
+pub mod daemonset;
 pub mod store;
+pub mod ttl;
 
-pub use self::object_ref::ObjectRef;
+pub use self::{
+    object_ref::ObjectRef,
+    projection::Projection,
+    secret_summary::{secret_summary_watcher, SecretSummary},
+};
 use crate::watcher;
 use futures::{Stream, TryStreamExt};
 use kube_client::Resource;
@@ -25,12 +34,56 @@ where
     stream.inspect_ok(move |event| store.apply_watcher_event(event))
 }
 
+/// Applies `transform` to all objects in `watcher::Event`s, before they are cached by a [`reflector`]
+///
+/// This can be used to reduce the memory used by the `Store`, for example by stripping fields that aren't needed
+/// (see [`strip_managed_fields`]), or by projecting objects into a smaller type (which must also implement `Resource`).
+///
+/// Keep in mind that the transformed objects are also what is emitted to the rest of the stream.
+///
+/// ```no_run
+/// use k8s_openapi::api::core::v1::Secret;
+/// use kube::{api::ListParams, Api, Client};
+/// use kube_runtime::{reflector, reflector::store, watcher};
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let writer = store::Writer::<Secret>::default();
+/// let stream = reflector::transform(
+///     watcher(Api::all(client), ListParams::default()),
+///     reflector::strip_managed_fields,
+/// );
+/// let stream = reflector(writer, stream);
+/// # Ok(())
+/// # }
+/// ```
+pub fn transform<K, T, W>(
+    stream: W,
+    mut transform: impl FnMut(K) -> T,
+) -> impl Stream<Item = watcher::Result<watcher::Event<T>>>
+where
+    W: Stream<Item = watcher::Result<watcher::Event<K>>>,
+{
+    stream.map_ok(move |event| event.map(&mut transform))
+}
+
+/// Removes the `managedFields` of an object, which usually make up a large part of its size
+///
+/// Intended to be used with [`transform`].
+#[must_use]
+pub fn strip_managed_fields<K: Resource>(mut obj: K) -> K {
+    obj.meta_mut().managed_fields = None;
+    obj
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{reflector, store, ObjectRef};
+    use super::{reflector, store, strip_managed_fields, transform, ObjectRef, Projection};
     use crate::watcher;
     use futures::{stream, StreamExt, TryStreamExt};
-    use k8s_openapi::{api::core::v1::ConfigMap, apimachinery::pkg::apis::meta::v1::ObjectMeta};
+    use k8s_openapi::{
+        api::core::v1::ConfigMap,
+        apimachinery::pkg::apis::meta::v1::{ManagedFieldsEntry, ObjectMeta},
+    };
     use rand::{
         distributions::{Bernoulli, Uniform},
         Rng,
@@ -146,6 +199,66 @@ mod tests {
         assert_eq!(store.get(&ObjectRef::from_obj(&cm_b)), Some(cm_b));
     }
 
+    #[tokio::test]
+    async fn reflector_should_cache_transformed_objects() {
+        let store_w = store::Writer::default();
+        let store = store_w.as_reader();
+        let cm = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some("a".to_string()),
+                managed_fields: Some(vec![ManagedFieldsEntry::default()]),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        };
+        reflector(
+            store_w,
+            transform(
+                stream::iter(vec![Ok(watcher::Event::Applied(cm.clone()))]),
+                strip_managed_fields,
+            ),
+        )
+        .map(|_| ())
+        .collect::<()>()
+        .await;
+        let cached = store.get(&ObjectRef::from_obj(&cm)).unwrap();
+        assert_eq!(cached.metadata.name, cm.metadata.name);
+        assert_eq!(cached.metadata.managed_fields, None);
+    }
+
+    #[tokio::test]
+    async fn reflector_should_cache_projections() {
+        let store_w = store::Writer::<Projection<ConfigMap, usize>>::default();
+        let store = store_w.as_reader();
+        let cm = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some("a".to_string()),
+                namespace: Some("ns".to_string()),
+                labels: Some(BTreeMap::new()),
+                ..ObjectMeta::default()
+            },
+            data: Some({
+                let mut data = BTreeMap::new();
+                data.insert("data".to_string(), "present!".to_string());
+                data
+            }),
+            ..ConfigMap::default()
+        };
+        reflector(
+            store_w,
+            transform(
+                stream::iter(vec![Ok(watcher::Event::Applied(cm.clone()))]),
+                |cm: ConfigMap| Projection::new(&cm, cm.data.as_ref().map_or(0, BTreeMap::len)),
+            ),
+        )
+        .map(|_| ())
+        .collect::<()>()
+        .await;
+        let cached = store.get(&ObjectRef::new("a").within("ns")).unwrap();
+        assert_eq!(cached.data, 1);
+        assert_eq!(cached.metadata.labels, None);
+    }
+
     #[tokio::test]
     async fn reflector_store_should_not_contain_duplicates() {
         let mut rng = rand::thread_rng();
@@ -0,0 +1,80 @@
+use derivative::Derivative;
+use kube_client::api::{ObjectMeta, Resource};
+use std::{borrow::Cow, fmt::Debug, marker::PhantomData};
+
+#[derive(Derivative)]
+#[derivative(
+    Debug(bound = "S: Debug"),
+    PartialEq(bound = "S: PartialEq"),
+    Clone(bound = "S: Clone")
+)]
+/// A compact projection `S` of an object of kind `K`
+///
+/// Only keeps the metadata required to identify the object, so that a [`Store`](super::Store)
+/// can cache projections that don't implement [`Resource`] themselves.
+///
+/// ```
+/// use k8s_openapi::api::core::v1::Pod;
+/// use kube_runtime::reflector::Projection;
+/// # let pod = Pod::default();
+/// let phase = Projection::new(&pod, pod.status.as_ref().and_then(|status| status.phase.clone()));
+/// ```
+pub struct Projection<K, S> {
+    /// The name, namespace, uid, and resource version of the projected object
+    pub metadata: ObjectMeta,
+    /// The projected data
+    pub data: S,
+    #[derivative(Debug = "ignore")]
+    _kind: PhantomData<fn() -> K>,
+}
+
+impl<K: Resource, S> Projection<K, S> {
+    /// Projects `obj` to `data`, keeping only the metadata that identifies `obj`
+    #[must_use]
+    pub fn new(obj: &K, data: S) -> Self {
+        let meta = obj.meta();
+        Projection {
+            metadata: ObjectMeta {
+                name: meta.name.clone(),
+                namespace: meta.namespace.clone(),
+                uid: meta.uid.clone(),
+                resource_version: meta.resource_version.clone(),
+                ..ObjectMeta::default()
+            },
+            data,
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<K: Resource, S> Resource for Projection<K, S> {
+    type DynamicType = K::DynamicType;
+
+    fn kind(dt: &Self::DynamicType) -> Cow<'_, str> {
+        K::kind(dt)
+    }
+
+    fn group(dt: &Self::DynamicType) -> Cow<'_, str> {
+        K::group(dt)
+    }
+
+    fn version(dt: &Self::DynamicType) -> Cow<'_, str> {
+        K::version(dt)
+    }
+
+    fn api_version(dt: &Self::DynamicType) -> Cow<'_, str> {
+        K::api_version(dt)
+    }
+
+    fn plural(dt: &Self::DynamicType) -> Cow<'_, str> {
+        K::plural(dt)
+    }
+
+    fn meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn meta_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
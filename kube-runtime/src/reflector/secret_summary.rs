@@ -0,0 +1,145 @@
+use super::Projection;
+use crate::watcher::{self, watcher_map};
+use futures::Stream;
+use k8s_openapi::api::core::v1::Secret;
+use kube_client::{api::ListParams, Api};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// The keys of a `Secret` and the hashes of their values, without the values themselves
+///
+/// Lets controllers that only need to notice changes to Secrets avoid keeping their data in memory.
+/// The hashes are unsalted SHA-256, so values that are easy to guess can be recovered from them,
+/// and summaries should be kept as private as the Secrets themselves.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct SecretSummary {
+    /// The type of the Secret, like `kubernetes.io/tls`
+    pub type_: Option<String>,
+    /// The hex encoded SHA-256 hashes of the values, by key
+    pub hashes: BTreeMap<String, String>,
+}
+
+impl SecretSummary {
+    /// Summarizes `secret`, dropping its data
+    ///
+    /// Only the name, namespace, uid and resource version of the metadata are kept,
+    /// since annotations like `kubectl.kubernetes.io/last-applied-configuration` may contain the data.
+    #[must_use]
+    pub fn project(secret: Secret) -> Projection<Secret, Self> {
+        let hashes = secret
+            .data
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), hash(&value.0)))
+            .collect();
+        let summary = SecretSummary {
+            type_: secret.type_.clone(),
+            hashes,
+        };
+        Projection::new(&secret, summary)
+    }
+
+    /// The keys that were added, removed or changed between `previous` and `self`
+    #[must_use]
+    pub fn changed_keys<'a>(&'a self, previous: &'a Self) -> Vec<&'a str> {
+        let mut changed = self
+            .hashes
+            .iter()
+            .filter(|(key, hash)| previous.hashes.get(*key) != Some(*hash))
+            .map(|(key, _)| key.as_str())
+            .chain(
+                previous
+                    .hashes
+                    .keys()
+                    .filter(|key| !self.hashes.contains_key(*key))
+                    .map(String::as_str),
+            )
+            .collect::<Vec<_>>();
+        changed.sort_unstable();
+        changed
+    }
+}
+
+fn hash(value: &[u8]) -> String {
+    Sha256::digest(value)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+/// Watches Secrets like [`watcher`](crate::watcher()), summarizing them as soon as they are received
+///
+/// The data of every Secret is dropped right after hashing it, so it is neither emitted nor
+/// kept in the [`Store`](super::Store) of a [`reflector`](super::reflector) fed by this stream.
+/// Every change to a Secret is still emitted, compare the summaries to see whether its data changed.
+///
+/// ```no_run
+/// use futures::{StreamExt, TryStreamExt};
+/// use k8s_openapi::api::core::v1::Secret;
+/// use kube::{api::{Api, ListParams}, Client};
+/// use kube_runtime::{reflector::{self, secret_summary_watcher, store}, utils::try_flatten_applied};
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let writer = store::Writer::default();
+/// let summaries = writer.as_reader();
+/// let stream = secret_summary_watcher(Api::<Secret>::all(client), ListParams::default());
+/// let mut applied = try_flatten_applied(reflector::reflector(writer, stream)).boxed();
+/// while let Some(secret) = applied.try_next().await? {
+///     println!("{:?} has keys {:?}", secret.metadata.name, secret.data.hashes.keys());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn secret_summary_watcher(
+    api: Api<Secret>,
+    list_params: ListParams,
+) -> impl Stream<Item = watcher::Result<watcher::Event<Projection<Secret, SecretSummary>>>> + Send {
+    watcher_map(api, list_params, SecretSummary::project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretSummary;
+    use k8s_openapi::{api::core::v1::Secret, apimachinery::pkg::apis::meta::v1::ObjectMeta, ByteString};
+    use std::collections::BTreeMap;
+
+    fn secret(data: &[(&str, &str)]) -> Secret {
+        Secret {
+            metadata: ObjectMeta {
+                name: Some("db".into()),
+                annotations: Some(BTreeMap::from([(
+                    "note".to_string(),
+                    "password=hunter2".to_string(),
+                )])),
+                ..ObjectMeta::default()
+            },
+            data: Some(
+                data.iter()
+                    .map(|(key, value)| (key.to_string(), ByteString(value.as_bytes().to_vec())))
+                    .collect(),
+            ),
+            ..Secret::default()
+        }
+    }
+
+    #[test]
+    fn summaries_only_keep_hashes() {
+        let summary = SecretSummary::project(secret(&[("user", "admin"), ("password", "hunter2")]));
+        assert_eq!(summary.metadata.name.as_deref(), Some("db"));
+        assert_eq!(summary.metadata.annotations, None);
+        assert_eq!(
+            summary.data.hashes["password"],
+            "f52fbd32b2b3b86ff88ef6c490628285f482af15ddcb29541f94bcf526a3f6c7"
+        );
+
+        let changed = SecretSummary::project(secret(&[("password", "hunter3"), ("token", "x")]));
+        assert_eq!(
+            changed.data.changed_keys(&summary.data),
+            ["password", "token", "user"]
+        );
+        assert!(summary.data.changed_keys(&summary.data).is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+//! Tracks how long objects have been in a state, and emits them once they exceed a deadline
+//!
+//! Driven by `watcher::Event`s, this is useful for alerting and remediation, such as finding pods that have been
+//! `Pending` for more than ten minutes.
+use super::ObjectRef;
+use crate::watcher;
+use futures::{stream::Fuse, Stream, StreamExt};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube_client::Resource;
+use pin_project::pin_project;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tokio::time::{self, Instant};
+use tokio_util::time::delay_queue::{self, DelayQueue};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("watcher error: {0}")]
+    Watcher(#[source] watcher::Error),
+    #[error("timer failure: {0}")]
+    TimerError(#[source] time::error::Error),
+}
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+struct Tracked<K> {
+    obj: K,
+    deadline: DateTime<Utc>,
+    // `None` once the object has been emitted for `deadline`
+    queue_key: Option<delay_queue::Key>,
+}
+
+/// Stream of objects that exceeded their deadline, see [`tracker`]
+#[pin_project]
+#[must_use = "streams do nothing unless polled"]
+pub struct Tracker<K, W, F>
+where
+    K: Resource,
+    K::DynamicType: Eq + Hash,
+{
+    #[pin]
+    stream: Fuse<W>,
+    deadline: F,
+    dyntype: K::DynamicType,
+    queue: DelayQueue<ObjectRef<K>>,
+    tracked: HashMap<ObjectRef<K>, Tracked<K>>,
+}
+
+impl<K, W, F> Tracker<K, W, F>
+where
+    K: Resource + Clone,
+    K::DynamicType: Eq + Hash + Clone,
+    F: FnMut(&K) -> Option<DateTime<Utc>>,
+{
+    fn new(stream: W, dyntype: K::DynamicType, deadline: F) -> Self
+    where
+        W: Stream,
+    {
+        Self {
+            stream: stream.fuse(),
+            deadline,
+            dyntype,
+            queue: DelayQueue::new(),
+            tracked: HashMap::new(),
+        }
+    }
+}
+
+fn track<K, F>(
+    queue: &mut DelayQueue<ObjectRef<K>>,
+    tracked: &mut HashMap<ObjectRef<K>, Tracked<K>>,
+    deadline_of: &mut F,
+    key: ObjectRef<K>,
+    obj: K,
+) where
+    K: Resource,
+    K::DynamicType: Eq + Hash + Clone,
+    F: FnMut(&K) -> Option<DateTime<Utc>>,
+{
+    let deadline = match deadline_of(&obj) {
+        Some(deadline) => deadline,
+        None => return untrack(queue, tracked, &key),
+    };
+    match tracked.get_mut(&key) {
+        // Keep the schedule (or don't emit again), but remember the latest version of the object
+        Some(entry) if entry.deadline == deadline => entry.obj = obj,
+        _ => {
+            untrack(queue, tracked, &key);
+            let run_at = Instant::now() + (deadline - Utc::now()).to_std().unwrap_or_default();
+            let queue_key = queue.insert_at(key.clone(), run_at);
+            tracked.insert(key, Tracked {
+                obj,
+                deadline,
+                queue_key: Some(queue_key),
+            });
+        }
+    }
+}
+
+fn untrack<K>(
+    queue: &mut DelayQueue<ObjectRef<K>>,
+    tracked: &mut HashMap<ObjectRef<K>, Tracked<K>>,
+    key: &ObjectRef<K>,
+) where
+    K: Resource,
+    K::DynamicType: Eq + Hash,
+{
+    if let Some(Tracked {
+        queue_key: Some(queue_key),
+        ..
+    }) = tracked.remove(key)
+    {
+        queue.remove(&queue_key);
+    }
+}
+
+impl<K, W, F> Stream for Tracker<K, W, F>
+where
+    K: Resource + Clone,
+    K::DynamicType: Eq + Hash + Clone,
+    W: Stream<Item = watcher::Result<watcher::Event<K>>>,
+    F: FnMut(&K) -> Option<DateTime<Utc>>,
+{
+    type Item = Result<K>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(event))) => match event {
+                    watcher::Event::Applied(obj) => {
+                        let key = ObjectRef::from_obj_with(&obj, this.dyntype.clone());
+                        track(this.queue, this.tracked, this.deadline, key, obj);
+                    }
+                    watcher::Event::Deleted(obj) => {
+                        let key = ObjectRef::from_obj_with(&obj, this.dyntype.clone());
+                        untrack(this.queue, this.tracked, &key);
+                    }
+                    watcher::Event::Restarted(objs) => {
+                        let keys = objs
+                            .iter()
+                            .map(|obj| ObjectRef::from_obj_with(obj, this.dyntype.clone()))
+                            .collect::<Vec<_>>();
+                        let listed = keys.iter().collect::<HashSet<_>>();
+                        let gone = this
+                            .tracked
+                            .keys()
+                            .filter(|key| !listed.contains(key))
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        for key in gone {
+                            untrack(this.queue, this.tracked, &key);
+                        }
+                        for (key, obj) in keys.into_iter().zip(objs) {
+                            track(this.queue, this.tracked, this.deadline, key, obj);
+                        }
+                    }
+                },
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(Error::Watcher(err)))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => break,
+            }
+        }
+
+        loop {
+            match this.queue.poll_expired(cx) {
+                Poll::Ready(Some(Ok(expired))) => {
+                    if let Some(entry) = this.tracked.get_mut(expired.get_ref()) {
+                        entry.queue_key = None;
+                        return Poll::Ready(Some(Ok(entry.obj.clone())));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(Error::TimerError(err)))),
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Emits objects from `watcher::Event`s once they pass the deadline returned by `deadline`
+///
+/// `deadline` is evaluated for every version of an object. Objects are tracked while it returns a deadline,
+/// and emitted (at most once per deadline) if they are still tracked when it passes.
+/// Objects are untracked once `deadline` returns `None` for them, or they are deleted.
+/// The latest version of the object is emitted.
+///
+/// The stream ends when the watcher stream ends.
+///
+/// ```no_run
+/// use k8s_openapi::{api::core::v1::Pod, chrono::Duration};
+/// use kube::{api::ListParams, Api, Client};
+/// use kube_runtime::{reflector::ttl, watcher};
+/// use futures::TryStreamExt;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let pods = watcher(Api::<Pod>::all(client), ListParams::default());
+/// let mut stuck = ttl::tracker(pods, |pod: &Pod| {
+///     let pending = pod.status.as_ref()?.phase.as_deref() == Some("Pending");
+///     let created = pod.metadata.creation_timestamp.as_ref()?.0;
+///     pending.then(|| created + Duration::minutes(10))
+/// });
+/// while let Some(pod) = stuck.try_next().await? {
+///     println!("{:?} has been pending for more than 10 minutes", pod.metadata.name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn tracker<K, W, F>(stream: W, deadline: F) -> Tracker<K, W, F>
+where
+    K: Resource + Clone,
+    K::DynamicType: Eq + Hash + Clone + Default,
+    W: Stream<Item = watcher::Result<watcher::Event<K>>>,
+    F: FnMut(&K) -> Option<DateTime<Utc>>,
+{
+    Tracker::new(stream, K::DynamicType::default(), deadline)
+}
+
+/// Like [`tracker`], for resources with a dynamic type, such as `DynamicObject`
+pub fn tracker_with<K, W, F>(stream: W, dyntype: K::DynamicType, deadline: F) -> Tracker<K, W, F>
+where
+    K: Resource + Clone,
+    K::DynamicType: Eq + Hash + Clone,
+    W: Stream<Item = watcher::Result<watcher::Event<K>>>,
+    F: FnMut(&K) -> Option<DateTime<Utc>>,
+{
+    Tracker::new(stream, dyntype, deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tracker;
+    use crate::watcher;
+    use futures::{stream, StreamExt, TryStreamExt};
+    use k8s_openapi::{
+        api::core::v1::ConfigMap,
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+        chrono::{Duration, Utc},
+    };
+    use std::collections::BTreeMap;
+
+    fn cm(name: &str, deadline: Option<Duration>) -> ConfigMap {
+        ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..ObjectMeta::default()
+            },
+            data: deadline.map(|d| {
+                let mut data = BTreeMap::new();
+                data.insert("deadline".to_string(), (Utc::now() + d).to_rfc3339());
+                data
+            }),
+            ..ConfigMap::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn tracker_emits_expired_objects_once() {
+        let expired = cm("expired", Some(Duration::seconds(-1)));
+        let events = vec![
+            watcher::Event::Applied(expired.clone()),
+            watcher::Event::Applied(cm("later", Some(Duration::hours(1)))),
+            watcher::Event::Applied(cm("deleted", Some(Duration::seconds(-1)))),
+            watcher::Event::Deleted(cm("deleted", None)),
+            watcher::Event::Applied(cm("resolved", Some(Duration::seconds(-1)))),
+            watcher::Event::Applied(cm("resolved", None)),
+        ];
+        let mut emitted = tracker(
+            stream::iter(events.into_iter().map(Ok)).chain(stream::pending()),
+            |cm: &ConfigMap| cm.data.as_ref()?.get("deadline")?.parse().ok(),
+        );
+        let first = emitted.try_next().await.unwrap().unwrap();
+        assert_eq!(first.metadata.name, expired.metadata.name);
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(100), emitted.next())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Generating and rotating Secrets owned by a controller, such as certificates and passwords
+//!
+//! A [`Rotator`] keeps a Secret generated by a controller fresh:
+//!
+//! - the data is versioned, every key `k` is also written as `k.v{version}`, and the previous versions are kept
+//!   for a while, so that consumers can accept both the old and the new credentials during a rotation
+//! - the Secret is rotated once the [`Policy::interval`] since the last rotation has passed
+//! - updates are atomic, a rotation replaces the Secret at the `resourceVersion` it was generated from,
+//!   so concurrent rotations conflict rather than overwrite each other
+//! - consumers are notified by annotating them with the new version, which rolls out workloads
+//!   when annotating their pod template
+//!
+//! [`Rotator::reconcile`] is meant to be called from the `reconcile` function of a
+//! [`Controller`](crate::Controller) that [`owns`](crate::Controller::owns) the Secrets,
+//! and requeues with [`Outcome::reconciler_action`] for the next rotation.
+//!
+//! ```no_run
+//! use k8s_openapi::{api::{apps::v1::Deployment, core::v1::Secret}, chrono::Duration, ByteString};
+//! use kube::{api::Api, runtime::rotation::{self, Notify, Policy, Rotator}, Client};
+//! use std::collections::BTreeMap;
+//! # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = Client::try_default().await?;
+//! let secrets = Api::<Secret>::namespaced(client.clone(), "apps");
+//! let rotator = Rotator::new(secrets, Policy::new(Duration::days(30)));
+//! let outcome = rotator
+//!     .reconcile("db-password", None, |version| {
+//!         let password = format!("generated-password-{}", version); // use a real generator
+//!         Ok::<_, std::convert::Infallible>(BTreeMap::from([(
+//!             "password".to_string(),
+//!             ByteString(password.into_bytes()),
+//!         )]))
+//!     })
+//!     .await?;
+//! if outcome.rotated {
+//!     let deployments = Api::<Deployment>::namespaced(client, "apps");
+//!     rotation::notify(&deployments, "db", &outcome, Notify::PodTemplate).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::BTreeMap;
+
+use k8s_openapi::{
+    api::core::v1::Secret,
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+    chrono::{DateTime, Duration, Utc},
+    ByteString,
+};
+use kube_client::api::{Api, ObjectMeta, Patch, PatchParams, PostParams, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::controller::ReconcilerAction;
+
+/// Annotation with the version of a rotated Secret, starting at 1
+pub const VERSION_ANNOTATION: &str = "rotation.kube.rs/version";
+/// Annotation with the time a Secret was last rotated, in RFC 3339
+pub const ROTATED_AT_ANNOTATION: &str = "rotation.kube.rs/rotated-at";
+/// Prefix of the annotations consumers are notified with, followed by the name of the Secret
+pub const CONSUMER_ANNOTATION_PREFIX: &str = "secrets.rotation.kube.rs/";
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to get secret: {0}")]
+    GetSecret(#[source] kube_client::Error),
+    #[error("failed to generate secret data: {0}")]
+    Generate(#[source] BoxError),
+    #[error("failed to write secret: {0}")]
+    WriteSecret(#[source] kube_client::Error),
+    #[error("failed to notify consumer: {0}")]
+    NotifyConsumer(#[source] kube_client::Error),
+}
+
+/// How often Secrets are rotated, and how many previous versions are kept
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    /// The time between rotations
+    pub interval: Duration,
+    /// The number of previous versions kept next to the current one
+    pub keep_versions: u64,
+}
+
+impl Policy {
+    /// Rotates every `interval`, keeping the previous version
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            keep_versions: 1,
+        }
+    }
+
+    /// Keeps `versions` previous versions next to the current one
+    #[must_use]
+    pub fn keep_versions(mut self, versions: u64) -> Self {
+        self.keep_versions = versions;
+        self
+    }
+
+    /// When `secret` is due for its next rotation, or `None` if it was never rotated
+    #[must_use]
+    pub fn next_rotation(&self, secret: &Secret) -> Option<DateTime<Utc>> {
+        let rotated_at = secret.annotations().get(ROTATED_AT_ANNOTATION)?;
+        let rotated_at = DateTime::parse_from_rfc3339(rotated_at).ok()?;
+        Some(rotated_at.with_timezone(&Utc) + self.interval)
+    }
+
+    /// Whether `secret` is due for rotation at `now`
+    #[must_use]
+    pub fn is_due(&self, secret: &Secret, now: DateTime<Utc>) -> bool {
+        self.next_rotation(secret).map_or(true, |next| next <= now)
+    }
+
+    // The next version of `secret`, or of a new Secret `name` owned by `owner`
+    fn next_version(
+        &self,
+        name: &str,
+        secret: Option<&Secret>,
+        owner: Option<OwnerReference>,
+        now: DateTime<Utc>,
+        generate: impl FnOnce(u64) -> Result<BTreeMap<String, ByteString>, Error>,
+    ) -> Result<(Secret, u64), Error> {
+        let next = secret.map_or(0, version) + 1;
+        let data = self.rotate_data(secret, next, generate(next)?);
+        let mut rotated = secret.cloned().unwrap_or_else(|| Secret {
+            metadata: ObjectMeta {
+                name: Some(name.into()),
+                owner_references: owner.map(|owner| vec![owner]),
+                ..ObjectMeta::default()
+            },
+            ..Secret::default()
+        });
+        let annotations = rotated.annotations_mut();
+        annotations.insert(VERSION_ANNOTATION.into(), next.to_string());
+        annotations.insert(ROTATED_AT_ANNOTATION.into(), now.to_rfc3339());
+        rotated.data = Some(data);
+        Ok((rotated, next))
+    }
+
+    // The data of the `version` of `secret`, with the keys of the kept previous versions
+    fn rotate_data(
+        &self,
+        secret: Option<&Secret>,
+        version: u64,
+        data: BTreeMap<String, ByteString>,
+    ) -> BTreeMap<String, ByteString> {
+        let oldest_kept = version.saturating_sub(self.keep_versions);
+        let mut rotated = secret
+            .and_then(|secret| secret.data.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(key, _)| key_version(key).map_or(false, |key_version| key_version >= oldest_kept))
+            .collect::<BTreeMap<_, _>>();
+        for (key, value) in data {
+            rotated.insert(format!("{}.v{}", key, version), value.clone());
+            rotated.insert(key, value);
+        }
+        rotated
+    }
+}
+
+/// The version of `secret`, or `0` if it was never rotated
+#[must_use]
+pub fn version(secret: &Secret) -> u64 {
+    secret
+        .annotations()
+        .get(VERSION_ANNOTATION)
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(0)
+}
+
+// The version of a versioned key `k.v{version}`
+fn key_version(key: &str) -> Option<u64> {
+    let (_, version) = key.rsplit_once(".v")?;
+    version.parse().ok()
+}
+
+/// Where consumers of a Secret are notified of its rotations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notify {
+    /// The annotations of the consumer itself, for controllers that watch it
+    Object,
+    /// The annotations of the pod template of a workload like a `Deployment`, which rolls out new pods
+    PodTemplate,
+}
+
+/// The result of [`Rotator::reconcile`]
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    /// The Secret, as written or found
+    pub secret: Secret,
+    /// The current version of the Secret
+    pub version: u64,
+    /// Whether the Secret was rotated (or created)
+    pub rotated: bool,
+    /// When the Secret is due for its next rotation
+    pub next_rotation: DateTime<Utc>,
+}
+
+impl Outcome {
+    /// Requeues the reconciliation for the next rotation
+    #[must_use]
+    pub fn reconciler_action(&self) -> ReconcilerAction {
+        let until = (self.next_rotation - Utc::now()).max(Duration::zero());
+        ReconcilerAction {
+            requeue_after: Some(until.to_std().unwrap_or_default()),
+        }
+    }
+}
+
+k8s_openapi::k8s_if_ge_1_19! {
+    impl Outcome {
+        /// An event for the object owning the Secret, if it was rotated
+        #[must_use]
+        pub fn event(&self) -> Option<crate::events::Event> {
+            if !self.rotated {
+                return None;
+            }
+            Some(crate::events::Event {
+                type_: crate::events::EventType::Normal,
+                reason: "SecretRotated".into(),
+                note: Some(format!("Rotated Secret {} to version {}", self.secret.name(), self.version)),
+                action: "RotateSecret".into(),
+                secondary: Some(self.secret.object_ref(&())),
+            })
+        }
+    }
+}
+
+/// Generates Secrets and rotates them according to a [`Policy`]
+///
+/// See the [module documentation](self) for the rotation scheme.
+#[derive(Clone)]
+pub struct Rotator {
+    api: Api<Secret>,
+    policy: Policy,
+}
+
+impl Rotator {
+    /// Rotates Secrets of `api` according to `policy`
+    #[must_use]
+    pub fn new(api: Api<Secret>, policy: Policy) -> Self {
+        Self { api, policy }
+    }
+
+    /// Creates the Secret `name`, or rotates it if it is due, with the data returned by `generate`
+    ///
+    /// `generate` is called with the new version of the Secret, only when it is created or rotated.
+    /// The Secret is owned by `owner` when it is created, so that a [`Controller`](crate::Controller)
+    /// owning it is triggered by its changes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the Secret cannot be read or written, or if `generate` fails. In particular, writing fails
+    /// with a conflict when the Secret was changed or created concurrently, and should then be retried.
+    pub async fn reconcile<E: Into<BoxError>>(
+        &self,
+        name: &str,
+        owner: Option<OwnerReference>,
+        generate: impl FnOnce(u64) -> Result<BTreeMap<String, ByteString>, E>,
+    ) -> Result<Outcome, Error> {
+        let secret = self.get(name).await?;
+        match secret {
+            Some(secret) if !self.policy.is_due(&secret, Utc::now()) => Ok(Outcome {
+                version: version(&secret),
+                next_rotation: self.policy.next_rotation(&secret).unwrap_or_else(Utc::now),
+                rotated: false,
+                secret,
+            }),
+            secret => self.write(name, secret, owner, generate).await,
+        }
+    }
+
+    /// Rotates the Secret `name` now, even if it is not due, such as when its data was compromised
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`Rotator::reconcile`].
+    pub async fn rotate<E: Into<BoxError>>(
+        &self,
+        name: &str,
+        owner: Option<OwnerReference>,
+        generate: impl FnOnce(u64) -> Result<BTreeMap<String, ByteString>, E>,
+    ) -> Result<Outcome, Error> {
+        let secret = self.get(name).await?;
+        self.write(name, secret, owner, generate).await
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<Secret>, Error> {
+        self.api.get_opt(name).await.map_err(Error::GetSecret)
+    }
+
+    async fn write<E: Into<BoxError>>(
+        &self,
+        name: &str,
+        secret: Option<Secret>,
+        owner: Option<OwnerReference>,
+        generate: impl FnOnce(u64) -> Result<BTreeMap<String, ByteString>, E>,
+    ) -> Result<Outcome, Error> {
+        let now = Utc::now();
+        let generate = |version| generate(version).map_err(|err| Error::Generate(err.into()));
+        let (secret, outcome_version) =
+            self.policy
+                .next_version(name, secret.as_ref(), owner, now, generate)?;
+        let pp = PostParams::default();
+        let written = if secret.resource_version().is_some() {
+            // Replacing at the resourceVersion that was rotated fails rather than overwrite concurrent rotations
+            self.api.replace(name, &pp, &secret).await
+        } else {
+            self.api.create(&pp, &secret).await
+        }
+        .map_err(Error::WriteSecret)?;
+        Ok(Outcome {
+            secret: written,
+            version: outcome_version,
+            rotated: true,
+            next_rotation: now + self.policy.interval,
+        })
+    }
+}
+
+/// Notifies the consumer `name` of the version of the Secret in `outcome`, by annotating it
+///
+/// The annotation is [`CONSUMER_ANNOTATION_PREFIX`] followed by the name of the Secret, so a consumer
+/// of several Secrets is notified of each of them.
+///
+/// # Errors
+///
+/// Fails if the consumer cannot be patched.
+pub async fn notify<K>(consumers: &Api<K>, name: &str, outcome: &Outcome, target: Notify) -> Result<(), Error>
+where
+    K: Resource + Clone + DeserializeOwned + std::fmt::Debug,
+{
+    let annotation = format!("{}{}", CONSUMER_ANNOTATION_PREFIX, outcome.secret.name());
+    let annotations = json!({ "annotations": { annotation: outcome.version.to_string() } });
+    let patch = match target {
+        Notify::Object => json!({ "metadata": annotations }),
+        Notify::PodTemplate => json!({ "spec": { "template": { "metadata": annotations } } }),
+    };
+    consumers
+        .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+        .map_err(Error::NotifyConsumer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::pin_mut;
+    use http::{Method, Request, Response, StatusCode};
+    use hyper::Body;
+    use k8s_openapi::api::apps::v1::Deployment;
+    use kube_client::Client;
+    use serde_json::Value;
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+    use tower_test::mock;
+
+    fn data(value: &str) -> BTreeMap<String, ByteString> {
+        BTreeMap::from([("password".to_string(), ByteString(value.into()))])
+    }
+
+    fn generate(version: u64) -> Result<BTreeMap<String, ByteString>, Infallible> {
+        Ok(data(&format!("password-{}", version)))
+    }
+
+    fn keys(secret: &Secret) -> Vec<&str> {
+        secret.data.as_ref().unwrap().keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn rotates_versioned_keys() {
+        let policy = Policy::new(Duration::days(1));
+        let now = Utc::now();
+        let mut secret: Option<Secret> = None;
+        let mut versions = Vec::new();
+        for value in ["a", "b", "c"] {
+            let (rotated, version) = policy
+                .next_version("db", secret.as_ref(), None, now, |_| Ok(data(value)))
+                .unwrap();
+            versions.push(version);
+            secret = Some(rotated);
+        }
+        let secret = secret.unwrap();
+        assert_eq!(versions, [1, 2, 3]);
+        assert_eq!(version(&secret), 3);
+        assert_eq!(secret.name(), "db");
+        assert_eq!(keys(&secret), ["password", "password.v2", "password.v3"]);
+        assert_eq!(secret.data.as_ref().unwrap()["password"], ByteString("c".into()));
+        assert_eq!(
+            secret.data.as_ref().unwrap()["password.v2"],
+            ByteString("b".into())
+        );
+
+        assert!(!policy.is_due(&secret, now));
+        assert!(policy.is_due(&secret, now + Duration::days(1)));
+        assert!(policy.is_due(&Secret::default(), now));
+    }
+
+    // The secret stored by a fake apiserver, which can let another writer win the next replace
+    #[derive(Default)]
+    struct Stored {
+        secret: Option<Value>,
+        version: u64,
+        race_next_replace: bool,
+        // The method of every request, with the resourceVersion of the written secret
+        requests: Vec<(Method, Option<String>)>,
+    }
+
+    // Serves one secret like the apiserver, checking the resourceVersion of replaces
+    fn secret_api(stored: Arc<Mutex<Stored>>) -> Api<Secret> {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        tokio::spawn(async move {
+            pin_mut!(handle);
+            while let Some((request, send)) = handle.next_request().await {
+                let method = request.method().clone();
+                let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                let mut stored = stored.lock().unwrap();
+                let written = (!body.is_empty()).then(|| serde_json::from_slice::<Value>(&body).unwrap());
+                let written_version = written
+                    .as_ref()
+                    .and_then(|secret| secret["metadata"]["resourceVersion"].as_str())
+                    .map(String::from);
+                stored.requests.push((method.clone(), written_version));
+                if stored.race_next_replace && method == Method::PUT {
+                    // Another writer replaced the secret since it was read
+                    stored.race_next_replace = false;
+                    stored.version += 1;
+                    let version = stored.version.to_string();
+                    if let Some(secret) = stored.secret.as_mut() {
+                        secret["metadata"]["resourceVersion"] = version.into();
+                    }
+                }
+                let (status, response) = match (method, stored.secret.clone(), written) {
+                    (Method::GET, Some(secret), _) => (StatusCode::OK, secret),
+                    (Method::POST, None, Some(secret)) | (Method::PUT, Some(_), Some(secret))
+                        if stored.secret.is_none()
+                            || secret["metadata"]["resourceVersion"] == stored.version.to_string() =>
+                    {
+                        stored.version += 1;
+                        let mut secret = secret;
+                        secret["metadata"]["resourceVersion"] = stored.version.to_string().into();
+                        stored.secret = Some(secret.clone());
+                        (StatusCode::OK, secret)
+                    }
+                    (Method::GET, None, _) => (StatusCode::NOT_FOUND, failure(404, "NotFound")),
+                    _ => (StatusCode::CONFLICT, failure(409, "Conflict")),
+                };
+                send.send_response(
+                    Response::builder()
+                        .status(status)
+                        .body(Body::from(response.to_string()))
+                        .unwrap(),
+                );
+            }
+        });
+        Api::namespaced(Client::new(mock_service, "default"), "default")
+    }
+
+    fn failure(code: u16, reason: &str) -> Value {
+        json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "message": reason,
+            "reason": reason,
+            "code": code,
+        })
+    }
+
+    #[tokio::test]
+    async fn writes_secrets_at_the_version_read() {
+        let stored = Arc::<Mutex<Stored>>::default();
+        let rotator = Rotator::new(secret_api(stored.clone()), Policy::new(Duration::days(1)));
+
+        let created = rotator.reconcile("db", None, generate).await.unwrap();
+        assert!(created.rotated);
+        assert_eq!(created.version, 1);
+        assert_eq!(keys(&created.secret), ["password", "password.v1"]);
+        let rotated = rotator.rotate("db", None, generate).await.unwrap();
+        assert_eq!(rotated.version, 2);
+        assert_eq!(
+            rotated.secret.data.as_ref().unwrap()["password"],
+            ByteString("password-2".into())
+        );
+
+        let requests = std::mem::take(&mut stored.lock().unwrap().requests);
+        assert_eq!(requests, [
+            (Method::GET, None),
+            (Method::POST, None),
+            (Method::GET, None),
+            (Method::PUT, Some("1".to_string())),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn concurrent_rotations_conflict() {
+        let stored = Arc::<Mutex<Stored>>::default();
+        let rotator = Rotator::new(secret_api(stored.clone()), Policy::new(Duration::days(1)));
+        rotator.reconcile("db", None, generate).await.unwrap();
+
+        stored.lock().unwrap().race_next_replace = true;
+        match rotator.rotate("db", None, generate).await {
+            Err(Error::WriteSecret(kube_client::Error::Api(err))) => assert_eq!(err.code, 409),
+            other => panic!("unexpected result {:?}", other.map(|outcome| outcome.version)),
+        }
+        assert_eq!(rotator.rotate("db", None, generate).await.unwrap().version, 2);
+    }
+
+    #[tokio::test]
+    async fn reconcile_skips_secrets_not_due() {
+        let stored = Arc::<Mutex<Stored>>::default();
+        let rotator = Rotator::new(secret_api(stored.clone()), Policy::new(Duration::days(1)));
+        let created = rotator.reconcile("db", None, generate).await.unwrap();
+
+        let mut generated = false;
+        let outcome = rotator
+            .reconcile("db", None, |version| {
+                generated = true;
+                generate(version)
+            })
+            .await
+            .unwrap();
+        assert!(!generated);
+        assert!(!outcome.rotated);
+        assert_eq!(outcome.version, 1);
+        assert_eq!(outcome.next_rotation, created.next_rotation);
+        let requests = stored.lock().unwrap().requests.len();
+        assert_eq!(requests, 3);
+    }
+
+    #[tokio::test]
+    async fn notifies_pod_templates() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            pin_mut!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), Method::PATCH);
+            assert_eq!(
+                request.uri().to_string(),
+                "/apis/apps/v1/namespaces/default/deployments/web?"
+            );
+            assert_eq!(
+                request.headers()[http::header::CONTENT_TYPE],
+                "application/merge-patch+json"
+            );
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            let patch = serde_json::from_slice::<Value>(&body).unwrap();
+            let annotations = json!({ "secrets.rotation.kube.rs/db": "2" });
+            assert_eq!(
+                patch,
+                json!({ "spec": { "template": { "metadata": { "annotations": annotations } } } })
+            );
+            let deployment = json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": { "name": "web", "namespace": "default" },
+            });
+            send.send_response(
+                Response::builder()
+                    .body(Body::from(deployment.to_string()))
+                    .unwrap(),
+            );
+        });
+
+        let outcome = Outcome {
+            secret: Secret {
+                metadata: ObjectMeta {
+                    name: Some("db".into()),
+                    ..ObjectMeta::default()
+                },
+                ..Secret::default()
+            },
+            version: 2,
+            rotated: true,
+            next_rotation: Utc::now(),
+        };
+        let deployments = Api::<Deployment>::namespaced(Client::new(mock_service, "default"), "default");
+        notify(&deployments, "web", &outcome, Notify::PodTemplate)
+            .await
+            .unwrap();
+        spawned.await.unwrap();
+    }
+}
@@ -0,0 +1,394 @@
+//! Tailing the logs of all containers of a set of pods at once, like `stern`
+use crate::{
+    reflector::ObjectRef,
+    watcher::{self, watcher},
+};
+use futures::{
+    future::{self, AbortHandle, Abortable, Either},
+    pin_mut, stream, Stream, StreamExt,
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube_client::{
+    api::{ListParams, LogLine, LogParams},
+    Api, ResourceExt,
+};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to watch pods: {0}")]
+    WatchPods(#[source] watcher::Error),
+    #[error("failed to tail container {container} of {pod}: {source}")]
+    Logs {
+        pod: ObjectRef<Pod>,
+        container: String,
+        #[source]
+        source: kube_client::Error,
+    },
+}
+
+/// A line logged by a container of a tailed pod
+#[derive(Clone, Debug)]
+pub struct TailedLine {
+    /// The pod that logged the line
+    pub pod: ObjectRef<Pod>,
+    /// The container that logged the line
+    pub container: String,
+    /// The logged line
+    pub line: LogLine,
+}
+
+// A running container, identified by its restart count so that restarted containers are tailed again
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Tailed {
+    pod: ObjectRef<Pod>,
+    uid: Option<String>,
+    container: String,
+    restarts: i32,
+}
+
+// The containers being tailed, whose tails are aborted once their run has terminated or they are dropped
+#[derive(Default)]
+struct Tails(HashMap<Tailed, AbortHandle>);
+
+impl Tails {
+    fn contains(&self, tailed: &Tailed) -> bool {
+        self.0.contains_key(tailed)
+    }
+
+    // Tails of earlier runs of the same container keep draining until `pod` reports them terminated
+    fn insert(&mut self, tailed: Tailed, handle: AbortHandle) {
+        self.0.insert(tailed, handle);
+    }
+
+    // Abort the tails of the container runs of `pod` that have terminated
+    //
+    // Following the log of a terminated run would otherwise reconnect to the next run of its container.
+    fn retain_running(&mut self, pod: &Pod) {
+        let uid = pod.uid();
+        self.0.retain(|t, handle| {
+            let terminated = t.uid == uid && run_terminated(pod, t);
+            if terminated {
+                handle.abort();
+            }
+            !terminated
+        });
+    }
+
+    // Abort the tails of pods that `keep` rejects
+    fn retain_pods(&mut self, mut keep: impl FnMut(&Option<String>) -> bool) {
+        self.0.retain(|t, handle| {
+            let kept = keep(&t.uid);
+            if !kept {
+                handle.abort();
+            }
+            kept
+        });
+    }
+}
+
+impl Drop for Tails {
+    fn drop(&mut self) {
+        for handle in self.0.values() {
+            handle.abort();
+        }
+    }
+}
+
+/// Tail the logs of all running containers of the pods that match `list_params`, merged into one stream
+///
+/// Pods are watched, so containers that start or restart later are tailed as well. Every container is
+/// followed with [`Api::follow_log_lines`] and `log_params`, which keeps tailing across dropped
+/// connections. Lines of different containers are interleaved in the order they arrive.
+///
+/// Failures to tail one container are emitted without ending the stream, as are watch errors.
+/// The stream only ends when it is dropped.
+///
+/// ```no_run
+/// use futures::TryStreamExt;
+/// use k8s_openapi::api::core::v1::Pod;
+/// use kube::{api::{Api, ListParams, LogParams}, Client};
+/// use kube_runtime::tail::tail_pods;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let pods: Api<Pod> = Api::namespaced(client, "apps");
+/// let lines = tail_pods(pods, ListParams::default().labels("app=web"), &LogParams::default());
+/// futures::pin_mut!(lines);
+/// while let Some(tailed) = lines.try_next().await? {
+///     println!("{} {}: {}", tailed.pod.name, tailed.container, tailed.line.message);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub fn tail_pods(
+    api: Api<Pod>,
+    list_params: ListParams,
+    log_params: &LogParams,
+) -> impl Stream<Item = Result<TailedLine, Error>> {
+    let (sender, receiver) = mpsc::channel(64);
+    let log_params = log_params.clone();
+    tokio::spawn(async move {
+        let client = api.clone().into_client();
+        let events = watcher(api, list_params);
+        pin_mut!(events);
+        // Stop watching as soon as the stream is dropped, rather than at the next event
+        let closed = sender.closed();
+        pin_mut!(closed);
+        // Dropping the tails when the stream is dropped stops tailing all containers
+        let mut tails = Tails::default();
+        loop {
+            let event = match future::select(events.next(), closed.as_mut()).await {
+                Either::Left((Some(event), _)) => event,
+                Either::Left((None, _)) | Either::Right(_) => return,
+            };
+            let pods = match event {
+                Ok(watcher::Event::Applied(pod)) => vec![pod],
+                Ok(watcher::Event::Deleted(pod)) => {
+                    let uid = pod.uid();
+                    tails.retain_pods(|t| *t != uid);
+                    continue;
+                }
+                Ok(watcher::Event::Restarted(pods)) => {
+                    let uids = pods.iter().map(ResourceExt::uid).collect::<HashSet<_>>();
+                    tails.retain_pods(|t| uids.contains(t));
+                    pods
+                }
+                Err(err) => {
+                    if sender.send(Err(Error::WatchPods(err))).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            for pod in &pods {
+                tails.retain_running(pod);
+                for container in running_containers(pod) {
+                    if !tails.contains(&container) {
+                        let api = Api::<Pod>::namespaced(
+                            client.clone(),
+                            container.pod.namespace.as_deref().unwrap_or_default(),
+                        );
+                        let (handle, registration) = AbortHandle::new_pair();
+                        let tail = tail_container(api, container.clone(), log_params.clone(), sender.clone());
+                        tokio::spawn(Abortable::new(tail, registration));
+                        tails.insert(container, handle);
+                    }
+                }
+            }
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|line| (line, receiver))
+    })
+}
+
+/// Tail the logs of all running containers of the pod `name`, see [`tail_pods`]
+#[allow(clippy::module_name_repetitions)]
+pub fn tail_pod(
+    api: Api<Pod>,
+    name: &str,
+    log_params: &LogParams,
+) -> impl Stream<Item = Result<TailedLine, Error>> {
+    let list_params = ListParams::default().fields(&format!("metadata.name={}", name));
+    tail_pods(api, list_params, log_params)
+}
+
+async fn tail_container(
+    api: Api<Pod>,
+    tailed: Tailed,
+    log_params: LogParams,
+    sender: mpsc::Sender<Result<TailedLine, Error>>,
+) {
+    let log_params = LogParams {
+        container: Some(tailed.container.clone()),
+        ..log_params
+    };
+    let lines = api.follow_log_lines(&tailed.pod.name, &log_params);
+    pin_mut!(lines);
+    while let Some(line) = lines.next().await {
+        let line = line
+            .map(|line| TailedLine {
+                pod: tailed.pod.clone(),
+                container: tailed.container.clone(),
+                line,
+            })
+            .map_err(|source| Error::Logs {
+                pod: tailed.pod.clone(),
+                container: tailed.container.clone(),
+                source,
+            });
+        if sender.send(line).await.is_err() {
+            // The merged stream was dropped
+            return;
+        }
+    }
+}
+
+// The containers of `pod` that are running, including init containers
+fn running_containers(pod: &Pod) -> Vec<Tailed> {
+    let status = match &pod.status {
+        Some(status) => status,
+        None => return Vec::new(),
+    };
+    let pod_ref = ObjectRef::from_obj(pod);
+    status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten())
+        .filter(|container| {
+            container
+                .state
+                .as_ref()
+                .map_or(false, |state| state.running.is_some())
+        })
+        .map(|container| Tailed {
+            pod: pod_ref.clone(),
+            uid: pod.uid(),
+            container: container.name.clone(),
+            restarts: container.restart_count,
+        })
+        .collect()
+}
+
+// Whether `pod` reports that the run `tailed` of its container has terminated
+fn run_terminated(pod: &Pod, tailed: &Tailed) -> bool {
+    let status = match &pod.status {
+        Some(status) => status,
+        None => return false,
+    };
+    if matches!(status.phase.as_deref(), Some("Succeeded" | "Failed")) {
+        return true;
+    }
+    status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten())
+        .find(|container| container.name == tailed.container)
+        .map_or(false, |container| {
+            container.restart_count > tailed.restarts
+                || container
+                    .state
+                    .as_ref()
+                    .map_or(false, |state| state.terminated.is_some())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{running_containers, Tailed, Tails};
+    use crate::reflector::ObjectRef;
+    use futures::{
+        executor::block_on,
+        future::{self, AbortHandle, Abortable, Either},
+    };
+    use k8s_openapi::api::core::v1::Pod;
+    use serde_json::json;
+
+    fn tailed(uid: &str, container: &str, restarts: i32) -> Tailed {
+        Tailed {
+            pod: ObjectRef::new("web-0").within("apps"),
+            uid: Some(uid.to_string()),
+            container: container.to_string(),
+            restarts,
+        }
+    }
+
+    // Starts a tail that never ends on its own, and whether it was aborted once the tails are done with
+    fn start(tails: &mut Tails, tailed: Tailed) -> impl FnOnce() -> bool {
+        let (handle, registration) = AbortHandle::new_pair();
+        let tail = Abortable::new(future::pending::<()>(), registration);
+        tails.insert(tailed, handle);
+        move || matches!(block_on(future::select(tail, future::ready(()))), Either::Left(_))
+    }
+
+    fn pod(web: serde_json::Value) -> Pod {
+        serde_json::from_value(json!({
+            "metadata": { "name": "web-0", "namespace": "apps", "uid": "1234" },
+            "status": {
+                "phase": "Running",
+                "containerStatuses": [
+                    web,
+                    { "name": "sidecar", "image": "", "imageID": "", "ready": true, "restartCount": 0,
+                      "state": { "running": {} } },
+                ],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn restarted_containers_drain_until_terminated() {
+        let mut tails = Tails::default();
+        let first = start(&mut tails, tailed("1234", "web", 0));
+        let sidecar = start(&mut tails, tailed("1234", "sidecar", 0));
+        let restarted = start(&mut tails, tailed("1234", "web", 1));
+        // The earlier run keeps draining until the pod reports it terminated
+        assert!(tails.contains(&tailed("1234", "web", 0)));
+        tails.retain_running(&pod(json!({
+            "name": "web", "image": "", "imageID": "", "ready": true, "restartCount": 1,
+            "state": { "running": {} },
+            "lastState": { "terminated": { "exitCode": 1 } },
+        })));
+        assert!(!tails.contains(&tailed("1234", "web", 0)));
+        assert!(tails.contains(&tailed("1234", "web", 1)));
+        assert!(first());
+        assert!(!sidecar());
+        drop(tails);
+        assert!(restarted());
+    }
+
+    #[test]
+    fn terminated_containers_stop_their_tails() {
+        let mut tails = Tails::default();
+        let web = start(&mut tails, tailed("1234", "web", 0));
+        let sidecar = start(&mut tails, tailed("1234", "sidecar", 0));
+        tails.retain_running(&pod(json!({
+            "name": "web", "image": "", "imageID": "", "ready": false, "restartCount": 0,
+            "state": { "terminated": { "exitCode": 0 } },
+        })));
+        assert!(web());
+        assert!(!sidecar());
+        assert!(tails.contains(&tailed("1234", "sidecar", 0)));
+    }
+
+    #[test]
+    fn deleted_pods_stop_their_tails() {
+        let mut tails = Tails::default();
+        let deleted = start(&mut tails, tailed("1234", "web", 0));
+        let recreated = start(&mut tails, tailed("5678", "web", 0));
+        tails.retain_pods(|uid| uid.as_deref() != Some("1234"));
+        assert!(deleted());
+        assert!(!recreated());
+        assert!(tails.contains(&tailed("5678", "web", 0)));
+    }
+
+    #[test]
+    fn only_running_containers_are_tailed() {
+        let pod: Pod = serde_json::from_value(json!({
+            "metadata": { "name": "web-0", "namespace": "apps", "uid": "1234" },
+            "status": {
+                "initContainerStatuses": [
+                    { "name": "migrate", "image": "", "imageID": "", "ready": false, "restartCount": 0,
+                      "state": { "terminated": { "exitCode": 0 } } },
+                ],
+                "containerStatuses": [
+                    { "name": "web", "image": "", "imageID": "", "ready": true, "restartCount": 2,
+                      "state": { "running": {} } },
+                    { "name": "sidecar", "image": "", "imageID": "", "ready": false, "restartCount": 0,
+                      "state": { "waiting": { "reason": "ContainerCreating" } } },
+                ],
+            },
+        }))
+        .unwrap();
+        let running = running_containers(&pod);
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].container, "web");
+        assert_eq!(running[0].restarts, 2);
+        assert_eq!(running[0].pod.namespace.as_deref(), Some("apps"));
+    }
+}
@@ -1,19 +1,23 @@
 //! Helpers for manipulating built-in streams
-use crate::watcher;
+use crate::{reflector::ObjectRef, watcher};
 use futures::{
     pin_mut,
     stream::{self, Peekable},
     Future, FutureExt, Stream, StreamExt, TryStream, TryStreamExt,
 };
+use kube_client::Resource;
 use pin_project::pin_project;
 use std::{
+    collections::{HashMap, HashSet},
     fmt::Debug,
+    hash::Hash,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError},
     task::Poll,
 };
 use stream::IntoStream;
 use tokio::{runtime::Handle, task::JoinHandle};
+use tracing::Span;
 
 /// Flattens each item in the list following the rules of [`watcher::Event::into_iter_applied`].
 pub fn try_flatten_applied<K, S: TryStream<Ok = watcher::Event<K>>>(
@@ -33,6 +37,146 @@ pub fn try_flatten_touched<K, S: TryStream<Ok = watcher::Event<K>>>(
         .try_flatten()
 }
 
+/// Spans that follow the lifecycle of the objects seen by a [`watcher`](crate::watcher())
+///
+/// [`LifecycleSpans::trace`] records every step in the lifecycle of an object (when it is first seen, every change
+/// of its `resourceVersion`, and its deletion) as an event in a short `object lifecycle` span, which carries the
+/// reference, `uid` and `resourceVersion` of the object. The spans are closed right away, so subscribers that buffer
+/// spans until they close (such as OpenTelemetry exporters) do not hold one per live object.
+/// Reconcilers can link their spans to the lifecycle of the reconciled object with [`LifecycleSpans::span`],
+/// for tracing the churn of objects across processes.
+///
+/// ```no_run
+/// use kube::{api::{Api, ListParams}, Client};
+/// use kube_runtime::{reflector::ObjectRef, utils::LifecycleSpans, watcher};
+/// use k8s_openapi::api::core::v1::Pod;
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let pods: Api<Pod> = Api::all(Client::try_default().await?);
+/// let spans = LifecycleSpans::new();
+/// let events = spans.trace(watcher(pods, ListParams::default()));
+/// // ... in the reconciler of `blog`
+/// if let Some(lifecycle) = spans.span(&ObjectRef::new("blog").within("apps")) {
+///     tracing::Span::current().follows_from(&lifecycle);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct LifecycleSpans<K: Resource> {
+    objects: Arc<Mutex<HashMap<ObjectRef<K>, Lifecycle>>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Lifecycle {
+    uid: Option<String>,
+    resource_version: Option<String>,
+}
+
+impl<K: Resource> Clone for LifecycleSpans<K> {
+    fn clone(&self) -> Self {
+        Self {
+            objects: self.objects.clone(),
+        }
+    }
+}
+
+impl<K: Resource> Default for LifecycleSpans<K> {
+    fn default() -> Self {
+        Self {
+            objects: Arc::default(),
+        }
+    }
+}
+
+impl<K> LifecycleSpans<K>
+where
+    K: Resource,
+    K::DynamicType: Default + Eq + Hash + Clone,
+{
+    /// Spans for no objects yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the lifecycles of the objects of the events of `stream`, which are passed through
+    pub fn trace<S>(&self, stream: S) -> impl Stream<Item = Result<watcher::Event<K>, S::Error>>
+    where
+        S: TryStream<Ok = watcher::Event<K>>,
+    {
+        let spans = self.clone();
+        stream.inspect_ok(move |event| spans.record(event))
+    }
+
+    /// A new `object lifecycle` span for the last seen version of the object `obj_ref`,
+    /// if it has been seen and not deleted since
+    #[must_use]
+    pub fn span(&self, obj_ref: &ObjectRef<K>) -> Option<Span> {
+        self.lock()
+            .get(obj_ref)
+            .map(|lifecycle| lifecycle_span(obj_ref, lifecycle))
+    }
+
+    fn record(&self, event: &watcher::Event<K>) {
+        let mut objects = self.lock();
+        match event {
+            watcher::Event::Applied(obj) => Self::applied(&mut objects, obj, "created"),
+            watcher::Event::Deleted(obj) => {
+                let obj_ref = ObjectRef::from_obj(obj);
+                if let Some(mut lifecycle) = objects.remove(&obj_ref) {
+                    lifecycle.resource_version = obj.meta().resource_version.clone();
+                    tracing::info!(parent: &lifecycle_span(&obj_ref, &lifecycle), "deleted");
+                }
+            }
+            watcher::Event::Restarted(objs) => {
+                let listed = objs.iter().map(ObjectRef::from_obj).collect::<HashSet<_>>();
+                // Objects that are missing from the relist were deleted while the watch was down
+                objects.retain(|obj_ref, lifecycle| {
+                    let kept = listed.contains(obj_ref);
+                    if !kept {
+                        tracing::info!(
+                            parent: &lifecycle_span(obj_ref, lifecycle),
+                            "deleted while not watched"
+                        );
+                    }
+                    kept
+                });
+                for obj in objs {
+                    Self::applied(&mut objects, obj, "listed");
+                }
+            }
+        }
+    }
+
+    fn applied(objects: &mut HashMap<ObjectRef<K>, Lifecycle>, obj: &K, first_seen: &str) {
+        let obj_ref = ObjectRef::from_obj(obj);
+        let lifecycle = Lifecycle {
+            uid: obj.meta().uid.clone(),
+            resource_version: obj.meta().resource_version.clone(),
+        };
+        let step = match objects.get(&obj_ref) {
+            Some(seen) if *seen == lifecycle => return,
+            Some(_) => "modified",
+            None => first_seen,
+        };
+        tracing::info!(parent: &lifecycle_span(&obj_ref, &lifecycle), "{}", step);
+        objects.insert(obj_ref, lifecycle);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ObjectRef<K>, Lifecycle>> {
+        self.objects.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn lifecycle_span<K: Resource>(obj_ref: &ObjectRef<K>, lifecycle: &Lifecycle) -> Span {
+    tracing::info_span!(
+        parent: None,
+        "object lifecycle",
+        "object.ref" = %obj_ref,
+        object.uid = ?lifecycle.uid,
+        object.resource_version = ?lifecycle.resource_version,
+    )
+}
+
 /// Allows splitting a `Stream` into several streams that each emit a disjoint subset of the input stream's items,
 /// like a streaming variant of pattern matching.
 ///
@@ -209,3 +353,72 @@ pub(crate) trait KubeRuntimeStreamExt: Stream + Sized {
 }
 
 impl<S: Stream> KubeRuntimeStreamExt for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lifecycle, LifecycleSpans};
+    use crate::{reflector::ObjectRef, watcher};
+    use futures::{stream, StreamExt};
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use kube_client::api::ObjectMeta;
+
+    fn cm(name: &str, resource_version: &str) -> ConfigMap {
+        ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("ns".to_string()),
+                uid: Some(format!("{}-uid", name)),
+                resource_version: Some(resource_version.to_string()),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        }
+    }
+
+    fn seen(spans: &LifecycleSpans<ConfigMap>, name: &str) -> Option<String> {
+        spans
+            .lock()
+            .get(&ObjectRef::new(name).within("ns"))
+            .and_then(|lifecycle| lifecycle.resource_version.clone())
+    }
+
+    #[tokio::test]
+    async fn trace_passes_events_through() {
+        let spans = LifecycleSpans::new();
+        let events = stream::iter(vec![
+            Ok::<_, ()>(watcher::Event::Applied(cm("a", "1"))),
+            Ok(watcher::Event::Deleted(cm("a", "2"))),
+        ]);
+        assert_eq!(spans.trace(events).count().await, 2);
+        assert_eq!(seen(&spans, "a"), None);
+    }
+
+    #[test]
+    fn follows_objects_until_deleted() {
+        let spans = LifecycleSpans::new();
+        spans.record(&watcher::Event::Applied(cm("a", "1")));
+        assert_eq!(spans.lock()[&ObjectRef::new("a").within("ns")], Lifecycle {
+            uid: Some("a-uid".to_string()),
+            resource_version: Some("1".to_string()),
+        });
+        assert!(spans.span(&ObjectRef::new("a").within("ns")).is_some());
+
+        spans.record(&watcher::Event::Applied(cm("a", "2")));
+        assert_eq!(seen(&spans, "a").as_deref(), Some("2"));
+
+        spans.record(&watcher::Event::Deleted(cm("a", "3")));
+        assert_eq!(seen(&spans, "a"), None);
+        assert!(spans.span(&ObjectRef::new("a").within("ns")).is_none());
+    }
+
+    #[test]
+    fn relists_forget_objects_deleted_while_not_watched() {
+        let spans = LifecycleSpans::new();
+        spans.record(&watcher::Event::Applied(cm("a", "1")));
+        spans.record(&watcher::Event::Applied(cm("b", "2")));
+        spans.record(&watcher::Event::Restarted(vec![cm("b", "3"), cm("c", "4")]));
+        assert_eq!(seen(&spans, "a"), None);
+        assert_eq!(seen(&spans, "b").as_deref(), Some("3"));
+        assert_eq!(seen(&spans, "c").as_deref(), Some("4"));
+    }
+}
@@ -285,3 +285,304 @@ pub mod delete {
             .map_err(Error::Await)
     }
 }
+
+/// Canary rollouts of `StatefulSet`s, by stepping down the partition of their rolling updates
+pub mod canary {
+    use super::{await_condition, Condition};
+    use k8s_openapi::api::{apps::v1::StatefulSet, core::v1::PodTemplateSpec};
+    use kube_client::{
+        api::{Patch, PatchParams},
+        Api,
+    };
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    pub enum Error {
+        #[error("failed to update statefulset: {0}")]
+        Update(#[source] kube_client::Error),
+        #[error("failed to wait for statefulset: {0}")]
+        Await(#[source] super::Error),
+        #[error("rollout failed at partition {0}, the previous template was restored")]
+        Failed(i32),
+        #[error("rollout timed out at partition {0}, the previous template was restored")]
+        TimedOut(i32),
+        #[error("statefulset uses the OnDelete update strategy, which has no partitions")]
+        OnDelete,
+    }
+
+    /// An await condition for `StatefulSet` that returns `true` once the pods from `partition` up run
+    /// the updated template, and all pods are ready
+    #[must_use]
+    pub fn is_partition_ready(partition: i32) -> impl Condition<StatefulSet> {
+        move |obj: Option<&StatefulSet>| {
+            if let Some(sts) = obj {
+                if let (Some(spec), Some(status)) = (&sts.spec, &sts.status) {
+                    let replicas = spec.replicas.unwrap_or(1);
+                    return status.observed_generation >= sts.metadata.generation
+                        && status.updated_replicas.unwrap_or(0) >= replicas - partition
+                        && status.ready_replicas.unwrap_or(0) >= replicas;
+                }
+            }
+            false
+        }
+    }
+
+    // Replaces the pod template, without updating the pods below `partition`
+    fn template_patch(template: &PodTemplateSpec, partition: i32) -> Value {
+        let mut template = serde_json::to_value(template).unwrap_or_default();
+        template["$patch"] = "replace".into();
+        json!({
+            "spec": {
+                "template": template,
+                "updateStrategy": { "type": "RollingUpdate", "rollingUpdate": { "partition": partition } },
+            }
+        })
+    }
+
+    fn partition_patch(partition: i32) -> Value {
+        json!({ "spec": { "updateStrategy": { "rollingUpdate": { "partition": partition } } } })
+    }
+
+    /// Update the pod template of the `StatefulSet` `name` to `template`, one partition at a time
+    ///
+    /// The template is changed without updating any pod, and then the partition of the rolling update is
+    /// lowered to each of `partitions` in turn, finishing at `0`. After every step, waits up to `step_timeout`
+    /// for the updated pods to become ready, see [`is_partition_ready`].
+    ///
+    /// The rollout is aborted if `failed` holds, or a step times out. The previous template and partition
+    /// are then restored, and the `StatefulSet` controller rolls the updated pods back as it would roll out
+    /// any update. Like any rolling update of a `StatefulSet`, this does not replace pods that are stuck
+    /// without ever becoming ready. Those need to be deleted by hand to be recreated from the restored
+    /// template.
+    ///
+    /// `StatefulSet`s with the `OnDelete` update strategy are refused and left unchanged, since their pods
+    /// are only updated when deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Failed`] or [`Error::TimedOut`] if the rollout was aborted and the previous template
+    /// restored, [`Error::OnDelete`] for `StatefulSet`s that are not updated with rolling updates,
+    /// and other errors if the `StatefulSet` could not be updated or watched.
+    ///
+    /// # Usage
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::apps::v1::StatefulSet;
+    /// use kube::{Api, runtime::wait::canary};
+    /// use std::time::Duration;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let api: Api<StatefulSet> = Api::namespaced(client, "apps");
+    /// let mut template = api.get("db").await?.spec.unwrap_or_default().template;
+    /// // .. change the template here ..
+    /// // Update 1 pod of 5, then 3, then all of them
+    /// let never_failed = |_: Option<&StatefulSet>| false;
+    /// canary::rollout(&api, "db", &template, &[4, 2], Duration::from_secs(300), never_failed).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rollout(
+        api: &Api<StatefulSet>,
+        name: &str,
+        template: &PodTemplateSpec,
+        partitions: &[i32],
+        step_timeout: Duration,
+        failed: impl Condition<StatefulSet>,
+    ) -> Result<StatefulSet, Error> {
+        let pp = PatchParams::default();
+        let sts = api.get(name).await.map_err(Error::Update)?;
+        let spec = sts.spec.unwrap_or_default();
+        let replicas = spec.replicas.unwrap_or(1);
+        let strategy = spec.update_strategy;
+        if strategy.as_ref().and_then(|strategy| strategy.type_.as_deref()) == Some("OnDelete") {
+            return Err(Error::OnDelete);
+        }
+        let previous_partition = strategy
+            .and_then(|strategy| strategy.rolling_update)
+            .and_then(|rolling_update| rolling_update.partition)
+            .unwrap_or(0);
+        let rollback = template_patch(&spec.template, previous_partition);
+
+        api.patch(name, &pp, &Patch::Strategic(template_patch(template, replicas)))
+            .await
+            .map_err(Error::Update)?;
+        let mut steps = partitions.to_vec();
+        if steps.last() != Some(&0) {
+            steps.push(0);
+        }
+        for partition in steps {
+            api.patch(name, &pp, &Patch::Strategic(partition_patch(partition)))
+                .await
+                .map_err(Error::Update)?;
+            let ready = is_partition_ready(partition);
+            let done = |obj: Option<&StatefulSet>| ready.matches_object(obj) || failed.matches_object(obj);
+            let step = await_condition(api.clone(), name, done);
+            let abort = match tokio::time::timeout(step_timeout, step).await {
+                Ok(Ok(())) => {
+                    let sts = api.get(name).await.map_err(Error::Update)?;
+                    if failed.matches_object(Some(&sts)) {
+                        Some(Error::Failed(partition))
+                    } else {
+                        None
+                    }
+                }
+                Ok(Err(err)) => return Err(Error::Await(err)),
+                Err(_) => Some(Error::TimedOut(partition)),
+            };
+            if let Some(err) = abort {
+                tracing::warn!("rolling back statefulset {}: {}", name, err);
+                api.patch(name, &pp, &Patch::Strategic(&rollback))
+                    .await
+                    .map_err(Error::Update)?;
+                return Err(err);
+            }
+        }
+        api.get(name).await.map_err(Error::Update)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{is_partition_ready, Condition};
+        use k8s_openapi::api::apps::v1::StatefulSet;
+        use serde_json::json;
+
+        #[test]
+        fn partition_readiness() {
+            let sts: StatefulSet = serde_json::from_value(json!({
+                "metadata": { "name": "db", "generation": 2 },
+                "spec": {
+                    "replicas": 5,
+                    "selector": {},
+                    "serviceName": "db",
+                    "template": {},
+                },
+                "status": { "replicas": 5, "observedGeneration": 2, "updatedReplicas": 2, "readyReplicas": 5 },
+            }))
+            .unwrap();
+            assert!(is_partition_ready(3).matches_object(Some(&sts)));
+            assert!(!is_partition_ready(2).matches_object(Some(&sts)));
+            assert!(!is_partition_ready(3).matches_object(None));
+        }
+    }
+}
+
+/// Waiting for custom resources to be served after installing their `CustomResourceDefinition`
+pub mod crds {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResourceList;
+    use kube_client::{
+        api::{ApiResource, GroupVersionKind},
+        error::ErrorResponse,
+        Client,
+    };
+    use std::time::Duration;
+    use thiserror::Error;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    #[derive(Debug, Error)]
+    pub enum Error {
+        #[error("failed to query discovery: {0}")]
+        Discovery(#[source] kube_client::Error),
+        #[error("{0} was not served within {1:?}")]
+        TimedOut(String, Duration),
+    }
+
+    /// Wait until discovery reports that `gvk` is served, for at most `timeout`
+    ///
+    /// A CRD being [established](super::conditions::is_crd_established) does not mean that its versions
+    /// are served yet, since every apiserver updates discovery on its own, and creating objects in between
+    /// fails with `404`. This polls the discovery of the group version until it lists the kind.
+    ///
+    /// Returns the served resource, ready to be used with `Api::all_with` and `Api::namespaced_with`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::TimedOut`] if `gvk` is not served within `timeout`, and [`Error::Discovery`]
+    /// if discovery fails other than by not serving the group version yet.
+    ///
+    /// # Usage
+    ///
+    /// ```no_run
+    /// use kube::{api::GroupVersionKind, runtime::wait::crds};
+    /// use std::time::Duration;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// // .. apply the crd here ..
+    /// let gvk = GroupVersionKind::gvk("clux.dev", "v1", "Foo");
+    /// let ar = crds::await_served(&client, &gvk, Duration::from_secs(30)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn await_served(
+        client: &Client,
+        gvk: &GroupVersionKind,
+        timeout: Duration,
+    ) -> Result<ApiResource, Error> {
+        let poll = async {
+            loop {
+                let list = if gvk.group.is_empty() {
+                    client.list_core_api_resources(&gvk.version).await
+                } else {
+                    client.list_api_group_resources(&gvk.api_version()).await
+                };
+                match list {
+                    Ok(list) => {
+                        if let Some(plural) = served_plural(&list, &gvk.kind) {
+                            return Ok(ApiResource::from_gvk_with_plural(gvk, plural));
+                        }
+                    }
+                    // The group version is not served yet, or its aggregated API is not available yet
+                    Err(kube_client::Error::Api(ErrorResponse { code: 404 | 503, .. })) => {}
+                    Err(err) => return Err(Error::Discovery(err)),
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, poll)
+            .await
+            .unwrap_or_else(|_| Err(Error::TimedOut(gvk.api_version() + "/" + &gvk.kind, timeout)))
+    }
+
+    // The plural name `kind` is served as, ignoring subresources
+    fn served_plural<'a>(list: &'a APIResourceList, kind: &str) -> Option<&'a str> {
+        list.resources
+            .iter()
+            .find(|res| res.kind == kind && !res.name.contains('/'))
+            .map(|res| res.name.as_str())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::served_plural;
+        use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResourceList;
+        use serde_json::json;
+
+        #[test]
+        fn finds_served_kind() {
+            let list: APIResourceList = serde_json::from_value(json!({
+                "groupVersion": "clux.dev/v1",
+                "resources": [
+                    {
+                        "name": "foos/status",
+                        "kind": "Foo",
+                        "namespaced": true,
+                        "singularName": "",
+                        "verbs": ["get"],
+                    },
+                    {
+                        "name": "foos",
+                        "kind": "Foo",
+                        "namespaced": true,
+                        "singularName": "foo",
+                        "verbs": ["get"],
+                    },
+                ],
+            }))
+            .unwrap();
+            assert_eq!(served_plural(&list, "Foo"), Some("foos"));
+            assert_eq!(served_plural(&list, "Bar"), None);
+        }
+    }
+}
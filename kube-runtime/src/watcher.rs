@@ -1,9 +1,9 @@
 //! Watches a Kubernetes Resource for changes, with error recovery
 
 use derivative::Derivative;
-use futures::{stream::BoxStream, Stream, StreamExt};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Stream, StreamExt};
 use kube_client::{
-    api::{ListParams, Resource, ResourceExt, WatchEvent},
+    api::{ListParams, ObjectList, PartialObjectMetadata, Resource, ResourceExt, WatchEvent},
     Api,
 };
 use serde::de::DeserializeOwned;
@@ -71,8 +71,19 @@ impl<K> Event<K> {
         }
         .into_iter()
     }
+
+    /// Maps all objects mentioned by the event, keeping the kind of event.
+    pub fn map<T>(self, mut f: impl FnMut(K) -> T) -> Event<T> {
+        match self {
+            Event::Applied(obj) => Event::Applied(f(obj)),
+            Event::Deleted(obj) => Event::Deleted(f(obj)),
+            Event::Restarted(objs) => Event::Restarted(objs.into_iter().map(f).collect()),
+        }
+    }
 }
 
+type WatchStream<K> = BoxStream<'static, kube_client::Result<WatchEvent<K>>>;
+
 #[derive(Derivative)]
 #[derivative(Debug)]
 /// The internal finite state machine driving the [`watcher`]
@@ -90,19 +101,80 @@ enum State<K: Resource + Clone> {
     Watching {
         resource_version: String,
         #[derivative(Debug = "ignore")]
-        stream: BoxStream<'static, kube_client::Result<WatchEvent<K>>>,
+        stream: WatchStream<K>,
     },
 }
 
+/// How the [`watcher`] lists and watches, which decides the type of the objects it emits
+trait ApiMode {
+    type Value: Resource + Clone + Send + 'static;
+
+    fn list<'a>(
+        &'a self,
+        lp: &'a ListParams,
+    ) -> BoxFuture<'a, kube_client::Result<ObjectList<Self::Value>>>;
+
+    fn watch<'a>(
+        &'a self,
+        lp: &'a ListParams,
+        version: &'a str,
+    ) -> BoxFuture<'a, kube_client::Result<WatchStream<Self::Value>>>;
+}
+
+/// Lists and watches the full objects
+struct FullObject<K> {
+    api: Api<K>,
+}
+
+impl<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static> ApiMode for FullObject<K> {
+    type Value = K;
+
+    fn list<'a>(&'a self, lp: &'a ListParams) -> BoxFuture<'a, kube_client::Result<ObjectList<K>>> {
+        self.api.list(lp).boxed()
+    }
+
+    fn watch<'a>(
+        &'a self,
+        lp: &'a ListParams,
+        version: &'a str,
+    ) -> BoxFuture<'a, kube_client::Result<WatchStream<K>>> {
+        async move { self.api.watch(lp, version).await.map(StreamExt::boxed) }.boxed()
+    }
+}
+
+/// Lists and watches only the metadata of the objects
+struct MetaOnly<K> {
+    api: Api<K>,
+}
+
+impl<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static> ApiMode for MetaOnly<K> {
+    type Value = PartialObjectMetadata;
+
+    fn list<'a>(
+        &'a self,
+        lp: &'a ListParams,
+    ) -> BoxFuture<'a, kube_client::Result<ObjectList<PartialObjectMetadata>>> {
+        self.api.list_metadata(lp).boxed()
+    }
+
+    fn watch<'a>(
+        &'a self,
+        lp: &'a ListParams,
+        version: &'a str,
+    ) -> BoxFuture<'a, kube_client::Result<WatchStream<PartialObjectMetadata>>> {
+        async move { self.api.watch_metadata(lp, version).await.map(StreamExt::boxed) }.boxed()
+    }
+}
+
 /// Progresses the watcher a single step, returning (event, state)
 ///
 /// This function should be trampolined: if event == `None`
 /// then the function should be called again until it returns a Some.
-async fn step_trampolined<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static>(
-    api: &Api<K>,
+async fn step_trampolined<A: ApiMode>(
+    api: &A,
     list_params: &ListParams,
-    state: State<K>,
-) -> (Option<Result<Event<K>>>, State<K>) {
+    state: State<A::Value>,
+) -> (Option<Result<Event<A::Value>>>, State<A::Value>) {
     match state {
         State::Empty => match api.list(list_params).await {
             Ok(list) => (Some(Ok(Event::Restarted(list.items))), State::InitListed {
@@ -113,8 +185,12 @@ async fn step_trampolined<K: Resource + Clone + DeserializeOwned + Debug + Send
         State::InitListed { resource_version } => match api.watch(list_params, &resource_version).await {
             Ok(stream) => (None, State::Watching {
                 resource_version,
-                stream: stream.boxed(),
+                stream,
             }),
+            // The resourceVersion of the initial list is already too old, so start over and re-list
+            Err(kube_client::Error::WatchExpired { source, .. }) => {
+                (Some(Err(Error::WatchError(source))), State::Empty)
+            }
             Err(err) => (
                 Some(Err(err).map_err(Error::WatchStartFailed)),
                 State::InitListed { resource_version },
@@ -154,6 +230,10 @@ async fn step_trampolined<K: Resource + Clone + DeserializeOwned + Debug + Send
                 };
                 (Some(Err(err).map_err(Error::WatchError)), new_state)
             }
+            Some(Err(kube_client::Error::WatchExpired { source, .. })) => {
+                // HTTP GONE, means we have desynced and need to start over and re-list :(
+                (Some(Err(Error::WatchError(source))), State::Empty)
+            }
             Some(Err(err)) => (Some(Err(err).map_err(Error::WatchFailed)), State::Watching {
                 resource_version,
                 stream,
@@ -164,11 +244,11 @@ async fn step_trampolined<K: Resource + Clone + DeserializeOwned + Debug + Send
 }
 
 /// Trampoline helper for `step_trampolined`
-async fn step<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static>(
-    api: &Api<K>,
+async fn step<A: ApiMode>(
+    api: &A,
     list_params: &ListParams,
-    mut state: State<K>,
-) -> (Result<Event<K>>, State<K>) {
+    mut state: State<A::Value>,
+) -> (Result<Event<A::Value>>, State<A::Value>) {
     loop {
         match step_trampolined(api, list_params, state).await {
             (Some(result), new_state) => return (result, new_state),
@@ -229,6 +309,53 @@ pub fn watcher<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static>(
     api: Api<K>,
     list_params: ListParams,
 ) -> impl Stream<Item = Result<Event<K>>> + Send {
+    watch_with(FullObject { api }, list_params)
+}
+
+/// Watches the metadata of a Kubernetes Resource for changes continuously
+///
+/// Like [`watcher`], but the objects only carry their metadata, as [`PartialObjectMetadata`].
+/// This is much cheaper for large objects when only names, labels, annotations, owners or finalizers
+/// matter, such as when mapping related objects to a controller. Recovers from errors like [`watcher`].
+///
+/// The kind of the objects is not part of their metadata, so a [`reflector`] over this stream is created
+/// with the [`ApiResource`](kube_client::api::ApiResource) of `K`:
+///
+/// ```no_run
+/// use kube::{
+///   api::{Api, ApiResource, ListParams, PartialObjectMetadata}, Client,
+///   runtime::{reflector::{self, store}, watcher::metadata_watcher}
+/// };
+/// use k8s_openapi::api::core::v1::Secret;
+/// use futures::TryStreamExt;
+/// #[tokio::main]
+/// async fn main() -> Result<(), kube::runtime::watcher::Error> {
+///     let client = Client::try_default().await.unwrap();
+///     let secrets: Api<Secret> = Api::namespaced(client, "apps");
+///     let writer = store::Writer::<PartialObjectMetadata>::new(ApiResource::erase::<Secret>(&()));
+///     let store = writer.as_reader();
+///     reflector::reflector(writer, metadata_watcher(secrets, ListParams::default()))
+///         .try_for_each(|_| async move {
+///             println!("{} secrets", store.state().len());
+///             Ok(())
+///         })
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+/// [`reflector`]: super::reflector::reflector
+pub fn metadata_watcher<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static>(
+    api: Api<K>,
+    list_params: ListParams,
+) -> impl Stream<Item = Result<Event<PartialObjectMetadata>>> + Send {
+    watch_with(MetaOnly { api }, list_params)
+}
+
+/// Drives the state machine of the [`watcher`] in the given mode
+fn watch_with<A: ApiMode + Send + Sync + 'static>(
+    api: A,
+    list_params: ListParams,
+) -> impl Stream<Item = Result<Event<A::Value>>> + Send {
     futures::stream::unfold(
         (api, list_params, State::Empty),
         |(api, list_params, state)| async {
@@ -238,6 +365,42 @@ pub fn watcher<K: Resource + Clone + DeserializeOwned + Debug + Send + 'static>(
     )
 }
 
+/// Watches a Kubernetes Resource for changes, projecting each object with `projector`
+///
+/// The projections are what the stream emits, so a [`reflector`] caches the (usually much smaller)
+/// projections rather than the full objects. Use [`Projection`] to cache projections that don't
+/// implement [`Resource`] themselves.
+///
+/// ```no_run
+/// use k8s_openapi::api::core::v1::Pod;
+/// use kube::{api::{Api, ListParams}, Client};
+/// use kube_runtime::{reflector::{self, store, Projection}, watcher::watcher_map};
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let writer = store::Writer::<Projection<Pod, Option<String>>>::default();
+/// let phases = writer.as_reader();
+/// let stream = watcher_map(Api::<Pod>::all(client), ListParams::default(), |pod| {
+///     Projection::new(&pod, pod.status.as_ref().and_then(|status| status.phase.clone()))
+/// });
+/// let stream = reflector::reflector(writer, stream);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`reflector`]: super::reflector::reflector
+/// [`Projection`]: super::reflector::Projection
+pub fn watcher_map<K, S>(
+    api: Api<K>,
+    list_params: ListParams,
+    mut projector: impl FnMut(K) -> S + Send + 'static,
+) -> impl Stream<Item = Result<Event<S>>> + Send
+where
+    K: Resource + Clone + DeserializeOwned + Debug + Send + 'static,
+    S: Send,
+{
+    watcher(api, list_params).map(move |event| event.map(|event| event.map(&mut projector)))
+}
+
 /// Watch a single named object for updates
 ///
 /// Emits `None` if the object is deleted (or not found), and `Some` if an object is updated (or created/found).
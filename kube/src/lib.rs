@@ -141,6 +141,7 @@ cfg_client! {
     pub use kube_client::api;
     pub use kube_client::discovery;
     pub use kube_client::client;
+    pub use kube_client::search;
 
     #[doc(inline)]
     pub use api::Api;
@@ -168,6 +169,10 @@ cfg_error! {
 #[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
 pub use kube_derive::CustomResource;
 
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub use kube_client::testing;
+
 /// Re-exports from [`kube-runtime`](kube_runtime)
 #[cfg(feature = "runtime")]
 #[cfg_attr(docsrs, doc(cfg(feature = "runtime")))]